@@ -1,21 +1,45 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufReader, Read, Seek};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, Read, Seek};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
+use arrow::array::StringDictionaryBuilder;
 use arrow::array::{
-    ArrayRef, Float32Builder, GenericStringBuilder, Int16Builder, Int32Array, Int32Builder,
-    Int8Builder, StringArray, StringDictionaryBuilder, StructArray, UInt16Array, UInt16Builder,
-    UInt32Builder, UInt8Array, UInt8Builder,
+    ArrayRef, BooleanBuilder, Float32Builder, GenericStringBuilder, Int16Builder, Int32Array,
+    Int32Builder, Int64Builder, Int8Builder, ListBuilder, NullArray, StringArray, StructArray,
+    UInt16Array, UInt16Builder, UInt32Builder, UInt8Array, UInt8Builder,
 };
-use arrow::{datatypes::Int32Type, error::ArrowError, record_batch::RecordBatch};
+use arrow::datatypes::{Field, Fields, Int32Type, Schema};
+use arrow::{error::ArrowError, record_batch::RecordBatch};
+use noodles::core::region::Interval;
 use noodles::core::Region;
-use noodles::sam::record::data::field::Tag;
+use noodles::sam::header::record::value::map::header::SortOrder;
+use noodles::sam::record::cigar::op::Kind;
+use noodles::sam::record::data::field::{Tag, Value};
 use noodles::sam::record::Data;
 use noodles::{bam, bgzf, csi, sam};
 
-use crate::batch_builder::{write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES};
+use crate::batch_builder::{
+    builder_fields, check_fields, check_no_offsets, count_err, finish_batch, map_records,
+    provenance, select_fields, write_ipc, write_ipc_err, BatchBuilder, ReferenceColumnBuilder,
+    WithOffsets, BUFFER_SIZE_BYTES,
+};
+use crate::coordinates::{needs_wide_positions, CoordinateSystem, PositionBuilder};
+use crate::coverage::{CoverageBatchBuilder, CoverageWindow, WindowAssignment, Windows};
+use crate::methylation::MethylationBuilder;
+use crate::pairs::{PairBatchBuilder, Pairer, DEFAULT_MAX_BUFFER};
+use crate::parallel::query_parallel;
+use crate::pileup::{Pileup, PileupBatchBuilder, PileupRow};
+use crate::progress::{
+    finish_progress, finish_query, is_cancelled, track, track_query, until_cancelled, Progress,
+    ProgressTracker,
+};
+use crate::region::{parse_aliased_region, tile_regions, ChromAlias, QueryMode};
+use crate::sampling::sample_records;
+use crate::sort_order::SortChecker;
 
 pub fn index_from_reader<R>(mut read: R) -> io::Result<csi::Index>
 where
@@ -48,25 +72,385 @@ pub fn index_from_path(path: &str) -> io::Result<csi::Index> {
     Ok(index)
 }
 
+/// Returns the schema of the batches returned by [`BamReader::records_to_ipc`] with `options`,
+/// reading only the header of the BAM file at `path` and its first `sample_size` records, not
+/// its index.
+///
+/// Tag columns are typed from those records, and given as Null, like in [`BamReader::fields`],
+/// if none of them has the tag. All fields are nullable, and the schema has no metadata.
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::bam::{peek_schema, BamOptions};
+///
+/// let options = BamOptions {
+///     tags: Some(vec!["NM".to_string()]),
+///     ..Default::default()
+/// };
+/// let schema = peek_schema("sample.bam", &options, 100).unwrap();
+/// ```
+pub fn peek_schema(
+    path: &str,
+    options: &BamOptions,
+    sample_size: usize,
+) -> Result<Schema, ArrowError> {
+    let mut reader = File::open(path)
+        .map(|file| bam::Reader::new(BufReader::new(file)))
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    let header = reader
+        .read_header()
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    let mut records = Vec::new();
+    let mut record = sam::alignment::Record::default();
+    while records.len() < sample_size {
+        match reader.read_record(&header, &mut record) {
+            Ok(0) => break,
+            Ok(_) => records.push((0, record.clone())),
+            Err(e) => return Err(ArrowError::ExternalError(e.into())),
+        }
+    }
+    let mut batch_builder = BamBatchBuilder::new(records.len(), &header, options)?;
+    let fields = if options.emit_offset.unwrap_or(false) {
+        let mut batch_builder = WithOffsets::new(batch_builder);
+        records.iter().for_each(|record| batch_builder.push(record));
+        builder_fields(batch_builder)?
+    } else {
+        records
+            .iter()
+            .for_each(|(_, record)| batch_builder.push(record));
+        builder_fields(batch_builder)?
+    };
+    Ok(Schema::new(fields))
+}
+
+/// Options for converting alignment records to Arrow.
+#[derive(Clone, Debug, Default)]
+pub struct BamOptions {
+    /// Auxiliary tags to materialize as dedicated columns, e.g. `["NM", "AS", "RG"]`.
+    ///
+    /// Each tag becomes a column named after it, appended after `end` and the length columns, in
+    /// the given order, and replaces the `tags` struct column. The type of each column follows the
+    /// first value of its tag: integers (`c`, `C`, `s`, `S`, `i`) are emitted as Int32, except
+    /// `I`, whose values may not fit and are emitted as Int64. `f` is Float32, `A`, `Z` and `H` are
+    /// Utf8, and `B` arrays are lists of the same types. Records without the tag, or with a value
+    /// that doesn't fit the column, get a null. A tag that is never seen yields a column of nulls.
+    pub tags: Option<Vec<String>>,
+    /// Add a `reference_length` column (Int32) with the number of reference bases spanned by the
+    /// alignment, i.e. the length of its `M`, `D`, `N`, `=` and `X` CIGAR operations. The
+    /// alignment end itself is always in the `end` column.
+    pub include_reference_length: Option<bool>,
+    /// Add a `query_length` column (Int32) with the number of read bases consumed by the CIGAR,
+    /// i.e. the length of its `M`, `I`, `S`, `=` and `X` operations. Hard clips are not counted.
+    pub include_query_length: Option<bool>,
+    /// Add `left_soft_clip`, `right_soft_clip`, `left_hard_clip` and `right_hard_clip` columns
+    /// (Int32) after the length columns with the length of the clips at each end of the CIGAR, or
+    /// 0 for an end without one. Records without a CIGAR get nulls.
+    pub include_clip_lengths: Option<bool>,
+    /// Skip records with a mapping quality below this value, like `samtools view -q`. A missing
+    /// mapping quality counts as 255.
+    pub min_mapq: Option<u8>,
+    /// Skip records without all of these flag bits set, like `samtools view -f`.
+    pub include_flags: Option<u16>,
+    /// Skip records with any of these flag bits set, like `samtools view -F`.
+    pub exclude_flags: Option<u16>,
+    /// The columns to return, in this order, e.g. `["rname", "pos", "qname"]`. Tag columns
+    /// selected with `tags` can be named too. Defaults to all of them, in their default order.
+    /// Leaving out `seq` and `qual` skips formatting them, and whole-file scans then skip decoding
+    /// them too, which speeds up large scans. The records seen by [`BamReader::scan_with`] and
+    /// [`BamReader::scan_map`] have an empty sequence and quality scores in that case.
+    pub fields: Option<Vec<String>>,
+    /// Emit `rname` and `rnext` as the index (Int32) of the reference sequence in the header,
+    /// instead of its name. Records without a reference sequence get a null. The names can be
+    /// looked up with [`BamReader::reference_names`].
+    pub emit_ref_as_index: Option<bool>,
+    /// The coordinate system of `pos` and `pnext`. Defaults to the native 1-based, closed
+    /// coordinates. See [`crate::coordinates`].
+    pub coordinate_system: Option<CoordinateSystem>,
+    /// Emit `pos`, `pnext` and `end` as UInt32 instead of Int32. Either is widened to 64 bits when
+    /// a reference sequence of the header is longer than 2^31 - 1 bases.
+    pub unsigned_positions: Option<bool>,
+    /// Add a `sample` column (dictionary-encoded Utf8) after the length columns with the `SM`
+    /// field of the read group named by each record's `RG` tag. Records without an `RG` tag, or
+    /// whose read group is missing from the header or has no sample, get a null.
+    pub include_sample: Option<bool>,
+    /// Add a Boolean column for each bit of the `flag` column, after the `sample` column:
+    /// `is_paired`, `is_proper_pair`, `is_unmapped`, `mate_unmapped`, `is_reverse`,
+    /// `mate_reverse`, `is_read1`, `is_read2`, `is_secondary`, `is_qc_fail`, `is_duplicate` and
+    /// `is_supplementary`. The `flag` column is kept.
+    pub decode_flags: Option<bool>,
+    /// Add a `vpos` column (UInt64), after all others, with the BGZF virtual offset at which each
+    /// record starts, to read it again with [`BamReader::records_to_ipc_from_vpos`]. Offsets are
+    /// only emitted by scans of the whole file or of a virtual offset range, not region queries.
+    pub emit_offset: Option<bool>,
+    /// Renames the reference sequences of query regions to the naming of the file, e.g. `chr1`
+    /// to `1`. Records keep the names of the file. See [`ChromAlias`].
+    pub chrom_alias: Option<ChromAlias>,
+    /// Fail on the first record out of coordinate order, i.e. whose reference sequence comes
+    /// before that of the previous record in the header, or whose alignment start is smaller on
+    /// the same reference sequence. Unplaced unmapped reads must come last. A header declaring
+    /// another sort order than `SO:coordinate`, other than `unknown`, fails up front. Only scans
+    /// and counts of the whole file are checked, not region queries or scans emitting offsets.
+    pub require_sorted: Option<bool>,
+    /// Keep each record passing the filters with this probability, from 0 to 1, for a random
+    /// subsample. Records are sampled before applying the limit by the methods returning records,
+    /// e.g. `records_to_ipc`, `regions_to_ipc` and `scan_from`, but not by counts, pileups,
+    /// coverage or pairs.
+    pub sample_fraction: Option<f64>,
+    /// Keep this many of the records passing the filters, chosen uniformly, in file order. This
+    /// reads all the records before returning any, and can't be combined with
+    /// [`BamOptions::sample_fraction`].
+    pub sample_count: Option<usize>,
+    /// The seed of the random subsample, so that the same records are kept on every scan.
+    /// Defaults to 0.
+    pub sample_seed: Option<u64>,
+    /// The path of an indexed FASTA file of the reference, to add `meth_cpg` and `unmeth_cpg`
+    /// columns (Int32) after the flag columns with the numbers of methylated and unmethylated CpG
+    /// sites of each bisulfite-converted read. See [`crate::methylation`]. Unmapped reads, and
+    /// reads on reference sequences missing from the FASTA file, get nulls.
+    pub methylation_reference: Option<String>,
+}
+
+/// Parses a query region, renaming its reference sequence to the naming of `header` with
+/// [`BamOptions::chrom_alias`].
+pub(crate) fn parse_query_region(
+    region: &str,
+    header: &sam::Header,
+    options: &BamOptions,
+) -> Result<Region, ArrowError> {
+    let reference_sequences = header.reference_sequences();
+    parse_aliased_region(region, options.chrom_alias.as_ref(), |name| {
+        reference_sequences.contains_key(name)
+    })
+}
+
+/// Returns the fields of a header record, e.g. `{"ID": "rg1", "SM": "sample1"}` for
+/// `@RG\tID:rg1\tSM:sample1`.
+///
+/// `map` is displayed as the tab-separated fields that follow the ID in a header line.
+pub(crate) fn header_record_fields(
+    id: &str,
+    map: &impl std::fmt::Display,
+) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::from([("ID".to_string(), id.to_string())]);
+    for field in map.to_string().split('\t') {
+        if let Some((tag, value)) = field.split_once(':') {
+            fields.insert(tag.to_string(), value.to_string());
+        }
+    }
+    fields
+}
+
+impl BamOptions {
+    /// Returns whether a record passes the `min_mapq`, `include_flags` and `exclude_flags`
+    /// filters.
+    pub fn keep(&self, record: &sam::alignment::Record) -> bool {
+        let flags = u16::from(record.flags());
+        let mapq = record.mapping_quality().map_or(255, u8::from);
+        self.include_flags.map_or(true, |f| flags & f == f)
+            && self.exclude_flags.map_or(true, |f| flags & f == 0)
+            && self.min_mapq.map_or(true, |q| mapq >= q)
+    }
+
+    /// Returns whether the records' sequence and quality scores are used, i.e. whether `fields`
+    /// selects `seq` or `qual`, or `methylation_reference` is set.
+    pub(crate) fn decodes_sequence(&self) -> bool {
+        self.methylation_reference.is_some()
+            || self.fields.as_ref().map_or(true, |fields| {
+                fields.iter().any(|field| field == "seq" || field == "qual")
+            })
+    }
+
+    /// Subsamples records with [`BamOptions::sample_fraction`] or [`BamOptions::sample_count`].
+    pub(crate) fn subsample<'a, T: 'a>(
+        &self,
+        records: impl Iterator<Item = Result<T, ArrowError>> + 'a,
+    ) -> Result<Box<dyn Iterator<Item = Result<T, ArrowError>> + 'a>, ArrowError> {
+        sample_records(
+            records,
+            self.sample_fraction,
+            self.sample_count,
+            self.sample_seed,
+        )
+    }
+
+    /// Returns whether [`BamOptions::sample_fraction`] or [`BamOptions::sample_count`] is set.
+    pub(crate) fn samples(&self) -> bool {
+        self.sample_fraction.is_some() || self.sample_count.is_some()
+    }
+}
+
+/// Reads the next record, or `None` at the end of the file. Without `sequence`, the record is
+/// read lazily and decoded without its sequence and quality scores, which are left empty.
+fn read_record<R: Read>(
+    reader: &mut bam::Reader<R>,
+    header: &sam::Header,
+    sequence: bool,
+) -> io::Result<Option<sam::alignment::Record>> {
+    if sequence {
+        let mut record = sam::alignment::Record::default();
+        return Ok((reader.read_record(header, &mut record)? > 0).then_some(record));
+    }
+    let mut lazy_record = bam::lazy::Record::default();
+    if reader.read_lazy_record(&mut lazy_record)? == 0 {
+        return Ok(None);
+    }
+    let mut builder = sam::alignment::Record::builder()
+        .set_flags(lazy_record.flags())
+        .set_cigar(lazy_record.cigar().try_into()?)
+        .set_template_length(lazy_record.template_length())
+        .set_data(lazy_record.data().try_into()?);
+    if let Some(read_name) = lazy_record.read_name() {
+        builder = builder.set_read_name(read_name.try_into()?);
+    }
+    if let Some(id) = lazy_record.reference_sequence_id()? {
+        builder = builder.set_reference_sequence_id(id);
+    }
+    if let Some(position) = lazy_record.alignment_start()? {
+        builder = builder.set_alignment_start(position);
+    }
+    if let Some(mapping_quality) = lazy_record.mapping_quality() {
+        builder = builder.set_mapping_quality(mapping_quality);
+    }
+    if let Some(id) = lazy_record.mate_reference_sequence_id()? {
+        builder = builder.set_mate_reference_sequence_id(id);
+    }
+    if let Some(position) = lazy_record.mate_alignment_start()? {
+        builder = builder.set_mate_alignment_start(position);
+    }
+    Ok(Some(builder.build()))
+}
+
+/// Drops the records rejected by the filters in `options`. Errors are passed through.
+pub(crate) fn filter_records<'a>(
+    records: impl Iterator<Item = Result<sam::alignment::Record, ArrowError>> + 'a,
+    options: &'a BamOptions,
+) -> impl Iterator<Item = Result<sam::alignment::Record, ArrowError>> + 'a {
+    records.filter(|i| i.as_ref().map_or(true, |record| options.keep(record)))
+}
+
+/// Fails on the first record out of coordinate order if [`BamOptions::require_sorted`] is set.
+/// Errors are passed through.
+pub(crate) fn check_sorted<'a>(
+    records: impl Iterator<Item = Result<sam::alignment::Record, ArrowError>> + 'a,
+    header: &'a sam::Header,
+    options: &BamOptions,
+) -> Result<impl Iterator<Item = Result<sam::alignment::Record, ArrowError>> + 'a, ArrowError> {
+    let reference_sequences = header.reference_sequences();
+    let mut checker = None;
+    if options.require_sorted.unwrap_or(false) {
+        let sort_order = header.header().and_then(|header| header.sort_order());
+        if let Some(sort_order) = sort_order
+            .filter(|sort_order| !matches!(sort_order, SortOrder::Coordinate | SortOrder::Unknown))
+        {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "Unsorted input: the header declares SO:{}, not SO:coordinate.",
+                sort_order
+            )));
+        }
+        let names = reference_sequences.keys().map(|name| name.as_str());
+        checker = Some(SortChecker::with_order(names.chain(["*"])));
+    }
+    Ok(records.map(move |i| {
+        let record = i?;
+        if let Some(checker) = &mut checker {
+            let chrom = record
+                .reference_sequence_id()
+                .and_then(|id| reference_sequences.get_index(id))
+                .map_or("*", |(name, _)| name.as_str());
+            let position = record.alignment_start().map_or(0, usize::from);
+            checker
+                .check(chrom, position, None)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        }
+        Ok(record)
+    }))
+}
+
+/// Returns whether a record overlapping `interval` matches it under `mode`.
+///
+/// Records without an alignment end, such as unmapped reads placed with their mate, span their
+/// alignment start only.
+pub(crate) fn in_interval(
+    record: &sam::alignment::Record,
+    interval: Interval,
+    mode: QueryMode,
+) -> bool {
+    match record.alignment_start() {
+        Some(start) => mode.matches(interval, start, record.alignment_end().unwrap_or(start)),
+        None => true,
+    }
+}
+
+/// Drops the records of a region query that do not match the region under `mode`. Errors are
+/// passed through.
+pub(crate) fn filter_interval(
+    records: impl Iterator<Item = Result<sam::alignment::Record, ArrowError>>,
+    interval: Interval,
+    mode: QueryMode,
+) -> impl Iterator<Item = Result<sam::alignment::Record, ArrowError>> {
+    records.filter(move |i| {
+        i.as_ref()
+            .map_or(true, |record| in_interval(record, interval, mode))
+    })
+}
+
 /// A BAM reader.
 pub struct BamReader<R> {
     reader: bam::Reader<bgzf::Reader<R>>,
     header: sam::Header,
+    /// The virtual position of the first record, after the header.
+    records_start: bgzf::VirtualPosition,
     index: csi::Index,
+    options: BamOptions,
+    query_mode: QueryMode,
+    limit: Option<usize>,
+    threads: usize,
+    path: Option<PathBuf>,
+    progress: Option<ProgressTracker>,
+    cancel: Option<Arc<AtomicBool>>,
 }
 
 impl BamReader<BufReader<File>> {
     /// Creates a BAM reader from a given file path.
+    ///
+    /// The index is loaded from `<path>.bai` or `<path>.csi`.
     pub fn new_from_path(path: &str) -> std::io::Result<Self> {
         let index = index_from_path(path)?;
+        Self::new_from_path_with_index(path, index)
+    }
+
+    /// Creates a BAM reader from a given file path and a prebuilt index.
+    ///
+    /// This avoids parsing the index again when opening the same file several times.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let index = reader.index().clone();
+    /// let mut reader = BamReader::new_from_path_with_index("sample.bam", index).unwrap();
+    /// ```
+    pub fn new_from_path_with_index(path: &str, index: csi::Index) -> std::io::Result<Self> {
         let file = std::fs::File::open(path)?;
         let buf_file = std::io::BufReader::with_capacity(BUFFER_SIZE_BYTES, file);
         let mut reader = bam::Reader::new(buf_file);
         let header = reader.read_header()?;
+        let records_start = reader.virtual_position();
         Ok(Self {
             reader,
             header,
+            records_start,
             index,
+            options: BamOptions::default(),
+            query_mode: QueryMode::default(),
+            limit: None,
+            threads: 1,
+            path: Some(PathBuf::from(path)),
+            progress: None,
+            cancel: None,
         })
     }
 }
@@ -75,20 +459,305 @@ impl<R> BamReader<R>
 where
     R: Read + Seek,
 {
-    /// Creates a BAM reader.
+    /// Creates a BAM reader from a stream and a prebuilt index.
     pub fn new(read: R, index: csi::Index) -> std::io::Result<Self> {
         let mut reader = bam::Reader::new(read);
         let header = reader.read_header()?;
+        let records_start = reader.virtual_position();
         Ok(Self {
             reader,
             header,
+            records_start,
             index,
+            options: BamOptions::default(),
+            query_mode: QueryMode::default(),
+            limit: None,
+            threads: 1,
+            path: None,
+            progress: None,
+            cancel: None,
         })
     }
 
+    /// Returns the index (BAI or CSI) used for queries.
+    pub fn index(&self) -> &csi::Index {
+        &self.index
+    }
+
+    /// Parses a query region, renamed with [`BamOptions::chrom_alias`].
+    fn query_region(&self, region: &str) -> Result<Region, ArrowError> {
+        parse_query_region(region, &self.header, &self.options)
+    }
+
+    /// Returns the SAM header, e.g. to reconstruct a writer.
+    pub fn header(&self) -> &sam::Header {
+        &self.header
+    }
+
+    /// Returns the text of the SAM header, one `@` line per record. Its lines are those of the
+    /// file, but grouped by kind, e.g. all `@SQ` lines before the `@RG` lines.
+    pub fn header_text(&self) -> String {
+        self.header.to_string()
+    }
+
+    /// Returns the names of the reference sequences in header order.
+    ///
+    /// These map the indices emitted with [`BamOptions::emit_ref_as_index`] back to names.
+    pub fn reference_names(&self) -> Vec<String> {
+        self.header
+            .reference_sequences()
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Returns the read groups (`@RG`) of the header, in header order, as maps from field tags
+    /// such as `ID`, `SM`, `PL` and `LB` to values.
+    pub fn read_groups(&self) -> Vec<BTreeMap<String, String>> {
+        self.header
+            .read_groups()
+            .iter()
+            .map(|(id, read_group)| header_record_fields(id, read_group))
+            .collect()
+    }
+
+    /// Returns the programs (`@PG`) of the header, in header order, as maps from field tags such
+    /// as `ID`, `PN`, `VN`, `CL` and `PP` to values.
+    pub fn programs(&self) -> Vec<BTreeMap<String, String>> {
+        self.header
+            .programs()
+            .iter()
+            .map(|(id, program)| header_record_fields(id, program))
+            .collect()
+    }
+
+    /// Returns the names and lengths of the reference sequences (`@SQ`) in header order.
+    pub fn reference_lengths(&self) -> Vec<(String, u64)> {
+        self.header
+            .reference_sequences()
+            .iter()
+            .map(|(name, reference_sequence)| {
+                (name.to_string(), reference_sequence.length().get() as u64)
+            })
+            .collect()
+    }
+
+    /// Sets the options used when converting records.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::{BamOptions, BamReader};
+    ///
+    /// let options = BamOptions {
+    ///     tags: Some(vec!["NM".to_string(), "MD".to_string()]),
+    ///     min_mapq: Some(30),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = BamReader::new_from_path("sample.bam")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_options(mut self, options: BamOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets how records are matched against query regions.
+    ///
+    /// By default, a region query returns every record overlapping the region. With
+    /// [`QueryMode::Contained`], records straddling either bound of the region are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    /// use oxbow::region::QueryMode;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam")
+    ///     .unwrap()
+    ///     .with_query_mode(QueryMode::Contained);
+    /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn with_query_mode(mut self, query_mode: QueryMode) -> Self {
+        self.query_mode = query_mode;
+        self
+    }
+
+    /// Caps the number of records returned, across all regions of a query combined.
+    ///
+    /// The limit applies after the filters in [`BamOptions`], and reading stops as soon as it is
+    /// reached. Counts are not limited.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap().with_limit(100);
+    /// let ipc = reader.regions_to_ipc(&["sq0", "sq1"]).unwrap();
+    /// ```
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Decompresses the BGZF blocks of scans and counts of the whole file on this many threads,
+    /// which is faster for large files. Records are returned in the same order. Region queries,
+    /// and scans emitting offsets, are decompressed on the calling thread. Defaults to 1.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap().with_threads(4);
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Reports the progress of scans, region queries and counts to `progress`, every `interval`
+    /// records and at the end of each scan. Bytes are those of the compressed file, whose size is
+    /// the total when the reader was created from a path. Region queries, which jump around the
+    /// file, report 0 bytes and no total, and the threads of a parallel query are counted
+    /// together. Pileups, coverage and pairs are not reported.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam")
+    ///     .unwrap()
+    ///     .with_progress(100_000, |records, bytes, total: Option<u64>| {
+    ///         eprintln!("{} records, {} of {:?} bytes", records, bytes, total);
+    ///     });
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_progress(mut self, interval: usize, progress: impl Progress + 'static) -> Self {
+        let tracker = ProgressTracker::new(Box::new(progress), interval, self.path.as_deref());
+        self.progress = Some(tracker);
+        self
+    }
+
+    /// Stops scans, region queries and counts at the next record once `cancel` is set, e.g. from
+    /// another thread. The records read until then are returned, and after a scan of the whole
+    /// file the reader is left after the last of them. Each thread of a parallel query stops at
+    /// its next record, and the records they read are returned. Pileups, coverage and pairs are
+    /// not cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let mut reader = BamReader::new_from_path("sample.bam")
+    ///     .unwrap()
+    ///     .with_cancel(cancel.clone());
+    /// let timer = std::thread::spawn(move || {
+    ///     std::thread::sleep(std::time::Duration::from_secs(10));
+    ///     cancel.store(true, Ordering::Relaxed);
+    /// });
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Reads the records from the current position to the end of the file with `scan`, given the
+    /// header and options, on [`BamReader::with_threads`] threads. The reader is left where `scan`
+    /// stopped.
+    ///
+    /// Without `sequence`, the records are decoded without their sequence and quality scores.
+    fn scan_records<T>(
+        &mut self,
+        sequence: bool,
+        scan: impl FnOnce(
+            &mut dyn Iterator<Item = Result<sam::alignment::Record, ArrowError>>,
+            &sam::Header,
+            &BamOptions,
+        ) -> Result<T, ArrowError>,
+    ) -> Result<T, ArrowError> {
+        let Some(worker_count) = NonZeroUsize::new(self.threads).filter(|n| n.get() > 1) else {
+            let (reader, progress) = (&mut self.reader, &mut self.progress);
+
+            let mut records = std::iter::from_fn(|| {
+                if is_cancelled(&self.cancel) {
+                    return None;
+                }
+                match read_record(reader, &self.header, sequence) {
+                    Ok(None) => None,
+                    Ok(Some(record)) => {
+                        track(progress, reader.virtual_position());
+                        Some(Ok(record))
+                    }
+                    Err(e) => Some(Err(ArrowError::ExternalError(e.into()))),
+                }
+            });
+            let result = scan(&mut records, &self.header, &self.options);
+            finish_progress(&mut self.progress, self.reader.virtual_position());
+            return result;
+        };
+        let vpos = self.reader.virtual_position();
+        let mut inner = bgzf::reader::Builder::default()
+            .set_worker_count(worker_count)
+            .build_from_reader(self.reader.get_mut().get_mut());
+        inner
+            .seek(vpos)
+            .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        let mut reader = bam::Reader::from(inner);
+        // The reader is moved to the end of the last record read, rather than to where the
+        // workers stopped, since seeking to the end of the file would keep the current block.
+        let mut end = vpos;
+        let mut records = std::iter::from_fn(|| {
+            if is_cancelled(&self.cancel) {
+                return None;
+            }
+            match read_record(&mut reader, &self.header, sequence) {
+                Ok(None) => None,
+                Ok(Some(record)) => {
+                    end = reader.virtual_position();
+                    track(&mut self.progress, end);
+                    Some(Ok(record))
+                }
+                Err(e) => Some(Err(ArrowError::ExternalError(e.into()))),
+            }
+        });
+        let result = scan(&mut records, &self.header, &self.options);
+        drop(reader);
+        finish_progress(&mut self.progress, end);
+        self.reader
+            .seek(end)
+            .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        result
+    }
+
+    /// Returns the fields of the batches returned by [`BamReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    ///
+    /// Tag columns, which are typed from the records, are given as Null, and the `tags` struct
+    /// without fields.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        let batch_builder = BamBatchBuilder::new(0, &self.header, &self.options)?;
+        if self.options.emit_offset.unwrap_or(false) {
+            return builder_fields(WithOffsets::<_, sam::alignment::Record>::new(batch_builder));
+        }
+        builder_fields(batch_builder)
+    }
+
     /// Returns the records in the given region as Apache Arrow IPC.
     ///
-    /// If the region is `None`, all records are returned.
+    /// If the region is `None`, all records are returned. The region `*` selects the unplaced
+    /// unmapped reads, as in [`BamReader::records_to_ipc_unmapped`].
     ///
     /// # Examples
     ///
@@ -99,67 +768,930 @@ where
     /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
     /// ```
     pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
-        let batch_builder = BamBatchBuilder::new(1024, &self.header)?;
+        self.scan_map(region, Some)
+    }
+
+    /// Returns the records in the given region for which `predicate` returns true as Apache
+    /// Arrow IPC, like [`BamReader::records_to_ipc`].
+    ///
+    /// The predicate sees the records passing the filters in [`BamOptions`], before they are
+    /// subsampled and limited.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader
+    ///     .scan_with(None, |record| record.flags().is_reverse_complemented())
+    ///     .unwrap();
+    /// ```
+    pub fn scan_with(
+        &mut self,
+        region: Option<&str>,
+        mut predicate: impl FnMut(&sam::alignment::Record) -> bool,
+    ) -> Result<Vec<u8>, ArrowError> {
+        self.scan_map(region, |record| predicate(&record).then_some(record))
+    }
+
+    /// Returns the records in the given region as Apache Arrow IPC, like
+    /// [`BamReader::records_to_ipc`], after passing each through `f`, which returns the record to
+    /// write, e.g. with a changed field, or `None` to drop it.
+    ///
+    /// `f` sees the records passing the filters in [`BamOptions`], before they are subsampled and
+    /// limited.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader
+    ///     .scan_map(None, |mut record| {
+    ///         *record.mapping_quality_mut() = None;
+    ///         Some(record)
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn scan_map(
+        &mut self,
+        region: Option<&str>,
+        mut f: impl FnMut(sam::alignment::Record) -> Option<sam::alignment::Record>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        if region == Some("*") {
+            return self.unmapped_to_ipc(f);
+        }
+        if region.is_some() {
+            check_no_offsets(self.options.emit_offset)?;
+        } else if self.options.emit_offset.unwrap_or(false) {
+            let vpos_lo = self.reader.virtual_position();
+            return self.vpos_range_to_ipc(vpos_lo, bgzf::VirtualPosition::MAX, f);
+        }
+        let batch_builder = BamBatchBuilder::new(1024, &self.header, &self.options)?
+            .with_metadata(provenance("bam", self.path.as_deref(), region.as_slice()));
+        let limit = self.limit.unwrap_or(usize::MAX);
         if let Some(region) = region {
-            let region: Region = region.parse().unwrap();
+            let region = self.query_region(region)?;
             let query = self
                 .reader
                 .query(&self.header, &self.index, &region)
                 .map_err(|e| ArrowError::ExternalError(e.into()))?
                 .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+            let query = track_query(query, &mut self.progress, &self.cancel);
+            let query = filter_interval(query, region.interval(), self.query_mode);
+            let records = map_records(filter_records(query, &self.options), &mut f);
+            let records = self.options.subsample(records)?;
+            let result = write_ipc_err(records.take(limit), batch_builder);
+            finish_query(&mut self.progress);
+            return result;
+        }
+        self.scan_records(
+            self.options.decodes_sequence(),
+            |records, header, options| {
+                let records = check_sorted(records, header, options)?;
+                let records = map_records(filter_records(records, options), &mut f);
+                let records = options.subsample(records)?;
+                write_ipc_err(records.take(limit), batch_builder)
+            },
+        )
+    }
 
-            return write_ipc_err(query, batch_builder);
+    /// Returns the records in the given regions as Apache Arrow IPC, in region order.
+    ///
+    /// Records matching more than one region are returned once per region. With
+    /// [`BamOptions::sample_fraction`] or [`BamOptions::sample_count`], the records of all the
+    /// regions are read before sampling them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader.regions_to_ipc(&["sq0:1-1000", "sq1"]).unwrap();
+    /// ```
+    pub fn regions_to_ipc(&mut self, regions: &[&str]) -> Result<Vec<u8>, ArrowError> {
+        check_no_offsets(self.options.emit_offset)?;
+        let metadata = provenance("bam", self.path.as_deref(), regions);
+        let regions = regions
+            .iter()
+            .map(|region| self.query_region(region))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut batch_builder =
+            BamBatchBuilder::new(1024, &self.header, &self.options)?.with_metadata(metadata);
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let sampled = self.options.samples();
+        let mut kept = Vec::new();
+        let mut remaining = if sampled { usize::MAX } else { limit };
+        for region in regions.iter() {
+            if remaining == 0 {
+                break;
+            }
+            let query = self
+                .reader
+                .query(&self.header, &self.index, region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+            for record in track_query(query, &mut self.progress, &self.cancel) {
+                let record = record.map_err(|e| ArrowError::ExternalError(e.into()))?;
+                if self.options.keep(&record)
+                    && in_interval(&record, region.interval(), self.query_mode)
+                {
+                    if sampled {
+                        kept.push(record);
+                    } else {
+                        batch_builder.push(&record);
+                    }
+                    remaining -= 1;
+                    if remaining == 0 {
+                        break;
+                    }
+                }
+            }
         }
-        let records = self
-            .reader
-            .records(&self.header)
-            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-        write_ipc_err(records, batch_builder)
+        finish_query(&mut self.progress);
+        if sampled {
+            let records = self.options.subsample(kept.into_iter().map(Ok))?;
+            return write_ipc_err(records.take(limit), batch_builder);
+        }
+        finish_batch(batch_builder)
     }
 
-    pub fn records_to_ipc_from_vpos(
+    /// Returns the records of each reference sequence, or of windows tiling them, as a separate
+    /// Apache Arrow IPC file per region, in header order.
+    ///
+    /// The regions are generated with [`tile_regions`] from the reference sequences of the header,
+    /// and are queried lazily, one at a time. Records overlapping several windows are returned
+    /// in each of them, unless the query mode is [`QueryMode::Contained`], and unplaced unmapped
+    /// reads in none.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// for ipc in reader.scan_all_regions(Some(1_000_000), None).unwrap() {
+    ///     let ipc = ipc.unwrap();
+    /// }
+    /// ```
+    pub fn scan_all_regions(
         &mut self,
-        pos_lo: (u64, u16),
-        pos_hi: (u64, u16),
-    ) -> Result<Vec<u8>, ArrowError> {
-        let vpos_lo = bgzf::VirtualPosition::try_from(pos_lo)
-            .map_err(|e| ArrowError::ExternalError(e.into()))?;
-        let vpos_hi = bgzf::VirtualPosition::try_from(pos_hi)
-            .map_err(|e| ArrowError::ExternalError(e.into()))?;
-        let batch_builder = BamBatchBuilder::new(1024, &self.header)?;
-        let records = BamRecords::new(&mut self.reader, &self.header, vpos_lo, vpos_hi)
-            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-        write_ipc_err(records, batch_builder)
+        window: Option<usize>,
+        step: Option<usize>,
+    ) -> Result<impl Iterator<Item = Result<Vec<u8>, ArrowError>> + '_, ArrowError> {
+        let sequences =
+            self.header
+                .reference_sequences()
+                .iter()
+                .map(|(name, reference_sequence)| {
+                    (name.to_string(), reference_sequence.length().get())
+                });
+        let regions = tile_regions(sequences, window, step)
+            .map_err(|e| ArrowError::InvalidArgumentError(e.to_string()))?;
+        Ok(regions
+            .into_iter()
+            .map(|region| self.records_to_ipc(Some(&region.to_string()))))
     }
-}
 
-struct BamBatchBuilder<'a> {
-    header: &'a sam::Header,
-    qname: GenericStringBuilder<i32>,
-    flag: UInt16Builder,
-    rname: StringDictionaryBuilder<Int32Type>,
-    pos: Int32Builder,
-    mapq: UInt8Builder,
-    cigar: GenericStringBuilder<i32>,
-    rnext: StringDictionaryBuilder<Int32Type>,
-    pnext: Int32Builder,
-    tlen: Int32Builder,
-    seq: GenericStringBuilder<i32>,
-    qual: GenericStringBuilder<i32>,
-    end: Int32Builder,
-    tags: TagsBuilder,
-}
+    /// Returns the records in the given regions as Apache Arrow IPC, querying them in parallel.
+    ///
+    /// The regions are split into contiguous chunks that are queried on up to `threads` threads
+    /// (by default, the available parallelism), each with its own file handle. The output is the
+    /// same as [`BamReader::regions_to_ipc`]. This requires a reader created from a file path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader
+    ///     .regions_to_ipc_parallel(&["sq0:1-1000", "sq1"], Some(4))
+    ///     .unwrap();
+    /// ```
+    pub fn regions_to_ipc_parallel(
+        &mut self,
+        regions: &[&str],
+        threads: Option<usize>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        check_no_offsets(self.options.emit_offset)?;
+        let path = self.path.as_ref().ok_or_else(|| {
+            ArrowError::InvalidArgumentError(
+                "Parallel queries require a reader created from a file path.".to_string(),
+            )
+        })?;
+        let metadata = provenance("bam", Some(path), regions);
+        let regions = regions
+            .iter()
+            .map(|region| self.query_region(region))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (header, index, options) = (&self.header, &self.index, &self.options);
+        let query_mode = self.query_mode;
+        let limit = self.limit.unwrap_or(usize::MAX);
+        // Sampling needs all the records, so the chunks only stop at the limit without it.
+        let chunk_limit = if options.samples() { usize::MAX } else { limit };
+        let (progress, cancel) = (&mut self.progress, &self.cancel);
+        let mut records = query_parallel(&regions, threads, progress, |regions, counter| {
+            let file = File::open(path).map_err(|e| ArrowError::ExternalError(e.into()))?;
+            let mut reader = bam::Reader::new(BufReader::with_capacity(BUFFER_SIZE_BYTES, file));
+            let mut records = Vec::new();
+            // Each chunk stops at the limit, and the concatenation is truncated to it below.
+            for region in regions {
+                let query = reader
+                    .query(header, index, region)
+                    .map_err(|e| ArrowError::ExternalError(e.into()))?;
+                for record in query {
+                    if records.len() == chunk_limit || is_cancelled(cancel) {
+                        return Ok(records);
+                    }
+                    let record = record.map_err(|e| ArrowError::ExternalError(e.into()))?;
+                    counter.record();
+                    if options.keep(&record) && in_interval(&record, region.interval(), query_mode)
+                    {
+                        records.push(record);
+                    }
+                }
+            }
+            Ok(records)
+        })?;
+        let batch_builder = BamBatchBuilder::new(records.len().min(limit), &self.header, options)?
+            .with_metadata(metadata);
+        if options.samples() {
+            let records = options.subsample(records.into_iter().map(Ok))?;
+            return write_ipc_err(records.take(limit), batch_builder);
+        }
+        records.truncate(limit);
+        write_ipc(records.into_iter(), batch_builder)
+    }
 
-enum TagArrayBuilder {
-    Character(GenericStringBuilder<i32>),
-    Int8(Int8Builder),
-    UInt8(UInt8Builder),
-    Int16(Int16Builder),
-    UInt16(UInt16Builder),
-    Int32(Int32Builder),
-    UInt32(UInt32Builder),
-    Float(Float32Builder),
-    String(GenericStringBuilder<i32>),
+    /// Returns a per-base pileup of the given regions as Apache Arrow IPC.
+    ///
+    /// Each covered reference position of a region gives a row with the columns `chrom`, `pos`
+    /// (1-based), `depth`, `a`, `c`, `g`, `t`, `n` and `del`: the number of aligned bases of each
+    /// kind, any other base counting as `n`, and of deletions spanning the position. The depth is
+    /// their sum. Insertions, clips and reference skips are not counted, and positions without
+    /// any are omitted. Records rejected by the filters in [`BamOptions`] and unmapped reads are
+    /// skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader.pileup_to_ipc(&["sq0:1-1000"]).unwrap();
+    /// ```
+    pub fn pileup_to_ipc(&mut self, regions: &[&str]) -> Result<Vec<u8>, ArrowError> {
+        let metadata = provenance("bam", self.path.as_deref(), regions);
+        let regions = regions
+            .iter()
+            .map(|region| self.query_region(region))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut batch_builder =
+            PileupBatchBuilder::new(1024, &self.header)?.with_metadata(metadata);
+        for region in regions.iter() {
+            let interval = region.interval();
+            let length = self
+                .header
+                .reference_sequences()
+                .get(region.name())
+                .map_or(usize::MAX, |reference_sequence| {
+                    reference_sequence.length().get()
+                });
+            let mut pileup = Pileup::new(
+                interval.start().map_or(1, |position| position.get()),
+                interval.end().map_or(length, |position| position.get()),
+            );
+            let query = self
+                .reader
+                .query(&self.header, &self.index, region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+            for record in query {
+                let record = record.map_err(|e| ArrowError::ExternalError(e.into()))?;
+                if self.options.keep(&record) && !record.flags().is_unmapped() {
+                    pileup.push(&record);
+                }
+            }
+            for (pos, counts) in pileup.into_counts() {
+                batch_builder.push(PileupRow {
+                    chrom: region.name(),
+                    pos,
+                    counts,
+                });
+            }
+        }
+        finish_batch(batch_builder)
+    }
+
+    /// Returns binned read counts of the given regions, or of all reference sequences, as Apache
+    /// Arrow IPC.
+    ///
+    /// Each region is tiled from its start with windows of `window_size` bases, the last of which
+    /// is truncated to the end of the region, giving a row each with the columns `chrom`,
+    /// `window_start`, `window_end` and `read_count`. Reads are counted in the window of their
+    /// alignment start, or in every window they overlap with [`WindowAssignment::Overlap`].
+    /// `include_mean_depth` adds a `mean_depth` column (Float64): the number of bases aligned to
+    /// the window by `M`, `=` and `X` operations, divided by its length. Records rejected by the
+    /// filters in [`BamOptions`] and unmapped reads are skipped, and `window_start` follows
+    /// [`BamOptions::coordinate_system`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    /// use oxbow::coverage::WindowAssignment;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader
+    ///     .coverage_windows(10_000, None, WindowAssignment::Start, true)
+    ///     .unwrap();
+    /// ```
+    pub fn coverage_windows(
+        &mut self,
+        window_size: usize,
+        regions: Option<&[&str]>,
+        assignment: WindowAssignment,
+        include_mean_depth: bool,
+    ) -> Result<Vec<u8>, ArrowError> {
+        if window_size == 0 {
+            return Err(ArrowError::InvalidArgumentError(
+                "The window size must be positive.".to_string(),
+            ));
+        }
+        let metadata = provenance("bam", self.path.as_deref(), regions.unwrap_or_default());
+        let regions = match regions {
+            Some(regions) => regions
+                .iter()
+                .map(|region| self.query_region(region))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => self
+                .header
+                .reference_sequences()
+                .keys()
+                .map(|name| Region::new(name.to_string(), ..))
+                .collect(),
+        };
+        let coordinate_system = self
+            .options
+            .coordinate_system
+            .unwrap_or(CoordinateSystem::OneBased);
+        let mut batch_builder =
+            CoverageBatchBuilder::new(1024, &self.header, include_mean_depth, coordinate_system)?
+                .with_metadata(metadata);
+        for region in regions.iter() {
+            // The query fails for reference sequences missing from the header.
+            let query = self
+                .reader
+                .query(&self.header, &self.index, region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+            let interval = region.interval();
+            let length = self.header.reference_sequences()[region.name()].length();
+            let mut windows = Windows::new(
+                interval.start().map_or(1, |position| position.get()),
+                interval
+                    .end()
+                    .map_or(length.get(), |position| position.get()),
+                window_size,
+                assignment,
+            );
+            for record in query {
+                let record = record.map_err(|e| ArrowError::ExternalError(e.into()))?;
+                if self.options.keep(&record) && !record.flags().is_unmapped() {
+                    windows.push(&record);
+                }
+            }
+            for (start, end, read_count, bases) in windows.into_windows() {
+                batch_builder.push(CoverageWindow {
+                    chrom: region.name(),
+                    start,
+                    end,
+                    read_count,
+                    bases,
+                });
+            }
+        }
+        finish_batch(batch_builder)
+    }
+
+    /// Returns the read pairs in the given region as Apache Arrow IPC, one row per template.
+    ///
+    /// Reads are matched with their mates by name. Each row has the `qname`, then the `rname`,
+    /// `pos`, `end`, `mapq` and `flag` of the first (`rname1`, ...) and last (`rname2`, ...)
+    /// segments, the `tlen`, the `fragment_length` from the leftmost start to the rightmost end
+    /// of mates aligned to the same reference sequence, and whether the reads are a
+    /// `proper_pair`. Pairs are returned once their second mate is read, followed by the reads
+    /// whose mate is not in the region, or was filtered out, with null mate columns. Unpaired
+    /// reads are returned as such orphans too. Secondary and supplementary alignments are
+    /// skipped. `emit_ref_as_index` and `coordinate_system` apply as for records.
+    ///
+    /// Reads are held in memory until their mate is found, which for pairs spanning a long
+    /// distance, or for a name-sorted file, can be most of the region. Reading fails when more
+    /// than `max_buffer` reads, by default 1,000,000, are waiting for their mates.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader.pairs_to_ipc(Some("sq0:1-1000"), None).unwrap();
+    /// ```
+    pub fn pairs_to_ipc(
+        &mut self,
+        region: Option<&str>,
+        max_buffer: Option<usize>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        let mut batch_builder = PairBatchBuilder::new(
+            1024,
+            &self.header,
+            self.options.emit_ref_as_index.unwrap_or(false),
+            self.options
+                .coordinate_system
+                .unwrap_or(CoordinateSystem::OneBased),
+        )?
+        .with_metadata(provenance("bam", self.path.as_deref(), region.as_slice()));
+        let records: Box<dyn Iterator<Item = Result<sam::alignment::Record, ArrowError>>> =
+            match region {
+                Some(region) => {
+                    let region = self.query_region(region)?;
+                    let query = self
+                        .reader
+                        .query(&self.header, &self.index, &region)
+                        .map_err(|e| ArrowError::ExternalError(e.into()))?
+                        .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+                    Box::new(filter_interval(query, region.interval(), self.query_mode))
+                }
+                None => Box::new(
+                    self.reader
+                        .records(&self.header)
+                        .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into()))),
+                ),
+            };
+        let mut pairer = Pairer::new(max_buffer.unwrap_or(DEFAULT_MAX_BUFFER));
+        for record in filter_records(records, &self.options) {
+            if let Some(pair) = pairer.push(record?)? {
+                batch_builder.push(&pair);
+            }
+        }
+        for pair in pairer.into_orphans() {
+            batch_builder.push(&pair);
+        }
+        finish_batch(batch_builder)
+    }
+
+    /// Returns the unplaced unmapped reads as Apache Arrow IPC.
+    ///
+    /// These are stored at the end of a coordinate-sorted BAM file, and the index is used to seek
+    /// past the placed reads. Unmapped reads placed alongside their mapped mate are returned by
+    /// region queries instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader.records_to_ipc_unmapped().unwrap();
+    /// ```
+    pub fn records_to_ipc_unmapped(&mut self) -> Result<Vec<u8>, ArrowError> {
+        self.unmapped_to_ipc(Some)
+    }
+
+    /// Returns the unplaced unmapped reads passed through `f` as Apache Arrow IPC.
+    fn unmapped_to_ipc(
+        &mut self,
+        f: impl FnMut(sam::alignment::Record) -> Option<sam::alignment::Record>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        check_no_offsets(self.options.emit_offset)?;
+        let batch_builder = BamBatchBuilder::new(1024, &self.header, &self.options)?
+            .with_metadata(provenance("bam", self.path.as_deref(), &["*"]));
+        let query = unmapped_records(&mut self.reader, &self.header, &self.index)?;
+        let query = track_query(query, &mut self.progress, &self.cancel);
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let records = map_records(filter_records(query, &self.options), f);
+        let records = self.options.subsample(records)?;
+        let result = write_ipc_err(records.take(limit), batch_builder);
+        finish_query(&mut self.progress);
+        result
+    }
+
+    /// Returns the number of records in the given region, without converting them to Arrow.
+    ///
+    /// If the region is `None`, all records are counted. The region `*` counts the unplaced
+    /// unmapped reads. Records rejected by the filters in [`BamOptions`] are not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let n = reader.count(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        let options = &self.options;
+        let count = match region {
+            Some("*") => {
+                let query = unmapped_records(&mut self.reader, &self.header, &self.index)?;
+                count_err(filter_records(
+                    track_query(query, &mut self.progress, &self.cancel),
+                    options,
+                ))
+            }
+            Some(region) => {
+                let region = self.query_region(region)?;
+                let query = self
+                    .reader
+                    .query(&self.header, &self.index, &region)
+                    .map_err(|e| ArrowError::ExternalError(e.into()))?
+                    .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+                let query = track_query(query, &mut self.progress, &self.cancel);
+                let query = filter_interval(query, region.interval(), self.query_mode);
+                count_err(filter_records(query, options))
+            }
+            None => {
+                return self.scan_records(false, |records, header, options| {
+                    let records = check_sorted(records, header, options)?;
+                    count_err(filter_records(records, options))
+                })
+            }
+        };
+        finish_query(&mut self.progress);
+        count
+    }
+
+    /// Returns the number of records in each of the given regions.
+    pub fn count_regions(&mut self, regions: &[&str]) -> Result<Vec<usize>, ArrowError> {
+        regions
+            .iter()
+            .map(|region| self.count(Some(region)))
+            .collect()
+    }
+
+    pub fn records_to_ipc_from_vpos(
+        &mut self,
+        pos_lo: (u64, u16),
+        pos_hi: (u64, u16),
+    ) -> Result<Vec<u8>, ArrowError> {
+        let vpos_lo = bgzf::VirtualPosition::try_from(pos_lo)
+            .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        let vpos_hi = bgzf::VirtualPosition::try_from(pos_hi)
+            .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        self.vpos_range_to_ipc(vpos_lo, vpos_hi, Some)
+    }
+
+    /// Returns the records from a BGZF virtual offset to the end of the file as Apache Arrow
+    /// IPC, e.g. to resume a scan from an offset emitted with [`BamOptions::emit_offset`].
+    ///
+    /// The offset must be the start of a record, not before the first record. This is checked
+    /// against the layout of the bytes at the offset, which an offset inside a record is very
+    /// unlikely to match.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamReader;
+    ///
+    /// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+    /// let ipc = reader.scan_from(123 << 16).unwrap();
+    /// ```
+    pub fn scan_from(&mut self, vpos: u64) -> Result<Vec<u8>, ArrowError> {
+        let vpos = bgzf::VirtualPosition::from(vpos);
+        self.check_record_start(vpos)?;
+        self.vpos_range_to_ipc(vpos, bgzf::VirtualPosition::MAX, Some)
+    }
+
+    /// Fails unless `vpos` looks like the start of a record.
+    fn check_record_start(&mut self, vpos: bgzf::VirtualPosition) -> Result<(), ArrowError> {
+        let invalid = || {
+            ArrowError::InvalidArgumentError(format!(
+                "Invalid virtual position: {}. Expected the start of a record.",
+                u64::from(vpos)
+            ))
+        };
+        if vpos < self.records_start {
+            return Err(invalid());
+        }
+        self.reader.seek(vpos).map_err(|_| invalid())?;
+        let n_references = self.header.reference_sequences().len();
+        match is_record_start(self.reader.get_mut(), n_references) {
+            Ok(true) => Ok(()),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Returns the records from `vpos_lo` up to `vpos_hi` passed through `f` as Apache Arrow IPC,
+    /// with their virtual offsets if [`BamOptions::emit_offset`] is set.
+    fn vpos_range_to_ipc(
+        &mut self,
+        vpos_lo: bgzf::VirtualPosition,
+        vpos_hi: bgzf::VirtualPosition,
+        mut f: impl FnMut(sam::alignment::Record) -> Option<sam::alignment::Record>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = BamBatchBuilder::new(1024, &self.header, &self.options)?;
+        let metadata = provenance("bam", self.path.as_deref(), &[]);
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let (options, progress, cancel) = (&self.options, &mut self.progress, &self.cancel);
+        let records =
+            BamRecords::new(&mut self.reader, &self.header, vpos_lo, vpos_hi).with_offsets();
+        let records = until_cancelled(records, cancel).map(|i| {
+            let (vpos, record) = i.map_err(|e| ArrowError::ExternalError(e.into()))?;
+            track(progress, bgzf::VirtualPosition::from(vpos));
+            Ok((vpos, record))
+        });
+        let result = if options.emit_offset.unwrap_or(false) {
+            let records =
+                records.filter(|i| i.as_ref().map_or(true, |(_, record)| options.keep(record)));
+            let records = map_records(records, |(vpos, record)| f(record).map(|r| (vpos, r)));
+            let records = options.subsample(records)?;
+            write_ipc_err(
+                records.take(limit),
+                WithOffsets::new(batch_builder).with_metadata(metadata),
+            )
+        } else {
+            let records = records.map(|i| i.map(|(_, record)| record));
+            let records = options.subsample(map_records(filter_records(records, options), f))?;
+            write_ipc_err(records.take(limit), batch_builder.with_metadata(metadata))
+        };
+        finish_progress(&mut self.progress, self.reader.virtual_position());
+        result
+    }
+}
+
+/// Returns whether the bytes read from `reader` start a BAM record, or the end of the file.
+///
+/// The block size must cover the fixed-length fields, the read name, the CIGAR and the sequence,
+/// the reference sequence IDs must be among the `n_references` of the header or -1, and the read
+/// name must end with its only NUL.
+fn is_record_start<R: BufRead>(reader: &mut R, n_references: usize) -> io::Result<bool> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(true);
+    }
+    let mut fixed = [0; 36];
+    reader.read_exact(&mut fixed)?;
+    let u16_at = |i: usize| u16::from_le_bytes([fixed[i], fixed[i + 1]]) as usize;
+    let i32_at = |i: usize| i32::from_le_bytes(fixed[i..i + 4].try_into().unwrap());
+    let block_size = i32_at(0) as u32 as usize;
+    let is_reference = |id: i32| id == -1 || (0..n_references as i64).contains(&(id as i64));
+    let name_len = fixed[12] as usize;
+    let (n_cigar_ops, seq_len) = (u16_at(16), i32_at(20) as u32 as usize);
+    if !is_reference(i32_at(4))
+        || !is_reference(i32_at(24))
+        || i32_at(8) < -1
+        || i32_at(28) < -1
+        || name_len == 0
+        || block_size < 32 + name_len + 4 * n_cigar_ops + seq_len.div_ceil(2) + seq_len
+    {
+        return Ok(false);
+    }
+    let mut name = vec![0; name_len];
+    reader.read_exact(&mut name)?;
+    Ok(name.iter().position(|&b| b == 0) == Some(name_len - 1))
+}
+
+/// Returns the unplaced unmapped reads in a coordinate-sorted BAM file.
+fn unmapped_records<'a, R>(
+    reader: &'a mut bam::Reader<bgzf::Reader<R>>,
+    header: &'a sam::Header,
+    index: &'a csi::Index,
+) -> Result<impl Iterator<Item = Result<sam::alignment::Record, ArrowError>> + 'a, ArrowError>
+where
+    R: Read + Seek,
+{
+    let query = reader
+        .query_unmapped(header, index)
+        .map_err(|e| ArrowError::ExternalError(e.into()))?
+        // The seek may land on placed reads that share the last bin.
+        .filter(|i| {
+            i.as_ref()
+                .map_or(true, |record| record.reference_sequence_id().is_none())
+        })
+        .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+    Ok(query)
+}
+
+/// Reads several BAM files as a single stream, e.g. a sample sharded by lane.
+///
+/// The files must share their reference sequences, with the same names and lengths in the same
+/// order. Records are returned file by file, in the order the paths are given.
+pub struct BamMultiReader {
+    readers: Vec<BamReader<BufReader<File>>>,
+    paths: Vec<String>,
+    options: BamOptions,
+    source: bool,
+}
+
+impl BamMultiReader {
+    /// Creates a reader over the BAM files at the given paths, each with an index at
+    /// `<path>.bai` or `<path>.csi`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bam::BamMultiReader;
+    ///
+    /// let mut reader = BamMultiReader::new_from_paths(&["lane1.bam", "lane2.bam"]).unwrap();
+    /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn new_from_paths(paths: &[&str]) -> io::Result<Self> {
+        let readers = paths
+            .iter()
+            .map(|path| BamReader::new_from_path(path))
+            .collect::<io::Result<Vec<_>>>()?;
+        let Some(first) = readers.first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "At least one BAM file is required.",
+            ));
+        };
+        let reference_sequences = |reader: &BamReader<BufReader<File>>| {
+            reader
+                .header
+                .reference_sequences()
+                .iter()
+                .map(|(name, rs)| (name.clone(), rs.length()))
+                .collect::<Vec<_>>()
+        };
+        let expected = reference_sequences(first);
+        for (reader, path) in readers.iter().zip(paths).skip(1) {
+            if reference_sequences(reader) != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Incompatible BAM headers: the reference sequences of {} differ from \
+                         those of {}.",
+                        path, paths[0]
+                    ),
+                ));
+            }
+        }
+        Ok(Self {
+            readers,
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            options: BamOptions::default(),
+            source: false,
+        })
+    }
+
+    /// Sets the options used when converting records, for all files.
+    pub fn with_options(mut self, options: BamOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Adds a `source` column (dictionary-encoded Utf8) with the path of the file each record
+    /// was read from. It comes last, after any columns selected with [`BamOptions::fields`].
+    pub fn with_source(mut self, source: bool) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Returns the records of all files in the given region as Apache Arrow IPC.
+    ///
+    /// If the region is `None`, all records are returned. Otherwise, each file is queried for
+    /// the region in turn.
+    pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
+        let metadata = provenance("bam", None, region.as_slice());
+        let region = region
+            .map(|region| self.readers[0].query_region(region))
+            .transpose()?;
+        let header = &self.readers[0].header;
+        let mut batch_builder = MultiBamBatchBuilder {
+            inner: BamBatchBuilder::new(1024, header, &self.options)?,
+            source: self.source.then(StringDictionaryBuilder::<Int32Type>::new),
+        }
+        .with_metadata(metadata);
+        for (reader, path) in self.readers.iter_mut().zip(&self.paths) {
+            for record in reader.records(region.as_ref())? {
+                let record = record?;
+                if self.options.keep(&record) {
+                    batch_builder.push((path, &record));
+                }
+            }
+        }
+        finish_batch(batch_builder)
+    }
+
+    /// Returns the number of records of all files in the given region, without converting them
+    /// to Arrow.
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        let region = region
+            .map(|region| self.readers[0].query_region(region))
+            .transpose()?;
+        let mut count = 0;
+        for reader in self.readers.iter_mut() {
+            let records = reader.records(region.as_ref())?;
+            count += count_err(filter_records(records, &self.options))?;
+        }
+        Ok(count)
+    }
+}
+
+impl BamReader<BufReader<File>> {
+    /// Returns the records overlapping `region`, or all records if it is `None`.
+    fn records(
+        &mut self,
+        region: Option<&Region>,
+    ) -> Result<Box<dyn Iterator<Item = Result<sam::alignment::Record, ArrowError>> + '_>, ArrowError>
+    {
+        match region {
+            Some(region) => {
+                let query = self
+                    .reader
+                    .query(&self.header, &self.index, region)
+                    .map_err(|e| ArrowError::ExternalError(e.into()))?
+                    .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+                Ok(Box::new(filter_interval(
+                    query,
+                    region.interval(),
+                    QueryMode::default(),
+                )))
+            }
+            None => {
+                Ok(Box::new(self.reader.records(&self.header).map(|i| {
+                    i.map_err(|e| ArrowError::ExternalError(e.into()))
+                })))
+            }
+        }
+    }
+}
+
+/// Builds BAM batches with an optional `source` column.
+struct MultiBamBatchBuilder {
+    inner: BamBatchBuilder,
+    source: Option<StringDictionaryBuilder<Int32Type>>,
+}
+
+impl BatchBuilder for MultiBamBatchBuilder {
+    type Record<'a> = (&'a str, &'a sam::alignment::Record);
+
+    fn push(&mut self, (path, record): Self::Record<'_>) {
+        self.inner.push(record);
+        if let Some(source) = self.source.as_mut() {
+            source.append_value(path);
+        }
+    }
+
+    fn finish(self) -> Result<RecordBatch, ArrowError> {
+        let batch = self.inner.finish()?;
+        let Some(mut source) = self.source else {
+            return Ok(batch);
+        };
+        let mut fields = batch.schema().fields().to_vec();
+        let source = Arc::new(source.finish()) as ArrayRef;
+        fields.push(Arc::new(Field::new(
+            "source",
+            source.data_type().clone(),
+            false,
+        )));
+        let mut columns = batch.columns().to_vec();
+        columns.push(source);
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+    }
+}
+
+pub(crate) struct BamBatchBuilder {
+    qname: GenericStringBuilder<i32>,
+    flag: UInt16Builder,
+    rname: ReferenceColumnBuilder,
+    pos: PositionBuilder,
+    mapq: UInt8Builder,
+    cigar: GenericStringBuilder<i32>,
+    rnext: ReferenceColumnBuilder,
+    pnext: PositionBuilder,
+    tlen: Int32Builder,
+    /// The `seq` and `qual` columns, unless [`BamOptions::fields`] leaves them out, to skip
+    /// formatting the bases and quality scores of each read.
+    seq: Option<GenericStringBuilder<i32>>,
+    qual: Option<GenericStringBuilder<i32>>,
+    end: PositionBuilder,
+    reference_length: Option<Int32Builder>,
+    query_length: Option<Int32Builder>,
+    /// The `left_soft_clip`, `right_soft_clip`, `left_hard_clip` and `right_hard_clip` columns.
+    clip_lengths: Option<[Int32Builder; 4]>,
+    /// The columns of [`FLAG_COLUMNS`].
+    flags: Option<[BooleanBuilder; 12]>,
+    /// The sample of each read group, and the `sample` column.
+    sample: Option<(HashMap<String, String>, StringDictionaryBuilder<Int32Type>)>,
+    methylation: Option<MethylationBuilder>,
+    tags: TagsBuilder,
+    tag_columns: Option<TagColumnsBuilder>,
+    fields: Option<Vec<String>>,
+    coordinate_system: CoordinateSystem,
+}
+
+enum TagArrayBuilder {
+    Character(GenericStringBuilder<i32>),
+    Int8(Int8Builder),
+    UInt8(UInt8Builder),
+    Int16(Int16Builder),
+    UInt16(UInt16Builder),
+    Int32(Int32Builder),
+    UInt32(UInt32Builder),
+    Float(Float32Builder),
+    String(GenericStringBuilder<i32>),
     Hex(GenericStringBuilder<i32>),
 }
 
@@ -179,7 +1711,6 @@ impl TagsBuilder {
     }
 
     pub fn push_tags(&mut self, data: &'_ Data) {
-        use sam::record::data::field::Value;
         self.tags.extend(data.keys());
 
         for tag in self.tags.iter() {
@@ -335,6 +1866,10 @@ impl TagsBuilder {
     }
 
     fn try_finish(&mut self) -> Result<StructArray, ArrowError> {
+        // A struct with no fields would otherwise have a length of zero.
+        if self.inner.is_empty() {
+            return Ok(StructArray::new_empty_fields(self.seen, None));
+        }
         let keys = self.inner.keys().map(|x| x.to_string()).collect::<Vec<_>>();
         let arrays: Vec<(&str, ArrayRef)> = self
             .inner
@@ -367,92 +1902,446 @@ impl Default for TagsBuilder {
     }
 }
 
-impl<'a> BamBatchBuilder<'a> {
-    pub fn new(capacity: usize, header: &'a sam::Header) -> Result<Self, ArrowError> {
-        let categories = StringArray::from(
-            header
-                .reference_sequences()
-                .iter()
-                .map(|(rs, _)| Some(rs.as_str()))
-                .collect::<Vec<_>>(),
-        );
-        Ok(Self {
-            header,
-            qname: GenericStringBuilder::<i32>::new(),
-            flag: UInt16Array::builder(capacity),
-            rname: StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
-                capacity,
-                &categories,
-            )?,
-            pos: Int32Array::builder(capacity),
-            mapq: UInt8Array::builder(capacity),
-            cigar: GenericStringBuilder::<i32>::new(),
-            rnext: StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
-                capacity,
-                &categories,
-            )?,
-            pnext: Int32Array::builder(capacity),
-            tlen: Int32Array::builder(capacity),
-            seq: GenericStringBuilder::<i32>::new(),
-            qual: GenericStringBuilder::<i32>::new(),
-            end: Int32Array::builder(capacity),
-            tags: TagsBuilder::new(),
-        })
-    }
+/// A typed column builder for a selected tag.
+enum TagColumnBuilder {
+    Int(Int32Builder),
+    Long(Int64Builder),
+    Float(Float32Builder),
+    String(GenericStringBuilder<i32>),
+    IntArray(ListBuilder<Int32Builder>),
+    LongArray(ListBuilder<Int64Builder>),
+    FloatArray(ListBuilder<Float32Builder>),
 }
 
-impl BatchBuilder for BamBatchBuilder<'_> {
-    type Record<'x> = &'x sam::alignment::Record;
-
-    fn push(&mut self, record: Self::Record<'_>) {
-        self.qname.append_option(record.read_name());
-        self.flag.append_value(record.flags().bits());
-        let rname = match record.reference_sequence(self.header) {
-            Some(Ok((name, _))) => Some(name.as_str()),
-            _ => None,
-        };
-        self.rname.append_option(rname);
-        self.pos
-            .append_option(record.alignment_start().map(|x| x.get() as i32));
-        self.mapq
-            .append_option(record.mapping_quality().map(|x| x.get()));
-        self.cigar.append_value(record.cigar().to_string());
-        let rnext = match record.mate_reference_sequence(self.header) {
-            Some(Ok((name, _))) => Some(name.as_str()),
-            _ => None,
+impl TagColumnBuilder {
+    /// Creates a builder for the kind of `value`, padded with `nulls` leading nulls.
+    fn new(value: &Value, nulls: usize) -> Self {
+        use sam::record::data::field::value::Array;
+        let mut builder = match value {
+            Value::Int8(_)
+            | Value::UInt8(_)
+            | Value::Int16(_)
+            | Value::UInt16(_)
+            | Value::Int32(_) => Self::Int(Int32Builder::new()),
+            // `I` values may not fit in an Int32.
+            Value::UInt32(_) => Self::Long(Int64Builder::new()),
+            Value::Float(_) => Self::Float(Float32Builder::new()),
+            Value::Character(_) | Value::String(_) | Value::Hex(_) => {
+                Self::String(GenericStringBuilder::<i32>::new())
+            }
+            Value::Array(Array::Float(_)) => {
+                Self::FloatArray(ListBuilder::new(Float32Builder::new()))
+            }
+            Value::Array(Array::UInt32(_)) => {
+                Self::LongArray(ListBuilder::new(Int64Builder::new()))
+            }
+            Value::Array(_) => Self::IntArray(ListBuilder::new(Int32Builder::new())),
         };
-        self.rnext.append_option(rnext);
-        self.pnext
-            .append_option(record.mate_alignment_start().map(|x| x.get() as i32));
-        self.tlen.append_value(record.template_length());
-        self.seq.append_value(record.sequence().to_string());
-        self.qual.append_value(record.quality_scores().to_string());
+        for _ in 0..nulls {
+            builder.append_null();
+        }
+        builder
+    }
 
-        // extra
-        self.end
-            .append_option(record.alignment_end().map(|x| x.get() as i32));
-        self.tags.push_tags(record.data());
+    /// Appends a value, or a null if it does not match the column type, e.g. an `I` value that
+    /// doesn't fit in the Int32 column of a tag first seen as `i`.
+    fn append_value(&mut self, value: &Value) {
+        use sam::record::data::field::value::Array;
+        match (self, value) {
+            (Self::Int(builder), value) => {
+                builder.append_option(value.as_int().and_then(|v| i32::try_from(v).ok()))
+            }
+            (Self::Long(builder), value) => builder.append_option(value.as_int()),
+            (Self::Float(builder), Value::Float(v)) => builder.append_value(*v),
+            (Self::Float(builder), value) => {
+                builder.append_option(value.as_int().map(|v| v as f32))
+            }
+            (Self::String(builder), Value::Character(v)) => builder.append_value(v.to_string()),
+            (Self::String(builder), Value::String(v)) => builder.append_value(v.as_str()),
+            (Self::String(builder), Value::Hex(v)) => builder.append_value(v.as_ref()),
+            (Self::IntArray(builder), Value::Array(array)) => {
+                let values = builder.values();
+                match array {
+                    Array::Int8(a) => a.iter().for_each(|v| values.append_value(i32::from(*v))),
+                    Array::UInt8(a) => a.iter().for_each(|v| values.append_value(i32::from(*v))),
+                    Array::Int16(a) => a.iter().for_each(|v| values.append_value(i32::from(*v))),
+                    Array::UInt16(a) => a.iter().for_each(|v| values.append_value(i32::from(*v))),
+                    Array::Int32(a) => values.append_slice(a),
+                    Array::UInt32(a) => a
+                        .iter()
+                        .for_each(|v| values.append_option(i32::try_from(*v).ok())),
+                    Array::Float(a) => a.iter().for_each(|v| values.append_value(*v as i32)),
+                }
+                builder.append(true);
+            }
+            (Self::LongArray(builder), Value::Array(array)) => {
+                let values = builder.values();
+                match array {
+                    Array::Int8(a) => a.iter().for_each(|v| values.append_value(i64::from(*v))),
+                    Array::UInt8(a) => a.iter().for_each(|v| values.append_value(i64::from(*v))),
+                    Array::Int16(a) => a.iter().for_each(|v| values.append_value(i64::from(*v))),
+                    Array::UInt16(a) => a.iter().for_each(|v| values.append_value(i64::from(*v))),
+                    Array::Int32(a) => a.iter().for_each(|v| values.append_value(i64::from(*v))),
+                    Array::UInt32(a) => a.iter().for_each(|v| values.append_value(i64::from(*v))),
+                    Array::Float(a) => a.iter().for_each(|v| values.append_value(*v as i64)),
+                }
+                builder.append(true);
+            }
+            (Self::FloatArray(builder), Value::Array(Array::Float(a))) => {
+                builder.values().append_slice(a);
+                builder.append(true);
+            }
+            (builder, _) => builder.append_null(),
+        }
     }
 
-    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
-        let tags = self.tags.try_finish()?;
-        RecordBatch::try_from_iter(vec![
-            // spec
+    fn append_null(&mut self) {
+        match self {
+            Self::Int(builder) => builder.append_null(),
+            Self::Long(builder) => builder.append_null(),
+            Self::Float(builder) => builder.append_null(),
+            Self::String(builder) => builder.append_null(),
+            Self::IntArray(builder) => builder.append_null(),
+            Self::LongArray(builder) => builder.append_null(),
+            Self::FloatArray(builder) => builder.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Int(builder) => Arc::new(builder.finish()),
+            Self::Long(builder) => Arc::new(builder.finish()),
+            Self::Float(builder) => Arc::new(builder.finish()),
+            Self::String(builder) => Arc::new(builder.finish()),
+            Self::IntArray(builder) => Arc::new(builder.finish()),
+            Self::LongArray(builder) => Arc::new(builder.finish()),
+            Self::FloatArray(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Builds one column per selected tag.
+///
+/// The type of each column is inferred from the first value seen for its tag.
+struct TagColumnsBuilder {
+    tags: Vec<Tag>,
+    builders: Vec<Option<TagColumnBuilder>>,
+    seen: usize,
+}
+
+impl TagColumnsBuilder {
+    pub fn new(tags: &[String]) -> Result<Self, ArrowError> {
+        let tags = tags
+            .iter()
+            .map(|tag| {
+                tag.parse::<Tag>().map_err(|_| {
+                    ArrowError::InvalidArgumentError(format!(
+                        "Invalid tag: {}. Tags must be exactly two characters.",
+                        tag
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let builders = tags.iter().map(|_| None).collect();
+        Ok(Self {
+            tags,
+            builders,
+            seen: 0,
+        })
+    }
+
+    pub fn push_tags(&mut self, data: &'_ Data) {
+        for (tag, builder) in self.tags.iter().zip(self.builders.iter_mut()) {
+            match (data.get(tag), builder) {
+                (Some(value), Some(builder)) => builder.append_value(value),
+                (Some(value), builder) => {
+                    let builder = builder.insert(TagColumnBuilder::new(value, self.seen));
+                    builder.append_value(value);
+                }
+                (None, Some(builder)) => builder.append_null(),
+                (None, None) => {}
+            }
+        }
+        self.seen += 1;
+    }
+
+    pub fn finish(&mut self) -> Vec<(String, ArrayRef)> {
+        self.tags
+            .iter()
+            .zip(self.builders.iter_mut())
+            .map(|(tag, builder)| {
+                let array = match builder {
+                    Some(builder) => builder.finish(),
+                    None => Arc::new(NullArray::new(self.seen)) as ArrayRef,
+                };
+                (tag.to_string(), array)
+            })
+            .collect()
+    }
+}
+
+/// The columns of [`BamOptions::decode_flags`], with the flag bit each one is set by.
+const FLAG_COLUMNS: [(&str, u16); 12] = [
+    ("is_paired", 0x1),
+    ("is_proper_pair", 0x2),
+    ("is_unmapped", 0x4),
+    ("mate_unmapped", 0x8),
+    ("is_reverse", 0x10),
+    ("mate_reverse", 0x20),
+    ("is_read1", 0x40),
+    ("is_read2", 0x80),
+    ("is_secondary", 0x100),
+    ("is_qc_fail", 0x200),
+    ("is_duplicate", 0x400),
+    ("is_supplementary", 0x800),
+];
+
+/// Returns the lengths of the left and right soft clips, then of the left and right hard clips,
+/// of a CIGAR. Hard clips are the outermost operations, with any soft clip inside them.
+fn clip_lengths(cigar: &sam::record::Cigar) -> [i32; 4] {
+    let ends = |ops: &mut dyn Iterator<Item = &sam::record::cigar::Op>| {
+        let mut ops = ops.peekable();
+        let mut clip = |kind| {
+            ops.next_if(|op| op.kind() == kind)
+                .map_or(0, |op| op.len() as i32)
+        };
+        let hard = clip(Kind::HardClip);
+        (clip(Kind::SoftClip), hard)
+    };
+    let (left_soft, left_hard) = ends(&mut cigar.iter());
+    let (right_soft, right_hard) = ends(&mut cigar.iter().rev());
+    [left_soft, right_soft, left_hard, right_hard]
+}
+
+impl BamBatchBuilder {
+    pub fn new(
+        capacity: usize,
+        header: &sam::Header,
+        options: &BamOptions,
+    ) -> Result<Self, ArrowError> {
+        let categories = StringArray::from(
+            header
+                .reference_sequences()
+                .iter()
+                .map(|(rs, _)| Some(rs.as_str()))
+                .collect::<Vec<_>>(),
+        );
+        let as_index = options.emit_ref_as_index.unwrap_or(false);
+        let unsigned = options.unsigned_positions.unwrap_or(false);
+        let wide = needs_wide_positions(
+            header
+                .reference_sequences()
+                .values()
+                .map(|reference_sequence| reference_sequence.length().get()),
+        );
+        let selected = |name: &str| {
+            options
+                .fields
+                .as_ref()
+                .map_or(true, |fields| fields.iter().any(|field| field == name))
+        };
+        Ok(Self {
+            qname: GenericStringBuilder::<i32>::new(),
+            flag: UInt16Array::builder(capacity),
+            rname: ReferenceColumnBuilder::new(capacity, categories.clone(), as_index)?,
+            pos: PositionBuilder::new(capacity, unsigned, wide),
+            mapq: UInt8Array::builder(capacity),
+            cigar: GenericStringBuilder::<i32>::new(),
+            rnext: ReferenceColumnBuilder::new(capacity, categories, as_index)?,
+            pnext: PositionBuilder::new(capacity, unsigned, wide),
+            tlen: Int32Array::builder(capacity),
+            seq: selected("seq").then(GenericStringBuilder::<i32>::new),
+            qual: selected("qual").then(GenericStringBuilder::<i32>::new),
+            end: PositionBuilder::new(capacity, unsigned, wide),
+            reference_length: options
+                .include_reference_length
+                .unwrap_or(false)
+                .then(|| Int32Array::builder(capacity)),
+            query_length: options
+                .include_query_length
+                .unwrap_or(false)
+                .then(|| Int32Array::builder(capacity)),
+            clip_lengths: options
+                .include_clip_lengths
+                .unwrap_or(false)
+                .then(|| std::array::from_fn(|_| Int32Array::builder(capacity))),
+            flags: options
+                .decode_flags
+                .unwrap_or(false)
+                .then(|| std::array::from_fn(|_| BooleanBuilder::with_capacity(capacity))),
+            sample: options.include_sample.unwrap_or(false).then(|| {
+                let samples = header
+                    .read_groups()
+                    .iter()
+                    .filter_map(|(id, read_group)| {
+                        Some((id.clone(), read_group.sample()?.to_string()))
+                    })
+                    .collect();
+                (samples, StringDictionaryBuilder::new())
+            }),
+            methylation: options
+                .methylation_reference
+                .as_deref()
+                .map(|path| MethylationBuilder::new(path, header))
+                .transpose()
+                .map_err(|e| ArrowError::ExternalError(e.into()))?,
+            tags: TagsBuilder::new(),
+            tag_columns: options
+                .tags
+                .as_deref()
+                .map(TagColumnsBuilder::new)
+                .transpose()?,
+            fields: options.fields.clone(),
+            coordinate_system: options
+                .coordinate_system
+                .unwrap_or(CoordinateSystem::OneBased),
+        })
+    }
+}
+
+impl BatchBuilder for BamBatchBuilder {
+    type Record<'x> = &'x sam::alignment::Record;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        self.qname.append_option(record.read_name());
+        self.flag.append_value(record.flags().bits());
+        self.rname.append(record.reference_sequence_id());
+        let start = |position: noodles::core::Position| {
+            self.coordinate_system
+                .start_from(CoordinateSystem::OneBased, position.get() as i64)
+        };
+        self.pos.append_option(record.alignment_start().map(start));
+        self.mapq
+            .append_option(record.mapping_quality().map(|x| x.get()));
+        self.cigar.append_value(record.cigar().to_string());
+        self.rnext.append(record.mate_reference_sequence_id());
+        self.pnext
+            .append_option(record.mate_alignment_start().map(start));
+        self.tlen.append_value(record.template_length());
+        if let Some(builder) = self.seq.as_mut() {
+            builder.append_value(record.sequence().to_string());
+        }
+        if let Some(builder) = self.qual.as_mut() {
+            builder.append_value(record.quality_scores().to_string());
+        }
+
+        // extra
+        self.end
+            .append_option(record.alignment_end().map(|x| x.get() as i64));
+        // Records without a CIGAR (`*`) have no lengths.
+        let cigar = record.cigar();
+        if let Some(builder) = self.reference_length.as_mut() {
+            builder.append_option((!cigar.is_empty()).then(|| cigar.alignment_span() as i32));
+        }
+        if let Some(builder) = self.query_length.as_mut() {
+            builder.append_option((!cigar.is_empty()).then(|| cigar.read_length() as i32));
+        }
+        if let Some(builders) = self.clip_lengths.as_mut() {
+            let clips = (!cigar.is_empty()).then(|| clip_lengths(cigar));
+            for (i, builder) in builders.iter_mut().enumerate() {
+                builder.append_option(clips.map(|clips| clips[i]));
+            }
+        }
+        if let Some(builders) = self.flags.as_mut() {
+            let flags = record.flags().bits();
+            for ((_, bit), builder) in FLAG_COLUMNS.iter().zip(builders.iter_mut()) {
+                builder.append_value(flags & bit != 0);
+            }
+        }
+        if let Some((samples, builder)) = self.sample.as_mut() {
+            let sample = match record
+                .data()
+                .get(&noodles::sam::record::data::field::tag::READ_GROUP)
+            {
+                Some(Value::String(id)) => samples.get(id.as_str()),
+                _ => None,
+            };
+            builder.append_option(sample);
+        }
+        if let Some(builder) = self.methylation.as_mut() {
+            builder.push(record);
+        }
+        match self.tag_columns.as_mut() {
+            Some(builder) => builder.push_tags(record.data()),
+            None => self.tags.push_tags(record.data()),
+        }
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let mut columns = vec![
+            // spec
             ("qname", Arc::new(self.qname.finish()) as ArrayRef),
             ("flag", Arc::new(self.flag.finish()) as ArrayRef),
-            ("rname", Arc::new(self.rname.finish()) as ArrayRef),
-            ("pos", Arc::new(self.pos.finish()) as ArrayRef),
+            ("rname", self.rname.finish()),
+            ("pos", self.pos.finish()?),
             ("mapq", Arc::new(self.mapq.finish()) as ArrayRef),
             ("cigar", Arc::new(self.cigar.finish()) as ArrayRef),
-            ("rnext", Arc::new(self.rnext.finish()) as ArrayRef),
-            ("pnext", Arc::new(self.pnext.finish()) as ArrayRef),
+            ("rnext", self.rnext.finish()),
+            ("pnext", self.pnext.finish()?),
             ("tlen", Arc::new(self.tlen.finish()) as ArrayRef),
-            ("seq", Arc::new(self.seq.finish()) as ArrayRef),
-            ("qual", Arc::new(self.qual.finish()) as ArrayRef),
-            ("tags", Arc::new(tags) as ArrayRef),
-            // extra
-            ("end", Arc::new(self.end.finish()) as ArrayRef),
-        ])
+        ];
+        if let Some(builder) = self.seq.as_mut() {
+            columns.push(("seq", Arc::new(builder.finish()) as ArrayRef));
+        }
+        if let Some(builder) = self.qual.as_mut() {
+            columns.push(("qual", Arc::new(builder.finish()) as ArrayRef));
+        }
+        let tag_columns = match self.tag_columns.as_mut() {
+            Some(builder) => builder.finish(),
+            None => {
+                let tags = self.tags.try_finish()?;
+                columns.push(("tags", Arc::new(tags) as ArrayRef));
+                Vec::new()
+            }
+        };
+        // extra
+        columns.push(("end", self.end.finish()?));
+        if let Some(builder) = self.reference_length.as_mut() {
+            columns.push(("reference_length", Arc::new(builder.finish()) as ArrayRef));
+        }
+        if let Some(builder) = self.query_length.as_mut() {
+            columns.push(("query_length", Arc::new(builder.finish()) as ArrayRef));
+        }
+        if let Some(builders) = self.clip_lengths.as_mut() {
+            let names = [
+                "left_soft_clip",
+                "right_soft_clip",
+                "left_hard_clip",
+                "right_hard_clip",
+            ];
+            for (name, builder) in names.into_iter().zip(builders.iter_mut()) {
+                columns.push((name, Arc::new(builder.finish()) as ArrayRef));
+            }
+        }
+        if let Some((_, builder)) = self.sample.as_mut() {
+            columns.push(("sample", Arc::new(builder.finish()) as ArrayRef));
+        }
+        if let Some(builders) = self.flags.as_mut() {
+            for ((name, _), builder) in FLAG_COLUMNS.iter().zip(builders.iter_mut()) {
+                columns.push((name, Arc::new(builder.finish()) as ArrayRef));
+            }
+        }
+        if let Some(builder) = self.methylation.as_mut() {
+            columns.extend(builder.finish());
+        }
+        columns.extend(
+            tag_columns
+                .iter()
+                .map(|(name, array)| (name.as_str(), array.clone())),
+        );
+        if let Some(fields) = self.fields.as_deref() {
+            // `seq` and `qual` are only built when selected, but are valid names all the same.
+            // They follow the nine spec columns.
+            let mut valid: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+            if self.seq.is_none() {
+                valid.insert(9, "seq");
+            }
+            if self.qual.is_none() {
+                valid.insert(10, "qual");
+            }
+            check_fields(fields, &valid)?;
+        }
+        select_fields(RecordBatch::try_from_iter(columns)?, self.fields.as_deref())
     }
 }
 
@@ -491,6 +2380,20 @@ where
     pub fn reset(&mut self) -> Option<bgzf::VirtualPosition> {
         self.reader.seek(self.vpos_lo).ok()
     }
+
+    /// Returns the records along with the virtual position at which each starts.
+    pub fn with_offsets(
+        mut self,
+    ) -> impl Iterator<Item = io::Result<(u64, sam::alignment::Record)>> + 'a
+    where
+        R: 'a,
+    {
+        std::iter::from_fn(move || {
+            let vpos = u64::from(self.reader.virtual_position());
+            self.next()
+                .map(|record| record.map(|record| (vpos, record)))
+        })
+    }
 }
 
 impl<R> Iterator for BamRecords<'_, R>
@@ -515,6 +2418,9 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::batch_builder::nullable_field;
+    use arrow::array::Array;
+    use arrow::datatypes::DataType;
     use arrow::ipc::reader::FileReader;
     use arrow::record_batch::RecordBatch;
 
@@ -547,4 +2453,1455 @@ mod tests {
         let record_batch = read_record_batch(Some("chr1:1-100000"));
         assert_eq!(record_batch.num_rows(), 2);
     }
+
+    /// Writes the records of a SAM file as BAM, with `header` if given.
+    fn sam_to_bam(name: &str, header: Option<&str>) -> Vec<u8> {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../fixtures");
+        path.push(name);
+        let mut reader = sam::Reader::new(BufReader::new(File::open(path).unwrap()));
+        let sam_header = reader.read_header().unwrap();
+        let header = header.map_or(sam_header.clone(), |header| header.parse().unwrap());
+        let mut writer = bam::Writer::new(Vec::new());
+        writer.write_header(&header).unwrap();
+        for record in reader.records(&sam_header) {
+            writer.write_record(&header, &record.unwrap()).unwrap();
+        }
+        writer.into_inner().finish().unwrap()
+    }
+
+    #[test]
+    fn test_require_sorted() {
+        let options = BamOptions {
+            require_sorted: Some(true),
+            ..Default::default()
+        };
+        let reader = |data, options| {
+            BamReader::new(std::io::Cursor::new(data), csi::Index::default())
+                .unwrap()
+                .with_options(options)
+        };
+        let sorted = sam_to_bam("sample.sam", None);
+        assert_eq!(
+            reader(sorted.clone(), options.clone()).count(None).unwrap(),
+            5
+        );
+
+        let unsorted = sam_to_bam("unsorted.sam", None);
+        assert_eq!(
+            reader(unsorted.clone(), BamOptions::default())
+                .count(None)
+                .unwrap(),
+            3
+        );
+        let error = reader(unsorted, options.clone())
+            .records_to_ipc(None)
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("record 3 (sq0:150) comes after sq1:50"));
+
+        // The header can't declare another sort order.
+        let header = "@HD\tVN:1.6\tSO:queryname\n@SQ\tSN:sq0\tLN:1000\n@SQ\tSN:sq1\tLN:1000\n";
+        let queryname = sam_to_bam("sample.sam", Some(header));
+        let error = reader(queryname, options).count(None).unwrap_err();
+        assert!(error.to_string().contains("SO:queryname"));
+    }
+
+    #[test]
+    fn test_methylation() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures");
+        let options = BamOptions {
+            methylation_reference: Some(dir.join("bisulfite.fa").to_str().unwrap().to_string()),
+            fields: Some(vec!["qname".into(), "meth_cpg".into(), "unmeth_cpg".into()]),
+            ..Default::default()
+        };
+        let mut reader = BamReader::new_from_path(dir.join("bisulfite.bam").to_str().unwrap())
+            .unwrap()
+            .with_options(options.clone());
+        let fields = reader.fields().unwrap();
+        assert_eq!(fields[1].name(), "meth_cpg");
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let column = |name| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        };
+        // Forward, reverse, reverse with `XG:Z:CT`, clipped with a deletion over a site, on a
+        // reference sequence missing from the FASTA file, and unmapped.
+        assert_eq!(
+            column("meth_cpg"),
+            [Some(2), Some(3), Some(2), Some(1), None, None]
+        );
+        assert_eq!(
+            column("unmeth_cpg"),
+            [Some(2), Some(1), Some(2), Some(0), None, None]
+        );
+
+        let options = BamOptions {
+            methylation_reference: Some(
+                dir.join("sample.plain.fa.gz").to_str().unwrap().to_string(),
+            ),
+            ..options
+        };
+        let reader = BamReader::new_from_path(dir.join("bisulfite.bam").to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        assert!(reader.fields().is_err());
+    }
+
+    #[test]
+    fn test_reuse_index() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let path = dir.to_str().unwrap();
+        let index = BamReader::new_from_path(path).unwrap().index().clone();
+        let mut reader = BamReader::new_from_path_with_index(path, index).unwrap();
+        assert_eq!(reader.count(Some("chr1:1-100000")).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_reference_lengths() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let lengths = reader.reference_lengths();
+        assert_eq!(lengths.len(), 24);
+        assert_eq!(lengths[0], ("chr1".to_string(), 249250621));
+        assert_eq!(lengths[22], ("chrM".to_string(), 16571));
+        let names: Vec<_> = lengths.into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, reader.reference_names());
+    }
+
+    #[test]
+    fn test_header_text() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/readgroups.bam");
+        let reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let text = reader.header_text();
+        assert!(text.starts_with("@HD\t"));
+        assert_eq!(
+            text.lines().filter(|line| line.starts_with("@SQ")).count(),
+            reader.header().reference_sequences().len()
+        );
+        assert_eq!(&text.parse::<sam::Header>().unwrap(), reader.header());
+    }
+
+    #[test]
+    fn test_chrom_alias() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        assert!(reader.count(Some("1")).is_err());
+        let options = BamOptions {
+            chrom_alias: Some(ChromAlias::UcscEnsembl),
+            ..Default::default()
+        };
+        let mut reader = reader.with_options(options);
+        assert_eq!(reader.count(Some("1:1-100000")).unwrap(), 2);
+        assert_eq!(reader.count(Some("chr1")).unwrap(), 4);
+        // Records keep the names of the file.
+        let ipc = reader.regions_to_ipc(&["1"]).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let rname = batch.column_by_name("rname").unwrap();
+        let rname = arrow::util::display::array_value_to_string(rname, 0).unwrap();
+        assert_eq!(rname, "chr1");
+    }
+
+    #[test]
+    fn test_count() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        for region in [None, Some("chr1"), Some("chr1:1-100000")] {
+            assert_eq!(
+                reader.count(region).unwrap(),
+                read_record_batch(region).num_rows()
+            );
+        }
+        assert_eq!(
+            reader.count_regions(&["chr1", "chr1:1-100000"]).unwrap(),
+            vec![4, 2]
+        );
+        assert!(reader.count(Some("chr1:x")).is_err());
+    }
+
+    #[test]
+    fn test_provenance() {
+        let record_batch = read_record_batch(Some("chr1:1-100000"));
+        let schema = record_batch.schema();
+        let metadata = schema.metadata();
+        assert_eq!(metadata["oxbow.format"], "bam");
+        assert_eq!(metadata["oxbow.region"], "chr1:1-100000");
+        assert!(metadata["oxbow.source_path"].ends_with("sample.bam"));
+
+        let record_batch = read_record_batch(None);
+        assert!(!record_batch
+            .schema()
+            .metadata()
+            .contains_key("oxbow.region"));
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let num_rows = |ipc: Vec<u8>| {
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader
+                .map(|batch| batch.unwrap().num_rows())
+                .sum::<usize>()
+        };
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_limit(5);
+        let regions = ["chr1", "chr1:1-100000", "chr3"];
+        assert_eq!(num_rows(reader.regions_to_ipc(&regions).unwrap()), 5);
+        for threads in [1, 3] {
+            let ipc = reader
+                .regions_to_ipc_parallel(&regions, Some(threads))
+                .unwrap();
+            assert_eq!(num_rows(ipc), 5);
+        }
+        assert_eq!(num_rows(reader.records_to_ipc(None).unwrap()), 5);
+        assert_eq!(num_rows(reader.records_to_ipc(Some("chr1")).unwrap()), 4);
+
+        // The limit applies after filtering.
+        let options = BamOptions {
+            exclude_flags: Some(0xD00),
+            ..Default::default()
+        };
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/flags.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options)
+            .with_limit(2);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let batch = FileReader::try_new(std::io::Cursor::new(ipc), None)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let qname = batch.column_by_name("qname").unwrap();
+        let qname = qname.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(qname.iter().flatten().collect::<Vec<_>>(), ["p1", "l1"]);
+    }
+
+    #[test]
+    fn test_regions_parallel() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        // Selected tags give a fixed schema; the field order of the `tags` struct is not.
+        let options = BamOptions {
+            tags: Some(vec!["NM".into(), "MD".into()]),
+            ..Default::default()
+        };
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let read = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let regions = ["chr3", "chr1:1-100000", "chr1"];
+        let sequential = read(reader.regions_to_ipc(&regions).unwrap());
+        assert_eq!(sequential.num_rows(), 8);
+        for threads in [1, 2, 8] {
+            let parallel = read(
+                reader
+                    .regions_to_ipc_parallel(&regions, Some(threads))
+                    .unwrap(),
+            );
+            assert_eq!(parallel, sequential);
+        }
+
+        let file = File::open(&dir).unwrap();
+        let index = index_from_path(dir.to_str().unwrap()).unwrap();
+        let mut reader = BamReader::new(file, index).unwrap();
+        assert!(reader.regions_to_ipc_parallel(&regions, None).is_err());
+    }
+
+    #[test]
+    fn test_coverage_windows() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/pairs.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let mut coverage = |regions: Option<&[&str]>, assignment| {
+            let ipc = reader
+                .coverage_windows(105, regions, assignment, true)
+                .unwrap();
+            let batch = FileReader::try_new(std::io::Cursor::new(ipc), None)
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            let column = |name| batch.column_by_name(name).unwrap().clone();
+            let starts = column("window_start");
+            let starts = starts.as_any().downcast_ref::<Int32Array>().unwrap();
+            let counts = column("read_count");
+            let counts = counts
+                .as_any()
+                .downcast_ref::<arrow::array::Int64Array>()
+                .unwrap();
+            let depths = column("mean_depth");
+            let depths = depths
+                .as_any()
+                .downcast_ref::<arrow::array::Float64Array>()
+                .unwrap();
+            (
+                starts.values().to_vec(),
+                counts.values().to_vec(),
+                depths
+                    .iter()
+                    .map(|d| (d.unwrap() * 105.0).round())
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        // p1 spans the first two windows, at 100-109.
+        let (starts, counts, depths) = coverage(Some(&["sq0:1-420"]), WindowAssignment::Start);
+        assert_eq!(starts, [1, 106, 211, 316]);
+        assert_eq!(counts, [1, 1, 1, 1]);
+        assert_eq!(depths, [6.0, 14.0, 10.0, 10.0]);
+        let (_, counts, _) = coverage(Some(&["sq0:1-420"]), WindowAssignment::Overlap);
+        assert_eq!(counts, [1, 2, 1, 1]);
+
+        // p1 starts before the region, so it's only counted by overlap.
+        let (starts, counts, depths) = coverage(Some(&["sq0:105-420"]), WindowAssignment::Start);
+        assert_eq!(starts, [105, 210, 315, 420]);
+        assert_eq!(counts, [1, 1, 1, 0]);
+        assert_eq!(depths, [15.0, 10.0, 10.0, 0.0]);
+        let (_, counts, _) = coverage(Some(&["sq0:105-420"]), WindowAssignment::Overlap);
+        assert_eq!(counts, [2, 1, 1, 0]);
+
+        // The last window of each reference sequence is truncated.
+        let (starts, counts, _) = coverage(None, WindowAssignment::Start);
+        assert_eq!(starts.len(), 2 * 10);
+        assert_eq!(starts[9], 946);
+        assert_eq!(counts.iter().sum::<i64>(), 9);
+
+        assert!(reader
+            .coverage_windows(0, None, WindowAssignment::Start, false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_scan_all_regions() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/pairs.bam");
+        let path = dir.to_str().unwrap();
+        let alignments = |ipc: Vec<u8>| {
+            let batch = FileReader::try_new(std::io::Cursor::new(ipc), None)
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            let column = |name| batch.column_by_name(name).unwrap().clone();
+            let (qname, flag, pos) = (column("qname"), column("flag"), column("pos"));
+            let qname = qname.as_any().downcast_ref::<StringArray>().unwrap();
+            let flag = flag.as_any().downcast_ref::<UInt16Array>().unwrap();
+            let pos = pos.as_any().downcast_ref::<Int32Array>().unwrap();
+            (0..batch.num_rows())
+                .map(|i| (qname.value(i).to_string(), flag.value(i), pos.value(i)))
+                .collect::<Vec<_>>()
+        };
+        let full: HashSet<_> = alignments(
+            BamReader::new_from_path(path)
+                .unwrap()
+                .records_to_ipc(None)
+                .unwrap(),
+        )
+        .into_iter()
+        .collect();
+
+        let mut reader = BamReader::new_from_path(path).unwrap();
+        let contigs: Vec<_> = reader
+            .scan_all_regions(None, None)
+            .unwrap()
+            .map(|ipc| alignments(ipc.unwrap()))
+            .collect();
+        assert_eq!(contigs.len(), 2);
+        assert_eq!(contigs.iter().map(Vec::len).sum::<usize>(), full.len());
+
+        for (window, step) in [(250, None), (300, Some(200))] {
+            let windows: Vec<_> = reader
+                .scan_all_regions(Some(window), step)
+                .unwrap()
+                .map(|ipc| alignments(ipc.unwrap()))
+                .collect();
+            assert_eq!(windows.len(), if step.is_some() { 10 } else { 8 });
+            let union: HashSet<_> = windows.into_iter().flatten().collect();
+            assert_eq!(union, full);
+        }
+        assert!(reader.scan_all_regions(Some(0), None).is_err());
+    }
+
+    #[test]
+    fn test_emit_ref_as_index() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/unmapped.bam");
+        let options = BamOptions {
+            emit_ref_as_index: Some(true),
+            ..Default::default()
+        };
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        assert_eq!(reader.reference_names(), ["sq0", "sq1"]);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let rname = record_batch.column_by_name("rname").unwrap();
+        let rname = rname.as_any().downcast_ref::<Int32Array>().unwrap();
+        // The unmapped mate placed with its mate keeps its reference sequence.
+        assert_eq!(
+            rname.iter().collect::<Vec<_>>(),
+            vec![Some(0), Some(0), Some(0), Some(1), None, None]
+        );
+        let rnext = record_batch.column_by_name("rnext").unwrap();
+        assert_eq!(rnext.data_type(), &DataType::Int32);
+        assert_eq!(rnext.null_count(), 4);
+    }
+
+    #[test]
+    fn test_unmapped() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/unmapped.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let read = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+
+        // The unmapped mate placed with its mapped mate is part of the region.
+        let mapped = read(reader.records_to_ipc(Some("sq0")).unwrap());
+        assert_eq!(mapped.num_rows(), 3);
+
+        let unmapped = read(reader.records_to_ipc_unmapped().unwrap());
+        assert_eq!(unmapped.num_rows(), 2);
+        let qname = unmapped.column_by_name("qname").unwrap();
+        let qname = qname.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            qname.iter().collect::<Vec<_>>(),
+            vec![Some("u1"), Some("u2")]
+        );
+        for name in ["rname", "pos", "rnext", "pnext", "end"] {
+            assert_eq!(unmapped.column_by_name(name).unwrap().null_count(), 2);
+        }
+
+        let sentinel = read(reader.records_to_ipc(Some("*")).unwrap());
+        assert_eq!(sentinel.num_rows(), 2);
+        assert_eq!(reader.count(Some("*")).unwrap(), 2);
+    }
+
+    fn fixture_reader(options: BamOptions) -> BamReader<BufReader<File>> {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options)
+    }
+
+    #[test]
+    fn test_subsample() {
+        let ipc_qnames = |ipc: Vec<u8>| {
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let mut qnames = Vec::new();
+            for batch in arrow_reader {
+                let batch = batch.unwrap();
+                let qname = batch.column_by_name("qname").unwrap();
+                let qname = qname.as_any().downcast_ref::<StringArray>().unwrap();
+                qnames.extend(qname.iter().map(|name| name.unwrap().to_string()));
+            }
+            qnames
+        };
+        let qnames = |options: BamOptions, region| {
+            ipc_qnames(fixture_reader(options).records_to_ipc(region).unwrap())
+        };
+        let all = qnames(BamOptions::default(), None);
+        let options = BamOptions {
+            sample_fraction: Some(0.5),
+            sample_seed: Some(1),
+            ..Default::default()
+        };
+        let sample = qnames(options.clone(), None);
+        assert_eq!(sample, qnames(options.clone(), None));
+        assert!(sample.len() > all.len() / 4 && sample.len() < all.len() * 3 / 4);
+        assert!(sample.iter().all(|qname| all.contains(qname)));
+        // Scans emitting offsets draw the same sample.
+        let with_offsets = BamOptions {
+            emit_offset: Some(true),
+            ..options.clone()
+        };
+        assert_eq!(qnames(with_offsets, None), sample);
+        // Counts are not subsampled.
+        assert_eq!(fixture_reader(options).count(None).unwrap(), all.len());
+
+        let options = BamOptions {
+            sample_count: Some(3),
+            ..Default::default()
+        };
+        let sample = qnames(options.clone(), Some("chr1"));
+        assert_eq!(sample, qnames(options.clone(), Some("chr1")));
+        assert_eq!(sample.len(), 3);
+        // Multi-region queries sample the records of all the regions at once.
+        let regions = ["chr1", "chr2"];
+        let sample = ipc_qnames(
+            fixture_reader(options.clone())
+                .regions_to_ipc(&regions)
+                .unwrap(),
+        );
+        assert_eq!(sample.len(), 3);
+        let parallel = fixture_reader(options.clone())
+            .regions_to_ipc_parallel(&regions, Some(2))
+            .unwrap();
+        assert_eq!(ipc_qnames(parallel), sample);
+        let limited = BamOptions {
+            sample_count: Some(10),
+            ..options.clone()
+        };
+        let mut reader = fixture_reader(limited).with_limit(2);
+        assert_eq!(
+            ipc_qnames(reader.regions_to_ipc(&regions).unwrap()).len(),
+            2
+        );
+        let unmapped = fixture_reader(BamOptions::default())
+            .count(Some("*"))
+            .unwrap();
+        assert_eq!(qnames(options, Some("*")).len(), unmapped.min(3));
+
+        let options = BamOptions {
+            sample_fraction: Some(2.0),
+            ..Default::default()
+        };
+        assert!(fixture_reader(options).records_to_ipc(None).is_err());
+    }
+
+    #[test]
+    fn test_progress() {
+        let n = fixture_reader(BamOptions::default()).count(None).unwrap() as u64;
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let size = std::fs::metadata(dir).unwrap().len();
+        for threads in [1, 4] {
+            let reports = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+            let sink = reports.clone();
+            let mut reader = fixture_reader(BamOptions::default())
+                .with_threads(threads)
+                .with_progress(2, move |records, bytes, total| {
+                    sink.borrow_mut().push((records, bytes, total))
+                });
+            reader.records_to_ipc(None).unwrap();
+            let reports = reports.borrow();
+            assert_eq!(reports.len() as u64, n / 2 + 1);
+            assert!(reports.iter().all(|&(_, _, total)| total == Some(size)));
+            assert_eq!(reports.last().unwrap().0, n);
+            assert!(reports
+                .windows(2)
+                .all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1));
+        }
+
+        // Region queries report their records without a position in the file.
+        let regions = ["chr1", "chr2"];
+        let queried = fixture_reader(BamOptions::default())
+            .count_regions(&regions)
+            .unwrap()
+            .iter()
+            .sum::<usize>() as u64;
+        for parallel in [false, true] {
+            let reports = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+            let sink = reports.clone();
+            let mut reader = fixture_reader(BamOptions::default())
+                .with_progress(2, move |records, bytes, total| {
+                    sink.borrow_mut().push((records, bytes, total))
+                });
+            if parallel {
+                reader.regions_to_ipc_parallel(&regions, Some(2)).unwrap();
+            } else {
+                reader.regions_to_ipc(&regions).unwrap();
+                assert_eq!(reports.borrow().len() as u64, queried / 2 + 1);
+            }
+            let reports = reports.borrow();
+            assert_eq!(*reports.last().unwrap(), (queried, 0, None));
+            assert!(reports.windows(2).all(|w| w[0].0 <= w[1].0));
+        }
+    }
+
+    #[test]
+    fn test_cancel() {
+        use std::sync::atomic::Ordering;
+
+        let n = fixture_reader(BamOptions::default()).count(None).unwrap();
+        for threads in [1, 4] {
+            let cancel = Arc::new(AtomicBool::new(false));
+            let (flag, mut fired) = (cancel.clone(), false);
+            // Cancels the scan after the third record, once, as if from another thread.
+            let mut reader = fixture_reader(BamOptions::default())
+                .with_threads(threads)
+                .with_cancel(cancel.clone())
+                .with_progress(3, move |_, _, _| {
+                    if !std::mem::replace(&mut fired, true) {
+                        flag.store(true, Ordering::Relaxed);
+                    }
+                });
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let rows: usize = arrow_reader.map(|batch| batch.unwrap().num_rows()).sum();
+            assert_eq!(rows, 3);
+
+            // The reader is left after the records returned.
+            cancel.store(false, Ordering::Relaxed);
+            assert_eq!(reader.count(None).unwrap(), n - 3);
+        }
+
+        // Region queries stop too, including on the threads of a parallel query.
+        let rows = |ipc: Vec<u8>| -> usize {
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.map(|batch| batch.unwrap().num_rows()).sum()
+        };
+        let (cancel, flag) = (
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let trigger = flag.clone();
+        let mut reader = fixture_reader(BamOptions::default())
+            .with_cancel(flag.clone())
+            .with_progress(3, move |_, _, _| trigger.store(true, Ordering::Relaxed));
+        assert_eq!(rows(reader.records_to_ipc(Some("chr1")).unwrap()), 3);
+        flag.store(false, Ordering::Relaxed);
+        assert_eq!(rows(reader.regions_to_ipc(&["chr1", "chr2"]).unwrap()), 3);
+        let mut reader = fixture_reader(BamOptions::default()).with_cancel(cancel.clone());
+        cancel.store(true, Ordering::Relaxed);
+        let regions = ["chr1", "chr2"];
+        assert_eq!(rows(reader.records_to_ipc(Some("chr1")).unwrap()), 0);
+        assert_eq!(rows(reader.regions_to_ipc(&regions).unwrap()), 0);
+        let parallel = reader.regions_to_ipc_parallel(&regions, Some(2)).unwrap();
+        assert_eq!(rows(parallel), 0);
+        assert_eq!(rows(reader.records_to_ipc(Some("*")).unwrap()), 0);
+        assert_eq!(reader.count(Some("chr1")).unwrap(), 0);
+        let mut reader = fixture_reader(BamOptions {
+            emit_offset: Some(true),
+            ..Default::default()
+        })
+        .with_cancel(cancel);
+        assert_eq!(rows(reader.records_to_ipc(None).unwrap()), 0);
+    }
+
+    #[test]
+    fn test_threads() {
+        // Tag columns are selected, since the fields of the `tags` struct are in no set order.
+        let options = BamOptions {
+            tags: Some(vec!["NM".into(), "MD".into()]),
+            ..Default::default()
+        };
+        let batches = |ipc: Vec<u8>| {
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.map(|batch| batch.unwrap()).collect::<Vec<_>>()
+        };
+        let single = batches(
+            fixture_reader(options.clone())
+                .records_to_ipc(None)
+                .unwrap(),
+        );
+        for threads in [2, 4] {
+            let mut reader = fixture_reader(options.clone()).with_threads(threads);
+            assert_eq!(batches(reader.records_to_ipc(None).unwrap()), single);
+            // The reader is left at the end of the file, as with a single thread.
+            assert_eq!(reader.count(None).unwrap(), 0);
+        }
+        let n = fixture_reader(BamOptions::default()).count(None).unwrap();
+        let mut reader = fixture_reader(BamOptions::default()).with_threads(4);
+        assert_eq!(reader.count(None).unwrap(), n);
+
+        // A limited scan stops at the same record, so a region query still works afterwards.
+        let mut reader = fixture_reader(options).with_threads(4).with_limit(3);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        assert_eq!(arrow_reader.next().unwrap().unwrap().num_rows(), 3);
+        assert_eq!(
+            reader.count(Some("chr1")).unwrap(),
+            fixture_reader(BamOptions::default())
+                .count(Some("chr1"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_scan_with() {
+        use arrow::array::{UInt16Array, UInt8Array};
+
+        let batch = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let flags = |batch: &RecordBatch| {
+            let flag = batch.column_by_name("flag").unwrap();
+            let flag = flag.as_any().downcast_ref::<UInt16Array>().unwrap();
+            flag.values().to_vec()
+        };
+        let all = flags(&read_record_batch(None));
+        let n_reverse = all.iter().filter(|&flag| flag & 0x10 != 0).count();
+        assert!(n_reverse > 0 && n_reverse < all.len());
+
+        let ipc = fixture_reader(BamOptions::default())
+            .scan_with(None, |record| record.flags().is_reverse_complemented())
+            .unwrap();
+        let reverse = flags(&batch(ipc));
+        assert_eq!(reverse.len(), n_reverse);
+        assert!(reverse.iter().all(|flag| flag & 0x10 != 0));
+
+        // Records are filtered by the options before the hook, and can be changed by it.
+        let options = BamOptions {
+            exclude_flags: Some(0x10),
+            ..Default::default()
+        };
+        let ipc = fixture_reader(options)
+            .scan_map(Some("chr1"), |mut record| {
+                *record.mapping_quality_mut() = None;
+                Some(record)
+            })
+            .unwrap();
+        let batch = batch(ipc);
+        assert!(flags(&batch).iter().all(|flag| flag & 0x10 == 0));
+        let mapq = batch.column_by_name("mapq").unwrap();
+        let mapq = mapq.as_any().downcast_ref::<UInt8Array>().unwrap();
+        assert!(!mapq.is_empty());
+        assert_eq!(mapq.null_count(), mapq.len());
+    }
+
+    #[test]
+    fn test_tag_columns() {
+        let options = BamOptions {
+            tags: Some(vec!["NM".into(), "X0".into(), "MD".into(), "ZZ".into()]),
+            ..Default::default()
+        };
+        let ipc = fixture_reader(options).records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let schema = record_batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(&names[11..], &["end", "NM", "X0", "MD", "ZZ"]);
+        assert_eq!(
+            schema.field_with_name("NM").unwrap().data_type(),
+            &DataType::Int32
+        );
+        assert_eq!(
+            schema.field_with_name("MD").unwrap().data_type(),
+            &DataType::Utf8
+        );
+        assert_eq!(
+            schema.field_with_name("ZZ").unwrap().data_type(),
+            &DataType::Null
+        );
+        let x0 = record_batch.column_by_name("X0").unwrap();
+        assert_eq!(x0.null_count(), 5);
+    }
+
+    #[test]
+    fn test_tag_column_types() {
+        use sam::record::data::field::value::Array;
+        use sam::record::data::field::Tag;
+
+        let tags = ["NM", "XI", "ZB", "ZL"].map(String::from);
+        let mut builder = TagColumnsBuilder::new(&tags).unwrap();
+        let tag = |s: &str| s.parse::<Tag>().unwrap();
+        let data = |nm, xi| -> Data {
+            [
+                (tag("NM"), nm),
+                (tag("XI"), xi),
+                (tag("ZB"), Value::Array(Array::UInt8(vec![1, 2]))),
+                (tag("ZL"), Value::Array(Array::UInt32(vec![u32::MAX]))),
+            ]
+            .into_iter()
+            .collect()
+        };
+        builder.push_tags(&data(Value::UInt8(1), Value::UInt32(u32::MAX)));
+        builder.push_tags(&data(Value::UInt32(u32::MAX), Value::Int8(-1)));
+        let columns: Vec<_> = builder.finish().into_iter().map(|(_, c)| c).collect();
+        let item = |ty| DataType::List(Arc::new(Field::new("item", ty, true)));
+        assert_eq!(columns[0].data_type(), &DataType::Int32);
+        assert_eq!(columns[1].data_type(), &DataType::Int64);
+        assert_eq!(columns[2].data_type(), &item(DataType::Int32));
+        assert_eq!(columns[3].data_type(), &item(DataType::Int64));
+
+        // An `I` value that doesn't fit in the Int32 column is null.
+        let nm = columns[0].as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(nm.iter().collect::<Vec<_>>(), [Some(1), None]);
+        let xi = columns[1]
+            .as_any()
+            .downcast_ref::<arrow::array::Int64Array>()
+            .unwrap();
+        assert_eq!(
+            xi.iter().collect::<Vec<_>>(),
+            [Some(i64::from(u32::MAX)), Some(-1)]
+        );
+    }
+
+    #[test]
+    fn test_fields() {
+        let options = BamOptions {
+            tags: Some(vec!["NM".into()]),
+            fields: Some(vec!["NM".into(), "pos".into(), "qname".into()]),
+            ..Default::default()
+        };
+        let ipc = fixture_reader(options).records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let schema = record_batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["NM", "pos", "qname"]);
+        assert_eq!(record_batch.num_rows(), 6);
+
+        let options = BamOptions {
+            fields: Some(vec!["qname".into(), "NM".into()]),
+            ..Default::default()
+        };
+        let error = fixture_reader(options).records_to_ipc(None).unwrap_err();
+        assert!(error.to_string().contains("Invalid field: NM"));
+        assert!(error.to_string().contains("qname, flag, rname"));
+    }
+
+    #[test]
+    fn test_fields_skip_sequence() {
+        let fields: Vec<String> = vec!["qname".into(), "flag".into(), "pos".into()];
+        let options = BamOptions {
+            fields: Some(fields.clone()),
+            ..Default::default()
+        };
+        let reader = fixture_reader(options.clone());
+        let builder = BamBatchBuilder::new(0, &reader.header, &options).unwrap();
+        assert!(builder.seq.is_none() && builder.qual.is_none());
+        let options = BamOptions {
+            fields: Some(vec!["qual".into()]),
+            ..options
+        };
+        let builder = BamBatchBuilder::new(0, &reader.header, &options).unwrap();
+        assert!(builder.seq.is_none() && builder.qual.is_some());
+
+        let read = |options| {
+            let ipc = fixture_reader(options).records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let selected = read(BamOptions {
+            fields: Some(fields.clone()),
+            ..Default::default()
+        });
+        let all = read(BamOptions::default());
+        let indices: Vec<_> = fields
+            .iter()
+            .map(|name| all.schema().index_of(name).unwrap())
+            .collect();
+        assert_eq!(selected, all.project(&indices).unwrap());
+
+        // The valid names include the skipped `seq` and `qual` columns.
+        let error = fixture_reader(BamOptions {
+            fields: Some(vec!["qname".into(), "sequence".into()]),
+            ..Default::default()
+        })
+        .records_to_ipc(None)
+        .unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("Invalid field: sequence"));
+        assert!(message.contains("tlen, seq, qual, tags, end"));
+    }
+
+    #[test]
+    fn test_fields_lazy_records() {
+        let fields: Vec<String> = ["qname", "flag", "rname", "pos", "mapq", "cigar", "end"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let options = BamOptions {
+            fields: Some(fields.clone()),
+            ..Default::default()
+        };
+        assert!(!options.decodes_sequence());
+        let read = |options: BamOptions, threads| {
+            let mut reader = fixture_reader(options).with_threads(threads);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let batches: Vec<_> = FileReader::try_new(std::io::Cursor::new(ipc), None)
+                .unwrap()
+                .map(|batch| batch.unwrap())
+                .collect();
+            arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap()
+        };
+        let all = read(BamOptions::default(), 1);
+        let indices: Vec<_> = fields
+            .iter()
+            .map(|name| all.schema().index_of(name).unwrap())
+            .collect();
+        let expected = all.project(&indices).unwrap();
+        for threads in [1, 2] {
+            let lazy = read(options.clone(), threads);
+            assert_eq!(lazy.schema().fields(), expected.schema().fields());
+            assert_eq!(lazy.num_rows(), expected.num_rows());
+            assert_eq!(lazy.columns(), expected.columns());
+        }
+        assert_eq!(
+            fixture_reader(options.clone()).count(None).unwrap(),
+            expected.num_rows()
+        );
+
+        // Predicates see the records without their sequence.
+        let mut reader = fixture_reader(options);
+        reader
+            .scan_with(None, |record| {
+                assert!(record.sequence().is_empty() && record.quality_scores().is_empty());
+                true
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_lengths() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/cigar.bam");
+        let options = BamOptions {
+            include_reference_length: Some(true),
+            include_query_length: Some(true),
+            ..Default::default()
+        };
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let schema = record_batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(&names[12..], &["end", "reference_length", "query_length"]);
+        let column = |name| {
+            let array = record_batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<Int32Array>().unwrap();
+            array.iter().collect::<Vec<_>>()
+        };
+        // 5M2I5M, 3S5M3D5M, 5M100N5M, 2H10M2S and an unmapped read without a CIGAR.
+        assert_eq!(
+            column("reference_length"),
+            [Some(10), Some(13), Some(110), Some(10), None]
+        );
+        assert_eq!(
+            column("query_length"),
+            [Some(12), Some(13), Some(10), Some(12), None]
+        );
+        assert_eq!(
+            column("end"),
+            [Some(19), Some(32), Some(139), Some(209), None]
+        );
+    }
+
+    #[test]
+    fn test_decode_flags() {
+        use arrow::array::{Array, BooleanArray};
+        use noodles::sam::record::Flags;
+
+        let options = BamOptions {
+            decode_flags: Some(true),
+            ..Default::default()
+        };
+        let mut batch_builder = BamBatchBuilder::new(4, &sam::Header::default(), &options).unwrap();
+        // Unpaired and unmapped, a proper pair's reverse first read, a mate of it marked as a
+        // duplicate, and a supplementary alignment failing QC.
+        let flags = [0x4, 0x63, 0x493, 0xa00];
+        for bits in flags {
+            let record = sam::alignment::Record::builder()
+                .set_flags(Flags::from_bits_truncate(bits))
+                .build();
+            batch_builder.push(&record);
+        }
+        let batch = batch_builder.finish().unwrap();
+        let decoded: Vec<u16> = (0..flags.len())
+            .map(|i| {
+                FLAG_COLUMNS.iter().fold(0, |bits, (name, bit)| {
+                    let column = batch.column_by_name(name).unwrap();
+                    let column = column.as_any().downcast_ref::<BooleanArray>().unwrap();
+                    assert_eq!(column.null_count(), 0);
+                    if column.value(i) {
+                        bits | bit
+                    } else {
+                        bits
+                    }
+                })
+            })
+            .collect();
+        assert_eq!(decoded, flags);
+        let is_read1 = batch.column_by_name("is_read1").unwrap();
+        let is_read1 = is_read1.as_any().downcast_ref::<BooleanArray>().unwrap();
+        assert_eq!(
+            is_read1.iter().collect::<Vec<_>>(),
+            [Some(false), Some(true), Some(false), Some(false)]
+        );
+        assert!(batch.column_by_name("flag").is_some());
+        assert_eq!(
+            batch.schema().fields().last().unwrap().name(),
+            "is_supplementary"
+        );
+    }
+
+    #[test]
+    fn test_clip_lengths() {
+        let read_clips = |fixture: &str, region| {
+            let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            dir.push(fixture);
+            let options = BamOptions {
+                include_clip_lengths: Some(true),
+                ..Default::default()
+            };
+            let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_options(options);
+            let ipc = reader.records_to_ipc(region).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let record_batch = arrow_reader.next().unwrap().unwrap();
+            let schema = record_batch.schema();
+            let names: Vec<_> = schema.fields().iter().map(|f| f.name().clone()).collect();
+            assert_eq!(
+                &names[12..],
+                &[
+                    "end",
+                    "left_soft_clip",
+                    "right_soft_clip",
+                    "left_hard_clip",
+                    "right_hard_clip"
+                ]
+            );
+            names[13..]
+                .iter()
+                .map(|name| {
+                    let array = record_batch.column_by_name(name).unwrap();
+                    let array = array.as_any().downcast_ref::<Int32Array>().unwrap();
+                    array.iter().collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        // 5M2I5M, 3S5M3D5M, 5M100N5M, 2H10M2S and an unmapped read without a CIGAR.
+        assert_eq!(
+            read_clips("../fixtures/cigar.bam", None),
+            [
+                [Some(0), Some(3), Some(0), Some(0), None],
+                [Some(0), Some(0), Some(0), Some(2), None],
+                [Some(0), Some(0), Some(0), Some(2), None],
+                [Some(0), Some(0), Some(0), Some(0), None],
+            ]
+        );
+        // The supplementary alignment of p2, 3H5M2H.
+        assert_eq!(
+            read_clips("../fixtures/pairs.bam", Some("sq0:500-500")),
+            [[Some(0)], [Some(0)], [Some(3)], [Some(2)]]
+        );
+    }
+
+    #[test]
+    fn test_pileup() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/cigar.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let ipc = reader.pileup_to_ipc(&["sq0:24-31"]).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let column = |name| {
+            let array = record_batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<Int32Array>().unwrap();
+            array.values().to_vec()
+        };
+        // 3S5M3D5M at 20 and 5M100N5M at 30: a deletion at 25-27, then both reads at 30-31.
+        assert_eq!(column("pos"), [24, 25, 26, 27, 28, 29, 30, 31]);
+        assert_eq!(column("depth"), [1, 1, 1, 1, 1, 1, 2, 2]);
+        assert_eq!(column("del"), [0, 1, 1, 1, 0, 0, 0, 0]);
+        assert_eq!(column("a"), [0, 0, 0, 0, 1, 0, 1, 0]);
+        assert_eq!(column("c"), [0, 0, 0, 0, 0, 1, 0, 1]);
+        assert_eq!(column("g"), [0, 0, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(column("t"), [1, 0, 0, 0, 0, 0, 0, 1]);
+
+        // Insertions, clips and the skipped 35-134 are not covered.
+        let ipc = reader.pileup_to_ipc(&["sq0"]).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(record_batch.num_rows(), 40);
+        let pos = record_batch.column_by_name("pos").unwrap();
+        let pos = pos.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert!(!pos.values().contains(&100));
+        assert_eq!(pos.values().last(), Some(&209));
+    }
+
+    #[test]
+    fn test_pairs() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/pairs.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let read_pairs = |reader: &mut BamReader<_>, region| {
+            let ipc = reader.pairs_to_ipc(region, None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let ints = |batch: &RecordBatch, name| {
+            let array = batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<Int32Array>().unwrap();
+            array.iter().collect::<Vec<_>>()
+        };
+
+        // The supplementary alignment of p2 and the secondary one of p1 are skipped, and o1 has
+        // its mate on sq1.
+        let batch = read_pairs(&mut reader, None);
+        let qname = batch.column_by_name("qname").unwrap();
+        let qname = qname.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            qname.iter().collect::<Vec<_>>(),
+            [Some("p1"), Some("p2"), Some("u1"), Some("o1")]
+        );
+        assert_eq!(
+            ints(&batch, "pos1"),
+            [Some(100), Some(150), Some(700), Some(600)]
+        );
+        assert_eq!(
+            ints(&batch, "pos2"),
+            [Some(300), Some(400), None, Some(100)]
+        );
+        assert_eq!(
+            ints(&batch, "tlen"),
+            [Some(210), Some(260), Some(0), Some(0)]
+        );
+        assert_eq!(
+            ints(&batch, "fragment_length"),
+            [Some(210), Some(260), None, None]
+        );
+        let proper_pair = batch.column_by_name("proper_pair").unwrap();
+        let proper_pair = proper_pair
+            .as_any()
+            .downcast_ref::<arrow::array::BooleanArray>()
+            .unwrap();
+        assert_eq!(
+            proper_pair.iter().collect::<Vec<_>>(),
+            [Some(true), Some(true), Some(false), Some(false)]
+        );
+
+        // Within sq0, o1 is an orphan.
+        let batch = read_pairs(&mut reader, Some("sq0"));
+        assert_eq!(
+            ints(&batch, "pos1"),
+            [Some(100), Some(150), Some(700), Some(600)]
+        );
+        assert_eq!(ints(&batch, "pos2"), [Some(300), Some(400), None, None]);
+        assert_eq!(batch.column_by_name("mapq2").unwrap().null_count(), 2);
+
+        // A read waiting for p1/2 and one waiting for p2/2 exceed a buffer of one read.
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        assert!(reader.pairs_to_ipc(None, Some(1)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_tag() {
+        let options = BamOptions {
+            tags: Some(vec!["NMX".into()]),
+            ..Default::default()
+        };
+        assert!(fixture_reader(options).records_to_ipc(None).is_err());
+    }
+
+    fn filtered_qnames(options: BamOptions, region: Option<&str>) -> Vec<String> {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/flags.bam");
+        let reader = || {
+            BamReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_options(options.clone())
+        };
+        let count = reader().count(region).unwrap();
+        let ipc = reader().records_to_ipc(region).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), count);
+        let qname = batch.column_by_name("qname").unwrap();
+        let qname = qname.as_any().downcast_ref::<StringArray>().unwrap();
+        qname.iter().map(|name| name.unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        // Secondary, duplicate and supplementary.
+        let exclude_flags = Some(0x100 | 0x400 | 0x800);
+        let options = BamOptions {
+            exclude_flags,
+            ..Default::default()
+        };
+        assert_eq!(filtered_qnames(options, None), ["p1", "l1", "p1", "m1"]);
+
+        // A missing mapping quality (255) passes.
+        let options = BamOptions {
+            min_mapq: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(filtered_qnames(options, None), ["p1", "d1", "p1", "m1"]);
+
+        let options = BamOptions {
+            include_flags: Some(0x1),
+            exclude_flags,
+            min_mapq: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(filtered_qnames(options, None), ["p1", "p1"]);
+
+        let options = BamOptions {
+            exclude_flags,
+            ..Default::default()
+        };
+        assert_eq!(filtered_qnames(options, Some("sq0:1-35")), ["p1"]);
+    }
+
+    #[test]
+    fn test_query_mode() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/flags.bam");
+        let path = dir.to_str().unwrap();
+        let qnames = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let batch = arrow_reader.next().unwrap().unwrap();
+            let qname = batch.column_by_name("qname").unwrap();
+            let qname = qname.as_any().downcast_ref::<StringArray>().unwrap();
+            qname
+                .iter()
+                .map(|name| name.unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        // The reads at 10-19 and 40-49 straddle the bounds.
+        let region = "sq0:15-45";
+        let mut reader = BamReader::new_from_path(path).unwrap();
+        assert_eq!(
+            qnames(reader.records_to_ipc(Some(region)).unwrap()),
+            ["p1", "s1", "d1", "l1"]
+        );
+        let mut reader = BamReader::new_from_path(path)
+            .unwrap()
+            .with_query_mode(QueryMode::Contained);
+        assert_eq!(
+            qnames(reader.records_to_ipc(Some(region)).unwrap()),
+            ["s1", "d1"]
+        );
+        assert_eq!(reader.count(Some(region)).unwrap(), 2);
+        // A read ending on the bound is contained.
+        let regions = [region, "sq0:20-29"];
+        let sequential = qnames(reader.regions_to_ipc(&regions).unwrap());
+        assert_eq!(sequential, ["s1", "d1", "s1"]);
+        let parallel = qnames(reader.regions_to_ipc_parallel(&regions, Some(2)).unwrap());
+        assert_eq!(parallel, sequential);
+    }
+
+    /// Returns the names and types of a list of fields, with nested fields made nullable.
+    fn name_types(fields: &arrow::datatypes::Fields) -> Vec<(String, DataType)> {
+        fields
+            .iter()
+            .map(|field| nullable_field(field))
+            .map(|field| (field.name().clone(), field.data_type().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_fields_match_records() {
+        let options = BamOptions {
+            fields: Some(vec![
+                "qname".into(),
+                "rname".into(),
+                "end".into(),
+                "query_length".into(),
+            ]),
+            include_query_length: Some(true),
+            ..Default::default()
+        };
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let fields = reader.fields().unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(
+            name_types(&fields),
+            name_types(record_batch.schema().fields())
+        );
+    }
+
+    #[test]
+    fn test_peek_schema() {
+        let options = BamOptions {
+            tags: Some(vec!["NM".into(), "MD".into()]),
+            include_query_length: Some(true),
+            ..Default::default()
+        };
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let path = dir.to_str().unwrap();
+        let schema = peek_schema(path, &options, 100).unwrap();
+        let record_batch = fixture_reader(options.clone())
+            .records_to_ipc(None)
+            .unwrap();
+        let mut arrow_reader =
+            FileReader::try_new(std::io::Cursor::new(record_batch), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(
+            name_types(schema.fields()),
+            name_types(record_batch.schema().fields())
+        );
+
+        // Without records, tag columns are Null, as in `fields`.
+        let schema = peek_schema(path, &options, 0).unwrap();
+        let fields = fixture_reader(options).fields().unwrap();
+        assert_eq!(name_types(schema.fields()), name_types(&fields));
+        assert_eq!(
+            schema.field_with_name("NM").unwrap().data_type(),
+            &DataType::Null
+        );
+    }
+
+    #[test]
+    fn test_multi_reader() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let path = dir.to_str().unwrap();
+        let mut reader = BamMultiReader::new_from_paths(&[path, path])
+            .unwrap()
+            .with_source(true);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let rows = read_record_batch(None).num_rows();
+        assert_eq!(record_batch.num_rows(), 2 * rows);
+        let source = record_batch.column_by_name("source").unwrap();
+        let source = arrow::compute::cast(source, &DataType::Utf8).unwrap();
+        let source = source.as_any().downcast_ref::<StringArray>().unwrap();
+        assert!(source.iter().all(|s| s == Some(path)));
+        assert_eq!(reader.count(Some("chr1")).unwrap(), 8);
+
+        let mut other = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        other.push("../fixtures/cigar.bam");
+        let result = BamMultiReader::new_from_paths(&[path, other.to_str().unwrap()]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_read_groups() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/readgroups.bam");
+        let options = BamOptions {
+            include_sample: Some(true),
+            ..Default::default()
+        };
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+
+        let read_groups = reader.read_groups();
+        assert_eq!(read_groups.len(), 2);
+        assert_eq!(
+            read_groups[1],
+            BTreeMap::from(
+                [("ID", "rg2"), ("SM", "bob"), ("PL", "ONT"), ("LB", "lib2")]
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+            )
+        );
+        let programs = reader.programs();
+        assert_eq!(programs.len(), 2);
+        assert_eq!(programs[0]["CL"], "bwa mem ref.fa r1.fq r2.fq");
+        assert_eq!(programs[1]["PP"], "bwa");
+
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let sample = record_batch.column_by_name("sample").unwrap();
+        let sample = arrow::compute::cast(sample, &DataType::Utf8).unwrap();
+        let sample = sample.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            sample.iter().collect::<Vec<_>>(),
+            [Some("alice"), Some("bob"), Some("alice"), None, None]
+        );
+    }
+
+    #[test]
+    fn test_emit_offset() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let options = BamOptions {
+            emit_offset: Some(true),
+            ..Default::default()
+        };
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let fields = reader.fields().unwrap();
+        assert_eq!(fields.last().unwrap().name(), "vpos");
+
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let vpos = record_batch
+            .column_by_name("vpos")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::UInt64Array>()
+            .unwrap();
+        let qname = record_batch.column_by_name("qname").unwrap();
+
+        // Each offset reads back the record it was emitted for.
+        for (i, vpos) in vpos.values().iter().enumerate() {
+            let lo = bgzf::VirtualPosition::from(*vpos).into();
+            let hi = bgzf::VirtualPosition::from(vpos + 1).into();
+            let ipc = reader.records_to_ipc_from_vpos(lo, hi).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let batch = arrow_reader.next().unwrap().unwrap();
+            assert_eq!(batch.num_rows(), 1);
+            assert_eq!(batch.column_by_name("qname").unwrap(), &qname.slice(i, 1));
+        }
+
+        assert!(reader.records_to_ipc(Some("sq0")).is_err());
+        assert!(reader.regions_to_ipc(&["sq0"]).is_err());
+    }
+
+    #[test]
+    fn test_scan_from() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let options = BamOptions {
+            emit_offset: Some(true),
+            ..Default::default()
+        };
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let read_batch = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let record_batch = read_batch(reader.records_to_ipc(None).unwrap());
+        let vpos = record_batch
+            .column_by_name("vpos")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::UInt64Array>()
+            .unwrap()
+            .value(2);
+
+        let tail = read_batch(reader.scan_from(vpos).unwrap());
+        let expected = record_batch.slice(2, record_batch.num_rows() - 2);
+        for name in ["qname", "pos", "vpos"] {
+            assert_eq!(
+                tail.column_by_name(name).unwrap(),
+                expected.column_by_name(name).unwrap()
+            );
+        }
+
+        assert!(reader.scan_from(0).is_err());
+        assert!(reader.scan_from(vpos + 1).is_err());
+    }
 }
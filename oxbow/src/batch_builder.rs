@@ -1,11 +1,333 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, GenericStringBuilder, Int32Builder, StringArray, StringDictionaryBuilder,
+    UInt64Builder,
+};
+use arrow::datatypes::{DataType, Field, Fields, Int32Type, Schema};
 use arrow::error::ArrowError;
 use arrow::ipc::writer::FileWriter;
 use arrow::record_batch::RecordBatch;
 
+use crate::stats::write_stats;
+
 pub trait BatchBuilder {
     type Record<'a>;
     fn push(&mut self, record: Self::Record<'_>);
     fn finish(self) -> Result<RecordBatch, ArrowError>;
+
+    /// Attaches schema-level metadata to the finished batch.
+    fn with_metadata(self, metadata: HashMap<String, String>) -> WithMetadata<Self>
+    where
+        Self: Sized,
+    {
+        WithMetadata {
+            inner: self,
+            metadata,
+        }
+    }
+}
+
+/// Returns the fields of the batches built by `builder`, by finishing it without records.
+///
+/// Whether a column of a batch is nullable depends on its records, so all fields are nullable.
+pub(crate) fn builder_fields(builder: impl BatchBuilder) -> Result<Fields, ArrowError> {
+    let batch = builder.finish()?;
+    Ok(batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| nullable_field(field))
+        .collect())
+}
+
+/// Returns `field` made nullable, along with the struct fields and list items nested in it.
+pub(crate) fn nullable_field(field: &Field) -> Field {
+    let data_type = match field.data_type() {
+        DataType::Struct(fields) => {
+            DataType::Struct(fields.iter().map(|field| nullable_field(field)).collect())
+        }
+        DataType::List(item) => DataType::List(Arc::new(nullable_field(item))),
+        data_type => data_type.clone(),
+    };
+    field.clone().with_data_type(data_type).with_nullable(true)
+}
+
+/// A batch builder whose batches carry schema-level metadata.
+pub struct WithMetadata<B> {
+    inner: B,
+    metadata: HashMap<String, String>,
+}
+
+impl<B: BatchBuilder> BatchBuilder for WithMetadata<B> {
+    type Record<'a> = B::Record<'a>;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        self.inner.push(record)
+    }
+
+    fn finish(self) -> Result<RecordBatch, ArrowError> {
+        let batch = self.inner.finish()?;
+        let schema = batch.schema().as_ref().clone().with_metadata(self.metadata);
+        batch.with_schema(Arc::new(schema))
+    }
+}
+
+/// A batch builder that appends a `vpos` column (UInt64) with the offset at which each record
+/// starts in its file, pushed along with the record.
+pub(crate) struct WithOffsets<B, T> {
+    inner: B,
+    vpos: UInt64Builder,
+    record: PhantomData<T>,
+}
+
+impl<B, T> WithOffsets<B, T> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            vpos: UInt64Builder::new(),
+            record: PhantomData,
+        }
+    }
+}
+
+impl<B, T> BatchBuilder for WithOffsets<B, T>
+where
+    B: for<'a> BatchBuilder<Record<'a> = &'a T>,
+    T: 'static,
+{
+    type Record<'a> = &'a (u64, T);
+
+    fn push(&mut self, (vpos, record): Self::Record<'_>) {
+        self.inner.push(record);
+        self.vpos.append_value(*vpos);
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let batch = self.inner.finish()?;
+        let mut fields = batch.schema().fields().to_vec();
+        fields.push(Arc::new(Field::new("vpos", DataType::UInt64, false)));
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(self.vpos.finish()));
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+    }
+}
+
+/// Fails if offsets are requested for a region query. Only scans of a whole file or of a virtual
+/// position range track where each record starts.
+pub(crate) fn check_no_offsets(emit_offset: Option<bool>) -> Result<(), ArrowError> {
+    if emit_offset.unwrap_or(false) {
+        return Err(ArrowError::InvalidArgumentError(
+            "Offsets can only be emitted when scanning the whole file or a virtual position range, not a region.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns schema metadata recording the provenance of a batch.
+///
+/// The keys are `oxbow.format`, `oxbow.version`, and, when known, `oxbow.source_path` and
+/// `oxbow.region`. Multiple regions are joined with commas.
+pub fn provenance(
+    format: &str,
+    source_path: Option<&Path>,
+    regions: &[&str],
+) -> HashMap<String, String> {
+    let mut metadata = HashMap::from([
+        ("oxbow.format".to_string(), format.to_string()),
+        (
+            "oxbow.version".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+    ]);
+    if let Some(path) = source_path {
+        metadata.insert(
+            "oxbow.source_path".to_string(),
+            path.to_string_lossy().into_owned(),
+        );
+    }
+    if !regions.is_empty() {
+        metadata.insert("oxbow.region".to_string(), regions.join(","));
+    }
+    metadata
+}
+
+/// A builder for a string column, optionally dictionary-encoded.
+///
+/// Dictionary encoding (`Dictionary<Int32, Utf8>`) saves memory and space for repetitive columns
+/// such as chromosome names, strands, sources and feature types.
+pub(crate) enum StringColumnBuilder {
+    Plain(GenericStringBuilder<i32>),
+    Dictionary(StringDictionaryBuilder<Int32Type>),
+}
+
+impl StringColumnBuilder {
+    /// Creates a builder for the column `name`, dictionary-encoded if it is one of
+    /// `dictionary_encode`.
+    pub fn new(name: &str, dictionary_encode: Option<&[String]>) -> Self {
+        if dictionary_encode.is_some_and(|names| names.iter().any(|n| n == name)) {
+            Self::Dictionary(StringDictionaryBuilder::<Int32Type>::new())
+        } else {
+            Self::Plain(GenericStringBuilder::<i32>::new())
+        }
+    }
+
+    pub fn append_value(&mut self, value: impl AsRef<str>) {
+        match self {
+            Self::Plain(builder) => builder.append_value(value),
+            Self::Dictionary(builder) => builder.append_value(value),
+        }
+    }
+
+    pub fn append_option(&mut self, value: Option<impl AsRef<str>>) {
+        match self {
+            Self::Plain(builder) => builder.append_option(value),
+            Self::Dictionary(builder) => builder.append_option(value),
+        }
+    }
+
+    pub fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Plain(builder) => Arc::new(builder.finish()),
+            Self::Dictionary(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// A builder for a column of reference sequence names, from their index in a header.
+///
+/// Names are dictionary-encoded over the header's reference sequences. Alternatively, the index
+/// itself can be emitted as an Int32 column, to be mapped back with the header's name table.
+/// Indices missing from the header give a null.
+pub(crate) struct ReferenceColumnBuilder {
+    names: StringArray,
+    inner: ReferenceColumn,
+}
+
+enum ReferenceColumn {
+    Name(StringDictionaryBuilder<Int32Type>),
+    Index(Int32Builder),
+}
+
+impl ReferenceColumnBuilder {
+    pub fn new(capacity: usize, names: StringArray, as_index: bool) -> Result<Self, ArrowError> {
+        let inner = if as_index {
+            ReferenceColumn::Index(Int32Builder::with_capacity(capacity))
+        } else {
+            ReferenceColumn::Name(StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
+                capacity, &names,
+            )?)
+        };
+        Ok(Self { names, inner })
+    }
+
+    pub fn append(&mut self, index: Option<usize>) {
+        let index = index.filter(|&i| i < self.names.len());
+        match &mut self.inner {
+            ReferenceColumn::Name(builder) => {
+                builder.append_option(index.map(|i| self.names.value(i)))
+            }
+            ReferenceColumn::Index(builder) => builder.append_option(index.map(|i| i as i32)),
+        }
+    }
+
+    /// Appends a reference sequence given by name, which need not be in the header.
+    ///
+    /// Names missing from the header are added to the dictionary, or give a null index.
+    pub fn append_name(&mut self, name: &str, index: Option<usize>) {
+        match &mut self.inner {
+            ReferenceColumn::Name(builder) => builder.append_value(name),
+            ReferenceColumn::Index(_) => self.append(index),
+        }
+    }
+
+    pub fn finish(&mut self) -> ArrayRef {
+        match &mut self.inner {
+            ReferenceColumn::Name(builder) => Arc::new(builder.finish()),
+            ReferenceColumn::Index(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Checks that the columns to dictionary-encode are among those that support it.
+pub(crate) fn check_dictionary_encode(
+    dictionary_encode: Option<&[String]>,
+    valid: &[&str],
+) -> Result<(), ArrowError> {
+    for name in dictionary_encode.unwrap_or_default() {
+        if !valid.contains(&name.as_str()) {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "Invalid dictionary-encoded field: {}. Valid fields are: {}.",
+                name,
+                valid.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Gives each dictionary-encoded column of a batch its own dictionary ID.
+///
+/// Fields built with [`RecordBatch::try_from_iter`] all share dictionary ID 0, which the IPC file
+/// writer rejects as a dictionary replacement when two columns have different dictionaries.
+fn with_unique_dict_ids(batch: RecordBatch) -> Result<RecordBatch, ArrowError> {
+    let schema = batch.schema();
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match field.data_type() {
+            DataType::Dictionary(..) => Field::new_dict(
+                field.name(),
+                field.data_type().clone(),
+                field.is_nullable(),
+                i as i64,
+                field.dict_is_ordered().unwrap_or(false),
+            )
+            .with_metadata(field.metadata().clone()),
+            _ => field.as_ref().clone(),
+        })
+        .collect();
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+    RecordBatch::try_new(Arc::new(schema), batch.columns().to_vec())
+}
+
+/// Checks that each of `fields` is one of the `valid` column names.
+///
+/// Returns an error listing the valid names otherwise.
+pub fn check_fields(fields: &[String], valid: &[&str]) -> Result<(), ArrowError> {
+    match fields.iter().find(|name| !valid.contains(&name.as_str())) {
+        Some(name) => Err(ArrowError::InvalidArgumentError(format!(
+            "Invalid field: {}. Valid fields are: {}.",
+            name,
+            valid.join(", ")
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Keeps only the named columns of a batch, in the given order.
+///
+/// Returns the batch unchanged if `fields` is `None`, and an error listing the valid names if a
+/// name is not a column of the batch.
+pub fn select_fields(
+    batch: RecordBatch,
+    fields: Option<&[String]>,
+) -> Result<RecordBatch, ArrowError> {
+    let Some(fields) = fields else {
+        return Ok(batch);
+    };
+    let schema = batch.schema();
+    let valid: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    check_fields(fields, &valid)?;
+    let indices = fields
+        .iter()
+        .map(|name| schema.index_of(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    batch.project(&indices)
 }
 
 pub fn write_ipc_err<T>(
@@ -30,9 +352,88 @@ pub fn write_ipc<T>(
     finish_batch(batch_builder)
 }
 
+/// Passes each record through a user hook, which returns the record to write, possibly changed,
+/// or `None` to drop it. Errors are passed through.
+pub(crate) fn map_records<'a, T>(
+    records: impl Iterator<Item = Result<T, ArrowError>> + 'a,
+    mut f: impl FnMut(T) -> Option<T> + 'a,
+) -> impl Iterator<Item = Result<T, ArrowError>> + 'a {
+    records.filter_map(move |i| match i {
+        Ok(record) => f(record).map(Ok),
+        Err(e) => Some(Err(e)),
+    })
+}
+
+/// Counts records without building any batches, stopping at the first error.
+pub fn count_err<T>(
+    mut records: impl Iterator<Item = Result<T, ArrowError>>,
+) -> Result<usize, ArrowError> {
+    records.try_fold(0, |n, record| record.map(|_| n + 1))
+}
+
+/// Caps the size of each record batch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BatchLimit {
+    /// At most this many records per batch.
+    Records(usize),
+    /// At most this many bytes of record data per batch. A record larger than the budget is
+    /// written in a batch of its own.
+    Bytes(usize),
+}
+
+/// Writes records as Apache Arrow IPC, starting a new batch whenever `limit` would be exceeded.
+///
+/// `new_builder` creates the builder for each batch and `size` returns the number of bytes a
+/// record counts towards a [`BatchLimit::Bytes`] budget.
+pub fn write_ipc_batched<T, B>(
+    records: impl Iterator<Item = Result<T, ArrowError>>,
+    mut new_builder: impl FnMut() -> Result<B, ArrowError>,
+    limit: BatchLimit,
+    size: impl Fn(&T) -> usize,
+) -> Result<Vec<u8>, ArrowError>
+where
+    B: for<'a> BatchBuilder<Record<'a> = &'a T>,
+{
+    let mut writer: Option<FileWriter<Vec<u8>>> = None;
+    let mut num_batches = 0;
+    let mut write = |batch: RecordBatch| -> Result<(), ArrowError> {
+        let batch = with_unique_dict_ids(batch)?;
+        let writer = match writer.as_mut() {
+            Some(writer) => writer,
+            None => writer.insert(FileWriter::try_new(Vec::new(), &batch.schema())?),
+        };
+        write_stats(writer, num_batches, &batch);
+        num_batches += 1;
+        writer.write(&batch)
+    };
+    let mut batch_builder = new_builder()?;
+    let (mut records_in_batch, mut bytes_in_batch) = (0, 0);
+    for record in records {
+        let record = record?;
+        let record_size = size(&record);
+        let is_full = match limit {
+            BatchLimit::Records(n) => records_in_batch >= n.max(1),
+            BatchLimit::Bytes(n) => bytes_in_batch + record_size > n,
+        };
+        if is_full && records_in_batch > 0 {
+            write(std::mem::replace(&mut batch_builder, new_builder()?).finish()?)?;
+            (records_in_batch, bytes_in_batch) = (0, 0);
+        }
+        batch_builder.push(&record);
+        records_in_batch += 1;
+        bytes_in_batch += record_size;
+    }
+    // The last batch is written even if empty, so that the schema is always present.
+    write(batch_builder.finish()?)?;
+    let mut writer = writer.expect("a batch was written");
+    writer.finish()?;
+    writer.into_inner()
+}
+
 pub fn finish_batch(batch_builder: impl BatchBuilder) -> Result<Vec<u8>, ArrowError> {
-    let batch = batch_builder.finish()?;
+    let batch = with_unique_dict_ids(batch_builder.finish()?)?;
     let mut writer = FileWriter::try_new(Vec::new(), &batch.schema())?;
+    write_stats(&mut writer, 0, &batch);
     writer.write(&batch)?;
     writer.finish()?;
     writer.into_inner()
@@ -0,0 +1,44 @@
+//! Helpers shared by the BigWig and BigBed readers.
+use arrow::error::ArrowError;
+
+use crate::region::query_region;
+
+/// Resolves a region string to a 0-based, half-open interval on a chromosome of the file.
+///
+/// Chromosome names are looked up in the file's chromosome table, given as `(name, length)` pairs;
+/// unknown names are an error. Missing bounds default to the start and end of the chromosome.
+pub(crate) fn interval_from_region<'a>(
+    region: &str,
+    chroms: impl IntoIterator<Item = (&'a str, u32)>,
+) -> Result<(String, u32, u32), ArrowError> {
+    let region = query_region(region)?;
+    let (name, length) = chroms
+        .into_iter()
+        .find(|(name, _)| *name == region.name())
+        .ok_or_else(|| {
+            ArrowError::InvalidArgumentError(format!("Unknown chromosome: {}", region.name()))
+        })?;
+    let start = region
+        .interval()
+        .start()
+        .map_or(0, |start| start.get() as u32 - 1); // 1-based to 0-based
+    let end = region
+        .interval()
+        .end()
+        .map_or(length, |end| end.get() as u32);
+    Ok((name.to_string(), start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_from_region() {
+        let interval = |region| interval_from_region(region, [("chr1", 1000)]).ok();
+        assert_eq!(interval("chr1"), Some(("chr1".to_string(), 0, 1000)));
+        assert_eq!(interval("chr1:11"), Some(("chr1".to_string(), 10, 1000)));
+        assert_eq!(interval("chr1:11-20"), Some(("chr1".to_string(), 10, 20)));
+        assert_eq!(interval("chr2:11-20"), None);
+    }
+}
@@ -1,17 +1,19 @@
 use std::fs::File;
 use std::io::{self, BufReader, Read, Seek};
-// use std::path::Path;
-use std::sync::Arc;
+use std::path::PathBuf;
 
-use arrow::array::{
-    ArrayRef, Float32Builder, GenericStringBuilder, Int32Builder, StringArray,
-    StringDictionaryBuilder,
-};
-use arrow::{datatypes::Int32Type, error::ArrowError, record_batch::RecordBatch};
-use noodles::core::Region;
+use arrow::datatypes::{Fields, Schema};
+use arrow::error::ArrowError;
 use noodles::{bcf, bgzf, csi, vcf};
 
-use crate::batch_builder::{write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES};
+use crate::batch_builder::{
+    check_no_offsets, count_err, provenance, write_ipc_err, BatchBuilder, WithOffsets,
+    BUFFER_SIZE_BYTES,
+};
+use crate::vcf::{
+    check_offsets, check_sorted, header_fields, header_to_string, parse_query_region,
+    VcfBatchBuilder, VcfOptions,
+};
 
 pub fn index_from_reader<R>(read: R) -> io::Result<csi::Index>
 where
@@ -21,40 +23,123 @@ where
     csi_reader.read_index()
 }
 
+/// Returns the schema of the batches returned by [`BcfReader::records_to_ipc`] with `options`,
+/// reading only the header of the BCF file at `path`, not its records or index. See
+/// [`crate::vcf::peek_schema`].
+pub fn peek_schema(path: &str, options: &VcfOptions) -> Result<Schema, ArrowError> {
+    let mut reader = File::open(path)
+        .map(|file| bcf::Reader::new(BufReader::new(file)))
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    let header = reader
+        .read_header()
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    Ok(Schema::new(header_fields(&header, options)?))
+}
+
 /// A BCF reader.
 pub struct BcfReader<R> {
     reader: bcf::Reader<bgzf::Reader<R>>,
     header: vcf::Header,
+    /// The virtual position of the first record, after the header.
+    records_start: bgzf::VirtualPosition,
     index: csi::Index,
+    options: VcfOptions,
+    path: Option<PathBuf>,
 }
 
 impl BcfReader<BufReader<File>> {
+    /// Creates a BCF reader from a given file path.
+    ///
+    /// The index is loaded from `<path>.csi`.
     pub fn new_from_path(path: &str) -> std::io::Result<Self> {
         let index = csi::read(format!("{}.csi", path))?;
+        Self::new_from_path_with_index(path, index)
+    }
+
+    /// Creates a BCF reader from a given file path and a prebuilt index.
+    ///
+    /// This avoids parsing the index again when opening the same file several times.
+    pub fn new_from_path_with_index(path: &str, index: csi::Index) -> std::io::Result<Self> {
         let file = std::fs::File::open(path)?;
         let buf_file = std::io::BufReader::with_capacity(BUFFER_SIZE_BYTES, file);
         let mut reader = bcf::Reader::new(buf_file);
         let header = reader.read_header()?;
+        let records_start = reader.virtual_position();
         Ok(Self {
             reader,
             header,
+            records_start,
             index,
+            options: VcfOptions::default(),
+            path: Some(PathBuf::from(path)),
         })
     }
 }
 
 impl<R: Read + Seek> BcfReader<R> {
-    /// Creates a BCF Reader.
+    /// Creates a BCF Reader from a stream and a prebuilt index.
     pub fn new(read: R, index: csi::Index) -> std::io::Result<Self> {
         let mut reader = bcf::Reader::new(read);
         let header = reader.read_header()?;
+        let records_start = reader.virtual_position();
         Ok(Self {
             reader,
             header,
+            records_start,
             index,
+            options: VcfOptions::default(),
+            path: None,
         })
     }
 
+    /// Returns the CSI index used for queries.
+    pub fn index(&self) -> &csi::Index {
+        &self.index
+    }
+
+    /// Returns the VCF header, e.g. to write modified records with [`crate::vcf::write_bcf`].
+    pub fn header(&self) -> &vcf::Header {
+        &self.header
+    }
+
+    /// Returns the text of the VCF header, as written by [`crate::vcf::header_to_string`].
+    pub fn header_text(&self) -> io::Result<String> {
+        header_to_string(&self.header)
+    }
+
+    /// Returns the names of the contigs in header order.
+    ///
+    /// These map the indices emitted with [`VcfOptions::emit_ref_as_index`] back to names.
+    pub fn reference_names(&self) -> Vec<String> {
+        self.header
+            .contigs()
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Returns the names and lengths of the contigs (`##contig`) in header order. Contigs
+    /// without a length are left out.
+    pub fn reference_lengths(&self) -> Vec<(String, u64)> {
+        self.header
+            .contigs()
+            .iter()
+            .filter_map(|(name, contig)| Some((name.to_string(), contig.length()? as u64)))
+            .collect()
+    }
+
+    /// Sets the options used when converting records.
+    pub fn with_options(mut self, options: VcfOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the fields of the batches returned by [`BcfReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        header_fields(&self.header, &self.options)
+    }
+
     /// Returns the records in the given region as Apache Arrow IPC.
     ///
     /// If the region is `None`, all records are returned.
@@ -68,21 +153,58 @@ impl<R: Read + Seek> BcfReader<R> {
     /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
     /// ```
     pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
-        let batch_builder = BcfBatchBuilder::new(1024, &self.header)?;
+        if region.is_some() {
+            check_no_offsets(self.options.emit_offset)?;
+        } else if self.options.emit_offset.unwrap_or(false) {
+            let vpos_lo = self.reader.virtual_position();
+            return self.vpos_range_to_ipc(vpos_lo, bgzf::VirtualPosition::MAX);
+        }
+        let batch_builder = VcfBatchBuilder::new(1024, &self.header, &self.options)?
+            .with_metadata(provenance("bcf", self.path.as_deref(), region.as_slice()));
         if let Some(region) = region {
-            let region: Region = region.parse().unwrap();
+            let region = parse_query_region(region, &self.header, &self.index, &self.options)?;
             let query = self
                 .reader
                 .query(&self.header, &self.index, &region)
                 .map_err(|e| ArrowError::ExternalError(e.into()))?
                 .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-            return write_ipc_err(query, batch_builder);
+            return write_ipc_err(self.options.subsample(query)?, batch_builder);
         }
         let records = self
             .reader
             .records(&self.header)
             .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-        write_ipc_err(records, batch_builder)
+        let records = check_sorted(records, &self.header, &self.options);
+        write_ipc_err(self.options.subsample(records)?, batch_builder)
+    }
+
+    /// Returns the number of records in the given region, without converting them to Arrow.
+    ///
+    /// If the region is `None`, all records are counted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bcf::BcfReader;
+    ///
+    /// let mut reader = BcfReader::new_from_path("sample.bcf").unwrap();
+    /// let n = reader.count(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        if let Some(region) = region {
+            let region = parse_query_region(region, &self.header, &self.index, &self.options)?;
+            let query = self
+                .reader
+                .query(&self.header, &self.index, &region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?
+                .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+            return count_err(query);
+        }
+        let records = self
+            .reader
+            .records(&self.header)
+            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+        count_err(check_sorted(records, &self.header, &self.options))
     }
 
     pub fn records_to_ipc_from_vpos(
@@ -94,81 +216,71 @@ impl<R: Read + Seek> BcfReader<R> {
             .map_err(|e| ArrowError::ExternalError(e.into()))?;
         let vpos_hi = bgzf::VirtualPosition::try_from(pos_hi)
             .map_err(|e| ArrowError::ExternalError(e.into()))?;
-        let batch_builder = BcfBatchBuilder::new(1024, &self.header)?;
-        let records = BcfRecords::new(&mut self.reader, &self.header, vpos_lo, vpos_hi)
-            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-        write_ipc_err(records, batch_builder)
+        self.vpos_range_to_ipc(vpos_lo, vpos_hi)
     }
-}
 
-struct BcfBatchBuilder {
-    chrom: StringDictionaryBuilder<Int32Type>,
-    pos: Int32Builder,
-    id: GenericStringBuilder<i32>,
-    ref_: GenericStringBuilder<i32>,
-    alt: GenericStringBuilder<i32>,
-    qual: Float32Builder,
-    filter: GenericStringBuilder<i32>,
-    info: GenericStringBuilder<i32>,
-    format: GenericStringBuilder<i32>,
-}
+    /// Returns the records from a BGZF virtual offset to the end of the file as Apache Arrow
+    /// IPC, e.g. to resume a scan from an offset emitted with [`VcfOptions::emit_offset`].
+    ///
+    /// The offset must be the start of a record: not before the first record, and with a record
+    /// readable from it. An offset inside a record is caught when the bytes at it don't parse as
+    /// a record, which is likely but not guaranteed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bcf::BcfReader;
+    ///
+    /// let mut reader = BcfReader::new_from_path("sample.bcf").unwrap();
+    /// let ipc = reader.scan_from(123 << 16).unwrap();
+    /// ```
+    pub fn scan_from(&mut self, vpos: u64) -> Result<Vec<u8>, ArrowError> {
+        let vpos = bgzf::VirtualPosition::from(vpos);
+        self.check_record_start(vpos)?;
+        self.vpos_range_to_ipc(vpos, bgzf::VirtualPosition::MAX)
+    }
 
-impl BcfBatchBuilder {
-    pub fn new(capacity: usize, header: &vcf::Header) -> Result<Self, ArrowError> {
-        let categories = StringArray::from(
-            header
-                .contigs()
-                .keys()
-                .map(|k| k.to_string())
-                .collect::<Vec<_>>(),
-        );
-        Ok(Self {
-            chrom: StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
-                capacity,
-                &categories,
-            )?,
-            pos: Int32Builder::with_capacity(capacity),
-            id: GenericStringBuilder::<i32>::new(),
-            ref_: GenericStringBuilder::<i32>::new(),
-            alt: GenericStringBuilder::<i32>::new(),
-            qual: Float32Builder::with_capacity(capacity),
-            filter: GenericStringBuilder::<i32>::new(),
-            info: GenericStringBuilder::<i32>::new(),
-            format: GenericStringBuilder::<i32>::new(),
-        })
+    /// Fails unless a record can be read from `vpos`.
+    fn check_record_start(&mut self, vpos: bgzf::VirtualPosition) -> Result<(), ArrowError> {
+        let invalid = || {
+            ArrowError::InvalidArgumentError(format!(
+                "Invalid virtual position: {}. Expected the start of a record.",
+                u64::from(vpos)
+            ))
+        };
+        if vpos < self.records_start {
+            return Err(invalid());
+        }
+        self.reader.seek(vpos).map_err(|_| invalid())?;
+        let mut record = vcf::Record::default();
+        self.reader
+            .read_record(&self.header, &mut record)
+            .map_err(|_| invalid())?;
+        Ok(())
     }
-}
 
-impl BatchBuilder for BcfBatchBuilder {
-    type Record<'a> = &'a vcf::Record;
-
-    fn push(&mut self, record: Self::Record<'_>) {
-        self.chrom.append_value(record.chromosome().to_string());
-        self.pos.append_value(usize::from(record.position()) as i32);
-        self.id.append_value(record.ids().to_string());
-        self.ref_.append_value(record.reference_bases().to_string());
-        self.alt.append_value(record.alternate_bases().to_string());
-        self.qual
-            .append_option(record.quality_score().map(f32::from));
-        self.filter
-            .append_option(record.filters().map(|f| f.to_string()));
-        self.info.append_value(record.info().to_string());
-        self.format.append_value(record.format().to_string());
-    }
-
-    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
-        RecordBatch::try_from_iter(vec![
-            // spec
-            ("chrom", Arc::new(self.chrom.finish()) as ArrayRef),
-            ("pos", Arc::new(self.pos.finish()) as ArrayRef),
-            ("id", Arc::new(self.id.finish()) as ArrayRef),
-            ("ref", Arc::new(self.ref_.finish()) as ArrayRef),
-            ("alt", Arc::new(self.alt.finish()) as ArrayRef),
-            ("qual", Arc::new(self.qual.finish()) as ArrayRef),
-            ("filter", Arc::new(self.filter.finish()) as ArrayRef),
-            ("info", Arc::new(self.info.finish()) as ArrayRef),
-            ("format", Arc::new(self.format.finish()) as ArrayRef),
-        ])
+    /// Returns the records from `vpos_lo` up to `vpos_hi` as Apache Arrow IPC, with their
+    /// virtual offsets if [`VcfOptions::emit_offset`] is set.
+    fn vpos_range_to_ipc(
+        &mut self,
+        vpos_lo: bgzf::VirtualPosition,
+        vpos_hi: bgzf::VirtualPosition,
+    ) -> Result<Vec<u8>, ArrowError> {
+        check_offsets(&self.options)?;
+        let batch_builder = VcfBatchBuilder::new(1024, &self.header, &self.options)?;
+        let metadata = provenance("bcf", self.path.as_deref(), &[]);
+        let records = BcfRecords::new(&mut self.reader, &self.header, vpos_lo, vpos_hi);
+        if self.options.emit_offset.unwrap_or(false) {
+            let records = records
+                .with_offsets()
+                .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+            return write_ipc_err(
+                records,
+                WithOffsets::new(batch_builder).with_metadata(metadata),
+            );
+        }
+        let records = records.map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+        write_ipc_err(records, batch_builder.with_metadata(metadata))
     }
 }
 
@@ -204,6 +316,18 @@ where
     pub fn reset(&mut self) -> Option<bgzf::VirtualPosition> {
         self.reader.seek(self.vpos_lo).ok()
     }
+
+    /// Returns the records along with the virtual position at which each starts.
+    pub fn with_offsets(mut self) -> impl Iterator<Item = io::Result<(u64, vcf::Record)>> + 'a
+    where
+        R: 'a,
+    {
+        std::iter::from_fn(move || {
+            let vpos = u64::from(self.reader.virtual_position());
+            self.next()
+                .map(|record| record.map(|record| (vpos, record)))
+        })
+    }
 }
 
 impl<R> Iterator for BcfRecords<'_, R>
@@ -0,0 +1,1314 @@
+//! A reader for the BED format.
+//!
+//! Records produce the `chrom`, `start` and `end` columns, with 0-based, half-open coordinates,
+//! followed by as many of the standard optional BED fields as the file has, up to BED12: `name`,
+//! `score`, `strand`, `thickStart`, `thickEnd`, `itemRgb`, `blockCount`, `blockSizes` and
+//! `blockStarts`. The number of fields is taken from the first record unless given in
+//! [`BedOptions::bed_fields`]. Fields of a record beyond that are kept, tab-separated, in a `rest`
+//! column. Track, browser and comment lines are skipped.
+//!
+//! BGZF-compressed files with a tabix or CSI index can be queried by region.
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Int32Builder, Int64Builder, ListBuilder, StringBuilder, StringDictionaryBuilder,
+    UInt8Builder,
+};
+use arrow::{
+    datatypes::{Fields, Int32Type},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use noodles::core::{region::Interval, Position, Region};
+use noodles::{bgzf, csi, tabix};
+
+use crate::batch_builder::{
+    builder_fields, check_dictionary_encode, count_err, map_records, provenance, select_fields,
+    write_ipc_err, BatchBuilder, StringColumnBuilder, BUFFER_SIZE_BYTES,
+};
+use crate::compression::decompress;
+use crate::coordinates::{CoordinateSystem, PositionBuilder};
+use crate::error_policy::{tolerate, ErrorPolicy, RecordError};
+use crate::region::{query_region, QueryMode};
+use crate::sampling::sample_records;
+use crate::sort_order::SortChecker;
+
+/// A BED record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BedRecord {
+    pub chrom: String,
+    pub start: u64,
+    pub end: u64,
+    pub name: Option<String>,
+    pub score: Option<i64>,
+    pub strand: Option<String>,
+    pub thick_start: Option<u64>,
+    pub thick_end: Option<u64>,
+    pub item_rgb: Option<Vec<u8>>,
+    pub block_count: Option<i32>,
+    pub block_sizes: Option<Vec<i32>>,
+    pub block_starts: Option<Vec<i32>>,
+    pub rest: Option<String>,
+}
+
+/// The names of the fields following `chrom`, `start` and `end` in BED12.
+const OPTIONAL_FIELDS: [&str; 9] = [
+    "name",
+    "score",
+    "strand",
+    "thickStart",
+    "thickEnd",
+    "itemRgb",
+    "blockCount",
+    "blockSizes",
+    "blockStarts",
+];
+
+/// Options for reading BED files.
+#[derive(Clone, Debug, Default)]
+pub struct BedOptions {
+    /// Keeps only records whose name (the 4th field) is one of the given names.
+    ///
+    /// Records without a name are dropped when this is set.
+    pub names: Option<Vec<String>>,
+    /// Keeps only records with a score of at least this value.
+    ///
+    /// Records without a score (BED3 and BED4, or a `.` score) are not filtered by score.
+    pub min_score: Option<i64>,
+    /// Keeps only records with a score of at most this value.
+    pub max_score: Option<i64>,
+    /// The number of standard BED fields (3 to 12) in each record.
+    ///
+    /// Defaults to the number of fields in the first record, capped at 12.
+    pub bed_fields: Option<usize>,
+    /// String columns to dictionary-encode, out of `name` and `strand`.
+    ///
+    /// `chrom` is always dictionary-encoded.
+    pub dictionary_encode: Option<Vec<String>>,
+    /// The coordinate system of `start` and `thickStart`. Defaults to the native 0-based,
+    /// half-open coordinates. See [`crate::coordinates`].
+    pub coordinate_system: Option<CoordinateSystem>,
+    /// Emit missing optional fields, such as a `.` score, as empty values instead of
+    /// nulls, for tools that don't handle nulls: an empty string for `name`, `strand` and
+    /// `rest`, 0 for the numeric fields, and an empty list for `itemRgb`, `blockSizes` and
+    /// `blockStarts`. Defaults to nulls.
+    pub missing_as_empty: Option<bool>,
+    /// What to do with records that fail to parse. Defaults to [`ErrorPolicy::Fail`].
+    pub on_error: Option<ErrorPolicy>,
+    /// Fail on the first record out of order, i.e. on a chromosome whose records came earlier
+    /// but were interrupted by another chromosome, or with a smaller start than the previous
+    /// record on the same chromosome. The error gives the line of the record, counted from where
+    /// the scan started. Malformed records skipped by [`BedOptions::on_error`] are not checked.
+    pub require_sorted: Option<bool>,
+    /// Keep each record passing the filters with this probability, from 0 to 1, for a random
+    /// subsample. Only `records_to_ipc` subsamples records.
+    pub sample_fraction: Option<f64>,
+    /// Keep this many of the records passing the filters, chosen uniformly, in file order. This
+    /// reads all the records before returning any, and can't be combined with
+    /// [`BedOptions::sample_fraction`].
+    pub sample_count: Option<usize>,
+    /// The seed of the random subsample, so that the same records are kept on every scan.
+    /// Defaults to 0.
+    pub sample_seed: Option<u64>,
+    /// The columns to return, in this order, e.g. `["name", "chrom", "start"]`. Defaults to all
+    /// of them, in their default order.
+    pub fields: Option<Vec<String>>,
+    /// Emit `start`, `end`, `thickStart` and `thickEnd` as UInt64 instead of UInt32, for
+    /// coordinates of 2^32 or more on chromosomes longer than 4 Gbp. Defaults to UInt32, as in
+    /// bigBed, with which larger coordinates are malformed records, handled by
+    /// [`BedOptions::on_error`].
+    pub wide_positions: Option<bool>,
+}
+
+impl BedOptions {
+    /// Returns whether a record passes the filters.
+    pub fn keep(&self, record: &BedRecord) -> bool {
+        if let Some(names) = &self.names {
+            let Some(name) = &record.name else {
+                return false;
+            };
+            if !names.contains(name) {
+                return false;
+            }
+        }
+        if let Some(score) = record.score {
+            if self.min_score.is_some_and(|min| score < min)
+                || self.max_score.is_some_and(|max| score > max)
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Subsamples records with [`BedOptions::sample_fraction`] or [`BedOptions::sample_count`].
+    fn subsample<'a>(
+        &self,
+        records: impl Iterator<Item = Result<BedRecord, ArrowError>> + 'a,
+    ) -> Result<Box<dyn Iterator<Item = Result<BedRecord, ArrowError>> + 'a>, ArrowError> {
+        sample_records(
+            records,
+            self.sample_fraction,
+            self.sample_count,
+            self.sample_seed,
+        )
+    }
+}
+
+fn invalid_data(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid line: {}", line),
+    )
+}
+
+fn parse<T: std::str::FromStr>(s: &str, line: &str) -> io::Result<T> {
+    s.parse().map_err(|_| invalid_data(line))
+}
+
+/// Returns whether a line carries no data.
+pub(crate) fn is_skipped(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+}
+
+/// Parses an optional field, where `.` stands for a missing value.
+fn parse_optional<T: std::str::FromStr>(s: Option<&str>, line: &str) -> io::Result<Option<T>> {
+    match s {
+        None | Some(".") => Ok(None),
+        Some(s) => parse(s, line).map(Some),
+    }
+}
+
+/// Parses a comma-separated list, allowing a trailing comma.
+fn parse_list<T: std::str::FromStr>(s: Option<&str>, line: &str) -> io::Result<Option<Vec<T>>> {
+    match s {
+        None | Some(".") => Ok(None),
+        Some(s) => s
+            .split(',')
+            .filter(|value| !value.is_empty())
+            .map(|value| parse(value, line))
+            .collect::<io::Result<_>>()
+            .map(Some),
+    }
+}
+
+/// Parses a BED line with `bed_fields` standard fields.
+fn parse_bed_line(line: &str, bed_fields: usize) -> io::Result<BedRecord> {
+    let mut fields = line.split('\t');
+    let (Some(chrom), Some(start), Some(end)) = (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(invalid_data(line));
+    };
+    let mut optional = fields.by_ref().take(bed_fields - 3);
+    let mut next = || optional.next();
+    let name = next().map(str::to_string);
+    let score = parse_optional(next(), line)?;
+    let strand = next().map(str::to_string);
+    let thick_start = parse_optional(next(), line)?;
+    let thick_end = parse_optional(next(), line)?;
+    let item_rgb = parse_list(next(), line)?;
+    let block_count = parse_optional(next(), line)?;
+    let block_sizes = parse_list(next(), line)?;
+    let block_starts = parse_list(next(), line)?;
+    let rest: Vec<&str> = fields.collect();
+    Ok(BedRecord {
+        chrom: chrom.to_string(),
+        start: parse(start, line)?,
+        end: parse(end, line)?,
+        name,
+        score,
+        strand,
+        thick_start,
+        thick_end,
+        item_rgb,
+        block_count,
+        block_sizes,
+        block_starts,
+        rest: (!rest.is_empty()).then(|| rest.join("\t")),
+    })
+}
+
+/// Loads the tabix or CSI index of a BED file from `<path>.tbi` or `<path>.csi`, if either exists.
+pub fn index_from_path(path: &str) -> io::Result<Option<csi::Index>> {
+    let tbi_path = format!("{}.tbi", path);
+    let csi_path = format!("{}.csi", path);
+    if Path::new(&tbi_path).exists() {
+        tabix::read(tbi_path).map(Some)
+    } else if Path::new(&csi_path).exists() {
+        csi::read(csi_path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns the number of fields of the first data line, capped at 12.
+fn detect_bed_fields<I>(lines: &mut std::iter::Peekable<I>) -> usize
+where
+    I: Iterator<Item = (usize, io::Result<String>)>,
+{
+    match lines.peek() {
+        Some((_, Ok(line))) => line.split('\t').count().clamp(3, 12),
+        _ => 3,
+    }
+}
+
+/// Resolves the number of standard BED fields, from the options, the width detected for the
+/// file, or the first line.
+fn bed_fields<I>(
+    options: &BedOptions,
+    detected: Option<usize>,
+    lines: &mut std::iter::Peekable<I>,
+) -> Result<usize, ArrowError>
+where
+    I: Iterator<Item = (usize, io::Result<String>)>,
+{
+    match options.bed_fields.or(detected) {
+        Some(n @ 3..=12) => Ok(n),
+        Some(n) => Err(ArrowError::InvalidArgumentError(format!(
+            "Invalid number of BED fields: {}. Expected 3 to 12.",
+            n
+        ))),
+        None => Ok(detect_bed_fields(lines)),
+    }
+}
+
+/// Checks that the positions of a record fit in their columns, UInt64 if `wide` or UInt32
+/// otherwise, once the starts are converted to `coordinate_system`.
+fn check_positions(
+    record: &BedRecord,
+    coordinate_system: CoordinateSystem,
+    wide: bool,
+) -> io::Result<()> {
+    let max = if wide { i64::MAX } else { u32::MAX.into() };
+    let starts = std::iter::once(record.start).chain(record.thick_start);
+    let ends = std::iter::once(record.end).chain(record.thick_end);
+    for (position, is_start) in starts
+        .map(|start| (start, true))
+        .chain(ends.map(|end| (end, false)))
+    {
+        let converted = i64::try_from(position).ok().map(|position| match is_start {
+            true => coordinate_system.start_from(CoordinateSystem::ZeroBasedHalfOpen, position),
+            false => position,
+        });
+        if !converted.is_some_and(|position| (0..=max).contains(&position)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid position: {}. Expected a converted position from 0 to {}, or \
+                     wide_positions for larger ones.",
+                    position, max
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses lines into the records passing the filters, returning the number of standard BED fields
+/// alongside them. Malformed records are handled by [`BedOptions::on_error`], collecting them into
+/// `errors`.
+fn parse_records<'a, I>(
+    lines: I,
+    options: &'a BedOptions,
+    detected: Option<usize>,
+    errors: &'a mut Vec<RecordError>,
+) -> Result<
+    (
+        usize,
+        impl Iterator<Item = Result<BedRecord, ArrowError>> + 'a,
+    ),
+    ArrowError,
+>
+where
+    I: Iterator<Item = io::Result<String>> + 'a,
+{
+    let mut lines = lines
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !is_skipped(line)))
+        .peekable();
+    let bed_fields = bed_fields(options, detected, &mut lines)?;
+    let mut checker = options
+        .require_sorted
+        .unwrap_or(false)
+        .then(SortChecker::default);
+    let coordinate_system = options
+        .coordinate_system
+        .unwrap_or(CoordinateSystem::ZeroBasedHalfOpen);
+    let wide = options.wide_positions.unwrap_or(false);
+    let records = lines.map(move |(i, line)| {
+        let record = line
+            .and_then(|line| parse_bed_line(&line, bed_fields))
+            .and_then(|record| check_positions(&record, coordinate_system, wide).map(|_| record));
+        let record = match (record, &mut checker) {
+            (Ok(record), Some(checker)) => checker
+                .check(&record.chrom, record.start as usize, Some(i + 1))
+                .map(|_| record),
+            (record, _) => record,
+        };
+        (i + 1, record)
+    });
+    let records = tolerate(records, options.on_error.unwrap_or_default(), errors)
+        .filter(move |i| i.as_ref().map_or(true, |record| options.keep(record)))
+        .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+    Ok((bed_fields, records))
+}
+
+/// Returns the lines of the records overlapping a region.
+pub(crate) fn query<'r>(
+    indexed_reader: Option<&'r mut csi::io::IndexedReader<bgzf::Reader<File>>>,
+    region: &'r Region,
+) -> Result<impl Iterator<Item = io::Result<String>> + 'r, ArrowError> {
+    let indexed_reader = indexed_reader.ok_or_else(|| {
+        ArrowError::InvalidArgumentError(
+            "Querying a region requires a .tbi or .csi index file for the given BED file."
+                .to_string(),
+        )
+    })?;
+    let query = indexed_reader
+        .query(region)
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    Ok(query.map(|i| i.map(|record| record.as_ref().to_string())))
+}
+
+/// Drops the records of a region query that do not match the region under `mode`. Errors are
+/// passed through.
+fn filter_interval(
+    records: impl Iterator<Item = Result<BedRecord, ArrowError>>,
+    interval: Interval,
+    mode: QueryMode,
+) -> impl Iterator<Item = Result<BedRecord, ArrowError>> {
+    records.filter(move |i| {
+        i.as_ref().map_or(true, |record| {
+            // 0-based, half-open to 1-based, closed. Empty features span the base after `start`.
+            let start = record.start as usize + 1;
+            let end = (record.end as usize).max(start);
+            match (Position::try_from(start), Position::try_from(end)) {
+                (Ok(start), Ok(end)) => mode.matches(interval, start, end),
+                _ => true,
+            }
+        })
+    })
+}
+
+/// A BED reader.
+pub struct BedReader<R> {
+    reader: R,
+    indexed_reader: Option<csi::io::IndexedReader<bgzf::Reader<File>>>,
+    /// The number of fields of the first record of a file, so that region queries share a schema.
+    detected_fields: Option<usize>,
+    options: BedOptions,
+    query_mode: QueryMode,
+    errors: Vec<RecordError>,
+    path: Option<PathBuf>,
+}
+
+impl BedReader<Box<dyn BufRead>> {
+    /// Creates a BED reader from a given file path.
+    ///
+    /// Gzip- and BGZF-compressed files are decompressed transparently. A BGZF-compressed file
+    /// with an index at `<path>.tbi` or `<path>.csi` can also be queried by region.
+    pub fn new_from_path(path: &str) -> io::Result<Self> {
+        let file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let mut reader = Self::new(decompress(file)?)?;
+        let file = BufReader::new(File::open(path)?);
+        let mut lines = decompress(file)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(true, |line| !is_skipped(line)))
+            .peekable();
+        reader.detected_fields = Some(detect_bed_fields(&mut lines));
+        if let Some(index) = index_from_path(path)? {
+            let file = File::open(path)?;
+            reader.indexed_reader = Some(csi::io::IndexedReader::new(file, index));
+        }
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> BedReader<R> {
+    /// Creates a BED reader.
+    ///
+    /// Readers created from a stream cannot be queried by region.
+    pub fn new(reader: R) -> io::Result<Self> {
+        Ok(Self {
+            reader,
+            indexed_reader: None,
+            detected_fields: None,
+            options: BedOptions::default(),
+            query_mode: QueryMode::default(),
+            errors: Vec::new(),
+            path: None,
+        })
+    }
+
+    /// Returns the index (tabix or CSI) used for queries, if any.
+    pub fn index(&self) -> Option<&csi::Index> {
+        self.indexed_reader.as_ref().map(|reader| reader.index())
+    }
+
+    /// Sets the filters applied when reading records.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bed::{BedOptions, BedReader};
+    ///
+    /// let options = BedOptions {
+    ///     min_score: Some(500),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = BedReader::new_from_path("sample.bed")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// ```
+    pub fn with_options(mut self, options: BedOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets how records are matched against query regions.
+    ///
+    /// By default, a region query returns every record overlapping the region. With
+    /// [`QueryMode::Contained`], records straddling either bound of the region are dropped.
+    pub fn with_query_mode(mut self, query_mode: QueryMode) -> Self {
+        self.query_mode = query_mode;
+        self
+    }
+
+    /// Returns the records skipped by the last scan under [`ErrorPolicy::SkipAndCollect`].
+    pub fn errors(&self) -> &[RecordError] {
+        &self.errors
+    }
+
+    /// Returns the distinct chromosomes (first field) of the records, sorted, without converting
+    /// them to Arrow.
+    ///
+    /// The names come straight from the index of an indexed file. Otherwise the records not read
+    /// yet are scanned once, like [`BedReader::count`], without parsing their other fields.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bed::BedReader;
+    ///
+    /// let mut reader = BedReader::new_from_path("sample.bed").unwrap();
+    /// let chroms = reader.chroms().unwrap();
+    /// ```
+    pub fn chroms(&mut self) -> io::Result<Vec<String>> {
+        if let Some(header) = self.index().and_then(|index| index.header()) {
+            let mut names: Vec<_> = header.reference_sequence_names().iter().cloned().collect();
+            names.sort();
+            return Ok(names);
+        }
+        let mut chroms = BTreeSet::new();
+        for line in (&mut self.reader).lines() {
+            let line = line?;
+            if is_skipped(&line) {
+                continue;
+            }
+            let chrom = line.split('\t').next().unwrap_or_default();
+            if !chroms.contains(chrom) {
+                chroms.insert(chrom.to_string());
+            }
+        }
+        Ok(chroms.into_iter().collect())
+    }
+
+    /// Returns the fields of the batches returned by [`BedReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    ///
+    /// The number of standard BED fields is taken from [`BedOptions::bed_fields`] or, for readers
+    /// created from a path, the first record. Streams default to BED3.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        let bed_fields = bed_fields(
+            &self.options,
+            self.detected_fields,
+            &mut std::iter::empty::<(usize, io::Result<String>)>().peekable(),
+        )?;
+        builder_fields(BedBatchBuilder::new(0, bed_fields, &self.options)?)
+    }
+
+    /// Returns the records in the given region as Apache Arrow IPC.
+    ///
+    /// If the region is `None`, all records are returned. Querying a region requires a
+    /// BGZF-compressed file with a tabix or CSI index. Records rejected by the filters in
+    /// [`BedOptions`] are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bed::BedReader;
+    ///
+    /// let mut reader = BedReader::new_from_path("sample.bed.gz").unwrap();
+    /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
+        self.scan_map(region, Some)
+    }
+
+    /// Returns the records in the given region for which `predicate` returns true as Apache
+    /// Arrow IPC, like [`BedReader::records_to_ipc`].
+    ///
+    /// The predicate sees the records passing the filters in [`BedOptions`], before they are
+    /// subsampled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bed::BedReader;
+    ///
+    /// let mut reader = BedReader::new_from_path("sample.bed").unwrap();
+    /// let ipc = reader
+    ///     .scan_with(None, |record| record.end - record.start >= 100)
+    ///     .unwrap();
+    /// ```
+    pub fn scan_with(
+        &mut self,
+        region: Option<&str>,
+        mut predicate: impl FnMut(&BedRecord) -> bool,
+    ) -> Result<Vec<u8>, ArrowError> {
+        self.scan_map(region, |record| predicate(&record).then_some(record))
+    }
+
+    /// Returns the records in the given region as Apache Arrow IPC, like
+    /// [`BedReader::records_to_ipc`], after passing each through `f`, which returns the record to
+    /// write, e.g. with a changed field, or `None` to drop it.
+    ///
+    /// `f` sees the records passing the filters in [`BedOptions`], before they are subsampled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bed::BedReader;
+    ///
+    /// let mut reader = BedReader::new_from_path("sample.bed").unwrap();
+    /// let ipc = reader
+    ///     .scan_map(None, |mut record| {
+    ///         record.name = record.name.map(|name| name.to_uppercase());
+    ///         Some(record)
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn scan_map(
+        &mut self,
+        region: Option<&str>,
+        mut f: impl FnMut(BedRecord) -> Option<BedRecord>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        let metadata = provenance("bed", self.path.as_deref(), region.as_slice());
+        if let Some(region) = region {
+            let region = query_region(region)?;
+            let lines = query(self.indexed_reader.as_mut(), &region)?;
+            let (bed_fields, records) =
+                parse_records(lines, &self.options, self.detected_fields, &mut self.errors)?;
+            let records = filter_interval(records, region.interval(), self.query_mode);
+            let records = self.options.subsample(map_records(records, &mut f))?;
+            let batch_builder =
+                BedBatchBuilder::new(1024, bed_fields, &self.options)?.with_metadata(metadata);
+            return write_ipc_err(records, batch_builder);
+        }
+        let (bed_fields, records) = parse_records(
+            (&mut self.reader).lines(),
+            &self.options,
+            None,
+            &mut self.errors,
+        )?;
+        let records = self.options.subsample(map_records(records, f))?;
+        let batch_builder =
+            BedBatchBuilder::new(1024, bed_fields, &self.options)?.with_metadata(metadata);
+        write_ipc_err(records, batch_builder)
+    }
+
+    /// Returns the number of records in the given region, without converting them to Arrow.
+    ///
+    /// If the region is `None`, all records are counted. Records rejected by the filters in
+    /// [`BedOptions`] are not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::bed::BedReader;
+    ///
+    /// let mut reader = BedReader::new_from_path("sample.bed").unwrap();
+    /// let n = reader.count(None).unwrap();
+    /// ```
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        if let Some(region) = region {
+            let region = query_region(region)?;
+            let lines = query(self.indexed_reader.as_mut(), &region)?;
+            let (_, records) =
+                parse_records(lines, &self.options, self.detected_fields, &mut self.errors)?;
+            return count_err(filter_interval(records, region.interval(), self.query_mode));
+        }
+        let (_, records) = parse_records(
+            (&mut self.reader).lines(),
+            &self.options,
+            None,
+            &mut self.errors,
+        )?;
+        count_err(records)
+    }
+}
+
+struct BedBatchBuilder {
+    bed_fields: usize,
+    coordinate_system: CoordinateSystem,
+    chrom: StringDictionaryBuilder<Int32Type>,
+    start: PositionBuilder,
+    end: PositionBuilder,
+    name: StringColumnBuilder,
+    score: Int64Builder,
+    strand: StringColumnBuilder,
+    thick_start: PositionBuilder,
+    thick_end: PositionBuilder,
+    item_rgb: ListBuilder<UInt8Builder>,
+    block_count: Int32Builder,
+    block_sizes: ListBuilder<Int32Builder>,
+    block_starts: ListBuilder<Int32Builder>,
+    rest: StringBuilder,
+    missing_as_empty: bool,
+    fields: Option<Vec<String>>,
+}
+
+impl BedBatchBuilder {
+    fn new(capacity: usize, bed_fields: usize, options: &BedOptions) -> Result<Self, ArrowError> {
+        let dictionary_encode = options.dictionary_encode.as_deref();
+        check_dictionary_encode(dictionary_encode, &["name", "strand"])?;
+        let wide = options.wide_positions.unwrap_or(false);
+        Ok(Self {
+            bed_fields,
+            coordinate_system: options
+                .coordinate_system
+                .unwrap_or(CoordinateSystem::ZeroBasedHalfOpen),
+            chrom: StringDictionaryBuilder::<Int32Type>::new(),
+            start: PositionBuilder::new(capacity, true, wide),
+            end: PositionBuilder::new(capacity, true, wide),
+            name: StringColumnBuilder::new("name", dictionary_encode),
+            score: Int64Builder::with_capacity(capacity),
+            strand: StringColumnBuilder::new("strand", dictionary_encode),
+            thick_start: PositionBuilder::new(capacity, true, wide),
+            thick_end: PositionBuilder::new(capacity, true, wide),
+            item_rgb: ListBuilder::with_capacity(UInt8Builder::new(), capacity),
+            block_count: Int32Builder::with_capacity(capacity),
+            block_sizes: ListBuilder::with_capacity(Int32Builder::new(), capacity),
+            block_starts: ListBuilder::with_capacity(Int32Builder::new(), capacity),
+            rest: StringBuilder::with_capacity(capacity, 0),
+            missing_as_empty: options.missing_as_empty.unwrap_or(false),
+            fields: options.fields.clone(),
+        })
+    }
+}
+
+impl BatchBuilder for BedBatchBuilder {
+    type Record<'a> = &'a BedRecord;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        self.chrom.append_value(&record.chrom);
+        // The positions were checked to fit when parsing.
+        let position = |position: u64| i64::try_from(position).unwrap_or(i64::MAX);
+        let start = |start: u64| {
+            self.coordinate_system
+                .start_from(CoordinateSystem::ZeroBasedHalfOpen, position(start))
+        };
+        self.start.append_value(start(record.start));
+        self.end.append_value(position(record.end));
+        let empty = self.missing_as_empty;
+        self.name
+            .append_option(record.name.as_deref().or(empty.then_some("")));
+        self.score
+            .append_option(record.score.or(empty.then_some(0)));
+        self.strand
+            .append_option(record.strand.as_deref().or(empty.then_some("")));
+        self.thick_start
+            .append_option(record.thick_start.map(start).or(empty.then_some(0)));
+        self.thick_end
+            .append_option(record.thick_end.map(position).or(empty.then_some(0)));
+        self.item_rgb.append_option(
+            record
+                .item_rgb
+                .as_deref()
+                .or(empty.then_some(&[]))
+                .map(|rgb| rgb.iter().copied().map(Some)),
+        );
+        self.block_count
+            .append_option(record.block_count.or(empty.then_some(0)));
+        self.block_sizes.append_option(
+            record
+                .block_sizes
+                .as_deref()
+                .or(empty.then_some(&[]))
+                .map(|sizes| sizes.iter().copied().map(Some)),
+        );
+        self.block_starts.append_option(
+            record
+                .block_starts
+                .as_deref()
+                .or(empty.then_some(&[]))
+                .map(|starts| starts.iter().copied().map(Some)),
+        );
+        self.rest
+            .append_option(record.rest.as_deref().or(empty.then_some("")));
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let optional = [
+            self.name.finish(),
+            Arc::new(self.score.finish()) as ArrayRef,
+            self.strand.finish(),
+            self.thick_start.finish()?,
+            self.thick_end.finish()?,
+            Arc::new(self.item_rgb.finish()) as ArrayRef,
+            Arc::new(self.block_count.finish()) as ArrayRef,
+            Arc::new(self.block_sizes.finish()) as ArrayRef,
+            Arc::new(self.block_starts.finish()) as ArrayRef,
+        ];
+        let batch = RecordBatch::try_from_iter(
+            vec![
+                ("chrom", Arc::new(self.chrom.finish()) as ArrayRef),
+                ("start", self.start.finish()?),
+                ("end", self.end.finish()?),
+            ]
+            .into_iter()
+            .chain(std::iter::zip(OPTIONAL_FIELDS, optional).take(self.bed_fields - 3))
+            .chain([("rest", Arc::new(self.rest.finish()) as ArrayRef)]),
+        )?;
+        select_fields(batch, self.fields.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch_builder::nullable_field;
+    use arrow::array::{
+        Array, Int32Array, Int64Array, ListArray, StringArray, UInt32Array, UInt8Array,
+    };
+    use arrow::datatypes::DataType;
+    use arrow::ipc::reader::FileReader;
+
+    fn read_ipc(ipc: Vec<u8>) -> RecordBatch {
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    fn column<T: 'static + Clone>(record_batch: &RecordBatch, name: &str) -> T {
+        record_batch
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<T>()
+            .unwrap()
+            .clone()
+    }
+
+    fn fixture_path() -> String {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bed");
+        dir.to_str().unwrap().to_string()
+    }
+
+    fn names(options: BedOptions) -> Vec<String> {
+        let mut reader = BedReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options.clone());
+        let record_batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let mut reader = BedReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        assert_eq!(reader.count(None).unwrap(), record_batch.num_rows());
+        let names: StringArray = column(&record_batch, "name");
+        names.iter().map(|name| name.unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_read_all() {
+        let mut reader = BedReader::new_from_path(&fixture_path()).unwrap();
+        let record_batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        assert_eq!(record_batch.num_rows(), 5);
+        let start: UInt32Array = column(&record_batch, "start");
+        let score: Int64Array = column(&record_batch, "score");
+        assert_eq!(start.values(), &[10, 100, 200, 50, 300]);
+        assert_eq!(score.values(), &[0, 500, 1000, 250, 750]);
+        assert_eq!(record_batch.column_by_name("rest").unwrap().null_count(), 5);
+    }
+
+    fn read_str(data: &str, bed_fields: Option<usize>) -> RecordBatch {
+        let options = BedOptions {
+            bed_fields,
+            ..Default::default()
+        };
+        let mut reader = BedReader::new(data.as_bytes())
+            .unwrap()
+            .with_options(options);
+        read_ipc(reader.records_to_ipc(None).unwrap())
+    }
+
+    fn column_names(record_batch: &RecordBatch) -> Vec<String> {
+        let schema = record_batch.schema();
+        schema.fields().iter().map(|f| f.name().clone()).collect()
+    }
+
+    #[test]
+    fn test_fields() {
+        let options = BedOptions {
+            fields: Some(vec!["name".into(), "chrom".into(), "start".into()]),
+            ..Default::default()
+        };
+        let mut reader = BedReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        let record_batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let schema = record_batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["name", "chrom", "start"]);
+        let fields = reader.fields().unwrap();
+        let field_names: Vec<_> = fields.iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(field_names, names);
+
+        let options = BedOptions {
+            fields: Some(vec!["chrom".into(), "blockCount".into()]),
+            bed_fields: Some(6),
+            ..Default::default()
+        };
+        let mut reader = BedReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        let error = reader.records_to_ipc(None).unwrap_err();
+        assert!(error.to_string().contains("Invalid field: blockCount"));
+        assert!(error.to_string().contains("chrom, start, end, name"));
+    }
+
+    #[test]
+    fn test_chroms() {
+        let data =
+            "track name=x\nsq2\t0\t10\nchr10\t5\t15\n# comment\nsq0\t0\t1\nsq2\t3\t4\nchr9\t0\t1\n";
+        let mut reader = BedReader::new(data.as_bytes()).unwrap();
+        assert_eq!(reader.chroms().unwrap(), ["chr10", "chr9", "sq0", "sq2"]);
+
+        // Indexed files list the names of the index.
+        let mut reader = BedReader::new_from_path(&fixture_path()).unwrap();
+        let expected = reader.chroms().unwrap();
+        assert_eq!(expected, ["sq0", "sq1", "sq2"]);
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../fixtures/sample.bed.gz");
+        let mut reader = BedReader::new_from_path(path.to_str().unwrap()).unwrap();
+        assert!(reader.index().is_some());
+        assert_eq!(reader.chroms().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_require_sorted() {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../fixtures/unsorted.bed");
+        let path = path.to_str().unwrap();
+        assert_eq!(
+            BedReader::new_from_path(path).unwrap().count(None).unwrap(),
+            4
+        );
+        let options = BedOptions {
+            require_sorted: Some(true),
+            on_error: Some(ErrorPolicy::Skip),
+            ..Default::default()
+        };
+        let mut reader = BedReader::new_from_path(path)
+            .unwrap()
+            .with_options(options.clone());
+        let error = reader.records_to_ipc(None).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("line 4 (sq0:30) comes after sq0:50"));
+
+        // Chromosomes must be contiguous, in any order.
+        let mut reader = BedReader::new("sq1\t0\t10\nsq0\t5\t15\nsq1\t20\t30\n".as_bytes())
+            .unwrap()
+            .with_options(options.clone());
+        assert!(reader.count(None).is_err());
+        let mut reader = BedReader::new("sq1\t0\t10\nsq0\t5\t15\n".as_bytes())
+            .unwrap()
+            .with_options(options);
+        assert_eq!(reader.count(None).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_subsample() {
+        let data = (0..1000)
+            .map(|i| format!("sq0\t{}\t{}\n", i, i + 1))
+            .collect::<Vec<_>>()
+            .concat();
+        let starts = |options: BedOptions| {
+            let mut reader = BedReader::new(data.as_bytes())
+                .unwrap()
+                .with_options(options);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let record_batch = arrow_reader.next().unwrap().unwrap();
+            column::<UInt32Array>(&record_batch, "start")
+                .values()
+                .to_vec()
+        };
+        let options = BedOptions {
+            sample_fraction: Some(0.2),
+            sample_seed: Some(42),
+            ..Default::default()
+        };
+        let sample = starts(options.clone());
+        assert_eq!(sample, starts(options));
+        assert!((150..250).contains(&sample.len()), "{}", sample.len());
+
+        let options = BedOptions {
+            sample_count: Some(10),
+            sample_seed: Some(42),
+            ..Default::default()
+        };
+        let sample = starts(options);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_scan_with() {
+        let data = "sq0\t0\t10\ta\t0\t+\n\
+            sq0\t20\t30\tb\t0\t-\n\
+            sq0\t40\t50\tc\t0\t+\n";
+        let read = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let ipc = BedReader::new(data.as_bytes())
+            .unwrap()
+            .scan_with(None, |record| record.strand.as_deref() == Some("+"))
+            .unwrap();
+        let record_batch = read(ipc);
+        assert_eq!(
+            column::<UInt32Array>(&record_batch, "start").values(),
+            &[0, 40]
+        );
+
+        let ipc = BedReader::new(data.as_bytes())
+            .unwrap()
+            .scan_map(None, |mut record| {
+                record.name = record.name.map(|name| name.to_uppercase());
+                (record.strand.as_deref() == Some("-")).then_some(record)
+            })
+            .unwrap();
+        let record_batch = read(ipc);
+        let name = column::<StringArray>(&record_batch, "name");
+        assert_eq!(name.iter().collect::<Vec<_>>(), [Some("B")]);
+    }
+
+    #[test]
+    fn test_read_bed3() {
+        let record_batch = read_str("sq0\t0\t10\nsq1\t5\t15\n", None);
+        assert_eq!(
+            column_names(&record_batch),
+            ["chrom", "start", "end", "rest"]
+        );
+        let end: UInt32Array = column(&record_batch, "end");
+        assert_eq!(end.values(), &[10, 15]);
+    }
+
+    #[test]
+    fn test_read_bed6() {
+        let record_batch = read_str("sq0\t0\t10\ta\t100\t+\n", None);
+        assert_eq!(
+            column_names(&record_batch),
+            ["chrom", "start", "end", "name", "score", "strand", "rest"]
+        );
+    }
+
+    #[test]
+    fn test_missing_as_empty() {
+        // A `.` score, and a short line missing its score and strand.
+        let data = "sq0\t0\t10\ta\t.\t+\nsq0\t5\t15\tb\nsq0\t5\t15\tc\t100\t-\n";
+        let read = |missing_as_empty| {
+            let options = BedOptions {
+                bed_fields: Some(6),
+                missing_as_empty,
+                ..Default::default()
+            };
+            let mut reader = BedReader::new(data.as_bytes())
+                .unwrap()
+                .with_options(options);
+            read_ipc(reader.records_to_ipc(None).unwrap())
+        };
+
+        let record_batch = read(None);
+        let score: Int64Array = column(&record_batch, "score");
+        assert_eq!(score.iter().collect::<Vec<_>>(), [None, None, Some(100)]);
+        let strand: StringArray = column(&record_batch, "strand");
+        assert_eq!(
+            strand.iter().collect::<Vec<_>>(),
+            [Some("+"), None, Some("-")]
+        );
+        assert_eq!(record_batch.column_by_name("rest").unwrap().null_count(), 3);
+
+        let record_batch = read(Some(true));
+        let score: Int64Array = column(&record_batch, "score");
+        assert_eq!(
+            score.iter().collect::<Vec<_>>(),
+            [Some(0), Some(0), Some(100)]
+        );
+        let strand: StringArray = column(&record_batch, "strand");
+        assert_eq!(
+            strand.iter().collect::<Vec<_>>(),
+            [Some("+"), Some(""), Some("-")]
+        );
+        let rest: StringArray = column(&record_batch, "rest");
+        assert_eq!(rest.null_count(), 0);
+        assert_eq!(rest.value(0), "");
+    }
+
+    #[test]
+    fn test_read_bed12() {
+        let data = "sq0\t0\t100\ta\t0\t+\t10\t90\t255,0,0\t2\t20,30,\t0,70,\n";
+        let record_batch = read_str(data, None);
+        assert_eq!(column_names(&record_batch).len(), 13);
+        let thick_start: UInt32Array = column(&record_batch, "thickStart");
+        let thick_end: UInt32Array = column(&record_batch, "thickEnd");
+        assert_eq!((thick_start.value(0), thick_end.value(0)), (10, 90));
+        let block_count: Int32Array = column(&record_batch, "blockCount");
+        assert_eq!(block_count.value(0), 2);
+        let item_rgb: ListArray = column(&record_batch, "itemRgb");
+        let item_rgb = item_rgb.value(0);
+        let item_rgb = item_rgb.as_any().downcast_ref::<UInt8Array>().unwrap();
+        assert_eq!(item_rgb.values(), &[255, 0, 0]);
+        for (name, expected) in [("blockSizes", [20, 30]), ("blockStarts", [0, 70])] {
+            let list: ListArray = column(&record_batch, name);
+            let values = list.value(0);
+            let values = values.as_any().downcast_ref::<Int32Array>().unwrap();
+            assert_eq!(values.values(), &expected);
+        }
+    }
+
+    #[test]
+    fn test_bed_fields() {
+        let data = "sq0\t0\t10\ta\t100\t+\textra\n";
+        let record_batch = read_str(data, Some(4));
+        assert_eq!(
+            column_names(&record_batch),
+            ["chrom", "start", "end", "name", "rest"]
+        );
+        let rest: StringArray = column(&record_batch, "rest");
+        assert_eq!(rest.value(0), "100\t+\textra");
+
+        // Fields beyond BED12 are kept in `rest`.
+        let data = "sq0\t0\t100\ta\t0\t+\t10\t90\t0\t1\t100\t0\tx\ty\n";
+        let record_batch = read_str(data, None);
+        let rest: StringArray = column(&record_batch, "rest");
+        assert_eq!(rest.value(0), "x\ty");
+
+        let options = BedOptions {
+            bed_fields: Some(13),
+            ..Default::default()
+        };
+        let mut reader = BedReader::new(data.as_bytes())
+            .unwrap()
+            .with_options(options);
+        assert!(reader.records_to_ipc(None).is_err());
+    }
+
+    #[test]
+    fn test_dictionary_encode() {
+        let options = BedOptions {
+            dictionary_encode: Some(vec!["strand".to_string()]),
+            ..Default::default()
+        };
+        let mut reader = BedReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        let record_batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let strand = record_batch.column_by_name("strand").unwrap();
+        assert_eq!(
+            strand.data_type(),
+            &DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+        );
+        let strand = arrow::compute::cast(strand, &DataType::Utf8).unwrap();
+        let plain = read_ipc(
+            BedReader::new_from_path(&fixture_path())
+                .unwrap()
+                .records_to_ipc(None)
+                .unwrap(),
+        );
+        assert_eq!(&strand, plain.column_by_name("strand").unwrap());
+        assert_eq!(
+            plain.column_by_name("name").unwrap().data_type(),
+            &DataType::Utf8
+        );
+
+        let options = BedOptions {
+            dictionary_encode: Some(vec!["score".to_string()]),
+            ..Default::default()
+        };
+        let mut reader = BedReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        assert!(reader.records_to_ipc(None).is_err());
+    }
+
+    #[test]
+    fn test_filters() {
+        let options = BedOptions {
+            names: Some(vec!["b".to_string(), "d".to_string(), "z".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(names(options), ["b", "d"]);
+        let options = BedOptions {
+            min_score: Some(250),
+            max_score: Some(750),
+            ..Default::default()
+        };
+        assert_eq!(names(options), ["b", "d", "e"]);
+        let options = BedOptions {
+            names: Some(vec!["a".to_string(), "b".to_string()]),
+            min_score: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(names(options), ["b"]);
+    }
+
+    #[test]
+    fn test_score_filter_without_score() {
+        let options = BedOptions {
+            min_score: Some(500),
+            bed_fields: Some(5),
+            ..Default::default()
+        };
+        let data = "sq0\t0\t10\nsq0\t5\t15\tx\t.\nsq0\t20\t30\ty\t100\n";
+        let mut reader = BedReader::new(data.as_bytes())
+            .unwrap()
+            .with_options(options);
+        let record_batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let start: UInt32Array = column(&record_batch, "start");
+        assert_eq!(start.values(), &[0, 5]);
+        assert_eq!(
+            record_batch.column_by_name("score").unwrap().null_count(),
+            2
+        );
+    }
+
+    fn query_names(region: &str, query_mode: QueryMode) -> Vec<String> {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bed.gz");
+        let reader = || {
+            BedReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_query_mode(query_mode)
+        };
+        let ipc = reader().records_to_ipc(Some(region)).unwrap();
+        let names: Vec<String> = FileReader::try_new(std::io::Cursor::new(ipc), None)
+            .unwrap()
+            .flat_map(|record_batch| {
+                let names: StringArray = column(&record_batch.unwrap(), "name");
+                names
+                    .iter()
+                    .map(|name| name.unwrap().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(reader().count(Some(region)).unwrap(), names.len());
+        names
+    }
+
+    #[test]
+    fn test_query() {
+        assert_eq!(query_names("sq0", QueryMode::Overlap), ["a", "b"]);
+        assert_eq!(query_names("sq1:1-100", QueryMode::Overlap), ["d"]);
+        // Coordinates are 0-based, half-open: `a` spans 11-20 and `b` 101-150.
+        assert_eq!(query_names("sq0:20-101", QueryMode::Overlap), ["a", "b"]);
+        assert!(query_names("sq0:21-100", QueryMode::Overlap).is_empty());
+
+        let mut reader = BedReader::new_from_path(&fixture_path()).unwrap();
+        assert!(reader.records_to_ipc(Some("sq0")).is_err());
+    }
+
+    #[test]
+    fn test_query_mode() {
+        assert_eq!(query_names("sq0:15-120", QueryMode::Overlap), ["a", "b"]);
+        assert!(query_names("sq0:15-120", QueryMode::Contained).is_empty());
+        assert_eq!(query_names("sq0:11-150", QueryMode::Contained), ["a", "b"]);
+        assert_eq!(query_names("sq0:11-149", QueryMode::Contained), ["a"]);
+        assert_eq!(query_names("sq0:12-150", QueryMode::Contained), ["b"]);
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let mut reader = BedReader::new("sq0\t0\n".as_bytes()).unwrap();
+        assert!(reader.records_to_ipc(None).is_err());
+        let mut reader = BedReader::new("sq0\t0\t10\tx\thigh\n".as_bytes()).unwrap();
+        assert!(reader.records_to_ipc(None).is_err());
+    }
+
+    /// Returns the names and types of a list of fields, with nested fields made nullable.
+    fn name_types(fields: &arrow::datatypes::Fields) -> Vec<(String, DataType)> {
+        fields
+            .iter()
+            .map(|field| nullable_field(field))
+            .map(|field| (field.name().clone(), field.data_type().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_fields_match_records() {
+        let options = BedOptions {
+            dictionary_encode: Some(vec!["name".into()]),
+            ..Default::default()
+        };
+        let mut reader = BedReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        let fields = reader.fields().unwrap();
+        let record_batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        assert_eq!(
+            name_types(&fields),
+            name_types(record_batch.schema().fields())
+        );
+
+        // Streams can't be peeked at, so they default to BED3.
+        let stream = std::io::Cursor::new("chr1\t0\t10\tname\n");
+        let reader = BedReader::new(stream).unwrap();
+        let names: Vec<_> = reader
+            .fields()
+            .unwrap()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(names, ["chrom", "start", "end", "rest"]);
+    }
+
+    #[test]
+    fn test_error_policy() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/malformed.bed");
+        let reader = |on_error| {
+            let options = BedOptions {
+                on_error: Some(on_error),
+                ..Default::default()
+            };
+            BedReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_options(options)
+        };
+        assert!(reader(ErrorPolicy::Fail).records_to_ipc(None).is_err());
+
+        let mut skipping = reader(ErrorPolicy::Skip);
+        let record_batch = read_ipc(skipping.records_to_ipc(None).unwrap());
+        let names: StringArray = column(&record_batch, "name");
+        assert_eq!(names.iter().collect::<Vec<_>>(), [Some("a"), Some("c")]);
+        assert!(skipping.errors().is_empty());
+
+        let mut collecting = reader(ErrorPolicy::SkipAndCollect);
+        assert_eq!(collecting.count(None).unwrap(), 2);
+        let errors = collecting.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+        assert!(errors[0].message.contains("sq0\tten"));
+    }
+}
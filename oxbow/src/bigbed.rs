@@ -9,16 +9,18 @@ use arrow::datatypes::Int32Type;
 use arrow::{error::ArrowError, record_batch::RecordBatch};
 use bigtools::utils::reopen::ReopenableFile;
 use bigtools::BigBedRead;
-use noodles::core::Region;
 use std::collections::HashSet;
 use std::io::{Read, Seek};
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::batch_builder::{finish_batch, BatchBuilder};
+use crate::batch_builder::{finish_batch, provenance, BatchBuilder};
+use crate::bbi::interval_from_region;
 
 /// A BigBed reader.
 pub struct BigBedReader<R> {
     read: BigBedRead<R>,
+    path: Option<PathBuf>,
 }
 
 pub struct BigBedRecord<'a> {
@@ -32,7 +34,10 @@ impl BigBedReader<ReopenableFile> {
     pub fn new_from_path(path: &str) -> std::io::Result<Self> {
         let read = BigBedRead::open_file(path)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-        Ok(Self { read })
+        Ok(Self {
+            read,
+            path: Some(PathBuf::from(path)),
+        })
     }
 }
 impl<R: Read + Seek> BigBedReader<R> {
@@ -40,7 +45,7 @@ impl<R: Read + Seek> BigBedReader<R> {
     pub fn new(read: R) -> std::io::Result<Self> {
         let read = BigBedRead::open(read)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-        Ok(Self { read })
+        Ok(Self { read, path: None })
     }
 
     /// Returns the records in the given region as Apache Arrow IPC.
@@ -60,49 +65,17 @@ impl<R: Read + Seek> BigBedReader<R> {
         region: Option<&str>,
         fields: Option<HashSet<&str>>,
     ) -> Result<Vec<u8>, ArrowError> {
-        let mut batch_builder = BigBedBatchBuilder::new(1024, &mut self.read, fields)?;
+        let mut batch_builder = BigBedBatchBuilder::new(1024, &mut self.read, fields)?
+            .with_metadata(provenance(
+                "bigbed",
+                self.path.as_deref(),
+                region.as_slice(),
+            ));
         match region {
             Some(region) => {
-                let region: Region = region.parse().unwrap();
-                let chrom_name = region.name().to_owned();
-                let (start, end) = match (region.interval().start(), region.interval().end()) {
-                    (Some(start), Some(end)) => {
-                        let start = start.get() as u32 - 1; // 1-based to 0-based
-                        let end = end.get() as u32;
-                        (start, end)
-                    }
-                    (Some(start), None) => {
-                        let start = start.get() as u32 - 1; // 1-based to 0-based
-                        let end = self
-                            .read
-                            .chroms()
-                            .iter()
-                            .find(|c| c.name == chrom_name)
-                            .map(|c| c.length);
-                        let end = end.ok_or_else(|| {
-                            ArrowError::InvalidArgumentError("Invalid chromosome".to_string())
-                        })?;
-                        (start, end)
-                    }
-                    (None, Some(end)) => {
-                        let start = 0;
-                        let end = end.get() as u32;
-                        (start, end)
-                    }
-                    (None, None) => {
-                        let start = 0;
-                        let end = self
-                            .read
-                            .chroms()
-                            .iter()
-                            .find(|c| c.name == chrom_name)
-                            .map(|c| c.length);
-                        let end = end.ok_or_else(|| {
-                            ArrowError::InvalidArgumentError("Invalid chromosome".to_string())
-                        })?;
-                        (start, end)
-                    }
-                };
+                let chroms = self.read.chroms().iter();
+                let (chrom_name, start, end) =
+                    interval_from_region(region, chroms.map(|c| (c.name.as_str(), c.length)))?;
                 let values = match self.read.get_interval(&chrom_name, start, end) {
                     Ok(v) => v,
                     Err(e) => {
@@ -110,7 +83,7 @@ impl<R: Read + Seek> BigBedReader<R> {
                     }
                 };
                 for value in values {
-                    let v = value.unwrap();
+                    let v = value.map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                     let record = BigBedRecord {
                         chrom: &chrom_name,
                         start: v.start,
@@ -127,9 +100,12 @@ impl<R: Read + Seek> BigBedReader<R> {
                 for chrom in chroms {
                     let start = 0;
                     let end = chrom.length;
-                    let values = self.read.get_interval(&chrom.name, start, end).unwrap();
+                    let values = self
+                        .read
+                        .get_interval(&chrom.name, start, end)
+                        .map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                     for value in values {
-                        let v = value.unwrap();
+                        let v = value.map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                         let record = BigBedRecord {
                             chrom: &chrom.name,
                             start: v.start,
@@ -177,8 +153,11 @@ impl BigBedBatchBuilder {
     ) -> Result<Self, ArrowError> {
         let chroms: Vec<String> = read.chroms().iter().map(|c| c.name.clone()).collect();
         let chroms: StringArray = StringArray::from(chroms);
-        let autosql = read.autosql().unwrap();
-        let mut declarations = bigtools::bed::autosql::parse::parse_autosql(&autosql).unwrap();
+        let autosql = read
+            .autosql()
+            .map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
+        let mut declarations = bigtools::bed::autosql::parse::parse_autosql(&autosql)
+            .map_err(|e| ArrowError::ParseError(format!("Invalid autoSql: {:?}", e)))?;
         if declarations.len() > 1 {
             panic!("Unexpected extra declarations");
         }
@@ -374,4 +353,12 @@ mod tests {
         let record_batch = read_record_batch(Some("chr17:100000-150000"));
         assert_eq!(record_batch.num_rows(), 3);
     }
+
+    #[test]
+    fn test_region_unknown_chrom() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/small.bigBed");
+        let mut reader = BigBedReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        assert!(reader.records_to_ipc(Some("chrZZ:1-100"), None).is_err());
+    }
 }
@@ -6,16 +6,18 @@ use arrow::datatypes::Int32Type;
 use arrow::{error::ArrowError, record_batch::RecordBatch};
 use bigtools::utils::reopen::ReopenableFile;
 use bigtools::{BigWigRead, Summary};
-use noodles::core::Region;
 use std::collections::HashSet;
 use std::io::{Read, Seek};
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::batch_builder::{finish_batch, BatchBuilder};
+use crate::batch_builder::{finish_batch, provenance, BatchBuilder};
+use crate::bbi::interval_from_region;
 
 /// A BigWig reader.
 pub struct BigWigReader<R> {
     read: BigWigRead<R>,
+    path: Option<PathBuf>,
 }
 
 pub struct BigWigRecord<'a, Value> {
@@ -30,7 +32,10 @@ impl BigWigReader<ReopenableFile> {
     pub fn new_from_path(path: &str) -> std::io::Result<Self> {
         let read = BigWigRead::open_file(path)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-        Ok(Self { read })
+        Ok(Self {
+            read,
+            path: Some(PathBuf::from(path)),
+        })
     }
 }
 
@@ -39,50 +44,12 @@ impl<R: Read + Seek> BigWigReader<R> {
     pub fn new(read: R) -> std::io::Result<Self> {
         let read = BigWigRead::open(read)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-        Ok(Self { read })
+        Ok(Self { read, path: None })
     }
 
     fn start_end_from_region(&mut self, region: &str) -> Result<(String, u32, u32), ArrowError> {
-        let region: Region = region.parse().unwrap();
-        let chrom_name = region.name().to_owned();
-        Ok(match (region.interval().start(), region.interval().end()) {
-            (Some(start), Some(end)) => {
-                let start = start.get() as u32 - 1; // 1-based to 0-based
-                let end = end.get() as u32;
-                (chrom_name, start, end)
-            }
-            (Some(start), None) => {
-                let start = start.get() as u32 - 1; // 1-based to 0-based
-                let end = self
-                    .read
-                    .chroms()
-                    .iter()
-                    .find(|c| c.name == chrom_name)
-                    .map(|c| c.length);
-                let end = end.ok_or_else(|| {
-                    ArrowError::InvalidArgumentError("Invalid chromosome".to_string())
-                })?;
-                (chrom_name, start, end)
-            }
-            (None, Some(end)) => {
-                let start = 0;
-                let end = end.get() as u32;
-                (chrom_name, start, end)
-            }
-            (None, None) => {
-                let start = 0;
-                let end = self
-                    .read
-                    .chroms()
-                    .iter()
-                    .find(|c| c.name == chrom_name)
-                    .map(|c| c.length);
-                let end = end.ok_or_else(|| {
-                    ArrowError::InvalidArgumentError("Invalid chromosome".to_string())
-                })?;
-                (chrom_name, start, end)
-            }
-        })
+        let chroms = self.read.chroms().iter();
+        interval_from_region(region, chroms.map(|c| (c.name.as_str(), c.length)))
     }
 
     /// Returns the records in the given region as Apache Arrow IPC.
@@ -100,7 +67,12 @@ impl<R: Read + Seek> BigWigReader<R> {
     pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
         let capacity = 1024;
         let mut batch_builder =
-            BigWigBatchBuilder::new(capacity, Float32Array::builder(capacity), &mut self.read)?;
+            BigWigBatchBuilder::new(capacity, Float32Array::builder(capacity), &mut self.read)?
+                .with_metadata(provenance(
+                    "bigwig",
+                    self.path.as_deref(),
+                    region.as_slice(),
+                ));
         match region {
             Some(region) => {
                 let (chrom_name, start, end) = self.start_end_from_region(region)?;
@@ -109,7 +81,7 @@ impl<R: Read + Seek> BigWigReader<R> {
                     .get_interval(&chrom_name, start, end)
                     .map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                 for value in values {
-                    let v = value.unwrap();
+                    let v = value.map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                     let record = BigWigRecord {
                         chrom: &chrom_name,
                         start: v.start,
@@ -125,9 +97,12 @@ impl<R: Read + Seek> BigWigReader<R> {
                 for chrom in chroms {
                     let start = 0;
                     let end = chrom.length;
-                    let values = self.read.get_interval(&chrom.name, start, end).unwrap();
+                    let values = self
+                        .read
+                        .get_interval(&chrom.name, start, end)
+                        .map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                     for value in values {
-                        let v = value.unwrap();
+                        let v = value.map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                         let record = BigWigRecord {
                             chrom: &chrom.name,
                             start: v.start,
@@ -196,7 +171,12 @@ impl<R: Read + Seek> BigWigReader<R> {
                 .map_or(true, |c| c.contains("sum_Squares"))
                 .then(|| Float64Array::builder(capacity)),
         );
-        let mut batch_builder = BigWigBatchBuilder::new(capacity, builder, &mut self.read)?;
+        let mut batch_builder = BigWigBatchBuilder::new(capacity, builder, &mut self.read)?
+            .with_metadata(provenance(
+                "bigwig",
+                self.path.as_deref(),
+                region.as_slice(),
+            ));
         match region {
             Some(region) => {
                 let (chrom_name, start, end) = self.start_end_from_region(region)?;
@@ -205,7 +185,7 @@ impl<R: Read + Seek> BigWigReader<R> {
                     .get_zoom_interval(&chrom_name, start, end, zoom_level)
                     .map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                 for value in values {
-                    let v = value.unwrap();
+                    let v = value.map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                     let record = BigWigRecord {
                         chrom: &chrom_name,
                         start: v.start,
@@ -232,7 +212,7 @@ impl<R: Read + Seek> BigWigReader<R> {
                             }
                         };
                     for value in values {
-                        let v = value.unwrap();
+                        let v = value.map_err(|e| ArrowError::ExternalError(Box::new(e)))?;
                         let record = BigWigRecord {
                             chrom: &chrom.name,
                             start: v.start,
@@ -432,4 +412,15 @@ mod tests {
         let record_batch = read_record_batch_zoom(Some("chr17:59000-60000"));
         assert_eq!(record_batch.num_rows(), 1);
     }
+
+    #[test]
+    fn test_region_unknown_chrom() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/valid.bigWig");
+        let mut reader = BigWigReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        assert!(reader.records_to_ipc(Some("chrZZ:1-100")).is_err());
+        assert!(reader
+            .zoom_records_to_ipc(Some("chrZZ"), 10240, None)
+            .is_err());
+    }
 }
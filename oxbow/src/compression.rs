@@ -0,0 +1,199 @@
+//! Detection of gzip and BGZF compression from a file's magic bytes, and BGZF reading of streams.
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use flate2::read::MultiGzDecoder;
+use flate2::write::DeflateEncoder;
+use noodles::bgzf;
+
+/// The compression of a stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Compression {
+    None,
+    /// Plain (possibly multi-member) gzip, which can only be read sequentially.
+    Gzip,
+    /// Blocked gzip, which supports random access with an index.
+    Bgzf,
+}
+
+impl Compression {
+    /// Detects the compression of a stream without consuming any of it.
+    pub fn detect<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        let buf = reader.fill_buf()?;
+        if !buf.starts_with(&[0x1f, 0x8b, 0x08]) {
+            return Ok(Self::None);
+        }
+        // A BGZF block is a gzip member whose first extra subfield is `BC`.
+        const FEXTRA: u8 = 0x04;
+        if buf.len() >= 14 && buf[3] & FEXTRA != 0 && &buf[12..14] == b"BC" {
+            Ok(Self::Bgzf)
+        } else {
+            Ok(Self::Gzip)
+        }
+    }
+}
+
+/// Wraps a reader in a decoder matching its detected compression.
+///
+/// Uncompressed streams are passed through unchanged.
+pub fn decompress<R>(mut reader: R) -> io::Result<Box<dyn BufRead>>
+where
+    R: BufRead + 'static,
+{
+    Ok(match Compression::detect(&mut reader)? {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Bgzf => Box::new(bgzf::Reader::new(reader)),
+    })
+}
+
+/// The most data in a BGZF block, as written by `bgzip`.
+const MAX_BLOCK_DATA: usize = 0xff00;
+
+/// The empty block ending a BGZF file.
+const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// A stream that can't seek, e.g. standard input, read as BGZF, for readers that expect BGZF
+/// files.
+///
+/// The stream is decompressed as with [`decompress`] and split again into BGZF blocks as it is
+/// read, stored without compression. Seeking fails, so region queries and virtual offsets are
+/// unavailable.
+pub struct BgzfStream {
+    inner: Box<dyn BufRead>,
+    block: Vec<u8>,
+    /// The position in `block` of the next byte to read.
+    pos: usize,
+    done: bool,
+}
+
+impl BgzfStream {
+    pub fn new<R: BufRead + 'static>(read: R) -> io::Result<Self> {
+        Ok(Self {
+            inner: decompress(read)?,
+            block: Vec::new(),
+            pos: 0,
+            done: false,
+        })
+    }
+
+    /// Reads the next block, or the EOF block at the end of the stream. Returns false after it.
+    fn next_block(&mut self) -> io::Result<bool> {
+        if self.done {
+            return Ok(false);
+        }
+        let mut data = Vec::with_capacity(MAX_BLOCK_DATA);
+        (&mut self.inner)
+            .take(MAX_BLOCK_DATA as u64)
+            .read_to_end(&mut data)?;
+        self.block.clear();
+        if data.is_empty() {
+            self.block.extend_from_slice(&BGZF_EOF);
+            self.done = true;
+        } else {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::none());
+            encoder.write_all(&data)?;
+            let cdata = encoder.finish()?;
+            let mut crc = flate2::Crc::new();
+            crc.update(&data);
+            // The gzip header with the `BC` subfield holding the block size minus 1.
+            self.block
+                .extend_from_slice(&[0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0]);
+            self.block.extend_from_slice(b"BC");
+            self.block.write_u16::<LittleEndian>(2)?;
+            self.block
+                .write_u16::<LittleEndian>((18 + cdata.len() + 8 - 1) as u16)?;
+            self.block.extend_from_slice(&cdata);
+            self.block.write_u32::<LittleEndian>(crc.sum())?;
+            self.block.write_u32::<LittleEndian>(data.len() as u32)?;
+        }
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+impl Read for BgzfStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.block.len() && !self.next_block()? {
+            return Ok(0);
+        }
+        let n = buf.len().min(self.block.len() - self.pos);
+        buf[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Seek for BgzfStream {
+    fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Seeking requires seekable input, not a stream.",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    const DATA: &[u8] = b">sq0\nACGT\n";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn bgzip(data: &[u8]) -> Vec<u8> {
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        let detect = |data: &[u8]| Compression::detect(&mut &data[..]).unwrap();
+        assert_eq!(detect(DATA), Compression::None);
+        assert_eq!(detect(b""), Compression::None);
+        assert_eq!(detect(&gzip(DATA)), Compression::Gzip);
+        assert_eq!(detect(&bgzip(DATA)), Compression::Bgzf);
+    }
+
+    #[test]
+    fn test_decompress() {
+        for data in [DATA.to_vec(), gzip(DATA), bgzip(DATA)] {
+            let mut buf = Vec::new();
+            decompress(std::io::Cursor::new(data))
+                .unwrap()
+                .read_to_end(&mut buf)
+                .unwrap();
+            assert_eq!(buf, DATA);
+        }
+    }
+
+    #[test]
+    fn test_bgzf_stream() {
+        let data = DATA.repeat(20_000);
+        for input in [data.clone(), gzip(&data), bgzip(&data)] {
+            let mut stream = BgzfStream::new(std::io::Cursor::new(input)).unwrap();
+            let mut blocks = Vec::new();
+            stream.read_to_end(&mut blocks).unwrap();
+            assert_eq!(
+                Compression::detect(&mut &blocks[..]).unwrap(),
+                Compression::Bgzf
+            );
+            assert!(blocks.ends_with(&BGZF_EOF));
+            let mut buf = Vec::new();
+            bgzf::Reader::new(&blocks[..])
+                .read_to_end(&mut buf)
+                .unwrap();
+            assert_eq!(buf, data);
+            assert!(stream.seek(SeekFrom::Start(0)).is_err());
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! Normalizing position columns across formats.
+//!
+//! SAM, VCF, GFF and GTF use 1-based, closed coordinates, while BED uses 0-based, half-open
+//! ones. The same interval has the same end in both, so only start positions are adjusted:
+//!
+//! | Format      | Native    | Adjusted columns                 |
+//! |-------------|-----------|----------------------------------|
+//! | BAM, CRAM   | 1-based   | `pos`, `pnext`, `window_start`   |
+//! | VCF, BCF    | 1-based   | `pos`                            |
+//! | GFF, GTF    | 1-based   | `start`                          |
+//! | BED         | 0-based   | `start`, `thickStart`            |
+//!
+//! `end` columns and BED `blockStarts`, which are relative to `start`, are unchanged. Query
+//! regions are always given as 1-based, closed intervals.
+//!
+//! The position columns are typed so that positions on large genomes, with chromosomes longer
+//! than 2^31 bases, don't overflow:
+//!
+//! | Format      | Type                                                 |
+//! |-------------|------------------------------------------------------|
+//! | BAM, CRAM   | Int32, or UInt32 with `unsigned_positions`           |
+//! | VCF, BCF    | Int32, or UInt32 with `unsigned_positions`           |
+//! | GFF, GTF    | Int64                                                |
+//! | BED         | UInt32 as in bigBed, or UInt64 with `wide_positions` |
+//!
+//! The BAM and VCF columns are widened to 64 bits when a reference sequence of the header is
+//! longer than 2^31 - 1 bases. Otherwise, a position that doesn't fit, e.g. in a VCF whose header
+//! doesn't declare contig lengths, fails the scan rather than wrapping.
+use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Int32Builder, Int64Builder, UInt32Builder, UInt64Builder};
+use arrow::error::ArrowError;
+
+/// The coordinate convention of the position columns.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CoordinateSystem {
+    /// 1-based, closed intervals, as in SAM, VCF and GFF.
+    OneBased,
+    /// 0-based, half-open intervals, as in BED.
+    ZeroBasedHalfOpen,
+}
+
+impl CoordinateSystem {
+    /// Converts a start position from `native`, the coordinate system of a format, to this one.
+    pub fn start_from(self, native: Self, start: i64) -> i64 {
+        match (native, self) {
+            (Self::OneBased, Self::ZeroBasedHalfOpen) => start - 1,
+            (Self::ZeroBasedHalfOpen, Self::OneBased) => start + 1,
+            _ => start,
+        }
+    }
+
+    /// Converts a start position from `native` to this coordinate system, failing if it doesn't
+    /// fit in a `UInt32` column, as a BED start of 2^32 - 1 doesn't once 1-based.
+    pub(crate) fn start_from_u32(self, native: Self, start: u32) -> io::Result<u32> {
+        u32::try_from(self.start_from(native, start.into())).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid start: {}. Expected a converted start from 0 to {}.",
+                    start,
+                    u32::MAX
+                ),
+            )
+        })
+    }
+}
+
+impl FromStr for CoordinateSystem {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1-based" => Ok(Self::OneBased),
+            "0-based" => Ok(Self::ZeroBasedHalfOpen),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid coordinate system: {}. Expected 1-based or 0-based.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// Returns whether positions on reference sequences of these lengths need 64-bit columns.
+pub(crate) fn needs_wide_positions(mut lengths: impl Iterator<Item = usize>) -> bool {
+    lengths.any(|length| length > i32::MAX as usize)
+}
+
+/// Builds a position column, signed or unsigned and of 32 or 64 bits.
+///
+/// A position that doesn't fit in a 32-bit column, e.g. on a contig whose length the header
+/// doesn't declare, fails the batch in [`PositionBuilder::finish`] rather than wrapping.
+pub(crate) struct PositionBuilder {
+    column: PositionColumn,
+    overflow: Option<i64>,
+}
+
+enum PositionColumn {
+    Int32(Int32Builder),
+    UInt32(UInt32Builder),
+    Int64(Int64Builder),
+    UInt64(UInt64Builder),
+}
+
+impl PositionBuilder {
+    pub fn new(capacity: usize, unsigned: bool, wide: bool) -> Self {
+        let column = match (unsigned, wide) {
+            (false, false) => PositionColumn::Int32(Int32Builder::with_capacity(capacity)),
+            (true, false) => PositionColumn::UInt32(UInt32Builder::with_capacity(capacity)),
+            (false, true) => PositionColumn::Int64(Int64Builder::with_capacity(capacity)),
+            (true, true) => PositionColumn::UInt64(UInt64Builder::with_capacity(capacity)),
+        };
+        Self {
+            column,
+            overflow: None,
+        }
+    }
+
+    pub fn append_option(&mut self, position: Option<i64>) {
+        let overflow = &mut self.overflow;
+        match &mut self.column {
+            PositionColumn::Int32(builder) => builder.append_option(narrow(position, overflow)),
+            PositionColumn::UInt32(builder) => builder.append_option(narrow(position, overflow)),
+            PositionColumn::Int64(builder) => builder.append_option(position),
+            PositionColumn::UInt64(builder) => builder.append_option(narrow(position, overflow)),
+        }
+    }
+
+    pub fn append_value(&mut self, position: i64) {
+        self.append_option(Some(position));
+    }
+
+    pub fn finish(&mut self) -> Result<ArrayRef, ArrowError> {
+        if let Some(position) = self.overflow.take() {
+            let data_type = match self.column {
+                PositionColumn::Int32(_) => "Int32",
+                PositionColumn::UInt32(_) => "UInt32",
+                PositionColumn::Int64(_) => "Int64",
+                PositionColumn::UInt64(_) => "UInt64",
+            };
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "Invalid position: {}. Expected a position that fits in the {} column, as sized \
+                 after the reference sequence lengths of the header.",
+                position, data_type
+            )));
+        }
+        Ok(match &mut self.column {
+            PositionColumn::Int32(builder) => Arc::new(builder.finish()),
+            PositionColumn::UInt32(builder) => Arc::new(builder.finish()),
+            PositionColumn::Int64(builder) => Arc::new(builder.finish()),
+            PositionColumn::UInt64(builder) => Arc::new(builder.finish()),
+        })
+    }
+}
+
+/// Converts a position to the type of a narrower column, recording the first position that
+/// doesn't fit in `overflow` and appending a null for it.
+fn narrow<T: TryFrom<i64>>(position: Option<i64>, overflow: &mut Option<i64>) -> Option<T> {
+    let position = position?;
+    T::try_from(position)
+        .map_err(|_| overflow.get_or_insert(position))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{Int64Type, UInt32Type, UInt64Type};
+    use arrow::ipc::reader::FileReader;
+    use arrow::record_batch::RecordBatch;
+    use std::io::Cursor;
+
+    use crate::bed::{BedOptions, BedReader};
+    use crate::error_policy::ErrorPolicy;
+    use crate::gff::GffReader;
+    use crate::gtf::GtfReader;
+    use crate::peak::{PeakFormat, PeakOptions, PeakReader};
+
+    fn read_ipc(ipc: Vec<u8>) -> RecordBatch {
+        let mut reader = FileReader::try_new(Cursor::new(ipc), None).unwrap();
+        reader.next().unwrap().unwrap()
+    }
+
+    /// Returns the `start` and `end` of the BED interval `chr1:100-200` and the equivalent GFF
+    /// feature, in the given coordinate system.
+    fn read_intervals(coordinate_system: CoordinateSystem) -> [(i64, i64); 2] {
+        let bed = Cursor::new("chr1\t99\t200\n");
+        let options = BedOptions {
+            coordinate_system: Some(coordinate_system),
+            ..Default::default()
+        };
+        let mut reader = BedReader::new(bed).unwrap().with_options(options);
+        let batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let bed_start = batch.column_by_name("start").unwrap();
+        let bed_end = batch.column_by_name("end").unwrap();
+        let bed = (
+            bed_start.as_primitive::<UInt32Type>().value(0) as i64,
+            bed_end.as_primitive::<UInt32Type>().value(0) as i64,
+        );
+
+        let gff = Cursor::new("chr1\t.\tgene\t100\t200\t.\t+\t.\tID=g1\n");
+        let mut reader = GffReader::new(gff)
+            .unwrap()
+            .with_coordinate_system(coordinate_system);
+        let batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let gff_start = batch.column_by_name("start").unwrap();
+        let gff_end = batch.column_by_name("end").unwrap();
+        assert_eq!(gff_start.null_count(), 0);
+        let gff = (
+            gff_start.as_primitive::<Int64Type>().value(0),
+            gff_end.as_primitive::<Int64Type>().value(0),
+        );
+        [bed, gff]
+    }
+
+    #[test]
+    fn test_bed_and_gff_agree() {
+        assert_eq!(
+            read_intervals(CoordinateSystem::OneBased),
+            [(100, 200), (100, 200)]
+        );
+        assert_eq!(
+            read_intervals(CoordinateSystem::ZeroBasedHalfOpen),
+            [(99, 200), (99, 200)]
+        );
+    }
+
+    #[test]
+    fn test_large_coordinates() {
+        let (start, end) = (3_000_000_000, 3_000_000_100);
+        let bed = format!("chr1\t{}\t{}\n", start - 1, end);
+        let mut reader = BedReader::new(Cursor::new(bed)).unwrap();
+        let batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let column = |name| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_primitive::<UInt32Type>()
+        };
+        assert_eq!(column("start").value(0) as i64, start - 1);
+        assert_eq!(column("end").value(0) as i64, end);
+
+        // A start of 2^32 - 1 doesn't fit in the UInt32 column once 1-based, so it fails rather
+        // than wrapping to 0, or is skipped.
+        let max = u32::MAX;
+        let bed = format!("chr1\t0\t10\nchr1\t{max}\t{max}\n");
+        let reader = |on_error| {
+            BedReader::new(Cursor::new(bed.clone()))
+                .unwrap()
+                .with_options(BedOptions {
+                    coordinate_system: Some(CoordinateSystem::OneBased),
+                    on_error: Some(on_error),
+                    ..Default::default()
+                })
+        };
+        let error = reader(ErrorPolicy::Fail).records_to_ipc(None).unwrap_err();
+        assert!(error.to_string().contains("Invalid position: 4294967295"));
+        let mut skipping = reader(ErrorPolicy::SkipAndCollect);
+        let batch = read_ipc(skipping.records_to_ipc(None).unwrap());
+        let starts = batch
+            .column_by_name("start")
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+        assert_eq!(starts.values(), &[1]);
+        assert_eq!(skipping.errors().len(), 1);
+        // In 0-based coordinates it is kept as is.
+        let mut reader = BedReader::new(Cursor::new(bed.clone())).unwrap();
+        let batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let starts = batch
+            .column_by_name("start")
+            .unwrap()
+            .as_primitive::<UInt32Type>();
+        assert_eq!(starts.values(), &[0, max]);
+
+        // Coordinates past 2^32 need the UInt64 columns of `wide_positions`.
+        let (wide_start, wide_end) = (5_000_000_000u64, 5_000_000_100u64);
+        let bed = format!("chr1\t{wide_start}\t{wide_end}\tx\t0\t+\t{wide_start}\t{wide_end}\n");
+        let reader = |wide_positions| {
+            BedReader::new(Cursor::new(bed.clone()))
+                .unwrap()
+                .with_options(BedOptions {
+                    coordinate_system: Some(CoordinateSystem::OneBased),
+                    wide_positions: Some(wide_positions),
+                    ..Default::default()
+                })
+        };
+        let error = reader(false).records_to_ipc(None).unwrap_err();
+        assert!(error.to_string().contains("Invalid position: 5000000000"));
+        let mut reader = reader(true);
+        let fields = reader.fields().unwrap();
+        let batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let column = |name| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_primitive::<UInt64Type>()
+                .value(0)
+        };
+        assert_eq!(column("start"), wide_start + 1);
+        assert_eq!(column("end"), wide_end);
+        assert_eq!(column("thickStart"), wide_start + 1);
+        assert_eq!(column("thickEnd"), wide_end);
+        assert_eq!(
+            fields.find("start").unwrap().1.data_type(),
+            &arrow::datatypes::DataType::UInt64
+        );
+
+        let peak = format!("chr1\t{max}\t{max}\t.\t0\t.\t1\t-1\t-1\t-1\n");
+        let mut reader = PeakReader::new(Cursor::new(peak), PeakFormat::Narrow)
+            .unwrap()
+            .with_options(PeakOptions {
+                coordinate_system: Some(CoordinateSystem::OneBased),
+                ..Default::default()
+            });
+        let error = reader.records_to_ipc(None).unwrap_err();
+        assert!(error.to_string().contains("Invalid start: 4294967295"));
+
+        let gff = format!("chr1\t.\tgene\t{}\t{}\t.\t+\t.\tID=g1\n", start, end);
+        let mut reader = GffReader::new(Cursor::new(gff)).unwrap();
+        let gff = read_ipc(reader.records_to_ipc(None).unwrap());
+        let gtf = format!(
+            "chr1\t.\tgene\t{}\t{}\t.\t+\t.\tgene_id \"g1\";\n",
+            start, end
+        );
+        let mut reader = GtfReader::new(Cursor::new(gtf)).unwrap();
+        let gtf = read_ipc(reader.records_to_ipc(None).unwrap());
+        for batch in [gff, gtf] {
+            let column = |name| {
+                batch
+                    .column_by_name(name)
+                    .unwrap()
+                    .as_primitive::<Int64Type>()
+            };
+            assert_eq!(column("start").value(0), start);
+            assert_eq!(column("end").value(0), end);
+        }
+    }
+
+    #[test]
+    fn test_start_from() {
+        use CoordinateSystem::*;
+        assert_eq!(ZeroBasedHalfOpen.start_from(OneBased, 10), 9);
+        assert_eq!(OneBased.start_from(ZeroBasedHalfOpen, 9), 10);
+        assert_eq!(OneBased.start_from(OneBased, 10), 10);
+        assert_eq!(
+            "0-based".parse::<CoordinateSystem>().unwrap(),
+            ZeroBasedHalfOpen
+        );
+        assert!("zero".parse::<CoordinateSystem>().is_err());
+    }
+}
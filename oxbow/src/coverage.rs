@@ -0,0 +1,216 @@
+//! Binned read counts and depths of alignment records.
+use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Float64Builder, Int32Builder, Int64Builder, StringArray, StringDictionaryBuilder,
+};
+use arrow::{datatypes::Int32Type, error::ArrowError, record_batch::RecordBatch};
+use noodles::sam;
+use noodles::sam::record::cigar::op::Kind;
+
+use crate::batch_builder::BatchBuilder;
+use crate::coordinates::CoordinateSystem;
+
+/// How reads are assigned to windows when counting them.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum WindowAssignment {
+    /// Each read counts once, in the window of its alignment start.
+    #[default]
+    Start,
+    /// Each read counts in every window its alignment overlaps.
+    Overlap,
+}
+
+impl FromStr for WindowAssignment {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::Start),
+            "overlap" => Ok(Self::Overlap),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid window assignment: {}. Expected start or overlap.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// The read counts and aligned bases of the windows tiling a 1-based, closed interval.
+pub(crate) struct Windows {
+    start: usize,
+    end: usize,
+    size: usize,
+    assignment: WindowAssignment,
+    read_counts: Vec<i64>,
+    bases: Vec<u64>,
+}
+
+impl Windows {
+    /// Tiles `[start, end]` with windows of `size` bases from `start`, the last of which is
+    /// truncated to `end`.
+    pub fn new(start: usize, end: usize, size: usize, assignment: WindowAssignment) -> Self {
+        let n = (end + 1).saturating_sub(start).div_ceil(size);
+        Self {
+            start,
+            end,
+            size,
+            assignment,
+            read_counts: vec![0; n],
+            bases: vec![0; n],
+        }
+    }
+
+    /// Returns the index of the window containing `position`, clamped to the interval.
+    fn window(&self, position: usize) -> usize {
+        (position.clamp(self.start, self.end) - self.start) / self.size
+    }
+
+    /// Adds the aligned bases of `[start, end]` to the windows they fall in.
+    fn add_bases(&mut self, start: usize, end: usize) {
+        let (start, end) = (start.max(self.start), end.min(self.end));
+        let mut position = start;
+        while position <= end {
+            let i = self.window(position);
+            let window_end = (self.start + (i + 1) * self.size - 1).min(end);
+            self.bases[i] += (window_end + 1 - position) as u64;
+            position = window_end + 1;
+        }
+    }
+
+    /// Counts a record in its windows and adds its matched bases (`M`, `=` and `X` operations)
+    /// to the depths.
+    pub fn push(&mut self, record: &sam::alignment::Record) {
+        let (Some(start), Some(end)) = (record.alignment_start(), record.alignment_end()) else {
+            return;
+        };
+        let (start, end) = (start.get(), end.get());
+        if end < self.start || start > self.end {
+            return;
+        }
+        match self.assignment {
+            WindowAssignment::Start if start >= self.start => {
+                let i = self.window(start);
+                self.read_counts[i] += 1;
+            }
+            WindowAssignment::Start => {}
+            WindowAssignment::Overlap => {
+                for i in self.window(start)..=self.window(end) {
+                    self.read_counts[i] += 1;
+                }
+            }
+        }
+        let mut reference_position = start;
+        for op in record.cigar().iter() {
+            let len = op.len();
+            match op.kind() {
+                Kind::Match | Kind::SequenceMatch | Kind::SequenceMismatch => {
+                    self.add_bases(reference_position, reference_position + len - 1);
+                    reference_position += len;
+                }
+                Kind::Deletion | Kind::Skip => reference_position += len,
+                Kind::Insertion | Kind::SoftClip | Kind::HardClip | Kind::Pad => {}
+            }
+        }
+    }
+
+    /// Returns the 1-based, closed bounds, the read count and the aligned bases of each window,
+    /// in position order.
+    pub fn into_windows(self) -> impl Iterator<Item = (usize, usize, i64, u64)> {
+        let (start, end, size) = (self.start, self.end, self.size);
+        self.read_counts
+            .into_iter()
+            .zip(self.bases)
+            .enumerate()
+            .map(move |(i, (read_count, bases))| {
+                let window_start = start + i * size;
+                let window_end = (window_start + size - 1).min(end);
+                (window_start, window_end, read_count, bases)
+            })
+    }
+}
+
+/// A window of a coverage track.
+pub(crate) struct CoverageWindow<'a> {
+    pub chrom: &'a str,
+    pub start: usize,
+    pub end: usize,
+    pub read_count: i64,
+    pub bases: u64,
+}
+
+pub(crate) struct CoverageBatchBuilder {
+    coordinate_system: CoordinateSystem,
+    chrom: StringDictionaryBuilder<Int32Type>,
+    window_start: Int32Builder,
+    window_end: Int32Builder,
+    read_count: Int64Builder,
+    mean_depth: Option<Float64Builder>,
+}
+
+impl CoverageBatchBuilder {
+    pub fn new(
+        capacity: usize,
+        header: &sam::Header,
+        include_mean_depth: bool,
+        coordinate_system: CoordinateSystem,
+    ) -> Result<Self, ArrowError> {
+        let categories = StringArray::from(
+            header
+                .reference_sequences()
+                .iter()
+                .map(|(rs, _)| Some(rs.as_str()))
+                .collect::<Vec<_>>(),
+        );
+        Ok(Self {
+            coordinate_system,
+            chrom: StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
+                capacity,
+                &categories,
+            )?,
+            window_start: Int32Builder::with_capacity(capacity),
+            window_end: Int32Builder::with_capacity(capacity),
+            read_count: Int64Builder::with_capacity(capacity),
+            mean_depth: include_mean_depth.then(|| Float64Builder::with_capacity(capacity)),
+        })
+    }
+}
+
+impl BatchBuilder for CoverageBatchBuilder {
+    type Record<'a> = CoverageWindow<'a>;
+
+    fn push(&mut self, window: Self::Record<'_>) {
+        self.chrom.append_value(window.chrom);
+        let start = self
+            .coordinate_system
+            .start_from(CoordinateSystem::OneBased, window.start as i64);
+        self.window_start.append_value(start as i32);
+        self.window_end.append_value(window.end as i32);
+        self.read_count.append_value(window.read_count);
+        if let Some(builder) = &mut self.mean_depth {
+            let length = window.end + 1 - window.start;
+            builder.append_value(window.bases as f64 / length as f64);
+        }
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let mut columns = vec![
+            ("chrom", Arc::new(self.chrom.finish()) as ArrayRef),
+            (
+                "window_start",
+                Arc::new(self.window_start.finish()) as ArrayRef,
+            ),
+            ("window_end", Arc::new(self.window_end.finish()) as ArrayRef),
+            ("read_count", Arc::new(self.read_count.finish()) as ArrayRef),
+        ];
+        if let Some(mut builder) = self.mean_depth {
+            columns.push(("mean_depth", Arc::new(builder.finish()) as ArrayRef));
+        }
+        RecordBatch::try_from_iter(columns)
+    }
+}
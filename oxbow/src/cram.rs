@@ -1,221 +1,328 @@
-use arrow::array::{
-    ArrayRef, GenericStringBuilder, Int32Array, Int32Builder, StringArray, StringDictionaryBuilder,
-    UInt16Array, UInt16Builder, UInt8Array, UInt8Builder,
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use arrow::datatypes::Fields;
+use arrow::error::ArrowError;
+use noodles::cram::crai;
+use noodles::fasta::repository::adapters::IndexedReader;
+use noodles::{cram, fasta, sam};
+
+use crate::bam::{
+    check_sorted, filter_interval, filter_records, parse_query_region, BamBatchBuilder, BamOptions,
 };
-use arrow::{
-    datatypes::Int32Type, error::ArrowError, record_batch::RecordBatch,
+use crate::batch_builder::{
+    builder_fields, count_err, provenance, write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES,
 };
-use noodles::core::Region;
-use noodles::fasta::repository::adapters::IndexedReader;
-use noodles::{cram, sam, fasta};
-use std::sync::Arc;
+use crate::region::QueryMode;
 
-use crate::batch_builder::{write_ipc_err, BatchBuilder};
+pub fn index_from_reader<R>(read: R) -> io::Result<crai::Index>
+where
+    R: Read,
+{
+    let mut crai_reader = crai::Reader::new(read);
+    crai_reader.read_index()
+}
 
-type BufferedReader = std::io::BufReader<std::fs::File>;
+pub fn index_from_path(path: &str) -> io::Result<Option<crai::Index>> {
+    let crai_path = format!("{}.crai", path);
+    if Path::new(&crai_path).exists() {
+        crai::read(crai_path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Builds a reference sequence repository from an indexed FASTA file.
+///
+/// The FASTA file must have an associated `.fai` index.
+pub fn repository_from_path(fasta_path: &str) -> io::Result<fasta::Repository> {
+    fasta::indexed_reader::Builder::default()
+        .build_from_path(fasta_path)
+        .map(IndexedReader::new)
+        .map(fasta::Repository::new)
+}
 
 /// A CRAM reader.
-pub struct CramReader {
-    reader: cram::IndexedReader<BufferedReader>,
+pub struct CramReader<R> {
+    reader: cram::Reader<R>,
     header: sam::Header,
-    repository: fasta::repository::Repository,
+    index: Option<crai::Index>,
+    options: BamOptions,
+    query_mode: QueryMode,
+    path: Option<PathBuf>,
 }
 
-impl CramReader {
-    /// Creates a CRAM reader.
-    pub fn new(path: &str, fasta_path: &str) -> std::io::Result<Self> {
-        // Load the index
-        let index = cram::crai::read(format!("{}.crai", path))?;
-
-        // Load the reference sequence
-        let repository = fasta::indexed_reader::Builder::default()
-            .build_from_path(fasta_path)
-            .map(IndexedReader::new)
-            .map(fasta::Repository::new)
-            .unwrap_or_default();
-
-        // Open the CRAM file
+impl CramReader<BufReader<File>> {
+    /// Creates a CRAM reader from a given file path.
+    ///
+    /// The index is loaded from `<path>.crai`, if it exists.
+    ///
+    /// The reference sequence is resolved from `fasta_path` when given. CRAM files that embed
+    /// their reference, or that were written without one, can be read without it.
+    pub fn new_from_path(path: &str, fasta_path: Option<&str>) -> std::io::Result<Self> {
+        let index = index_from_path(path)?;
+        let repository = match fasta_path {
+            Some(fasta_path) => repository_from_path(fasta_path)?,
+            None => fasta::Repository::default(),
+        };
         let file = std::fs::File::open(path)?;
-        let bufreader = std::io::BufReader::with_capacity(BUFFER_SIZE_BYTES, file);
-        let mut reader = cram::indexed_reader::IndexedReader::new(bufreader, index);
+        let buf_file = std::io::BufReader::with_capacity(BUFFER_SIZE_BYTES, file);
+        let mut reader = Self::new(buf_file, index, repository)?;
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R> CramReader<R>
+where
+    R: Read + Seek,
+{
+    /// Creates a CRAM reader from a stream and an optional prebuilt index.
+    pub fn new(
+        read: R,
+        index: Option<crai::Index>,
+        repository: fasta::Repository,
+    ) -> std::io::Result<Self> {
+        let mut reader = cram::reader::Builder::default()
+            .set_reference_sequence_repository(repository)
+            .build_from_reader(read);
         reader.read_file_definition()?;
         let header = reader.read_file_header()?;
+        Ok(Self {
+            reader,
+            header,
+            index,
+            options: BamOptions::default(),
+            query_mode: QueryMode::default(),
+            path: None,
+        })
+    }
+
+    /// Returns the CRAM index used for queries, if any.
+    pub fn index(&self) -> Option<&crai::Index> {
+        self.index.as_ref()
+    }
+
+    /// Returns the SAM header, e.g. to reconstruct a writer.
+    pub fn header(&self) -> &sam::Header {
+        &self.header
+    }
+
+    /// Returns the text of the SAM header, one `@` line per record. Its lines are those of the
+    /// file, but grouped by kind, e.g. all `@SQ` lines before the `@RG` lines.
+    pub fn header_text(&self) -> String {
+        self.header.to_string()
+    }
+
+    /// Returns the names of the reference sequences in header order.
+    ///
+    /// These map the indices emitted with [`BamOptions::emit_ref_as_index`] back to names.
+    pub fn reference_names(&self) -> Vec<String> {
+        self.header
+            .reference_sequences()
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Returns the names and lengths of the reference sequences (`@SQ`) in header order.
+    pub fn reference_lengths(&self) -> Vec<(String, u64)> {
+        self.header
+            .reference_sequences()
+            .iter()
+            .map(|(name, reference_sequence)| {
+                (name.to_string(), reference_sequence.length().get() as u64)
+            })
+            .collect()
+    }
+
+    /// Sets the options used when converting records.
+    pub fn with_options(mut self, options: BamOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets how records are matched against query regions, as in
+    /// [`BamReader::with_query_mode`](crate::bam::BamReader::with_query_mode).
+    pub fn with_query_mode(mut self, query_mode: QueryMode) -> Self {
+        self.query_mode = query_mode;
+        self
+    }
 
-        Ok(Self { reader, header, repository })
+    /// Returns the fields of the batches returned by [`CramReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    ///
+    /// Tag columns, which are typed from the records, are given as Null, and the `tags` struct
+    /// without fields.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(BamBatchBuilder::new(0, &self.header, &self.options)?)
     }
 
     /// Returns the records in the given region as Apache Arrow IPC.
     ///
-    /// If the region is `None`, all records are returned.
+    /// If the region is `None`, all records are returned. Querying a region requires a CRAM
+    /// index (CRAI).
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use oxbow::cram::CramReader;
     ///
-    /// let mut reader = CramReader::new("sample.cram", "sample.fasta").unwrap();
+    /// let mut reader = CramReader::new_from_path("sample.cram", Some("sample.fasta")).unwrap();
     /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
     /// ```
     pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
-        
-        let batch_builder = CramBatchBuilder::new(1024, &self.header)?;
-
+        let batch_builder = BamBatchBuilder::new(1024, &self.header, &self.options)?
+            .with_metadata(provenance("cram", self.path.as_deref(), region.as_slice()));
+        let header = &self.header;
         if let Some(region) = region {
-            let region: Region = region.parse().unwrap();
+            let index = self.index.as_ref().ok_or_else(|| {
+                ArrowError::InvalidArgumentError(
+                    "Querying a region requires a .crai index file for the given CRAM file."
+                        .to_string(),
+                )
+            })?;
+            let region = parse_query_region(region, header, &self.options)?;
+            let reference_sequence_id = header.reference_sequences().get_index_of(region.name());
             let query = self
                 .reader
-                .query(&self.repository, &self.header, &region)
+                .query(header, index, &region)
                 .map_err(|e| ArrowError::ExternalError(e.into()))?
+                // Multi-reference slices can yield records from other reference sequences.
+                .filter(|i| {
+                    i.as_ref().map_or(true, |record| {
+                        record.reference_sequence_id() == reference_sequence_id
+                    })
+                })
+                .map(|i| i.and_then(|record| record.try_into_alignment_record(header)))
                 .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-            return write_ipc_err(query, batch_builder);
+            let query = filter_interval(query, region.interval(), self.query_mode);
+            let records = self
+                .options
+                .subsample(filter_records(query, &self.options))?;
+            return write_ipc_err(records, batch_builder);
         }
-        
-        let records = self.reader.records(&self.repository, &self.header).map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+        let records = self
+            .reader
+            .records(header)
+            .map(|i| i.and_then(|record| record.try_into_alignment_record(header)))
+            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+        let records = check_sorted(records, header, &self.options)?;
+        let records = self
+            .options
+            .subsample(filter_records(records, &self.options))?;
         write_ipc_err(records, batch_builder)
     }
-}
 
-struct CramBatchBuilder<'a> {
-    header: &'a sam::Header,
-    qname: GenericStringBuilder<i32>,
-    flag: UInt16Builder,
-    cramflag: UInt8Builder,
-    rname: StringDictionaryBuilder<Int32Type>,
-    pos: Int32Builder,
-    mapq: UInt8Builder,
-    // cigar: GenericStringBuilder<i32>,
-    rnext: StringDictionaryBuilder<Int32Type>,
-    pnext: Int32Builder,
-    tlen: Int32Builder,
-    seq: GenericStringBuilder<i32>,
-    qual: GenericStringBuilder<i32>,
-    end: Int32Builder,
+    /// Returns the number of records in the given region, without converting them to Arrow.
+    ///
+    /// If the region is `None`, all records are counted. Counting a region requires a CRAM index
+    /// (CRAI). Records rejected by the filters in [`BamOptions`] are not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::cram::CramReader;
+    ///
+    /// let mut reader = CramReader::new_from_path("sample.cram", Some("sample.fasta")).unwrap();
+    /// let n = reader.count(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        let header = &self.header;
+        if let Some(region) = region {
+            let index = self.index.as_ref().ok_or_else(|| {
+                ArrowError::InvalidArgumentError(
+                    "Querying a region requires a .crai index file for the given CRAM file."
+                        .to_string(),
+                )
+            })?;
+            let region = parse_query_region(region, header, &self.options)?;
+            let reference_sequence_id = header.reference_sequences().get_index_of(region.name());
+            let query = self
+                .reader
+                .query(header, index, &region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?
+                // Multi-reference slices can yield records from other reference sequences.
+                .filter(|i| {
+                    i.as_ref().map_or(true, |record| {
+                        record.reference_sequence_id() == reference_sequence_id
+                    })
+                })
+                .map(|i| i.and_then(|record| record.try_into_alignment_record(header)))
+                .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+            let query = filter_interval(query, region.interval(), self.query_mode);
+            return count_err(filter_records(query, &self.options));
+        }
+        let records = self
+            .reader
+            .records(header)
+            .map(|i| i.and_then(|record| record.try_into_alignment_record(header)))
+            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+        let records = check_sorted(records, header, &self.options)?;
+        count_err(filter_records(records, &self.options))
+    }
 }
 
-impl<'a> CramBatchBuilder<'a> {
-    pub fn new(capacity: usize, header: &'a sam::Header) -> Result<Self, ArrowError> {
-        let categories = StringArray::from(
-            header
-                .reference_sequences()
-                .iter()
-                .map(|(rs, _)| Some(rs.as_str()))
-                .collect::<Vec<_>>(),
-        );
-        Ok(Self {
-            header,
-            qname: GenericStringBuilder::<i32>::new(),
-            flag: UInt16Array::builder(capacity),
-            cramflag: UInt8Array::builder(capacity),
-            rname: StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
-                capacity,
-                &categories,
-            )?,
-            pos: Int32Array::builder(capacity),
-            mapq: UInt8Array::builder(capacity),
-            // cigar: GenericStringBuilder::<i32>::new(),
-            rnext: StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
-                capacity,
-                &categories,
-            )?,
-            pnext: Int32Array::builder(capacity),
-            tlen: Int32Array::builder(capacity),
-            seq: GenericStringBuilder::<i32>::new(),
-            qual: GenericStringBuilder::<i32>::new(),
-            end: Int32Array::builder(capacity),
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::ipc::reader::FileReader;
+    use arrow::record_batch::RecordBatch;
+
+    fn read_record_batch(region: Option<&str>) -> RecordBatch {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.cram");
+        let mut fasta_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_dir.push("../fixtures/sample.fa");
+        let mut reader =
+            CramReader::new_from_path(dir.to_str().unwrap(), fasta_dir.to_str()).unwrap();
+        let ipc = reader.records_to_ipc(region).unwrap();
+        let cursor = std::io::Cursor::new(ipc);
+        let mut arrow_reader = FileReader::try_new(cursor, None).unwrap();
+        // make sure we have one batch
+        assert_eq!(arrow_reader.num_batches(), 1);
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_read_all() {
+        let record_batch = read_record_batch(None);
+        assert_eq!(record_batch.num_rows(), 6);
     }
-}
 
-impl<'a> BatchBuilder for CramBatchBuilder<'a> {
-    type Record<'x> = &'a cram::Record;
+    #[test]
+    fn test_region_full() {
+        let record_batch = read_record_batch(Some("sq0"));
+        assert_eq!(record_batch.num_rows(), 3);
+    }
 
-    fn push(&mut self, record: Self::Record<'_>) {
-        // let record = record.try_into_alignment_record(&self.header).unwrap();
-        let reference_sequences = self.header.reference_sequences();
+    #[test]
+    fn rest_region_partial() {
+        let record_batch = read_record_batch(Some("sq0:1-40"));
+        assert_eq!(record_batch.num_rows(), 2);
+    }
 
-        self.qname.append_option(record.read_name());
-        self.flag.append_value(record.flags().bits());
-        self.cramflag.append_value(record.cram_flags().bits());
-        let rname = match record.reference_sequence(reference_sequences) {
-            Some(Ok((name, _))) => Some(name.as_str()),
-            _ => None,
-        };
-        self.rname.append_option(rname);
-        self.pos
-            .append_option(record.alignment_start().map(|x| x.get() as i32));
-        self.mapq
-            .append_option(record.mapping_quality().map(|x| x.get()));
-        // self.cigar.append_value(record.cigar().to_string());
-        let rnext = match record.mate_reference_sequence(reference_sequences) {
-            Some(Ok((name, _))) => Some(name.as_str()),
-            _ => None,
-        };
-        self.rnext.append_option(rnext);
-        self.pnext
-            .append_option(record.mate_alignment_start().map(|x| x.get() as i32));
-        self.tlen.append_value(record.template_length());
-        self.seq.append_value(record.sequence().to_string());
-        self.qual.append_value(record.quality_scores().to_string());
-
-        // extra
-        self.end
-            .append_option(record.alignment_end().map(|x| x.get() as i32));
-    }
-
-    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
-        RecordBatch::try_from_iter(vec![
-            // spec
-            ("qname", Arc::new(self.qname.finish()) as ArrayRef),
-            ("flag", Arc::new(self.flag.finish()) as ArrayRef),
-            ("cramflag", Arc::new(self.cramflag.finish()) as ArrayRef),
-            ("rname", Arc::new(self.rname.finish()) as ArrayRef),
-            ("pos", Arc::new(self.pos.finish()) as ArrayRef),
-            ("mapq", Arc::new(self.mapq.finish()) as ArrayRef),
-            // ("cigar", Arc::new(self.cigar.finish()) as ArrayRef),
-            ("rnext", Arc::new(self.rnext.finish()) as ArrayRef),
-            ("pnext", Arc::new(self.pnext.finish()) as ArrayRef),
-            ("tlen", Arc::new(self.tlen.finish()) as ArrayRef),
-            ("seq", Arc::new(self.seq.finish()) as ArrayRef),
-            ("qual", Arc::new(self.qual.finish()) as ArrayRef),
-            // extra
-            ("end", Arc::new(self.end.finish()) as ArrayRef),
-        ])
+    #[test]
+    fn test_count() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.cram");
+        let mut fasta_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fasta_dir.push("../fixtures/sample.fa");
+        let mut reader =
+            CramReader::new_from_path(dir.to_str().unwrap(), fasta_dir.to_str()).unwrap();
+        for region in [None, Some("sq0"), Some("sq0:1-40")] {
+            let expected = read_record_batch(region).num_rows();
+            assert_eq!(reader.count(region).unwrap(), expected);
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    // use super::*;
-    // use arrow::ipc::reader::FileReader;
-    // use arrow::record_batch::RecordBatch;
-
-    // fn read_record_batch(region: Option<&str>) -> RecordBatch {
-    //     let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    //     dir.push("fixtures/sample.cram");
-    //     let mut reader = CramReader::new(dir.to_str().unwrap()).unwrap();
-    //     let ipc = reader.records_to_ipc(region).unwrap();
-    //     let cursor = std::io::Cursor::new(ipc);
-    //     let mut arrow_reader = FileReader::try_new(cursor, None).unwrap();
-    //     // make sure we have one batch
-    //     assert_eq!(arrow_reader.num_batches(), 1);
-    //     arrow_reader.next().unwrap().unwrap()
-    // }
-
-    // #[test]
-    // fn test_read_all() {
-    //     let record_batch = read_record_batch(None);
-    //     assert_eq!(record_batch.num_rows(), 6);
-    // }
-
-    // #[test]
-    // fn test_region_full() {
-    //     let record_batch = read_record_batch(Some("chr1"));
-    //     assert_eq!(record_batch.num_rows(), 4);
-    // }
-
-    // #[test]
-    // fn rest_region_partial() {
-    //     let record_batch = read_record_batch(Some("chr1:1-100000"));
-    //     assert_eq!(record_batch.num_rows(), 2);
-    // }
+    #[test]
+    fn test_query_without_index() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.cram");
+        let file = std::fs::File::open(dir).unwrap();
+        let mut reader = CramReader::new(file, None, fasta::Repository::default()).unwrap();
+        assert!(reader.records_to_ipc(Some("sq0")).is_err());
+    }
 }
@@ -0,0 +1,143 @@
+//! Handling of malformed records.
+//!
+//! By default, a record that fails to parse aborts the whole scan. The line-based readers (BED,
+//! GFF, GTF and PAF) can instead skip such records with [`ErrorPolicy::Skip`], or skip and keep
+//! them with [`ErrorPolicy::SkipAndCollect`], to be inspected after the scan with the reader's
+//! `errors` method.
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// What to do with a record that fails to parse.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ErrorPolicy {
+    /// Abort the scan with the error.
+    #[default]
+    Fail,
+    /// Drop the record and continue.
+    Skip,
+    /// Drop the record, continue, and keep its line number and error.
+    SkipAndCollect,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(Self::Fail),
+            "skip" => Ok(Self::Skip),
+            "skip_and_collect" => Ok(Self::SkipAndCollect),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid error policy: {}. Expected fail, skip or skip_and_collect.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// A record skipped under [`ErrorPolicy::SkipAndCollect`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecordError {
+    /// The 1-based number of the line, counted from where the scan started.
+    pub line: usize,
+    /// The parse error.
+    pub message: String,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The records of a scan, with malformed ones handled by a policy.
+///
+/// Only `InvalidData` errors are records that failed to parse. Other I/O errors always abort the
+/// scan, since reading past them could fail forever.
+pub(crate) struct Tolerant<'a, I> {
+    records: I,
+    policy: ErrorPolicy,
+    errors: &'a mut Vec<RecordError>,
+}
+
+/// Applies `policy` to records numbered by line, collecting skipped records into `errors`, which
+/// is cleared first.
+pub(crate) fn tolerate<I, T>(
+    records: I,
+    policy: ErrorPolicy,
+    errors: &mut Vec<RecordError>,
+) -> Tolerant<'_, I>
+where
+    I: Iterator<Item = (usize, io::Result<T>)>,
+{
+    errors.clear();
+    Tolerant {
+        records,
+        policy,
+        errors,
+    }
+}
+
+impl<I, T> Iterator for Tolerant<'_, I>
+where
+    I: Iterator<Item = (usize, io::Result<T>)>,
+{
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.records.next()? {
+                (line, Err(e)) if e.kind() == io::ErrorKind::InvalidData => match self.policy {
+                    ErrorPolicy::Fail => return Some(Err(e)),
+                    ErrorPolicy::Skip => {}
+                    ErrorPolicy::SkipAndCollect => self.errors.push(RecordError {
+                        line,
+                        message: e.to_string(),
+                    }),
+                },
+                (_, result) => return Some(result),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tolerate() {
+        let records = || {
+            vec![
+                (1, Ok(1)),
+                (2, Err(io::Error::new(io::ErrorKind::InvalidData, "bad"))),
+                (4, Ok(3)),
+            ]
+            .into_iter()
+        };
+        let mut errors = Vec::new();
+
+        let results: Vec<_> = tolerate(records(), ErrorPolicy::Fail, &mut errors).collect();
+        assert!(results[1].is_err());
+
+        let results: io::Result<Vec<_>> =
+            tolerate(records(), ErrorPolicy::Skip, &mut errors).collect();
+        assert_eq!(results.unwrap(), [1, 3]);
+        assert!(errors.is_empty());
+
+        let results: io::Result<Vec<_>> =
+            tolerate(records(), ErrorPolicy::SkipAndCollect, &mut errors).collect();
+        assert_eq!(results.unwrap(), [1, 3]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "line 2: bad");
+
+        // Other I/O errors aren't malformed records.
+        let records = vec![(1, Err::<i32, _>(io::Error::other("disk")))].into_iter();
+        let mut results = tolerate(records, ErrorPolicy::Skip, &mut errors);
+        assert!(results.next().unwrap().is_err());
+    }
+}
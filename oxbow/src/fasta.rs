@@ -1,36 +1,337 @@
 use arrow::array::{ArrayRef, GenericStringBuilder};
+use arrow::datatypes::Fields;
 use arrow::{error::ArrowError, record_batch::RecordBatch};
-use noodles::core::Region;
+use noodles::bgzf::{self, gzi};
 use noodles::fasta;
-use noodles::fasta::fai;
+use noodles::fasta::{fai, io::BufReadSeek};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::batch_builder::{write_ipc, BatchBuilder, BUFFER_SIZE_BYTES};
+use crate::batch_builder::{
+    builder_fields, count_err, provenance, select_fields, write_ipc_batched, write_ipc_err,
+    BatchBuilder, BatchLimit, BUFFER_SIZE_BYTES,
+};
+use crate::compression::{decompress, Compression};
+use crate::region::{query_region, tile_regions};
+use crate::sequence::{
+    masked_fractions, within_length, KmerColumnBuilder, MotifCountsBuilder, SequenceColumnBuilder,
+    SequenceEncoding, SequenceStatsBuilder,
+};
 
-type BufferedReader = std::io::BufReader<std::fs::File>;
+/// Options for converting FASTA records to Arrow.
+#[derive(Clone, Debug, Default)]
+pub struct FastaOptions {
+    /// Add a `length` column (UInt64) with the length of each sequence.
+    pub include_length: Option<bool>,
+    /// Add a `gc_content` column (Float32) with the GC fraction of each sequence, ignoring `N`
+    /// bases. It is null for empty or all-`N` sequences.
+    pub include_gc: Option<bool>,
+    /// The encoding of the `sequence` column. Defaults to [`SequenceEncoding::Utf8`]; the
+    /// [`SequenceEncoding::TwoBit`] encoding adds a `sequence_mask` column after it.
+    pub encoding: Option<SequenceEncoding>,
+    /// The columns to return, in this order, e.g. `["sequence", "name"]`. Defaults to all of
+    /// them, in their default order.
+    pub fields: Option<Vec<String>>,
+    /// Add a `kmers` column (List<Utf8>) after the others with the uppercased k-mers of this
+    /// size of each sequence. See [`crate::sequence::kmers`].
+    pub kmer_size: Option<usize>,
+    /// The number of bases between the starts of consecutive k-mers. Defaults to 1.
+    pub kmer_stride: Option<usize>,
+    /// Keep k-mers with bases other than A, C, G and T, which are skipped by default.
+    pub allow_ambiguous_kmers: Option<bool>,
+    /// Emit the 32-bit FNV-1a hash of each k-mer (List<UInt32>) instead of the k-mer.
+    pub hash_kmers: Option<bool>,
+    /// Add a column (Int32) per motif, after all others, with the number of
+    /// occurrences of the motif in each sequence, overlapping ones included, e.g. `["CG"]` for a
+    /// `CG_count` column of CpG sites. Motifs can use IUPAC ambiguity codes, such as `CCWGG`. See
+    /// [`crate::sequence::count_motif`].
+    pub count_motifs: Option<Vec<String>>,
+    /// Skip records with fewer bases. Skipped records are not counted, nor toward the limit.
+    /// Region queries, which return one record per region, are not filtered by this or the
+    /// other record filters.
+    pub min_length: Option<usize>,
+    /// Skip records with more bases, like [`FastaOptions::min_length`].
+    pub max_length: Option<usize>,
+    /// Skip records with a larger fraction of `N` bases (in either case), e.g. `0.1`, like
+    /// [`FastaOptions::min_length`]. See [`crate::sequence::masked_fractions`].
+    pub max_n_fraction: Option<f32>,
+    /// Skip records with a larger fraction of lowercase, soft-masked bases, like
+    /// [`FastaOptions::max_n_fraction`].
+    pub max_lowercase_fraction: Option<f32>,
+}
+
+/// Reads a FASTA index (`.fai`), e.g. to query a stream with
+/// [`FastaReader::new_from_reader_with_index`].
+pub fn index_from_reader<R: Read>(read: R) -> io::Result<fai::Index> {
+    fai::Reader::new(BufReader::new(read)).read_index()
+}
+
+/// Copies a FASTA index, e.g. one returned by [`FastaReader::index`], for reuse with
+/// [`FastaReader::new_with_index`]. Index records don't implement `Clone`.
+pub fn copy_index(index: &fai::Index) -> fai::Index {
+    index
+        .iter()
+        .map(|record| {
+            fai::Record::new(
+                record.name(),
+                record.length(),
+                record.offset(),
+                record.line_bases(),
+                record.line_width(),
+            )
+        })
+        .collect()
+}
+
+/// Returns the path of the `<path>.<extension>` index next to a FASTA file, or an error naming it
+/// if it doesn't exist.
+fn index_path(path: &str, extension: &str) -> io::Result<String> {
+    let index_path = format!("{}.{}", path, extension);
+    if !std::path::Path::new(&index_path).exists() {
+        let kind = match extension {
+            "fai" => "FASTA index (`samtools faidx`)",
+            _ => "GZ index (`bgzip -r`)",
+        };
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Missing {} for {}: {}", kind, path, index_path),
+        ));
+    }
+    Ok(index_path)
+}
 
 /// A FASTA reader.
 pub struct FastaReader {
-    reader: fasta::IndexedReader<BufferedReader>,
-    stream_reader: fasta::Reader<Box<dyn std::io::BufRead>>,
+    /// `None` for plain gzip-compressed files and unindexed streams, which can't be queried.
+    reader: Option<fasta::IndexedReader<Box<dyn BufReadSeek>>>,
+    /// `None` for indexed streams, which are scanned through `reader` instead.
+    stream_reader: Option<fasta::Reader<Box<dyn BufRead>>>,
+    batch_limit: Option<BatchLimit>,
+    limit: Option<usize>,
+    options: FastaOptions,
+    path: Option<PathBuf>,
 }
 
 impl FastaReader {
     /// Creates a Fasta Reader.
+    ///
+    /// Uncompressed and BGZF-compressed files require an associated FASTA index (`<path>.fai`),
+    /// and BGZF-compressed files also a GZ index (`<path>.gzi`), for random access to regions.
+    /// Either missing is a `NotFound` error naming it. Plain gzip-compressed files are read
+    /// without an index, but can't be queried by region.
     pub fn new(path: &str) -> std::io::Result<Self> {
-        let index = fai::read(format!("{}.fai", path))?;
-        let file = std::fs::File::open(path)?;
-        let bufreader = std::io::BufReader::with_capacity(BUFFER_SIZE_BYTES, file);
-        let reader = fasta::indexed_reader::Builder::default()
-            .set_index(index)
-            .build_from_reader(bufreader)?;
-        let stream_reader = fasta::reader::Builder.build_from_path(path)?;
+        Self::open(path, None)
+    }
+
+    /// Creates a Fasta Reader with a prebuilt FASTA index instead of reading `<path>.fai`.
+    ///
+    /// This avoids parsing the index again when opening the same file several times.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fasta::{copy_index, FastaReader};
+    ///
+    /// let reader = FastaReader::new("sample.fasta").unwrap();
+    /// let index = copy_index(reader.index().unwrap());
+    /// let mut reader = FastaReader::new_with_index("sample.fasta", index).unwrap();
+    /// ```
+    pub fn new_with_index(path: &str, index: fai::Index) -> std::io::Result<Self> {
+        Self::open(path, Some(index))
+    }
+
+    fn open(path: &str, index: Option<fai::Index>) -> std::io::Result<Self> {
+        let mut file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let reader = match Compression::detect(&mut file)? {
+            Compression::Gzip => None,
+            compression => {
+                let index = match index {
+                    Some(index) => index,
+                    None => fai::read(index_path(path, "fai")?)?,
+                };
+                let inner: Box<dyn BufReadSeek> = match compression {
+                    Compression::Bgzf => {
+                        let gzi = gzi::read(index_path(path, "gzi")?)?;
+                        Box::new(bgzf::IndexedReader::new(file, gzi))
+                    }
+                    _ => Box::new(file),
+                };
+                Some(fasta::IndexedReader::new(inner, index))
+            }
+        };
+        let file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let stream_reader = fasta::Reader::new(decompress(file)?);
         Ok(Self {
             reader,
-            stream_reader,
+            stream_reader: Some(stream_reader),
+            batch_limit: None,
+            limit: None,
+            options: FastaOptions::default(),
+            path: Some(PathBuf::from(path)),
         })
     }
 
+    /// Creates a Fasta Reader over a stream, e.g. a Python file-like object.
+    ///
+    /// The stream is read from start to end and can't be queried by region. Wrap compressed
+    /// streams with [`decompress`] first.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::compression::decompress;
+    /// use oxbow::fasta::FastaReader;
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    ///
+    /// let file = BufReader::new(File::open("sample.fasta.gz").unwrap());
+    /// let mut reader = FastaReader::new_from_reader(decompress(file).unwrap());
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn new_from_reader<R: BufRead + 'static>(read: R) -> Self {
+        Self {
+            reader: None,
+            stream_reader: Some(fasta::Reader::new(Box::new(read))),
+            batch_limit: None,
+            limit: None,
+            options: FastaOptions::default(),
+            path: None,
+        }
+    }
+
+    /// Creates a Fasta Reader over an uncompressed, seekable stream and its FASTA index.
+    ///
+    /// Unlike [`FastaReader::new_from_reader`], the stream can be queried by region.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fasta::FastaReader;
+    /// use noodles::fasta::fai;
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    ///
+    /// let file = BufReader::new(File::open("sample.fasta").unwrap());
+    /// let index = fai::read("sample.fasta.fai").unwrap();
+    /// let mut reader = FastaReader::new_from_reader_with_index(file, index);
+    /// let ipc = reader.records_to_ipc(Some("sq0")).unwrap();
+    /// ```
+    pub fn new_from_reader_with_index<R: BufRead + Seek + 'static>(
+        read: R,
+        index: fai::Index,
+    ) -> Self {
+        let inner: Box<dyn BufReadSeek> = Box::new(read);
+        Self {
+            reader: Some(fasta::IndexedReader::new(inner, index)),
+            stream_reader: None,
+            batch_limit: None,
+            limit: None,
+            options: FastaOptions::default(),
+            path: None,
+        }
+    }
+
+    /// Returns the FASTA index used for queries, or `None` for plain gzip-compressed files and
+    /// unindexed streams.
+    pub fn index(&self) -> Option<&fai::Index> {
+        self.reader.as_ref().map(|reader| reader.index())
+    }
+
+    /// Returns the names and lengths of the sequences in the FASTA index, in index order, e.g. to
+    /// allocate genome-wide structures. Readers without an index return none.
+    pub fn reference_lengths(&self) -> Vec<(String, u64)> {
+        self.index()
+            .into_iter()
+            .flat_map(|index| index.iter())
+            .map(|record| (record.name().to_string(), record.length()))
+            .collect()
+    }
+
+    /// Returns the bases of the whole sequence named `name`, or `None` if the index has no such
+    /// sequence. This requires an index.
+    pub(crate) fn sequence(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let reader = self.reader.as_mut().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Reading a reference sequence requires an indexed FASTA file.",
+            )
+        })?;
+        if !reader.index().iter().any(|record| record.name() == name) {
+            return Ok(None);
+        }
+        let record = reader.query(&noodles::core::Region::new(name, ..))?;
+        Ok(Some(record.sequence().as_ref().to_vec()))
+    }
+
+    /// Sets the options used when converting records.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fasta::{FastaOptions, FastaReader};
+    ///
+    /// let options = FastaOptions {
+    ///     include_length: Some(true),
+    ///     include_gc: Some(true),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = FastaReader::new("sample.fasta.gz")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_options(mut self, options: FastaOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Splits the output into record batches capped by `limit`.
+    ///
+    /// By default, all records are written in a single batch. Since sequences vary widely in length,
+    /// a [`BatchLimit::Bytes`] budget on the total sequence length keeps batch memory predictable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fasta::FastaReader;
+    /// use oxbow::BatchLimit;
+    ///
+    /// let mut reader = FastaReader::new("sample.fasta.gz")
+    ///     .unwrap()
+    ///     .with_batch_limit(BatchLimit::Bytes(64 * 1024 * 1024));
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_batch_limit(mut self, limit: BatchLimit) -> Self {
+        self.batch_limit = Some(limit);
+        self
+    }
+
+    /// Caps the number of records returned, across all regions of a query combined.
+    ///
+    /// Regions past the limit are not read. Counts are not limited.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fasta::FastaReader;
+    ///
+    /// let mut reader = FastaReader::new("sample.fasta.gz").unwrap().with_limit(10);
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Returns the fields of the batches returned by [`FastaReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(FastaBatchBuilder::new(0, &self.options)?)
+    }
+
     /// Returns the records in the given region as Apache Arrow IPC.
     ///
     /// If the region is `None`, all records are returned.
@@ -44,30 +345,257 @@ impl FastaReader {
     /// let ipc = reader.records_to_ipc(Some("sq0")).unwrap();
     /// ```
     pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
-        let batch_builder = FastaBatchBuilder::new(1024)?;
-
         if let Some(region) = region {
-            let region: Region = region.parse().unwrap();
-            let query = self.reader.query(&region).unwrap();
-            let iter = std::iter::once(query);
-            return write_ipc(iter, batch_builder);
+            return self.regions_to_ipc(&[region], None);
+        }
+
+        let mut stream = stream(&mut self.stream_reader, &mut self.reader)
+            .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        let records = stream
+            .records()
+            .map(|r| r.map_err(|e| ArrowError::ExternalError(e.into())));
+        let records = filter_records(records, &self.options);
+        let metadata = provenance("fasta", self.path.as_deref(), &[]);
+        let records = records.take(self.limit.unwrap_or(usize::MAX));
+        write_records(records, self.batch_limit, &self.options, metadata)
+    }
+
+    /// Returns the number of records, without converting them to Arrow.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fasta::FastaReader;
+    ///
+    /// let mut reader = FastaReader::new("sample.fasta.gz").unwrap();
+    /// let n = reader.count().unwrap();
+    /// ```
+    pub fn count(&mut self) -> Result<usize, ArrowError> {
+        let mut stream = stream(&mut self.stream_reader, &mut self.reader)
+            .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        let records = stream
+            .records()
+            .map(|r| r.map_err(|e| ArrowError::ExternalError(e.into())));
+        count_err(filter_records(records, &self.options))
+    }
+
+    /// Returns the sequences of the given regions as Apache Arrow IPC, one record per region.
+    ///
+    /// Querying is unavailable for plain gzip-compressed files and unindexed streams.
+    ///
+    /// If `reverse_complement` is given, it must have one flag per region. The sequence of each
+    /// region whose flag is set is reverse-complemented, e.g. for features on the minus strand.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fasta::FastaReader;
+    ///
+    /// let mut reader = FastaReader::new("sample.fasta.gz").unwrap();
+    /// let ipc = reader
+    ///     .regions_to_ipc(&["sq0:1-100", "sq1:1-100"], Some(&[false, true]))
+    ///     .unwrap();
+    /// ```
+    pub fn regions_to_ipc(
+        &mut self,
+        regions: &[&str],
+        reverse_complement: Option<&[bool]>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        if let Some(flags) = reverse_complement {
+            if flags.len() != regions.len() {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "Expected {} reverse complement flags, got {}.",
+                    regions.len(),
+                    flags.len()
+                )));
+            }
         }
+        let metadata = provenance("fasta", self.path.as_deref(), regions);
+        let reader = self.reader.as_mut().ok_or_else(|| {
+            ArrowError::InvalidArgumentError(
+                "Querying a region requires a FASTA index, which plain gzip-compressed FASTA \
+                 files and unindexed streams don't have. Compress the file with bgzip and index \
+                 it, or pass the index of the stream, to enable queries."
+                    .to_string(),
+            )
+        })?;
+        let records = regions.iter().enumerate().map(|(i, region)| {
+            let region = query_region(region)?;
+            let record = reader
+                .query(&region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+            let record = match reverse_complement {
+                Some(flags) if flags[i] => fasta::Record::new(
+                    record.definition().clone(),
+                    fasta::record::Sequence::from(reverse_complement_sequence(
+                        record.sequence().as_ref(),
+                    )),
+                ),
+                _ => record,
+            };
+            Ok(record)
+        });
+        let records = records.take(self.limit.unwrap_or(usize::MAX));
+        write_records(records, self.batch_limit, &self.options, metadata)
+    }
 
-        let records = self.stream_reader.records().map(|r| r.unwrap());
-        write_ipc(records, batch_builder)
+    /// Returns the sequence of each record, or of windows tiling them, as a separate Apache Arrow
+    /// IPC file per region, in index order.
+    ///
+    /// The regions are generated with [`tile_regions`] from the lengths in the FASTA index, and
+    /// are queried lazily, one at a time. This requires an index, like
+    /// [`FastaReader::regions_to_ipc`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fasta::FastaReader;
+    ///
+    /// let mut reader = FastaReader::new("sample.fasta").unwrap();
+    /// for ipc in reader.scan_all_regions(Some(1_000_000), None).unwrap() {
+    ///     let ipc = ipc.unwrap();
+    /// }
+    /// ```
+    pub fn scan_all_regions(
+        &mut self,
+        window: Option<usize>,
+        step: Option<usize>,
+    ) -> Result<impl Iterator<Item = Result<Vec<u8>, ArrowError>> + '_, ArrowError> {
+        let index = self.index().ok_or_else(|| {
+            ArrowError::InvalidArgumentError(
+                "Scanning all regions requires a FASTA index.".to_string(),
+            )
+        })?;
+        let sequences = index
+            .iter()
+            .map(|record| (record.name().to_string(), record.length() as usize));
+        let regions = tile_regions(sequences, window, step)
+            .map_err(|e| ArrowError::InvalidArgumentError(e.to_string()))?;
+        Ok(regions
+            .into_iter()
+            .map(|region| self.regions_to_ipc(&[&region.to_string()], None)))
+    }
+}
+
+/// Returns a reader over all records, from the start of an indexed stream if there is no separate
+/// stream reader.
+fn stream<'a>(
+    stream_reader: &'a mut Option<fasta::Reader<Box<dyn BufRead>>>,
+    reader: &'a mut Option<fasta::IndexedReader<Box<dyn BufReadSeek>>>,
+) -> io::Result<fasta::Reader<Box<dyn BufRead + 'a>>> {
+    match (stream_reader, reader) {
+        (Some(stream_reader), _) => Ok(fasta::Reader::new(Box::new(stream_reader.get_mut()))),
+        (None, Some(reader)) => {
+            let inner = reader.get_mut();
+            inner.seek(SeekFrom::Start(0))?;
+            Ok(fasta::Reader::new(Box::new(inner)))
+        }
+        (None, None) => unreachable!("a FASTA reader has a stream or an indexed reader"),
     }
 }
 
+/// Skips records rejected by the length, `N` and soft-masking filters of [`FastaOptions`].
+fn filter_records<'a>(
+    records: impl Iterator<Item = Result<fasta::Record, ArrowError>> + 'a,
+    options: &FastaOptions,
+) -> impl Iterator<Item = Result<fasta::Record, ArrowError>> + 'a {
+    let (min_length, max_length) = (options.min_length, options.max_length);
+    let (max_n, max_lowercase) = (options.max_n_fraction, options.max_lowercase_fraction);
+    records.filter(move |record| {
+        let Ok(record) = record else {
+            return true;
+        };
+        let sequence = record.sequence().as_ref();
+        if !within_length(sequence.len(), min_length, max_length) {
+            return false;
+        }
+        if max_n.is_none() && max_lowercase.is_none() {
+            return true;
+        }
+        let (n, lowercase) = masked_fractions(sequence);
+        max_n.map_or(true, |max| n <= max) && max_lowercase.map_or(true, |max| lowercase <= max)
+    })
+}
+
+fn write_records(
+    records: impl Iterator<Item = Result<fasta::Record, ArrowError>>,
+    batch_limit: Option<BatchLimit>,
+    options: &FastaOptions,
+    metadata: HashMap<String, String>,
+) -> Result<Vec<u8>, ArrowError> {
+    let new_builder = || {
+        FastaBatchBuilder::new(1024, options).map(|builder| builder.with_metadata(metadata.clone()))
+    };
+    match batch_limit {
+        Some(limit) => write_ipc_batched(records, new_builder, limit, |record| {
+            record.sequence().len()
+        }),
+        None => write_ipc_err(records, new_builder()?),
+    }
+}
+
+/// Returns the complement of a nucleotide.
+///
+/// IUPAC ambiguity codes are complemented and case is preserved, which keeps soft-masking intact.
+/// Unknown characters are returned unchanged.
+fn complement(base: u8) -> u8 {
+    let complement = match base.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'T' | b'U' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        _ => return base,
+    };
+    if base.is_ascii_lowercase() {
+        complement.to_ascii_lowercase()
+    } else {
+        complement
+    }
+}
+
+/// Returns the reverse complement of a nucleotide sequence.
+pub fn reverse_complement_sequence(sequence: &[u8]) -> Vec<u8> {
+    sequence
+        .iter()
+        .rev()
+        .map(|&base| complement(base))
+        .collect()
+}
+
 struct FastaBatchBuilder {
     name: GenericStringBuilder<i32>,
-    sequence: GenericStringBuilder<i32>,
+    sequence: SequenceColumnBuilder,
+    stats: SequenceStatsBuilder,
+    kmers: Option<KmerColumnBuilder>,
+    motifs: Option<MotifCountsBuilder>,
+    fields: Option<Vec<String>>,
 }
 
 impl FastaBatchBuilder {
-    pub fn new(_capacity: usize) -> Result<Self, ArrowError> {
+    pub fn new(_capacity: usize, options: &FastaOptions) -> Result<Self, ArrowError> {
         Ok(Self {
             name: GenericStringBuilder::<i32>::new(),
-            sequence: GenericStringBuilder::<i32>::new(),
+            sequence: SequenceColumnBuilder::new(options.encoding.unwrap_or_default()),
+            stats: SequenceStatsBuilder::new(
+                options.include_length.unwrap_or(false),
+                options.include_gc.unwrap_or(false),
+            ),
+            kmers: KmerColumnBuilder::new(
+                options.kmer_size,
+                options.kmer_stride,
+                options.allow_ambiguous_kmers.unwrap_or(false),
+                options.hash_kmers.unwrap_or(false),
+            )?,
+            motifs: MotifCountsBuilder::new(options.count_motifs.as_deref())?,
+            fields: options.fields.clone(),
         })
     }
 }
@@ -79,14 +607,530 @@ impl BatchBuilder for FastaBatchBuilder {
         let seq = record.sequence().as_ref();
 
         self.name.append_value(record.name());
-        self.sequence
-            .append_value(std::str::from_utf8(seq).unwrap());
+        self.sequence.push(seq);
+        self.stats.push(seq);
+        if let Some(builder) = self.kmers.as_mut() {
+            builder.push(seq);
+        }
+        if let Some(builder) = self.motifs.as_mut() {
+            builder.push(seq);
+        }
     }
 
     fn finish(mut self) -> Result<RecordBatch, ArrowError> {
-        RecordBatch::try_from_iter(vec![
-            ("name", Arc::new(self.name.finish()) as ArrayRef),
-            ("sequence", Arc::new(self.sequence.finish()) as ArrayRef),
-        ])
+        let mut columns = vec![("name", Arc::new(self.name.finish()) as ArrayRef)];
+        columns.extend(self.sequence.finish());
+        columns.extend(self.stats.finish());
+        columns.extend(self.kmers.as_mut().map(KmerColumnBuilder::finish));
+        if let Some(builder) = self.motifs.as_mut() {
+            columns.extend(builder.finish());
+        }
+        select_fields(RecordBatch::try_from_iter(columns)?, self.fields.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Float32Array, StringArray, UInt64Array};
+    use arrow::ipc::reader::FileReader;
+
+    fn read_sequences(regions: &[&str], reverse_complement: Option<&[bool]>) -> Vec<String> {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let mut reader = FastaReader::new(dir.to_str().unwrap()).unwrap();
+        let ipc = reader.regions_to_ipc(regions, reverse_complement).unwrap();
+        let cursor = std::io::Cursor::new(ipc);
+        let mut arrow_reader = FileReader::try_new(cursor, None).unwrap();
+        // make sure we have one batch
+        assert_eq!(arrow_reader.num_batches(), 1);
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let sequence = batch
+            .column_by_name("sequence")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        (0..sequence.len())
+            .map(|i| sequence.value(i).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_reverse_complement_sequence() {
+        assert_eq!(reverse_complement_sequence(b"ACGTN"), b"NACGT");
+        assert_eq!(reverse_complement_sequence(b"acgtNNac"), b"gtNNacgt");
+        assert_eq!(reverse_complement_sequence(b"RYKMBVDHSW"), b"WSDHBVKMRY");
+        assert_eq!(reverse_complement_sequence(b"A-.*"), b"*.-T");
+    }
+
+    #[test]
+    fn test_scan_all_regions() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let mut reader = FastaReader::new(dir.to_str().unwrap()).unwrap();
+        let sequences = |reader: &mut FastaReader, window| {
+            reader
+                .scan_all_regions(window, None)
+                .unwrap()
+                .map(|ipc| {
+                    let ipc = ipc.unwrap();
+                    let mut arrow_reader =
+                        FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+                    let batch = arrow_reader.next().unwrap().unwrap();
+                    let sequence = batch.column_by_name("sequence").unwrap();
+                    let sequence = sequence.as_any().downcast_ref::<StringArray>().unwrap();
+                    sequence.value(0).to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        let contigs = sequences(&mut reader, None);
+        assert_eq!(
+            contigs.iter().map(String::len).collect::<Vec<_>>(),
+            [120, 90, 45]
+        );
+        let windows = sequences(&mut reader, Some(50));
+        assert_eq!(windows.len(), 3 + 2 + 1);
+        assert_eq!(windows.concat(), contigs.concat());
+    }
+
+    #[test]
+    fn test_regions_reverse_complement() {
+        let regions = ["sq0:1-10", "sq0:1-10", "sq1:5-20"];
+        let forward = read_sequences(&regions, None);
+        let sequences = read_sequences(&regions, Some(&[false, true, true]));
+        assert_eq!(sequences[0], forward[0]);
+        assert_eq!(
+            sequences[1].as_bytes(),
+            reverse_complement_sequence(forward[1].as_bytes())
+        );
+        assert_eq!(
+            sequences[2].as_bytes(),
+            reverse_complement_sequence(forward[2].as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_regions_reverse_complement_length_mismatch() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let mut reader = FastaReader::new(dir.to_str().unwrap()).unwrap();
+        assert!(reader.regions_to_ipc(&["sq0"], Some(&[])).is_err());
+    }
+
+    /// Returns the summed sequence lengths of each batch, one `Vec` per batch.
+    fn read_batch_lengths(limit: BatchLimit, regions: Option<&[&str]>) -> Vec<Vec<usize>> {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let mut reader = FastaReader::new(dir.to_str().unwrap())
+            .unwrap()
+            .with_batch_limit(limit);
+        let ipc = match regions {
+            Some(regions) => reader.regions_to_ipc(regions, None).unwrap(),
+            None => reader.records_to_ipc(None).unwrap(),
+        };
+        let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader
+            .map(|batch| {
+                let batch = batch.unwrap();
+                let sequence = batch
+                    .column_by_name("sequence")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .unwrap()
+                    .clone();
+                sequence.iter().map(|s| s.unwrap().len()).collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_limit_records() {
+        let batches = read_batch_lengths(BatchLimit::Records(2), None);
+        assert_eq!(batches, vec![vec![120, 90], vec![45]]);
+    }
+
+    #[test]
+    fn test_batch_limit_bytes() {
+        for budget in [10, 100, 150, 1000] {
+            let batches = read_batch_lengths(BatchLimit::Bytes(budget), None);
+            assert_eq!(batches.iter().flatten().count(), 3);
+            for batch in batches {
+                // Only a single record may exceed the budget.
+                assert!(batch.iter().sum::<usize>() <= budget || batch.len() == 1);
+            }
+        }
+        let batches = read_batch_lengths(BatchLimit::Bytes(150), None);
+        assert_eq!(batches, vec![vec![120], vec![90, 45]]);
+        let batches = read_batch_lengths(BatchLimit::Bytes(100), None);
+        assert_eq!(batches, vec![vec![120], vec![90], vec![45]]);
+    }
+
+    #[test]
+    fn test_batch_limit_regions() {
+        let regions = ["sq0:1-10", "sq1:1-10", "sq2:1-10"];
+        let batches = read_batch_lengths(BatchLimit::Bytes(25), Some(&regions));
+        assert_eq!(batches, vec![vec![10, 10], vec![10]]);
+    }
+
+    #[test]
+    fn test_sequence_stats() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let options = FastaOptions {
+            include_length: Some(true),
+            include_gc: Some(true),
+            ..Default::default()
+        };
+        let mut reader = FastaReader::new(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let ipc = reader.regions_to_ipc(&["sq0", "sq2:1-4"], None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let length = batch.column_by_name("length").unwrap();
+        let length = length.as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(length.values(), &[120, 4]);
+        let gc = batch.column_by_name("gc_content").unwrap();
+        let gc = gc.as_any().downcast_ref::<Float32Array>().unwrap();
+        // sq2:1-4 is ATGA.
+        assert_eq!(gc.value(1), 0.25);
+    }
+
+    #[test]
+    fn test_filter_length() {
+        let names = |min_length, max_length, limit: Option<usize>| {
+            let fasta = std::io::Cursor::new(">a\nA\n>b\nACG\n>c\nACGTA\n>d\nACGTACG\n");
+            let options = FastaOptions {
+                min_length,
+                max_length,
+                ..Default::default()
+            };
+            let mut reader = FastaReader::new_from_reader(fasta).with_options(options);
+            if let Some(limit) = limit {
+                reader = reader.with_limit(limit);
+            }
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let batch = arrow_reader.next().unwrap().unwrap();
+            let name = batch.column_by_name("name").unwrap();
+            let name = name.as_any().downcast_ref::<StringArray>().unwrap();
+            name.iter()
+                .flatten()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        // Both bounds are inclusive.
+        assert_eq!(names(Some(3), Some(5), None), ["b", "c"]);
+        assert_eq!(names(Some(4), None, None), ["c", "d"]);
+        assert_eq!(names(None, Some(1), None), ["a"]);
+        // Skipped records don't count toward the limit.
+        assert_eq!(names(Some(5), None, Some(1)), ["c"]);
+
+        let fasta = std::io::Cursor::new(">a\nA\n>b\nACG\n");
+        let options = FastaOptions {
+            min_length: Some(2),
+            ..Default::default()
+        };
+        let mut reader = FastaReader::new_from_reader(fasta).with_options(options);
+        assert_eq!(reader.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_filter_masked() {
+        let names = |max_n_fraction, max_lowercase_fraction| {
+            // a has 1/4 N bases, b 1/2 soft-masked bases, c 3/4 soft-masked N bases.
+            let fasta = std::io::Cursor::new(">a\nACGN\n>b\nACgt\n>c\nAnnn\n>d\n\n");
+            let options = FastaOptions {
+                max_n_fraction,
+                max_lowercase_fraction,
+                ..Default::default()
+            };
+            let mut reader = FastaReader::new_from_reader(fasta).with_options(options);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let batch = arrow_reader.next().unwrap().unwrap();
+            let name = batch.column_by_name("name").unwrap();
+            let name = name.as_any().downcast_ref::<StringArray>().unwrap();
+            name.iter()
+                .flatten()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        // The thresholds are inclusive, and empty sequences have no masked bases.
+        assert_eq!(names(Some(0.25), None), ["a", "b", "d"]);
+        assert_eq!(names(Some(0.2), None), ["b", "d"]);
+        assert_eq!(names(None, Some(0.5)), ["a", "b", "d"]);
+        assert_eq!(names(None, Some(0.0)), ["a", "d"]);
+        assert_eq!(names(Some(0.75), Some(0.75)), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_kmers() {
+        use arrow::array::{AsArray, UInt32Array};
+
+        let read_batch = |hash_kmers| {
+            let fasta = std::io::Cursor::new(">a\nACGTNacgta\n>b\nAC\n");
+            let options = FastaOptions {
+                kmer_size: Some(3),
+                kmer_stride: Some(2),
+                hash_kmers: Some(hash_kmers),
+                ..Default::default()
+            };
+            let mut reader = FastaReader::new_from_reader(fasta).with_options(options);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+
+        let batch = read_batch(false);
+        let kmers = batch.column_by_name("kmers").unwrap().as_list::<i32>();
+        // ACG, GTN (skipped), Nac (skipped), cgt, and the trailing `a` is dropped.
+        let kmers_a = kmers.value(0);
+        let kmers_a: Vec<_> = kmers_a.as_string::<i32>().iter().flatten().collect();
+        assert_eq!(kmers_a, ["ACG", "CGT"]);
+        assert_eq!(kmers.value_length(1), 0);
+
+        let batch = read_batch(true);
+        let kmers = batch.column_by_name("kmers").unwrap().as_list::<i32>();
+        let hashes = kmers.value(0);
+        let hashes = hashes.as_any().downcast_ref::<UInt32Array>().unwrap();
+        assert_eq!(hashes.value(0), crate::sequence::hash_kmer(b"ACG"));
+
+        let options = FastaOptions {
+            kmer_size: Some(0),
+            ..Default::default()
+        };
+        let fasta = std::io::Cursor::new(">a\nACGT\n");
+        let mut reader = FastaReader::new_from_reader(fasta).with_options(options);
+        assert!(reader.records_to_ipc(None).is_err());
+    }
+
+    #[test]
+    fn test_sequence_encoding() {
+        use crate::sequence::decode_two_bit;
+        use arrow::array::{AsArray, BooleanArray};
+        use arrow::datatypes::UInt8Type;
+
+        let read_batch = |encoding| {
+            let fasta = std::io::Cursor::new(">a\nACGTNacgt\n>b\nRNA\n");
+            let options = FastaOptions {
+                encoding: Some(encoding),
+                ..Default::default()
+            };
+            let mut reader = FastaReader::new_from_reader(fasta).with_options(options);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+
+        let batch = read_batch(SequenceEncoding::TwoBit);
+        let schema = batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["name", "sequence", "sequence_mask"]);
+        let packed = batch.column_by_name("sequence").unwrap().as_list::<i32>();
+        let mask = batch
+            .column_by_name("sequence_mask")
+            .unwrap()
+            .as_list::<i32>();
+        let masks: Vec<Vec<bool>> = (0..2)
+            .map(|i| {
+                let mask = mask.value(i);
+                let mask = mask.as_any().downcast_ref::<BooleanArray>().unwrap();
+                mask.values().iter().collect()
+            })
+            .collect();
+        assert_eq!(
+            masks,
+            [
+                vec![false, false, false, false, true, false, false, false, false],
+                vec![true, true, false],
+            ]
+        );
+        let sequences: Vec<Vec<u8>> = (0..2)
+            .map(|i| {
+                let packed = packed.value(i);
+                decode_two_bit(packed.as_primitive::<UInt8Type>().values(), &masks[i])
+            })
+            .collect();
+        assert_eq!(sequences, [b"ACGTNACGT".to_vec(), b"NNA".to_vec()]);
+
+        let batch = read_batch(SequenceEncoding::OneHot);
+        assert_eq!(batch.num_columns(), 2);
+        let one_hot = batch.column_by_name("sequence").unwrap().as_list::<i32>();
+        let bases = one_hot.value(1);
+        let bases = bases.as_fixed_size_list();
+        assert_eq!(bases.value_length(), 4);
+        assert_eq!(
+            bases.values().as_primitive::<UInt8Type>().values(),
+            &[0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let read = |fields: &[&str]| {
+            let options = FastaOptions {
+                include_length: Some(true),
+                fields: Some(fields.iter().map(|f| f.to_string()).collect()),
+                ..Default::default()
+            };
+            FastaReader::new(dir.to_str().unwrap())
+                .unwrap()
+                .with_options(options)
+                .regions_to_ipc(&["sq0"], None)
+        };
+        let ipc = read(&["length", "name"]).unwrap();
+        let batch = FileReader::try_new(std::io::Cursor::new(ipc), None)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let schema = batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["length", "name"]);
+
+        let error = read(&["name", "gc_content"]).unwrap_err();
+        assert!(error.to_string().contains("name, sequence, length"));
+    }
+
+    #[test]
+    fn test_count() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let mut reader = FastaReader::new(dir.to_str().unwrap()).unwrap();
+        assert_eq!(reader.count().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let num_rows = |ipc: Vec<u8>| {
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader
+                .map(|batch| batch.unwrap().num_rows())
+                .sum::<usize>()
+        };
+        let mut reader = FastaReader::new(dir.to_str().unwrap())
+            .unwrap()
+            .with_limit(2);
+        let ipc = reader.regions_to_ipc(&["sq0", "sq1", "sq2"], None).unwrap();
+        assert_eq!(num_rows(ipc), 2);
+        assert_eq!(num_rows(reader.records_to_ipc(None).unwrap()), 2);
+    }
+
+    #[test]
+    fn test_reference_lengths() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let reader = FastaReader::new(dir.to_str().unwrap()).unwrap();
+        let expected = [("sq0", 120), ("sq1", 90), ("sq2", 45)];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(name, length)| (name.to_string(), length))
+            .collect();
+        assert_eq!(reader.reference_lengths(), expected);
+        let fasta = std::io::Cursor::new(">a\nACGT\n");
+        assert!(FastaReader::new_from_reader(fasta)
+            .reference_lengths()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_reuse_index() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let path = dir.to_str().unwrap();
+        let reader = FastaReader::new(path).unwrap();
+        let index = copy_index(reader.index().unwrap());
+        assert_eq!(&index, reader.index().unwrap());
+        let mut reader = FastaReader::new_with_index(path, index).unwrap();
+        assert!(reader.records_to_ipc(Some("sq0")).is_ok());
+    }
+
+    #[test]
+    fn test_read_compressed() {
+        let read_all = |name: &str| {
+            let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            dir.push("../fixtures");
+            dir.push(name);
+            let mut reader = FastaReader::new(dir.to_str().unwrap()).unwrap();
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let expected = read_all("sample.fa");
+        assert_eq!(read_all("sample.fa.gz").columns(), expected.columns());
+        assert_eq!(read_all("sample.plain.fa.gz").columns(), expected.columns());
+    }
+
+    #[test]
+    fn test_query_compressed() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa.gz");
+        let mut reader = FastaReader::new(dir.to_str().unwrap()).unwrap();
+        let ipc = reader.regions_to_ipc(&["sq1:5-20"], None).unwrap();
+        let expected = read_sequences(&["sq1:5-20"], None);
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let sequence = batch.column_by_name("sequence").unwrap();
+        let sequence = sequence.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(sequence.value(0), expected[0]);
+
+        // A BGZF file needs both indexes.
+        let tmp = std::env::temp_dir().join(format!("oxbow-bgzf-fasta-{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let copy = tmp.join("sample.fa.gz");
+        std::fs::copy(&dir, &copy).unwrap();
+        let copy = copy.to_str().unwrap();
+        let error = FastaReader::new(copy).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("sample.fa.gz.fai"));
+        std::fs::copy(format!("{}.fai", dir.display()), format!("{}.fai", copy)).unwrap();
+        let error = FastaReader::new(copy).err().unwrap();
+        assert!(error.to_string().contains("sample.fa.gz.gzi"));
+        std::fs::copy(format!("{}.gzi", dir.display()), format!("{}.gzi", copy)).unwrap();
+        let mut reader = FastaReader::new(copy).unwrap();
+        assert!(reader.records_to_ipc(Some("sq2:1-4")).is_ok());
+        std::fs::remove_dir_all(&tmp).unwrap();
+
+        // Plain gzip can only be read sequentially.
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.plain.fa.gz");
+        let mut reader = FastaReader::new(dir.to_str().unwrap()).unwrap();
+        assert!(reader.records_to_ipc(Some("sq0")).is_err());
+    }
+
+    #[test]
+    fn test_read_from_reader() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let path = dir.to_str().unwrap();
+        let read_batch = |reader: &mut FastaReader, region: Option<&str>| {
+            let ipc = reader.records_to_ipc(region).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let expected = read_batch(&mut FastaReader::new(path).unwrap(), None);
+        let expected_region = read_batch(&mut FastaReader::new(path).unwrap(), Some("sq1:5-20"));
+
+        let file = BufReader::new(File::open(path).unwrap());
+        let mut reader = FastaReader::new_from_reader(file);
+        assert_eq!(read_batch(&mut reader, None).columns(), expected.columns());
+        let file = BufReader::new(File::open(path).unwrap());
+        let mut reader = FastaReader::new_from_reader(file);
+        assert!(reader.records_to_ipc(Some("sq0")).is_err());
+
+        let file = BufReader::new(File::open(path).unwrap());
+        let index = fai::read(format!("{}.fai", path)).unwrap();
+        let mut reader = FastaReader::new_from_reader_with_index(file, index);
+        let batch = read_batch(&mut reader, Some("sq1:5-20"));
+        assert_eq!(batch.columns(), expected_region.columns());
+        // A full scan after a query starts over from the beginning of the stream.
+        assert_eq!(read_batch(&mut reader, None).columns(), expected.columns());
     }
 }
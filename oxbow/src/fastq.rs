@@ -1,25 +1,187 @@
-use arrow::array::{ArrayRef, GenericStringBuilder};
+use arrow::array::{
+    ArrayRef, BooleanBuilder, GenericStringBuilder, Int32Builder, ListBuilder, UInt8Builder,
+};
+use arrow::datatypes::Fields;
 use arrow::{error::ArrowError, record_batch::RecordBatch};
 use noodles::fastq;
 use std::{
     fs::File,
     io::{self, BufRead, BufReader},
+    path::PathBuf,
     str,
     sync::Arc,
 };
 
-use crate::batch_builder::{write_ipc, BatchBuilder};
+use crate::batch_builder::{
+    builder_fields, count_err, provenance, select_fields, write_ipc, write_ipc_err, BatchBuilder,
+};
+use crate::compression::decompress;
+use crate::sequence::{within_length, KmerColumnBuilder, MotifCountsBuilder, SequenceStatsBuilder};
+
+/// Options for converting FASTQ records to Arrow.
+#[derive(Clone, Debug, Default)]
+pub struct FastqOptions {
+    /// Decode the `quality_scores` column into Phred scores (`List<UInt8>`) instead of the raw
+    /// ASCII string. Records with a character below the offset get a null list.
+    pub decode_quality: Option<bool>,
+    /// The ASCII offset of the quality encoding. Defaults to 33; use 64 for legacy Phred+64 files.
+    pub quality_offset: Option<u8>,
+    /// Add a `length` column (UInt64) with the length of each sequence.
+    pub include_length: Option<bool>,
+    /// Add a `gc_content` column (Float32) with the GC fraction of each sequence, ignoring `N`
+    /// bases. It is null for empty or all-`N` sequences.
+    pub include_gc: Option<bool>,
+    /// Parse Illumina Casava 1.8 headers, e.g. `@EAS139:136:FC706VJ:2:2104:15343:197393
+    /// 1:Y:18:ATCACG`, into `instrument` (Utf8), `lane`, `tile`, `x`, `y` (Int32), `read` (Int32),
+    /// `is_filtered` (Boolean), `control` (Int32) and `index` (Utf8) columns, added after all
+    /// others. The `description` column keeps the text after the name either way, and records
+    /// whose header is not in this format get nulls.
+    pub parse_casava: Option<bool>,
+    /// Add a `kmers` column (List<Utf8>), before the Casava columns, with the uppercased k-mers
+    /// of this size of each sequence. See [`crate::sequence::kmers`].
+    pub kmer_size: Option<usize>,
+    /// The number of bases between the starts of consecutive k-mers. Defaults to 1.
+    pub kmer_stride: Option<usize>,
+    /// Keep k-mers with bases other than A, C, G and T, which are skipped by default.
+    pub allow_ambiguous_kmers: Option<bool>,
+    /// Emit the 32-bit FNV-1a hash of each k-mer (List<UInt32>) instead of the k-mer.
+    pub hash_kmers: Option<bool>,
+    /// Add a column (Int32) per motif, before the Casava columns and after `kmers`, with the number of
+    /// occurrences of the motif in each sequence, overlapping ones included, e.g. `["CG"]` for a
+    /// `CG_count` column of CpG sites. Motifs can use IUPAC ambiguity codes, such as `CCWGG`. See
+    /// [`crate::sequence::count_motif`].
+    pub count_motifs: Option<Vec<String>>,
+    /// Skip reads with fewer bases. Skipped reads are not counted either.
+    pub min_length: Option<usize>,
+    /// Skip reads with more bases, like [`FastqOptions::min_length`].
+    pub max_length: Option<usize>,
+    /// The columns to return, in this order, e.g. `["sequence", "name"]`. Defaults to all of
+    /// them, in their default order.
+    pub fields: Option<Vec<String>>,
+}
+
+/// The fields of a Casava 1.8 header, from the name
+/// `<instrument>:<run>:<flowcell>:<lane>:<tile>:<x>:<y>` and the description
+/// `<read>:<is filtered>:<control>:<index>`.
+#[derive(Debug, PartialEq)]
+struct Casava<'a> {
+    instrument: &'a str,
+    lane: i32,
+    tile: i32,
+    x: i32,
+    y: i32,
+    read: i32,
+    is_filtered: bool,
+    control: i32,
+    index: &'a str,
+}
+
+impl<'a> Casava<'a> {
+    fn parse(name: &'a str, description: &'a str) -> Option<Self> {
+        let name: Vec<&str> = name.split(':').collect();
+        let [instrument, _, _, lane, tile, x, y] = name[..] else {
+            return None;
+        };
+        let description: Vec<&str> = description.splitn(4, ':').collect();
+        let [read, is_filtered, control, index] = description[..] else {
+            return None;
+        };
+        let is_filtered = match is_filtered {
+            "Y" => true,
+            "N" => false,
+            _ => return None,
+        };
+        Some(Self {
+            instrument,
+            lane: lane.parse().ok()?,
+            tile: tile.parse().ok()?,
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+            read: read.parse().ok()?,
+            is_filtered,
+            control: control.parse().ok()?,
+            index,
+        })
+    }
+}
+
+/// Builds the columns of Casava 1.8 header fields.
+struct CasavaBuilder {
+    instrument: GenericStringBuilder<i32>,
+    lane: Int32Builder,
+    tile: Int32Builder,
+    x: Int32Builder,
+    y: Int32Builder,
+    read: Int32Builder,
+    is_filtered: BooleanBuilder,
+    control: Int32Builder,
+    index: GenericStringBuilder<i32>,
+}
+
+impl CasavaBuilder {
+    fn new() -> Self {
+        Self {
+            instrument: GenericStringBuilder::<i32>::new(),
+            lane: Int32Builder::new(),
+            tile: Int32Builder::new(),
+            x: Int32Builder::new(),
+            y: Int32Builder::new(),
+            read: Int32Builder::new(),
+            is_filtered: BooleanBuilder::new(),
+            control: Int32Builder::new(),
+            index: GenericStringBuilder::<i32>::new(),
+        }
+    }
+
+    fn push(&mut self, casava: Option<Casava<'_>>) {
+        self.instrument
+            .append_option(casava.as_ref().map(|c| c.instrument));
+        self.lane.append_option(casava.as_ref().map(|c| c.lane));
+        self.tile.append_option(casava.as_ref().map(|c| c.tile));
+        self.x.append_option(casava.as_ref().map(|c| c.x));
+        self.y.append_option(casava.as_ref().map(|c| c.y));
+        self.read.append_option(casava.as_ref().map(|c| c.read));
+        self.is_filtered
+            .append_option(casava.as_ref().map(|c| c.is_filtered));
+        self.control
+            .append_option(casava.as_ref().map(|c| c.control));
+        self.index.append_option(casava.as_ref().map(|c| c.index));
+    }
+
+    fn finish(&mut self) -> Vec<(&'static str, ArrayRef)> {
+        vec![
+            ("instrument", Arc::new(self.instrument.finish()) as ArrayRef),
+            ("lane", Arc::new(self.lane.finish()) as ArrayRef),
+            ("tile", Arc::new(self.tile.finish()) as ArrayRef),
+            ("x", Arc::new(self.x.finish()) as ArrayRef),
+            ("y", Arc::new(self.y.finish()) as ArrayRef),
+            ("read", Arc::new(self.read.finish()) as ArrayRef),
+            (
+                "is_filtered",
+                Arc::new(self.is_filtered.finish()) as ArrayRef,
+            ),
+            ("control", Arc::new(self.control.finish()) as ArrayRef),
+            ("index", Arc::new(self.index.finish()) as ArrayRef),
+        ]
+    }
+}
 
 pub struct FastqReader<R> {
     reader: fastq::Reader<R>,
+    options: FastqOptions,
+    path: Option<PathBuf>,
 }
 
-impl FastqReader<BufReader<File>> {
+impl FastqReader<Box<dyn BufRead>> {
+    /// Creates a FASTQ reader from a given file path.
+    ///
+    /// Gzip- and BGZF-compressed files are detected from their magic bytes and decompressed
+    /// transparently.
     pub fn new_from_path(path: &str) -> io::Result<Self> {
-        let reader = File::open(path)
-            .map(BufReader::new)
-            .map(fastq::Reader::new)?;
-        Ok(Self { reader })
+        let file = File::open(path).map(BufReader::new)?;
+        let mut reader = Self::new(decompress(file)?)?;
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
     }
 }
 
@@ -29,7 +191,39 @@ where
 {
     pub fn new(read: R) -> io::Result<Self> {
         let reader = fastq::Reader::new(read);
-        Ok(Self { reader })
+        Ok(Self {
+            reader,
+            options: FastqOptions::default(),
+            path: None,
+        })
+    }
+
+    /// Sets the options used when converting records.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fastq::{FastqOptions, FastqReader};
+    ///
+    /// let options = FastqOptions {
+    ///     decode_quality: Some(true),
+    ///     quality_offset: Some(64),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = FastqReader::new_from_path("sample.fastq")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// let ipc = reader.records_to_ipc().unwrap();
+    /// ```
+    pub fn with_options(mut self, options: FastqOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the fields of the batches returned by [`FastqReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(FastqBatchBuilder::new(0, &self.options)?)
     }
 
     /// Returns the records in the given region as Apache Arrow IPC.
@@ -45,26 +239,234 @@ where
     /// let ipc = reader.records_to_ipc().unwrap();
     /// ```
     pub fn records_to_ipc(&mut self) -> Result<Vec<u8>, ArrowError> {
-        let batch_builder = FastqBatchBuilder::new(1024)?;
-        let records = self.reader.records().map(|r| r.unwrap());
+        let batch_builder = FastqBatchBuilder::new(1024, &self.options)?.with_metadata(provenance(
+            "fastq",
+            self.path.as_deref(),
+            &[],
+        ));
+        let (min_length, max_length) = (self.options.min_length, self.options.max_length);
+        let records = self
+            .reader
+            .records()
+            .map(|r| r.unwrap())
+            .filter(|record| within_length(record.sequence().len(), min_length, max_length));
         write_ipc(records, batch_builder)
     }
+
+    /// Returns the read pairs of paired-end files as Apache Arrow IPC, with the first reads from
+    /// this reader and the second reads from `mates`.
+    ///
+    /// Each pair gives a row with the columns `name`, `seq_1`, `qual_1`, `seq_2` and `qual_2`.
+    /// Quality scores follow [`FastqOptions::decode_quality`], while the other options don't
+    /// apply. The names of the reads of a pair must match, ignoring a `/1` or `/2` suffix (and the
+    /// description, which holds the read number in Casava 1.8 headers), and both files must have
+    /// the same number of reads.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fastq::FastqReader;
+    ///
+    /// let mut reader = FastqReader::new_from_path("sample_R1.fastq.gz").unwrap();
+    /// let mut mates = FastqReader::new_from_path("sample_R2.fastq.gz").unwrap();
+    /// let ipc = reader.paired_to_ipc(&mut mates).unwrap();
+    /// ```
+    pub fn paired_to_ipc<S: BufRead>(
+        &mut self,
+        mates: &mut FastqReader<S>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = FastqPairBatchBuilder::new(&self.options).with_metadata(provenance(
+            "fastq",
+            self.path.as_deref(),
+            &[],
+        ));
+        let (mut first, mut second) = (self.reader.records(), mates.reader.records());
+        write_ipc_err(zip_pairs(|| (first.next(), second.next())), batch_builder)
+    }
+
+    /// Returns the read pairs of an interleaved file, in which each first read is followed by
+    /// its mate, as Apache Arrow IPC.
+    ///
+    /// The rows are the same as those of [`FastqReader::paired_to_ipc`], and the file must have
+    /// an even number of reads.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fastq::FastqReader;
+    ///
+    /// let mut reader = FastqReader::new_from_path("interleaved.fastq.gz").unwrap();
+    /// let ipc = reader.interleaved_to_ipc().unwrap();
+    /// ```
+    pub fn interleaved_to_ipc(&mut self) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = FastqPairBatchBuilder::new(&self.options).with_metadata(provenance(
+            "fastq",
+            self.path.as_deref(),
+            &[],
+        ));
+        let mut records = self.reader.records();
+        write_ipc_err(
+            zip_pairs(|| (records.next(), records.next())),
+            batch_builder,
+        )
+    }
+
+    /// Returns the number of records, without converting them to Arrow.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::fastq::FastqReader;
+    ///
+    /// let mut reader = FastqReader::new_from_path("sample.fastq.gz").unwrap();
+    /// let n = reader.count().unwrap();
+    /// ```
+    pub fn count(&mut self) -> Result<usize, ArrowError> {
+        let (min_length, max_length) = (self.options.min_length, self.options.max_length);
+        let records = self
+            .reader
+            .records()
+            .map(|r| r.map_err(|e| ArrowError::ExternalError(e.into())))
+            .filter(|record| {
+                record.as_ref().map_or(true, |record| {
+                    within_length(record.sequence().len(), min_length, max_length)
+                })
+            });
+        count_err(records)
+    }
+}
+
+/// Returns the name of a read without its `/1` or `/2` mate suffix.
+fn mate_name(name: &[u8]) -> &[u8] {
+    name.strip_suffix(b"/1")
+        .or_else(|| name.strip_suffix(b"/2"))
+        .unwrap_or(name)
+}
+
+/// Pairs the reads returned two at a time by `next_pair`, failing at the first read without a
+/// mate or whose mate has another name.
+fn zip_pairs<F>(
+    mut next_pair: F,
+) -> impl Iterator<Item = Result<(fastq::Record, fastq::Record), ArrowError>>
+where
+    F: FnMut() -> (
+        Option<io::Result<fastq::Record>>,
+        Option<io::Result<fastq::Record>>,
+    ),
+{
+    let mut n = 0;
+    std::iter::from_fn(move || {
+        n += 1;
+        let (first, second) = match next_pair() {
+            (None, None) => return None,
+            (Some(first), Some(second)) => (first, second),
+            _ => {
+                return Some(Err(ArrowError::InvalidArgumentError(format!(
+                    "Read pair {} is missing a mate.",
+                    n
+                ))))
+            }
+        };
+        let pair = first.and_then(|first| Ok((first, second?)));
+        Some(
+            pair.map_err(|e| ArrowError::ExternalError(e.into()))
+                .and_then(|(first, second)| {
+                    if mate_name(first.name()) != mate_name(second.name()) {
+                        return Err(ArrowError::InvalidArgumentError(format!(
+                            "Read pair {} has mismatched names: {} and {}.",
+                            n,
+                            String::from_utf8_lossy(first.name()),
+                            String::from_utf8_lossy(second.name())
+                        )));
+                    }
+                    Ok((first, second))
+                }),
+        )
+    })
+}
+
+enum QualityScoresBuilder {
+    Raw(GenericStringBuilder<i32>),
+    Decoded {
+        builder: ListBuilder<UInt8Builder>,
+        offset: u8,
+    },
+}
+
+impl QualityScoresBuilder {
+    fn new(options: &FastqOptions) -> Self {
+        if options.decode_quality.unwrap_or(false) {
+            Self::Decoded {
+                builder: ListBuilder::new(UInt8Builder::new()),
+                offset: options.quality_offset.unwrap_or(33),
+            }
+        } else {
+            Self::Raw(GenericStringBuilder::<i32>::new())
+        }
+    }
+
+    fn append(&mut self, quality_scores: &[u8]) {
+        match self {
+            Self::Raw(builder) => builder.append_value(str::from_utf8(quality_scores).unwrap()),
+            Self::Decoded { builder, offset } => {
+                let scores: Option<Vec<u8>> = quality_scores
+                    .iter()
+                    .map(|&c| c.checked_sub(*offset))
+                    .collect();
+                match scores {
+                    Some(scores) => {
+                        builder.values().append_slice(&scores);
+                        builder.append(true);
+                    }
+                    None => builder.append_null(),
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Raw(builder) => Arc::new(builder.finish()),
+            Self::Decoded { builder, .. } => Arc::new(builder.finish()),
+        }
+    }
 }
 
 struct FastqBatchBuilder {
     name: GenericStringBuilder<i32>,
     description: GenericStringBuilder<i32>,
     sequence: GenericStringBuilder<i32>,
-    quality_scores: GenericStringBuilder<i32>,
+    quality_scores: QualityScoresBuilder,
+    stats: SequenceStatsBuilder,
+    kmers: Option<KmerColumnBuilder>,
+    motifs: Option<MotifCountsBuilder>,
+    casava: Option<CasavaBuilder>,
+    fields: Option<Vec<String>>,
 }
 
 impl FastqBatchBuilder {
-    pub fn new(_capacity: usize) -> Result<Self, ArrowError> {
+    pub fn new(_capacity: usize, options: &FastqOptions) -> Result<Self, ArrowError> {
         Ok(Self {
             name: GenericStringBuilder::<i32>::new(),
             description: GenericStringBuilder::<i32>::new(),
             sequence: GenericStringBuilder::<i32>::new(),
-            quality_scores: GenericStringBuilder::<i32>::new(),
+            quality_scores: QualityScoresBuilder::new(options),
+            stats: SequenceStatsBuilder::new(
+                options.include_length.unwrap_or(false),
+                options.include_gc.unwrap_or(false),
+            ),
+            kmers: KmerColumnBuilder::new(
+                options.kmer_size,
+                options.kmer_stride,
+                options.allow_ambiguous_kmers.unwrap_or(false),
+                options.hash_kmers.unwrap_or(false),
+            )?,
+            motifs: MotifCountsBuilder::new(options.count_motifs.as_deref())?,
+            casava: options
+                .parse_casava
+                .unwrap_or(false)
+                .then(CasavaBuilder::new),
+            fields: options.fields.clone(),
         })
     }
 }
@@ -73,28 +475,413 @@ impl BatchBuilder for FastqBatchBuilder {
     type Record<'a> = &'a fastq::Record;
 
     fn push(&mut self, record: Self::Record<'_>) {
-        self.name
-            .append_value(str::from_utf8(record.name()).unwrap());
-        self.description
-            .append_value(str::from_utf8(record.description()).unwrap());
+        let name = str::from_utf8(record.name()).unwrap();
+        let description = str::from_utf8(record.description()).unwrap();
+        self.name.append_value(name);
+        self.description.append_value(description);
+        if let Some(builder) = self.casava.as_mut() {
+            builder.push(Casava::parse(name, description));
+        }
         self.sequence
             .append_value(str::from_utf8(record.sequence()).unwrap());
-        self.quality_scores
-            .append_value(str::from_utf8(record.quality_scores()).unwrap());
+        self.quality_scores.append(record.quality_scores());
+        self.stats.push(record.sequence());
+        if let Some(builder) = self.kmers.as_mut() {
+            builder.push(record.sequence());
+        }
+        if let Some(builder) = self.motifs.as_mut() {
+            builder.push(record.sequence());
+        }
     }
 
     fn finish(mut self) -> Result<RecordBatch, ArrowError> {
-        RecordBatch::try_from_iter(vec![
+        let mut columns = vec![
             ("name", Arc::new(self.name.finish()) as ArrayRef),
             (
                 "description",
                 Arc::new(self.description.finish()) as ArrayRef,
             ),
             ("sequence", Arc::new(self.sequence.finish()) as ArrayRef),
-            (
-                "quality_scores",
-                Arc::new(self.quality_scores.finish()) as ArrayRef,
-            ),
-        ])
+            ("quality_scores", self.quality_scores.finish()),
+        ];
+        columns.extend(self.stats.finish());
+        columns.extend(self.kmers.as_mut().map(KmerColumnBuilder::finish));
+        if let Some(builder) = self.motifs.as_mut() {
+            columns.extend(builder.finish());
+        }
+        if let Some(builder) = self.casava.as_mut() {
+            columns.extend(builder.finish());
+        }
+        select_fields(RecordBatch::try_from_iter(columns)?, self.fields.as_deref())
+    }
+}
+
+/// Builds batches of read pairs.
+struct FastqPairBatchBuilder {
+    name: GenericStringBuilder<i32>,
+    sequences: [GenericStringBuilder<i32>; 2],
+    quality_scores: [QualityScoresBuilder; 2],
+    fields: Option<Vec<String>>,
+}
+
+impl FastqPairBatchBuilder {
+    fn new(options: &FastqOptions) -> Self {
+        Self {
+            name: GenericStringBuilder::<i32>::new(),
+            sequences: std::array::from_fn(|_| GenericStringBuilder::<i32>::new()),
+            quality_scores: std::array::from_fn(|_| QualityScoresBuilder::new(options)),
+            fields: options.fields.clone(),
+        }
+    }
+}
+
+impl BatchBuilder for FastqPairBatchBuilder {
+    type Record<'a> = &'a (fastq::Record, fastq::Record);
+
+    fn push(&mut self, (first, second): Self::Record<'_>) {
+        self.name
+            .append_value(str::from_utf8(mate_name(first.name())).unwrap());
+        for (i, record) in [first, second].into_iter().enumerate() {
+            self.sequences[i].append_value(str::from_utf8(record.sequence()).unwrap());
+            self.quality_scores[i].append(record.quality_scores());
+        }
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let [seq_1, seq_2] = &mut self.sequences;
+        let [qual_1, qual_2] = &mut self.quality_scores;
+        let batch = RecordBatch::try_from_iter([
+            ("name", Arc::new(self.name.finish()) as ArrayRef),
+            ("seq_1", Arc::new(seq_1.finish()) as ArrayRef),
+            ("qual_1", qual_1.finish()),
+            ("seq_2", Arc::new(seq_2.finish()) as ArrayRef),
+            ("qual_2", qual_2.finish()),
+        ])?;
+        select_fields(batch, self.fields.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{
+        Array, BooleanArray, Float32Array, Int32Array, ListArray, StringArray, UInt64Array,
+        UInt8Array,
+    };
+    use arrow::ipc::reader::FileReader;
+
+    fn read_record_batch<R: BufRead>(reader: FastqReader<R>, options: FastqOptions) -> RecordBatch {
+        let ipc = reader.with_options(options).records_to_ipc().unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    fn fixture_reader() -> FastqReader<Box<dyn BufRead>> {
+        fixture_reader_from("sample.fastq")
+    }
+
+    fn fixture_reader_from(name: &str) -> FastqReader<Box<dyn BufRead>> {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures");
+        dir.push(name);
+        FastqReader::new_from_path(dir.to_str().unwrap()).unwrap()
+    }
+
+    fn decoded_scores(record_batch: &RecordBatch) -> Vec<Option<Vec<u8>>> {
+        let column = record_batch.column_by_name("quality_scores").unwrap();
+        let list = column.as_any().downcast_ref::<ListArray>().unwrap();
+        list.iter()
+            .map(|scores| {
+                scores.map(|scores| {
+                    let scores = scores.as_any().downcast_ref::<UInt8Array>().unwrap();
+                    scores.values().to_vec()
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fields() {
+        let options = FastqOptions {
+            include_length: Some(true),
+            fields: Some(vec!["sequence".into(), "length".into(), "name".into()]),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch(fixture_reader(), options);
+        let schema = record_batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["sequence", "length", "name"]);
+
+        let options = FastqOptions {
+            fields: Some(vec!["name".into(), "length".into()]),
+            ..Default::default()
+        };
+        let error = fixture_reader()
+            .with_options(options)
+            .records_to_ipc()
+            .unwrap_err();
+        assert!(error.to_string().contains("Invalid field: length"));
+        assert!(error.to_string().contains("name, description, sequence"));
+    }
+
+    #[test]
+    fn test_read_all() {
+        let record_batch = read_record_batch(fixture_reader(), FastqOptions::default());
+        assert_eq!(record_batch.num_rows(), 3);
+        let column = record_batch.column_by_name("quality_scores").unwrap();
+        let quality_scores = column.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(quality_scores.value(0), "IIII#");
+        assert_eq!(quality_scores.value(2), "");
+    }
+
+    #[test]
+    fn test_decode_quality() {
+        let options = FastqOptions {
+            decode_quality: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch(fixture_reader(), options);
+        assert_eq!(
+            decoded_scores(&record_batch),
+            vec![
+                Some(vec![40, 40, 40, 40, 2]),
+                Some(vec![0, 10, 20, 30]),
+                Some(vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_quality_offset_64() {
+        let reader = FastqReader::new(&b"@r0\nACGT\n+\nhh@B\n@r1\nAC\n+\n5h\n"[..]).unwrap();
+        let options = FastqOptions {
+            decode_quality: Some(true),
+            quality_offset: Some(64),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch(reader, options);
+        // `5` is below the Phred+64 offset.
+        assert_eq!(
+            decoded_scores(&record_batch),
+            vec![Some(vec![40, 40, 0, 2]), None]
+        );
+    }
+
+    #[test]
+    fn test_sequence_stats() {
+        let options = FastqOptions {
+            include_length: Some(true),
+            include_gc: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch(fixture_reader(), options);
+        let column = record_batch.column_by_name("length").unwrap();
+        let length = column.as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(length.values(), &[5, 4, 0]);
+        let column = record_batch.column_by_name("gc_content").unwrap();
+        let gc_content = column.as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(gc_content.value(0), 0.5);
+        assert_eq!(gc_content.value(1), 1.0);
+        assert!(gc_content.is_null(2));
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(fixture_reader().count().unwrap(), 3);
+        let mut reader = FastqReader::new(&b"@r0\nACGT\n+\nhh@B\n@r1\n"[..]).unwrap();
+        assert!(reader.count().is_err());
+    }
+
+    #[test]
+    fn test_read_compressed() {
+        let expected = read_record_batch(fixture_reader(), FastqOptions::default());
+        for name in ["sample.fastq.gz", "sample.fastq.bgz"] {
+            let record_batch =
+                read_record_batch(fixture_reader_from(name), FastqOptions::default());
+            assert_eq!(record_batch.columns(), expected.columns());
+        }
+    }
+
+    #[test]
+    fn test_read_pipe() {
+        // A pipe, e.g. standard input, which can only be read.
+        struct Pipe(std::io::Cursor<Vec<u8>>);
+        impl io::Read for Pipe {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+        let expected = read_record_batch(fixture_reader(), FastqOptions::default());
+        for name in ["sample.fastq", "sample.fastq.gz"] {
+            let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            dir.push("../fixtures");
+            dir.push(name);
+            let pipe = Pipe(std::io::Cursor::new(std::fs::read(dir).unwrap()));
+            let reader = FastqReader::new(decompress(BufReader::new(pipe)).unwrap()).unwrap();
+            let record_batch = read_record_batch(reader, FastqOptions::default());
+            assert_eq!(record_batch.columns(), expected.columns());
+        }
+    }
+
+    #[test]
+    fn test_parse_casava() {
+        let casava = Casava::parse("EAS139:136:FC706VJ:2:2104:15343:197393", "1:Y:18:ATCACG");
+        assert_eq!(
+            casava,
+            Some(Casava {
+                instrument: "EAS139",
+                lane: 2,
+                tile: 2104,
+                x: 15343,
+                y: 197393,
+                read: 1,
+                is_filtered: true,
+                control: 18,
+                index: "ATCACG",
+            })
+        );
+        assert!(Casava::parse("EAS139:136:FC706VJ:2:2104:15343:197393", "1:X:18:A").is_none());
+        assert!(Casava::parse("r0", "desc zero").is_none());
+
+        let data = b"@M1:7:FC1:1:11:100:200 2:N:0:ACGT+TTGA\nAC\n+\nII\n@r1 free text\nAC\n+\nII\n";
+        let options = FastqOptions {
+            parse_casava: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch(FastqReader::new(&data[..]).unwrap(), options);
+        let column = record_batch.column_by_name("description").unwrap();
+        let description = column.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(description.value(1), "free text");
+        let column = record_batch.column_by_name("index").unwrap();
+        let index = column.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(index.iter().collect::<Vec<_>>(), [Some("ACGT+TTGA"), None]);
+        let column = record_batch.column_by_name("is_filtered").unwrap();
+        let is_filtered = column.as_any().downcast_ref::<BooleanArray>().unwrap();
+        assert_eq!(is_filtered.iter().collect::<Vec<_>>(), [Some(false), None]);
+        assert_eq!(record_batch.num_columns(), 13);
+    }
+
+    #[test]
+    fn test_filter_length() {
+        let data = b"@r0\nA\n+\nI\n@r1\nACG\n+\nIII\n@r2\nACGTA\n+\nIIIII\n";
+        let options = FastqOptions {
+            min_length: Some(3),
+            max_length: Some(5),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch(FastqReader::new(&data[..]).unwrap(), options.clone());
+        let column = record_batch.column_by_name("name").unwrap();
+        let name = column.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(name.iter().collect::<Vec<_>>(), [Some("r1"), Some("r2")]);
+        let options = FastqOptions {
+            max_length: Some(2),
+            ..options
+        };
+        let mut reader = FastqReader::new(&data[..]).unwrap().with_options(options);
+        assert_eq!(reader.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_kmers() {
+        use arrow::array::AsArray;
+
+        let data = b"@r0\nACGTA\n+\nIIIII\n@r1\nANNNA\n+\nIIIII\n";
+        let options = FastqOptions {
+            kmer_size: Some(4),
+            allow_ambiguous_kmers: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch(FastqReader::new(&data[..]).unwrap(), options);
+        let kmers = record_batch
+            .column_by_name("kmers")
+            .unwrap()
+            .as_list::<i32>();
+        let kmers_r1 = kmers.value(1);
+        let kmers_r1: Vec<_> = kmers_r1.as_string::<i32>().iter().flatten().collect();
+        assert_eq!(kmers_r1, ["ANNN", "NNNA"]);
+        assert_eq!(kmers.value_length(0), 2);
+    }
+
+    #[test]
+    fn test_count_motifs() {
+        let data = b"@r0\nCGCGA\n+\nIIIII\n@r1\nCCAGGcctgg\n+\nIIIIIIIIII\n";
+        let options = FastqOptions {
+            count_motifs: Some(vec!["cg".to_string(), "CCWGG".to_string()]),
+            kmer_size: Some(4),
+            parse_casava: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch(FastqReader::new(&data[..]).unwrap(), options);
+        let names: Vec<_> = record_batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect();
+        let kmers = names.iter().position(|name| name == "kmers").unwrap();
+        assert_eq!(
+            names[kmers + 1..kmers + 4],
+            ["CG_count", "CCWGG_count", "instrument"]
+        );
+        let counts = |name| {
+            let column = record_batch.column_by_name(name).unwrap();
+            column
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        };
+        assert_eq!(counts("CG_count"), [2, 0]);
+        assert_eq!(counts("CCWGG_count"), [0, 2]);
+    }
+
+    fn pair_names(ipc: Vec<u8>) -> Vec<String> {
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let columns = ["name", "seq_1", "qual_1", "seq_2", "qual_2"];
+        assert_eq!(record_batch.schema().fields().len(), columns.len());
+        let column = record_batch.column_by_name("name").unwrap();
+        let names = column.as_any().downcast_ref::<StringArray>().unwrap();
+        names.iter().map(|name| name.unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_paired() {
+        let r1 = b"@p0/1\nACGT\n+\nIIII\n@p1 1:N:0:ACGT\nAC\n+\nII\n";
+        let r2 = b"@p0/2\nTTTT\n+\n####\n@p1 2:N:0:ACGT\nGG\n+\nII\n";
+        let mut reader = FastqReader::new(&r1[..]).unwrap();
+        let mut mates = FastqReader::new(&r2[..]).unwrap();
+        assert_eq!(
+            pair_names(reader.paired_to_ipc(&mut mates).unwrap()),
+            ["p0", "p1"]
+        );
+
+        let mut reader = FastqReader::new(&r1[..]).unwrap();
+        let mut mates = FastqReader::new(&b"@p0/2\nTTTT\n+\n####\n@p2\nGG\n+\nII\n"[..]).unwrap();
+        let error = reader.paired_to_ipc(&mut mates).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Read pair 2 has mismatched names: p1 and p2."));
+
+        let mut reader = FastqReader::new(&r1[..]).unwrap();
+        let mut mates = FastqReader::new(&r2[..18]).unwrap();
+        let error = reader.paired_to_ipc(&mut mates).unwrap_err();
+        assert!(error.to_string().contains("missing a mate"));
+    }
+
+    #[test]
+    fn test_interleaved() {
+        let data =
+            b"@p0/1\nACGT\n+\nIIII\n@p0/2\nTTTT\n+\n####\n@p1/1\nAC\n+\nII\n@p1/2\nGG\n+\nII\n";
+        let mut reader = FastqReader::new(&data[..]).unwrap();
+        assert_eq!(
+            pair_names(reader.interleaved_to_ipc().unwrap()),
+            ["p0", "p1"]
+        );
+
+        let mut reader = FastqReader::new(&data[..50]).unwrap();
+        let error = reader.interleaved_to_ipc().unwrap_err();
+        assert!(error.to_string().contains("Read pair 2 is missing a mate."));
     }
 }
@@ -0,0 +1,265 @@
+//! Flattening list columns into one row per item, like `explode` in pandas, for tools that can't
+//! read Arrow lists, e.g. GFF `Array` attributes or BAM `B` tags.
+use std::io;
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, StructArray, UInt32Builder};
+use arrow::compute::take;
+use arrow::datatypes::{DataType, Field, Fields, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+
+use crate::stats::write_stats;
+
+/// What to make of rows whose list is empty or null.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum EmptyLists {
+    /// Keep the row once, with a null item.
+    #[default]
+    Null,
+    /// Drop the row.
+    Drop,
+}
+
+impl FromStr for EmptyLists {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "null" => Ok(Self::Null),
+            "drop" => Ok(Self::Drop),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid empty lists policy: {}. Expected null or drop.", s),
+            )),
+        }
+    }
+}
+
+/// Returns `batch` with one row per item of the list column `column`, whose other columns are
+/// repeated for each item. A field of a struct column, e.g. a GFF attribute, is named with a
+/// dot, as in `attributes.Parent`.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use arrow::array::{ArrayRef, Int32Array, ListArray};
+/// use arrow::datatypes::Int32Type;
+/// use arrow::record_batch::RecordBatch;
+/// use oxbow::flatten::{flatten_batch, EmptyLists};
+///
+/// let ids = Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef;
+/// let items = ListArray::from_iter_primitive::<Int32Type, _, _>([
+///     Some(vec![Some(10), Some(20)]),
+///     Some(vec![]),
+/// ]);
+/// let batch = RecordBatch::try_from_iter([("id", ids), ("items", Arc::new(items) as ArrayRef)])
+///     .unwrap();
+/// let flat = flatten_batch(&batch, "items", EmptyLists::Drop).unwrap();
+/// assert_eq!(flat.num_rows(), 2);
+/// ```
+pub fn flatten_batch(
+    batch: &RecordBatch,
+    column: &str,
+    empty_lists: EmptyLists,
+) -> Result<RecordBatch, ArrowError> {
+    let invalid = |expected: &str| {
+        ArrowError::InvalidArgumentError(format!(
+            "Invalid column to flatten: {}. Expected {}.",
+            column, expected
+        ))
+    };
+    let mut path = column.split('.');
+    let name = path.next().unwrap_or_default();
+    let path: Vec<&str> = path.collect();
+    let index = batch
+        .schema()
+        .index_of(name)
+        .map_err(|_| invalid("a column of the batch"))?;
+    let mut target = batch.column(index).clone();
+    for name in &path {
+        let DataType::Struct(_) = target.data_type() else {
+            return Err(invalid("a struct field, named after its struct column"));
+        };
+        target = target
+            .as_struct()
+            .column_by_name(name)
+            .cloned()
+            .ok_or_else(|| invalid("a field of its struct column"))?;
+    }
+    let DataType::List(item) = target.data_type() else {
+        return Err(invalid("a list column"));
+    };
+    let list = target.as_list::<i32>();
+
+    // The row of each item, and the index of the item in the values of the list, if any.
+    let mut rows = UInt32Builder::new();
+    let mut items = UInt32Builder::new();
+    let offsets = list.value_offsets();
+    for i in 0..list.len() {
+        let (start, end) = (offsets[i] as u32, offsets[i + 1] as u32);
+        if list.is_valid(i) && start < end {
+            for item in start..end {
+                rows.append_value(i as u32);
+                items.append_value(item);
+            }
+        } else if empty_lists == EmptyLists::Null {
+            rows.append_value(i as u32);
+            items.append_null();
+        }
+    }
+    let (rows, items) = (rows.finish(), items.finish());
+    let values = take(list.values().as_ref(), &items, None)?;
+    let item_type = item.data_type().clone();
+
+    let schema = batch.schema();
+    let mut fields = schema.fields().to_vec();
+    let mut columns = batch
+        .columns()
+        .iter()
+        .map(|column| take(column.as_ref(), &rows, None))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (field, column) =
+        replace_nested(&fields[index], &columns[index], &path, item_type, values)?;
+    (fields[index], columns[index]) = (Arc::new(field), column);
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
+/// Replaces the field at `path` in a struct column (or the column itself, if `path` is empty)
+/// with `values`, of the item type of the list.
+fn replace_nested(
+    field: &Field,
+    array: &ArrayRef,
+    path: &[&str],
+    item_type: DataType,
+    values: ArrayRef,
+) -> Result<(Field, ArrayRef), ArrowError> {
+    let Some((name, path)) = path.split_first() else {
+        let field = field.clone().with_data_type(item_type);
+        return Ok((field.with_nullable(true), values));
+    };
+    let array = array.as_struct();
+    let (fields, mut columns, nulls) = array.clone().into_parts();
+    let mut fields = fields.to_vec();
+    let index = fields
+        .iter()
+        .position(|field| field.name() == name)
+        .expect("the path was resolved");
+    let (child, column) = replace_nested(&fields[index], &columns[index], path, item_type, values)?;
+    (fields[index], columns[index]) = (Arc::new(child), column);
+    let fields = Fields::from(fields);
+    let array = StructArray::try_new(fields.clone(), columns, nulls)?;
+    let field = field.clone().with_data_type(DataType::Struct(fields));
+    Ok((field, Arc::new(array)))
+}
+
+/// Flattens the list column `column` of Arrow IPC file bytes, as returned by the readers, like
+/// [`flatten_batch`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::flatten::{flatten_list_columns, EmptyLists};
+/// use oxbow::gff::GffReader;
+/// use oxbow::gxf::{AttributeDef, AttributeType};
+///
+/// let mut reader = GffReader::new_from_path("sample.gff").unwrap();
+/// let defs = [AttributeDef::new("Parent", AttributeType::Array)];
+/// let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+/// let ipc = flatten_list_columns(&ipc, "attributes.Parent", EmptyLists::Null).unwrap();
+/// ```
+pub fn flatten_list_columns(
+    ipc: &[u8],
+    column: &str,
+    empty_lists: EmptyLists,
+) -> Result<Vec<u8>, ArrowError> {
+    let reader = FileReader::try_new(Cursor::new(ipc), None)?;
+    // The schema of the flattened batches, even if there are none.
+    let empty = RecordBatch::new_empty(reader.schema());
+    let schema = flatten_batch(&empty, column, empty_lists)?.schema();
+    let mut writer = FileWriter::try_new(Vec::new(), &schema)?;
+    for (index, batch) in reader.enumerate() {
+        let batch = flatten_batch(&batch?, column, empty_lists)?;
+        write_stats(&mut writer, index, &batch);
+        writer.write(&batch)?;
+    }
+    writer.finish()?;
+    writer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gff::GffReader;
+    use crate::gxf::{AttributeDef, AttributeType};
+    use arrow::array::StringArray;
+
+    fn read_gff() -> RecordBatch {
+        let data = "##gff-version 3\n\
+            chr1\ttest\texon\t100\t200\t.\t+\t.\tID=e1;Parent=t1,t2\n\
+            chr1\ttest\tgene\t100\t900\t.\t+\t.\tID=g1\n\
+            chr1\ttest\texon\t300\t400\t.\t+\t.\tID=e2;Parent=t2\n";
+        let defs = [
+            AttributeDef::new("ID", AttributeType::String),
+            AttributeDef::new("Parent", AttributeType::Array),
+        ];
+        let mut reader = GffReader::new(data.as_bytes()).unwrap();
+        let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+        let ipc = flatten_list_columns(&ipc, "attributes.Parent", EmptyLists::Null).unwrap();
+        let mut arrow_reader = FileReader::try_new(Cursor::new(ipc), None).unwrap();
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    fn strings(array: &ArrayRef) -> Vec<Option<&str>> {
+        let array = array.as_any().downcast_ref::<StringArray>().unwrap();
+        array.iter().collect()
+    }
+
+    #[test]
+    fn test_flatten_gff_parent() {
+        let batch = read_gff();
+        let attributes = batch.column_by_name("attributes").unwrap().as_struct();
+        let ids = attributes.column_by_name("ID").unwrap();
+        let parents = attributes.column_by_name("Parent").unwrap();
+        assert_eq!(parents.data_type(), &DataType::Utf8);
+        assert_eq!(
+            strings(ids),
+            [Some("e1"), Some("e1"), Some("g1"), Some("e2")]
+        );
+        assert_eq!(strings(parents), [Some("t1"), Some("t2"), None, Some("t2")]);
+
+        // A string column isn't a list.
+        let error = flatten_batch(&batch, "attributes.ID", EmptyLists::Null).unwrap_err();
+        assert!(error.to_string().contains("Expected a list column"));
+    }
+
+    #[test]
+    fn test_flatten_drop_empty() {
+        let data = "##gff-version 3\n\
+            chr1\ttest\texon\t100\t200\t.\t+\t.\tParent=t1,t2\n\
+            chr1\ttest\tgene\t100\t900\t.\t+\t.\tID=g1\n";
+        let defs = [AttributeDef::new("Parent", AttributeType::Array)];
+        let mut reader = GffReader::new(data.as_bytes()).unwrap();
+        let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+        let ipc = flatten_list_columns(&ipc, "attributes.Parent", EmptyLists::Drop).unwrap();
+        let batch = FileReader::try_new(Cursor::new(ipc), None)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let parents = batch.column_by_name("attributes").unwrap().as_struct();
+        let parents = parents.column_by_name("Parent").unwrap();
+        assert_eq!(strings(parents), [Some("t1"), Some("t2")]);
+        assert_eq!("drop".parse::<EmptyLists>().unwrap(), EmptyLists::Drop);
+
+        let missing = flatten_batch(&batch, "attributes.Missing", EmptyLists::Drop);
+        assert!(missing.is_err());
+    }
+}
@@ -1,22 +1,107 @@
+//! A reader for the GFF3 format.
+//!
+//! Records are read up to the `##FASTA` section, if any, whose sequences can be read afterwards
+//! with [`GffReader::embedded_fasta`]. `##sequence-region` directives are kept in the
+//! `gff.sequence_regions` schema metadata, as comma-separated `seqid:start-end` regions.
+//!
+//! `start` and `end` are Int64, for features past 2^31 on large chromosomes, as are those of
+//! [`crate::gtf`].
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use arrow::array::{ArrayRef, Float32Builder, GenericStringBuilder, Int32Builder};
+use arrow::array::{
+    ArrayRef, Float32Builder, GenericStringBuilder, Int64Builder, Int8Builder,
+    StringDictionaryBuilder,
+};
+use arrow::datatypes::{Fields, Int32Type};
 use arrow::error::ArrowError;
 use arrow::record_batch::RecordBatch;
 use noodles::gff;
+use noodles::gff::directive::SequenceRegion;
+use noodles::gff::record::Phase;
+use noodles::gff::{Directive, Line};
 
-use crate::batch_builder::{write_ipc_err, BatchBuilder};
+use crate::batch_builder::{
+    builder_fields, check_dictionary_encode, count_err, finish_batch, provenance, select_fields,
+    BatchBuilder, StringColumnBuilder,
+};
+use crate::coordinates::CoordinateSystem;
+use crate::error_policy::{tolerate, ErrorPolicy, RecordError};
+use crate::fasta::FastaReader;
+use crate::gxf::{
+    feature_type_matches, gff_attributes, gff_encoded_attributes, gff_parent_ids, strand_builder,
+    AttributeDef, AttributeScanner, AttributeValue, AttributesBuilder, ParentIdsBuilder,
+};
+
+/// Options for converting GFF records to Arrow.
+///
+/// Each option can also be set with the matching `with_*` method of [`GffReader`].
+#[derive(Clone, Debug, Default)]
+pub struct GffOptions {
+    /// Add a `parent_id` column (`List<Utf8>`) with the IDs of each feature's parents.
+    ///
+    /// The hierarchy is harmonized across GFF3 and GTF, so that features can be grouped the same
+    /// way for both. See [`gff_parent_ids`](crate::gxf::gff_parent_ids).
+    pub parent_ids: Option<bool>,
+    /// String columns to dictionary-encode, out of `seqid`, `source` and `type`.
+    ///
+    /// `strand` is always dictionary-encoded.
+    pub dictionary_encode: Option<Vec<String>>,
+    /// The coordinate system of the `start` column. Defaults to the native 1-based, closed
+    /// coordinates. See [`crate::coordinates`].
+    pub coordinate_system: Option<CoordinateSystem>,
+    /// Emit a missing `score` as NaN, a missing `phase` as -1 and missing attribute fields as
+    /// empty strings or lists, instead of nulls, for tools that don't handle nulls. Defaults to
+    /// nulls.
+    pub missing_as_empty: Option<bool>,
+    /// Percent-decode the values of the attribute fields, e.g. `a%3Bb` into `a;b`, as GFF3
+    /// requires. Defaults to on. When off, the values are kept percent-encoded, as they are
+    /// written. The raw `attributes` column is always percent-encoded.
+    pub percent_decode: Option<bool>,
+    /// Infer `Int64` and `Float64` attribute types in [`GffReader::scan_attribute_defs`], from
+    /// the values of each attribute. Defaults to off, which is faster and reports all
+    /// single-valued attributes as strings.
+    pub infer_attribute_types: Option<bool>,
+    /// Force the types of the given attributes in [`GffReader::scan_attribute_defs`],
+    /// overriding inference. Other attributes are still scanned as usual.
+    pub attribute_type_overrides: Option<Vec<AttributeDef>>,
+    /// Select the features whose `type` is one of the given types, matched exactly. Other
+    /// features are skipped before their attributes are read, so they aren't counted nor seen by
+    /// [`GffReader::scan_attribute_defs`]. Defaults to all features.
+    pub feature_types: Option<Vec<String>>,
+    /// Match [`GffOptions::feature_types`] ignoring ASCII case, e.g. so that `cds` selects `CDS`
+    /// features. Defaults to case-sensitive.
+    pub ignore_feature_type_case: Option<bool>,
+    /// What to do with records that fail to parse. Defaults to [`ErrorPolicy::Fail`].
+    pub on_error: Option<ErrorPolicy>,
+    /// The columns to return, in this order, e.g. `["type", "seqid", "start"]`. Defaults to all
+    /// of them, in their default order.
+    pub fields: Option<Vec<String>>,
+}
 
 pub struct GffReader<R> {
     reader: gff::Reader<R>,
+    options: GffOptions,
+    errors: Vec<RecordError>,
+    sequence_regions: Vec<SequenceRegion>,
+    at_fasta: bool,
+    path: Option<PathBuf>,
 }
 
 impl GffReader<BufReader<File>> {
     pub fn new_from_path(path: &str) -> std::io::Result<Self> {
         let reader = File::open(path).map(BufReader::new).map(gff::Reader::new)?;
-        Ok(Self { reader })
+        Ok(Self {
+            reader,
+            options: GffOptions::default(),
+            errors: Vec::new(),
+            sequence_regions: Vec::new(),
+            at_fasta: false,
+            path: Some(PathBuf::from(path)),
+        })
     }
 }
 
@@ -26,53 +111,368 @@ where
 {
     pub fn new(read: R) -> std::io::Result<Self> {
         let reader = gff::Reader::new(read);
-        Ok(Self { reader })
+        Ok(Self {
+            reader,
+            options: GffOptions::default(),
+            errors: Vec::new(),
+            sequence_regions: Vec::new(),
+            at_fasta: false,
+            path: None,
+        })
+    }
+
+    /// Sets the options of the conversion to Arrow, replacing any set before.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gff::{GffOptions, GffReader};
+    ///
+    /// let options = GffOptions {
+    ///     parent_ids: Some(true),
+    ///     feature_types: Some(vec!["exon".to_string()]),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = GffReader::new_from_path("sample.gff")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// ```
+    pub fn with_options(mut self, options: GffOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets [`GffOptions::parent_ids`].
+    pub fn with_parent_ids(mut self, parent_ids: bool) -> Self {
+        self.options.parent_ids = Some(parent_ids);
+        self
+    }
+
+    /// Sets [`GffOptions::dictionary_encode`].
+    pub fn with_dictionary_encode(mut self, dictionary_encode: Option<Vec<String>>) -> Self {
+        self.options.dictionary_encode = dictionary_encode;
+        self
+    }
+
+    /// Sets [`GffOptions::coordinate_system`].
+    pub fn with_coordinate_system(mut self, coordinate_system: CoordinateSystem) -> Self {
+        self.options.coordinate_system = Some(coordinate_system);
+        self
+    }
+
+    /// Sets [`GffOptions::missing_as_empty`].
+    pub fn with_missing_as_empty(mut self, missing_as_empty: bool) -> Self {
+        self.options.missing_as_empty = Some(missing_as_empty);
+        self
+    }
+
+    /// Sets [`GffOptions::percent_decode`].
+    pub fn with_percent_decoding(mut self, percent_decode: bool) -> Self {
+        self.options.percent_decode = Some(percent_decode);
+        self
+    }
+
+    /// Sets [`GffOptions::infer_attribute_types`].
+    pub fn with_attribute_type_inference(mut self, infer_attribute_types: bool) -> Self {
+        self.options.infer_attribute_types = Some(infer_attribute_types);
+        self
+    }
+
+    /// Sets [`GffOptions::attribute_type_overrides`].
+    pub fn with_attribute_type_overrides(mut self, overrides: Vec<AttributeDef>) -> Self {
+        self.options.attribute_type_overrides = Some(overrides);
+        self
+    }
+
+    /// Sets [`GffOptions::feature_types`].
+    pub fn with_feature_types(mut self, feature_types: Option<Vec<String>>) -> Self {
+        self.options.feature_types = feature_types;
+        self
+    }
+
+    /// Sets [`GffOptions::ignore_feature_type_case`].
+    pub fn with_ignore_feature_type_case(mut self, ignore_case: bool) -> Self {
+        self.options.ignore_feature_type_case = Some(ignore_case);
+        self
+    }
+
+    /// Sets [`GffOptions::on_error`].
+    pub fn with_error_policy(mut self, on_error: ErrorPolicy) -> Self {
+        self.options.on_error = Some(on_error);
+        self
+    }
+
+    /// Sets [`GffOptions::fields`].
+    pub fn with_fields(mut self, fields: Option<Vec<String>>) -> Self {
+        self.options.fields = fields;
+        self
+    }
+
+    /// Returns the fields of the batches returned by [`GffReader::records_to_ipc`] with the given
+    /// attribute definitions and the current options, without reading any records.
+    pub fn fields(&self, attribute_defs: Option<&[AttributeDef]>) -> Result<Fields, ArrowError> {
+        builder_fields(GffBatchBuilder::new(0, attribute_defs, &self.options)?)
     }
 
     /// Returns the records in the given region as Apache Arrow IPC.
     ///
+    /// If `attribute_defs` is given, the `attributes` column is a struct with one typed field per
+    /// definition. Otherwise, it holds the raw attributes string.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gff::GffReader;
+    ///
+    /// let mut reader = GffReader::new_from_path("sample.gff").unwrap();
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn records_to_ipc(
+        &mut self,
+        attribute_defs: Option<&[AttributeDef]>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        let mut batch_builder = GffBatchBuilder::new(1024, attribute_defs, &self.options)?;
+        for result in self.records() {
+            let record = result.map_err(|e| ArrowError::ExternalError(e.into()))?;
+            batch_builder.push(&record);
+        }
+        // The directives are only known once the records have been read.
+        let mut metadata = provenance("gff", self.path.as_deref(), &[]);
+        if !self.sequence_regions.is_empty() {
+            let regions: Vec<String> = self
+                .sequence_regions
+                .iter()
+                .map(|region| {
+                    format!(
+                        "{}:{}-{}",
+                        region.reference_sequence_name(),
+                        region.start(),
+                        region.end()
+                    )
+                })
+                .collect();
+            metadata.insert("gff.sequence_regions".to_string(), regions.join(","));
+        }
+        finish_batch(batch_builder.with_metadata(metadata))
+    }
+
+    /// Returns the number of records, without converting them to Arrow.
+    ///
     /// # Examples
     ///
     /// ```no_run
     /// use oxbow::gff::GffReader;
     ///
     /// let mut reader = GffReader::new_from_path("sample.gff").unwrap();
-    /// let ipc = reader.records_to_ipc().unwrap();
+    /// let n = reader.count().unwrap();
     /// ```
-    pub fn records_to_ipc(&mut self) -> Result<Vec<u8>, ArrowError> {
-        let batch_builder = GffBatchBuilder::new(1024)?;
+    pub fn count(&mut self) -> Result<usize, ArrowError> {
         let records = self
-            .reader
             .records()
             .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-        write_ipc_err(records, batch_builder)
+        count_err(records)
+    }
+
+    /// Returns the `##sequence-region` directives read so far.
+    pub fn sequence_regions(&self) -> &[SequenceRegion] {
+        &self.sequence_regions
+    }
+
+    /// Returns the records skipped by the last scan under [`ErrorPolicy::SkipAndCollect`].
+    pub fn errors(&self) -> &[RecordError] {
+        &self.errors
+    }
+
+    /// Returns the records not read yet, of the selected feature types, up to the `##FASTA`
+    /// section.
+    fn records(&mut self) -> impl Iterator<Item = io::Result<gff::Record>> + '_ {
+        let records = Records {
+            lines: self.reader.lines(),
+            line: 0,
+            sequence_regions: &mut self.sequence_regions,
+            at_fasta: &mut self.at_fasta,
+        };
+        let (feature_types, ignore_case) = (
+            self.options.feature_types.as_deref(),
+            self.options.ignore_feature_type_case.unwrap_or(false),
+        );
+        let on_error = self.options.on_error.unwrap_or_default();
+        tolerate(records, on_error, &mut self.errors).filter(move |result| {
+            result.as_ref().map_or(true, |record| {
+                feature_type_matches(feature_types, ignore_case, record.ty())
+            })
+        })
+    }
+
+    /// Returns the distinct reference sequences (`seqid`) of the records not read yet, sorted,
+    /// without converting them to Arrow.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gff::GffReader;
+    ///
+    /// let mut reader = GffReader::new_from_path("sample.gff").unwrap();
+    /// let chroms = reader.chroms().unwrap();
+    /// ```
+    pub fn chroms(&mut self) -> io::Result<Vec<String>> {
+        let mut chroms = BTreeSet::new();
+        for result in self.records() {
+            let record = result?;
+            if !chroms.contains(record.reference_sequence_name()) {
+                chroms.insert(record.reference_sequence_name().to_string());
+            }
+        }
+        Ok(chroms.into_iter().collect())
+    }
+
+    /// Reads all records and returns the attributes found, in order of first appearance.
+    ///
+    /// This consumes the reader, so a new reader is needed to read the records afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gff::GffReader;
+    ///
+    /// let defs = GffReader::new_from_path("sample.gff")
+    ///     .unwrap()
+    ///     .scan_attribute_defs()
+    ///     .unwrap();
+    /// let mut reader = GffReader::new_from_path("sample.gff").unwrap();
+    /// let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+    /// ```
+    pub fn scan_attribute_defs(&mut self) -> std::io::Result<Vec<AttributeDef>> {
+        let mut scanner = AttributeScanner::new()
+            .with_type_inference(self.options.infer_attribute_types.unwrap_or(false))
+            .with_overrides(
+                self.options
+                    .attribute_type_overrides
+                    .clone()
+                    .unwrap_or_default(),
+            );
+        for result in self.records() {
+            let record = result?;
+            scanner.push_gff(&record);
+        }
+        Ok(scanner.collect())
+    }
+}
+
+impl<R> GffReader<R>
+where
+    R: BufRead + 'static,
+{
+    /// Returns a reader over the sequences of the `##FASTA` section, or `None` if there is none.
+    ///
+    /// Records not read yet are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gff::GffReader;
+    ///
+    /// let mut reader = GffReader::new_from_path("sample.gff").unwrap();
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// if let Some(mut fasta) = reader.embedded_fasta().unwrap() {
+    ///     let ipc = fasta.records_to_ipc(None).unwrap();
+    /// }
+    /// ```
+    pub fn embedded_fasta(mut self) -> io::Result<Option<FastaReader>> {
+        for result in self.records() {
+            result?;
+        }
+        Ok(self
+            .at_fasta
+            .then(|| FastaReader::new_from_reader(self.reader.into_inner())))
+    }
+}
+
+/// The records of a GFF file, collecting `##sequence-region` directives and stopping at the
+/// `##FASTA` section.
+struct Records<'a, R> {
+    lines: gff::reader::Lines<'a, R>,
+    line: usize,
+    sequence_regions: &'a mut Vec<SequenceRegion>,
+    at_fasta: &'a mut bool,
+}
+
+impl<R: BufRead> Iterator for Records<'_, R> {
+    type Item = (usize, io::Result<gff::Record>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Past the `##FASTA` line, the lines are sequences rather than records.
+        if *self.at_fasta {
+            return None;
+        }
+        loop {
+            let result = self.lines.next()?;
+            self.line += 1;
+            match result {
+                Ok(Line::Record(record)) => return Some((self.line, Ok(record))),
+                Ok(Line::Directive(Directive::SequenceRegion(region))) => {
+                    self.sequence_regions.push(region)
+                }
+                Ok(Line::Directive(Directive::StartOfFasta)) => {
+                    *self.at_fasta = true;
+                    return None;
+                }
+                Ok(_) => {}
+                Err(e) => return Some((self.line, Err(e))),
+            }
+        }
     }
 }
 
 struct GffBatchBuilder {
-    reference_sequence_name: GenericStringBuilder<i32>,
-    source: GenericStringBuilder<i32>,
-    ty: GenericStringBuilder<i32>,
-    start: Int32Builder,
-    end: Int32Builder,
+    coordinate_system: CoordinateSystem,
+    reference_sequence_name: StringColumnBuilder,
+    source: StringColumnBuilder,
+    ty: StringColumnBuilder,
+    start: Int64Builder,
+    end: Int64Builder,
     score: Float32Builder,
-    strand: GenericStringBuilder<i32>,
-    phase: GenericStringBuilder<i32>,
+    strand: StringDictionaryBuilder<Int32Type>,
+    phase: Int8Builder,
     attributes: GenericStringBuilder<i32>,
+    attribute_fields: Option<AttributesBuilder>,
+    parent_ids: Option<ParentIdsBuilder>,
+    missing_as_empty: bool,
+    fields: Option<Vec<String>>,
+    percent_decode: bool,
 }
 
 impl GffBatchBuilder {
-    pub fn new(capacity: usize) -> Result<Self, ArrowError> {
+    pub fn new(
+        capacity: usize,
+        attribute_defs: Option<&[AttributeDef]>,
+        options: &GffOptions,
+    ) -> Result<Self, ArrowError> {
+        let dictionary_encode = options.dictionary_encode.as_deref();
+        let missing_as_empty = options.missing_as_empty.unwrap_or(false);
+        check_dictionary_encode(dictionary_encode, &["seqid", "source", "type"])?;
         Ok(Self {
-            reference_sequence_name: GenericStringBuilder::<i32>::new(),
-            source: GenericStringBuilder::<i32>::new(),
-            ty: GenericStringBuilder::<i32>::new(),
-            start: Int32Builder::with_capacity(capacity),
-            end: Int32Builder::with_capacity(capacity),
+            coordinate_system: options
+                .coordinate_system
+                .unwrap_or(CoordinateSystem::OneBased),
+            reference_sequence_name: StringColumnBuilder::new("seqid", dictionary_encode),
+            source: StringColumnBuilder::new("source", dictionary_encode),
+            ty: StringColumnBuilder::new("type", dictionary_encode),
+            start: Int64Builder::with_capacity(capacity),
+            end: Int64Builder::with_capacity(capacity),
             score: Float32Builder::new(),
-            strand: GenericStringBuilder::<i32>::new(),
-            phase: GenericStringBuilder::<i32>::new(),
+            strand: strand_builder(capacity)?,
+            phase: Int8Builder::with_capacity(capacity),
             attributes: GenericStringBuilder::<i32>::new(),
+            attribute_fields: attribute_defs
+                .map(|defs| AttributesBuilder::new(defs, missing_as_empty)),
+            parent_ids: options
+                .parent_ids
+                .unwrap_or(false)
+                .then(ParentIdsBuilder::new),
+            missing_as_empty,
+            fields: options.fields.clone(),
+            percent_decode: options.percent_decode.unwrap_or(true),
         })
     }
 }
@@ -85,50 +485,89 @@ impl BatchBuilder for GffBatchBuilder {
             .append_value(record.reference_sequence_name());
         self.source.append_value(record.source());
         self.ty.append_value(record.ty());
-        self.start.append_value(usize::from(record.start()) as i32);
-        self.end.append_value(usize::from(record.end()) as i32);
+        let start = usize::from(record.start()) as i64;
+        self.start.append_value(
+            self.coordinate_system
+                .start_from(CoordinateSystem::OneBased, start),
+        );
+        self.end.append_value(usize::from(record.end()) as i64);
         match record.score() {
             Some(score) => self.score.append_value(score),
+            None if self.missing_as_empty => self.score.append_value(f32::NAN),
             None => self.score.append_null(),
         }
         self.strand.append_value(record.strand());
-        match record.phase() {
-            Some(phase) => self.phase.append_value(phase),
-            None => self.phase.append_null(),
+        self.phase.append_option(
+            record
+                .phase()
+                .map(|phase| match phase {
+                    Phase::Zero => 0,
+                    Phase::One => 1,
+                    Phase::Two => 2,
+                })
+                .or(self.missing_as_empty.then_some(-1)),
+        );
+        match self.attribute_fields.as_mut() {
+            Some(builder) if self.percent_decode => builder.push(gff_attributes(record)),
+            Some(builder) => {
+                let attributes = gff_encoded_attributes(record);
+                builder.push(
+                    attributes
+                        .iter()
+                        .map(|(name, value)| (*name, AttributeValue::from(value))),
+                )
+            }
+            None => self
+                .attributes
+                .append_value(record.attributes().to_string()),
+        }
+        if let Some(builder) = self.parent_ids.as_mut() {
+            builder.push(&gff_parent_ids(record));
         }
-        self.attributes
-            .append_value(record.attributes().to_string());
     }
 
     fn finish(mut self) -> Result<RecordBatch, ArrowError> {
-        RecordBatch::try_from_iter(vec![
-            (
-                "seqid",
-                Arc::new(self.reference_sequence_name.finish()) as ArrayRef,
-            ),
-            ("source", Arc::new(self.source.finish()) as ArrayRef),
-            ("type", Arc::new(self.ty.finish()) as ArrayRef),
+        let attributes = match self.attribute_fields.as_mut() {
+            Some(builder) => Arc::new(builder.finish()?) as ArrayRef,
+            None => Arc::new(self.attributes.finish()) as ArrayRef,
+        };
+        let mut columns = vec![
+            ("seqid", self.reference_sequence_name.finish()),
+            ("source", self.source.finish()),
+            ("type", self.ty.finish()),
             ("start", Arc::new(self.start.finish()) as ArrayRef),
             ("end", Arc::new(self.end.finish()) as ArrayRef),
             ("score", Arc::new(self.score.finish()) as ArrayRef),
             ("strand", Arc::new(self.strand.finish()) as ArrayRef),
             ("phase", Arc::new(self.phase.finish()) as ArrayRef),
-            ("attributes", Arc::new(self.attributes.finish()) as ArrayRef),
-        ])
+            ("attributes", attributes),
+        ];
+        if let Some(builder) = self.parent_ids.as_mut() {
+            columns.push(("parent_id", builder.finish()));
+        }
+        select_fields(RecordBatch::try_from_iter(columns)?, self.fields.as_deref())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::batch_builder::nullable_field;
+    use crate::gxf::AttributeType;
+    use arrow::array::{Array, ListArray, StringArray, StructArray};
+    use arrow::datatypes::DataType;
     use arrow::ipc::reader::FileReader;
     use arrow::record_batch::RecordBatch;
 
-    fn read_record_batch() -> RecordBatch {
+    fn fixture_path() -> String {
         let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         dir.push("../fixtures/example.gff");
-        let mut reader = GffReader::new_from_path(dir.to_str().unwrap()).unwrap();
-        let ipc = reader.records_to_ipc().unwrap();
+        dir.to_str().unwrap().to_string()
+    }
+
+    fn read_record_batch(attribute_defs: Option<&[AttributeDef]>) -> RecordBatch {
+        let mut reader = GffReader::new_from_path(&fixture_path()).unwrap();
+        let ipc = reader.records_to_ipc(attribute_defs).unwrap();
         let cursor = std::io::Cursor::new(ipc);
         let mut arrow_reader = FileReader::try_new(cursor, None).unwrap();
         // make sure we have one batch
@@ -138,7 +577,524 @@ mod tests {
 
     #[test]
     fn test_read_all() {
-        let record_batch = read_record_batch();
+        let record_batch = read_record_batch(None);
         assert_eq!(record_batch.num_rows(), 6);
     }
+
+    #[test]
+    fn test_count() {
+        let mut reader = GffReader::new_from_path(&fixture_path()).unwrap();
+        assert_eq!(reader.count().unwrap(), 6);
+    }
+
+    #[test]
+    fn test_options_match_setters() {
+        let options = GffOptions {
+            parent_ids: Some(true),
+            dictionary_encode: Some(vec!["type".to_string()]),
+            coordinate_system: Some(CoordinateSystem::ZeroBasedHalfOpen),
+            missing_as_empty: Some(true),
+            percent_decode: Some(false),
+            feature_types: Some(vec!["EXON".to_string()]),
+            ignore_feature_type_case: Some(true),
+            on_error: Some(ErrorPolicy::Skip),
+            ..Default::default()
+        };
+        let defs = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options.clone())
+            .scan_attribute_defs()
+            .unwrap();
+        let with_options = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options)
+            .records_to_ipc(Some(&defs))
+            .unwrap();
+        let with_setters = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_parent_ids(true)
+            .with_dictionary_encode(Some(vec!["type".to_string()]))
+            .with_coordinate_system(CoordinateSystem::ZeroBasedHalfOpen)
+            .with_missing_as_empty(true)
+            .with_percent_decoding(false)
+            .with_feature_types(Some(vec!["EXON".to_string()]))
+            .with_ignore_feature_type_case(true)
+            .with_error_policy(ErrorPolicy::Skip)
+            .records_to_ipc(Some(&defs))
+            .unwrap();
+        let read = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let batch = read(with_options);
+        assert!(batch.num_rows() > 0);
+        assert_eq!(batch, read(with_setters));
+    }
+
+    #[test]
+    fn test_fields() {
+        let options = GffOptions {
+            fields: Some(vec!["type".into(), "seqid".into(), "start".into()]),
+            ..Default::default()
+        };
+        let mut reader = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        let fields = reader.fields(None).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let schema = batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["type", "seqid", "start"]);
+        let field_names: Vec<_> = fields.iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(field_names, names);
+
+        let mut reader = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_fields(Some(vec!["seqid".into(), "parent_id".into()]));
+        let error = reader.records_to_ipc(None).unwrap_err();
+        assert!(error.to_string().contains("Invalid field: parent_id"));
+        assert!(error.to_string().contains("seqid, source, type, start"));
+    }
+
+    #[test]
+    fn test_chroms() {
+        let data = "##gff-version 3\n\
+                    sq2\t.\tgene\t1\t10\t.\t+\t.\tID=a\n\
+                    sq0\t.\tgene\t1\t10\t.\t+\t.\tID=b\n\
+                    sq2\t.\tgene\t5\t10\t.\t+\t.\tID=c\n\
+                    ##FASTA\n>sq9\nACGT\n";
+        let mut reader = GffReader::new(data.as_bytes()).unwrap();
+        assert_eq!(reader.chroms().unwrap(), ["sq0", "sq2"]);
+        let mut reader = GffReader::new_from_path(&fixture_path()).unwrap();
+        assert_eq!(reader.chroms().unwrap(), ["ctg123"]);
+    }
+
+    #[test]
+    fn test_scan_attribute_defs() {
+        let mut reader = GffReader::new_from_path(&fixture_path()).unwrap();
+        let defs = reader.scan_attribute_defs().unwrap();
+        let names: Vec<_> = defs.iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, vec!["ID", "Name", "Parent"]);
+    }
+
+    #[test]
+    fn test_read_attributes() {
+        let defs = vec![
+            AttributeDef::new("ID", AttributeType::String),
+            AttributeDef::new("Parent", AttributeType::Array),
+        ];
+        let record_batch = read_record_batch(Some(&defs));
+        let attributes = record_batch
+            .column_by_name("attributes")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .unwrap();
+        assert_eq!(attributes.num_columns(), 2);
+        let parent = attributes.column_by_name("Parent").unwrap();
+        assert_eq!(parent.data_type(), &defs[1].arrow_type());
+        assert_eq!(parent.null_count(), 1);
+    }
+
+    #[test]
+    fn test_parent_ids() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/transcripts.gff");
+        let mut reader = GffReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_parent_ids(true);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let gff_parent_ids = record_batch.column_by_name("parent_id").unwrap();
+
+        // An equivalent GTF file gives the same hierarchy.
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/transcripts.gtf");
+        let mut reader = crate::gtf::GtfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_parent_ids(true);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let gtf_parent_ids = record_batch.column_by_name("parent_id").unwrap();
+        assert_eq!(gff_parent_ids, gtf_parent_ids);
+
+        let parent_ids = gff_parent_ids.as_any().downcast_ref::<ListArray>().unwrap();
+        assert!(parent_ids.is_null(0));
+        let parent_id = |i: usize| {
+            let values = parent_ids.value(i);
+            let values = values.as_any().downcast_ref::<StringArray>().unwrap();
+            values
+                .iter()
+                .map(Option::unwrap)
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(parent_id(1), vec!["g1"]);
+        assert_eq!(parent_id(3), vec!["t1"]);
+    }
+
+    #[test]
+    fn test_multiple_parent_ids() {
+        let data = "##gff-version 3\nchr1\ttest\texon\t100\t200\t.\t+\t.\tID=e1;Parent=t1,t2\n";
+        let mut reader = GffReader::new(data.as_bytes())
+            .unwrap()
+            .with_parent_ids(true);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let parent_ids = record_batch
+            .column_by_name("parent_id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .unwrap()
+            .value(0);
+        assert_eq!(parent_ids.len(), 2);
+    }
+
+    #[test]
+    fn test_directives_and_fasta() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/directives.gff");
+        let path = dir.to_str().unwrap();
+
+        let mut reader = GffReader::new_from_path(path).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(record_batch.num_rows(), 3);
+        assert_eq!(
+            record_batch.schema().metadata()["gff.sequence_regions"],
+            "ctg1:1-40,ctg2:1-20"
+        );
+        assert_eq!(reader.sequence_regions().len(), 2);
+        // Reading again doesn't parse the sequences as records.
+        assert_eq!(reader.count().unwrap(), 0);
+
+        let mut fasta = reader.embedded_fasta().unwrap().unwrap();
+        let ipc = fasta.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let names = record_batch.column_by_name("name").unwrap();
+        let names = names.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            names.iter().collect::<Vec<_>>(),
+            [Some("ctg1"), Some("ctg2")]
+        );
+        let sequences = record_batch.column_by_name("sequence").unwrap();
+        let sequences = sequences.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(sequences.value(0).len(), 40);
+
+        // Unread records are skipped.
+        let reader = GffReader::new_from_path(path).unwrap();
+        assert_eq!(
+            reader.embedded_fasta().unwrap().unwrap().count().unwrap(),
+            2
+        );
+        let reader = GffReader::new_from_path(&fixture_path()).unwrap();
+        assert!(reader.embedded_fasta().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_missing_as_empty() {
+        let data = "##gff-version 3\n\
+            chr1\ttest\tgene\t100\t200\t.\t+\t.\tID=g1\n\
+            chr1\ttest\tCDS\t100\t200\t5\t+\t0\tID=c1;Parent=g1\n";
+        let defs = vec![
+            AttributeDef::new("ID", AttributeType::String),
+            AttributeDef::new("Parent", AttributeType::Array),
+        ];
+        let read = |missing_as_empty| {
+            let mut reader = GffReader::new(data.as_bytes())
+                .unwrap()
+                .with_missing_as_empty(missing_as_empty);
+            let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+
+        let record_batch = read(false);
+        for name in ["score", "phase"] {
+            assert_eq!(record_batch.column_by_name(name).unwrap().null_count(), 1);
+        }
+        let attributes = record_batch.column_by_name("attributes").unwrap();
+        let attributes = attributes.as_any().downcast_ref::<StructArray>().unwrap();
+        assert!(attributes.column_by_name("Parent").unwrap().is_null(0));
+
+        let record_batch = read(true);
+        let score = record_batch.column_by_name("score").unwrap();
+        let score = score
+            .as_any()
+            .downcast_ref::<arrow::array::Float32Array>()
+            .unwrap();
+        assert_eq!(score.null_count(), 0);
+        assert!(score.value(0).is_nan());
+        assert_eq!(score.value(1), 5.0);
+        let phase = record_batch.column_by_name("phase").unwrap();
+        let phase = phase
+            .as_any()
+            .downcast_ref::<arrow::array::Int8Array>()
+            .unwrap();
+        assert_eq!(phase.iter().collect::<Vec<_>>(), [Some(-1), Some(0)]);
+        let attributes = record_batch.column_by_name("attributes").unwrap();
+        let attributes = attributes.as_any().downcast_ref::<StructArray>().unwrap();
+        let parent = attributes.column_by_name("Parent").unwrap();
+        let parent = parent.as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(parent.null_count(), 0);
+        assert_eq!(parent.value(0).len(), 0);
+    }
+
+    #[test]
+    fn test_attribute_type_inference() {
+        let data = "##gff-version 3\n\
+            chr1\ttest\tgene\t100\t200\t.\t+\t.\tID=g1;gene_length=101;gc=0.4;code=1\n\
+            chr1\ttest\tgene\t300\t400\t.\t+\t.\tID=g2;gene_length=101;gc=1;code=x\n";
+        let defs = GffReader::new(data.as_bytes())
+            .unwrap()
+            .with_attribute_type_inference(true)
+            .scan_attribute_defs()
+            .unwrap();
+        assert_eq!(
+            defs,
+            vec![
+                AttributeDef::new("ID", AttributeType::String),
+                AttributeDef::new("gene_length", AttributeType::Int64),
+                AttributeDef::new("gc", AttributeType::Float64),
+                AttributeDef::new("code", AttributeType::String),
+            ]
+        );
+
+        let mut reader = GffReader::new(data.as_bytes()).unwrap();
+        let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let attributes = record_batch.column_by_name("attributes").unwrap();
+        let attributes = attributes.as_any().downcast_ref::<StructArray>().unwrap();
+        let gene_length = attributes.column_by_name("gene_length").unwrap();
+        let gene_length = gene_length
+            .as_any()
+            .downcast_ref::<arrow::array::Int64Array>()
+            .unwrap();
+        assert_eq!(gene_length.values().to_vec(), vec![101, 101]);
+
+        let defs = GffReader::new(data.as_bytes())
+            .unwrap()
+            .scan_attribute_defs()
+            .unwrap();
+        assert!(defs.iter().all(|def| def.ty == AttributeType::String));
+    }
+
+    #[test]
+    fn test_attribute_type_overrides() {
+        let data = "##gff-version 3\n\
+            chr1\ttest\tgene\t100\t200\t.\t+\t.\tID=001;gene_length=101\n\
+            chr1\ttest\tgene\t300\t400\t.\t+\t.\tID=002;gene_length=101\n";
+        let defs = GffReader::new(data.as_bytes())
+            .unwrap()
+            .with_attribute_type_inference(true)
+            .with_attribute_type_overrides(vec![AttributeDef::new("ID", AttributeType::String)])
+            .scan_attribute_defs()
+            .unwrap();
+        assert_eq!(
+            defs,
+            vec![
+                AttributeDef::new("ID", AttributeType::String),
+                AttributeDef::new("gene_length", AttributeType::Int64),
+            ]
+        );
+
+        // Leading zeros survive in a string column.
+        let ipc = GffReader::new(data.as_bytes())
+            .unwrap()
+            .records_to_ipc(Some(&defs))
+            .unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let attributes = record_batch.column_by_name("attributes").unwrap();
+        let attributes = attributes.as_any().downcast_ref::<StructArray>().unwrap();
+        let id = attributes.column_by_name("ID").unwrap();
+        let id = id.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(id.value(0), "001");
+    }
+
+    #[test]
+    fn test_dictionary_encode() {
+        let fields = vec!["seqid".to_string(), "type".to_string()];
+        let mut reader = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_dictionary_encode(Some(fields));
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let dictionary = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let plain = read_record_batch(None);
+        for name in ["seqid", "type"] {
+            let column = record_batch.column_by_name(name).unwrap();
+            assert_eq!(column.data_type(), &dictionary);
+            let column = arrow::compute::cast(column, &DataType::Utf8).unwrap();
+            assert_eq!(&column, plain.column_by_name(name).unwrap());
+        }
+        let source = record_batch.column_by_name("source").unwrap();
+        assert_eq!(source.data_type(), &DataType::Utf8);
+
+        let mut reader = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_dictionary_encode(Some(vec!["attributes".to_string()]));
+        assert!(reader.records_to_ipc(None).is_err());
+    }
+
+    /// Returns the names and types of a list of fields, with nested fields made nullable.
+    fn name_types(fields: &arrow::datatypes::Fields) -> Vec<(String, DataType)> {
+        fields
+            .iter()
+            .map(|field| nullable_field(field))
+            .map(|field| (field.name().clone(), field.data_type().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_fields_match_records() {
+        let defs = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .scan_attribute_defs()
+            .unwrap();
+        let reader = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_parent_ids(true);
+        let fields = reader.fields(Some(&defs)).unwrap();
+        let mut reader = GffReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_parent_ids(true);
+        let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(
+            name_types(&fields),
+            name_types(record_batch.schema().fields())
+        );
+    }
+
+    #[test]
+    fn test_error_policy() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/malformed.gff");
+        let reader = |on_error| {
+            GffReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_error_policy(on_error)
+        };
+        assert!(reader(ErrorPolicy::Fail).records_to_ipc(None).is_err());
+        assert!(reader(ErrorPolicy::Fail).scan_attribute_defs().is_err());
+
+        let defs = reader(ErrorPolicy::Skip).scan_attribute_defs().unwrap();
+        let names: Vec<_> = defs.iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, ["ID", "Parent"]);
+
+        let mut reader = reader(ErrorPolicy::SkipAndCollect);
+        let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(record_batch.num_rows(), 2);
+        let errors = reader.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+    }
+
+    #[test]
+    fn test_malformed_attribute_array() {
+        // `%FF` percent-decodes to invalid UTF-8 in the second value of `Parent`.
+        let data = "##gff-version 3\n\
+            chr1\ttest\tgene\t100\t200\t.\t+\t.\tID=g1\n\
+            chr1\ttest\tmRNA\t100\t200\t.\t+\t.\tID=t1;Parent=g1,%FF\n\
+            chr1\ttest\texon\t100\t150\t.\t+\t.\tID=e1;Parent=g1\n";
+        let reader = |on_error| {
+            GffReader::new(data.as_bytes())
+                .unwrap()
+                .with_parent_ids(true)
+                .with_error_policy(on_error)
+        };
+        assert!(reader(ErrorPolicy::Fail).scan_attribute_defs().is_err());
+        assert!(reader(ErrorPolicy::Fail).records_to_ipc(None).is_err());
+
+        let defs = reader(ErrorPolicy::Skip).scan_attribute_defs().unwrap();
+        let mut reader = reader(ErrorPolicy::SkipAndCollect);
+        let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        assert_eq!(arrow_reader.next().unwrap().unwrap().num_rows(), 2);
+        assert_eq!(reader.errors()[0].line, 3);
+    }
+
+    #[test]
+    fn test_percent_decoding() {
+        let data = "##gff-version 3\n\
+            chr1\ttest\tgene\t100\t200\t.\t+\t.\tID=g1;Note=a%3Bb%2Cc;Description=x%2Cy,z\n";
+        let defs = vec![
+            AttributeDef::new("Note", AttributeType::String),
+            AttributeDef::new("Description", AttributeType::Array),
+        ];
+        let read = |percent_decode| {
+            let mut reader = GffReader::new(data.as_bytes())
+                .unwrap()
+                .with_percent_decoding(percent_decode);
+            let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let record_batch = arrow_reader.next().unwrap().unwrap();
+            let attributes = record_batch.column_by_name("attributes").unwrap();
+            let attributes = attributes.as_any().downcast_ref::<StructArray>().unwrap();
+            let note = attributes.column_by_name("Note").unwrap();
+            let note = note
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .value(0);
+            let description = attributes.column_by_name("Description").unwrap();
+            let description = description.as_any().downcast_ref::<ListArray>().unwrap();
+            let description = description.value(0);
+            let description = description.as_any().downcast_ref::<StringArray>().unwrap();
+            let description: Vec<_> = description.iter().map(Option::unwrap).collect();
+            (note.to_string(), description.join("|"))
+        };
+
+        assert_eq!(read(true), ("a;b,c".to_string(), "x,y|z".to_string()));
+        assert_eq!(
+            read(false),
+            ("a%3Bb%2Cc".to_string(), "x%2Cy|z".to_string())
+        );
+    }
+
+    #[test]
+    fn test_feature_types() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/transcripts.gff");
+        let reader = |types: &[&str]| {
+            GffReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_feature_types(Some(types.iter().map(|t| t.to_string()).collect()))
+        };
+        let mut cds = reader(&["CDS"]);
+        let ipc = cds.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(record_batch.num_rows(), 1);
+        let ty = record_batch
+            .column_by_name("type")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(ty.value(0), "CDS");
+
+        assert_eq!(reader(&["Cds"]).count().unwrap(), 0);
+        let mut reader_ignoring_case = reader(&["Cds"]).with_ignore_feature_type_case(true);
+        assert_eq!(reader_ignoring_case.count().unwrap(), 1);
+
+        // Skipped features don't contribute attributes.
+        let defs = reader(&["gene"]).scan_attribute_defs().unwrap();
+        let names: Vec<_> = defs.iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, ["ID"]);
+    }
 }
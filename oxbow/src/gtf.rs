@@ -1,22 +1,91 @@
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use arrow::array::{ArrayRef, Float32Builder, GenericStringBuilder, Int32Builder};
+use arrow::array::{
+    ArrayRef, Float32Builder, GenericStringBuilder, Int64Builder, Int8Builder,
+    StringDictionaryBuilder,
+};
+use arrow::datatypes::{Fields, Int32Type};
 use arrow::error::ArrowError;
 use arrow::record_batch::RecordBatch;
 use noodles::gtf;
 
-use crate::batch_builder::{write_ipc_err, BatchBuilder};
+use crate::batch_builder::{
+    builder_fields, check_dictionary_encode, count_err, provenance, select_fields, write_ipc_err,
+    BatchBuilder, StringColumnBuilder,
+};
+use crate::coordinates::CoordinateSystem;
+use crate::error_policy::{tolerate, ErrorPolicy, RecordError};
+use crate::gxf::{
+    feature_type_matches, gtf_attributes, gtf_parent_ids, strand_builder, AttributeDef,
+    AttributeScanner, AttributesBuilder, DuplicateAttributes, ParentIdsBuilder,
+};
+
+/// Options for converting GTF records to Arrow.
+///
+/// Each option can also be set with the matching `with_*` method of [`GtfReader`].
+#[derive(Clone, Debug, Default)]
+pub struct GtfOptions {
+    /// Add a `parent_id` column (`List<Utf8>`) with the IDs of each feature's parents.
+    ///
+    /// The hierarchy is harmonized across GFF3 and GTF, so that features can be grouped the same
+    /// way for both. See [`gtf_parent_ids`](crate::gxf::gtf_parent_ids).
+    pub parent_ids: Option<bool>,
+    /// String columns to dictionary-encode, out of `seqid`, `source` and `type`.
+    ///
+    /// `strand` is always dictionary-encoded.
+    pub dictionary_encode: Option<Vec<String>>,
+    /// The coordinate system of the `start` column. Defaults to the native 1-based, closed
+    /// coordinates. See [`crate::coordinates`].
+    pub coordinate_system: Option<CoordinateSystem>,
+    /// Emit a missing `score` as NaN, a missing `phase` as -1 and missing attribute fields as
+    /// empty strings or lists, instead of nulls, for tools that don't handle nulls. Defaults to
+    /// nulls.
+    pub missing_as_empty: Option<bool>,
+    /// Infer `Int64` and `Float64` attribute types in [`GtfReader::scan_attribute_defs`], from
+    /// the values of each attribute. Defaults to off, which is faster and reports all
+    /// single-valued attributes as strings.
+    pub infer_attribute_types: Option<bool>,
+    /// Force the types of the given attributes in [`GtfReader::scan_attribute_defs`],
+    /// overriding inference. Other attributes are still scanned as usual.
+    pub attribute_type_overrides: Option<Vec<AttributeDef>>,
+    /// What to make of attributes repeated in a record, e.g. `tag "basic"; tag "CCDS";`, both in
+    /// [`GtfReader::scan_attribute_defs`] and in the attribute columns. Defaults to
+    /// [`DuplicateAttributes::First`].
+    pub duplicate_attributes: Option<DuplicateAttributes>,
+    /// Select the features whose `type` is one of the given types, matched exactly. Other
+    /// features are skipped before their attributes are read, so they aren't counted nor seen by
+    /// [`GtfReader::scan_attribute_defs`]. Defaults to all features.
+    pub feature_types: Option<Vec<String>>,
+    /// Match [`GtfOptions::feature_types`] ignoring ASCII case, e.g. so that `cds` selects `CDS`
+    /// features. Defaults to case-sensitive.
+    pub ignore_feature_type_case: Option<bool>,
+    /// What to do with records that fail to parse. Defaults to [`ErrorPolicy::Fail`].
+    pub on_error: Option<ErrorPolicy>,
+    /// The columns to return, in this order, e.g. `["type", "seqid", "start"]`. Defaults to all
+    /// of them, in their default order.
+    pub fields: Option<Vec<String>>,
+}
 
 pub struct GtfReader<R> {
     reader: gtf::Reader<R>,
+    options: GtfOptions,
+    errors: Vec<RecordError>,
+    path: Option<PathBuf>,
 }
 
 impl GtfReader<BufReader<File>> {
     pub fn new_from_path(path: &str) -> std::io::Result<Self> {
         let reader = File::open(path).map(BufReader::new).map(gtf::Reader::new)?;
-        Ok(Self { reader })
+        Ok(Self {
+            reader,
+            options: GtfOptions::default(),
+            errors: Vec::new(),
+            path: Some(PathBuf::from(path)),
+        })
     }
 }
 
@@ -26,53 +95,282 @@ where
 {
     pub fn new(read: R) -> std::io::Result<Self> {
         let reader = gtf::Reader::new(read);
-        Ok(Self { reader })
+        Ok(Self {
+            reader,
+            options: GtfOptions::default(),
+            errors: Vec::new(),
+            path: None,
+        })
+    }
+
+    /// Sets the options of the conversion to Arrow, replacing any set before.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gtf::{GtfOptions, GtfReader};
+    ///
+    /// let options = GtfOptions {
+    ///     parent_ids: Some(true),
+    ///     feature_types: Some(vec!["exon".to_string()]),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = GtfReader::new_from_path("sample.gtf")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// ```
+    pub fn with_options(mut self, options: GtfOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets [`GtfOptions::parent_ids`].
+    pub fn with_parent_ids(mut self, parent_ids: bool) -> Self {
+        self.options.parent_ids = Some(parent_ids);
+        self
+    }
+
+    /// Sets [`GtfOptions::dictionary_encode`].
+    pub fn with_dictionary_encode(mut self, dictionary_encode: Option<Vec<String>>) -> Self {
+        self.options.dictionary_encode = dictionary_encode;
+        self
+    }
+
+    /// Sets [`GtfOptions::coordinate_system`].
+    pub fn with_coordinate_system(mut self, coordinate_system: CoordinateSystem) -> Self {
+        self.options.coordinate_system = Some(coordinate_system);
+        self
+    }
+
+    /// Sets [`GtfOptions::missing_as_empty`].
+    pub fn with_missing_as_empty(mut self, missing_as_empty: bool) -> Self {
+        self.options.missing_as_empty = Some(missing_as_empty);
+        self
+    }
+
+    /// Sets [`GtfOptions::infer_attribute_types`].
+    pub fn with_attribute_type_inference(mut self, infer_attribute_types: bool) -> Self {
+        self.options.infer_attribute_types = Some(infer_attribute_types);
+        self
+    }
+
+    /// Sets [`GtfOptions::attribute_type_overrides`].
+    pub fn with_attribute_type_overrides(mut self, overrides: Vec<AttributeDef>) -> Self {
+        self.options.attribute_type_overrides = Some(overrides);
+        self
+    }
+
+    /// Sets [`GtfOptions::duplicate_attributes`].
+    pub fn with_duplicate_attributes(mut self, duplicates: DuplicateAttributes) -> Self {
+        self.options.duplicate_attributes = Some(duplicates);
+        self
+    }
+
+    /// Sets [`GtfOptions::feature_types`].
+    pub fn with_feature_types(mut self, feature_types: Option<Vec<String>>) -> Self {
+        self.options.feature_types = feature_types;
+        self
+    }
+
+    /// Sets [`GtfOptions::ignore_feature_type_case`].
+    pub fn with_ignore_feature_type_case(mut self, ignore_case: bool) -> Self {
+        self.options.ignore_feature_type_case = Some(ignore_case);
+        self
+    }
+
+    /// Sets [`GtfOptions::on_error`].
+    pub fn with_error_policy(mut self, on_error: ErrorPolicy) -> Self {
+        self.options.on_error = Some(on_error);
+        self
+    }
+
+    /// Sets [`GtfOptions::fields`].
+    pub fn with_fields(mut self, fields: Option<Vec<String>>) -> Self {
+        self.options.fields = fields;
+        self
+    }
+
+    /// Returns the records skipped by the last scan under [`ErrorPolicy::SkipAndCollect`].
+    pub fn errors(&self) -> &[RecordError] {
+        &self.errors
+    }
+
+    /// Returns the records not read yet, of the selected feature types.
+    fn records(&mut self) -> impl Iterator<Item = io::Result<gtf::Record>> + '_ {
+        let records = self
+            .reader
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| match line {
+                Ok(gtf::Line::Record(record)) => Some((i + 1, Ok(record))),
+                Ok(_) => None,
+                Err(e) => Some((i + 1, Err(e))),
+            });
+        let (feature_types, ignore_case) = (
+            self.options.feature_types.as_deref(),
+            self.options.ignore_feature_type_case.unwrap_or(false),
+        );
+        let on_error = self.options.on_error.unwrap_or_default();
+        tolerate(records, on_error, &mut self.errors).filter(move |result| {
+            result.as_ref().map_or(true, |record| {
+                feature_type_matches(feature_types, ignore_case, record.ty())
+            })
+        })
+    }
+
+    /// Returns the fields of the batches returned by [`GtfReader::records_to_ipc`] with the given
+    /// attribute definitions and the current options, without reading any records.
+    pub fn fields(&self, attribute_defs: Option<&[AttributeDef]>) -> Result<Fields, ArrowError> {
+        builder_fields(GtfBatchBuilder::new(0, attribute_defs, &self.options)?)
     }
 
     /// Returns the records in the given region as Apache Arrow IPC.
     ///
+    /// If `attribute_defs` is given, the `attributes` column is a struct with one typed field per
+    /// definition. Otherwise, it holds the raw attributes string.
+    ///
     /// # Examples
     ///
     /// ```no_run
     /// use oxbow::gtf::GtfReader;
     ///
     /// let mut reader = GtfReader::new_from_path("sample.gtf").unwrap();
-    /// let ipc = reader.records_to_ipc().unwrap();
+    /// let ipc = reader.records_to_ipc(None).unwrap();
     /// ```
-    pub fn records_to_ipc(&mut self) -> Result<Vec<u8>, ArrowError> {
-        let batch_builder = GtfBatchBuilder::new(1024)?;
+    pub fn records_to_ipc(
+        &mut self,
+        attribute_defs: Option<&[AttributeDef]>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = GtfBatchBuilder::new(1024, attribute_defs, &self.options)?
+            .with_metadata(provenance("gtf", self.path.as_deref(), &[]));
         let records = self
-            .reader
             .records()
             .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
         write_ipc_err(records, batch_builder)
     }
+
+    /// Returns the number of records, without converting them to Arrow.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gtf::GtfReader;
+    ///
+    /// let mut reader = GtfReader::new_from_path("sample.gtf").unwrap();
+    /// let n = reader.count().unwrap();
+    /// ```
+    pub fn count(&mut self) -> Result<usize, ArrowError> {
+        let records = self
+            .records()
+            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+        count_err(records)
+    }
+
+    /// Returns the distinct reference sequences (`seqid`) of the records not read yet, sorted,
+    /// without converting them to Arrow.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gtf::GtfReader;
+    ///
+    /// let mut reader = GtfReader::new_from_path("sample.gtf").unwrap();
+    /// let chroms = reader.chroms().unwrap();
+    /// ```
+    pub fn chroms(&mut self) -> io::Result<Vec<String>> {
+        let mut chroms = BTreeSet::new();
+        for result in self.records() {
+            let record = result?;
+            if !chroms.contains(record.reference_sequence_name()) {
+                chroms.insert(record.reference_sequence_name().to_string());
+            }
+        }
+        Ok(chroms.into_iter().collect())
+    }
+
+    /// Reads all records and returns the attributes found, in order of first appearance.
+    ///
+    /// This consumes the reader, so a new reader is needed to read the records afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::gtf::GtfReader;
+    ///
+    /// let defs = GtfReader::new_from_path("sample.gtf")
+    ///     .unwrap()
+    ///     .scan_attribute_defs()
+    ///     .unwrap();
+    /// let mut reader = GtfReader::new_from_path("sample.gtf").unwrap();
+    /// let ipc = reader.records_to_ipc(Some(&defs)).unwrap();
+    /// ```
+    pub fn scan_attribute_defs(&mut self) -> std::io::Result<Vec<AttributeDef>> {
+        let mut scanner = AttributeScanner::new()
+            .with_type_inference(self.options.infer_attribute_types.unwrap_or(false))
+            .with_overrides(
+                self.options
+                    .attribute_type_overrides
+                    .clone()
+                    .unwrap_or_default(),
+            )
+            .with_duplicates(self.options.duplicate_attributes.unwrap_or_default());
+        for result in self.records() {
+            let record = result?;
+            scanner.push_gtf(&record);
+        }
+        Ok(scanner.collect())
+    }
 }
 
 struct GtfBatchBuilder {
-    reference_sequence_name: GenericStringBuilder<i32>,
-    source: GenericStringBuilder<i32>,
-    ty: GenericStringBuilder<i32>,
-    start: Int32Builder,
-    end: Int32Builder,
+    coordinate_system: CoordinateSystem,
+    reference_sequence_name: StringColumnBuilder,
+    source: StringColumnBuilder,
+    ty: StringColumnBuilder,
+    start: Int64Builder,
+    end: Int64Builder,
     score: Float32Builder,
-    strand: GenericStringBuilder<i32>,
-    frame: GenericStringBuilder<i32>,
+    strand: StringDictionaryBuilder<Int32Type>,
+    phase: Int8Builder,
     attributes: GenericStringBuilder<i32>,
+    attribute_fields: Option<AttributesBuilder>,
+    parent_ids: Option<ParentIdsBuilder>,
+    missing_as_empty: bool,
+    fields: Option<Vec<String>>,
 }
 
 impl GtfBatchBuilder {
-    pub fn new(capacity: usize) -> Result<Self, ArrowError> {
+    pub fn new(
+        capacity: usize,
+        attribute_defs: Option<&[AttributeDef]>,
+        options: &GtfOptions,
+    ) -> Result<Self, ArrowError> {
+        let dictionary_encode = options.dictionary_encode.as_deref();
+        let missing_as_empty = options.missing_as_empty.unwrap_or(false);
+        let duplicates = options.duplicate_attributes.unwrap_or_default();
+        check_dictionary_encode(dictionary_encode, &["seqid", "source", "type"])?;
         Ok(Self {
-            reference_sequence_name: GenericStringBuilder::<i32>::new(),
-            source: GenericStringBuilder::<i32>::new(),
-            ty: GenericStringBuilder::<i32>::new(),
-            start: Int32Builder::with_capacity(capacity),
-            end: Int32Builder::with_capacity(capacity),
+            coordinate_system: options
+                .coordinate_system
+                .unwrap_or(CoordinateSystem::OneBased),
+            reference_sequence_name: StringColumnBuilder::new("seqid", dictionary_encode),
+            source: StringColumnBuilder::new("source", dictionary_encode),
+            ty: StringColumnBuilder::new("type", dictionary_encode),
+            start: Int64Builder::with_capacity(capacity),
+            end: Int64Builder::with_capacity(capacity),
             score: Float32Builder::new(),
-            strand: GenericStringBuilder::<i32>::new(),
-            frame: GenericStringBuilder::<i32>::new(),
+            strand: strand_builder(capacity)?,
+            phase: Int8Builder::with_capacity(capacity),
             attributes: GenericStringBuilder::<i32>::new(),
+            attribute_fields: attribute_defs.map(|defs| {
+                AttributesBuilder::new(defs, missing_as_empty).with_duplicates(duplicates)
+            }),
+            parent_ids: options
+                .parent_ids
+                .unwrap_or(false)
+                .then(ParentIdsBuilder::new),
+            missing_as_empty,
+            fields: options.fields.clone(),
         })
     }
 }
@@ -85,53 +383,80 @@ impl BatchBuilder for GtfBatchBuilder {
             .append_value(record.reference_sequence_name());
         self.source.append_value(record.source());
         self.ty.append_value(record.ty());
-        self.start.append_value(usize::from(record.start()) as i32);
-        self.end.append_value(usize::from(record.end()) as i32);
+        let start = usize::from(record.start()) as i64;
+        self.start.append_value(
+            self.coordinate_system
+                .start_from(CoordinateSystem::OneBased, start),
+        );
+        self.end.append_value(usize::from(record.end()) as i64);
         match record.score() {
             Some(score) => self.score.append_value(score),
+            None if self.missing_as_empty => self.score.append_value(f32::NAN),
             None => self.score.append_null(),
         }
+        // GTF writes features without a strand as `.`, as in GFF.
         match record.strand() {
             Some(strand) => self.strand.append_value(strand),
-            None => self.strand.append_null(),
+            None => self.strand.append_value("."),
+        }
+        self.phase.append_option(
+            record
+                .frame()
+                .map(|frame| u8::from(frame) as i8)
+                .or(self.missing_as_empty.then_some(-1)),
+        );
+        match self.attribute_fields.as_mut() {
+            Some(builder) => builder.push(gtf_attributes(record)),
+            None => self
+                .attributes
+                .append_value(record.attributes().to_string()),
         }
-        match record.frame() {
-            Some(frame) => self.frame.append_value(frame.to_string()),
-            None => self.frame.append_null(),
+        if let Some(builder) = self.parent_ids.as_mut() {
+            builder.push(&gtf_parent_ids(record));
         }
-        self.attributes
-            .append_value(record.attributes().to_string());
     }
 
     fn finish(mut self) -> Result<RecordBatch, ArrowError> {
-        RecordBatch::try_from_iter(vec![
-            (
-                "seqid",
-                Arc::new(self.reference_sequence_name.finish()) as ArrayRef,
-            ),
-            ("source", Arc::new(self.source.finish()) as ArrayRef),
-            ("type", Arc::new(self.ty.finish()) as ArrayRef),
+        let attributes = match self.attribute_fields.as_mut() {
+            Some(builder) => Arc::new(builder.finish()?) as ArrayRef,
+            None => Arc::new(self.attributes.finish()) as ArrayRef,
+        };
+        let mut columns = vec![
+            ("seqid", self.reference_sequence_name.finish()),
+            ("source", self.source.finish()),
+            ("type", self.ty.finish()),
             ("start", Arc::new(self.start.finish()) as ArrayRef),
             ("end", Arc::new(self.end.finish()) as ArrayRef),
             ("score", Arc::new(self.score.finish()) as ArrayRef),
             ("strand", Arc::new(self.strand.finish()) as ArrayRef),
-            ("frame", Arc::new(self.frame.finish()) as ArrayRef),
-            ("attributes", Arc::new(self.attributes.finish()) as ArrayRef),
-        ])
+            ("phase", Arc::new(self.phase.finish()) as ArrayRef),
+            ("attributes", attributes),
+        ];
+        if let Some(builder) = self.parent_ids.as_mut() {
+            columns.push(("parent_id", builder.finish()));
+        }
+        select_fields(RecordBatch::try_from_iter(columns)?, self.fields.as_deref())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gxf::AttributeType;
+    use arrow::array::{Array, Float32Array, Int8Array, ListArray, StringArray, StructArray};
+    use arrow::datatypes::DataType;
     use arrow::ipc::reader::FileReader;
     use arrow::record_batch::RecordBatch;
 
-    fn read_record_batch() -> RecordBatch {
+    fn fixture_path() -> String {
         let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         dir.push("../fixtures/example.gtf");
-        let mut reader = GtfReader::new_from_path(dir.to_str().unwrap()).unwrap();
-        let ipc = reader.records_to_ipc().unwrap();
+        dir.to_str().unwrap().to_string()
+    }
+
+    fn read_record_batch(attribute_defs: Option<&[AttributeDef]>) -> RecordBatch {
+        let mut reader = GtfReader::new_from_path(&fixture_path()).unwrap();
+        let ipc = reader.records_to_ipc(attribute_defs).unwrap();
         let cursor = std::io::Cursor::new(ipc);
         let mut arrow_reader = FileReader::try_new(cursor, None).unwrap();
         // make sure we have one batch
@@ -141,7 +466,305 @@ mod tests {
 
     #[test]
     fn test_read_all() {
-        let record_batch = read_record_batch();
+        let record_batch = read_record_batch(None);
         assert_eq!(record_batch.num_rows(), 2);
     }
+
+    #[test]
+    fn test_options_match_setters() {
+        let options = GtfOptions {
+            parent_ids: Some(true),
+            dictionary_encode: Some(vec!["type".to_string()]),
+            coordinate_system: Some(CoordinateSystem::ZeroBasedHalfOpen),
+            missing_as_empty: Some(true),
+            duplicate_attributes: Some(DuplicateAttributes::Collect),
+            feature_types: Some(vec!["TRANSCRIPT".to_string()]),
+            ignore_feature_type_case: Some(true),
+            on_error: Some(ErrorPolicy::Skip),
+            ..Default::default()
+        };
+        let defs = GtfReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options.clone())
+            .scan_attribute_defs()
+            .unwrap();
+        let with_options = GtfReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options)
+            .records_to_ipc(Some(&defs))
+            .unwrap();
+        let with_setters = GtfReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_parent_ids(true)
+            .with_dictionary_encode(Some(vec!["type".to_string()]))
+            .with_coordinate_system(CoordinateSystem::ZeroBasedHalfOpen)
+            .with_missing_as_empty(true)
+            .with_duplicate_attributes(DuplicateAttributes::Collect)
+            .with_feature_types(Some(vec!["TRANSCRIPT".to_string()]))
+            .with_ignore_feature_type_case(true)
+            .with_error_policy(ErrorPolicy::Skip)
+            .records_to_ipc(Some(&defs))
+            .unwrap();
+        let read = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let batch = read(with_options);
+        assert!(batch.num_rows() > 0);
+        assert_eq!(batch, read(with_setters));
+    }
+
+    #[test]
+    fn test_fields() {
+        let options = GtfOptions {
+            fields: Some(vec!["type".into(), "seqid".into(), "start".into()]),
+            ..Default::default()
+        };
+        let mut reader = GtfReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        let fields = reader.fields(None).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let schema = batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["type", "seqid", "start"]);
+        let field_names: Vec<_> = fields.iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(field_names, names);
+
+        let mut reader = GtfReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_fields(Some(vec!["seqid".into(), "parent_id".into()]));
+        let error = reader.records_to_ipc(None).unwrap_err();
+        assert!(error.to_string().contains("Invalid field: parent_id"));
+        assert!(error.to_string().contains("seqid, source, type, start"));
+    }
+
+    #[test]
+    fn test_chroms() {
+        let mut reader = GtfReader::new_from_path(&fixture_path()).unwrap();
+        assert_eq!(reader.chroms().unwrap(), ["1"]);
+        // The reader is consumed.
+        assert!(reader.chroms().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_count() {
+        let mut reader = GtfReader::new_from_path(&fixture_path()).unwrap();
+        assert_eq!(reader.count().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_read_scanned_attributes() {
+        let mut reader = GtfReader::new_from_path(&fixture_path()).unwrap();
+        let defs = reader.scan_attribute_defs().unwrap();
+        assert_eq!(defs.len(), 7);
+        let record_batch = read_record_batch(Some(&defs));
+        let attributes = record_batch
+            .column_by_name("attributes")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .unwrap();
+        assert_eq!(attributes.num_columns(), 7);
+        let transcript_id = attributes.column_by_name("transcript_id").unwrap();
+        assert_eq!(transcript_id.null_count(), 1);
+    }
+
+    #[test]
+    fn test_strand_and_phase() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/transcripts.gtf");
+        let mut reader = GtfReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let gtf = arrow_reader.next().unwrap().unwrap();
+
+        // An equivalent GFF file gives the same typed columns.
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/transcripts.gff");
+        let mut reader = crate::gff::GffReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let gff = arrow_reader.next().unwrap().unwrap();
+
+        for name in ["strand", "phase"] {
+            assert_eq!(gtf.column_by_name(name), gff.column_by_name(name));
+        }
+        let strand = gtf.column_by_name("strand").unwrap();
+        assert_eq!(
+            strand.data_type(),
+            &DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+        );
+        let phase = gtf
+            .column_by_name("phase")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int8Array>()
+            .unwrap();
+        assert_eq!(
+            phase.iter().collect::<Vec<_>>(),
+            vec![None, None, None, Some(0), None]
+        );
+    }
+
+    #[test]
+    fn test_source_and_score() {
+        let gtf = "chr1\thavana\tgene\t100\t900\t.\t+\t.\tgene_id \"g1\";\n\
+            chr1\tensembl\texon\t100\t200\t5.5\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let gff = "##gff-version 3\n\
+            chr1\thavana\tgene\t100\t900\t.\t+\t.\tID=g1\n\
+            chr1\tensembl\texon\t100\t200\t5.5\t+\t.\tID=e1;Parent=t1\n";
+        for dictionary_encode in [None, Some(vec!["source".to_string()])] {
+            let mut reader = GtfReader::new(gtf.as_bytes())
+                .unwrap()
+                .with_dictionary_encode(dictionary_encode.clone());
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let gtf = arrow_reader.next().unwrap().unwrap();
+            let mut reader = crate::gff::GffReader::new(gff.as_bytes())
+                .unwrap()
+                .with_dictionary_encode(dictionary_encode);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let gff = arrow_reader.next().unwrap().unwrap();
+
+            for name in ["source", "score"] {
+                assert_eq!(gtf.column_by_name(name), gff.column_by_name(name));
+            }
+        }
+
+        let mut reader = GtfReader::new(gtf.as_bytes()).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let source = record_batch
+            .column_by_name("source")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            source.iter().collect::<Vec<_>>(),
+            vec![Some("havana"), Some("ensembl")]
+        );
+        let score = record_batch
+            .column_by_name("score")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .unwrap();
+        assert_eq!(score.iter().collect::<Vec<_>>(), vec![None, Some(5.5)]);
+    }
+
+    #[test]
+    fn test_error_policy() {
+        let data = "#!genome-build test\n\
+            chr1\ttest\tgene\t100\t200\t.\t+\t.\tgene_id \"g1\";\n\
+            chr1\ttest\tgene\t300\n\
+            chr1\ttest\tgene\t400\t500\t.\t-\t.\tgene_id \"g2\";\n";
+        let reader = |on_error| {
+            GtfReader::new(data.as_bytes())
+                .unwrap()
+                .with_error_policy(on_error)
+        };
+        assert!(reader(ErrorPolicy::Fail).count().is_err());
+        assert_eq!(reader(ErrorPolicy::Skip).count().unwrap(), 2);
+
+        let mut reader = reader(ErrorPolicy::SkipAndCollect);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        assert_eq!(arrow_reader.next().unwrap().unwrap().num_rows(), 2);
+        let errors = reader.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+    }
+
+    #[test]
+    fn test_feature_types() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/transcripts.gtf");
+        let reader = |types: &[&str]| {
+            GtfReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_feature_types(Some(types.iter().map(|t| t.to_string()).collect()))
+        };
+        let mut cds = reader(&["CDS"]);
+        let ipc = cds.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(record_batch.num_rows(), 1);
+        let ty = record_batch
+            .column_by_name("type")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(ty.value(0), "CDS");
+
+        assert_eq!(reader(&["exon", "gene"]).count().unwrap(), 3);
+        assert_eq!(reader(&["cds"]).count().unwrap(), 0);
+        let mut reader_ignoring_case = reader(&["cds"]).with_ignore_feature_type_case(true);
+        assert_eq!(reader_ignoring_case.count().unwrap(), 1);
+
+        // Skipped features don't contribute attributes.
+        let defs = reader(&["gene"]).scan_attribute_defs().unwrap();
+        let names: Vec<_> = defs.iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, ["gene_id"]);
+    }
+
+    #[test]
+    fn test_duplicate_attributes() {
+        let data = "chr1\ttest\ttranscript\t100\t200\t.\t+\t.\t\
+            gene_id \"g1\"; tag \"basic\"; tag \"CCDS\";\n\
+            chr1\ttest\ttranscript\t300\t400\t.\t+\t.\tgene_id \"g2\"; tag \"basic\";\n";
+        let reader = |duplicates| {
+            GtfReader::new(data.as_bytes())
+                .unwrap()
+                .with_duplicate_attributes(duplicates)
+        };
+        let read_tags = |duplicates| {
+            let defs = reader(duplicates).scan_attribute_defs().unwrap();
+            let ipc = reader(duplicates).records_to_ipc(Some(&defs)).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let record_batch = arrow_reader.next().unwrap().unwrap();
+            let attributes = record_batch.column_by_name("attributes").unwrap();
+            let attributes = attributes.as_any().downcast_ref::<StructArray>().unwrap();
+            (defs, attributes.column_by_name("tag").unwrap().clone())
+        };
+
+        let (defs, tags) = read_tags(DuplicateAttributes::First);
+        assert_eq!(defs[1], AttributeDef::new("tag", AttributeType::String));
+        let tags = tags.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            tags.iter().collect::<Vec<_>>(),
+            [Some("basic"), Some("basic")]
+        );
+
+        let (_, tags) = read_tags(DuplicateAttributes::Last);
+        let tags = tags.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            tags.iter().collect::<Vec<_>>(),
+            [Some("CCDS"), Some("basic")]
+        );
+
+        let (defs, tags) = read_tags(DuplicateAttributes::Collect);
+        assert_eq!(defs[0], AttributeDef::new("gene_id", AttributeType::String));
+        assert_eq!(defs[1], AttributeDef::new("tag", AttributeType::Array));
+        let tags = tags.as_any().downcast_ref::<ListArray>().unwrap();
+        let tags: Vec<Vec<String>> = tags
+            .iter()
+            .map(|list| {
+                let list = list.unwrap();
+                let list = list.as_any().downcast_ref::<StringArray>().unwrap();
+                list.iter().map(|s| s.unwrap().to_string()).collect()
+            })
+            .collect();
+        assert_eq!(tags, [vec!["basic", "CCDS"], vec!["basic"]]);
+        assert_eq!(
+            "collect".parse::<DuplicateAttributes>().unwrap(),
+            DuplicateAttributes::Collect
+        );
+    }
 }
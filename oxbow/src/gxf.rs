@@ -0,0 +1,726 @@
+//! Attribute handling shared by the GFF and GTF readers.
+//!
+//! GFF3 and GTF encode their attributes differently, but both can be viewed as a list of
+//! `(name, value)` pairs. The types here harmonize the two so that selected attributes can be
+//! materialized as typed Arrow columns.
+use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Float64Builder, GenericStringBuilder, Int64Builder, ListBuilder, StringArray,
+    StringDictionaryBuilder, StructArray,
+};
+use arrow::datatypes::{DataType, Field, Int32Type};
+use arrow::error::ArrowError;
+use noodles::{gff, gtf};
+
+/// The type of a materialized attribute column.
+///
+/// A value that doesn't parse as the number of an `Int64` or `Float64` column turns the whole
+/// column into a `Utf8` one, with the numbers read so far converted back to strings, so that no
+/// value is lost.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AttributeType {
+    /// A single string value (`Utf8`).
+    String,
+    /// A list of string values (`List<Utf8>`).
+    Array,
+    /// A single integer value (`Int64`).
+    Int64,
+    /// A single floating-point value (`Float64`).
+    Float64,
+}
+
+impl AttributeType {
+    /// Returns the narrowest type of a single value: `Int64` or `Float64` if it parses as such,
+    /// and `String` otherwise.
+    fn infer(s: &str) -> Self {
+        if s.parse::<i64>().is_ok() {
+            Self::Int64
+        } else if s.parse::<f64>().is_ok() && s.bytes().any(|b| b.is_ascii_digit()) {
+            // The digit check keeps words such as `inf` or `NaN` as strings.
+            Self::Float64
+        } else {
+            Self::String
+        }
+    }
+
+    /// Returns the narrowest type that can hold values of both types.
+    fn join(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Array, _) | (_, Self::Array) => Self::Array,
+            (Self::String, _) | (_, Self::String) => Self::String,
+            _ => Self::Float64,
+        }
+    }
+}
+
+impl FromStr for AttributeType {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "String" => Ok(Self::String),
+            "Array" => Ok(Self::Array),
+            "Int64" => Ok(Self::Int64),
+            "Float64" => Ok(Self::Float64),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid attribute type: {}. Expected String, Array, Int64 or Float64.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// What to make of an attribute repeated in a record, as GTF allows, e.g. `tag "basic"; tag
+/// "CCDS";`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DuplicateAttributes {
+    /// Keep the first value.
+    #[default]
+    First,
+    /// Keep the last value.
+    Last,
+    /// Keep all the values, in order, as a list. The attribute is reported as an
+    /// [`AttributeType::Array`] by the [`AttributeScanner`].
+    Collect,
+}
+
+impl FromStr for DuplicateAttributes {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            "collect" => Ok(Self::Collect),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid duplicate attributes policy: {}. Expected first, last or collect.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// An attribute to materialize as a column.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AttributeDef {
+    pub name: String,
+    pub ty: AttributeType,
+}
+
+impl AttributeDef {
+    pub fn new(name: &str, ty: AttributeType) -> Self {
+        Self {
+            name: name.to_string(),
+            ty,
+        }
+    }
+
+    /// Returns the Arrow data type of the attribute column.
+    pub fn arrow_type(&self) -> DataType {
+        match self.ty {
+            AttributeType::String => DataType::Utf8,
+            AttributeType::Array => {
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)))
+            }
+            AttributeType::Int64 => DataType::Int64,
+            AttributeType::Float64 => DataType::Float64,
+        }
+    }
+}
+
+impl TryFrom<(String, String)> for AttributeDef {
+    type Error = io::Error;
+
+    /// Creates an attribute definition from a `(name, type)` pair, e.g. `("gene_id", "String")`.
+    fn try_from((name, ty): (String, String)) -> Result<Self, Self::Error> {
+        let ty = ty.parse()?;
+        Ok(Self { name, ty })
+    }
+}
+
+/// A borrowed attribute value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AttributeValue<'a> {
+    String(&'a str),
+    Array(Vec<&'a str>),
+}
+
+impl AttributeValue<'_> {
+    /// Returns the type of the value, inferring numbers from single values if `infer` is set.
+    fn ty(&self, infer: bool) -> AttributeType {
+        match self {
+            Self::String(s) if infer => AttributeType::infer(s),
+            Self::String(_) => AttributeType::String,
+            Self::Array(_) => AttributeType::Array,
+        }
+    }
+
+    /// Returns the value as a single string, if it is one.
+    fn as_single(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            Self::Array(values) if values.len() == 1 => Some(values[0]),
+            Self::Array(_) => None,
+        }
+    }
+
+    /// Returns the values of both, in order, as a list.
+    fn concat(self, other: Self) -> Self {
+        let mut values = match self {
+            Self::String(s) => vec![s],
+            Self::Array(values) => values,
+        };
+        match other {
+            Self::String(s) => values.push(s),
+            Self::Array(other) => values.extend(other),
+        }
+        Self::Array(values)
+    }
+}
+
+impl<'a> From<&'a gff::record::attributes::field::Value> for AttributeValue<'a> {
+    fn from(value: &'a gff::record::attributes::field::Value) -> Self {
+        match value.as_string() {
+            Some(s) => Self::String(s),
+            None => Self::Array(value.iter().map(|s| s.as_str()).collect()),
+        }
+    }
+}
+
+/// Returns the attributes of a GFF record as `(name, value)` pairs.
+pub fn gff_attributes(
+    record: &gff::Record,
+) -> impl Iterator<Item = (&str, AttributeValue<'_>)> + '_ {
+    record
+        .attributes()
+        .iter()
+        .map(|(tag, value)| (tag.as_str(), AttributeValue::from(value)))
+}
+
+/// Returns the attributes of a GFF record with their values percent-encoded again, as GFF3 writes
+/// them, e.g. `a%3Bb` for `a;b`. The values can be viewed as an [`AttributeValue`] with `From`.
+pub fn gff_encoded_attributes(
+    record: &gff::Record,
+) -> Vec<(&str, gff::record::attributes::field::Value)> {
+    use gff::record::attributes::field::Value;
+
+    let encode = |s: &String| Value::from(s.as_str()).to_string();
+    record
+        .attributes()
+        .iter()
+        .map(|(tag, value)| {
+            let value = match value {
+                Value::String(s) => Value::String(encode(s)),
+                Value::Array(values) => Value::Array(values.iter().map(encode).collect()),
+            };
+            (tag.as_str(), value)
+        })
+        .collect()
+}
+
+/// Returns the attributes of a GTF record as `(name, value)` pairs.
+pub fn gtf_attributes(
+    record: &gtf::Record,
+) -> impl Iterator<Item = (&str, AttributeValue<'_>)> + '_ {
+    record
+        .attributes()
+        .iter()
+        .map(|entry| (entry.key(), AttributeValue::String(entry.value())))
+}
+
+/// Returns the value of the first attribute called `name`, as a list.
+fn attribute_values<'a>(
+    mut attributes: impl Iterator<Item = (&'a str, AttributeValue<'a>)>,
+    name: &str,
+) -> Vec<&'a str> {
+    match attributes.find(|(key, _)| *key == name) {
+        Some((_, AttributeValue::String(s))) => vec![s],
+        Some((_, AttributeValue::Array(values))) => values,
+        None => Vec::new(),
+    }
+}
+
+/// Returns the IDs of the parents of a GFF record, from its `Parent` attribute.
+///
+/// GFF3 features can have several parents, e.g. an exon shared by two transcripts.
+pub fn gff_parent_ids(record: &gff::Record) -> Vec<&str> {
+    attribute_values(gff_attributes(record), "Parent")
+}
+
+/// Returns the ID of the parent of a GTF record, harmonized with the GFF3 hierarchy.
+///
+/// A transcript's parent is its `gene_id`, genes have none, and all other features, such as
+/// exons and CDSs, belong to their `transcript_id`.
+pub fn gtf_parent_ids(record: &gtf::Record) -> Vec<&str> {
+    let name = match record.ty() {
+        "gene" => return Vec::new(),
+        "transcript" => "gene_id",
+        _ => "transcript_id",
+    };
+    attribute_values(gtf_attributes(record), name)
+}
+
+/// Returns whether a feature of type `ty` is selected by the given types, matched exactly and
+/// optionally ignoring ASCII case. All features are selected without types.
+pub(crate) fn feature_type_matches(types: Option<&[String]>, ignore_case: bool, ty: &str) -> bool {
+    match types {
+        None => true,
+        Some(types) if ignore_case => types.iter().any(|t| t.eq_ignore_ascii_case(ty)),
+        Some(types) => types.iter().any(|t| t == ty),
+    }
+}
+
+/// Discovers the attributes present in a set of records.
+///
+/// Attributes are reported in order of first appearance. An attribute seen as both a single value
+/// and a list is reported as an [`AttributeType::Array`].
+///
+/// With type inference, a single-valued attribute whose values all parse as integers is reported
+/// as an [`AttributeType::Int64`], and one whose values all parse as numbers as an
+/// [`AttributeType::Float64`].
+///
+/// Overridden attributes keep the given type whatever their values, and are reported even if they
+/// are never seen.
+#[derive(Debug, Default)]
+pub struct AttributeScanner {
+    defs: Vec<AttributeDef>,
+    index: HashMap<String, usize>,
+    infer_types: bool,
+    overrides: HashMap<String, AttributeType>,
+    duplicates: DuplicateAttributes,
+}
+
+impl AttributeScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Infers numeric attribute types from the values seen. Defaults to off, which reports all
+    /// single-valued attributes as strings without parsing them.
+    pub fn with_type_inference(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    /// Forces the types of the given attributes, e.g. to keep a numeric-looking ID a string.
+    pub fn with_overrides(mut self, overrides: Vec<AttributeDef>) -> Self {
+        self.overrides = overrides
+            .into_iter()
+            .map(|def| (def.name, def.ty))
+            .collect();
+        self
+    }
+
+    /// Sets what to make of attributes repeated in a record. With
+    /// [`DuplicateAttributes::Collect`], they are reported as [`AttributeType::Array`]s.
+    /// Defaults to [`DuplicateAttributes::First`].
+    pub fn with_duplicates(mut self, duplicates: DuplicateAttributes) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    pub fn push<'a, I>(&mut self, attributes: I)
+    where
+        I: IntoIterator<Item = (&'a str, AttributeValue<'a>)>,
+    {
+        // The attributes of this record, to find repeated ones.
+        let mut seen = Vec::new();
+        for (name, value) in attributes {
+            let repeated = self.duplicates == DuplicateAttributes::Collect
+                && self.index.get(name).is_some_and(|i| seen.contains(i));
+            match (self.index.get(name), self.overrides.get(name)) {
+                (Some(_), Some(_)) => {}
+                (Some(&i), None) if repeated => self.defs[i].ty = AttributeType::Array,
+                (Some(&i), None) => {
+                    self.defs[i].ty = self.defs[i].ty.join(value.ty(self.infer_types));
+                }
+                (None, ty) => {
+                    let ty = ty.copied().unwrap_or_else(|| value.ty(self.infer_types));
+                    self.index.insert(name.to_string(), self.defs.len());
+                    self.defs.push(AttributeDef::new(name, ty));
+                }
+            }
+            seen.push(self.index[name]);
+        }
+    }
+
+    pub fn push_gff(&mut self, record: &gff::Record) {
+        self.push(gff_attributes(record));
+    }
+
+    pub fn push_gtf(&mut self, record: &gtf::Record) {
+        self.push(gtf_attributes(record));
+    }
+
+    /// Returns the discovered attribute definitions, followed by any overridden attributes that
+    /// weren't seen, in name order.
+    pub fn collect(mut self) -> Vec<AttributeDef> {
+        let mut unseen: Vec<_> = self
+            .overrides
+            .into_iter()
+            .filter(|(name, _)| !self.index.contains_key(name))
+            .map(|(name, ty)| AttributeDef { name, ty })
+            .collect();
+        unseen.sort_by(|a, b| a.name.cmp(&b.name));
+        self.defs.extend(unseen);
+        self.defs
+    }
+}
+
+enum AttributeBuilder {
+    String(GenericStringBuilder<i32>),
+    Array(ListBuilder<GenericStringBuilder<i32>>),
+    Int64(Int64Builder),
+    Float64(Float64Builder),
+}
+
+impl AttributeBuilder {
+    fn new(ty: AttributeType) -> Self {
+        match ty {
+            AttributeType::String => Self::String(GenericStringBuilder::<i32>::new()),
+            AttributeType::Array => {
+                Self::Array(ListBuilder::new(GenericStringBuilder::<i32>::new()))
+            }
+            AttributeType::Int64 => Self::Int64(Int64Builder::new()),
+            AttributeType::Float64 => Self::Float64(Float64Builder::new()),
+        }
+    }
+
+    /// Appends a value, coercing it to the column type if needed.
+    ///
+    /// A list stored in a string column is joined with commas, as in the GFF3 encoding, and a
+    /// single value stored in a list column becomes a one-element list. A value that isn't a
+    /// number turns a numeric column into a string column.
+    fn append_value(&mut self, value: &AttributeValue) {
+        match self {
+            Self::Int64(builder) => {
+                if let Some(n) = value.as_single().and_then(|s| s.parse().ok()) {
+                    return builder.append_value(n);
+                }
+            }
+            Self::Float64(builder) => {
+                if let Some(n) = value.as_single().and_then(|s| s.parse().ok()) {
+                    return builder.append_value(n);
+                }
+            }
+            _ => {}
+        }
+        self.fall_back_to_string();
+        match (self, value) {
+            (Self::String(builder), AttributeValue::String(s)) => builder.append_value(s),
+            (Self::String(builder), AttributeValue::Array(values)) => {
+                builder.append_value(values.join(","))
+            }
+            (Self::Array(builder), AttributeValue::String(s)) => {
+                builder.values().append_value(s);
+                builder.append(true);
+            }
+            (Self::Array(builder), AttributeValue::Array(values)) => {
+                for v in values {
+                    builder.values().append_value(v);
+                }
+                builder.append(true);
+            }
+            (Self::Int64(_) | Self::Float64(_), _) => unreachable!(),
+        }
+    }
+
+    /// Turns a numeric column into a string column holding the values appended so far.
+    fn fall_back_to_string(&mut self) {
+        let values: Vec<Option<String>> = match self {
+            Self::Int64(builder) => builder
+                .finish()
+                .iter()
+                .map(|n| n.map(|n| n.to_string()))
+                .collect(),
+            Self::Float64(builder) => builder
+                .finish()
+                .iter()
+                .map(|n| n.map(|n| n.to_string()))
+                .collect(),
+            Self::String(_) | Self::Array(_) => return,
+        };
+        let mut builder = GenericStringBuilder::<i32>::new();
+        for value in values {
+            builder.append_option(value);
+        }
+        *self = Self::String(builder);
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            Self::String(builder) => builder.append_null(),
+            Self::Array(builder) => builder.append_null(),
+            Self::Int64(builder) => builder.append_null(),
+            Self::Float64(builder) => builder.append_null(),
+        }
+    }
+
+    /// Appends an empty string or list, 0, or NaN.
+    fn append_empty(&mut self) {
+        match self {
+            Self::String(builder) => builder.append_value(""),
+            Self::Array(builder) => builder.append(true),
+            Self::Int64(builder) => builder.append_value(0),
+            Self::Float64(builder) => builder.append_value(f64::NAN),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::String(builder) => Arc::new(builder.finish()),
+            Self::Array(builder) => Arc::new(builder.finish()),
+            Self::Int64(builder) => Arc::new(builder.finish()),
+            Self::Float64(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Builds a struct column with one field per attribute definition.
+///
+/// Attributes missing from a record are null, or empty if `missing_as_empty` is set. Attributes
+/// repeated in a record are kept after [`DuplicateAttributes`].
+pub(crate) struct AttributesBuilder {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    builders: Vec<AttributeBuilder>,
+    seen: usize,
+    missing_as_empty: bool,
+    duplicates: DuplicateAttributes,
+}
+
+impl AttributesBuilder {
+    pub fn new(defs: &[AttributeDef], missing_as_empty: bool) -> Self {
+        Self {
+            names: defs.iter().map(|def| def.name.clone()).collect(),
+            index: defs
+                .iter()
+                .enumerate()
+                .map(|(i, def)| (def.name.clone(), i))
+                .collect(),
+            builders: defs
+                .iter()
+                .map(|def| AttributeBuilder::new(def.ty))
+                .collect(),
+            seen: 0,
+            missing_as_empty,
+            duplicates: DuplicateAttributes::default(),
+        }
+    }
+
+    pub fn with_duplicates(mut self, duplicates: DuplicateAttributes) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    pub fn push<'a, I>(&mut self, attributes: I)
+    where
+        I: IntoIterator<Item = (&'a str, AttributeValue<'a>)>,
+    {
+        let mut values: Vec<Option<AttributeValue>> = vec![None; self.builders.len()];
+        for (name, value) in attributes {
+            if let Some(&i) = self.index.get(name) {
+                values[i] = match (values[i].take(), self.duplicates) {
+                    (None, _) | (Some(_), DuplicateAttributes::Last) => Some(value),
+                    (Some(first), DuplicateAttributes::First) => Some(first),
+                    (Some(values), DuplicateAttributes::Collect) => Some(values.concat(value)),
+                };
+            }
+        }
+        for (builder, value) in self.builders.iter_mut().zip(values) {
+            match value {
+                Some(value) => builder.append_value(&value),
+                None if self.missing_as_empty => builder.append_empty(),
+                None => builder.append_null(),
+            }
+        }
+        self.seen += 1;
+    }
+
+    pub fn finish(&mut self) -> Result<StructArray, ArrowError> {
+        if self.builders.is_empty() {
+            // A struct with no fields would otherwise have a length of zero.
+            return Ok(StructArray::new_empty_fields(self.seen, None));
+        }
+        let columns = self
+            .names
+            .iter()
+            .zip(self.builders.iter_mut())
+            .map(|(name, builder)| (name.as_str(), builder.finish()))
+            .collect::<Vec<_>>();
+        StructArray::try_from(columns)
+    }
+}
+
+/// The values of the `strand` column, shared by GFF and GTF.
+const STRANDS: [&str; 4] = ["+", "-", ".", "?"];
+
+/// Creates a builder for the `strand` column, dictionary-encoded over `+`, `-`, `.` and `?`.
+///
+/// The dictionary is fixed so that GFF and GTF files, which have no unknown (`?`) strand, give
+/// the same column type and dictionary.
+pub(crate) fn strand_builder(
+    capacity: usize,
+) -> Result<StringDictionaryBuilder<Int32Type>, ArrowError> {
+    StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
+        capacity,
+        &StringArray::from(STRANDS.to_vec()),
+    )
+}
+
+/// Builds a `parent_id` column (`List<Utf8>`), null for features without a parent.
+pub(crate) struct ParentIdsBuilder {
+    inner: ListBuilder<GenericStringBuilder<i32>>,
+}
+
+impl ParentIdsBuilder {
+    pub fn new() -> Self {
+        Self {
+            inner: ListBuilder::new(GenericStringBuilder::<i32>::new()),
+        }
+    }
+
+    pub fn push(&mut self, parent_ids: &[&str]) {
+        if parent_ids.is_empty() {
+            self.inner.append_null();
+            return;
+        }
+        for parent_id in parent_ids {
+            self.inner.values().append_value(parent_id);
+        }
+        self.inner.append(true);
+    }
+
+    pub fn finish(&mut self) -> ArrayRef {
+        Arc::new(self.inner.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_def_try_from() {
+        let def = AttributeDef::try_from(("Parent".to_string(), "Array".to_string())).unwrap();
+        assert_eq!(def, AttributeDef::new("Parent", AttributeType::Array));
+        let def = AttributeDef::try_from(("level".to_string(), "Int64".to_string())).unwrap();
+        assert_eq!(def.arrow_type(), DataType::Int64);
+        assert!(AttributeDef::try_from(("ID".to_string(), "Int".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_scanner_infers_types() {
+        let records = [
+            vec![
+                ("ID", AttributeValue::String("g1")),
+                ("level", AttributeValue::String("2")),
+                ("score", AttributeValue::String("3")),
+                ("code", AttributeValue::String("7")),
+            ],
+            vec![
+                ("ID", AttributeValue::String("12")),
+                ("level", AttributeValue::String("1")),
+                ("score", AttributeValue::String("0.5")),
+                ("code", AttributeValue::String("7b")),
+            ],
+        ];
+        let scan = |infer_types| {
+            let mut scanner = AttributeScanner::new().with_type_inference(infer_types);
+            for record in records.clone() {
+                scanner.push(record);
+            }
+            scanner
+                .collect()
+                .into_iter()
+                .map(|def| def.ty)
+                .collect::<Vec<_>>()
+        };
+        use AttributeType::*;
+        assert_eq!(scan(true), vec![String, Int64, Float64, String]);
+        assert_eq!(scan(false), vec![String; 4]);
+    }
+
+    #[test]
+    fn test_scanner_overrides() {
+        let mut scanner = AttributeScanner::new()
+            .with_type_inference(true)
+            .with_overrides(vec![
+                AttributeDef::new("ID", AttributeType::String),
+                AttributeDef::new("Note", AttributeType::Array),
+            ]);
+        scanner.push(vec![
+            ("ID", AttributeValue::String("1")),
+            ("level", AttributeValue::String("2")),
+        ]);
+        scanner.push(vec![("ID", AttributeValue::Array(vec!["2", "3"]))]);
+        assert_eq!(
+            scanner.collect(),
+            vec![
+                AttributeDef::new("ID", AttributeType::String),
+                AttributeDef::new("level", AttributeType::Int64),
+                AttributeDef::new("Note", AttributeType::Array),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_numeric_column_falls_back_to_string() {
+        let defs = [
+            AttributeDef::new("level", AttributeType::Int64),
+            AttributeDef::new("score", AttributeType::Float64),
+        ];
+        let mut builder = AttributesBuilder::new(&defs, false);
+        builder.push(vec![
+            ("level", AttributeValue::String("2")),
+            ("score", AttributeValue::String("0.5")),
+        ]);
+        builder.push(vec![("score", AttributeValue::String("1"))]);
+        builder.push(vec![
+            ("level", AttributeValue::String("high")),
+            ("score", AttributeValue::String("1e3")),
+        ]);
+        let array = builder.finish().unwrap();
+
+        let level = array.column_by_name("level").unwrap();
+        let level = level.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            level.iter().collect::<Vec<_>>(),
+            vec![Some("2"), None, Some("high")]
+        );
+        let score = array.column_by_name("score").unwrap();
+        let score = score
+            .as_any()
+            .downcast_ref::<arrow::array::Float64Array>()
+            .unwrap();
+        assert_eq!(score.values().to_vec(), vec![0.5, 1.0, 1000.0]);
+    }
+
+    #[test]
+    fn test_scanner_promotes_to_array() {
+        let mut scanner = AttributeScanner::new();
+        scanner.push(vec![("ID", AttributeValue::String("a"))]);
+        scanner.push(vec![
+            ("ID", AttributeValue::Array(vec!["b", "c"])),
+            ("Name", AttributeValue::String("x")),
+        ]);
+        assert_eq!(
+            scanner.collect(),
+            vec![
+                AttributeDef::new("ID", AttributeType::Array),
+                AttributeDef::new("Name", AttributeType::String),
+            ]
+        );
+    }
+}
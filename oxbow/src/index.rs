@@ -0,0 +1,300 @@
+//! Building tabix and CSI indexes for BGZF-compressed, tab-delimited files.
+//!
+//! Indexes are written next to the indexed file, as `<path>.tbi` or `<path>.csi`, where the
+//! readers look for them.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::str::FromStr;
+
+use noodles::bgzf;
+use noodles::core::Position;
+use noodles::csi::{self, index::reference_sequence::bin::Chunk};
+use noodles::tabix;
+
+use crate::compression::Compression;
+
+/// The layout of the indexed file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Preset {
+    Bed,
+    Gff,
+    Sam,
+    Vcf,
+}
+
+impl Preset {
+    /// Infers the preset from a file name, ignoring a `.gz` or `.bgz` extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let path = path.to_lowercase();
+        let path = path
+            .strip_suffix(".gz")
+            .or_else(|| path.strip_suffix(".bgz"))
+            .unwrap_or(&path);
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension {
+            "bed" => Some(Self::Bed),
+            "gff" | "gff3" | "gtf" => Some(Self::Gff),
+            "sam" => Some(Self::Sam),
+            "vcf" => Some(Self::Vcf),
+            _ => None,
+        }
+    }
+
+    fn header(&self) -> csi::index::Header {
+        match self {
+            Self::Bed => csi::index::header::Builder::bed(),
+            Self::Gff => csi::index::header::Builder::gff(),
+            Self::Sam => csi::index::header::Builder::sam(),
+            Self::Vcf => csi::index::header::Builder::vcf(),
+        }
+        .build()
+    }
+
+    /// Returns the reference sequence name and the 1-based, closed interval of a line.
+    fn interval<'a>(&self, line: &'a str) -> io::Result<(&'a str, Position, Position)> {
+        let invalid_data = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid line: {}", line),
+            )
+        };
+        let parse = |s: Option<&str>| -> io::Result<usize> {
+            s.and_then(|s| s.parse().ok()).ok_or_else(invalid_data)
+        };
+        let fields: Vec<&str> = line.split('\t').collect();
+        let name = match self {
+            Self::Sam => fields.get(2),
+            _ => fields.first(),
+        };
+        let name = *name.ok_or_else(invalid_data)?;
+        let (start, end) = match self {
+            // 0-based, half-open
+            Self::Bed => {
+                let start = parse(fields.get(1).copied())? + 1;
+                let end = parse(fields.get(2).copied())?;
+                (start, end.max(start))
+            }
+            Self::Gff => (
+                parse(fields.get(3).copied())?,
+                parse(fields.get(4).copied())?,
+            ),
+            // The span of the CIGAR operations consuming the reference.
+            Self::Sam => {
+                let start = parse(fields.get(3).copied())?;
+                let cigar = fields.get(5).ok_or_else(invalid_data)?;
+                // A missing (`*`) CIGAR has no operations, and spans the start only.
+                let (mut length, mut digits) = (0, 0);
+                for c in cigar.bytes() {
+                    match c {
+                        b'0'..=b'9' => digits = digits * 10 + usize::from(c - b'0'),
+                        b'M' | b'D' | b'N' | b'=' | b'X' => {
+                            length += digits;
+                            digits = 0;
+                        }
+                        _ => digits = 0,
+                    }
+                }
+                (start, start + length.max(1) - 1)
+            }
+            // The span of the reference allele, unless INFO has an END.
+            Self::Vcf => {
+                let start = parse(fields.get(1).copied())?;
+                let reference_bases = fields.get(3).ok_or_else(invalid_data)?;
+                let end = fields
+                    .get(7)
+                    .and_then(|info| info.split(';').find_map(|kv| kv.strip_prefix("END=")))
+                    .map(|end| parse(Some(end)))
+                    .transpose()?
+                    .unwrap_or(start + reference_bases.len().max(1) - 1);
+                (start, end.max(start))
+            }
+        };
+        let start = Position::try_from(start).map_err(|_| invalid_data())?;
+        let end = Position::try_from(end).map_err(|_| invalid_data())?;
+        Ok((name, start, end))
+    }
+
+    /// Returns whether a line carries no data.
+    fn is_skipped(&self, line: &str) -> bool {
+        line.is_empty()
+            || line.starts_with('#')
+            || (*self == Self::Bed && (line.starts_with("track") || line.starts_with("browser")))
+            // Header lines, and unplaced reads, which have no reference sequence.
+            || (*self == Self::Sam
+                && (line.starts_with('@') || line.split('\t').nth(2) == Some("*")))
+    }
+}
+
+impl FromStr for Preset {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bed" => Ok(Self::Bed),
+            "gff" | "gtf" => Ok(Self::Gff),
+            "sam" => Ok(Self::Sam),
+            "vcf" => Ok(Self::Vcf),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid preset: {}. Expected bed, gff, sam or vcf.", s),
+            )),
+        }
+    }
+}
+
+/// Builds an index of a BGZF-compressed file.
+///
+/// The preset is inferred from the file name when not given.
+pub fn build_index(path: &str, preset: Option<Preset>) -> io::Result<csi::Index> {
+    let preset = preset.or_else(|| Preset::from_path(path)).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Could not infer the preset of {}. Give one explicitly.",
+                path
+            ),
+        )
+    })?;
+    let mut file = BufReader::new(File::open(path)?);
+    if Compression::detect(&mut file)? != Compression::Bgzf {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Only BGZF-compressed files can be indexed. Compress the file with bgzip.",
+        ));
+    }
+    let mut reader = bgzf::Reader::new(file);
+    let mut indexer = tabix::index::Indexer::default();
+    indexer.set_header(preset.header());
+    let mut line = String::new();
+    loop {
+        let start = reader.virtual_position();
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end_matches(['\n', '\r']);
+        if preset.is_skipped(line) {
+            continue;
+        }
+        let (name, start_position, end_position) = preset.interval(line)?;
+        let chunk = Chunk::new(start, reader.virtual_position());
+        indexer.add_record(name, start_position, end_position, chunk)?;
+    }
+    Ok(indexer.build())
+}
+
+/// Builds a tabix index of a BGZF-compressed file and writes it to `<path>.tbi`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::index::write_tabix;
+///
+/// write_tabix("sample.vcf.gz", None).unwrap();
+/// ```
+pub fn write_tabix(path: &str, preset: Option<Preset>) -> io::Result<()> {
+    let index = build_index(path, preset)?;
+    tabix::write(format!("{}.tbi", path), &index)
+}
+
+/// Builds a CSI index of a BGZF-compressed file and writes it to `<path>.csi`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::index::{write_csi, Preset};
+///
+/// write_csi("sample.bed.gz", Some(Preset::Bed)).unwrap();
+/// ```
+pub fn write_csi(path: &str, preset: Option<Preset>) -> io::Result<()> {
+    let index = build_index(path, preset)?;
+    csi::write(format!("{}.csi", path), &index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcf::VcfReader;
+    use arrow::ipc::reader::FileReader;
+
+    fn num_rows(ipc: Vec<u8>) -> usize {
+        let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader.map(|batch| batch.unwrap().num_rows()).sum()
+    }
+
+    /// Copies a fixture to a fresh directory, so that it has no index.
+    fn copy_fixture(name: &str, dir: &str) -> String {
+        let mut src = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        src.push("../fixtures");
+        src.push(name);
+        let dir = std::env::temp_dir().join(format!("oxbow-{}-{}", dir, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dst = dir.join(name);
+        std::fs::copy(src, &dst).unwrap();
+        dst.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_preset_from_path() {
+        assert_eq!(Preset::from_path("a.bed.gz"), Some(Preset::Bed));
+        assert_eq!(Preset::from_path("a.VCF.bgz"), Some(Preset::Vcf));
+        assert_eq!(Preset::from_path("a.gff3.gz"), Some(Preset::Gff));
+        assert_eq!(Preset::from_path("a.sam.gz"), Some(Preset::Sam));
+        assert_eq!(Preset::from_path("a.txt.gz"), None);
+        assert!("bam".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn test_write_tabix_then_query() {
+        let path = copy_fixture("sample.vcf.gz", "tabix");
+        write_tabix(&path, None).unwrap();
+        let mut reader = VcfReader::new_from_path(&path).unwrap();
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let mut expected = VcfReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        for region in ["sq0", "sq0:15-30", "sq1:1-5"] {
+            assert_eq!(
+                num_rows(reader.records_to_ipc(Some(region)).unwrap()),
+                num_rows(expected.records_to_ipc(Some(region)).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_csi_then_query() {
+        let path = copy_fixture("sample.vcf.gz", "csi");
+        write_csi(&path, Some(Preset::Vcf)).unwrap();
+        assert!(std::path::Path::new(&format!("{}.csi", path)).exists());
+        let mut reader = VcfReader::new_from_path(&path).unwrap();
+        assert!(num_rows(reader.records_to_ipc(Some("sq0")).unwrap()) > 0);
+    }
+
+    #[test]
+    fn test_build_bed_index() {
+        let dir = std::env::temp_dir().join(format!("oxbow-bed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sample.bed.gz");
+        let mut writer = bgzf::Writer::new(File::create(&path).unwrap());
+        io::Write::write_all(
+            &mut writer,
+            b"track name=x\nsq0\t0\t10\nsq0\t20\t30\nsq1\t5\t6\n",
+        )
+        .unwrap();
+        writer.finish().unwrap();
+        let index = build_index(path.to_str().unwrap(), None).unwrap();
+        let names = index.header().unwrap().reference_sequence_names();
+        assert_eq!(names.iter().collect::<Vec<_>>(), ["sq0", "sq1"]);
+        let (start, end) = (
+            Position::try_from(1).unwrap(),
+            Position::try_from(10).unwrap(),
+        );
+        assert_eq!(index.query(0, start..=end).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_build_index_uncompressed() {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../fixtures/sample.bed");
+        assert!(build_index(path.to_str().unwrap(), None).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Output helpers for the Apache Arrow IPC produced by the readers.
+use std::io::{Cursor, Write};
+
+use arrow::datatypes::{Fields, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::{FileReader, StreamReader};
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatchReader;
+
+/// Writes record batches to `writer` in the Arrow IPC stream format.
+///
+/// The schema is written once up front, followed by each batch as it is read. The stream is
+/// finished before the writer is returned.
+pub fn write_ipc_stream<W: Write>(
+    reader: impl RecordBatchReader,
+    writer: W,
+) -> Result<W, ArrowError> {
+    let mut writer = StreamWriter::try_new(writer, &reader.schema())?;
+    for batch in reader {
+        writer.write(&batch?)?;
+    }
+    writer.finish()?;
+    writer.into_inner()
+}
+
+/// Re-encodes Arrow IPC file bytes, as returned by the readers, in the IPC stream format.
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::bam::BamReader;
+/// use oxbow::io::write_ipc_stream_from_file;
+///
+/// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+/// let ipc = reader.records_to_ipc(None).unwrap();
+/// let file = std::fs::File::create("sample.arrows").unwrap();
+/// write_ipc_stream_from_file(&ipc, file).unwrap();
+/// ```
+pub fn write_ipc_stream_from_file<W: Write>(ipc: &[u8], writer: W) -> Result<W, ArrowError> {
+    let reader = FileReader::try_new(Cursor::new(ipc), None)?;
+    write_ipc_stream(reader, writer)
+}
+
+/// Reads Arrow IPC bytes in either the file format, as returned by the readers, or the stream
+/// format.
+pub fn ipc_reader(ipc: &[u8]) -> Result<Box<dyn RecordBatchReader + '_>, ArrowError> {
+    if ipc.starts_with(b"ARROW1") {
+        Ok(Box::new(FileReader::try_new(Cursor::new(ipc), None)?))
+    } else {
+        Ok(Box::new(StreamReader::try_new(Cursor::new(ipc), None)?))
+    }
+}
+
+/// Encodes a schema as an Arrow IPC stream without batches.
+pub fn schema_to_ipc(schema: &Schema) -> Result<Vec<u8>, ArrowError> {
+    let mut writer = StreamWriter::try_new(Vec::new(), schema)?;
+    writer.finish()?;
+    writer.into_inner()
+}
+
+/// Encodes fields, e.g. those returned by a reader's `fields` method, as an Arrow IPC stream
+/// without batches.
+pub fn fields_to_ipc(fields: Fields) -> Result<Vec<u8>, ArrowError> {
+    schema_to_ipc(&Schema::new(fields))
+}
+
+/// Iterates over the record batches of Arrow IPC file bytes, one at a time, re-encoding each as
+/// a self-contained IPC stream.
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::fasta::FastaReader;
+/// use oxbow::io::IpcStreamBatches;
+/// use oxbow::BatchLimit;
+///
+/// let mut reader = FastaReader::new("sample.fasta")
+///     .unwrap()
+///     .with_batch_limit(BatchLimit::Records(1000));
+/// let ipc = reader.records_to_ipc(None).unwrap();
+/// for stream in IpcStreamBatches::try_new(ipc).unwrap() {
+///     let stream = stream.unwrap();
+/// }
+/// ```
+pub struct IpcStreamBatches {
+    reader: FileReader<Cursor<Vec<u8>>>,
+}
+
+impl IpcStreamBatches {
+    /// Creates an iterator over Arrow IPC file bytes, as returned by the readers.
+    pub fn try_new(ipc: Vec<u8>) -> Result<Self, ArrowError> {
+        let reader = FileReader::try_new(Cursor::new(ipc), None)?;
+        Ok(Self { reader })
+    }
+
+    /// Returns the schema as an IPC stream without batches.
+    pub fn schema_ipc(&self) -> Result<Vec<u8>, ArrowError> {
+        schema_to_ipc(&self.reader.schema())
+    }
+}
+
+impl Iterator for IpcStreamBatches {
+    type Item = Result<Vec<u8>, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch = self.reader.next()?;
+        Some(batch.and_then(|batch| {
+            let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema())?;
+            writer.write(&batch)?;
+            writer.finish()?;
+            writer.into_inner()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bam::BamReader;
+    use crate::fasta::FastaReader;
+    use crate::BatchLimit;
+
+    #[test]
+    fn test_write_ipc_stream_from_file() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+
+        let stream = write_ipc_stream_from_file(&ipc, Vec::new()).unwrap();
+        let stream_reader = StreamReader::try_new(Cursor::new(&stream), None).unwrap();
+        let file_reader = FileReader::try_new(Cursor::new(&ipc), None).unwrap();
+        assert_eq!(stream_reader.schema(), file_reader.schema());
+        let batches = stream_reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 6);
+
+        for ipc in [&ipc, &stream] {
+            let rows: usize = ipc_reader(ipc)
+                .unwrap()
+                .map(|b| b.unwrap().num_rows())
+                .sum();
+            assert_eq!(rows, 6);
+        }
+    }
+
+    #[test]
+    fn test_ipc_stream_batches() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.fa");
+        let mut reader = FastaReader::new(dir.to_str().unwrap())
+            .unwrap()
+            .with_batch_limit(BatchLimit::Records(2));
+        let ipc = reader.records_to_ipc(None).unwrap();
+
+        let batches = IpcStreamBatches::try_new(ipc).unwrap();
+        let schema = StreamReader::try_new(Cursor::new(batches.schema_ipc().unwrap()), None)
+            .unwrap()
+            .schema();
+        let rows: Vec<usize> = batches
+            .map(|stream| {
+                let mut reader = StreamReader::try_new(Cursor::new(stream.unwrap()), None).unwrap();
+                assert_eq!(reader.schema(), schema);
+                let batch = reader.next().unwrap().unwrap();
+                assert!(reader.next().is_none());
+                batch.num_rows()
+            })
+            .collect();
+        assert_eq!(rows, vec![2, 1]);
+    }
+}
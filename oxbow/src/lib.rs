@@ -25,13 +25,43 @@
 
 pub mod bam;
 mod batch_builder;
-pub mod fasta;
-pub mod fastq;
-pub mod vpos;
-// pub mod cram;
+mod bbi;
 pub mod bcf;
+pub mod bed;
 pub mod bigbed;
 pub mod bigwig;
+pub mod compression;
+pub mod coordinates;
+pub mod coverage;
+pub mod cram;
+pub mod error_policy;
+pub mod fasta;
+pub mod fastq;
+pub mod flatten;
 pub mod gff;
 pub mod gtf;
+pub mod gxf;
+pub mod index;
+pub mod io;
+pub mod maf;
+pub mod methylation;
+pub mod naming;
+pub mod paf;
+mod pairs;
+mod parallel;
+pub mod peak;
+mod pileup;
+pub mod progress;
+pub mod region;
+pub mod sam;
+mod sampling;
+pub mod sequence;
+mod sort_order;
+pub mod stats;
+pub mod tsv;
+pub mod twobit;
 pub mod vcf;
+pub mod vpos;
+pub mod wig;
+
+pub use batch_builder::BatchLimit;
@@ -0,0 +1,395 @@
+//! A reader for the UCSC Multiple Alignment Format (MAF).
+//!
+//! Each sequence (`s`) line of an alignment block becomes a row with the columns `block_id`, the
+//! 0-based index of its block, `score`, from the block's `a` line, and `src`, `start`, `size`,
+//! `strand`, `src_size` and `text`, as in the file. Coordinates are 0-based and, on the `-`
+//! strand, relative to the reverse complement of the source sequence.
+//!
+//! The auxiliary `i` and `e` lines are skipped. Quality (`q`) lines are skipped too, unless
+//! [`MafOptions::include_quality`] is set.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Builder, StringBuilder, UInt64Builder};
+use arrow::datatypes::Fields;
+use arrow::{error::ArrowError, record_batch::RecordBatch};
+
+use crate::batch_builder::{
+    builder_fields, count_err, provenance, write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES,
+};
+use crate::compression::decompress;
+
+/// A sequence line of an alignment block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MafRecord {
+    pub block_id: u64,
+    pub score: Option<f64>,
+    pub src: String,
+    pub start: u64,
+    pub size: u64,
+    pub strand: String,
+    pub src_size: u64,
+    pub text: String,
+    pub quality: Option<String>,
+}
+
+/// Options for reading MAF files.
+#[derive(Clone, Debug, Default)]
+pub struct MafOptions {
+    /// Add a `quality` column (Utf8) with the `q` line following each sequence line. It is null
+    /// for sequences without one.
+    pub include_quality: Option<bool>,
+}
+
+fn invalid_data(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid line: {}", line),
+    )
+}
+
+fn parse<T: std::str::FromStr>(s: &str, line: &str) -> io::Result<T> {
+    s.parse().map_err(|_| invalid_data(line))
+}
+
+/// Parses the score of an `a` line, e.g. `a score=23262.0`.
+fn parse_score(line: &str) -> io::Result<Option<f64>> {
+    line.split_whitespace()
+        .skip(1)
+        .find_map(|field| field.strip_prefix("score="))
+        .map(|score| parse(score, line))
+        .transpose()
+}
+
+/// Parses an `s` line of the given block.
+fn parse_sequence_line(line: &str, block_id: u64, score: Option<f64>) -> io::Result<MafRecord> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let ["s", src, start, size, strand, src_size, text] = fields[..] else {
+        return Err(invalid_data(line));
+    };
+    if strand != "+" && strand != "-" {
+        return Err(invalid_data(line));
+    }
+    Ok(MafRecord {
+        block_id,
+        score,
+        src: src.to_string(),
+        start: parse(start, line)?,
+        size: parse(size, line)?,
+        strand: strand.to_string(),
+        src_size: parse(src_size, line)?,
+        text: text.to_string(),
+        quality: None,
+    })
+}
+
+/// The sequence lines of the alignment blocks read from lines of text.
+struct MafRecords<I> {
+    lines: I,
+    /// The index of the current block, or `None` before the first `a` line.
+    block_id: Option<u64>,
+    score: Option<f64>,
+    /// The last sequence line, held back until it is known whether a `q` line follows.
+    pending: Option<MafRecord>,
+}
+
+impl<I> MafRecords<I> {
+    fn new(lines: I) -> Self {
+        Self {
+            lines,
+            block_id: None,
+            score: None,
+            pending: None,
+        }
+    }
+}
+
+impl<I> Iterator for MafRecords<I>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    type Item = io::Result<MafRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(e)),
+                None => return self.pending.take().map(Ok),
+            };
+            let line = line.trim();
+            match line.split_whitespace().next() {
+                Some("a") => {
+                    self.block_id = Some(self.block_id.map_or(0, |id| id + 1));
+                    self.score = match parse_score(line) {
+                        Ok(score) => score,
+                        Err(e) => return Some(Err(e)),
+                    };
+                }
+                Some("s") => {
+                    let Some(block_id) = self.block_id else {
+                        return Some(Err(invalid_data(line)));
+                    };
+                    let record = match parse_sequence_line(line, block_id, self.score) {
+                        Ok(record) => record,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    if let Some(pending) = self.pending.replace(record) {
+                        return Some(Ok(pending));
+                    }
+                }
+                Some("q") => {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    match (&fields[..], self.pending.as_mut()) {
+                        (["q", src, quality], Some(pending)) if pending.src == *src => {
+                            pending.quality = Some(quality.to_string());
+                        }
+                        _ => return Some(Err(invalid_data(line))),
+                    }
+                }
+                // Comments and the `##maf` header, blank lines ending a block, and `i` and `e`
+                // lines.
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A MAF reader.
+pub struct MafReader<R> {
+    reader: R,
+    options: MafOptions,
+    path: Option<PathBuf>,
+}
+
+impl MafReader<Box<dyn BufRead>> {
+    /// Creates a MAF reader from a given file path.
+    ///
+    /// Gzip- and BGZF-compressed files are decompressed transparently.
+    pub fn new_from_path(path: &str) -> io::Result<Self> {
+        let file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let mut reader = Self::new(decompress(file)?)?;
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> MafReader<R> {
+    /// Creates a MAF reader.
+    pub fn new(reader: R) -> io::Result<Self> {
+        Ok(Self {
+            reader,
+            options: MafOptions::default(),
+            path: None,
+        })
+    }
+
+    /// Sets the options used when converting records.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::maf::{MafOptions, MafReader};
+    ///
+    /// let options = MafOptions {
+    ///     include_quality: Some(true),
+    /// };
+    /// let mut reader = MafReader::new_from_path("sample.maf")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// ```
+    pub fn with_options(mut self, options: MafOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn records(&mut self) -> impl Iterator<Item = Result<MafRecord, ArrowError>> + '_ {
+        MafRecords::new((&mut self.reader).lines())
+            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())))
+    }
+
+    /// Returns the fields of the batches returned by [`MafReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(MafBatchBuilder::new(0, &self.options))
+    }
+
+    /// Returns the sequence lines of all alignment blocks as Apache Arrow IPC.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::maf::MafReader;
+    ///
+    /// let mut reader = MafReader::new_from_path("sample.maf").unwrap();
+    /// let ipc = reader.records_to_ipc().unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = MafBatchBuilder::new(1024, &self.options).with_metadata(provenance(
+            "maf",
+            self.path.as_deref(),
+            &[],
+        ));
+        write_ipc_err(self.records(), batch_builder)
+    }
+
+    /// Returns the number of sequence lines, without converting them to Arrow.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::maf::MafReader;
+    ///
+    /// let mut reader = MafReader::new_from_path("sample.maf").unwrap();
+    /// let n = reader.count().unwrap();
+    /// ```
+    pub fn count(&mut self) -> Result<usize, ArrowError> {
+        count_err(self.records())
+    }
+}
+
+struct MafBatchBuilder {
+    block_id: UInt64Builder,
+    score: Float64Builder,
+    src: StringBuilder,
+    start: UInt64Builder,
+    size: UInt64Builder,
+    strand: StringBuilder,
+    src_size: UInt64Builder,
+    text: StringBuilder,
+    quality: Option<StringBuilder>,
+}
+
+impl MafBatchBuilder {
+    fn new(capacity: usize, options: &MafOptions) -> Self {
+        Self {
+            block_id: UInt64Builder::with_capacity(capacity),
+            score: Float64Builder::with_capacity(capacity),
+            src: StringBuilder::with_capacity(capacity, 0),
+            start: UInt64Builder::with_capacity(capacity),
+            size: UInt64Builder::with_capacity(capacity),
+            strand: StringBuilder::with_capacity(capacity, capacity),
+            src_size: UInt64Builder::with_capacity(capacity),
+            text: StringBuilder::with_capacity(capacity, 0),
+            quality: options
+                .include_quality
+                .unwrap_or(false)
+                .then(|| StringBuilder::with_capacity(capacity, 0)),
+        }
+    }
+}
+
+impl BatchBuilder for MafBatchBuilder {
+    type Record<'a> = &'a MafRecord;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        self.block_id.append_value(record.block_id);
+        self.score.append_option(record.score);
+        self.src.append_value(&record.src);
+        self.start.append_value(record.start);
+        self.size.append_value(record.size);
+        self.strand.append_value(&record.strand);
+        self.src_size.append_value(record.src_size);
+        self.text.append_value(&record.text);
+        if let Some(builder) = self.quality.as_mut() {
+            builder.append_option(record.quality.as_ref());
+        }
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let mut columns = vec![
+            ("block_id", Arc::new(self.block_id.finish()) as ArrayRef),
+            ("score", Arc::new(self.score.finish()) as ArrayRef),
+            ("src", Arc::new(self.src.finish()) as ArrayRef),
+            ("start", Arc::new(self.start.finish()) as ArrayRef),
+            ("size", Arc::new(self.size.finish()) as ArrayRef),
+            ("strand", Arc::new(self.strand.finish()) as ArrayRef),
+            ("src_size", Arc::new(self.src_size.finish()) as ArrayRef),
+            ("text", Arc::new(self.text.finish()) as ArrayRef),
+        ];
+        if let Some(builder) = self.quality.as_mut() {
+            columns.push(("quality", Arc::new(builder.finish()) as ArrayRef));
+        }
+        RecordBatch::try_from_iter(columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Float64Array, StringArray, UInt64Array};
+    use arrow::ipc::reader::FileReader;
+
+    fn read_record_batch(options: MafOptions) -> RecordBatch {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.maf");
+        let mut reader = MafReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let ipc = reader.records_to_ipc().unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    fn column<T: 'static + Clone>(record_batch: &RecordBatch, name: &str) -> T {
+        record_batch
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<T>()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_read_all() {
+        let record_batch = read_record_batch(MafOptions::default());
+        assert_eq!(record_batch.num_rows(), 5);
+        let block_id: UInt64Array = column(&record_batch, "block_id");
+        assert_eq!(block_id.values(), &[0, 0, 0, 1, 1]);
+        let score: Float64Array = column(&record_batch, "score");
+        assert_eq!(
+            score.iter().collect::<Vec<_>>()[2..],
+            [Some(23262.0), None, None]
+        );
+        let src: StringArray = column(&record_batch, "src");
+        assert_eq!(src.value(1), "panTro1.chr6");
+        let start: UInt64Array = column(&record_batch, "start");
+        assert_eq!(start.value(2), 116834);
+        let strand: StringArray = column(&record_batch, "strand");
+        assert_eq!(strand.value(2), "-");
+        let text: StringArray = column(&record_batch, "text");
+        assert_eq!(text.value(2), "AAA-GGGAAT");
+        assert!(record_batch.column_by_name("quality").is_none());
+
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.maf");
+        let mut reader = MafReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        assert_eq!(reader.count().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_quality() {
+        let options = MafOptions {
+            include_quality: Some(true),
+        };
+        let record_batch = read_record_batch(options);
+        let quality: StringArray = column(&record_batch, "quality");
+        assert_eq!(quality.null_count(), 3);
+        assert_eq!(quality.value(1), "99999-9999");
+        assert_eq!(quality.value(4), "9999999999");
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let read = |data: &str| MafReader::new(data.as_bytes()).unwrap().records_to_ipc();
+        // A sequence line outside a block.
+        assert!(read("s hg18.chr7 0 3 + 100 ACG\n").is_err());
+        assert!(read("a score=1\ns hg18.chr7 0 3 ? 100 ACG\n").is_err());
+        // A quality line for another sequence.
+        assert!(read("a\ns hg18.chr7 0 3 + 100 ACG\nq mm4.chr6 999\n").is_err());
+        assert!(read("a score=x\n").is_err());
+    }
+}
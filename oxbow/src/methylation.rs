@@ -0,0 +1,234 @@
+//! CpG methylation calls of bisulfite-converted reads.
+//!
+//! Bisulfite treatment converts unmethylated cytosines to uracil, read as thymine, while
+//! methylated cytosines stay. A read from the reference strand therefore shows a `C` at a
+//! methylated CpG site and a `T` at an unmethylated one (C to T). A read from the opposite strand
+//! shows the same on the `G` of the site, as `G` or `A` (G to A), since its bases are stored in
+//! reference orientation.
+use std::io;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Int32Builder};
+use noodles::sam;
+use noodles::sam::record::cigar::op::Kind;
+use noodles::sam::record::data::field::{Tag, Value};
+
+use crate::fasta::FastaReader;
+
+/// The `XG` tag of Bismark, with the conversion of each read.
+const BISULFITE_STRAND: Tag = match Tag::new(*b"XG") {
+    Some(tag) => tag,
+    None => unreachable!(),
+};
+
+/// The conversion seen in a bisulfite read, relative to the reference strand.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Conversion {
+    /// A read from the reference strand, with C to T conversions.
+    CToT,
+    /// A read from the opposite strand, with G to A conversions.
+    GToA,
+}
+
+impl Conversion {
+    /// Returns the conversion of a read, from its `XG` tag (`CT` or `GA`, as set by Bismark), or
+    /// else assuming a directional library: single-end forward reads and forward first or
+    /// reverse second reads of pairs are from the reference strand.
+    pub fn of(record: &sam::alignment::Record) -> Self {
+        if let Some(Value::String(xg)) = record.data().get(&BISULFITE_STRAND) {
+            match xg.as_str() {
+                "CT" => return Self::CToT,
+                "GA" => return Self::GToA,
+                _ => {}
+            }
+        }
+        let flags = record.flags();
+        let second = flags.is_segmented() && flags.is_last_segment();
+        if flags.is_reverse_complemented() != second {
+            Self::GToA
+        } else {
+            Self::CToT
+        }
+    }
+}
+
+/// Returns the numbers of methylated and unmethylated CpG sites of a read, given the bases of the
+/// reference sequence it is aligned to.
+///
+/// Only aligned bases (`M`, `=` and `X` operations) are called. Other bases than the expected
+/// pair at a site, e.g. a `G` where the reference has the `C` of a site in a C to T read, are not
+/// counted.
+///
+/// # Examples
+///
+/// ```
+/// use oxbow::methylation::{call_cpg, Conversion};
+/// use noodles::sam;
+///
+/// let header = "@SQ\tSN:sq0\tLN:6\n".parse().unwrap();
+/// let data = b"r0\t0\tsq0\t1\t60\t6M\t*\t0\t0\tACGTTG\t*\n";
+/// let mut reader = sam::Reader::new(&data[..]);
+/// let record = reader.records(&header).next().unwrap().unwrap();
+/// assert_eq!(call_cpg(b"ACGTCG", &record, Conversion::CToT), (1, 1));
+/// ```
+pub fn call_cpg(
+    reference: &[u8],
+    record: &sam::alignment::Record,
+    conversion: Conversion,
+) -> (i32, i32) {
+    let Some(start) = record.alignment_start() else {
+        return (0, 0);
+    };
+    let base = |i: usize| reference.get(i).map(u8::to_ascii_uppercase);
+    let sequence = record.sequence().as_ref();
+    let (mut methylated, mut unmethylated) = (0, 0);
+    // The 0-based reference and read positions.
+    let (mut reference_position, mut read_position) = (start.get() - 1, 0);
+    for op in record.cigar().iter() {
+        let len = op.len();
+        match op.kind() {
+            Kind::Match | Kind::SequenceMatch | Kind::SequenceMismatch => {
+                for i in 0..len {
+                    let position = reference_position + i;
+                    let Some(read_base) = sequence.get(read_position + i) else {
+                        break;
+                    };
+                    let read_base = u8::from(*read_base);
+                    let call = match conversion {
+                        Conversion::CToT if base(position) == Some(b'C') => {
+                            (base(position + 1) == Some(b'G')).then_some((b'C', b'T'))
+                        }
+                        Conversion::GToA if base(position) == Some(b'G') && position > 0 => {
+                            (base(position - 1) == Some(b'C')).then_some((b'G', b'A'))
+                        }
+                        _ => None,
+                    };
+                    match call {
+                        Some((kept, _)) if read_base == kept => methylated += 1,
+                        Some((_, converted)) if read_base == converted => unmethylated += 1,
+                        _ => {}
+                    }
+                }
+                reference_position += len;
+                read_position += len;
+            }
+            Kind::Deletion | Kind::Skip => reference_position += len,
+            Kind::Insertion | Kind::SoftClip => read_position += len,
+            Kind::HardClip | Kind::Pad => {}
+        }
+    }
+    (methylated, unmethylated)
+}
+
+/// Builds the `meth_cpg` and `unmeth_cpg` columns (Int32) of alignment records, reading their
+/// reference sequences from an indexed FASTA file.
+pub(crate) struct MethylationBuilder {
+    reference: FastaReader,
+    /// The names of the reference sequences of the header.
+    names: Vec<String>,
+    /// The index of the last reference sequence read, and its bases if the FASTA file has it.
+    cached: Option<(usize, Option<Vec<u8>>)>,
+    methylated: Int32Builder,
+    unmethylated: Int32Builder,
+}
+
+impl MethylationBuilder {
+    pub fn new(path: &str, header: &sam::Header) -> io::Result<Self> {
+        let reference = FastaReader::new(path)?;
+        if reference.index().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid methylation reference: {}. Expected an indexed, uncompressed or BGZF-compressed FASTA file.",
+                    path
+                ),
+            ));
+        }
+        Ok(Self {
+            reference,
+            names: header
+                .reference_sequences()
+                .keys()
+                .map(|name| name.to_string())
+                .collect(),
+            cached: None,
+            methylated: Int32Builder::new(),
+            unmethylated: Int32Builder::new(),
+        })
+    }
+
+    /// Returns the bases of the reference sequence at `id` in the header, reading them unless
+    /// they are those of the previous record.
+    fn reference(&mut self, id: usize) -> Option<&[u8]> {
+        if self.cached.as_ref().map(|(cached, _)| *cached) != Some(id) {
+            // A reference sequence that can't be read gets nulls, like one missing from the file.
+            let sequence = self
+                .names
+                .get(id)
+                .and_then(|name| self.reference.sequence(name).ok().flatten());
+            self.cached = Some((id, sequence));
+        }
+        self.cached.as_ref()?.1.as_deref()
+    }
+
+    pub fn push(&mut self, record: &sam::alignment::Record) {
+        let calls = match record.reference_sequence_id() {
+            Some(id) if !record.flags().is_unmapped() => {
+                let conversion = Conversion::of(record);
+                self.reference(id)
+                    .map(|reference| call_cpg(reference, record, conversion))
+            }
+            _ => None,
+        };
+        self.methylated.append_option(calls.map(|(m, _)| m));
+        self.unmethylated.append_option(calls.map(|(_, u)| u));
+    }
+
+    pub fn finish(&mut self) -> [(&'static str, ArrayRef); 2] {
+        [
+            ("meth_cpg", Arc::new(self.methylated.finish()) as ArrayRef),
+            (
+                "unmeth_cpg",
+                Arc::new(self.unmethylated.finish()) as ArrayRef,
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(line: &str) -> sam::alignment::Record {
+        let header = "@SQ\tSN:sq0\tLN:100\n".parse().unwrap();
+        let mut reader = sam::Reader::new(line.as_bytes());
+        reader.records(&header).next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_conversion() {
+        let conversion = |flag, tags| {
+            let line = format!("r0\t{}\tsq0\t1\t60\t4M\t*\t0\t0\tACGT\t*{}", flag, tags);
+            Conversion::of(&record(&line))
+        };
+        assert_eq!(conversion(0, ""), Conversion::CToT);
+        assert_eq!(conversion(16, ""), Conversion::GToA);
+        // Second reads of pairs are from the opposite strand of their orientation.
+        assert_eq!(conversion(129, ""), Conversion::GToA);
+        assert_eq!(conversion(145, ""), Conversion::CToT);
+        assert_eq!(conversion(16, "\tXG:Z:CT"), Conversion::CToT);
+        assert_eq!(conversion(0, "\tXG:Z:GA"), Conversion::GToA);
+    }
+
+    #[test]
+    fn test_call_cpg() {
+        let reference = b"acgtcgacg";
+        let forward = record("r0\t0\tsq0\t1\t60\t9M\t*\t0\t0\tACGTTGATG\t*");
+        assert_eq!(call_cpg(reference, &forward, Conversion::CToT), (1, 2));
+        let reverse = record("r0\t16\tsq0\t1\t60\t9M\t*\t0\t0\tACATCGACA\t*");
+        assert_eq!(call_cpg(reference, &reverse, Conversion::GToA), (1, 2));
+        // Inserted bases are skipped, and other bases than the pair of a site aren't counted.
+        let insertion = record("r0\t0\tsq0\t2\t60\t3M2I2M\t*\t0\t0\tCGTCCAG\t*");
+        assert_eq!(call_cpg(reference, &insertion, Conversion::CToT), (1, 0));
+    }
+}
@@ -0,0 +1,211 @@
+//! Renaming the columns of the IPC files returned by the readers, e.g. to the names another
+//! ecosystem expects.
+//!
+//! The readers name their columns after each format, e.g. `chrom` and `start` in BED but `rname`
+//! and `pos` in SAM. [`ColumnNaming`] presets map these names across formats, and
+//! [`rename_columns`] applies a preset or any other map of names to every batch of a file.
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::datatypes::{Field, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+
+use crate::stats::{batch_stats, write_column_stats, ColumnStats};
+
+/// A preset of column names.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ColumnNaming {
+    /// The names of the readers, e.g. `chrom` in VCF and BED and `seqid` in GFF.
+    #[default]
+    OxbowDefault,
+    /// The names of SAM for every format: the reference sequence is `rname` and the start
+    /// position `pos`, alongside `mapq` and the other alignment columns.
+    SamNative,
+}
+
+impl ColumnNaming {
+    /// Returns the renames of this preset, from the names of the readers to those of the preset.
+    pub fn renames(self) -> HashMap<String, String> {
+        let renames: &[(&str, &str)] = match self {
+            Self::OxbowDefault => &[],
+            Self::SamNative => &[("chrom", "rname"), ("seqid", "rname"), ("start", "pos")],
+        };
+        renames
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect()
+    }
+}
+
+impl FromStr for ColumnNaming {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "oxbow_default" => Ok(Self::OxbowDefault),
+            "sam_native" => Ok(Self::SamNative),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid column naming: {}. Expected oxbow_default or sam_native.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// Returns `schema` with its columns renamed after `renames`. Names that aren't columns of the
+/// schema are ignored, so that the same renames apply to several formats.
+///
+/// Fails if two columns would get the same name, e.g. renaming `chrom` to `end` in BED.
+pub fn rename_schema(
+    schema: &Schema,
+    renames: &HashMap<String, String>,
+) -> Result<Schema, ArrowError> {
+    let mut names = HashSet::new();
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let name = renames.get(field.name()).unwrap_or(field.name());
+            if !names.insert(name.as_str()) {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "Invalid renames: two columns would be named {}. Expected unique names.",
+                    name
+                )));
+            }
+            Ok(Field::clone(field).with_name(name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Schema::new_with_metadata(fields, schema.metadata().clone()))
+}
+
+/// Renames the columns of Arrow IPC file bytes, as returned by the readers, after `renames`,
+/// like [`rename_schema`].
+///
+/// The batch statistics of the file (see [`crate::stats`]) are recorded again under the new
+/// names, so that renamed coordinate columns keep them.
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::bed::BedReader;
+/// use oxbow::naming::{rename_columns, ColumnNaming};
+///
+/// let mut reader = BedReader::new_from_path("sample.bed").unwrap();
+/// let ipc = reader.records_to_ipc(None).unwrap();
+/// let mut renames = ColumnNaming::SamNative.renames();
+/// renames.insert("name".to_string(), "qname".to_string());
+/// let ipc = rename_columns(&ipc, &renames).unwrap();
+/// ```
+pub fn rename_columns(
+    ipc: &[u8],
+    renames: &HashMap<String, String>,
+) -> Result<Vec<u8>, ArrowError> {
+    let reader = FileReader::try_new(Cursor::new(ipc), None)?;
+    let schema = Arc::new(rename_schema(&reader.schema(), renames)?);
+    let mut writer = FileWriter::try_new(Vec::new(), &schema)?;
+    for (index, batch) in reader.enumerate() {
+        let batch = batch?;
+        // The statistics are taken under the names of the readers, then recorded under the new
+        // ones, so that renamed coordinate columns keep them.
+        let stats = batch_stats(&batch).into_iter().map(|stats| ColumnStats {
+            name: renames.get(&stats.name).unwrap_or(&stats.name).clone(),
+            ..stats
+        });
+        write_column_stats(&mut writer, index, stats);
+        let batch = RecordBatch::try_new(schema.clone(), batch.columns().to_vec())?;
+        writer.write(&batch)?;
+    }
+    writer.finish()?;
+    writer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bed::BedReader;
+    use crate::stats::{read_stats, StatValue};
+
+    fn read_bed() -> Vec<u8> {
+        let data = "chr1\t10\t20\tfeature0\nchr2\t30\t40\tfeature1\n";
+        let mut reader = BedReader::new(data.as_bytes()).unwrap();
+        reader.records_to_ipc(None).unwrap()
+    }
+
+    fn read_batch(ipc: &[u8]) -> RecordBatch {
+        let mut reader = FileReader::try_new(Cursor::new(ipc), None).unwrap();
+        reader.next().unwrap().unwrap()
+    }
+
+    fn names(batch: &RecordBatch) -> Vec<String> {
+        let schema = batch.schema();
+        schema.fields().iter().map(|f| f.name().clone()).collect()
+    }
+
+    #[test]
+    fn test_sam_native() {
+        let ipc = read_bed();
+        let expected = read_batch(&ipc);
+        let renamed =
+            read_batch(&rename_columns(&ipc, &ColumnNaming::SamNative.renames()).unwrap());
+        assert_eq!(names(&renamed)[..3], ["rname", "pos", "end"]);
+        assert_eq!(renamed.columns(), expected.columns());
+        assert_eq!(renamed.schema().metadata(), expected.schema().metadata());
+
+        let stats = read_stats(Cursor::new(
+            rename_columns(&ipc, &ColumnNaming::SamNative.renames()).unwrap(),
+        ))
+        .unwrap();
+        let names: Vec<_> = stats[0].iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["rname", "pos", "end"]);
+        assert_eq!(stats[0][1].min, StatValue::Int(10));
+
+        let unchanged = rename_columns(&ipc, &ColumnNaming::OxbowDefault.renames()).unwrap();
+        assert_eq!(read_batch(&unchanged), expected);
+        assert_eq!(
+            "sam_native".parse::<ColumnNaming>().unwrap(),
+            ColumnNaming::SamNative
+        );
+        assert!("sam".parse::<ColumnNaming>().is_err());
+    }
+
+    #[test]
+    fn test_custom_renames() {
+        let ipc = read_bed();
+        let renames = HashMap::from([
+            ("chrom".to_string(), "contig".to_string()),
+            ("missing".to_string(), "ignored".to_string()),
+        ]);
+        let renamed = rename_columns(&ipc, &renames).unwrap();
+        assert_eq!(
+            names(&read_batch(&renamed))[..3],
+            ["contig", "start", "end"]
+        );
+        // The renamed reference sequence column keeps its statistics.
+        let stats = read_stats(Cursor::new(renamed)).unwrap();
+        let stat_names: Vec<_> = stats[0].iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(stat_names, ["contig", "start", "end"]);
+        assert_eq!(stats[0][0].min, StatValue::Utf8("chr1".to_string()));
+        assert_eq!(stats[0][0].max, StatValue::Utf8("chr2".to_string()));
+
+        // Swapping names is allowed, unlike giving two columns the same one.
+        let swap = HashMap::from([
+            ("start".to_string(), "end".to_string()),
+            ("end".to_string(), "start".to_string()),
+        ]);
+        let renamed = read_batch(&rename_columns(&ipc, &swap).unwrap());
+        assert_eq!(names(&renamed)[..3], ["chrom", "end", "start"]);
+
+        let collision = HashMap::from([("chrom".to_string(), "end".to_string())]);
+        let error = rename_columns(&ipc, &collision).unwrap_err();
+        assert!(error.to_string().contains("two columns would be named end"));
+    }
+}
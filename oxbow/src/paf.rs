@@ -0,0 +1,486 @@
+//! A reader for the Pairwise mApping Format (PAF) written by minimap2 and other aligners.
+//!
+//! Each line becomes a row with the twelve mandatory columns: `query_name`, `query_length`,
+//! `query_start`, `query_end`, `strand`, `target_name`, `target_length`, `target_start`,
+//! `target_end`, `matches`, `alignment_length` and `mapq`. Coordinates are 0-based and half-open.
+//!
+//! Optional `TAG:TYPE:VALUE` fields, such as `tp:A:P` or `NM:i:3`, can be materialized as
+//! columns with [`PafOptions::tags`].
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Float32Builder, Int64Builder, NullArray, StringBuilder, UInt64Builder, UInt8Builder,
+};
+use arrow::datatypes::Fields;
+use arrow::{error::ArrowError, record_batch::RecordBatch};
+
+use crate::batch_builder::{
+    builder_fields, check_dictionary_encode, count_err, provenance, write_ipc_err, BatchBuilder,
+    StringColumnBuilder, BUFFER_SIZE_BYTES,
+};
+use crate::compression::decompress;
+use crate::error_policy::{tolerate, ErrorPolicy, RecordError};
+
+/// A pairwise alignment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PafRecord {
+    pub query_name: String,
+    pub query_length: u64,
+    pub query_start: u64,
+    pub query_end: u64,
+    pub strand: String,
+    pub target_name: String,
+    pub target_length: u64,
+    pub target_start: u64,
+    pub target_end: u64,
+    pub matches: u64,
+    pub alignment_length: u64,
+    pub mapq: u8,
+    /// The optional fields, as written, e.g. `NM:i:3`.
+    pub tags: Vec<String>,
+}
+
+/// Options for reading PAF files.
+#[derive(Clone, Debug, Default)]
+pub struct PafOptions {
+    /// Optional tags to materialize as dedicated columns, e.g. `["tp", "cm", "NM"]`.
+    ///
+    /// Each tag becomes a column named after it, appended after `mapq`, in the given order.
+    /// Integer (`i`) values are emitted as Int64, float (`f`) values as Float32, and all others
+    /// as Utf8. Records without the tag get a null. A tag that is never seen yields a column of
+    /// Arrow type Null.
+    pub tags: Option<Vec<String>>,
+    /// String columns to dictionary-encode, out of `query_name`, `strand` and `target_name`.
+    pub dictionary_encode: Option<Vec<String>>,
+    /// What to do with records that fail to parse. Defaults to [`ErrorPolicy::Fail`].
+    pub on_error: Option<ErrorPolicy>,
+}
+
+fn invalid_data(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid line: {}", line),
+    )
+}
+
+fn parse<T: std::str::FromStr>(s: &str, line: &str) -> io::Result<T> {
+    s.parse().map_err(|_| invalid_data(line))
+}
+
+fn parse_record(line: &str) -> io::Result<PafRecord> {
+    let mut fields = line.split('\t');
+    let mut next = || fields.next().ok_or_else(|| invalid_data(line));
+    let record = PafRecord {
+        query_name: next()?.to_string(),
+        query_length: parse(next()?, line)?,
+        query_start: parse(next()?, line)?,
+        query_end: parse(next()?, line)?,
+        strand: match next()? {
+            strand @ ("+" | "-") => strand.to_string(),
+            _ => return Err(invalid_data(line)),
+        },
+        target_name: next()?.to_string(),
+        target_length: parse(next()?, line)?,
+        target_start: parse(next()?, line)?,
+        target_end: parse(next()?, line)?,
+        matches: parse(next()?, line)?,
+        alignment_length: parse(next()?, line)?,
+        mapq: parse(next()?, line)?,
+        tags: Vec::new(),
+    };
+    let tags = fields.map(str::to_string).collect();
+    Ok(PafRecord { tags, ..record })
+}
+
+/// A PAF reader.
+pub struct PafReader<R> {
+    reader: R,
+    options: PafOptions,
+    errors: Vec<RecordError>,
+    path: Option<PathBuf>,
+}
+
+impl PafReader<Box<dyn BufRead>> {
+    /// Creates a PAF reader from a given file path.
+    ///
+    /// Gzip- and BGZF-compressed files are decompressed transparently.
+    pub fn new_from_path(path: &str) -> io::Result<Self> {
+        let file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let mut reader = Self::new(decompress(file)?)?;
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> PafReader<R> {
+    /// Creates a PAF reader.
+    pub fn new(reader: R) -> io::Result<Self> {
+        Ok(Self {
+            reader,
+            options: PafOptions::default(),
+            errors: Vec::new(),
+            path: None,
+        })
+    }
+
+    /// Sets the options used when converting records.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::paf::{PafOptions, PafReader};
+    ///
+    /// let options = PafOptions {
+    ///     tags: Some(vec!["tp".to_string(), "NM".to_string()]),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = PafReader::new_from_path("sample.paf")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// ```
+    pub fn with_options(mut self, options: PafOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the records skipped by the last scan under [`ErrorPolicy::SkipAndCollect`].
+    pub fn errors(&self) -> &[RecordError] {
+        &self.errors
+    }
+
+    fn records(&mut self) -> impl Iterator<Item = Result<PafRecord, ArrowError>> + '_ {
+        let records = (&mut self.reader)
+            .lines()
+            .enumerate()
+            .filter(|(_, i)| i.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|(i, line)| (i + 1, line.and_then(|line| parse_record(&line))));
+        tolerate(
+            records,
+            self.options.on_error.unwrap_or_default(),
+            &mut self.errors,
+        )
+        .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())))
+    }
+
+    /// Returns the fields of the batches returned by [`PafReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    ///
+    /// Tag columns, which are typed from the records, are given as Null.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(PafBatchBuilder::new(0, &self.options)?)
+    }
+
+    /// Returns all records as Apache Arrow IPC.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::paf::PafReader;
+    ///
+    /// let mut reader = PafReader::new_from_path("sample.paf").unwrap();
+    /// let ipc = reader.records_to_ipc().unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = PafBatchBuilder::new(1024, &self.options)?.with_metadata(provenance(
+            "paf",
+            self.path.as_deref(),
+            &[],
+        ));
+        write_ipc_err(self.records(), batch_builder)
+    }
+
+    /// Returns the number of records, without converting them to Arrow.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::paf::PafReader;
+    ///
+    /// let mut reader = PafReader::new_from_path("sample.paf").unwrap();
+    /// let n = reader.count().unwrap();
+    /// ```
+    pub fn count(&mut self) -> Result<usize, ArrowError> {
+        count_err(self.records())
+    }
+}
+
+/// A typed column builder for a selected tag.
+enum TagColumnBuilder {
+    Int(Int64Builder),
+    Float(Float32Builder),
+    String(StringBuilder),
+}
+
+impl TagColumnBuilder {
+    /// Creates a builder for the type of a value, padded with `nulls` leading nulls.
+    fn new(ty: &str, nulls: usize) -> Self {
+        let mut builder = match ty {
+            "i" => Self::Int(Int64Builder::new()),
+            "f" => Self::Float(Float32Builder::new()),
+            _ => Self::String(StringBuilder::new()),
+        };
+        for _ in 0..nulls {
+            builder.append_null();
+        }
+        builder
+    }
+
+    /// Appends a value, or a null if it does not match the column type.
+    fn append_value(&mut self, value: &str) {
+        match self {
+            Self::Int(builder) => builder.append_option(value.parse().ok()),
+            Self::Float(builder) => builder.append_option(value.parse().ok()),
+            Self::String(builder) => builder.append_value(value),
+        }
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            Self::Int(builder) => builder.append_null(),
+            Self::Float(builder) => builder.append_null(),
+            Self::String(builder) => builder.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Int(builder) => Arc::new(builder.finish()),
+            Self::Float(builder) => Arc::new(builder.finish()),
+            Self::String(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+struct PafBatchBuilder {
+    query_name: StringColumnBuilder,
+    query_length: UInt64Builder,
+    query_start: UInt64Builder,
+    query_end: UInt64Builder,
+    strand: StringColumnBuilder,
+    target_name: StringColumnBuilder,
+    target_length: UInt64Builder,
+    target_start: UInt64Builder,
+    target_end: UInt64Builder,
+    matches: UInt64Builder,
+    alignment_length: UInt64Builder,
+    mapq: UInt8Builder,
+    tags: Vec<String>,
+    tag_builders: Vec<Option<TagColumnBuilder>>,
+    seen: usize,
+}
+
+impl PafBatchBuilder {
+    fn new(capacity: usize, options: &PafOptions) -> Result<Self, ArrowError> {
+        let dictionary_encode = options.dictionary_encode.as_deref();
+        check_dictionary_encode(dictionary_encode, &["query_name", "strand", "target_name"])?;
+        let tags = options.tags.clone().unwrap_or_default();
+        if let Some(tag) = tags.iter().find(|tag| tag.len() != 2) {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "Invalid tag: {}. Tags must be exactly two characters.",
+                tag
+            )));
+        }
+        Ok(Self {
+            query_name: StringColumnBuilder::new("query_name", dictionary_encode),
+            query_length: UInt64Builder::with_capacity(capacity),
+            query_start: UInt64Builder::with_capacity(capacity),
+            query_end: UInt64Builder::with_capacity(capacity),
+            strand: StringColumnBuilder::new("strand", dictionary_encode),
+            target_name: StringColumnBuilder::new("target_name", dictionary_encode),
+            target_length: UInt64Builder::with_capacity(capacity),
+            target_start: UInt64Builder::with_capacity(capacity),
+            target_end: UInt64Builder::with_capacity(capacity),
+            matches: UInt64Builder::with_capacity(capacity),
+            alignment_length: UInt64Builder::with_capacity(capacity),
+            mapq: UInt8Builder::with_capacity(capacity),
+            tag_builders: tags.iter().map(|_| None).collect(),
+            tags,
+            seen: 0,
+        })
+    }
+}
+
+impl BatchBuilder for PafBatchBuilder {
+    type Record<'a> = &'a PafRecord;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        self.query_name.append_value(&record.query_name);
+        self.query_length.append_value(record.query_length);
+        self.query_start.append_value(record.query_start);
+        self.query_end.append_value(record.query_end);
+        self.strand.append_value(&record.strand);
+        self.target_name.append_value(&record.target_name);
+        self.target_length.append_value(record.target_length);
+        self.target_start.append_value(record.target_start);
+        self.target_end.append_value(record.target_end);
+        self.matches.append_value(record.matches);
+        self.alignment_length.append_value(record.alignment_length);
+        self.mapq.append_value(record.mapq);
+        for (tag, builder) in self.tags.iter().zip(self.tag_builders.iter_mut()) {
+            let field = record.tags.iter().find_map(|field| {
+                let (name, rest) = field.split_once(':')?;
+                (name == tag).then(|| rest.split_once(':')).flatten()
+            });
+            match (field, builder) {
+                (Some((_, value)), Some(builder)) => builder.append_value(value),
+                (Some((ty, value)), builder) => {
+                    let builder = builder.insert(TagColumnBuilder::new(ty, self.seen));
+                    builder.append_value(value);
+                }
+                (None, Some(builder)) => builder.append_null(),
+                (None, None) => {}
+            }
+        }
+        self.seen += 1;
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let mut columns = vec![
+            ("query_name".to_string(), self.query_name.finish()),
+            (
+                "query_length".to_string(),
+                Arc::new(self.query_length.finish()) as ArrayRef,
+            ),
+            (
+                "query_start".to_string(),
+                Arc::new(self.query_start.finish()) as ArrayRef,
+            ),
+            (
+                "query_end".to_string(),
+                Arc::new(self.query_end.finish()) as ArrayRef,
+            ),
+            ("strand".to_string(), self.strand.finish()),
+            ("target_name".to_string(), self.target_name.finish()),
+            (
+                "target_length".to_string(),
+                Arc::new(self.target_length.finish()) as ArrayRef,
+            ),
+            (
+                "target_start".to_string(),
+                Arc::new(self.target_start.finish()) as ArrayRef,
+            ),
+            (
+                "target_end".to_string(),
+                Arc::new(self.target_end.finish()) as ArrayRef,
+            ),
+            (
+                "matches".to_string(),
+                Arc::new(self.matches.finish()) as ArrayRef,
+            ),
+            (
+                "alignment_length".to_string(),
+                Arc::new(self.alignment_length.finish()) as ArrayRef,
+            ),
+            ("mapq".to_string(), Arc::new(self.mapq.finish()) as ArrayRef),
+        ];
+        for (tag, builder) in self.tags.iter().zip(self.tag_builders.iter_mut()) {
+            let array = match builder {
+                Some(builder) => builder.finish(),
+                None => Arc::new(NullArray::new(self.seen)) as ArrayRef,
+            };
+            columns.push((tag.clone(), array));
+        }
+        RecordBatch::try_from_iter(columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Float32Array, StringArray, UInt64Array};
+    use arrow::datatypes::DataType;
+    use arrow::ipc::reader::FileReader;
+
+    fn read_record_batch(fixture: &str, options: PafOptions) -> RecordBatch {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures");
+        dir.push(fixture);
+        let mut reader = PafReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let ipc = reader.records_to_ipc().unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_read_all() {
+        let record_batch = read_record_batch("sample.paf", PafOptions::default());
+        assert_eq!(record_batch.num_rows(), 3);
+        assert_eq!(record_batch.num_columns(), 12);
+        let target_start = record_batch
+            .column_by_name("target_start")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(target_start.values(), &[100, 5000, 0]);
+
+        // The gzipped copy reads the same.
+        let gzipped = read_record_batch("sample.paf.gz", PafOptions::default());
+        assert_eq!(gzipped.columns(), record_batch.columns());
+    }
+
+    #[test]
+    fn test_tags() {
+        let options = PafOptions {
+            tags: Some(vec!["tp".into(), "NM".into(), "de".into(), "ZZ".into()]),
+            ..Default::default()
+        };
+        let record_batch = read_record_batch("sample.paf", options);
+        assert_eq!(record_batch.num_columns(), 16);
+        let tp = record_batch.column_by_name("tp").unwrap();
+        let tp = tp.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(tp.value(0), "P");
+        assert_eq!(tp.value(1), "S");
+        let nm = record_batch.column_by_name("NM").unwrap();
+        assert_eq!(nm.data_type(), &DataType::Int64);
+        assert_eq!(nm.null_count(), 1);
+        let de = record_batch.column_by_name("de").unwrap();
+        let de = de.as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(de.value(0), 0.0123);
+        let zz = record_batch.column_by_name("ZZ").unwrap();
+        assert_eq!(zz.data_type(), &DataType::Null);
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let read = |data: &str| PafReader::new(data.as_bytes()).unwrap().records_to_ipc();
+        assert!(read("q1\t100\t0\t50\t+\tt1\t1000\t0\t50\t45\n").is_err());
+        assert!(read("q1\t100\t0\t50\t.\tt1\t1000\t0\t50\t45\t50\t60\n").is_err());
+        let options = PafOptions {
+            tags: Some(vec!["NMX".into()]),
+            ..Default::default()
+        };
+        let mut reader = PafReader::new(&b""[..]).unwrap().with_options(options);
+        assert!(reader.records_to_ipc().is_err());
+    }
+
+    #[test]
+    fn test_error_policy() {
+        let data = "q1\t100\t0\t50\t+\tt1\t1000\t0\t50\t45\t50\t60\n\
+            q2\t100\t0\t50\t+\tt1\t1000\t0\t50\n\
+            \n\
+            q3\t100\t0\t50\t-\tt1\t1000\tzero\t50\t45\t50\t60\n\
+            q4\t100\t0\t50\t-\tt1\t1000\t0\t50\t45\t50\t60\n";
+        let reader = |on_error| {
+            let options = PafOptions {
+                on_error: Some(on_error),
+                ..Default::default()
+            };
+            PafReader::new(data.as_bytes())
+                .unwrap()
+                .with_options(options)
+        };
+        assert!(reader(ErrorPolicy::Fail).count().is_err());
+        assert_eq!(reader(ErrorPolicy::Skip).count().unwrap(), 2);
+
+        let mut reader = reader(ErrorPolicy::SkipAndCollect);
+        reader.records_to_ipc().unwrap();
+        let lines: Vec<_> = reader.errors().iter().map(|error| error.line).collect();
+        assert_eq!(lines, [2, 4]);
+    }
+}
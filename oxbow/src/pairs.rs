@@ -0,0 +1,245 @@
+//! Pairing the mates of paired-end alignment records.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanBuilder, GenericStringBuilder, Int32Builder, StringArray, UInt16Builder,
+    UInt8Builder,
+};
+use arrow::{error::ArrowError, record_batch::RecordBatch};
+use noodles::sam;
+
+use crate::batch_builder::{BatchBuilder, ReferenceColumnBuilder};
+use crate::coordinates::CoordinateSystem;
+
+/// The number of reads that may wait for their mates by default.
+pub(crate) const DEFAULT_MAX_BUFFER: usize = 1_000_000;
+
+/// The first and last segments of a template. Either is missing for an orphan.
+pub(crate) struct Pair {
+    pub read1: Option<sam::alignment::Record>,
+    pub read2: Option<sam::alignment::Record>,
+}
+
+impl Pair {
+    fn orphan(record: sam::alignment::Record) -> Self {
+        if record.flags().is_last_segment() {
+            Self {
+                read1: None,
+                read2: Some(record),
+            }
+        } else {
+            Self {
+                read1: Some(record),
+                read2: None,
+            }
+        }
+    }
+
+    fn mates(a: sam::alignment::Record, b: sam::alignment::Record) -> Self {
+        let (read1, read2) = if a.flags().is_last_segment() {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        Self {
+            read1: Some(read1),
+            read2: Some(read2),
+        }
+    }
+}
+
+/// Buffers the reads of a stream by name until their mates arrive.
+///
+/// Secondary and supplementary alignments are skipped, and unpaired or unnamed reads are
+/// returned as orphans right away. Every read waiting for its mate stays in memory, so at most
+/// `max_buffer` of them are held.
+pub(crate) struct Pairer {
+    pending: HashMap<Vec<u8>, (usize, sam::alignment::Record)>,
+    seen: usize,
+    max_buffer: usize,
+}
+
+impl Pairer {
+    pub fn new(max_buffer: usize) -> Self {
+        Self {
+            pending: HashMap::new(),
+            seen: 0,
+            max_buffer,
+        }
+    }
+
+    /// Adds a read, returning its pair once both mates have been seen.
+    pub fn push(&mut self, record: sam::alignment::Record) -> Result<Option<Pair>, ArrowError> {
+        let flags = record.flags();
+        if flags.is_secondary() || flags.is_supplementary() {
+            return Ok(None);
+        }
+        let name = match record.read_name() {
+            Some(name) if flags.is_segmented() => AsRef::<[u8]>::as_ref(name).to_vec(),
+            _ => return Ok(Some(Pair::orphan(record))),
+        };
+        if let Some((_, mate)) = self.pending.remove(&name) {
+            return Ok(Some(Pair::mates(mate, record)));
+        }
+        if self.pending.len() >= self.max_buffer {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "More than {} reads are waiting for their mates. Raise the buffer size or query a smaller region.",
+                self.max_buffer
+            )));
+        }
+        self.pending.insert(name, (self.seen, record));
+        self.seen += 1;
+        Ok(None)
+    }
+
+    /// Returns the reads whose mates were never seen, in the order they were added.
+    pub fn into_orphans(self) -> impl Iterator<Item = Pair> {
+        let mut orphans: Vec<_> = self.pending.into_values().collect();
+        orphans.sort_by_key(|(i, _)| *i);
+        orphans.into_iter().map(|(_, record)| Pair::orphan(record))
+    }
+}
+
+/// The columns of one mate.
+struct MateBuilder {
+    rname: ReferenceColumnBuilder,
+    pos: Int32Builder,
+    end: Int32Builder,
+    mapq: UInt8Builder,
+    flag: UInt16Builder,
+}
+
+impl MateBuilder {
+    fn new(capacity: usize, names: StringArray, as_index: bool) -> Result<Self, ArrowError> {
+        Ok(Self {
+            rname: ReferenceColumnBuilder::new(capacity, names, as_index)?,
+            pos: Int32Builder::with_capacity(capacity),
+            end: Int32Builder::with_capacity(capacity),
+            mapq: UInt8Builder::with_capacity(capacity),
+            flag: UInt16Builder::with_capacity(capacity),
+        })
+    }
+
+    fn push(&mut self, record: Option<&sam::alignment::Record>, start: impl Fn(usize) -> i32) {
+        self.rname
+            .append(record.and_then(|r| r.reference_sequence_id()));
+        self.pos.append_option(
+            record
+                .and_then(|r| r.alignment_start())
+                .map(|p| start(p.get())),
+        );
+        self.end.append_option(
+            record
+                .and_then(|r| r.alignment_end())
+                .map(|p| p.get() as i32),
+        );
+        self.mapq
+            .append_option(record.and_then(|r| r.mapping_quality()).map(|q| q.get()));
+        self.flag.append_option(record.map(|r| r.flags().bits()));
+    }
+
+    fn finish(&mut self, suffix: char) -> Vec<(String, ArrayRef)> {
+        vec![
+            (format!("rname{suffix}"), self.rname.finish()),
+            (
+                format!("pos{suffix}"),
+                Arc::new(self.pos.finish()) as ArrayRef,
+            ),
+            (format!("end{suffix}"), Arc::new(self.end.finish())),
+            (format!("mapq{suffix}"), Arc::new(self.mapq.finish())),
+            (format!("flag{suffix}"), Arc::new(self.flag.finish())),
+        ]
+    }
+}
+
+/// Returns the number of reference bases from the leftmost start to the rightmost end of two
+/// mates aligned to the same reference sequence.
+fn fragment_length(read1: &sam::alignment::Record, read2: &sam::alignment::Record) -> Option<i32> {
+    if read1.reference_sequence_id()? != read2.reference_sequence_id()? {
+        return None;
+    }
+    let start = read1.alignment_start()?.min(read2.alignment_start()?);
+    let end = read1.alignment_end()?.max(read2.alignment_end()?);
+    Some((end.get() - start.get() + 1) as i32)
+}
+
+pub(crate) struct PairBatchBuilder {
+    qname: GenericStringBuilder<i32>,
+    read1: MateBuilder,
+    read2: MateBuilder,
+    tlen: Int32Builder,
+    fragment_length: Int32Builder,
+    proper_pair: BooleanBuilder,
+    coordinate_system: CoordinateSystem,
+}
+
+impl PairBatchBuilder {
+    pub fn new(
+        capacity: usize,
+        header: &sam::Header,
+        as_index: bool,
+        coordinate_system: CoordinateSystem,
+    ) -> Result<Self, ArrowError> {
+        let categories = StringArray::from(
+            header
+                .reference_sequences()
+                .iter()
+                .map(|(rs, _)| Some(rs.as_str()))
+                .collect::<Vec<_>>(),
+        );
+        Ok(Self {
+            qname: GenericStringBuilder::<i32>::new(),
+            read1: MateBuilder::new(capacity, categories.clone(), as_index)?,
+            read2: MateBuilder::new(capacity, categories, as_index)?,
+            tlen: Int32Builder::with_capacity(capacity),
+            fragment_length: Int32Builder::with_capacity(capacity),
+            proper_pair: BooleanBuilder::with_capacity(capacity),
+            coordinate_system,
+        })
+    }
+}
+
+impl BatchBuilder for PairBatchBuilder {
+    type Record<'a> = &'a Pair;
+
+    fn push(&mut self, pair: Self::Record<'_>) {
+        let (read1, read2) = (pair.read1.as_ref(), pair.read2.as_ref());
+        let Some(read) = read1.or(read2) else {
+            return;
+        };
+        let coordinate_system = self.coordinate_system;
+        let start = |position: usize| {
+            coordinate_system.start_from(CoordinateSystem::OneBased, position as i64) as i32
+        };
+        self.qname.append_option(read.read_name());
+        self.read1.push(read1, start);
+        self.read2.push(read2, start);
+        self.tlen.append_value(read.template_length());
+        self.fragment_length
+            .append_option(read1.zip(read2).and_then(|(a, b)| fragment_length(a, b)));
+        self.proper_pair
+            .append_value(read.flags().is_properly_aligned());
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let mut columns = vec![(
+            "qname".to_string(),
+            Arc::new(self.qname.finish()) as ArrayRef,
+        )];
+        columns.extend(self.read1.finish('1'));
+        columns.extend(self.read2.finish('2'));
+        columns.extend([
+            ("tlen".to_string(), Arc::new(self.tlen.finish()) as ArrayRef),
+            (
+                "fragment_length".to_string(),
+                Arc::new(self.fragment_length.finish()),
+            ),
+            (
+                "proper_pair".to_string(),
+                Arc::new(self.proper_pair.finish()),
+            ),
+        ]);
+        RecordBatch::try_from_iter(columns)
+    }
+}
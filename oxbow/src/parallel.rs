@@ -0,0 +1,61 @@
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use arrow::error::ArrowError;
+use noodles::core::Region;
+
+use crate::progress::{finish_query, ProgressTracker, QueryCounter};
+
+/// How often the calling thread reports the progress of the query threads.
+const PROGRESS_POLL: Duration = Duration::from_millis(10);
+
+/// Runs `query` over contiguous chunks of `regions` on up to `threads` scoped threads.
+///
+/// Each call receives its own chunk and is expected to open its own reader, since a seekable
+/// handle cannot be shared. The results are concatenated in the order of `regions`. If `threads`
+/// is `None`, the available parallelism is used.
+///
+/// The calls count the records they read with the given [`QueryCounter`], which the calling
+/// thread reports to `progress` while they run.
+pub(crate) fn query_parallel<T, F>(
+    regions: &[Region],
+    threads: Option<usize>,
+    progress: &mut Option<ProgressTracker>,
+    query: F,
+) -> Result<Vec<T>, ArrowError>
+where
+    T: Send,
+    F: Fn(&[Region], &QueryCounter) -> Result<Vec<T>, ArrowError> + Sync,
+{
+    if regions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let threads = threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, NonZeroUsize::get))
+        .clamp(1, regions.len());
+    let chunk_size = regions.len().div_ceil(threads);
+    let (query, counter) = (&query, &QueryCounter::default());
+    let result = std::thread::scope(|scope| {
+        let handles: Vec<_> = regions
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || query(chunk, counter)))
+            .collect();
+        if let Some(progress) = progress.as_mut() {
+            while !handles.iter().all(|handle| handle.is_finished()) {
+                progress.update_query(counter.get());
+                std::thread::sleep(PROGRESS_POLL);
+            }
+            progress.update_query(counter.get());
+        }
+        let mut records = Vec::new();
+        for handle in handles {
+            let chunk = handle.join().map_err(|_| {
+                ArrowError::ComputeError("A region query thread panicked.".to_string())
+            })??;
+            records.extend(chunk);
+        }
+        Ok(records)
+    });
+    finish_query(progress);
+    result
+}
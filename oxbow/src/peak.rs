@@ -0,0 +1,456 @@
+//! Readers for the narrowPeak and broadPeak formats written by peak callers such as MACS2.
+//!
+//! Both are BED6 followed by typed fields: `chrom`, `start`, `end`, `name`, `score` and `strand`,
+//! with 0-based, half-open coordinates, then `signal_value`, `p_value` and `q_value` (Float32).
+//! narrowPeak adds `peak` (Int32), the offset of the summit from `start`. The statistics are
+//! kept as written, including the `-1` of a value that wasn't computed, while a `.` name or
+//! strand is null. Track, browser and comment lines are skipped.
+//!
+//! BGZF-compressed files with a tabix or CSI index can be queried by region.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Float32Builder, Int32Builder, Int64Builder, StringBuilder, StringDictionaryBuilder,
+    UInt32Builder,
+};
+use arrow::{
+    datatypes::{Fields, Int32Type},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use noodles::core::{Position, Region};
+use noodles::{bgzf, csi};
+
+use crate::batch_builder::{
+    builder_fields, count_err, provenance, write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES,
+};
+use crate::bed::{index_from_path, is_skipped, query};
+use crate::compression::decompress;
+use crate::coordinates::CoordinateSystem;
+use crate::error_policy::{tolerate, ErrorPolicy, RecordError};
+use crate::region::{query_region, QueryMode};
+
+/// The flavor of a peak file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PeakFormat {
+    /// narrowPeak (BED6+4), with the summit of each peak.
+    Narrow,
+    /// broadPeak (BED6+3).
+    Broad,
+}
+
+impl PeakFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Narrow => "narrowpeak",
+            Self::Broad => "broadpeak",
+        }
+    }
+}
+
+/// A called peak.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeakRecord {
+    pub chrom: String,
+    pub start: u32,
+    pub end: u32,
+    pub name: Option<String>,
+    pub score: i64,
+    pub strand: Option<String>,
+    pub signal_value: f32,
+    pub p_value: f32,
+    pub q_value: f32,
+    /// The offset of the summit from `start`, in narrowPeak only.
+    pub peak: Option<i32>,
+}
+
+/// Options for reading peak files.
+#[derive(Clone, Debug, Default)]
+pub struct PeakOptions {
+    /// The coordinate system of `start`. Defaults to the native 0-based, half-open coordinates.
+    /// See [`crate::coordinates`].
+    pub coordinate_system: Option<CoordinateSystem>,
+    /// What to do with records that fail to parse. Defaults to [`ErrorPolicy::Fail`].
+    pub on_error: Option<ErrorPolicy>,
+}
+
+fn invalid_data(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid line: {}", line),
+    )
+}
+
+fn parse<T: std::str::FromStr>(s: &str, line: &str) -> io::Result<T> {
+    s.parse().map_err(|_| invalid_data(line))
+}
+
+fn parse_peak_line(line: &str, format: PeakFormat) -> io::Result<PeakRecord> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let n = match format {
+        PeakFormat::Narrow => 10,
+        PeakFormat::Broad => 9,
+    };
+    if fields.len() != n {
+        return Err(invalid_data(line));
+    }
+    let optional = |s: &str| (s != ".").then(|| s.to_string());
+    Ok(PeakRecord {
+        chrom: fields[0].to_string(),
+        start: parse(fields[1], line)?,
+        end: parse(fields[2], line)?,
+        name: optional(fields[3]),
+        score: parse(fields[4], line)?,
+        strand: optional(fields[5]),
+        signal_value: parse(fields[6], line)?,
+        p_value: parse(fields[7], line)?,
+        q_value: parse(fields[8], line)?,
+        peak: fields.get(9).map(|peak| parse(peak, line)).transpose()?,
+    })
+}
+
+/// Parses lines into records. Malformed records are handled by [`PeakOptions::on_error`],
+/// collecting them into `errors`.
+fn parse_records<'a>(
+    lines: impl Iterator<Item = io::Result<String>> + 'a,
+    format: PeakFormat,
+    options: &PeakOptions,
+    errors: &'a mut Vec<RecordError>,
+) -> impl Iterator<Item = Result<PeakRecord, ArrowError>> + 'a {
+    let coordinate_system = options
+        .coordinate_system
+        .unwrap_or(CoordinateSystem::ZeroBasedHalfOpen);
+    let records = lines
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !is_skipped(line)))
+        .map(move |(i, line)| {
+            let record = line
+                .and_then(|line| parse_peak_line(&line, format))
+                .and_then(|record| {
+                    coordinate_system
+                        .start_from_u32(CoordinateSystem::ZeroBasedHalfOpen, record.start)
+                        .map(|_| record)
+                });
+            (i + 1, record)
+        });
+    tolerate(records, options.on_error.unwrap_or_default(), errors)
+        .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())))
+}
+
+/// Drops the records of a region query that do not match the region under `mode`. Errors are
+/// passed through.
+fn filter_interval<'a>(
+    records: impl Iterator<Item = Result<PeakRecord, ArrowError>> + 'a,
+    region: &Region,
+    mode: QueryMode,
+) -> impl Iterator<Item = Result<PeakRecord, ArrowError>> + 'a {
+    let interval = region.interval();
+    records.filter(move |i| {
+        i.as_ref().map_or(true, |record| {
+            let start = record.start as usize + 1;
+            let end = (record.end as usize).max(start);
+            match (Position::try_from(start), Position::try_from(end)) {
+                (Ok(start), Ok(end)) => mode.matches(interval, start, end),
+                _ => true,
+            }
+        })
+    })
+}
+
+/// A narrowPeak or broadPeak reader.
+pub struct PeakReader<R> {
+    reader: R,
+    indexed_reader: Option<csi::io::IndexedReader<bgzf::Reader<File>>>,
+    format: PeakFormat,
+    options: PeakOptions,
+    query_mode: QueryMode,
+    errors: Vec<RecordError>,
+    path: Option<PathBuf>,
+}
+
+impl PeakReader<Box<dyn BufRead>> {
+    /// Creates a peak reader from a given file path.
+    ///
+    /// Gzip- and BGZF-compressed files are decompressed transparently. A BGZF-compressed file
+    /// with an index at `<path>.tbi` or `<path>.csi` can also be queried by region.
+    pub fn new_from_path(path: &str, format: PeakFormat) -> io::Result<Self> {
+        let file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let mut reader = Self::new(decompress(file)?, format)?;
+        if let Some(index) = index_from_path(path)? {
+            let file = File::open(path)?;
+            reader.indexed_reader = Some(csi::io::IndexedReader::new(file, index));
+        }
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> PeakReader<R> {
+    /// Creates a peak reader.
+    ///
+    /// Readers created from a stream cannot be queried by region.
+    pub fn new(reader: R, format: PeakFormat) -> io::Result<Self> {
+        Ok(Self {
+            reader,
+            indexed_reader: None,
+            format,
+            options: PeakOptions::default(),
+            query_mode: QueryMode::default(),
+            errors: Vec::new(),
+            path: None,
+        })
+    }
+
+    /// Sets the options used when converting records.
+    pub fn with_options(mut self, options: PeakOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets how records are matched against query regions.
+    ///
+    /// By default, a region query returns every record overlapping the region. With
+    /// [`QueryMode::Contained`], records straddling either bound of the region are dropped.
+    pub fn with_query_mode(mut self, query_mode: QueryMode) -> Self {
+        self.query_mode = query_mode;
+        self
+    }
+
+    /// Returns the records skipped by the last scan under [`ErrorPolicy::SkipAndCollect`].
+    pub fn errors(&self) -> &[RecordError] {
+        &self.errors
+    }
+
+    /// Returns the fields of the batches returned by [`PeakReader::records_to_ipc`], without
+    /// reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(PeakBatchBuilder::new(0, self.format, &self.options))
+    }
+
+    /// Returns the records in the given region as Apache Arrow IPC.
+    ///
+    /// If the region is `None`, all records are returned. Querying a region requires a
+    /// BGZF-compressed file with a tabix or CSI index.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::peak::{PeakFormat, PeakReader};
+    ///
+    /// let mut reader = PeakReader::new_from_path("peaks.narrowPeak.gz", PeakFormat::Narrow).unwrap();
+    /// let ipc = reader.records_to_ipc(Some("chr1:1-1000000")).unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
+        let metadata = provenance(self.format.name(), self.path.as_deref(), region.as_slice());
+        let batch_builder =
+            PeakBatchBuilder::new(1024, self.format, &self.options).with_metadata(metadata);
+        if let Some(region) = region {
+            let region = query_region(region)?;
+            let lines = query(self.indexed_reader.as_mut(), &region)?;
+            let records = parse_records(lines, self.format, &self.options, &mut self.errors);
+            let records = filter_interval(records, &region, self.query_mode);
+            return write_ipc_err(records, batch_builder);
+        }
+        let lines = (&mut self.reader).lines();
+        let records = parse_records(lines, self.format, &self.options, &mut self.errors);
+        write_ipc_err(records, batch_builder)
+    }
+
+    /// Returns the number of records in the given region, without converting them to Arrow.
+    ///
+    /// If the region is `None`, all records are counted.
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        if let Some(region) = region {
+            let region = query_region(region)?;
+            let lines = query(self.indexed_reader.as_mut(), &region)?;
+            let records = parse_records(lines, self.format, &self.options, &mut self.errors);
+            return count_err(filter_interval(records, &region, self.query_mode));
+        }
+        let lines = (&mut self.reader).lines();
+        count_err(parse_records(
+            lines,
+            self.format,
+            &self.options,
+            &mut self.errors,
+        ))
+    }
+}
+
+struct PeakBatchBuilder {
+    coordinate_system: CoordinateSystem,
+    chrom: StringDictionaryBuilder<Int32Type>,
+    start: UInt32Builder,
+    end: UInt32Builder,
+    name: StringBuilder,
+    score: Int64Builder,
+    strand: StringBuilder,
+    signal_value: Float32Builder,
+    p_value: Float32Builder,
+    q_value: Float32Builder,
+    /// The `peak` column, in narrowPeak only.
+    peak: Option<Int32Builder>,
+}
+
+impl PeakBatchBuilder {
+    fn new(capacity: usize, format: PeakFormat, options: &PeakOptions) -> Self {
+        Self {
+            coordinate_system: options
+                .coordinate_system
+                .unwrap_or(CoordinateSystem::ZeroBasedHalfOpen),
+            chrom: StringDictionaryBuilder::<Int32Type>::new(),
+            start: UInt32Builder::with_capacity(capacity),
+            end: UInt32Builder::with_capacity(capacity),
+            name: StringBuilder::with_capacity(capacity, 0),
+            score: Int64Builder::with_capacity(capacity),
+            strand: StringBuilder::with_capacity(capacity, 0),
+            signal_value: Float32Builder::with_capacity(capacity),
+            p_value: Float32Builder::with_capacity(capacity),
+            q_value: Float32Builder::with_capacity(capacity),
+            peak: (format == PeakFormat::Narrow).then(|| Int32Builder::with_capacity(capacity)),
+        }
+    }
+}
+
+impl BatchBuilder for PeakBatchBuilder {
+    type Record<'a> = &'a PeakRecord;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        self.chrom.append_value(&record.chrom);
+        // The converted start was checked to fit when parsing.
+        let start = self
+            .coordinate_system
+            .start_from_u32(CoordinateSystem::ZeroBasedHalfOpen, record.start)
+            .unwrap_or(u32::MAX);
+        self.start.append_value(start);
+        self.end.append_value(record.end);
+        self.name.append_option(record.name.as_deref());
+        self.score.append_value(record.score);
+        self.strand.append_option(record.strand.as_deref());
+        self.signal_value.append_value(record.signal_value);
+        self.p_value.append_value(record.p_value);
+        self.q_value.append_value(record.q_value);
+        if let Some(builder) = self.peak.as_mut() {
+            builder.append_option(record.peak);
+        }
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let mut columns = vec![
+            ("chrom", Arc::new(self.chrom.finish()) as ArrayRef),
+            ("start", Arc::new(self.start.finish()) as ArrayRef),
+            ("end", Arc::new(self.end.finish()) as ArrayRef),
+            ("name", Arc::new(self.name.finish()) as ArrayRef),
+            ("score", Arc::new(self.score.finish()) as ArrayRef),
+            ("strand", Arc::new(self.strand.finish()) as ArrayRef),
+            (
+                "signal_value",
+                Arc::new(self.signal_value.finish()) as ArrayRef,
+            ),
+            ("p_value", Arc::new(self.p_value.finish()) as ArrayRef),
+            ("q_value", Arc::new(self.q_value.finish()) as ArrayRef),
+        ];
+        if let Some(builder) = self.peak.as_mut() {
+            columns.push(("peak", Arc::new(builder.finish()) as ArrayRef));
+        }
+        RecordBatch::try_from_iter(columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{DataType, Float32Type, Int32Type, UInt32Type};
+    use arrow::ipc::reader::FileReader;
+
+    fn read_ipc(ipc: Vec<u8>) -> RecordBatch {
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    fn fixture_path(name: &str) -> String {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../fixtures");
+        path.push(name);
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_read_narrowpeak() {
+        let path = fixture_path("sample.narrowPeak");
+        let mut reader = PeakReader::new_from_path(&path, PeakFormat::Narrow).unwrap();
+        let batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        let schema = batch.schema();
+        let types: Vec<_> = schema
+            .fields()
+            .iter()
+            .map(|field| (field.name().as_str(), field.data_type().clone()))
+            .skip(6)
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("signal_value", DataType::Float32),
+                ("p_value", DataType::Float32),
+                ("q_value", DataType::Float32),
+                ("peak", DataType::Int32),
+            ]
+        );
+        assert_eq!(batch.num_rows(), 4);
+        let column = |name| batch.column_by_name(name).unwrap();
+        assert_eq!(column("start").as_primitive::<UInt32Type>().value(0), 9980);
+        assert_eq!(
+            column("p_value").as_primitive::<Float32Type>().value(0),
+            11.5
+        );
+        assert_eq!(column("peak").as_primitive::<Int32Type>().value(2), 133);
+        assert!(column("strand").is_null(0));
+        // A q-value that wasn't computed is kept as -1.
+        assert_eq!(
+            column("q_value").as_primitive::<Float32Type>().value(3),
+            -1.0
+        );
+        assert_eq!(reader.fields().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_read_broadpeak() {
+        let path = fixture_path("sample.broadPeak");
+        let mut reader = PeakReader::new_from_path(&path, PeakFormat::Broad).unwrap();
+        let batch = read_ipc(reader.records_to_ipc(None).unwrap());
+        assert_eq!(batch.num_columns(), 9);
+        assert!(batch.column_by_name("peak").is_none());
+        let signal = batch.column_by_name("signal_value").unwrap();
+        assert_eq!(signal.as_primitive::<Float32Type>().value(1), 8.4);
+
+        // narrowPeak lines have a field too many.
+        let path = fixture_path("sample.narrowPeak");
+        let mut reader = PeakReader::new_from_path(&path, PeakFormat::Broad).unwrap();
+        assert!(reader.count(None).is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let path = fixture_path("sample.narrowPeak.gz");
+        let mut reader = PeakReader::new_from_path(&path, PeakFormat::Narrow).unwrap();
+        let batch = read_ipc(reader.records_to_ipc(Some("chr1:10000-16000")).unwrap());
+        let names = batch.column_by_name("name").unwrap().as_string::<i32>();
+        assert_eq!(
+            names.iter().collect::<Vec<_>>(),
+            [Some("sample_peak_1"), Some("sample_peak_2")]
+        );
+        let mut reader = PeakReader::new_from_path(&path, PeakFormat::Narrow)
+            .unwrap()
+            .with_query_mode(QueryMode::Contained);
+        assert_eq!(reader.count(Some("chr1:10000-16000")).unwrap(), 1);
+        assert_eq!(reader.count(Some("chr2")).unwrap(), 1);
+
+        // Plain files have no index.
+        let path = fixture_path("sample.narrowPeak");
+        let mut reader = PeakReader::new_from_path(&path, PeakFormat::Narrow).unwrap();
+        assert!(reader.count(Some("chr1")).is_err());
+    }
+}
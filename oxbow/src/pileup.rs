@@ -0,0 +1,152 @@
+//! Per-base pileups of alignment records.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Int32Builder, StringArray, StringDictionaryBuilder};
+use arrow::{datatypes::Int32Type, error::ArrowError, record_batch::RecordBatch};
+use noodles::sam;
+use noodles::sam::record::cigar::op::Kind;
+
+use crate::batch_builder::BatchBuilder;
+
+/// The tallies at a reference position: `A`, `C`, `G`, `T`, any other base, and deletions.
+type Counts = [i32; 6];
+
+const DELETION: usize = 5;
+
+fn base_index(base: u8) -> usize {
+    match base.to_ascii_uppercase() {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        b'T' => 3,
+        _ => 4,
+    }
+}
+
+/// The tallies of the bases aligned to each position of a 1-based, closed interval.
+pub(crate) struct Pileup {
+    start: usize,
+    end: usize,
+    counts: BTreeMap<usize, Counts>,
+}
+
+impl Pileup {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    fn tally(&mut self, position: usize, index: usize) {
+        if (self.start..=self.end).contains(&position) {
+            self.counts.entry(position).or_default()[index] += 1;
+        }
+    }
+
+    /// Tallies the bases and deletions of a record, walking its CIGAR.
+    ///
+    /// Insertions, clips and reference skips are not tallied.
+    pub fn push(&mut self, record: &sam::alignment::Record) {
+        let Some(start) = record.alignment_start() else {
+            return;
+        };
+        let sequence = record.sequence().as_ref();
+        let (mut reference_position, mut read_position) = (start.get(), 0);
+        for op in record.cigar().iter() {
+            let len = op.len();
+            match op.kind() {
+                Kind::Match | Kind::SequenceMatch | Kind::SequenceMismatch => {
+                    for i in 0..len {
+                        let index = sequence
+                            .get(read_position + i)
+                            .map_or(4, |&base| base_index(u8::from(base)));
+                        self.tally(reference_position + i, index);
+                    }
+                    reference_position += len;
+                    read_position += len;
+                }
+                Kind::Deletion => {
+                    for i in 0..len {
+                        self.tally(reference_position + i, DELETION);
+                    }
+                    reference_position += len;
+                }
+                Kind::Skip => reference_position += len,
+                Kind::Insertion | Kind::SoftClip => read_position += len,
+                Kind::HardClip | Kind::Pad => {}
+            }
+        }
+    }
+
+    /// Returns the covered positions and their tallies, in position order.
+    pub fn into_counts(self) -> impl Iterator<Item = (usize, Counts)> {
+        self.counts.into_iter()
+    }
+}
+
+/// A position of a pileup.
+pub(crate) struct PileupRow<'a> {
+    pub chrom: &'a str,
+    pub pos: usize,
+    pub counts: Counts,
+}
+
+pub(crate) struct PileupBatchBuilder {
+    chrom: StringDictionaryBuilder<Int32Type>,
+    pos: Int32Builder,
+    depth: Int32Builder,
+    counts: [Int32Builder; 6],
+}
+
+impl PileupBatchBuilder {
+    pub fn new(capacity: usize, header: &sam::Header) -> Result<Self, ArrowError> {
+        let categories = StringArray::from(
+            header
+                .reference_sequences()
+                .iter()
+                .map(|(rs, _)| Some(rs.as_str()))
+                .collect::<Vec<_>>(),
+        );
+        Ok(Self {
+            chrom: StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
+                capacity,
+                &categories,
+            )?,
+            pos: Int32Builder::with_capacity(capacity),
+            depth: Int32Builder::with_capacity(capacity),
+            counts: std::array::from_fn(|_| Int32Builder::with_capacity(capacity)),
+        })
+    }
+}
+
+impl BatchBuilder for PileupBatchBuilder {
+    type Record<'a> = PileupRow<'a>;
+
+    fn push(&mut self, row: Self::Record<'_>) {
+        self.chrom.append_value(row.chrom);
+        self.pos.append_value(row.pos as i32);
+        self.depth.append_value(row.counts.iter().sum());
+        for (builder, count) in self.counts.iter_mut().zip(row.counts) {
+            builder.append_value(count);
+        }
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        let counts = ["a", "c", "g", "t", "n", "del"]
+            .into_iter()
+            .zip(self.counts.iter_mut())
+            .map(|(name, builder)| (name, Arc::new(builder.finish()) as ArrayRef));
+        RecordBatch::try_from_iter(
+            vec![
+                ("chrom", Arc::new(self.chrom.finish()) as ArrayRef),
+                ("pos", Arc::new(self.pos.finish()) as ArrayRef),
+                ("depth", Arc::new(self.depth.finish()) as ArrayRef),
+            ]
+            .into_iter()
+            .chain(counts),
+        )
+    }
+}
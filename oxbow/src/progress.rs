@@ -0,0 +1,183 @@
+//! Progress reports of long scans, e.g. to show a progress bar over a multi-GB file.
+//!
+//! The BAM and VCF readers call a [`Progress`] set with `with_progress` while scanning a whole
+//! file, or querying regions of it. It runs on the scanning thread, between records, so it
+//! doesn't hold up the threads decompressing blocks. The threads of a parallel query count their
+//! records together, and the calling thread reports them.
+//!
+//! Their scans and queries can also be cancelled with a flag set with `with_cancel`, which the
+//! threads of a parallel query check too.
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use noodles::bgzf;
+
+/// Receives the progress of a scan.
+pub trait Progress {
+    /// Reports the number of records read so far, the number of bytes of the file read, and the
+    /// size of the file, if known.
+    ///
+    /// Region queries, which jump around the file, report 0 bytes and no size.
+    fn report(&mut self, records: u64, bytes: u64, total_bytes: Option<u64>);
+}
+
+impl<F: FnMut(u64, u64, Option<u64>)> Progress for F {
+    fn report(&mut self, records: u64, bytes: u64, total_bytes: Option<u64>) {
+        self(records, bytes, total_bytes)
+    }
+}
+
+/// Counts the records of a scan, reporting them every `interval` records and at the end.
+pub(crate) struct ProgressTracker {
+    progress: Box<dyn Progress>,
+    interval: u64,
+    total_bytes: Option<u64>,
+    records: u64,
+}
+
+impl ProgressTracker {
+    /// Creates a tracker for a scan of the file at `path`, whose size is the total, if any.
+    pub fn new(progress: Box<dyn Progress>, interval: usize, path: Option<&Path>) -> Self {
+        Self {
+            progress,
+            interval: interval.max(1) as u64,
+            total_bytes: path.and_then(|path| path.metadata().ok()).map(|m| m.len()),
+            records: 0,
+        }
+    }
+
+    /// Counts a record, ending at `bytes` into the file.
+    pub fn record(&mut self, bytes: u64) {
+        self.records += 1;
+        if self.records % self.interval == 0 {
+            self.progress.report(self.records, bytes, self.total_bytes);
+        }
+    }
+
+    /// Reports the records of the scan, which stopped at `bytes` into the file, and starts
+    /// counting again for the next one.
+    pub fn finish(&mut self, bytes: u64) {
+        self.progress.report(self.records, bytes, self.total_bytes);
+        self.records = 0;
+    }
+
+    /// Counts a record of a region query.
+    pub fn record_query(&mut self) {
+        self.update_query(self.records + 1);
+    }
+
+    /// Counts the records of a region query up to `records`, e.g. as counted by the threads of a
+    /// parallel query, reporting them if another `interval` has been reached.
+    pub fn update_query(&mut self, records: u64) {
+        if records / self.interval > self.records / self.interval {
+            self.progress.report(records, 0, None);
+        }
+        self.records = records;
+    }
+
+    /// Reports the records of a region query, and starts counting again for the next one.
+    pub fn finish_query(&mut self) {
+        self.progress.report(self.records, 0, None);
+        self.records = 0;
+    }
+}
+
+/// Counts the records read by the threads of a parallel query.
+#[derive(Default)]
+pub(crate) struct QueryCounter(AtomicU64);
+
+impl QueryCounter {
+    /// Counts a record.
+    pub fn record(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of records counted so far.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts a record of a BGZF file ending at `vpos`, if progress is tracked.
+pub(crate) fn track(progress: &mut Option<ProgressTracker>, vpos: bgzf::VirtualPosition) {
+    if let Some(progress) = progress {
+        progress.record(vpos.compressed());
+    }
+}
+
+/// Reports the end of a scan of a BGZF file, which stopped at `vpos`, if progress is tracked.
+pub(crate) fn finish_progress(progress: &mut Option<ProgressTracker>, vpos: bgzf::VirtualPosition) {
+    if let Some(progress) = progress {
+        progress.finish(vpos.compressed());
+    }
+}
+
+/// Counts the records of a region query, if progress is tracked, and stops it at the next record
+/// once it has been cancelled.
+pub(crate) fn track_query<'a, T: 'a>(
+    records: impl Iterator<Item = T> + 'a,
+    progress: &'a mut Option<ProgressTracker>,
+    cancel: &'a Option<Arc<AtomicBool>>,
+) -> impl Iterator<Item = T> + 'a {
+    until_cancelled(records, cancel).inspect(move |_| {
+        if let Some(progress) = progress {
+            progress.record_query();
+        }
+    })
+}
+
+/// Stops `records` before reading the next one once the scan has been cancelled.
+pub(crate) fn until_cancelled<'a, T: 'a>(
+    mut records: impl Iterator<Item = T> + 'a,
+    cancel: &'a Option<Arc<AtomicBool>>,
+) -> impl Iterator<Item = T> + 'a {
+    std::iter::from_fn(move || {
+        if is_cancelled(cancel) {
+            None
+        } else {
+            records.next()
+        }
+    })
+}
+
+/// Reports the end of a region query, if progress is tracked.
+pub(crate) fn finish_query(progress: &mut Option<ProgressTracker>) {
+    if let Some(progress) = progress {
+        progress.finish_query();
+    }
+}
+
+/// Returns whether a scan has been cancelled, if it can be.
+pub(crate) fn is_cancelled(cancel: &Option<Arc<AtomicBool>>) -> bool {
+    cancel
+        .as_ref()
+        .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_tracker() {
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let sink = reports.clone();
+        let progress = move |records, bytes, total| sink.borrow_mut().push((records, bytes, total));
+        let mut tracker = ProgressTracker::new(Box::new(progress), 2, None);
+        for bytes in [10, 20, 30] {
+            tracker.record(bytes);
+        }
+        tracker.finish(35);
+        assert_eq!(*reports.borrow(), [(2, 20, None), (3, 35, None)]);
+
+        reports.borrow_mut().clear();
+        tracker.record_query();
+        tracker.update_query(5);
+        tracker.update_query(5);
+        tracker.finish_query();
+        assert_eq!(*reports.borrow(), [(5, 0, None), (5, 0, None)]);
+    }
+}
@@ -0,0 +1,413 @@
+//! Parsing query regions and matching records against them.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+use arrow::error::ArrowError;
+use noodles::core::{region::Interval, Position, Region};
+
+use crate::compression::decompress;
+
+/// Parses a region: a whole reference sequence (`chr1`), an interval (`chr1:1-1000`), or an
+/// open-ended interval (`chr1:500-` or `chr1:500`).
+///
+/// Positions are 1-based and closed, and may have thousands separators (`chr1:1,000-2,000`).
+///
+/// # Examples
+///
+/// ```
+/// use oxbow::region::parse_region;
+///
+/// let region = parse_region("chr1:1,000-2,000").unwrap();
+/// assert_eq!(region.to_string(), "chr1:1000-2000");
+/// assert_eq!(parse_region("chr1:500-").unwrap().to_string(), "chr1:500");
+/// assert!(parse_region("chr1:2000-1000").is_err());
+/// ```
+pub fn parse_region(s: &str) -> io::Result<Region> {
+    let s = s.trim();
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid region: {}", s),
+        )
+    };
+    let (name, interval) = match s.rsplit_once(':') {
+        Some((name, interval)) => {
+            let interval = interval.replace(',', "");
+            let interval = interval.strip_suffix('-').unwrap_or(&interval);
+            if interval.is_empty() {
+                return Err(invalid());
+            }
+            let interval: Interval = interval.parse().map_err(|_| invalid())?;
+            (name, interval)
+        }
+        None => (s, Interval::from(..)),
+    };
+    if name.is_empty() {
+        return Err(invalid());
+    }
+    if let (Some(start), Some(end)) = (interval.start(), interval.end()) {
+        if start > end {
+            return Err(invalid());
+        }
+    }
+    Ok(Region::new(name, interval))
+}
+
+/// Parses regions with [`parse_region`], stopping at the first invalid one.
+pub fn parse_regions<S: AsRef<str>>(regions: &[S]) -> io::Result<Vec<Region>> {
+    regions
+        .iter()
+        .map(|region| parse_region(region.as_ref()))
+        .collect()
+}
+
+/// Returns regions covering reference sequences, given as `(name, length)` pairs.
+///
+/// Without a `window`, each sequence gives one whole-sequence region. Otherwise, each sequence is
+/// tiled with windows of `window` bases starting every `step` bases (by default, `window`), the
+/// last of which is truncated to the end of the sequence. A `step` smaller than `window` gives
+/// overlapping windows, and a larger one leaves gaps between them.
+///
+/// # Examples
+///
+/// ```
+/// use oxbow::region::tile_regions;
+///
+/// let regions = tile_regions([("sq0", 250), ("sq1", 100)], Some(100), None).unwrap();
+/// let regions: Vec<_> = regions.iter().map(|region| region.to_string()).collect();
+/// assert_eq!(regions, ["sq0:1-100", "sq0:101-200", "sq0:201-250", "sq1:1-100"]);
+/// ```
+pub fn tile_regions<N: Into<String>>(
+    sequences: impl IntoIterator<Item = (N, usize)>,
+    window: Option<usize>,
+    step: Option<usize>,
+) -> io::Result<Vec<Region>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+    let (window, step) = match (window, step) {
+        (None, Some(_)) => return Err(invalid("A step requires a window.")),
+        (None, None) => {
+            return Ok(sequences
+                .into_iter()
+                .map(|(name, _)| Region::new(name, ..))
+                .collect())
+        }
+        (Some(window), step) => (window, step.unwrap_or(window)),
+    };
+    if window == 0 || step == 0 {
+        return Err(invalid("The window and step must be positive."));
+    }
+    let mut regions = Vec::new();
+    for (name, length) in sequences {
+        let name: String = name.into();
+        let mut start = 1;
+        while start <= length {
+            let end = (start + window - 1).min(length);
+            // Both bounds are at least 1.
+            let interval = Position::new(start).unwrap()..=Position::new(end).unwrap();
+            regions.push(Region::new(name.clone(), interval));
+            if end == length {
+                break;
+            }
+            start += step;
+        }
+    }
+    Ok(regions)
+}
+
+/// Reads the regions of a BED file, plain or compressed, from its first three fields.
+///
+/// BED intervals are 0-based and half-open, and are converted to 1-based, closed regions. Track,
+/// browser and comment lines are skipped.
+pub fn regions_from_bed<P: AsRef<Path>>(path: P) -> io::Result<Vec<Region>> {
+    let reader = decompress(BufReader::new(File::open(path)?))?;
+    let mut regions = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if crate::bed::is_skipped(&line) {
+            continue;
+        }
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid line: {}", line),
+            )
+        };
+        let mut fields = line.split('\t');
+        let (Some(name), Some(start), Some(end)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid());
+        };
+        let start: usize = start.parse().map_err(|_| invalid())?;
+        let end: usize = end.parse().map_err(|_| invalid())?;
+        if end <= start {
+            return Err(invalid());
+        }
+        let (Ok(start), Ok(end)) = (Position::try_from(start + 1), Position::try_from(end)) else {
+            return Err(invalid());
+        };
+        regions.push(Region::new(name, start..=end));
+    }
+    Ok(regions)
+}
+
+/// Renames the reference sequences of query regions to the naming of a file, e.g. to query
+/// `chr1` in a file that calls it `1`.
+///
+/// Only query regions are renamed; records keep the names of the file. A name the file already
+/// has is never renamed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChromAlias {
+    /// Renames each name in the map to the name it maps to.
+    Map(HashMap<String, String>),
+    /// Adds or strips the UCSC `chr` prefix of the Ensembl names, mapping `chrM` to `MT` and
+    /// back, whichever the file has.
+    UcscEnsembl,
+}
+
+impl ChromAlias {
+    /// Returns the name of the file for the query name `name`, where `has_name` tells whether
+    /// the file has a name. Unknown names are returned unchanged, to fail the query as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oxbow::region::ChromAlias;
+    ///
+    /// let has_name = |name: &str| ["1", "MT"].contains(&name);
+    /// assert_eq!(ChromAlias::UcscEnsembl.resolve("chr1", has_name), "1");
+    /// assert_eq!(ChromAlias::UcscEnsembl.resolve("chrM", has_name), "MT");
+    /// assert_eq!(ChromAlias::UcscEnsembl.resolve("chrX", has_name), "chrX");
+    /// ```
+    pub fn resolve(&self, name: &str, has_name: impl Fn(&str) -> bool) -> String {
+        if has_name(name) {
+            return name.to_string();
+        }
+        let alias = match self {
+            Self::Map(map) => map.get(name).cloned(),
+            Self::UcscEnsembl => Some(match name {
+                "chrM" => "MT".to_string(),
+                "MT" => "chrM".to_string(),
+                _ => match name.strip_prefix("chr") {
+                    Some(stripped) => stripped.to_string(),
+                    None => format!("chr{}", name),
+                },
+            }),
+        };
+        alias
+            .filter(|alias| has_name(alias))
+            .unwrap_or_else(|| name.to_string())
+    }
+}
+
+impl FromStr for ChromAlias {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ucsc_ensembl" => Ok(Self::UcscEnsembl),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid chromosome alias preset: {}. Expected ucsc_ensembl.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// Renames the reference sequence of a query region with an optional [`ChromAlias`].
+pub(crate) fn alias_region(
+    region: Region,
+    alias: Option<&ChromAlias>,
+    has_name: impl Fn(&str) -> bool,
+) -> Region {
+    match alias {
+        Some(alias) => Region::new(alias.resolve(region.name(), has_name), region.interval()),
+        None => region,
+    }
+}
+
+/// Parses a query region string, e.g. `sq0:1-1000`, returning an Arrow error if it is invalid.
+/// See [`parse_region`].
+pub(crate) fn query_region(region: &str) -> Result<Region, ArrowError> {
+    parse_region(region).map_err(|e| ArrowError::InvalidArgumentError(e.to_string()))
+}
+
+/// Parses a query region string and renames its reference sequence with `alias`, given whether
+/// the file has a name. See [`ChromAlias`].
+pub(crate) fn parse_aliased_region(
+    region: &str,
+    alias: Option<&ChromAlias>,
+    has_name: impl Fn(&str) -> bool,
+) -> Result<Region, ArrowError> {
+    Ok(alias_region(query_region(region)?, alias, has_name))
+}
+
+/// How records are matched against a query region.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum QueryMode {
+    /// Records overlapping the region by at least one base.
+    #[default]
+    Overlap,
+    /// Records lying entirely within the region.
+    Contained,
+}
+
+impl QueryMode {
+    /// Returns whether a record spanning the 1-based, closed interval `[start, end]` matches a
+    /// query `interval`, given that it overlaps it.
+    ///
+    /// Index queries already return only overlapping records, so this only rejects records
+    /// straddling a bound of the interval in `Contained` mode.
+    pub(crate) fn matches(&self, interval: Interval, start: Position, end: Position) -> bool {
+        match self {
+            Self::Overlap => true,
+            Self::Contained => {
+                interval.start().map_or(true, |bound| start >= bound)
+                    && interval.end().map_or(true, |bound| end <= bound)
+            }
+        }
+    }
+}
+
+impl FromStr for QueryMode {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overlap" => Ok(Self::Overlap),
+            "contained" => Ok(Self::Contained),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid query mode: {}. Expected overlap or contained.", s),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let position = |n| Position::try_from(n).unwrap();
+        let interval: Interval = (position(10)..=position(20)).into();
+        let matches =
+            |mode: QueryMode, start, end| mode.matches(interval, position(start), position(end));
+        assert!(matches(QueryMode::Overlap, 5, 15));
+        assert!(matches(QueryMode::Contained, 10, 20));
+        assert!(!matches(QueryMode::Contained, 9, 20));
+        assert!(!matches(QueryMode::Contained, 10, 21));
+        let unbounded: Interval = (position(10)..).into();
+        assert!(QueryMode::Contained.matches(unbounded, position(10), position(1000)));
+        assert!("contained".parse::<QueryMode>().is_ok());
+        assert!("inside".parse::<QueryMode>().is_err());
+    }
+
+    #[test]
+    fn test_chrom_alias() {
+        let ensembl = |name: &str| ["1", "2", "MT"].contains(&name);
+        let ucsc = |name: &str| ["chr1", "chrM"].contains(&name);
+        let alias = ChromAlias::UcscEnsembl;
+        assert_eq!(alias.resolve("chr2", ensembl), "2");
+        assert_eq!(alias.resolve("1", ensembl), "1");
+        assert_eq!(alias.resolve("1", ucsc), "chr1");
+        assert_eq!(alias.resolve("MT", ucsc), "chrM");
+        assert_eq!(alias.resolve("3", ucsc), "3");
+
+        let alias = ChromAlias::Map(HashMap::from([("chrUn".to_string(), "2".to_string())]));
+        assert_eq!(alias.resolve("chrUn", ensembl), "2");
+        assert_eq!(alias.resolve("chr1", ensembl), "chr1");
+
+        let region = parse_region("chr1:1-10").unwrap();
+        let region = alias_region(region, Some(&ChromAlias::UcscEnsembl), ensembl);
+        assert_eq!(region.to_string(), "1:1-10");
+        assert!("ucsc_ensembl".parse::<ChromAlias>().is_ok());
+        assert!("ucsc".parse::<ChromAlias>().is_err());
+    }
+
+    #[test]
+    fn test_parse_region() {
+        let parse = |s| parse_region(s).map(|region| region.to_string());
+        assert_eq!(parse("chr1:1-1000").unwrap(), "chr1:1-1000");
+        assert_eq!(parse("chr1:1,000-2,000").unwrap(), "chr1:1000-2000");
+        assert_eq!(parse(" chr1 ").unwrap(), "chr1");
+        assert_eq!(parse("chr1:500-").unwrap(), "chr1:500");
+        assert_eq!(parse("chr1:500").unwrap(), "chr1:500");
+        let region = parse_region("chr1:500-").unwrap();
+        assert!(region.interval().end().is_none());
+        for s in [
+            "",
+            ":1-10",
+            "chr1:",
+            "chr1:-",
+            "chr1:-10",
+            "chr1:0-10",
+            "chr1:10-5",
+            "chr1:a-b",
+            "chr1:1-2-3",
+        ] {
+            assert!(parse_region(s).is_err(), "{:?}", s);
+        }
+        assert_eq!(
+            parse_regions(&["chr1", "chr2:1-10"])
+                .unwrap()
+                .iter()
+                .map(Region::to_string)
+                .collect::<Vec<_>>(),
+            ["chr1", "chr2:1-10"]
+        );
+        assert!(parse_regions(&["chr1", "chr2:x"]).is_err());
+    }
+
+    #[test]
+    fn test_tile_regions() {
+        let tile = |window, step| {
+            tile_regions([("sq0", 10), ("sq1", 0)], window, step)
+                .map(|regions| regions.iter().map(Region::to_string).collect::<Vec<_>>())
+        };
+        assert_eq!(tile(None, None).unwrap(), ["sq0", "sq1"]);
+        assert_eq!(
+            tile(Some(4), None).unwrap(),
+            ["sq0:1-4", "sq0:5-8", "sq0:9-10"]
+        );
+        assert_eq!(
+            tile(Some(4), Some(3)).unwrap(),
+            ["sq0:1-4", "sq0:4-7", "sq0:7-10"]
+        );
+        assert_eq!(tile(Some(2), Some(5)).unwrap(), ["sq0:1-2", "sq0:6-7"]);
+        assert_eq!(tile(Some(20), None).unwrap(), ["sq0:1-10"]);
+        assert!(tile(None, Some(2)).is_err());
+        assert!(tile(Some(0), None).is_err());
+        assert!(tile(Some(2), Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_regions_from_bed() {
+        let dir = std::env::temp_dir().join(format!("oxbow-regions-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("regions.bed");
+        std::fs::write(
+            &path,
+            "track name=x\nchr1\t0\t100\tname\n# comment\nchr2\t9\t10\n",
+        )
+        .unwrap();
+        let regions: Vec<_> = regions_from_bed(&path)
+            .unwrap()
+            .iter()
+            .map(Region::to_string)
+            .collect();
+        assert_eq!(regions, ["chr1:1-100", "chr2:10-10"]);
+
+        for data in ["chr1\t10\n", "chr1\t10\t10\n", "chr1\tx\t10\n"] {
+            std::fs::write(&path, data).unwrap();
+            assert!(regions_from_bed(&path).is_err(), "{:?}", data);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
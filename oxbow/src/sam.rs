@@ -0,0 +1,443 @@
+//! A reader for the SAM format.
+//!
+//! Records produce the same columns as BAM records, with the same [`BamOptions`].
+//!
+//! SAM files can be queried by region only through an index, which requires BGZF compression
+//! and a tabix or CSI index at `<path>.tbi` or `<path>.csi`, as built by
+//! [`write_tabix`](crate::index::write_tabix) with [`Preset::Sam`](crate::index::Preset::Sam).
+//! Plain SAM files have no index, so querying them fails with an error, unless linear scans are
+//! allowed with [`SamReader::with_linear_scan`]. A linear scan reads every record of the file and
+//! keeps those overlapping the region, so it is only suited to small files.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use arrow::datatypes::Fields;
+use arrow::error::ArrowError;
+use noodles::core::region::Interval;
+use noodles::core::Region;
+use noodles::{bgzf, csi, sam, tabix};
+
+use crate::bam::{
+    check_sorted, filter_interval, filter_records, parse_query_region, BamBatchBuilder, BamOptions,
+};
+use crate::batch_builder::{
+    builder_fields, count_err, provenance, write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES,
+};
+use crate::compression::decompress;
+use crate::region::QueryMode;
+use crate::sampling::sample_records;
+
+/// Loads the tabix or CSI index of a SAM file from `<path>.tbi` or `<path>.csi`, if either exists.
+pub fn index_from_path(path: &str) -> io::Result<Option<csi::Index>> {
+    let tbi_path = format!("{}.tbi", path);
+    let csi_path = format!("{}.csi", path);
+    if Path::new(&tbi_path).exists() {
+        tabix::read(tbi_path).map(Some)
+    } else if Path::new(&csi_path).exists() {
+        csi::read(csi_path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+type Records<'a> = Box<dyn Iterator<Item = Result<sam::alignment::Record, ArrowError>> + 'a>;
+
+/// A SAM reader.
+pub struct SamReader<R> {
+    reader: sam::Reader<R>,
+    header: sam::Header,
+    indexed_reader: Option<(sam::Reader<bgzf::Reader<File>>, csi::Index)>,
+    options: BamOptions,
+    query_mode: QueryMode,
+    linear_scan: bool,
+    path: Option<PathBuf>,
+}
+
+impl SamReader<Box<dyn BufRead>> {
+    /// Creates a SAM reader from a given file path.
+    ///
+    /// Gzip- and BGZF-compressed files are decompressed transparently. A BGZF-compressed file
+    /// with an index at `<path>.tbi` or `<path>.csi` can also be queried by region.
+    pub fn new_from_path(path: &str) -> io::Result<Self> {
+        let file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let mut reader = Self::new(decompress(file)?)?;
+        if let Some(index) = index_from_path(path)? {
+            let mut indexed_reader = sam::Reader::new(bgzf::Reader::new(File::open(path)?));
+            indexed_reader.read_header()?;
+            reader.indexed_reader = Some((indexed_reader, index));
+        }
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> SamReader<R> {
+    /// Creates a SAM reader.
+    ///
+    /// Readers created from a stream can only be queried by region with a linear scan.
+    pub fn new(read: R) -> io::Result<Self> {
+        let mut reader = sam::Reader::new(read);
+        let header = reader.read_header()?;
+        Ok(Self {
+            reader,
+            header,
+            indexed_reader: None,
+            options: BamOptions::default(),
+            query_mode: QueryMode::default(),
+            linear_scan: false,
+            path: None,
+        })
+    }
+
+    /// Returns the index (tabix or CSI) used for queries, if any.
+    pub fn index(&self) -> Option<&csi::Index> {
+        self.indexed_reader.as_ref().map(|(_, index)| index)
+    }
+
+    /// Returns the SAM header, e.g. to reconstruct a writer.
+    pub fn header(&self) -> &sam::Header {
+        &self.header
+    }
+
+    /// Returns the text of the SAM header, one `@` line per record. Its lines are those of the
+    /// file, but grouped by kind, e.g. all `@SQ` lines before the `@RG` lines.
+    pub fn header_text(&self) -> String {
+        self.header.to_string()
+    }
+
+    /// Returns the names of the reference sequences in header order.
+    ///
+    /// These map the indices emitted with [`BamOptions::emit_ref_as_index`] back to names.
+    pub fn reference_names(&self) -> Vec<String> {
+        self.header
+            .reference_sequences()
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Returns the names and lengths of the reference sequences (`@SQ`) in header order.
+    pub fn reference_lengths(&self) -> Vec<(String, u64)> {
+        self.header
+            .reference_sequences()
+            .iter()
+            .map(|(name, reference_sequence)| {
+                (name.to_string(), reference_sequence.length().get() as u64)
+            })
+            .collect()
+    }
+
+    /// Sets the options used when converting records.
+    pub fn with_options(mut self, options: BamOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets how records are matched against query regions, as in
+    /// [`BamReader::with_query_mode`](crate::bam::BamReader::with_query_mode).
+    pub fn with_query_mode(mut self, query_mode: QueryMode) -> Self {
+        self.query_mode = query_mode;
+        self
+    }
+
+    /// Allows querying an unindexed file by reading all of its records and dropping those
+    /// outside the region. Defaults to off, which makes such queries fail. Indexed files are
+    /// always queried through their index.
+    ///
+    /// The scan starts from the current position of the reader, so a reader can be scanned only
+    /// once.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::sam::SamReader;
+    ///
+    /// let mut reader = SamReader::new_from_path("sample.sam")
+    ///     .unwrap()
+    ///     .with_linear_scan(true);
+    /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn with_linear_scan(mut self, linear_scan: bool) -> Self {
+        self.linear_scan = linear_scan;
+        self
+    }
+
+    /// Returns the fields of the batches returned by [`SamReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    ///
+    /// Tag columns, which are typed from the records, are given as Null, and the `tags` struct
+    /// without fields.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(BamBatchBuilder::new(0, &self.header, &self.options)?)
+    }
+
+    /// Returns the records passing the filters, in `region` if given.
+    fn records(&mut self, region: Option<&Region>) -> Result<Records<'_>, ArrowError> {
+        let header = &self.header;
+        let Some(region) = region else {
+            let records = self
+                .reader
+                .records(header)
+                .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+            let records = check_sorted(records, header, &self.options)?;
+            return Ok(Box::new(filter_records(records, &self.options)));
+        };
+        let records: Records<'_> = if let Some((reader, index)) = self.indexed_reader.as_mut() {
+            let query = reader
+                .query(header, index, region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?
+                .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+            Box::new(query)
+        } else if self.linear_scan {
+            let reference_sequence_id = header
+                .reference_sequences()
+                .get_index_of(region.name())
+                .ok_or_else(|| {
+                    ArrowError::InvalidArgumentError(format!(
+                        "Invalid reference sequence name: {}",
+                        region.name()
+                    ))
+                })?;
+            let interval = region.interval();
+            let scan = self
+                .reader
+                .records(header)
+                .filter(move |i| {
+                    i.as_ref().map_or(true, |record| {
+                        record.reference_sequence_id() == Some(reference_sequence_id)
+                            && overlaps(record, interval)
+                    })
+                })
+                .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+            Box::new(scan)
+        } else {
+            return Err(ArrowError::InvalidArgumentError(
+                "Querying a region requires a BGZF-compressed SAM file with a .tbi or .csi index. \
+                 Plain SAM files can only be queried with a linear scan over the whole file, \
+                 which must be allowed explicitly."
+                    .to_string(),
+            ));
+        };
+        let records = filter_interval(records, region.interval(), self.query_mode);
+        Ok(Box::new(filter_records(records, &self.options)))
+    }
+
+    /// Returns the records in the given region as Apache Arrow IPC.
+    ///
+    /// If the region is `None`, all records are returned. See the [module](self) documentation
+    /// for how regions are queried.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::sam::SamReader;
+    ///
+    /// let mut reader = SamReader::new_from_path("sample.sam.gz").unwrap();
+    /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = BamBatchBuilder::new(1024, &self.header, &self.options)?
+            .with_metadata(provenance("sam", self.path.as_deref(), region.as_slice()));
+        let region = region
+            .map(|region| parse_query_region(region, &self.header, &self.options))
+            .transpose()?;
+        let options = &self.options;
+        let (fraction, count, seed) = (
+            options.sample_fraction,
+            options.sample_count,
+            options.sample_seed,
+        );
+        let records = sample_records(self.records(region.as_ref())?, fraction, count, seed)?;
+        write_ipc_err(records, batch_builder)
+    }
+
+    /// Returns the number of records in the given region, without converting them to Arrow.
+    ///
+    /// If the region is `None`, all records are counted. Records rejected by the filters in
+    /// [`BamOptions`] are not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::sam::SamReader;
+    ///
+    /// let mut reader = SamReader::new_from_path("sample.sam").unwrap();
+    /// let n = reader.count(None).unwrap();
+    /// ```
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        let region = region
+            .map(|region| parse_query_region(region, &self.header, &self.options))
+            .transpose()?;
+        count_err(self.records(region.as_ref())?)
+    }
+}
+
+/// Returns whether a record overlaps `interval`. Records without an alignment end span their
+/// alignment start only.
+fn overlaps(record: &sam::alignment::Record, interval: Interval) -> bool {
+    record.alignment_start().is_some_and(|start| {
+        let end = record.alignment_end().unwrap_or(start);
+        interval.intersects(Interval::from(start..=end))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{write_tabix, Preset};
+    use arrow::array::{Array, AsArray, StringArray};
+    use arrow::compute::cast;
+    use arrow::datatypes::{DataType, Int64Type};
+    use arrow::ipc::reader::FileReader;
+
+    fn fixture_path() -> String {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.sam");
+        dir.to_str().unwrap().to_string()
+    }
+
+    fn names(ipc: Vec<u8>) -> Vec<String> {
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let names = record_batch.column_by_name("qname").unwrap();
+        let names = names.as_any().downcast_ref::<StringArray>().unwrap();
+        names.iter().map(|name| name.unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_read_all() {
+        let mut reader = SamReader::new_from_path(&fixture_path()).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        assert_eq!(names(ipc), ["r1", "r2", "r3", "r4", "r5"]);
+        let options = BamOptions {
+            min_mapq: Some(30),
+            ..Default::default()
+        };
+        let mut reader = SamReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options);
+        assert_eq!(reader.count(None).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_require_sorted() {
+        let options = BamOptions {
+            require_sorted: Some(true),
+            ..Default::default()
+        };
+        let mut reader = SamReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_options(options.clone());
+        assert_eq!(reader.count(None).unwrap(), 5);
+
+        let path = fixture_path().replace("sample.sam", "unsorted.sam");
+        let mut reader = SamReader::new_from_path(&path).unwrap();
+        assert_eq!(reader.count(None).unwrap(), 3);
+        let mut reader = SamReader::new_from_path(&path)
+            .unwrap()
+            .with_options(options);
+        let error = reader.records_to_ipc(None).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("record 3 (sq0:150) comes after sq1:50"));
+    }
+
+    #[test]
+    fn test_unindexed_query() {
+        let mut reader = SamReader::new_from_path(&fixture_path()).unwrap();
+        assert!(reader.index().is_none());
+        let error = reader.records_to_ipc(Some("sq0")).unwrap_err();
+        assert!(error.to_string().contains("linear scan"));
+    }
+
+    #[test]
+    fn test_linear_scan() {
+        let scan = |region| {
+            let mut reader = SamReader::new_from_path(&fixture_path())
+                .unwrap()
+                .with_linear_scan(true);
+            names(reader.records_to_ipc(Some(region)).unwrap())
+        };
+        assert_eq!(scan("sq0:105-155"), ["r1", "r2"]);
+        // r2 spans 150-161, over its deletion.
+        assert_eq!(scan("sq0:160-300"), ["r2"]);
+        assert_eq!(scan("sq1"), ["r4"]);
+
+        let mut reader = SamReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_linear_scan(true)
+            .with_query_mode(QueryMode::Contained);
+        assert_eq!(reader.count(Some("sq0:100-160")).unwrap(), 1);
+        let mut reader = SamReader::new_from_path(&fixture_path())
+            .unwrap()
+            .with_linear_scan(true);
+        assert!(reader.count(Some("sq2")).is_err());
+    }
+
+    #[test]
+    fn test_indexed_query() {
+        let dir = std::env::temp_dir().join(format!("oxbow-sam-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sample.sam.gz");
+        let mut writer = bgzf::Writer::new(File::create(&path).unwrap());
+        io::Write::write_all(&mut writer, &std::fs::read(fixture_path()).unwrap()).unwrap();
+        writer.finish().unwrap();
+        let path = path.to_str().unwrap();
+        write_tabix(path, Some(Preset::Sam)).unwrap();
+
+        let mut reader = SamReader::new_from_path(path).unwrap();
+        assert!(reader.index().is_some());
+        let ipc = reader.records_to_ipc(Some("sq0:105-155")).unwrap();
+        assert_eq!(names(ipc), ["r1", "r2"]);
+        assert_eq!(reader.count(Some("sq1")).unwrap(), 1);
+        assert_eq!(reader.count(None).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_unsigned_positions() {
+        let read = |sam: String, unsigned| {
+            let options = BamOptions {
+                unsigned_positions: Some(unsigned),
+                ..Default::default()
+            };
+            let mut reader = SamReader::new(sam.as_bytes())
+                .unwrap()
+                .with_options(options);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let batch = arrow_reader.next().unwrap().unwrap();
+            ["pos", "pnext", "end"].map(|name| {
+                let array = batch.column_by_name(name).unwrap();
+                let values = cast(array, &DataType::Int64).unwrap();
+                let value = values.as_primitive::<Int64Type>().value(0);
+                (array.data_type().clone(), value)
+            })
+        };
+        let sam = |length: u64, pos: u64| {
+            format!(
+                "@SQ\tSN:sq0\tLN:{}\nr0\t0\tsq0\t{}\t60\t4M\t=\t{}\t0\tACGT\t*\tNM:i:0\n",
+                length, pos, pos
+            )
+        };
+        let [pos, pnext, end] = read(sam(100, 10), false);
+        assert_eq!(
+            [pos, pnext, end],
+            [
+                (DataType::Int32, 10),
+                (DataType::Int32, 10),
+                (DataType::Int32, 13)
+            ]
+        );
+        let [pos, _, end] = read(sam(100, 10), true);
+        assert_eq!([pos, end], [(DataType::UInt32, 10), (DataType::UInt32, 13)]);
+        // Positions past 2^31 on a 3 Gb reference sequence don't overflow.
+        let pos = 2_500_000_000;
+        let [pos_column, pnext, end] = read(sam(3_000_000_000, pos), false);
+        assert_eq!(pos_column, (DataType::Int64, pos as i64));
+        assert_eq!(pnext, (DataType::Int64, pos as i64));
+        assert_eq!(end, (DataType::Int64, pos as i64 + 3));
+        let [pos_column, _, _] = read(sam(3_000_000_000, pos), true);
+        assert_eq!(pos_column, (DataType::UInt64, pos as i64));
+    }
+}
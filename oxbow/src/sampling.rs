@@ -0,0 +1,124 @@
+//! Random subsampling of records.
+//!
+//! Scans can keep each record with a given probability, or an exact number of records chosen
+//! uniformly with reservoir sampling. Both are driven by a seeded generator, so that the same seed
+//! always selects the same records of a file.
+use arrow::error::ArrowError;
+
+/// A SplitMix64 pseudo-random number generator.
+///
+/// It is small and fast, and its output is fixed for a seed, unlike that of generators whose
+/// algorithm may change between releases.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniform value in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a uniform value in `[0, n)`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+/// Subsamples `records`, keeping each with probability `fraction`, or `count` of them chosen
+/// uniformly, in their original order. Without either, all records are kept. The generator is
+/// seeded with `seed`, or 0.
+///
+/// Reservoir sampling with `count` reads all the records before returning the first one, and
+/// fails on the first error. Otherwise errors are passed through.
+pub(crate) fn sample_records<'a, T: 'a>(
+    records: impl Iterator<Item = Result<T, ArrowError>> + 'a,
+    fraction: Option<f64>,
+    count: Option<usize>,
+    seed: Option<u64>,
+) -> Result<Box<dyn Iterator<Item = Result<T, ArrowError>> + 'a>, ArrowError> {
+    let mut rng = Rng::new(seed.unwrap_or(0));
+    match (fraction, count) {
+        (Some(_), Some(_)) => Err(ArrowError::InvalidArgumentError(
+            "Only one of a sample fraction and a sample count can be given.".to_string(),
+        )),
+        (Some(fraction), None) if !(0.0..=1.0).contains(&fraction) => {
+            Err(ArrowError::InvalidArgumentError(format!(
+                "Invalid sample fraction: {}. Expected a value from 0 to 1.",
+                fraction
+            )))
+        }
+        (Some(fraction), None) => Ok(Box::new(records.filter(move |i| {
+            // Errors are kept without drawing, so that they don't shift the sample.
+            i.is_err() || rng.next_f64() < fraction
+        }))),
+        (None, Some(count)) => {
+            let mut reservoir = Vec::with_capacity(count.min(1 << 16));
+            for (i, record) in records.enumerate() {
+                let record = record?;
+                if reservoir.len() < count {
+                    reservoir.push((i, record));
+                } else {
+                    let j = rng.below(i as u64 + 1) as usize;
+                    if j < count {
+                        reservoir[j] = (i, record);
+                    }
+                }
+            }
+            reservoir.sort_unstable_by_key(|(i, _)| *i);
+            Ok(Box::new(
+                reservoir.into_iter().map(|(_, record)| Ok(record)),
+            ))
+        }
+        (None, None) => Ok(Box::new(records)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(fraction: Option<f64>, count: Option<usize>, seed: Option<u64>) -> Vec<usize> {
+        let records = (0..10_000).map(Ok);
+        sample_records(records, fraction, count, seed)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sample_fraction() {
+        let kept = sample(Some(0.1), None, Some(42));
+        assert!((900..1100).contains(&kept.len()), "{}", kept.len());
+        assert!(kept.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(kept, sample(Some(0.1), None, Some(42)));
+        assert_ne!(kept, sample(Some(0.1), None, Some(7)));
+
+        assert!(sample(Some(0.0), None, None).is_empty());
+        assert_eq!(sample(Some(1.0), None, None).len(), 10_000);
+        assert!(sample_records((0..1).map(Ok), Some(1.5), None, None).is_err());
+    }
+
+    #[test]
+    fn test_sample_count() {
+        let kept = sample(None, Some(100), Some(42));
+        assert_eq!(kept.len(), 100);
+        assert!(kept.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(kept, sample(None, Some(100), Some(42)));
+        // The sample spans the whole input.
+        assert!(kept.last().unwrap() - kept.first().unwrap() > 5_000);
+
+        assert_eq!(sample(None, Some(20_000), None).len(), 10_000);
+        assert!(sample_records((0..1).map(Ok), Some(0.5), Some(1), None).is_err());
+    }
+}
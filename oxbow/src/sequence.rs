@@ -0,0 +1,532 @@
+//! Summary columns computed from sequences as records are read, and encodings of the sequences
+//! themselves.
+use std::collections::HashSet;
+use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanBuilder, FixedSizeListBuilder, Float32Builder, GenericStringBuilder,
+    Int32Builder, ListBuilder, UInt32Builder, UInt64Builder, UInt8Builder,
+};
+use arrow::error::ArrowError;
+
+/// Returns the GC fraction of a sequence, ignoring `N` bases.
+///
+/// Returns `None` if the sequence is empty or all `N`.
+pub fn gc_content(sequence: &[u8]) -> Option<f32> {
+    let (mut gc, mut called) = (0usize, 0usize);
+    for base in sequence {
+        match base {
+            b'G' | b'C' | b'g' | b'c' => {
+                gc += 1;
+                called += 1;
+            }
+            b'N' | b'n' => {}
+            _ => called += 1,
+        }
+    }
+    (called > 0).then(|| gc as f32 / called as f32)
+}
+
+/// Returns the fractions of `N` (in either case) and of lowercase, i.e. soft-masked, bases of a
+/// sequence, in a single pass. Both are 0 for an empty sequence.
+///
+/// # Examples
+///
+/// ```
+/// use oxbow::sequence::masked_fractions;
+///
+/// assert_eq!(masked_fractions(b"ACnNacgt"), (0.25, 0.625));
+/// ```
+pub fn masked_fractions(sequence: &[u8]) -> (f32, f32) {
+    let (mut n, mut lowercase) = (0usize, 0usize);
+    for base in sequence {
+        n += matches!(base, b'N' | b'n') as usize;
+        lowercase += base.is_ascii_lowercase() as usize;
+    }
+    let length = sequence.len().max(1) as f32;
+    (n as f32 / length, lowercase as f32 / length)
+}
+
+/// Builds the optional `length` and `gc_content` columns.
+pub(crate) struct SequenceStatsBuilder {
+    length: Option<UInt64Builder>,
+    gc_content: Option<Float32Builder>,
+}
+
+impl SequenceStatsBuilder {
+    pub fn new(include_length: bool, include_gc: bool) -> Self {
+        Self {
+            length: include_length.then(UInt64Builder::new),
+            gc_content: include_gc.then(Float32Builder::new),
+        }
+    }
+
+    pub fn push(&mut self, sequence: &[u8]) {
+        if let Some(builder) = self.length.as_mut() {
+            builder.append_value(sequence.len() as u64);
+        }
+        if let Some(builder) = self.gc_content.as_mut() {
+            builder.append_option(gc_content(sequence));
+        }
+    }
+
+    pub fn finish(&mut self) -> Vec<(&'static str, ArrayRef)> {
+        let mut columns = Vec::new();
+        if let Some(builder) = self.length.as_mut() {
+            columns.push(("length", Arc::new(builder.finish()) as ArrayRef));
+        }
+        if let Some(builder) = self.gc_content.as_mut() {
+            columns.push(("gc_content", Arc::new(builder.finish()) as ArrayRef));
+        }
+        columns
+    }
+}
+
+/// Returns the k-mers of a sequence, from windows of `k` bases starting every `stride` bases.
+///
+/// Trailing bases that don't fill a whole window are dropped, so a sequence shorter than `k` has
+/// no k-mers. Unless `allow_ambiguous` is set, windows with a base other than A, C, G or T (in
+/// either case) are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use oxbow::sequence::kmers;
+///
+/// let kmers: Vec<_> = kmers(b"ACGTNACGT", 3, 2, false).collect();
+/// assert_eq!(kmers, [&b"ACG"[..], b"CGT"]);
+/// ```
+pub fn kmers(
+    sequence: &[u8],
+    k: usize,
+    stride: usize,
+    allow_ambiguous: bool,
+) -> impl Iterator<Item = &[u8]> {
+    let n = (sequence.len() + 1).saturating_sub(k);
+    (0..n)
+        .step_by(stride)
+        .map(move |start| &sequence[start..start + k])
+        .filter(move |kmer| allow_ambiguous || kmer.iter().all(|&base| two_bit(base).is_some()))
+}
+
+/// Returns whether a sequence of `length` bases passes the optional `min_length` and
+/// `max_length` filters, both inclusive.
+pub(crate) fn within_length(
+    length: usize,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+) -> bool {
+    min_length.map_or(true, |min| length >= min) && max_length.map_or(true, |max| length <= max)
+}
+
+/// Returns the 32-bit FNV-1a hash of an uppercased k-mer.
+pub fn hash_kmer(kmer: &[u8]) -> u32 {
+    kmer.iter().fold(0x811c9dc5, |hash, &base| {
+        (hash ^ base.to_ascii_uppercase() as u32).wrapping_mul(0x01000193)
+    })
+}
+
+enum KmerList {
+    Utf8(ListBuilder<GenericStringBuilder<i32>>),
+    Hashed(ListBuilder<UInt32Builder>),
+}
+
+/// Builds the optional `kmers` column, as lists of uppercased k-mers (List<Utf8>) or of their
+/// hashes (List<UInt32>), see [`kmers`] and [`hash_kmer`].
+pub(crate) struct KmerColumnBuilder {
+    k: usize,
+    stride: usize,
+    allow_ambiguous: bool,
+    inner: KmerList,
+}
+
+impl KmerColumnBuilder {
+    /// Returns a builder for k-mers of `k` bases every `stride` (by default, 1) bases, or `None`
+    /// without `k`.
+    pub fn new(
+        k: Option<usize>,
+        stride: Option<usize>,
+        allow_ambiguous: bool,
+        hashed: bool,
+    ) -> Result<Option<Self>, ArrowError> {
+        let Some(k) = k else {
+            return Ok(None);
+        };
+        let stride = stride.unwrap_or(1);
+        if k == 0 || stride == 0 {
+            return Err(ArrowError::InvalidArgumentError(
+                "The k-mer size and stride must be positive.".to_string(),
+            ));
+        }
+        let inner = if hashed {
+            KmerList::Hashed(ListBuilder::new(UInt32Builder::new()))
+        } else {
+            KmerList::Utf8(ListBuilder::new(GenericStringBuilder::<i32>::new()))
+        };
+        Ok(Some(Self {
+            k,
+            stride,
+            allow_ambiguous,
+            inner,
+        }))
+    }
+
+    pub fn push(&mut self, sequence: &[u8]) {
+        let kmers = kmers(sequence, self.k, self.stride, self.allow_ambiguous);
+        match &mut self.inner {
+            KmerList::Utf8(builder) => {
+                for kmer in kmers {
+                    builder
+                        .values()
+                        .append_value(String::from_utf8_lossy(kmer).to_ascii_uppercase());
+                }
+                builder.append(true);
+            }
+            KmerList::Hashed(builder) => {
+                for kmer in kmers {
+                    builder.values().append_value(hash_kmer(kmer));
+                }
+                builder.append(true);
+            }
+        }
+    }
+
+    pub fn finish(&mut self) -> (&'static str, ArrayRef) {
+        let array = match &mut self.inner {
+            KmerList::Utf8(builder) => Arc::new(builder.finish()) as ArrayRef,
+            KmerList::Hashed(builder) => Arc::new(builder.finish()) as ArrayRef,
+        };
+        ("kmers", array)
+    }
+}
+
+/// Returns the bases matched by an IUPAC nucleotide code, in either case, as a mask of A, C, G
+/// and T (1, 2, 4 and 8, i.e. 1 shifted by [`two_bit`]). `U` is read as `T`.
+fn iupac_mask(code: u8) -> Option<u8> {
+    let mask = match code.to_ascii_uppercase() {
+        b'A' => 0b0001,
+        b'C' => 0b0010,
+        b'G' => 0b0100,
+        b'T' | b'U' => 0b1000,
+        b'R' => 0b0101,
+        b'Y' => 0b1010,
+        b'S' => 0b0110,
+        b'W' => 0b1001,
+        b'K' => 0b1100,
+        b'M' => 0b0011,
+        b'B' => 0b1110,
+        b'D' => 0b1101,
+        b'H' => 0b1011,
+        b'V' => 0b0111,
+        b'N' => 0b1111,
+        _ => return None,
+    };
+    Some(mask)
+}
+
+/// Parses a motif of IUPAC nucleotide codes into the bases matched at each of its positions.
+fn motif_masks(motif: &str) -> Result<Vec<u8>, ArrowError> {
+    let masks: Option<Vec<u8>> = motif.bytes().map(iupac_mask).collect();
+    match masks {
+        Some(masks) if !masks.is_empty() => Ok(masks),
+        _ => Err(ArrowError::InvalidArgumentError(format!(
+            "Invalid motif: {}. Expected IUPAC nucleotide codes.",
+            motif
+        ))),
+    }
+}
+
+/// Returns the number of occurrences of a motif, as masks of the bases matched at each position,
+/// overlapping ones included.
+fn count_masks(sequence: &[u8], masks: &[u8]) -> usize {
+    sequence
+        .windows(masks.len())
+        .filter(|window| {
+            window
+                .iter()
+                .zip(masks)
+                .all(|(&base, &mask)| two_bit(base).is_some_and(|bits| (1 << bits) & mask != 0))
+        })
+        .count()
+}
+
+/// Returns the number of occurrences of `motif` in a sequence, overlapping ones included.
+///
+/// Both are read in either case. The motif can use IUPAC ambiguity codes, e.g. `W` for A or T,
+/// while bases of the sequence other than A, C, G and T match nothing, not even `N`.
+///
+/// # Examples
+///
+/// ```
+/// use oxbow::sequence::count_motif;
+///
+/// assert_eq!(count_motif(b"ACGCGcg", "CG").unwrap(), 3);
+/// assert_eq!(count_motif(b"CCAGGxCCTGG", "ccwgg").unwrap(), 2);
+/// ```
+pub fn count_motif(sequence: &[u8], motif: &str) -> Result<usize, ArrowError> {
+    Ok(count_masks(sequence, &motif_masks(motif)?))
+}
+
+/// Builds the optional motif count columns (Int32), one per motif, named after the uppercased
+/// motif with a `_count` suffix, e.g. `CG_count`. See [`count_motif`].
+pub(crate) struct MotifCountsBuilder {
+    motifs: Vec<(String, Vec<u8>)>,
+    counts: Vec<Int32Builder>,
+}
+
+impl MotifCountsBuilder {
+    /// Returns a builder counting `motifs`, or `None` without them.
+    pub fn new(motifs: Option<&[String]>) -> Result<Option<Self>, ArrowError> {
+        let Some(motifs) = motifs else {
+            return Ok(None);
+        };
+        let mut names = HashSet::new();
+        let motifs = motifs
+            .iter()
+            .map(|motif| {
+                let name = format!("{}_count", motif.to_ascii_uppercase());
+                if !names.insert(name.clone()) {
+                    return Err(ArrowError::InvalidArgumentError(format!(
+                        "Duplicate motif: {}",
+                        motif
+                    )));
+                }
+                Ok((name, motif_masks(motif)?))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let counts = motifs.iter().map(|_| Int32Builder::new()).collect();
+        Ok(Some(Self { motifs, counts }))
+    }
+
+    pub fn push(&mut self, sequence: &[u8]) {
+        for ((_, masks), builder) in self.motifs.iter().zip(&mut self.counts) {
+            builder.append_value(count_masks(sequence, masks) as i32);
+        }
+    }
+
+    pub fn finish(&mut self) -> Vec<(&str, ArrayRef)> {
+        self.motifs
+            .iter()
+            .zip(&mut self.counts)
+            .map(|((name, _), builder)| (name.as_str(), Arc::new(builder.finish()) as ArrayRef))
+            .collect()
+    }
+}
+
+/// How the `sequence` column is encoded.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SequenceEncoding {
+    /// The sequence as a string.
+    #[default]
+    Utf8,
+    /// The bases packed four to a byte (List<UInt8>), as with [`encode_two_bit`], plus a
+    /// `sequence_mask` column (List<Boolean>) flagging the bases other than A, C, G and T.
+    TwoBit,
+    /// One `[A, C, G, T]` indicator per base (List<FixedSizeList<UInt8, 4>>). Other bases are
+    /// all zeros.
+    OneHot,
+}
+
+impl FromStr for SequenceEncoding {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Utf8" => Ok(Self::Utf8),
+            "TwoBit" => Ok(Self::TwoBit),
+            "OneHot" => Ok(Self::OneHot),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid sequence encoding: {}. Expected Utf8, TwoBit or OneHot.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// Returns the 2-bit code of a base, `A` = 0, `C` = 1, `G` = 2 and `T` = 3, ignoring case.
+fn two_bit(base: u8) -> Option<u8> {
+    match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+/// Packs a sequence four bases to a byte, the first base in the highest bits, and returns it
+/// with a mask of the bases other than A, C, G and T, which are packed as A.
+///
+/// # Examples
+///
+/// ```
+/// use oxbow::sequence::{decode_two_bit, encode_two_bit};
+///
+/// let (packed, mask) = encode_two_bit(b"ACGTN");
+/// assert_eq!(packed, [0b00011011, 0b00000000]);
+/// assert_eq!(mask, [false, false, false, false, true]);
+/// assert_eq!(decode_two_bit(&packed, &mask), b"ACGTN");
+/// ```
+pub fn encode_two_bit(sequence: &[u8]) -> (Vec<u8>, Vec<bool>) {
+    let mut packed = vec![0; sequence.len().div_ceil(4)];
+    let mut mask = Vec::with_capacity(sequence.len());
+    for (i, &base) in sequence.iter().enumerate() {
+        let code = two_bit(base);
+        packed[i / 4] |= code.unwrap_or(0) << (6 - 2 * (i % 4));
+        mask.push(code.is_none());
+    }
+    (packed, mask)
+}
+
+/// Unpacks a sequence packed by [`encode_two_bit`], with one mask flag per base. Masked bases
+/// are returned as `N`.
+pub fn decode_two_bit(packed: &[u8], mask: &[bool]) -> Vec<u8> {
+    mask.iter()
+        .enumerate()
+        .map(|(i, &masked)| {
+            if masked {
+                return b'N';
+            }
+            b"ACGT"[((packed[i / 4] >> (6 - 2 * (i % 4))) & 0b11) as usize]
+        })
+        .collect()
+}
+
+/// Builds the `sequence` column in a [`SequenceEncoding`].
+pub(crate) enum SequenceColumnBuilder {
+    Utf8(GenericStringBuilder<i32>),
+    TwoBit {
+        packed: ListBuilder<UInt8Builder>,
+        mask: ListBuilder<BooleanBuilder>,
+    },
+    OneHot(ListBuilder<FixedSizeListBuilder<UInt8Builder>>),
+}
+
+impl SequenceColumnBuilder {
+    pub fn new(encoding: SequenceEncoding) -> Self {
+        match encoding {
+            SequenceEncoding::Utf8 => Self::Utf8(GenericStringBuilder::<i32>::new()),
+            SequenceEncoding::TwoBit => Self::TwoBit {
+                packed: ListBuilder::new(UInt8Builder::new()),
+                mask: ListBuilder::new(BooleanBuilder::new()),
+            },
+            SequenceEncoding::OneHot => Self::OneHot(ListBuilder::new(FixedSizeListBuilder::new(
+                UInt8Builder::new(),
+                4,
+            ))),
+        }
+    }
+
+    pub fn push(&mut self, sequence: &[u8]) {
+        match self {
+            Self::Utf8(builder) => builder.append_value(String::from_utf8_lossy(sequence)),
+            Self::TwoBit { packed, mask } => {
+                let (bases, masked) = encode_two_bit(sequence);
+                packed.values().append_slice(&bases);
+                packed.append(true);
+                mask.values().append_slice(&masked);
+                mask.append(true);
+            }
+            Self::OneHot(builder) => {
+                let bases = builder.values();
+                for &base in sequence {
+                    let code = two_bit(base);
+                    for i in 0..4 {
+                        bases.values().append_value(u8::from(code == Some(i)));
+                    }
+                    bases.append(true);
+                }
+                builder.append(true);
+            }
+        }
+    }
+
+    pub fn finish(&mut self) -> Vec<(&'static str, ArrayRef)> {
+        match self {
+            Self::Utf8(builder) => vec![("sequence", Arc::new(builder.finish()) as ArrayRef)],
+            Self::TwoBit { packed, mask } => vec![
+                ("sequence", Arc::new(packed.finish()) as ArrayRef),
+                ("sequence_mask", Arc::new(mask.finish()) as ArrayRef),
+            ],
+            Self::OneHot(builder) => vec![("sequence", Arc::new(builder.finish()) as ArrayRef)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gc_content() {
+        assert_eq!(gc_content(b"GGCC"), Some(1.0));
+        assert_eq!(gc_content(b"ACgt"), Some(0.5));
+        assert_eq!(gc_content(b"GCNNAT"), Some(0.5));
+        assert_eq!(gc_content(b"NNnn"), None);
+        assert_eq!(gc_content(b""), None);
+    }
+
+    #[test]
+    fn test_count_motif() {
+        // Overlapping CpG sites.
+        assert_eq!(count_motif(b"CGCGCG", "CG").unwrap(), 3);
+        assert_eq!(count_motif(b"AAAA", "AA").unwrap(), 3);
+        assert_eq!(count_motif(b"cgCG", "Cg").unwrap(), 2);
+        assert_eq!(count_motif(b"C", "CG").unwrap(), 0);
+        // W is A or T.
+        assert_eq!(count_motif(b"CCAGGCCTGGCCGGG", "CCWGG").unwrap(), 2);
+        // N matches any base of the sequence, but ambiguous bases match nothing.
+        assert_eq!(count_motif(b"ACNTANT", "ANT").unwrap(), 0);
+        assert_eq!(count_motif(b"ACTAGT", "ANT").unwrap(), 2);
+        assert!(count_motif(b"ACGT", "CX").is_err());
+        assert!(count_motif(b"ACGT", "").is_err());
+        assert!(MotifCountsBuilder::new(Some(&["CG".into(), "cg".into()])).is_err());
+    }
+
+    #[test]
+    fn test_two_bit_round_trip() {
+        for sequence in [&b""[..], b"A", b"ACGTACGTA", b"NNACgtRYN", b"acgt"] {
+            let (packed, mask) = encode_two_bit(sequence);
+            assert_eq!(packed.len(), sequence.len().div_ceil(4));
+            let expected: Vec<u8> = sequence
+                .iter()
+                .map(|&base| match base.to_ascii_uppercase() {
+                    base @ (b'A' | b'C' | b'G' | b'T') => base,
+                    _ => b'N',
+                })
+                .collect();
+            assert_eq!(decode_two_bit(&packed, &mask), expected);
+        }
+        let (_, mask) = encode_two_bit(b"ANCRT");
+        assert_eq!(mask, [false, true, false, true, false]);
+        assert!("2bit".parse::<SequenceEncoding>().is_err());
+        assert_eq!(
+            "OneHot".parse::<SequenceEncoding>().unwrap(),
+            SequenceEncoding::OneHot
+        );
+    }
+
+    #[test]
+    fn test_kmers() {
+        let all = |sequence, k, stride, allow_ambiguous| -> Vec<&[u8]> {
+            kmers(sequence, k, stride, allow_ambiguous).collect()
+        };
+        assert_eq!(
+            all(&b"ACGTACG"[..], 3, 1, false),
+            [&b"ACG"[..], b"CGT", b"GTA", b"TAC", b"ACG"]
+        );
+        // The trailing `G` doesn't fill a window.
+        assert_eq!(all(&b"ACGTACG"[..], 3, 3, false), [&b"ACG"[..], b"TAC"]);
+        assert_eq!(all(&b"ACNTA"[..], 2, 1, false), [&b"AC"[..], b"TA"]);
+        assert_eq!(all(&b"ACNTA"[..], 2, 1, true).len(), 4);
+        assert!(all(&b"AC"[..], 3, 1, false).is_empty());
+        assert_eq!(hash_kmer(b"acg"), hash_kmer(b"ACG"));
+        assert_ne!(hash_kmer(b"ACG"), hash_kmer(b"ACT"));
+        assert!(KmerColumnBuilder::new(Some(0), None, false, false).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! Validation of coordinate-sorted input.
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+/// Checks that records come sorted by reference sequence, then by start position.
+///
+/// The records of each reference sequence must be contiguous. When the order of the reference
+/// sequences is known, e.g. from a header, they must also come in that order. Reference sequences
+/// missing from it only need to be contiguous.
+#[derive(Debug, Default)]
+pub(crate) struct SortChecker {
+    /// The position of each reference sequence in the known order.
+    ranks: HashMap<String, usize>,
+    /// The reference sequences whose records have ended.
+    done: HashSet<String>,
+    /// The reference sequence and position of the previous record.
+    last: Option<(String, usize)>,
+    /// The rank of the last reference sequence seen in the known order.
+    last_rank: Option<usize>,
+    /// The number of records checked.
+    n: usize,
+}
+
+impl SortChecker {
+    /// Creates a checker for reference sequences expected in the order of `names`.
+    pub fn with_order<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let ranks = names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name.to_string(), i))
+            .collect();
+        Self {
+            ranks,
+            ..Default::default()
+        }
+    }
+
+    /// Checks the next record, at `position` on `chrom`.
+    ///
+    /// The error names the record by `line`, if given, or by its 1-based number among the records
+    /// checked.
+    pub fn check(&mut self, chrom: &str, position: usize, line: Option<usize>) -> io::Result<()> {
+        self.n += 1;
+        let rank = self.ranks.get(chrom).copied();
+        let Some((last_chrom, last_position)) = &mut self.last else {
+            self.last = Some((chrom.to_string(), position));
+            self.last_rank = rank;
+            return Ok(());
+        };
+        let unsorted = if last_chrom == chrom {
+            position < *last_position
+        } else {
+            rank.zip(self.last_rank)
+                .is_some_and(|(rank, last_rank)| rank < last_rank)
+                || self.done.contains(chrom)
+        };
+        if unsorted {
+            let record = match line {
+                Some(line) => format!("line {}", line),
+                None => format!("record {}", self.n),
+            };
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Unsorted input: {} ({}:{}) comes after {}:{}.",
+                    record, chrom, position, last_chrom, last_position
+                ),
+            ));
+        }
+        if last_chrom != chrom {
+            self.done
+                .insert(std::mem::replace(last_chrom, chrom.to_string()));
+            self.last_rank = rank.or(self.last_rank);
+        }
+        *last_position = position;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let mut checker = SortChecker::default();
+        for (chrom, position) in [("b", 1), ("b", 1), ("b", 5), ("a", 2)] {
+            checker.check(chrom, position, None).unwrap();
+        }
+        let error = checker.check("a", 1, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsorted input: record 5 (a:1) comes after a:2."
+        );
+
+        // A reference sequence can't come back.
+        let mut checker = SortChecker::default();
+        checker.check("a", 1, None).unwrap();
+        checker.check("b", 1, None).unwrap();
+        let error = checker.check("a", 5, Some(7)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsorted input: line 7 (a:5) comes after b:1."
+        );
+
+        // Known reference sequences must come in order, others anywhere.
+        let mut checker = SortChecker::with_order(["a", "b"]);
+        checker.check("c", 1, None).unwrap();
+        checker.check("a", 1, None).unwrap();
+        checker.check("d", 1, None).unwrap();
+        checker.check("b", 1, None).unwrap();
+        let mut checker = SortChecker::with_order(["a", "b"]);
+        checker.check("b", 1, None).unwrap();
+        checker.check("c", 1, None).unwrap();
+        assert!(checker.check("a", 1, None).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! Statistics of the coordinate columns of each batch, e.g. to set the column statistics of
+//! Parquet row groups without reading the records again.
+//!
+//! The IPC files returned by the readers record the minimum and maximum of the reference sequence
+//! and position columns of each of their batches, in the custom metadata of the file footer, with
+//! keys like `oxbow.stats.0.pos.min`. [`read_stats`] reads them back. Files renamed with
+//! [`crate::naming::rename_columns`] keep the statistics under the new column names.
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+use arrow::array::{Array, AsArray};
+use arrow::compute::kernels::aggregate::{max, max_string, min, min_string};
+use arrow::compute::kernels::cast::cast;
+use arrow::datatypes::{DataType, Int64Type, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+
+/// The columns with statistics, whichever of them a batch has: the reference sequence (`chrom`,
+/// `rname` or `seqid`) and the positions of the records.
+pub const COORDINATE_COLUMNS: [&str; 6] = ["chrom", "rname", "seqid", "pos", "start", "end"];
+
+/// A minimum or maximum value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatValue {
+    /// A value of an integer column, such as a position or a reference sequence index.
+    Int(i64),
+    /// A value of a string column, such as a reference sequence name, compared bytewise.
+    Utf8(String),
+}
+
+impl std::fmt::Display for StatValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Utf8(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The minimum and maximum of a column of a batch, ignoring nulls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnStats {
+    pub name: String,
+    pub min: StatValue,
+    pub max: StatValue,
+}
+
+/// Returns the statistics of the coordinate columns of a batch, in the order of
+/// [`COORDINATE_COLUMNS`]. Columns that are missing, have only nulls or are not of an integer or
+/// string type are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use arrow::array::{ArrayRef, Int32Array};
+/// use arrow::record_batch::RecordBatch;
+/// use oxbow::stats::{batch_stats, StatValue};
+///
+/// let pos = Arc::new(Int32Array::from(vec![Some(30), None, Some(10)])) as ArrayRef;
+/// let batch = RecordBatch::try_from_iter([("pos", pos)]).unwrap();
+/// let stats = batch_stats(&batch);
+/// assert_eq!(stats[0].min, StatValue::Int(10));
+/// assert_eq!(stats[0].max, StatValue::Int(30));
+/// ```
+pub fn batch_stats(batch: &RecordBatch) -> Vec<ColumnStats> {
+    COORDINATE_COLUMNS
+        .iter()
+        .filter_map(|name| {
+            let (min, max) = column_bounds(batch.column_by_name(name)?.as_ref())?;
+            Some(ColumnStats {
+                name: name.to_string(),
+                min,
+                max,
+            })
+        })
+        .collect()
+}
+
+/// Returns the minimum and maximum of an integer or string column, or `None` for other types or
+/// a column of nulls.
+fn column_bounds(array: &dyn Array) -> Option<(StatValue, StatValue)> {
+    match array.data_type() {
+        DataType::Dictionary(_, value_type) if value_type.as_ref() == &DataType::Utf8 => {
+            column_bounds(cast(array, &DataType::Utf8).ok()?.as_ref())
+        }
+        DataType::Utf8 => {
+            let array = array.as_string::<i32>();
+            let bounds = (min_string(array)?, max_string(array)?);
+            Some((
+                StatValue::Utf8(bounds.0.to_string()),
+                StatValue::Utf8(bounds.1.to_string()),
+            ))
+        }
+        data_type if data_type.is_integer() => {
+            let array = cast(array, &DataType::Int64).ok()?;
+            let array = array.as_primitive::<Int64Type>();
+            Some((StatValue::Int(min(array)?), StatValue::Int(max(array)?)))
+        }
+        _ => None,
+    }
+}
+
+/// Records the statistics of the batch at `index` of the file in the footer metadata.
+pub(crate) fn write_stats<W: std::io::Write>(
+    writer: &mut FileWriter<W>,
+    index: usize,
+    batch: &RecordBatch,
+) {
+    write_column_stats(writer, index, batch_stats(batch));
+}
+
+/// Records already computed statistics of the batch at `index` of the file in the footer
+/// metadata, under the names of their [`ColumnStats`].
+pub(crate) fn write_column_stats<W: std::io::Write>(
+    writer: &mut FileWriter<W>,
+    index: usize,
+    stats: impl IntoIterator<Item = ColumnStats>,
+) {
+    for stats in stats {
+        let key = format!("oxbow.stats.{}.{}", index, stats.name);
+        writer.write_metadata(format!("{}.min", key), stats.min.to_string());
+        writer.write_metadata(format!("{}.max", key), stats.max.to_string());
+    }
+}
+
+/// Returns the statistics of each batch of an IPC file returned by a reader, as recorded in its
+/// footer, without reading the batches.
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::bam::BamReader;
+/// use oxbow::stats::read_stats;
+///
+/// let mut reader = BamReader::new_from_path("sample.bam").unwrap();
+/// let ipc = reader.records_to_ipc(None).unwrap();
+/// for (i, stats) in read_stats(std::io::Cursor::new(ipc)).unwrap().iter().enumerate() {
+///     println!("batch {}: {:?}", i, stats);
+/// }
+/// ```
+pub fn read_stats<R: Read + Seek>(ipc: R) -> Result<Vec<Vec<ColumnStats>>, ArrowError> {
+    let reader = FileReader::try_new(ipc, None)?;
+    let (num_batches, schema) = (reader.num_batches(), reader.schema());
+    (0..num_batches)
+        .map(|index| parse_stats(reader.custom_metadata(), &schema, index))
+        .collect()
+}
+
+/// Parses the recorded statistics of the batch at `index`, in the order of the columns of
+/// `schema` and typed after them. Columns renamed with [`crate::naming::rename_columns`] keep
+/// their statistics under their new names.
+fn parse_stats(
+    metadata: &HashMap<String, String>,
+    schema: &Schema,
+    index: usize,
+) -> Result<Vec<ColumnStats>, ArrowError> {
+    let mut stats = Vec::new();
+    for field in schema.fields() {
+        let name = field.name();
+        let key = format!("oxbow.stats.{}.{}", index, name);
+        let (Some(min), Some(max)) = (
+            metadata.get(&format!("{}.min", key)),
+            metadata.get(&format!("{}.max", key)),
+        ) else {
+            continue;
+        };
+        let is_integer = field.data_type().is_integer();
+        let parse = |value: &str| {
+            if !is_integer {
+                return Ok(StatValue::Utf8(value.to_string()));
+            }
+            value.parse().map(StatValue::Int).map_err(|_| {
+                ArrowError::ParseError(format!(
+                    "Invalid statistic of {}: {}. Expected an integer.",
+                    name, value
+                ))
+            })
+        };
+        stats.push(ColumnStats {
+            name: name.to_string(),
+            min: parse(min)?,
+            max: parse(max)?,
+        });
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bam::BamReader;
+    use crate::tsv::{Column, ColumnType, TsvReader};
+    use crate::BatchLimit;
+    use arrow::array::{Int32Array, StringArray};
+
+    fn read_batches(ipc: &[u8]) -> Vec<RecordBatch> {
+        let reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        reader.map(|batch| batch.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_bam_stats() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.bam");
+        let mut reader = BamReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let ipc = reader.records_to_ipc(Some("chr1")).unwrap();
+        let stats = read_stats(std::io::Cursor::new(&ipc)).unwrap();
+        let batches = read_batches(&ipc);
+        assert_eq!(stats.len(), batches.len());
+        assert_eq!(stats[0], batch_stats(&batches[0]));
+
+        let pos = batches[0].column_by_name("pos").unwrap();
+        let pos = pos.as_any().downcast_ref::<Int32Array>().unwrap();
+        let pos: Vec<_> = pos.iter().flatten().map(i64::from).collect();
+        let names: Vec<_> = stats[0].iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["rname", "pos", "end"]);
+        assert_eq!(stats[0][0].min, StatValue::Utf8("chr1".to_string()));
+        assert_eq!(stats[0][0].max, StatValue::Utf8("chr1".to_string()));
+        assert_eq!(stats[0][1].min, StatValue::Int(*pos.iter().min().unwrap()));
+        assert_eq!(stats[0][1].max, StatValue::Int(*pos.iter().max().unwrap()));
+    }
+
+    #[test]
+    fn test_stats_per_batch() {
+        let data = "chr2\t300\nchr1\t100\nchr3\t50\nchr1\t\n";
+        let columns = vec![
+            Column::new("chrom", ColumnType::Utf8),
+            Column::new("start", ColumnType::Int64),
+        ];
+        let mut reader = TsvReader::new(data.as_bytes(), columns)
+            .unwrap()
+            .with_batch_limit(BatchLimit::Records(2));
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let stats = read_stats(std::io::Cursor::new(&ipc)).unwrap();
+        let batches = read_batches(&ipc);
+        assert_eq!(batches.len(), 2);
+        for (stats, batch) in stats.iter().zip(&batches) {
+            assert_eq!(stats, &batch_stats(batch));
+        }
+        let chrom = |s: &str| StatValue::Utf8(s.to_string());
+        assert_eq!(
+            stats[0],
+            [
+                ColumnStats {
+                    name: "chrom".to_string(),
+                    min: chrom("chr1"),
+                    max: chrom("chr2"),
+                },
+                ColumnStats {
+                    name: "start".to_string(),
+                    min: StatValue::Int(100),
+                    max: StatValue::Int(300),
+                },
+            ]
+        );
+        // Nulls are ignored.
+        assert_eq!(stats[1][1].min, StatValue::Int(50));
+        assert_eq!(stats[1][1].max, StatValue::Int(50));
+        let chroms = batches[1].column_by_name("chrom").unwrap();
+        let chroms = chroms.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(chroms.value(0), "chr3");
+    }
+}
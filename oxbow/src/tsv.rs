@@ -0,0 +1,793 @@
+//! A reader for arbitrary tab-separated tables, such as peak calls or annotation tables.
+//!
+//! The columns are given up front, as names and types, in the order of the fields of each line.
+//! Lines with more fields than columns have the extra fields dropped. Empty fields and `.` are
+//! null. Comment lines, starting with `#`, and empty lines are skipped.
+//!
+//! Designating a column for the chromosome and the start (and optionally the end) of each record
+//! allows querying them by region: BGZF-compressed files with a tabix or CSI index are queried
+//! through it, and any other file by a linear pass over all of its records.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow::datatypes::{DataType, Fields};
+use arrow::{error::ArrowError, record_batch::RecordBatch};
+use noodles::core::{region::Interval, Position, Region};
+use noodles::{bgzf, csi};
+
+use crate::batch_builder::{
+    builder_fields, count_err, provenance, write_ipc_batched, write_ipc_err, BatchBuilder,
+    BatchLimit, BUFFER_SIZE_BYTES,
+};
+use crate::bed::index_from_path;
+use crate::compression::decompress;
+use crate::coordinates::CoordinateSystem;
+use crate::error_policy::{tolerate, ErrorPolicy, RecordError};
+use crate::region::{query_region, QueryMode};
+
+/// The type of a column.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColumnType {
+    /// A string (`Utf8`).
+    Utf8,
+    /// An integer (`Int64`).
+    Int64,
+    /// A floating-point number (`Float64`).
+    Float64,
+    /// A boolean (`Boolean`), written as `true` or `false` in any case, or `1` or `0`.
+    Boolean,
+}
+
+impl ColumnType {
+    /// Returns the Arrow data type of the column.
+    pub fn arrow_type(&self) -> DataType {
+        match self {
+            Self::Utf8 => DataType::Utf8,
+            Self::Int64 => DataType::Int64,
+            Self::Float64 => DataType::Float64,
+            Self::Boolean => DataType::Boolean,
+        }
+    }
+
+    fn parse(&self, s: &str) -> Option<Value> {
+        match self {
+            Self::Utf8 => Some(Value::Utf8(s.to_string())),
+            Self::Int64 => s.parse().ok().map(Value::Int64),
+            Self::Float64 => s.parse().ok().map(Value::Float64),
+            Self::Boolean => match s.to_ascii_lowercase().as_str() {
+                "true" | "1" => Some(Value::Boolean(true)),
+                "false" | "0" => Some(Value::Boolean(false)),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl FromStr for ColumnType {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Utf8" => Ok(Self::Utf8),
+            "Int64" => Ok(Self::Int64),
+            "Float64" => Ok(Self::Float64),
+            "Boolean" => Ok(Self::Boolean),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid column type: {}. Expected Utf8, Int64, Float64 or Boolean.",
+                    s
+                ),
+            )),
+        }
+    }
+}
+
+/// A column of a table.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub ty: ColumnType,
+}
+
+impl Column {
+    pub fn new(name: &str, ty: ColumnType) -> Self {
+        Self {
+            name: name.to_string(),
+            ty,
+        }
+    }
+}
+
+impl TryFrom<(String, String)> for Column {
+    type Error = io::Error;
+
+    /// Creates a column from a `(name, type)` pair, e.g. `("score", "Float64")`.
+    fn try_from((name, ty): (String, String)) -> Result<Self, Self::Error> {
+        let ty = ty.parse()?;
+        Ok(Self { name, ty })
+    }
+}
+
+/// A value of a record.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Utf8(String),
+    Int64(i64),
+    Float64(f64),
+    Boolean(bool),
+}
+
+/// A record of a table, with a value or null for each column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TsvRecord {
+    pub values: Vec<Option<Value>>,
+}
+
+/// Options for reading tables.
+#[derive(Clone, Debug, Default)]
+pub struct TsvOptions {
+    /// The index of the column with the chromosome of each record, a Utf8 column. Required,
+    /// along with [`TsvOptions::start_column`], to query regions.
+    pub chrom_column: Option<usize>,
+    /// The index of the column with the start of each record, an Int64 column.
+    pub start_column: Option<usize>,
+    /// The index of the column with the end of each record, an Int64 column. Records without an
+    /// end column span their start only.
+    pub end_column: Option<usize>,
+    /// The coordinate system of the start and end columns, to match them against query regions.
+    /// Defaults to 0-based, half-open coordinates, as in BED. Their values are returned as they
+    /// are. See [`crate::coordinates`].
+    pub coordinate_system: Option<CoordinateSystem>,
+    /// Skip the first line that isn't a comment, e.g. a row of column names. Tabix queries, which
+    /// only return records, don't skip anything.
+    pub skip_header: Option<bool>,
+    /// What to do with records that fail to parse. Defaults to [`ErrorPolicy::Fail`].
+    pub on_error: Option<ErrorPolicy>,
+}
+
+/// The indices of the coordinate columns.
+#[derive(Clone, Copy, Debug)]
+struct Coordinates {
+    chrom: usize,
+    start: usize,
+    end: Option<usize>,
+    coordinate_system: CoordinateSystem,
+}
+
+impl TsvOptions {
+    /// Checks the coordinate columns against `columns`, returning them if both the chromosome
+    /// and start columns are given.
+    fn coordinates(&self, columns: &[Column]) -> Result<Option<Coordinates>, ArrowError> {
+        let check = |option: &str, index: Option<usize>, ty: ColumnType| match index {
+            Some(i) if columns.get(i).map(|column| column.ty) != Some(ty) => {
+                Err(ArrowError::InvalidArgumentError(format!(
+                    "Invalid {}: {}. Expected the index of a {:?} column.",
+                    option, i, ty
+                )))
+            }
+            _ => Ok(index),
+        };
+        let chrom = check("chrom column", self.chrom_column, ColumnType::Utf8)?;
+        let start = check("start column", self.start_column, ColumnType::Int64)?;
+        let end = check("end column", self.end_column, ColumnType::Int64)?;
+        let coordinate_system = self
+            .coordinate_system
+            .unwrap_or(CoordinateSystem::ZeroBasedHalfOpen);
+        Ok(chrom.zip(start).map(|(chrom, start)| Coordinates {
+            chrom,
+            start,
+            end,
+            coordinate_system,
+        }))
+    }
+}
+
+fn invalid_data(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid line: {}", line),
+    )
+}
+
+fn is_skipped(line: &str) -> bool {
+    line.trim().is_empty() || line.starts_with('#')
+}
+
+fn parse_line(line: &str, columns: &[Column]) -> io::Result<TsvRecord> {
+    let mut fields = line.split('\t');
+    let values = columns
+        .iter()
+        .map(|column| match fields.next() {
+            None => Err(invalid_data(line)),
+            Some("" | ".") => Ok(None),
+            Some(field) => column
+                .ty
+                .parse(field)
+                .map(Some)
+                .ok_or_else(|| invalid_data(line)),
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(TsvRecord { values })
+}
+
+/// Parses lines into records, skipping the header if `skip_header`. Malformed records are
+/// handled by [`TsvOptions::on_error`], collecting them into `errors`.
+fn parse_records<'a, I>(
+    lines: I,
+    columns: &'a [Column],
+    options: &TsvOptions,
+    skip_header: bool,
+    errors: &'a mut Vec<RecordError>,
+) -> impl Iterator<Item = Result<TsvRecord, ArrowError>> + 'a
+where
+    I: Iterator<Item = io::Result<String>> + 'a,
+{
+    let records = lines
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !is_skipped(line)))
+        .skip(usize::from(skip_header))
+        .map(|(i, line)| (i + 1, line.and_then(|line| parse_line(&line, columns))));
+    tolerate(records, options.on_error.unwrap_or_default(), errors)
+        .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())))
+}
+
+/// Returns the 1-based, closed interval of a record, or `None` if it has no chromosome or start.
+fn record_interval<'r>(
+    record: &'r TsvRecord,
+    coordinates: &Coordinates,
+) -> Option<(&'r str, Position, Position)> {
+    let int = |i: usize| match record.values.get(i) {
+        Some(Some(Value::Int64(v))) => Some(*v),
+        _ => None,
+    };
+    let chrom = match record.values.get(coordinates.chrom) {
+        Some(Some(Value::Utf8(chrom))) => chrom.as_str(),
+        _ => return None,
+    };
+    let start = CoordinateSystem::OneBased
+        .start_from(coordinates.coordinate_system, int(coordinates.start)?);
+    // Empty features of half-open coordinates span the base after `start`.
+    let end = coordinates.end.and_then(int).unwrap_or(start).max(start);
+    let start = Position::try_from(usize::try_from(start).ok()?).ok()?;
+    let end = Position::try_from(usize::try_from(end).ok()?).ok()?;
+    Some((chrom, start, end))
+}
+
+/// Keeps the records of a region matching it under `mode`. Records without coordinates never
+/// match. Errors are passed through.
+fn filter_region<'a>(
+    records: impl Iterator<Item = Result<TsvRecord, ArrowError>> + 'a,
+    coordinates: Coordinates,
+    region: &'a Region,
+    mode: QueryMode,
+) -> impl Iterator<Item = Result<TsvRecord, ArrowError>> + 'a {
+    let interval = region.interval();
+    records.filter(move |i| {
+        i.as_ref().map_or(true, |record| {
+            record_interval(record, &coordinates).is_some_and(|(chrom, start, end)| {
+                chrom == region.name()
+                    && interval.intersects(Interval::from(start..=end))
+                    && mode.matches(interval, start, end)
+            })
+        })
+    })
+}
+
+/// A table reader.
+pub struct TsvReader<R> {
+    reader: R,
+    indexed_reader: Option<csi::io::IndexedReader<bgzf::Reader<File>>>,
+    columns: Vec<Column>,
+    options: TsvOptions,
+    query_mode: QueryMode,
+    batch_limit: Option<BatchLimit>,
+    errors: Vec<RecordError>,
+    path: Option<PathBuf>,
+}
+
+impl TsvReader<Box<dyn BufRead>> {
+    /// Creates a table reader from a given file path, with the given columns.
+    ///
+    /// Gzip- and BGZF-compressed files are decompressed transparently. A BGZF-compressed file
+    /// with an index at `<path>.tbi` or `<path>.csi` is queried through it.
+    pub fn new_from_path(path: &str, columns: Vec<Column>) -> io::Result<Self> {
+        let file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let mut reader = Self::new(decompress(file)?, columns)?;
+        if let Some(index) = index_from_path(path)? {
+            let file = File::open(path)?;
+            reader.indexed_reader = Some(csi::io::IndexedReader::new(file, index));
+        }
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> TsvReader<R> {
+    /// Creates a table reader, with the given columns.
+    ///
+    /// Readers created from a stream are queried by a linear pass.
+    pub fn new(reader: R, columns: Vec<Column>) -> io::Result<Self> {
+        Ok(Self {
+            reader,
+            indexed_reader: None,
+            columns,
+            options: TsvOptions::default(),
+            query_mode: QueryMode::default(),
+            batch_limit: None,
+            errors: Vec::new(),
+            path: None,
+        })
+    }
+
+    /// Sets the options used when reading records.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::tsv::{Column, ColumnType, TsvOptions, TsvReader};
+    ///
+    /// let columns = vec![
+    ///     Column::new("chrom", ColumnType::Utf8),
+    ///     Column::new("start", ColumnType::Int64),
+    ///     Column::new("end", ColumnType::Int64),
+    /// ];
+    /// let options = TsvOptions {
+    ///     chrom_column: Some(0),
+    ///     start_column: Some(1),
+    ///     end_column: Some(2),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = TsvReader::new_from_path("peaks.tsv", columns)
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// ```
+    pub fn with_options(mut self, options: TsvOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets how records are matched against query regions.
+    ///
+    /// By default, a region query returns every record overlapping the region. With
+    /// [`QueryMode::Contained`], records straddling either bound of the region are dropped.
+    pub fn with_query_mode(mut self, query_mode: QueryMode) -> Self {
+        self.query_mode = query_mode;
+        self
+    }
+
+    /// Caps the size of each record batch, so that large tables are returned in chunks. A
+    /// [`BatchLimit::Bytes`] budget counts the bytes of the string values of each record.
+    pub fn with_batch_limit(mut self, limit: BatchLimit) -> Self {
+        self.batch_limit = Some(limit);
+        self
+    }
+
+    /// Returns the records skipped by the last scan under [`ErrorPolicy::SkipAndCollect`].
+    pub fn errors(&self) -> &[RecordError] {
+        &self.errors
+    }
+
+    /// Returns the fields of the batches returned by [`TsvReader::records_to_ipc`], without
+    /// reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(TsvBatchBuilder::new(0, &self.columns)?)
+    }
+
+    /// Returns the records of the given region, or all of them, parsed and filtered.
+    fn records<'a>(
+        &'a mut self,
+        region: Option<&'a Region>,
+    ) -> Result<Box<dyn Iterator<Item = Result<TsvRecord, ArrowError>> + 'a>, ArrowError> {
+        let coordinates = self.options.coordinates(&self.columns)?;
+        let skip_header = self.options.skip_header.unwrap_or(false);
+        let Some(region) = region else {
+            let lines = (&mut self.reader).lines();
+            return Ok(Box::new(parse_records(
+                lines,
+                &self.columns,
+                &self.options,
+                skip_header,
+                &mut self.errors,
+            )));
+        };
+        let coordinates = coordinates.ok_or_else(|| {
+            ArrowError::InvalidArgumentError(
+                "Querying a region requires a chrom column and a start column.".to_string(),
+            )
+        })?;
+        let records: Box<dyn Iterator<Item = Result<TsvRecord, ArrowError>> + 'a> =
+            match self.indexed_reader.as_mut() {
+                Some(indexed_reader) => {
+                    let query = indexed_reader
+                        .query(region)
+                        .map_err(|e| ArrowError::ExternalError(e.into()))?
+                        .map(|i| i.map(|record| record.as_ref().to_string()));
+                    Box::new(parse_records(
+                        query,
+                        &self.columns,
+                        &self.options,
+                        false,
+                        &mut self.errors,
+                    ))
+                }
+                None => Box::new(parse_records(
+                    (&mut self.reader).lines(),
+                    &self.columns,
+                    &self.options,
+                    skip_header,
+                    &mut self.errors,
+                )),
+            };
+        Ok(Box::new(filter_region(
+            records,
+            coordinates,
+            region,
+            self.query_mode,
+        )))
+    }
+
+    /// Returns the records in the given region as Apache Arrow IPC.
+    ///
+    /// If the region is `None`, all records are returned. Querying a region requires
+    /// [`TsvOptions::chrom_column`] and [`TsvOptions::start_column`]. Files without an index are
+    /// queried by a linear pass, which reads the stream to its end like a scan of the whole file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::tsv::{Column, ColumnType, TsvOptions, TsvReader};
+    ///
+    /// let columns = vec![
+    ///     Column::new("chrom", ColumnType::Utf8),
+    ///     Column::new("start", ColumnType::Int64),
+    /// ];
+    /// let options = TsvOptions {
+    ///     chrom_column: Some(0),
+    ///     start_column: Some(1),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = TsvReader::new_from_path("sites.tsv.gz", columns)
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// let ipc = reader.records_to_ipc(Some("chr1:1-1000")).unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
+        let metadata = provenance("tsv", self.path.as_deref(), region.as_slice());
+        let region = region.map(query_region).transpose()?;
+        let batch_limit = self.batch_limit;
+        let columns = self.columns.clone();
+        let new_builder =
+            || TsvBatchBuilder::new(1024, &columns).map(|b| b.with_metadata(metadata.clone()));
+        let records = self.records(region.as_ref())?;
+        match batch_limit {
+            Some(limit) => write_ipc_batched(records, new_builder, limit, TsvRecord::size),
+            None => write_ipc_err(records, new_builder()?),
+        }
+    }
+
+    /// Returns the number of records in the given region, without converting them to Arrow.
+    ///
+    /// If the region is `None`, all records are counted.
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        let region = region.map(query_region).transpose()?;
+        let records = self.records(region.as_ref())?;
+        count_err(records)
+    }
+}
+
+impl TsvRecord {
+    /// Returns the number of bytes of the record towards a [`BatchLimit::Bytes`] budget: those
+    /// of its strings, and 8 for each other value.
+    fn size(&self) -> usize {
+        self.values
+            .iter()
+            .map(|value| match value {
+                Some(Value::Utf8(s)) => s.len(),
+                _ => 8,
+            })
+            .sum()
+    }
+}
+
+enum ColumnBuilder {
+    Utf8(StringBuilder),
+    Int64(Int64Builder),
+    Float64(Float64Builder),
+    Boolean(BooleanBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(ty: ColumnType, capacity: usize) -> Self {
+        match ty {
+            ColumnType::Utf8 => Self::Utf8(StringBuilder::with_capacity(capacity, 0)),
+            ColumnType::Int64 => Self::Int64(Int64Builder::with_capacity(capacity)),
+            ColumnType::Float64 => Self::Float64(Float64Builder::with_capacity(capacity)),
+            ColumnType::Boolean => Self::Boolean(BooleanBuilder::with_capacity(capacity)),
+        }
+    }
+
+    fn append(&mut self, value: Option<&Value>) {
+        match (self, value) {
+            (Self::Utf8(builder), Some(Value::Utf8(v))) => builder.append_value(v),
+            (Self::Int64(builder), Some(Value::Int64(v))) => builder.append_value(*v),
+            (Self::Float64(builder), Some(Value::Float64(v))) => builder.append_value(*v),
+            (Self::Boolean(builder), Some(Value::Boolean(v))) => builder.append_value(*v),
+            (Self::Utf8(builder), _) => builder.append_null(),
+            (Self::Int64(builder), _) => builder.append_null(),
+            (Self::Float64(builder), _) => builder.append_null(),
+            (Self::Boolean(builder), _) => builder.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Utf8(builder) => Arc::new(builder.finish()),
+            Self::Int64(builder) => Arc::new(builder.finish()),
+            Self::Float64(builder) => Arc::new(builder.finish()),
+            Self::Boolean(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+struct TsvBatchBuilder {
+    names: Vec<String>,
+    builders: Vec<ColumnBuilder>,
+}
+
+impl TsvBatchBuilder {
+    fn new(capacity: usize, columns: &[Column]) -> Result<Self, ArrowError> {
+        if columns.is_empty() {
+            return Err(ArrowError::InvalidArgumentError(
+                "Reading a table requires at least one column.".to_string(),
+            ));
+        }
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|other| other.name == column.name) {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "Duplicate column: {}.",
+                    column.name
+                )));
+            }
+        }
+        Ok(Self {
+            names: columns.iter().map(|column| column.name.clone()).collect(),
+            builders: columns
+                .iter()
+                .map(|column| ColumnBuilder::new(column.ty, capacity))
+                .collect(),
+        })
+    }
+}
+
+impl BatchBuilder for TsvBatchBuilder {
+    type Record<'a> = &'a TsvRecord;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        for (builder, value) in self.builders.iter_mut().zip(record.values.iter()) {
+            builder.append(value.as_ref());
+        }
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        // All columns are nullable, so that the batches of a chunked scan share a schema.
+        RecordBatch::try_from_iter_with_nullable(
+            self.names
+                .iter()
+                .zip(self.builders.iter_mut())
+                .map(|(name, builder)| (name, builder.finish(), true)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{Float64Type, Int64Type};
+    use arrow::ipc::reader::FileReader;
+
+    fn read_ipc(ipc: Vec<u8>) -> Vec<RecordBatch> {
+        let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader.map(|batch| batch.unwrap()).collect()
+    }
+
+    /// The columns of `fixtures/peaks.tsv`.
+    fn peak_columns() -> Vec<Column> {
+        vec![
+            Column::new("name", ColumnType::Utf8),
+            Column::new("chrom", ColumnType::Utf8),
+            Column::new("start", ColumnType::Int64),
+            Column::new("end", ColumnType::Int64),
+            Column::new("signal", ColumnType::Float64),
+        ]
+    }
+
+    fn peak_options() -> TsvOptions {
+        TsvOptions {
+            chrom_column: Some(1),
+            start_column: Some(2),
+            end_column: Some(3),
+            ..Default::default()
+        }
+    }
+
+    fn peaks() -> TsvReader<Box<dyn BufRead>> {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("../fixtures/peaks.tsv");
+        TsvReader::new_from_path(path.to_str().unwrap(), peak_columns())
+            .unwrap()
+            .with_options(peak_options())
+    }
+
+    fn names(batches: &[RecordBatch]) -> Vec<String> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                let names = batch.column_by_name("name").unwrap().as_string::<i32>();
+                names
+                    .iter()
+                    .map(|name| name.unwrap().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_read_all() {
+        let batches = read_ipc(peaks().records_to_ipc(None).unwrap());
+        let batch = &batches[0];
+        let schema = batch.schema();
+        let types: Vec<_> = schema
+            .fields()
+            .iter()
+            .map(|field| (field.name().as_str(), field.data_type().clone()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("name", DataType::Utf8),
+                ("chrom", DataType::Utf8),
+                ("start", DataType::Int64),
+                ("end", DataType::Int64),
+                ("signal", DataType::Float64),
+            ]
+        );
+        assert_eq!(batch.num_rows(), 5);
+        let start = batch.column_by_name("start").unwrap();
+        assert_eq!(start.as_primitive::<Int64Type>().value(0), 100);
+        let signal = batch.column_by_name("signal").unwrap();
+        assert_eq!(signal.as_primitive::<Float64Type>().value(3), 9.25);
+        assert!(signal.is_null(2));
+        assert_eq!(peaks().count(None).unwrap(), 5);
+        assert_eq!(peaks().fields().unwrap()[4].data_type(), &DataType::Float64);
+    }
+
+    #[test]
+    fn test_query_linear() {
+        let query = |region, query_mode| {
+            let mut reader = peaks().with_query_mode(query_mode);
+            names(&read_ipc(reader.records_to_ipc(Some(region)).unwrap()))
+        };
+        // chr1:201 is the first base after peak1, in 0-based, half-open coordinates.
+        assert_eq!(
+            query("chr1:201-1001", QueryMode::Overlap),
+            ["peak2", "peak3"]
+        );
+        assert_eq!(
+            query("chr1:101-300", QueryMode::Contained),
+            ["peak1", "peak2"]
+        );
+        assert_eq!(query("chr2", QueryMode::Overlap), ["peak4", "peak5"]);
+        assert_eq!(peaks().count(Some("chr1:1-100")).unwrap(), 0);
+
+        // The same coordinates read as 1-based include chr1:100.
+        let options = TsvOptions {
+            coordinate_system: Some(CoordinateSystem::OneBased),
+            ..peak_options()
+        };
+        let mut reader = peaks().with_options(options);
+        assert_eq!(reader.count(Some("chr1:1-100")).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_query_tabix() {
+        let dir = std::env::temp_dir().join(format!("oxbow-tsv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sites.bed.gz");
+        let mut writer = bgzf::Writer::new(File::create(&path).unwrap());
+        io::Write::write_all(
+            &mut writer,
+            b"chr1\t10\t20\ta\ttrue\nchr1\t30\t40\tb\tfalse\nchr2\t5\t6\tc\t1\n",
+        )
+        .unwrap();
+        writer.finish().unwrap();
+        let path = path.to_str().unwrap();
+        crate::index::write_tabix(path, None).unwrap();
+        let columns = vec![
+            Column::new("chrom", ColumnType::Utf8),
+            Column::new("start", ColumnType::Int64),
+            Column::new("end", ColumnType::Int64),
+            Column::new("name", ColumnType::Utf8),
+            Column::new("pass", ColumnType::Boolean),
+        ];
+        let options = TsvOptions {
+            chrom_column: Some(0),
+            start_column: Some(1),
+            end_column: Some(2),
+            ..Default::default()
+        };
+        let mut reader = TsvReader::new_from_path(path, columns)
+            .unwrap()
+            .with_options(options);
+        assert!(reader.indexed_reader.is_some());
+        let batches = read_ipc(reader.records_to_ipc(Some("chr1:25-50")).unwrap());
+        assert_eq!(names(&batches), ["b"]);
+        let pass = batches[0].column_by_name("pass").unwrap().as_boolean();
+        assert!(!pass.value(0));
+        assert_eq!(reader.count(Some("chr2")).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_batch_limit() {
+        let mut reader = peaks().with_batch_limit(BatchLimit::Records(2));
+        let batches = read_ipc(reader.records_to_ipc(None).unwrap());
+        let rows: Vec<_> = batches.iter().map(|batch| batch.num_rows()).collect();
+        assert_eq!(rows, [2, 2, 1]);
+        assert_eq!(names(&batches).len(), 5);
+    }
+
+    #[test]
+    fn test_skip_header() {
+        let data = "chrom\tstart\nchr1\t5\n";
+        let columns = || {
+            vec![
+                Column::new("chrom", ColumnType::Utf8),
+                Column::new("start", ColumnType::Int64),
+            ]
+        };
+        let mut reader = TsvReader::new(data.as_bytes(), columns()).unwrap();
+        assert!(reader.records_to_ipc(None).is_err());
+        let options = TsvOptions {
+            skip_header: Some(true),
+            ..Default::default()
+        };
+        let mut reader = TsvReader::new(data.as_bytes(), columns())
+            .unwrap()
+            .with_options(options);
+        assert_eq!(reader.count(None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_invalid() {
+        // A missing field.
+        let mut reader = TsvReader::new("peak1\tchr1\t100\n".as_bytes(), peak_columns()).unwrap();
+        assert!(reader.count(None).is_err());
+
+        // Coordinate columns of the wrong type.
+        let options = TsvOptions {
+            start_column: Some(0),
+            ..peak_options()
+        };
+        let mut reader = peaks().with_options(options);
+        let error = reader.count(Some("chr1")).unwrap_err();
+        assert!(error.to_string().contains("Invalid start column: 0"));
+
+        // A region query without coordinate columns.
+        let mut reader = peaks().with_options(TsvOptions::default());
+        assert!(reader.count(Some("chr1")).is_err());
+
+        assert!("Int32".parse::<ColumnType>().is_err());
+        let column = Column::try_from(("x".to_string(), "Boolean".to_string())).unwrap();
+        assert_eq!(column, Column::new("x", ColumnType::Boolean));
+        let columns = vec![column.clone(), column];
+        assert!(TsvReader::new("".as_bytes(), columns)
+            .unwrap()
+            .fields()
+            .is_err());
+    }
+}
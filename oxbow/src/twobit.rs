@@ -0,0 +1,410 @@
+//! A reader for UCSC .2bit sequence files.
+//!
+//! Each sequence, or region of a sequence, becomes a row with the columns `name`, `start`, `end`
+//! and `sequence`. `start` and `end` are 1-based and closed, like query regions. Bases are
+//! unpacked from their 2-bit codes, with the N-blocks of a sequence restored as `N` and its
+//! mask blocks as lowercase (soft-masked) bases.
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, StringBuilder, UInt64Builder};
+use arrow::datatypes::Fields;
+use arrow::{error::ArrowError, record_batch::RecordBatch};
+
+use crate::batch_builder::{
+    builder_fields, provenance, write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES,
+};
+use crate::region::query_region;
+
+const SIGNATURE: u32 = 0x1A412743;
+
+/// A sequence, or a region of one, read from a .2bit file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TwoBitRecord {
+    pub name: String,
+    /// The 1-based start of the region.
+    pub start: u64,
+    /// The 1-based, inclusive end of the region.
+    pub end: u64,
+    pub sequence: Vec<u8>,
+}
+
+/// The layout of a sequence record: its length, the 0-based start and length of each N-block
+/// and mask block, and where its packed bases begin.
+struct SequenceLayout {
+    length: u64,
+    n_blocks: Vec<(u64, u64)>,
+    mask_blocks: Vec<(u64, u64)>,
+    offset: u64,
+}
+
+/// A .2bit reader.
+pub struct TwoBitReader<R> {
+    reader: R,
+    big_endian: bool,
+    /// The name and record offset of each sequence, in file order.
+    index: Vec<(String, u64)>,
+    path: Option<PathBuf>,
+}
+
+impl TwoBitReader<BufReader<File>> {
+    /// Creates a .2bit reader from a given file path.
+    pub fn new_from_path(path: &str) -> io::Result<Self> {
+        let file = BufReader::with_capacity(BUFFER_SIZE_BYTES, File::open(path)?);
+        let mut reader = Self::new(file)?;
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: Read + Seek> TwoBitReader<R> {
+    /// Creates a .2bit reader, reading the header and the sequence index.
+    ///
+    /// Files of either byte order, and version 1 files with 64-bit offsets, are supported.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 16];
+        reader.read_exact(&mut header)?;
+        let big_endian = match header[..4].try_into().unwrap() {
+            bytes if u32::from_le_bytes(bytes) == SIGNATURE => false,
+            bytes if u32::from_be_bytes(bytes) == SIGNATURE => true,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid .2bit signature.",
+                ))
+            }
+        };
+        let mut this = Self {
+            reader,
+            big_endian,
+            index: Vec::new(),
+            path: None,
+        };
+        let word = |i: usize| this.u32_from(header[i..i + 4].try_into().unwrap());
+        let (version, sequence_count) = (word(4), word(8));
+        if version > 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported .2bit version: {}", version),
+            ));
+        }
+        for _ in 0..sequence_count {
+            let mut name_size = [0];
+            this.reader.read_exact(&mut name_size)?;
+            let mut name = vec![0; name_size[0] as usize];
+            this.reader.read_exact(&mut name)?;
+            let name = String::from_utf8(name)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let offset = match version {
+                0 => this.read_u32()? as u64,
+                _ => {
+                    let mut bytes = [0; 8];
+                    this.reader.read_exact(&mut bytes)?;
+                    match big_endian {
+                        true => u64::from_be_bytes(bytes),
+                        false => u64::from_le_bytes(bytes),
+                    }
+                }
+            };
+            this.index.push((name, offset));
+        }
+        Ok(this)
+    }
+
+    fn u32_from(&self, bytes: [u8; 4]) -> u32 {
+        match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        }
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(self.u32_from(bytes))
+    }
+
+    /// Reads `count` block starts followed by as many block sizes.
+    fn read_blocks(&mut self) -> io::Result<Vec<(u64, u64)>> {
+        let count = self.read_u32()? as usize;
+        let starts = (0..count)
+            .map(|_| self.read_u32().map(u64::from))
+            .collect::<io::Result<Vec<_>>>()?;
+        let sizes = (0..count)
+            .map(|_| self.read_u32().map(u64::from))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(starts.into_iter().zip(sizes).collect())
+    }
+
+    fn layout(&mut self, offset: u64) -> io::Result<SequenceLayout> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let length = self.read_u32()? as u64;
+        let n_blocks = self.read_blocks()?;
+        let mask_blocks = self.read_blocks()?;
+        // reserved
+        self.read_u32()?;
+        let offset = self.reader.stream_position()?;
+        Ok(SequenceLayout {
+            length,
+            n_blocks,
+            mask_blocks,
+            offset,
+        })
+    }
+
+    /// Reads the bases of the 0-based, half-open interval `[start, end)` of a sequence.
+    fn read_bases(&mut self, layout: &SequenceLayout, start: u64, end: u64) -> io::Result<Vec<u8>> {
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let first = start / 4;
+        let mut packed = vec![0; ((end - 1) / 4 - first + 1) as usize];
+        self.reader.seek(SeekFrom::Start(layout.offset + first))?;
+        self.reader.read_exact(&mut packed)?;
+        let mut bases: Vec<u8> = (start..end)
+            .map(|i| {
+                let byte = packed[(i / 4 - first) as usize];
+                b"TCAG"[((byte >> (6 - 2 * (i % 4))) & 0b11) as usize]
+            })
+            .collect();
+        let overlap = |&(block_start, size): &(u64, u64)| {
+            let from = block_start.clamp(start, end);
+            let to = (block_start + size).clamp(from, end);
+            (from - start) as usize..(to - start) as usize
+        };
+        for range in layout.n_blocks.iter().map(overlap) {
+            bases[range].fill(b'N');
+        }
+        for range in layout.mask_blocks.iter().map(overlap) {
+            bases[range].make_ascii_lowercase();
+        }
+        Ok(bases)
+    }
+
+    /// Returns the names of the sequences, in file order.
+    pub fn sequence_names(&self) -> Vec<String> {
+        self.index.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Returns the fields of the batches returned by [`TwoBitReader::records_to_ipc`], without
+    /// reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(TwoBitBatchBuilder::new(0))
+    }
+
+    /// Returns the sequences in the given region as Apache Arrow IPC.
+    ///
+    /// If the region is `None`, all sequences are returned whole.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::twobit::TwoBitReader;
+    ///
+    /// let mut reader = TwoBitReader::new_from_path("sample.2bit").unwrap();
+    /// let ipc = reader.records_to_ipc(Some("chr1:1-1000")).unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
+        if let Some(region) = region {
+            return self.regions_to_ipc(&[region]);
+        }
+        let batch_builder = TwoBitBatchBuilder::new(1024).with_metadata(provenance(
+            "2bit",
+            self.path.as_deref(),
+            &[],
+        ));
+        let index = self.index.clone();
+        let records = index.into_iter().map(|(name, offset)| {
+            let layout = self.layout(offset)?;
+            let sequence = self.read_bases(&layout, 0, layout.length)?;
+            Ok(TwoBitRecord {
+                name,
+                start: 1,
+                end: layout.length,
+                sequence,
+            })
+        });
+        write_ipc_err(
+            records.map(|i: io::Result<_>| i.map_err(|e| ArrowError::ExternalError(e.into()))),
+            batch_builder,
+        )
+    }
+
+    /// Returns the sequences of the given regions as Apache Arrow IPC, one record per region.
+    ///
+    /// Only the packed bases of each region are read, using the sequence index of the file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::twobit::TwoBitReader;
+    ///
+    /// let mut reader = TwoBitReader::new_from_path("sample.2bit").unwrap();
+    /// let ipc = reader.regions_to_ipc(&["chr1:1-100", "chr2"]).unwrap();
+    /// ```
+    pub fn regions_to_ipc(&mut self, regions: &[&str]) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = TwoBitBatchBuilder::new(regions.len()).with_metadata(provenance(
+            "2bit",
+            self.path.as_deref(),
+            regions,
+        ));
+        let records = regions.iter().map(|region| {
+            let parsed = query_region(region)?;
+            let name = parsed.name();
+            let offset = self
+                .index
+                .iter()
+                .find_map(|(n, offset)| (n == name).then_some(*offset))
+                .ok_or_else(|| {
+                    ArrowError::InvalidArgumentError(format!("Unknown sequence: {}", name))
+                })?;
+            let layout = self
+                .layout(offset)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+            let interval = parsed.interval();
+            let start = interval.start().map_or(1, |position| position.get() as u64);
+            let end = interval
+                .end()
+                .map_or(layout.length, |position| position.get() as u64);
+            if end > layout.length {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "Invalid region: {}. {} has {} bases.",
+                    region, name, layout.length
+                )));
+            }
+            let sequence = self
+                .read_bases(&layout, start - 1, end)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+            Ok(TwoBitRecord {
+                name: name.to_string(),
+                start,
+                end,
+                sequence,
+            })
+        });
+        write_ipc_err(records, batch_builder)
+    }
+
+    /// Returns the number of sequences, without reading them.
+    pub fn count(&self) -> usize {
+        self.index.len()
+    }
+}
+
+struct TwoBitBatchBuilder {
+    name: StringBuilder,
+    start: UInt64Builder,
+    end: UInt64Builder,
+    sequence: StringBuilder,
+}
+
+impl TwoBitBatchBuilder {
+    fn new(capacity: usize) -> Self {
+        Self {
+            name: StringBuilder::with_capacity(capacity, 0),
+            start: UInt64Builder::with_capacity(capacity),
+            end: UInt64Builder::with_capacity(capacity),
+            sequence: StringBuilder::with_capacity(capacity, 0),
+        }
+    }
+}
+
+impl BatchBuilder for TwoBitBatchBuilder {
+    type Record<'a> = &'a TwoBitRecord;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        self.name.append_value(&record.name);
+        self.start.append_value(record.start);
+        self.end.append_value(record.end);
+        self.sequence
+            .append_value(String::from_utf8_lossy(&record.sequence));
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        RecordBatch::try_from_iter(vec![
+            ("name", Arc::new(self.name.finish()) as ArrayRef),
+            ("start", Arc::new(self.start.finish()) as ArrayRef),
+            ("end", Arc::new(self.end.finish()) as ArrayRef),
+            ("sequence", Arc::new(self.sequence.finish()) as ArrayRef),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, StringArray, UInt64Array};
+    use arrow::ipc::reader::FileReader;
+
+    use crate::fasta::FastaReader;
+
+    fn fixture(name: &str) -> String {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures");
+        dir.push(name);
+        dir.to_str().unwrap().to_string()
+    }
+
+    fn read_sequences(ipc: Vec<u8>) -> Vec<String> {
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let sequence = batch.column_by_name("sequence").unwrap();
+        let sequence = sequence.as_any().downcast_ref::<StringArray>().unwrap();
+        sequence.iter().map(|s| s.unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_regions_match_fasta() {
+        // chr1 has an N-block at 21-30 and mask blocks at 41-60 and 141-150; chr2 begins with a
+        // mask block and has a masked N at 31.
+        let regions = [
+            "chr1",
+            "chr2",
+            "chr1:1-1",
+            "chr1:18-45",
+            "chr1:59-62",
+            "chr1:147-150",
+            "chr2:30-33",
+        ];
+        let mut twobit = TwoBitReader::new_from_path(&fixture("twobit.2bit")).unwrap();
+        let mut fasta = FastaReader::new(&fixture("twobit.fa")).unwrap();
+        assert_eq!(
+            read_sequences(twobit.regions_to_ipc(&regions).unwrap()),
+            read_sequences(fasta.regions_to_ipc(&regions, None).unwrap())
+        );
+        assert_eq!(
+            read_sequences(twobit.records_to_ipc(None).unwrap()),
+            read_sequences(fasta.records_to_ipc(None).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_read_2bit() {
+        let mut reader = TwoBitReader::new_from_path(&fixture("twobit.2bit")).unwrap();
+        assert_eq!(reader.sequence_names(), ["chr1", "chr2"]);
+        assert_eq!(reader.count(), 2);
+        let ipc = reader.regions_to_ipc(&["chr1:18-25", "chr2"]).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let column = |name| {
+            let array = batch.column_by_name(name).unwrap();
+            array
+                .as_any()
+                .downcast_ref::<UInt64Array>()
+                .unwrap()
+                .values()
+                .to_vec()
+        };
+        assert_eq!(column("start"), [18, 1]);
+        assert_eq!(column("end"), [25, 37]);
+        let sequence = batch.column_by_name("sequence").unwrap();
+        let sequence = sequence.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(sequence.value(0), "TCGNNNNN");
+
+        assert!(reader.regions_to_ipc(&["chr3"]).is_err());
+        assert!(reader.regions_to_ipc(&["chr2:30-40"]).is_err());
+        assert!(TwoBitReader::new(std::io::Cursor::new(vec![0; 16])).is_err());
+    }
+}
@@ -1,17 +1,47 @@
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, BufReader, Read, Seek};
-use std::path::Path;
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
+use arrow::array::{Array, AsArray};
 use arrow::array::{
-    ArrayRef, Float32Builder, GenericStringBuilder, Int32Builder, StringArray,
-    StringDictionaryBuilder,
+    ArrayRef, BooleanBuilder, Float32Builder, GenericStringBuilder, Int32Builder, ListBuilder,
+    StringArray,
 };
-use arrow::{datatypes::Int32Type, error::ArrowError, record_batch::RecordBatch};
+use arrow::datatypes::{DataType, Fields, Schema};
+use arrow::record_batch::RecordBatchReader;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use arrow::{error::ArrowError, record_batch::RecordBatch};
 use noodles::core::Region;
-use noodles::{bgzf, csi, tabix, vcf};
+use noodles::vcf::header::record::value::map::format::Type as FormatType;
+use noodles::vcf::header::record::value::map::info::Type as InfoType;
+use noodles::vcf::header::record::value::map::{Format, Info, Map};
+use noodles::vcf::header::Number;
+use noodles::vcf::record::genotypes::keys::Key as FormatKey;
+use noodles::vcf::record::genotypes::sample::value::Array as SampleArray;
+use noodles::vcf::record::genotypes::sample::Value as SampleValue;
+use noodles::vcf::record::info::field::value::Array as InfoArray;
+use noodles::vcf::record::info::field::{Key as InfoKey, Value as InfoValue};
+use noodles::{bcf, bgzf, csi, tabix, vcf};
 
-use crate::batch_builder::{write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES};
+use crate::batch_builder::{
+    builder_fields, check_no_offsets, count_err, finish_batch, map_records, provenance,
+    select_fields, write_ipc, write_ipc_err, BatchBuilder, ReferenceColumnBuilder, WithOffsets,
+    BUFFER_SIZE_BYTES,
+};
+use crate::compression::BgzfStream;
+use crate::coordinates::{needs_wide_positions, CoordinateSystem, PositionBuilder};
+use crate::parallel::query_parallel;
+use crate::progress::{
+    finish_progress, finish_query, is_cancelled, track, track_query, until_cancelled, Progress,
+    ProgressTracker,
+};
+use crate::region::{parse_aliased_region, ChromAlias};
+use crate::sampling::sample_records;
+use crate::sort_order::SortChecker;
 
 fn read_magic(read: &mut dyn Read) -> io::Result<[u8; 4]> {
     let mut magic = [0; 4];
@@ -48,44 +78,551 @@ pub fn index_from_path(path: &str) -> io::Result<csi::Index> {
     Ok(index)
 }
 
+/// Returns the schema of the batches returned by [`VcfReader::records_to_ipc`] with `options`,
+/// reading only the header of the VCF file at `path`, not its records or index.
+///
+/// INFO and FORMAT columns are typed from their header definitions. All fields are nullable, and
+/// the schema has no metadata.
+///
+/// # Examples
+///
+/// ```no_run
+/// use oxbow::vcf::{peek_schema, VcfOptions};
+///
+/// let options = VcfOptions {
+///     info_fields: Some(vec!["DP".to_string()]),
+///     ..Default::default()
+/// };
+/// let schema = peek_schema("sample.vcf.gz", &options).unwrap();
+/// ```
+pub fn peek_schema(path: &str, options: &VcfOptions) -> Result<Schema, ArrowError> {
+    let mut reader = File::open(path)
+        .map(|file| vcf::Reader::new(bgzf::Reader::new(BufReader::new(file))))
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    let header = reader
+        .read_header()
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    Ok(Schema::new(header_fields(&header, options)?))
+}
+
+/// Returns the fields of the batches of VCF or BCF records with `header` and `options`.
+pub(crate) fn header_fields(
+    header: &vcf::Header,
+    options: &VcfOptions,
+) -> Result<Fields, ArrowError> {
+    let batch_builder = VcfBatchBuilder::new(0, header, options)?;
+    if options.emit_offset.unwrap_or(false) {
+        return builder_fields(WithOffsets::<_, vcf::Record>::new(batch_builder));
+    }
+    builder_fields(batch_builder)
+}
+
+/// Options for converting variant records to Arrow.
+#[derive(Clone, Debug, Default)]
+pub struct VcfOptions {
+    /// INFO fields to materialize as dedicated columns, e.g. `["AF", "DP"]`.
+    ///
+    /// Each field becomes a column named after it, in the given order, in place of the `info`
+    /// column. Columns are typed from the header definition: `Integer` as Int32, `Float` as
+    /// Float32, `Character` and `String` as Utf8, and `Flag` as Boolean. Fields with a `Number`
+    /// other than 0 or 1 (e.g. `A`, `R`, `G` or `.`) are lists. Missing values are null, except for
+    /// flags, which are `false` when absent.
+    pub info_fields: Option<Vec<String>>,
+    /// FORMAT fields to materialize per sample, e.g. `["GT", "DP", "AD"]`.
+    ///
+    /// A column named `{sample}.{key}` is appended after `format` for each sample and field, typed
+    /// from the header definition as for INFO fields. `GT` is a string such as `0/1` or `1|0`.
+    pub genotype_fields: Option<Vec<String>>,
+    /// Samples whose FORMAT fields are materialized. Defaults to all samples in header order.
+    pub samples: Option<Vec<String>>,
+    /// Emit `chrom` as the index (Int32) of the contig in the header, instead of its name.
+    /// Records on contigs missing from the header get a null. The names can be looked up with
+    /// [`VcfReader::reference_names`].
+    pub emit_ref_as_index: Option<bool>,
+    /// The coordinate system of `pos`. Defaults to the native 1-based coordinates. See
+    /// [`crate::coordinates`].
+    pub coordinate_system: Option<CoordinateSystem>,
+    /// Emit `pos` and `end` as UInt32 instead of Int32. Either is widened to 64 bits when a
+    /// contig of the header is longer than 2^31 - 1 bases.
+    pub unsigned_positions: Option<bool>,
+    /// Expand gVCF reference blocks, i.e. records with an `END` INFO key, into one row per
+    /// position from `pos` to `END`.
+    ///
+    /// Each expanded row has `end` equal to its `pos`, and repeats the other columns of the block,
+    /// so `ref` is the reference base at the start of the block. Limits and counts apply to
+    /// records, not rows.
+    ///
+    /// The `<NON_REF>` allele of GATK gVCFs is read as the unspecified allele and written as its
+    /// VCF 4.2 equivalent, `<*>`, in `alt`.
+    pub expand_gvcf_blocks: Option<bool>,
+    /// Add `svtype` (Utf8) and `svlen` (Int32) columns after `filter`, from the `SVTYPE` and
+    /// `SVLEN` INFO keys. `svlen` is the length of the first ALT allele. Both are null for
+    /// records without them.
+    pub include_sv_fields: Option<bool>,
+    /// Add `mate_chrom` (Utf8) and `mate_pos` (Int32) columns after `filter` with the mate
+    /// position of the first breakend ALT allele, e.g. `chr2` and `123` for `A[chr2:123[`. Both
+    /// are null for records without a mate position.
+    pub include_breakend_mates: Option<bool>,
+    /// Emit `id` as a list (`List<Utf8>`) of the IDs of each record, e.g. `["rs3", "rs4"]` for
+    /// `rs3;rs4`, instead of the IDs as written. Either is null for records without IDs (`.`).
+    pub split_ids: Option<bool>,
+    /// Emit `filter` as a list (`List<Utf8>`) of the filters each record failed, or `["PASS"]`,
+    /// instead of the filters as written, e.g. `q10;s50`. Either is null for records whose
+    /// filters weren't applied (`.`).
+    pub split_filters: Option<bool>,
+    /// Split records with several ALT alleles into one row per allele, as `bcftools norm -m-`.
+    ///
+    /// In each row, INFO and FORMAT fields with `Number=A`, `R` or `G` keep the values of the
+    /// reference and that allele only, and `GT` is recoded so that the allele is `1` and the other
+    /// ALT alleles are `0`. Limits and counts apply to records, not rows.
+    pub split_multiallelic: Option<bool>,
+    /// Add a `vpos` column (UInt64), after all others, with the BGZF virtual offset at which each
+    /// record starts, to read it again with [`VcfReader::records_to_ipc_from_vpos`]. Offsets are
+    /// only emitted by scans of the whole file or of a virtual offset range, not region queries,
+    /// and not with `split_multiallelic`.
+    pub emit_offset: Option<bool>,
+    /// Renames the chromosomes of query regions to the naming of the file, e.g. `chr1` to `1`.
+    /// Records keep the names of the file. See [`ChromAlias`].
+    pub chrom_alias: Option<ChromAlias>,
+    /// Fail on the first record out of order, i.e. whose chromosome comes before that of the
+    /// previous record in the header contigs, or whose position is smaller on the same
+    /// chromosome. Chromosomes missing from the header only need their records to be contiguous.
+    /// Only scans and counts of the whole file are checked, not region queries or scans emitting
+    /// offsets.
+    pub require_sorted: Option<bool>,
+    /// Keep each record with this probability, from 0 to 1, for a random subsample of the
+    /// records, not of the genotype samples. Only `records_to_ipc` subsamples records, before
+    /// applying the limit.
+    pub sample_fraction: Option<f64>,
+    /// Keep this many records, chosen uniformly, in file order. This reads all the records before
+    /// returning any, and can't be combined with [`VcfOptions::sample_fraction`].
+    pub sample_count: Option<usize>,
+    /// The seed of the random subsample, so that the same records are kept on every scan.
+    /// Defaults to 0.
+    pub sample_seed: Option<u64>,
+    /// The columns to return, in this order, e.g. `["chrom", "pos", "DP"]`. INFO and FORMAT
+    /// columns selected with `info_fields` and `genotype_fields` can be named too. Defaults to
+    /// all of them, in their default order.
+    pub fields: Option<Vec<String>>,
+}
+
+impl VcfOptions {
+    /// Subsamples records with [`VcfOptions::sample_fraction`] or [`VcfOptions::sample_count`].
+    pub(crate) fn subsample<'a>(
+        &self,
+        records: impl Iterator<Item = Result<vcf::Record, ArrowError>> + 'a,
+    ) -> Result<Box<dyn Iterator<Item = Result<vcf::Record, ArrowError>> + 'a>, ArrowError> {
+        sample_records(
+            records,
+            self.sample_fraction,
+            self.sample_count,
+            self.sample_seed,
+        )
+    }
+}
+
+/// Fails on the first record out of order if [`VcfOptions::require_sorted`] is set. Errors are
+/// passed through.
+pub(crate) fn check_sorted<'a>(
+    records: impl Iterator<Item = Result<vcf::Record, ArrowError>> + 'a,
+    header: &vcf::Header,
+    options: &VcfOptions,
+) -> impl Iterator<Item = Result<vcf::Record, ArrowError>> + 'a {
+    let mut checker = options
+        .require_sorted
+        .unwrap_or(false)
+        .then(|| SortChecker::with_order(header.contigs().keys().map(|name| name.as_ref())));
+    records.map(move |i| {
+        let record = i?;
+        if let Some(checker) = &mut checker {
+            let chrom = record.chromosome().to_string();
+            checker
+                .check(&chrom, usize::from(record.position()), None)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        }
+        Ok(record)
+    })
+}
+
+/// Parses a query region, renaming its chromosome with [`VcfOptions::chrom_alias`] to a contig of
+/// `header` or a reference sequence of `index`.
+pub(crate) fn parse_query_region(
+    region: &str,
+    header: &vcf::Header,
+    index: &csi::Index,
+    options: &VcfOptions,
+) -> Result<Region, ArrowError> {
+    let index_names = index
+        .header()
+        .map(|header| header.reference_sequence_names());
+    parse_aliased_region(region, options.chrom_alias.as_ref(), |name| {
+        header.contigs().contains_key(name) || index_names.is_some_and(|names| names.contains(name))
+    })
+}
+
+/// Fails if offsets are requested along with `split_multiallelic`, since the rows split from a
+/// record would share its offset.
+pub(crate) fn check_offsets(options: &VcfOptions) -> Result<(), ArrowError> {
+    if options.emit_offset.unwrap_or(false) && options.split_multiallelic.unwrap_or(false) {
+        return Err(ArrowError::InvalidArgumentError(
+            "Offsets cannot be emitted along with split multiallelic records.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the mate contig and position of a breakend allele in bracket notation, e.g.
+/// `("chr2", 123)` for `A[chr2:123[` or `]chr2:123]A`.
+fn breakend_mate(allele: &str) -> Option<(&str, i32)> {
+    let start = allele.find(['[', ']'])?;
+    let bracket = allele[start..].chars().next()?;
+    let len = allele[start + 1..].find(bracket)?;
+    let mate = &allele[start + 1..start + 1 + len];
+    let (chrom, pos) = mate.rsplit_once(':')?;
+    let chrom = chrom
+        .strip_prefix('<')
+        .and_then(|chrom| chrom.strip_suffix('>'))
+        .unwrap_or(chrom);
+    Some((chrom, pos.parse().ok()?))
+}
+
 /// A VCF reader.
 pub struct VcfReader<R> {
     reader: vcf::Reader<bgzf::Reader<R>>,
     header: vcf::Header,
+    /// The virtual position of the first record, after the header.
+    records_start: bgzf::VirtualPosition,
     index: csi::Index,
+    options: VcfOptions,
+    limit: Option<usize>,
+    threads: usize,
+    path: Option<PathBuf>,
+    /// Whether the input can seek, unlike a stream read with [`VcfReader::new_from_stream`].
+    seekable: bool,
+    progress: Option<ProgressTracker>,
+    cancel: Option<Arc<AtomicBool>>,
 }
 
 impl VcfReader<BufReader<File>> {
+    /// Creates a VCF reader from a given file path.
+    ///
+    /// The index is loaded from `<path>.tbi` or `<path>.csi`.
     pub fn new_from_path(path: &str) -> std::io::Result<Self> {
         let index = index_from_path(path)?;
+        Self::new_from_path_with_index(path, index)
+    }
+
+    /// Creates a VCF reader from a given file path and a prebuilt index.
+    ///
+    /// This avoids parsing the index again when opening the same file several times.
+    pub fn new_from_path_with_index(path: &str, index: csi::Index) -> std::io::Result<Self> {
         let file = std::fs::File::open(path)?;
         let buf_file = std::io::BufReader::with_capacity(BUFFER_SIZE_BYTES, file);
         let mut reader = vcf::Reader::new(bgzf::Reader::new(buf_file));
         let header = reader.read_header()?;
+        let records_start = reader.virtual_position();
         Ok(Self {
             reader,
             header,
+            records_start,
             index,
+            options: VcfOptions::default(),
+            limit: None,
+            threads: 1,
+            path: Some(PathBuf::from(path)),
+            progress: None,
+            cancel: None,
+            seekable: true,
         })
     }
 }
 
+impl VcfReader<BgzfStream> {
+    /// Creates a VCF reader from a stream that can't seek, e.g. standard input, either
+    /// uncompressed or gzip- or BGZF-compressed.
+    ///
+    /// A stream can only be scanned or counted as a whole: region queries and offsets need a
+    /// seekable, indexed file, and [`VcfReader::with_threads`] is ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_stream(std::io::stdin().lock()).unwrap();
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn new_from_stream<R: BufRead + 'static>(read: R) -> io::Result<Self> {
+        let mut reader = Self::new(BgzfStream::new(read)?, csi::Index::default())?;
+        reader.seekable = false;
+        Ok(reader)
+    }
+}
+
 impl<R: Read + Seek> VcfReader<R> {
-    /// Creates a VCF Reader.
+    /// Creates a VCF Reader from a stream and a prebuilt index.
     pub fn new(read: R, index: csi::Index) -> std::io::Result<Self> {
         let mut reader = vcf::Reader::new(bgzf::Reader::new(read));
         let header = reader.read_header()?;
+        let records_start = reader.virtual_position();
         Ok(Self {
             reader,
             header,
+            records_start,
             index,
+            options: VcfOptions::default(),
+            limit: None,
+            threads: 1,
+            path: None,
+            progress: None,
+            cancel: None,
+            seekable: true,
         })
     }
 
+    /// Returns the index (tabix or CSI) used for queries.
+    pub fn index(&self) -> &csi::Index {
+        &self.index
+    }
+
+    /// Fails for a stream, which can only be scanned as a whole, e.g. with `what` set to
+    /// "Querying a region".
+    fn check_seekable(&self, what: &str) -> Result<(), ArrowError> {
+        if self.seekable {
+            return Ok(());
+        }
+        Err(ArrowError::InvalidArgumentError(format!(
+            "{} requires seekable input, with an index. Streams can only be scanned as a whole.",
+            what
+        )))
+    }
+
+    /// Parses a query region, renamed with [`VcfOptions::chrom_alias`].
+    fn query_region(&self, region: &str) -> Result<Region, ArrowError> {
+        self.check_seekable("Querying a region")?;
+        parse_query_region(region, &self.header, &self.index, &self.options)
+    }
+
+    /// Returns the VCF header, e.g. to write modified records with [`write_vcf`].
+    pub fn header(&self) -> &vcf::Header {
+        &self.header
+    }
+
+    /// Returns the text of the VCF header, as written by [`header_to_string`]. Its lines are
+    /// those of the file, but grouped by kind, e.g. all `INFO` lines before the `FILTER` lines.
+    pub fn header_text(&self) -> io::Result<String> {
+        header_to_string(&self.header)
+    }
+
+    /// Returns the names of the contigs in header order.
+    ///
+    /// These map the indices emitted with [`VcfOptions::emit_ref_as_index`] back to names.
+    pub fn reference_names(&self) -> Vec<String> {
+        self.header
+            .contigs()
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Returns the names and lengths of the contigs (`##contig`) in header order. Contigs
+    /// without a length are left out.
+    pub fn reference_lengths(&self) -> Vec<(String, u64)> {
+        self.header
+            .contigs()
+            .iter()
+            .filter_map(|(name, contig)| Some((name.to_string(), contig.length()? as u64)))
+            .collect()
+    }
+
+    /// Sets the options used when converting records.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::{VcfOptions, VcfReader};
+    ///
+    /// let options = VcfOptions {
+    ///     info_fields: Some(vec!["AF".to_string(), "DP".to_string()]),
+    ///     genotype_fields: Some(vec!["GT".to_string()]),
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz")
+    ///     .unwrap()
+    ///     .with_options(options);
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_options(mut self, options: VcfOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Caps the number of records returned, across all regions of a query combined.
+    ///
+    /// Reading stops as soon as the limit is reached. Counts are not limited.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap().with_limit(100);
+    /// let ipc = reader.regions_to_ipc(&["sq0", "sq1"]).unwrap();
+    /// ```
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Decompresses the BGZF blocks of scans and counts of the whole file on this many threads,
+    /// which is faster for large files. Records are returned in the same order. Region queries,
+    /// and scans emitting offsets, are decompressed on the calling thread. Defaults to 1.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap().with_threads(4);
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Reports the progress of scans, region queries and counts to `progress`, every `interval`
+    /// records and at the end of each scan. Bytes are those of the compressed file, whose size is
+    /// the total when the reader was created from a path. Region queries, which jump around the
+    /// file, report 0 bytes and no total, and the threads of a parallel query are counted
+    /// together.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz")
+    ///     .unwrap()
+    ///     .with_progress(100_000, |records, bytes, total: Option<u64>| {
+    ///         eprintln!("{} records, {} of {:?} bytes", records, bytes, total);
+    ///     });
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_progress(mut self, interval: usize, progress: impl Progress + 'static) -> Self {
+        let tracker = ProgressTracker::new(Box::new(progress), interval, self.path.as_deref());
+        self.progress = Some(tracker);
+        self
+    }
+
+    /// Stops scans, region queries and counts at the next record once `cancel` is set, e.g. from
+    /// another thread. The records read until then are returned, and after a scan of the whole
+    /// file the reader is left after the last of them. Each thread of a parallel query stops at
+    /// its next record, and the records they read are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz")
+    ///     .unwrap()
+    ///     .with_cancel(cancel.clone());
+    /// let timer = std::thread::spawn(move || {
+    ///     std::thread::sleep(std::time::Duration::from_secs(10));
+    ///     cancel.store(true, Ordering::Relaxed);
+    /// });
+    /// let ipc = reader.records_to_ipc(None).unwrap();
+    /// ```
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Reads the records from the current position to the end of the file with `scan`, given the
+    /// header and options, on [`VcfReader::with_threads`] threads. The reader is left where `scan`
+    /// stopped.
+    fn scan_records<T>(
+        &mut self,
+        scan: impl FnOnce(
+            &mut dyn Iterator<Item = Result<vcf::Record, ArrowError>>,
+            &vcf::Header,
+            &VcfOptions,
+        ) -> Result<T, ArrowError>,
+    ) -> Result<T, ArrowError> {
+        let threads = if self.seekable { self.threads } else { 1 };
+        let Some(worker_count) = NonZeroUsize::new(threads).filter(|n| n.get() > 1) else {
+            let (reader, progress) = (&mut self.reader, &mut self.progress);
+
+            let mut records = std::iter::from_fn(|| {
+                if is_cancelled(&self.cancel) {
+                    return None;
+                }
+                let mut record = vcf::Record::default();
+                match reader.read_record(&self.header, &mut record) {
+                    Ok(0) => None,
+                    Ok(_) => {
+                        track(progress, reader.virtual_position());
+                        Some(Ok(record))
+                    }
+                    Err(e) => Some(Err(ArrowError::ExternalError(e.into()))),
+                }
+            });
+            let result = scan(&mut records, &self.header, &self.options);
+            finish_progress(&mut self.progress, self.reader.virtual_position());
+            return result;
+        };
+        let vpos = self.reader.virtual_position();
+        let mut inner = bgzf::reader::Builder::default()
+            .set_worker_count(worker_count)
+            .build_from_reader(self.reader.get_mut().get_mut());
+        inner
+            .seek(vpos)
+            .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        let mut reader = vcf::Reader::new(inner);
+        // As in `BamReader::scan_records`, the reader is moved to the end of the last record read.
+        let mut end = vpos;
+        let mut records = std::iter::from_fn(|| {
+            if is_cancelled(&self.cancel) {
+                return None;
+            }
+            let mut record = vcf::Record::default();
+            match reader.read_record(&self.header, &mut record) {
+                Ok(0) => None,
+                Ok(_) => {
+                    end = reader.virtual_position();
+                    track(&mut self.progress, end);
+                    Some(Ok(record))
+                }
+                Err(e) => Some(Err(ArrowError::ExternalError(e.into()))),
+            }
+        });
+        let result = scan(&mut records, &self.header, &self.options);
+        drop(reader);
+        finish_progress(&mut self.progress, end);
+        self.reader
+            .get_mut()
+            .seek(end)
+            .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        result
+    }
+
+    /// Returns the fields of the batches returned by [`VcfReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        header_fields(&self.header, &self.options)
+    }
+
     /// Returns the records in the given region as Apache Arrow IPC.
     ///
     /// If the region is `None`, all records are returned.
     ///
+    /// The `end` column is the `END` INFO key if present, otherwise the last position of `ref`.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -95,21 +632,221 @@ impl<R: Read + Seek> VcfReader<R> {
     /// let ipc = reader.records_to_ipc(Some("sq0:1-1000")).unwrap();
     /// ```
     pub fn records_to_ipc(&mut self, region: Option<&str>) -> Result<Vec<u8>, ArrowError> {
-        let batch_builder = VcfBatchBuilder::new(1024, &self.header)?;
+        self.scan_map(region, Some)
+    }
+
+    /// Returns the records in the given region for which `predicate` returns true as Apache
+    /// Arrow IPC, like [`VcfReader::records_to_ipc`].
+    ///
+    /// The predicate sees the records before they are subsampled and limited.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap();
+    /// let ipc = reader
+    ///     .scan_with(None, |record| record.alternate_bases().len() > 1)
+    ///     .unwrap();
+    /// ```
+    pub fn scan_with(
+        &mut self,
+        region: Option<&str>,
+        mut predicate: impl FnMut(&vcf::Record) -> bool,
+    ) -> Result<Vec<u8>, ArrowError> {
+        self.scan_map(region, |record| predicate(&record).then_some(record))
+    }
+
+    /// Returns the records in the given region as Apache Arrow IPC, like
+    /// [`VcfReader::records_to_ipc`], after passing each through `f`, which returns the record to
+    /// write, e.g. with a changed field, or `None` to drop it.
+    ///
+    /// `f` sees the records before they are subsampled and limited.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap();
+    /// let ipc = reader
+    ///     .scan_map(None, |mut record| {
+    ///         *record.quality_score_mut() = None;
+    ///         Some(record)
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn scan_map(
+        &mut self,
+        region: Option<&str>,
+        mut f: impl FnMut(vcf::Record) -> Option<vcf::Record>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        if region.is_some() {
+            check_no_offsets(self.options.emit_offset)?;
+        } else if self.options.emit_offset.unwrap_or(false) {
+            let vpos_lo = self.reader.virtual_position();
+            return self.vpos_range_to_ipc(vpos_lo, bgzf::VirtualPosition::MAX, f);
+        }
+        let batch_builder = VcfBatchBuilder::new(1024, &self.header, &self.options)?
+            .with_metadata(provenance("vcf", self.path.as_deref(), region.as_slice()));
+        let limit = self.limit.unwrap_or(usize::MAX);
         if let Some(region) = region {
-            let region: Region = region.parse().unwrap();
+            let region = self.query_region(region)?;
             let query = self
                 .reader
                 .query(&self.header, &self.index, &region)
                 .map_err(|e| ArrowError::ExternalError(e.into()))?
                 .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-            return write_ipc_err(query, batch_builder);
+            let query = track_query(query, &mut self.progress, &self.cancel);
+            let records = self.options.subsample(map_records(query, &mut f))?;
+            let result = write_ipc_err(records.take(limit), batch_builder);
+            finish_query(&mut self.progress);
+            return result;
+        }
+        self.scan_records(|records, header, options| {
+            let records = check_sorted(records, header, options);
+            let records = options.subsample(map_records(records, &mut f))?;
+            write_ipc_err(records.take(limit), batch_builder)
+        })
+    }
+
+    /// Returns the records in the given regions as Apache Arrow IPC, in region order.
+    ///
+    /// Records overlapping more than one region are returned once per region.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap();
+    /// let ipc = reader.regions_to_ipc(&["sq0:1-1000", "sq1"]).unwrap();
+    /// ```
+    pub fn regions_to_ipc(&mut self, regions: &[&str]) -> Result<Vec<u8>, ArrowError> {
+        check_no_offsets(self.options.emit_offset)?;
+        let metadata = provenance("vcf", self.path.as_deref(), regions);
+        let regions = regions
+            .iter()
+            .map(|region| self.query_region(region))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut batch_builder =
+            VcfBatchBuilder::new(1024, &self.header, &self.options)?.with_metadata(metadata);
+        let mut remaining = self.limit.unwrap_or(usize::MAX);
+        for region in regions.iter() {
+            if remaining == 0 {
+                break;
+            }
+            let query = self
+                .reader
+                .query(&self.header, &self.index, region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+            for record in track_query(query.take(remaining), &mut self.progress, &self.cancel) {
+                let record = record.map_err(|e| ArrowError::ExternalError(e.into()))?;
+                batch_builder.push(&record);
+                remaining -= 1;
+            }
         }
-        let records = self
-            .reader
-            .records(&self.header)
-            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-        write_ipc_err(records, batch_builder)
+        finish_query(&mut self.progress);
+        finish_batch(batch_builder)
+    }
+
+    /// Returns the records in the given regions as Apache Arrow IPC, querying them in parallel.
+    ///
+    /// The regions are split into contiguous chunks that are queried on up to `threads` threads
+    /// (by default, the available parallelism), each with its own file handle. The output is the
+    /// same as [`VcfReader::regions_to_ipc`]. This requires a reader created from a file path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap();
+    /// let ipc = reader
+    ///     .regions_to_ipc_parallel(&["sq0:1-1000", "sq1"], Some(4))
+    ///     .unwrap();
+    /// ```
+    pub fn regions_to_ipc_parallel(
+        &mut self,
+        regions: &[&str],
+        threads: Option<usize>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        check_no_offsets(self.options.emit_offset)?;
+        let path = self.path.as_ref().ok_or_else(|| {
+            ArrowError::InvalidArgumentError(
+                "Parallel queries require a reader created from a file path.".to_string(),
+            )
+        })?;
+        let metadata = provenance("vcf", Some(path), regions);
+        let regions = regions
+            .iter()
+            .map(|region| self.query_region(region))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (header, index) = (&self.header, &self.index);
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let (progress, cancel) = (&mut self.progress, &self.cancel);
+        let mut records = query_parallel(&regions, threads, progress, |regions, counter| {
+            let file = File::open(path).map_err(|e| ArrowError::ExternalError(e.into()))?;
+            let buf_file = BufReader::with_capacity(BUFFER_SIZE_BYTES, file);
+            let mut reader = vcf::Reader::new(bgzf::Reader::new(buf_file));
+            let mut records = Vec::new();
+            for region in regions {
+                let query = reader
+                    .query(header, index, region)
+                    .map_err(|e| ArrowError::ExternalError(e.into()))?;
+                // Each chunk stops at the limit, and the concatenation is truncated to it below.
+                for record in query.take(limit - records.len()) {
+                    if is_cancelled(cancel) {
+                        return Ok(records);
+                    }
+                    records.push(record.map_err(|e| ArrowError::ExternalError(e.into()))?);
+                    counter.record();
+                }
+            }
+            Ok(records)
+        })?;
+        records.truncate(limit);
+        let batch_builder = VcfBatchBuilder::new(records.len(), &self.header, &self.options)?
+            .with_metadata(metadata);
+        write_ipc(records.into_iter(), batch_builder)
+    }
+
+    /// Returns the number of records in the given region, without converting them to Arrow.
+    ///
+    /// If the region is `None`, all records are counted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap();
+    /// let n = reader.count(Some("sq0:1-1000")).unwrap();
+    /// ```
+    pub fn count(&mut self, region: Option<&str>) -> Result<usize, ArrowError> {
+        if let Some(region) = region {
+            let region = self.query_region(region)?;
+            let query = self
+                .reader
+                .query(&self.header, &self.index, &region)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?
+                .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+            let count = count_err(track_query(query, &mut self.progress, &self.cancel));
+            finish_query(&mut self.progress);
+            return count;
+        }
+        self.scan_records(|records, header, options| {
+            count_err(check_sorted(records, header, options))
+        })
+    }
+
+    /// Returns the number of records in each of the given regions.
+    pub fn count_regions(&mut self, regions: &[&str]) -> Result<Vec<usize>, ArrowError> {
+        regions
+            .iter()
+            .map(|region| self.count(Some(region)))
+            .collect()
     }
 
     pub fn records_to_ipc_from_vpos(
@@ -121,27 +858,645 @@ impl<R: Read + Seek> VcfReader<R> {
             .map_err(|e| ArrowError::ExternalError(e.into()))?;
         let vpos_hi = bgzf::VirtualPosition::try_from(pos_hi)
             .map_err(|e| ArrowError::ExternalError(e.into()))?;
-        let batch_builder = VcfBatchBuilder::new(1024, &self.header)?;
-        let records = VcfRecords::new(&mut self.reader, &self.header, vpos_lo, vpos_hi)
-            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
-        write_ipc_err(records, batch_builder)
+        self.vpos_range_to_ipc(vpos_lo, vpos_hi, Some)
+    }
+
+    /// Returns the records from a BGZF virtual offset to the end of the file as Apache Arrow
+    /// IPC, e.g. to resume a scan from an offset emitted with [`VcfOptions::emit_offset`].
+    ///
+    /// The offset must be the start of a line, not before the first record, from which a record
+    /// can be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::vcf::VcfReader;
+    ///
+    /// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap();
+    /// let ipc = reader.scan_from(123 << 16).unwrap();
+    /// ```
+    pub fn scan_from(&mut self, vpos: u64) -> Result<Vec<u8>, ArrowError> {
+        let vpos = bgzf::VirtualPosition::from(vpos);
+        self.check_record_start(vpos)?;
+        self.vpos_range_to_ipc(vpos, bgzf::VirtualPosition::MAX, Some)
+    }
+
+    /// Fails unless `vpos` starts a line from which a record can be read.
+    fn check_record_start(&mut self, vpos: bgzf::VirtualPosition) -> Result<(), ArrowError> {
+        let invalid = || {
+            ArrowError::InvalidArgumentError(format!(
+                "Invalid virtual position: {}. Expected the start of a record.",
+                u64::from(vpos)
+            ))
+        };
+        if vpos < self.records_start {
+            return Err(invalid());
+        }
+        // Within a block, a line starts after a newline.
+        if vpos.uncompressed() > 0 {
+            let previous = (vpos.compressed(), vpos.uncompressed() - 1);
+            let previous = bgzf::VirtualPosition::try_from(previous).map_err(|_| invalid())?;
+            let mut byte = [0];
+            let reader = self.reader.get_mut();
+            reader.seek(previous).map_err(|_| invalid())?;
+            reader.read_exact(&mut byte).map_err(|_| invalid())?;
+            if byte != [b'\n'] {
+                return Err(invalid());
+            }
+        }
+        self.reader.seek(vpos).map_err(|_| invalid())?;
+        let mut record = vcf::Record::default();
+        self.reader
+            .read_record(&self.header, &mut record)
+            .map_err(|_| invalid())?;
+        Ok(())
+    }
+
+    /// Returns the records from `vpos_lo` up to `vpos_hi` passed through `f` as Apache Arrow IPC,
+    /// with their virtual offsets if [`VcfOptions::emit_offset`] is set.
+    fn vpos_range_to_ipc(
+        &mut self,
+        vpos_lo: bgzf::VirtualPosition,
+        vpos_hi: bgzf::VirtualPosition,
+        mut f: impl FnMut(vcf::Record) -> Option<vcf::Record>,
+    ) -> Result<Vec<u8>, ArrowError> {
+        self.check_seekable("Reading virtual offsets")?;
+        check_offsets(&self.options)?;
+        let batch_builder = VcfBatchBuilder::new(1024, &self.header, &self.options)?;
+        let metadata = provenance("vcf", self.path.as_deref(), &[]);
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let (progress, cancel) = (&mut self.progress, &self.cancel);
+        let records =
+            VcfRecords::new(&mut self.reader, &self.header, vpos_lo, vpos_hi).with_offsets();
+        let records = until_cancelled(records, cancel).map(|i| {
+            let (vpos, record) = i.map_err(|e| ArrowError::ExternalError(e.into()))?;
+            track(progress, bgzf::VirtualPosition::from(vpos));
+            Ok((vpos, record))
+        });
+        let result = if self.options.emit_offset.unwrap_or(false) {
+            let records = map_records(records, |(vpos, record)| f(record).map(|r| (vpos, r)));
+            write_ipc_err(
+                records.take(limit),
+                WithOffsets::new(batch_builder).with_metadata(metadata),
+            )
+        } else {
+            let records = map_records(records.map(|i| i.map(|(_, record)| record)), f);
+            write_ipc_err(records.take(limit), batch_builder.with_metadata(metadata))
+        };
+        finish_progress(&mut self.progress, self.reader.virtual_position());
+        result
     }
 }
 
-struct VcfBatchBuilder {
-    chrom: StringDictionaryBuilder<Int32Type>,
-    pos: Int32Builder,
-    id: GenericStringBuilder<i32>,
+pub(crate) struct VcfBatchBuilder {
+    /// The index of each contig in the header.
+    contigs: HashMap<String, usize>,
+    chrom: ReferenceColumnBuilder,
+    pos: PositionBuilder,
+    end: PositionBuilder,
+    id: SeparatedColumnBuilder,
     ref_: GenericStringBuilder<i32>,
     alt: GenericStringBuilder<i32>,
     qual: Float32Builder,
-    filter: GenericStringBuilder<i32>,
+    filter: SeparatedColumnBuilder,
     info: GenericStringBuilder<i32>,
     format: GenericStringBuilder<i32>,
+    info_fields: Option<Vec<(InfoKey, FieldColumnBuilder)>>,
+    genotype_fields: Vec<GenotypeColumn>,
+    coordinate_system: CoordinateSystem,
+    expand_gvcf_blocks: bool,
+    sv_fields: Option<(GenericStringBuilder<i32>, Int32Builder)>,
+    breakend_mates: Option<(GenericStringBuilder<i32>, Int32Builder)>,
+    allele_numbers: Option<AlleleNumbers>,
+    fields: Option<Vec<String>>,
+}
+
+/// A column of `;`-separated values, such as `id` and `filter`, as written or as a list.
+enum SeparatedColumnBuilder {
+    Joined(GenericStringBuilder<i32>),
+    Split(ListBuilder<GenericStringBuilder<i32>>),
+}
+
+impl SeparatedColumnBuilder {
+    fn new(split: bool) -> Self {
+        if split {
+            Self::Split(ListBuilder::new(GenericStringBuilder::<i32>::new()))
+        } else {
+            Self::Joined(GenericStringBuilder::<i32>::new())
+        }
+    }
+
+    /// Appends the values of a record, or a null if it has none.
+    fn append<'a>(&mut self, values: impl ExactSizeIterator<Item = &'a str>) {
+        if values.len() == 0 {
+            return self.append_null();
+        }
+        match self {
+            Self::Joined(builder) => builder.append_value(values.collect::<Vec<_>>().join(";")),
+            Self::Split(builder) => {
+                for value in values {
+                    builder.values().append_value(value);
+                }
+                builder.append(true);
+            }
+        }
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            Self::Joined(builder) => builder.append_null(),
+            Self::Split(builder) => builder.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Joined(builder) => Arc::new(builder.finish()),
+            Self::Split(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// A borrowed INFO or FORMAT field value.
+enum FieldValue<'a> {
+    Integer(i32),
+    Float(f32),
+    Character(char),
+    String(&'a str),
+    IntegerArray(&'a [Option<i32>]),
+    FloatArray(&'a [Option<f32>]),
+    CharacterArray(&'a [Option<char>]),
+    StringArray(&'a [Option<String>]),
+}
+
+impl<'a> FieldValue<'a> {
+    /// Returns the value of an INFO field, or `None` for a flag.
+    fn from_info(value: &'a InfoValue) -> Option<Self> {
+        let value = match value {
+            InfoValue::Integer(n) => Self::Integer(*n),
+            InfoValue::Float(n) => Self::Float(*n),
+            InfoValue::Flag => return None,
+            InfoValue::Character(c) => Self::Character(*c),
+            InfoValue::String(s) => Self::String(s),
+            InfoValue::Array(InfoArray::Integer(values)) => Self::IntegerArray(values),
+            InfoValue::Array(InfoArray::Float(values)) => Self::FloatArray(values),
+            InfoValue::Array(InfoArray::Character(values)) => Self::CharacterArray(values),
+            InfoValue::Array(InfoArray::String(values)) => Self::StringArray(values),
+        };
+        Some(value)
+    }
+
+    fn from_sample(value: &'a SampleValue) -> Self {
+        match value {
+            SampleValue::Integer(n) => Self::Integer(*n),
+            SampleValue::Float(n) => Self::Float(*n),
+            SampleValue::Character(c) => Self::Character(*c),
+            SampleValue::String(s) => Self::String(s),
+            SampleValue::Array(SampleArray::Integer(values)) => Self::IntegerArray(values),
+            SampleValue::Array(SampleArray::Float(values)) => Self::FloatArray(values),
+            SampleValue::Array(SampleArray::Character(values)) => Self::CharacterArray(values),
+            SampleValue::Array(SampleArray::String(values)) => Self::StringArray(values),
+        }
+    }
+}
+
+impl std::fmt::Display for FieldValue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn join<T: ToString>(values: &[Option<T>]) -> String {
+            values
+                .iter()
+                .map(|v| v.as_ref().map_or(".".to_string(), |v| v.to_string()))
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+        match self {
+            Self::Integer(n) => write!(f, "{n}"),
+            Self::Float(n) => write!(f, "{n}"),
+            Self::Character(c) => write!(f, "{c}"),
+            Self::String(s) => write!(f, "{s}"),
+            Self::IntegerArray(values) => write!(f, "{}", join(values)),
+            Self::FloatArray(values) => write!(f, "{}", join(values)),
+            Self::CharacterArray(values) => write!(f, "{}", join(values)),
+            Self::StringArray(values) => write!(f, "{}", join(values)),
+        }
+    }
+}
+
+/// A typed column builder for an INFO or FORMAT field.
+enum FieldColumnBuilder {
+    Flag(BooleanBuilder),
+    Integer(Int32Builder),
+    Float(Float32Builder),
+    String(GenericStringBuilder<i32>),
+    IntegerList(ListBuilder<Int32Builder>),
+    FloatList(ListBuilder<Float32Builder>),
+    StringList(ListBuilder<GenericStringBuilder<i32>>),
+}
+
+impl FieldColumnBuilder {
+    fn from_info(info: &Map<Info>) -> Self {
+        let is_list = !matches!(info.number(), Number::Count(0) | Number::Count(1));
+        match info.ty() {
+            InfoType::Flag => Self::Flag(BooleanBuilder::new()),
+            InfoType::Integer => Self::new_integer(is_list),
+            InfoType::Float => Self::new_float(is_list),
+            InfoType::Character | InfoType::String => Self::new_string(is_list),
+        }
+    }
+
+    fn from_format(format: &Map<Format>) -> Self {
+        let is_list = !matches!(format.number(), Number::Count(0) | Number::Count(1));
+        match format.ty() {
+            FormatType::Integer => Self::new_integer(is_list),
+            FormatType::Float => Self::new_float(is_list),
+            FormatType::Character | FormatType::String => Self::new_string(is_list),
+        }
+    }
+
+    fn new_integer(is_list: bool) -> Self {
+        if is_list {
+            Self::IntegerList(ListBuilder::new(Int32Builder::new()))
+        } else {
+            Self::Integer(Int32Builder::new())
+        }
+    }
+
+    fn new_float(is_list: bool) -> Self {
+        if is_list {
+            Self::FloatList(ListBuilder::new(Float32Builder::new()))
+        } else {
+            Self::Float(Float32Builder::new())
+        }
+    }
+
+    fn new_string(is_list: bool) -> Self {
+        if is_list {
+            Self::StringList(ListBuilder::new(GenericStringBuilder::<i32>::new()))
+        } else {
+            Self::String(GenericStringBuilder::<i32>::new())
+        }
+    }
+
+    /// Appends the value of an INFO field.
+    ///
+    /// `value` is `None` if the field is absent, and `Some(None)` if its value is missing (`.`).
+    fn append_info(&mut self, value: Option<Option<&InfoValue>>) {
+        match self {
+            Self::Flag(builder) => builder.append_value(value.is_some()),
+            builder => builder.append(value.flatten().and_then(FieldValue::from_info)),
+        }
+    }
+
+    /// Appends the value of a FORMAT field.
+    fn append_sample(&mut self, value: Option<Option<&SampleValue>>) {
+        self.append(value.flatten().map(FieldValue::from_sample));
+    }
+
+    /// Appends a value, or a null if it is missing or does not match the column type.
+    ///
+    /// A single value in a list column becomes a one-element list, and any value in a string column
+    /// is written as text.
+    fn append(&mut self, value: Option<FieldValue>) {
+        let Some(value) = value else {
+            self.append_null();
+            return;
+        };
+        match (self, value) {
+            (Self::Integer(builder), FieldValue::Integer(n)) => builder.append_value(n),
+            (Self::Float(builder), FieldValue::Float(n)) => builder.append_value(n),
+            (Self::String(builder), value) => builder.append_value(value.to_string()),
+            (Self::IntegerList(builder), FieldValue::Integer(n)) => {
+                builder.values().append_value(n);
+                builder.append(true);
+            }
+            (Self::IntegerList(builder), FieldValue::IntegerArray(values)) => {
+                builder.values().extend(values.iter().copied());
+                builder.append(true);
+            }
+            (Self::FloatList(builder), FieldValue::Float(n)) => {
+                builder.values().append_value(n);
+                builder.append(true);
+            }
+            (Self::FloatList(builder), FieldValue::FloatArray(values)) => {
+                builder.values().extend(values.iter().copied());
+                builder.append(true);
+            }
+            (Self::StringList(builder), FieldValue::StringArray(values)) => {
+                builder.values().extend(values.iter().map(|v| v.as_deref()));
+                builder.append(true);
+            }
+            (Self::StringList(builder), FieldValue::CharacterArray(values)) => {
+                builder
+                    .values()
+                    .extend(values.iter().map(|v| v.map(|c| c.to_string())));
+                builder.append(true);
+            }
+            // BCF stores string lists as a single comma-separated string.
+            (Self::StringList(builder), FieldValue::String(value)) => {
+                builder
+                    .values()
+                    .extend(value.split(',').map(|v| (v != ".").then_some(v)));
+                builder.append(true);
+            }
+            (Self::StringList(builder), value) => {
+                builder.values().append_value(value.to_string());
+                builder.append(true);
+            }
+            (builder, _) => builder.append_null(),
+        }
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            Self::Flag(builder) => builder.append_null(),
+            Self::Integer(builder) => builder.append_null(),
+            Self::Float(builder) => builder.append_null(),
+            Self::String(builder) => builder.append_null(),
+            Self::IntegerList(builder) => builder.append_null(),
+            Self::FloatList(builder) => builder.append_null(),
+            Self::StringList(builder) => builder.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Flag(builder) => Arc::new(builder.finish()),
+            Self::Integer(builder) => Arc::new(builder.finish()),
+            Self::Float(builder) => Arc::new(builder.finish()),
+            Self::String(builder) => Arc::new(builder.finish()),
+            Self::IntegerList(builder) => Arc::new(builder.finish()),
+            Self::FloatList(builder) => Arc::new(builder.finish()),
+            Self::StringList(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// A column holding a FORMAT field of one sample.
+struct GenotypeColumn {
+    name: String,
+    sample: usize,
+    key: FormatKey,
+    builder: FieldColumnBuilder,
+}
+
+fn info_columns(
+    header: &vcf::Header,
+    options: &VcfOptions,
+) -> Result<Option<Vec<(InfoKey, FieldColumnBuilder)>>, ArrowError> {
+    let Some(keys) = options.info_fields.as_ref() else {
+        return Ok(None);
+    };
+    keys.iter()
+        .map(|key| {
+            let (key, info) = header.infos().get_key_value(key.as_str()).ok_or_else(|| {
+                ArrowError::InvalidArgumentError(format!("INFO field not found in header: {}", key))
+            })?;
+            Ok((key.clone(), FieldColumnBuilder::from_info(info)))
+        })
+        .collect::<Result<Vec<_>, ArrowError>>()
+        .map(Some)
+}
+
+fn genotype_columns(
+    header: &vcf::Header,
+    options: &VcfOptions,
+) -> Result<Vec<GenotypeColumn>, ArrowError> {
+    let Some(keys) = options.genotype_fields.as_ref() else {
+        return Ok(Vec::new());
+    };
+    let keys = keys
+        .iter()
+        .map(|key| {
+            header.formats().get_key_value(key.as_str()).ok_or_else(|| {
+                ArrowError::InvalidArgumentError(format!(
+                    "FORMAT field not found in header: {}",
+                    key
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, ArrowError>>()?;
+    let samples: Vec<(usize, &str)> = match options.samples.as_ref() {
+        Some(samples) => samples
+            .iter()
+            .map(|sample| {
+                header
+                    .sample_names()
+                    .get_full(sample.as_str())
+                    .map(|(i, name)| (i, name.as_str()))
+                    .ok_or_else(|| {
+                        ArrowError::InvalidArgumentError(format!(
+                            "Sample not found in header: {}",
+                            sample
+                        ))
+                    })
+            })
+            .collect::<Result<_, ArrowError>>()?,
+        None => header
+            .sample_names()
+            .iter()
+            .map(|name| name.as_str())
+            .enumerate()
+            .collect(),
+    };
+    let columns = samples
+        .iter()
+        .flat_map(|&(sample, name)| {
+            keys.iter().map(move |(key, format)| GenotypeColumn {
+                name: format!("{}.{}", name, key),
+                sample,
+                key: (*key).clone(),
+                builder: FieldColumnBuilder::from_format(format),
+            })
+        })
+        .collect();
+    Ok(columns)
+}
+
+/// The `Number` of each INFO and FORMAT field in a header, used to split multi-allelic records.
+struct AlleleNumbers {
+    info: HashMap<InfoKey, Number>,
+    format: HashMap<FormatKey, Number>,
+}
+
+impl AlleleNumbers {
+    fn new(header: &vcf::Header) -> Self {
+        Self {
+            info: header
+                .infos()
+                .iter()
+                .map(|(key, info)| (key.clone(), info.number()))
+                .collect(),
+            format: header
+                .formats()
+                .iter()
+                .map(|(key, format)| (key.clone(), format.number()))
+                .collect(),
+        }
+    }
+
+    /// Returns one biallelic record per ALT allele of `record`.
+    fn split(&self, record: &vcf::record::Record) -> Vec<vcf::record::Record> {
+        let n_alts = record.alternate_bases().len();
+        (1..=n_alts)
+            .map(|allele| {
+                let mut split = record.clone();
+                let alt = record.alternate_bases()[allele - 1].clone();
+                *split.alternate_bases_mut() = vec![alt].into();
+                for (key, value) in split.info_mut().as_mut().iter_mut() {
+                    let Some(number) = self.info.get(key) else {
+                        continue;
+                    };
+                    if let Some(Some(selected)) = value
+                        .as_ref()
+                        .map(|value| select_info(value, *number, n_alts, allele))
+                    {
+                        *value = Some(selected);
+                    }
+                }
+                let genotypes = record.genotypes();
+                let keys = genotypes.keys().clone();
+                let values = genotypes
+                    .values()
+                    .map(|sample| {
+                        keys.iter()
+                            .zip(sample.values())
+                            .map(|(key, value)| {
+                                let value = value.as_ref()?;
+                                if key == &vcf::record::genotypes::keys::key::GENOTYPE {
+                                    if let SampleValue::String(gt) = value {
+                                        return Some(SampleValue::String(recode_genotype(
+                                            gt, allele,
+                                        )));
+                                    }
+                                }
+                                self.format
+                                    .get(key)
+                                    .and_then(|number| {
+                                        select_sample(value, *number, n_alts, allele)
+                                    })
+                                    .or_else(|| Some(value.clone()))
+                            })
+                            .collect()
+                    })
+                    .collect();
+                *split.genotypes_mut() = vcf::record::Genotypes::new(keys, values);
+                split
+            })
+            .collect()
+    }
+}
+
+/// Returns the indices of the values of a field with `number` that belong to the reference and
+/// ALT allele `allele` (1-based) of `n_alts`, or `None` if the values are shared by all alleles.
+fn allele_indices(number: Number, len: usize, n_alts: usize, allele: usize) -> Option<Vec<usize>> {
+    match number {
+        Number::A => Some(vec![allele - 1]),
+        Number::R => Some(vec![0, allele]),
+        // Haploid genotypes have one likelihood per allele, diploid ones one per unordered pair.
+        Number::G if len == n_alts + 1 => Some(vec![0, allele]),
+        Number::G => {
+            let het = allele * (allele + 1) / 2;
+            Some(vec![0, het, het + allele])
+        }
+        _ => None,
+    }
+}
+
+/// Returns the values at `indices`, or `None` if any is out of bounds.
+fn select<T: Clone>(values: &[Option<T>], indices: &[usize]) -> Option<Vec<Option<T>>> {
+    indices.iter().map(|&i| values.get(i).cloned()).collect()
+}
+
+/// Returns the values of an INFO field that belong to `allele`, or `None` if they are shared.
+fn select_info(
+    value: &InfoValue,
+    number: Number,
+    n_alts: usize,
+    allele: usize,
+) -> Option<InfoValue> {
+    let InfoValue::Array(array) = value else {
+        return None;
+    };
+    let array = match array {
+        InfoArray::Integer(values) => InfoArray::Integer(select(
+            values,
+            &allele_indices(number, values.len(), n_alts, allele)?,
+        )?),
+        InfoArray::Float(values) => InfoArray::Float(select(
+            values,
+            &allele_indices(number, values.len(), n_alts, allele)?,
+        )?),
+        InfoArray::Character(values) => InfoArray::Character(select(
+            values,
+            &allele_indices(number, values.len(), n_alts, allele)?,
+        )?),
+        InfoArray::String(values) => InfoArray::String(select(
+            values,
+            &allele_indices(number, values.len(), n_alts, allele)?,
+        )?),
+    };
+    Some(InfoValue::Array(array))
+}
+
+/// Returns the values of a FORMAT field that belong to `allele`, or `None` if they are shared.
+fn select_sample(
+    value: &SampleValue,
+    number: Number,
+    n_alts: usize,
+    allele: usize,
+) -> Option<SampleValue> {
+    let SampleValue::Array(array) = value else {
+        return None;
+    };
+    let array = match array {
+        SampleArray::Integer(values) => SampleArray::Integer(select(
+            values,
+            &allele_indices(number, values.len(), n_alts, allele)?,
+        )?),
+        SampleArray::Float(values) => SampleArray::Float(select(
+            values,
+            &allele_indices(number, values.len(), n_alts, allele)?,
+        )?),
+        SampleArray::Character(values) => SampleArray::Character(select(
+            values,
+            &allele_indices(number, values.len(), n_alts, allele)?,
+        )?),
+        SampleArray::String(values) => SampleArray::String(select(
+            values,
+            &allele_indices(number, values.len(), n_alts, allele)?,
+        )?),
+    };
+    Some(SampleValue::Array(array))
+}
+
+/// Recodes a genotype such as `1/2` for `allele`, which becomes `1`. Other ALT alleles become
+/// `0`, and missing alleles and phasing are kept.
+fn recode_genotype(gt: &str, allele: usize) -> String {
+    let mut recoded = String::with_capacity(gt.len());
+    let mut rest = gt;
+    loop {
+        let end = rest.find(['/', '|']).unwrap_or(rest.len());
+        match rest[..end].parse::<usize>() {
+            Ok(n) if n == allele => recoded.push('1'),
+            Ok(_) => recoded.push('0'),
+            Err(_) => recoded.push_str(&rest[..end]),
+        }
+        let Some(separator) = rest[end..].chars().next() else {
+            return recoded;
+        };
+        recoded.push(separator);
+        rest = &rest[end + 1..];
+    }
 }
 
 impl VcfBatchBuilder {
-    pub fn new(capacity: usize, header: &vcf::Header) -> Result<Self, ArrowError> {
+    pub fn new(
+        capacity: usize,
+        header: &vcf::Header,
+        options: &VcfOptions,
+    ) -> Result<Self, ArrowError> {
+        let info_fields = info_columns(header, options)?;
+        let genotype_fields = genotype_columns(header, options)?;
         let categories = StringArray::from(
             header
                 .contigs()
@@ -149,53 +1504,201 @@ impl VcfBatchBuilder {
                 .map(|k| k.to_string())
                 .collect::<Vec<_>>(),
         );
+        let contigs = header
+            .contigs()
+            .keys()
+            .enumerate()
+            .map(|(i, name)| (name.to_string(), i))
+            .collect();
+        let as_index = options.emit_ref_as_index.unwrap_or(false);
+        let unsigned = options.unsigned_positions.unwrap_or(false);
+        let wide = needs_wide_positions(header.contigs().values().filter_map(|c| c.length()));
         Ok(Self {
-            chrom: StringDictionaryBuilder::<Int32Type>::new_with_dictionary(
-                capacity,
-                &categories,
-            )?,
-            pos: Int32Builder::with_capacity(capacity),
-            id: GenericStringBuilder::<i32>::new(),
+            contigs,
+            chrom: ReferenceColumnBuilder::new(capacity, categories, as_index)?,
+            pos: PositionBuilder::new(capacity, unsigned, wide),
+            end: PositionBuilder::new(capacity, unsigned, wide),
+            id: SeparatedColumnBuilder::new(options.split_ids.unwrap_or(false)),
             ref_: GenericStringBuilder::<i32>::new(),
             alt: GenericStringBuilder::<i32>::new(),
             qual: Float32Builder::with_capacity(capacity),
-            filter: GenericStringBuilder::<i32>::new(),
+            filter: SeparatedColumnBuilder::new(options.split_filters.unwrap_or(false)),
             info: GenericStringBuilder::<i32>::new(),
             format: GenericStringBuilder::<i32>::new(),
+            info_fields,
+            genotype_fields,
+            coordinate_system: options
+                .coordinate_system
+                .unwrap_or(CoordinateSystem::OneBased),
+            expand_gvcf_blocks: options.expand_gvcf_blocks.unwrap_or(false),
+            sv_fields: options.include_sv_fields.unwrap_or(false).then(|| {
+                (
+                    GenericStringBuilder::<i32>::new(),
+                    Int32Builder::with_capacity(capacity),
+                )
+            }),
+            allele_numbers: options
+                .split_multiallelic
+                .unwrap_or(false)
+                .then(|| AlleleNumbers::new(header)),
+            fields: options.fields.clone(),
+            breakend_mates: options.include_breakend_mates.unwrap_or(false).then(|| {
+                (
+                    GenericStringBuilder::<i32>::new(),
+                    Int32Builder::with_capacity(capacity),
+                )
+            }),
         })
     }
-}
 
-impl BatchBuilder for VcfBatchBuilder {
-    type Record<'a> = &'a vcf::record::Record;
+    /// Appends the rows of `record`, one per position if it is an expanded gVCF block.
+    fn push_record(&mut self, record: &vcf::record::Record) {
+        let pos = usize::from(record.position());
+        // The `END` INFO key if present, otherwise the last base of `ref`. Symbolic alleles such
+        // as `<NON_REF>` do not affect it.
+        let end = record.end().ok().map(usize::from);
+        let is_block = matches!(
+            record
+                .info()
+                .get(&vcf::record::info::field::key::END_POSITION),
+            Some(Some(_))
+        );
+        match end {
+            Some(end) if self.expand_gvcf_blocks && is_block => {
+                for pos in pos..=end.max(pos) {
+                    self.push_row(record, pos, Some(pos));
+                }
+            }
+            _ => self.push_row(record, pos, end),
+        }
+    }
 
-    fn push(&mut self, record: Self::Record<'_>) {
-        self.chrom.append_value(record.chromosome().to_string());
-        self.pos.append_value(usize::from(record.position()) as i32);
-        self.id.append_value(record.ids().to_string());
+    /// Appends a row for `record` spanning `pos` to `end`, in 1-based coordinates.
+    fn push_row(&mut self, record: &vcf::record::Record, pos: usize, end: Option<usize>) {
+        let chrom = record.chromosome().to_string();
+        self.chrom
+            .append_name(&chrom, self.contigs.get(&chrom).copied());
+        self.pos.append_value(
+            self.coordinate_system
+                .start_from(CoordinateSystem::OneBased, pos as i64),
+        );
+        self.end.append_option(end.map(|end| end as i64));
+        self.id.append(record.ids().iter().map(|id| id.as_ref()));
         self.ref_.append_value(record.reference_bases().to_string());
         self.alt.append_value(record.alternate_bases().to_string());
         self.qual
             .append_option(record.quality_score().map(f32::from));
-        self.filter
-            .append_option(record.filters().map(|f| f.to_string()));
-        self.info.append_value(record.info().to_string());
+        match record.filters() {
+            Some(vcf::record::Filters::Pass) => self.filter.append(["PASS"].into_iter()),
+            Some(vcf::record::Filters::Fail(filters)) => {
+                self.filter.append(filters.iter().map(String::as_str))
+            }
+            None => self.filter.append_null(),
+        }
+        if let Some((svtype, svlen)) = self.sv_fields.as_mut() {
+            use vcf::record::info::field::key;
+            match record.info().get(&key::SV_TYPE) {
+                Some(Some(InfoValue::String(value))) => svtype.append_value(value),
+                _ => svtype.append_null(),
+            }
+            svlen.append_option(match record.info().get(&key::SV_LENGTHS) {
+                Some(Some(InfoValue::Integer(n))) => Some(*n),
+                Some(Some(InfoValue::Array(InfoArray::Integer(values)))) => {
+                    values.first().copied().flatten()
+                }
+                _ => None,
+            });
+        }
+        if let Some((mate_chrom, mate_pos)) = self.breakend_mates.as_mut() {
+            let mate = record
+                .alternate_bases()
+                .iter()
+                .find_map(|allele| match allele {
+                    vcf::record::alternate_bases::Allele::Breakend(breakend) => {
+                        breakend_mate(breakend)
+                    }
+                    _ => None,
+                });
+            mate_chrom.append_option(mate.map(|(chrom, _)| chrom));
+            mate_pos.append_option(mate.map(|(_, pos)| pos));
+        }
+        match self.info_fields.as_mut() {
+            Some(info_fields) => {
+                for (key, builder) in info_fields.iter_mut() {
+                    builder.append_info(record.info().get(key));
+                }
+            }
+            None => self.info.append_value(record.info().to_string()),
+        }
         self.format.append_value(record.format().to_string());
+        for column in self.genotype_fields.iter_mut() {
+            let value = record
+                .genotypes()
+                .get_index(column.sample)
+                .and_then(|sample| sample.get(&column.key));
+            column.builder.append_sample(value);
+        }
+    }
+}
+
+impl BatchBuilder for VcfBatchBuilder {
+    type Record<'a> = &'a vcf::record::Record;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        let split = self
+            .allele_numbers
+            .as_ref()
+            .filter(|_| record.alternate_bases().len() > 1)
+            .map(|numbers| numbers.split(record));
+        match split {
+            Some(records) => records.iter().for_each(|record| self.push_record(record)),
+            None => self.push_record(record),
+        }
     }
 
     fn finish(mut self) -> Result<RecordBatch, ArrowError> {
-        RecordBatch::try_from_iter(vec![
+        let mut columns: Vec<(&str, ArrayRef)> = vec![
             // spec
-            ("chrom", Arc::new(self.chrom.finish()) as ArrayRef),
-            ("pos", Arc::new(self.pos.finish()) as ArrayRef),
-            ("id", Arc::new(self.id.finish()) as ArrayRef),
+            ("chrom", self.chrom.finish()),
+            ("pos", self.pos.finish()?),
+            ("end", self.end.finish()?),
+            ("id", self.id.finish()),
             ("ref", Arc::new(self.ref_.finish()) as ArrayRef),
             ("alt", Arc::new(self.alt.finish()) as ArrayRef),
             ("qual", Arc::new(self.qual.finish()) as ArrayRef),
-            ("filter", Arc::new(self.filter.finish()) as ArrayRef),
-            ("info", Arc::new(self.info.finish()) as ArrayRef),
-            ("format", Arc::new(self.format.finish()) as ArrayRef),
-        ])
+            ("filter", self.filter.finish()),
+        ];
+        if let Some((svtype, svlen)) = self.sv_fields.as_mut() {
+            columns.push(("svtype", Arc::new(svtype.finish()) as ArrayRef));
+            columns.push(("svlen", Arc::new(svlen.finish()) as ArrayRef));
+        }
+        if let Some((mate_chrom, mate_pos)) = self.breakend_mates.as_mut() {
+            columns.push(("mate_chrom", Arc::new(mate_chrom.finish()) as ArrayRef));
+            columns.push(("mate_pos", Arc::new(mate_pos.finish()) as ArrayRef));
+        }
+        match self.info_fields.as_mut() {
+            Some(info_fields) => {
+                let arrays: Vec<ArrayRef> = info_fields
+                    .iter_mut()
+                    .map(|(_, builder)| builder.finish())
+                    .collect();
+                columns.extend(info_fields.iter().map(|(key, _)| key.as_ref()).zip(arrays));
+            }
+            None => columns.push(("info", Arc::new(self.info.finish()) as ArrayRef)),
+        }
+        columns.push(("format", Arc::new(self.format.finish()) as ArrayRef));
+        let arrays: Vec<ArrayRef> = self
+            .genotype_fields
+            .iter_mut()
+            .map(|column| column.builder.finish())
+            .collect();
+        columns.extend(
+            self.genotype_fields
+                .iter()
+                .map(|column| column.name.as_str())
+                .zip(arrays),
+        );
+        select_fields(RecordBatch::try_from_iter(columns)?, self.fields.as_deref())
     }
 }
 
@@ -231,6 +1734,18 @@ where
     pub fn reset(&mut self) -> Option<bgzf::VirtualPosition> {
         self.reader.seek(self.vpos_lo).ok()
     }
+
+    /// Returns the records along with the virtual position at which each starts.
+    pub fn with_offsets(mut self) -> impl Iterator<Item = io::Result<(u64, vcf::Record)>> + 'a
+    where
+        R: 'a,
+    {
+        std::iter::from_fn(move || {
+            let vpos = u64::from(self.reader.virtual_position());
+            self.next()
+                .map(|record| record.map(|record| (vpos, record)))
+        })
+    }
 }
 
 impl<R> Iterator for VcfRecords<'_, R>
@@ -252,11 +1767,1041 @@ where
     }
 }
 
+/// Writes batches with the columns returned by [`VcfReader::records_to_ipc`] as bgzipped VCF.
+///
+/// This is the inverse of a scan. Records are rebuilt from the `chrom`, `pos`, `id`, `ref`,
+/// `alt`, `qual` and `filter` columns, the `info` column or, failing that, the columns named
+/// after INFO fields of `header` (see [`VcfOptions::info_fields`]), and the `{sample}.{key}`
+/// columns of the samples in `header` (see [`VcfOptions::genotype_fields`]). Only `chrom`, `pos`
+/// and `ref` are required, and `pos` must be 1-based.
+///
+/// If there are no genotype columns, the samples are dropped from the header and sites only are
+/// written. Other columns, such as `end` or `format`, are ignored.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io::Cursor;
+///
+/// use arrow::ipc::reader::FileReader;
+/// use oxbow::vcf::{write_vcf, VcfReader};
+///
+/// let mut reader = VcfReader::new_from_path("sample.vcf.gz").unwrap();
+/// let ipc = reader.records_to_ipc(None).unwrap();
+/// let batches = FileReader::try_new(Cursor::new(ipc), None).unwrap();
+/// let file = std::fs::File::create("copy.vcf.gz").unwrap();
+/// write_vcf(file, reader.header(), batches).unwrap();
+/// ```
+pub fn write_vcf<W: Write>(
+    write: W,
+    header: &vcf::Header,
+    batches: impl RecordBatchReader,
+) -> Result<(), ArrowError> {
+    let columns = RecordColumns::new(header, &batches.schema());
+    let header = columns.header(header);
+    let mut writer = vcf::Writer::new(bgzf::Writer::new(write));
+    writer
+        .write_header(&header)
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    for batch in batches {
+        for record in columns.records(&header, &batch?)? {
+            writer
+                .write_record(&header, &record)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        }
+    }
+    writer
+        .get_mut()
+        .try_finish()
+        .map_err(|e| ArrowError::ExternalError(e.into()))
+}
+
+/// Writes batches with the columns returned by [`VcfReader::records_to_ipc`] as BCF.
+///
+/// Records are rebuilt as in [`write_vcf`].
+pub fn write_bcf<W: Write>(
+    write: W,
+    header: &vcf::Header,
+    batches: impl RecordBatchReader,
+) -> Result<(), ArrowError> {
+    let columns = RecordColumns::new(header, &batches.schema());
+    let header = columns.header(header);
+    let mut writer = bcf::Writer::new(write);
+    writer
+        .write_header(&header)
+        .map_err(|e| ArrowError::ExternalError(e.into()))?;
+    for batch in batches {
+        for record in columns.records(&header, &batch?)? {
+            writer
+                .write_record(&header, &record)
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+        }
+    }
+    writer
+        .try_finish()
+        .map_err(|e| ArrowError::ExternalError(e.into()))
+}
+
+/// Returns the text of a VCF header, e.g. to pass it to [`write_vcf`] from another language.
+pub fn header_to_string(header: &vcf::Header) -> io::Result<String> {
+    let mut writer = vcf::Writer::new(Vec::new());
+    writer.write_header(header)?;
+    String::from_utf8(writer.into_inner())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Parses the text of a VCF header, as returned by [`header_to_string`].
+pub fn header_from_str(text: &str) -> io::Result<vcf::Header> {
+    text.parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The columns of a schema that records are rebuilt from.
+struct RecordColumns {
+    /// INFO keys with a column, if there is no `info` column.
+    info_fields: Option<Vec<String>>,
+    /// FORMAT keys with a column for at least one sample, with `GT` first.
+    format_keys: Vec<String>,
+}
+
+impl RecordColumns {
+    fn new(header: &vcf::Header, schema: &Schema) -> Self {
+        let has_column = |name: &str| schema.column_with_name(name).is_some();
+        let info_fields = (!has_column("info")).then(|| {
+            header
+                .infos()
+                .keys()
+                .map(|key| key.to_string())
+                .filter(|key| has_column(key))
+                .collect()
+        });
+        let mut format_keys: Vec<String> = header
+            .formats()
+            .keys()
+            .map(|key| key.to_string())
+            .filter(|key| {
+                header
+                    .sample_names()
+                    .iter()
+                    .any(|sample| has_column(&format!("{}.{}", sample, key)))
+            })
+            .collect();
+        format_keys.sort_by_key(|key| key != "GT");
+        Self {
+            info_fields,
+            format_keys,
+        }
+    }
+
+    /// Returns `header`, without samples if there are no genotype columns.
+    fn header(&self, header: &vcf::Header) -> vcf::Header {
+        let mut header = header.clone();
+        if self.format_keys.is_empty() {
+            header.sample_names_mut().clear();
+        }
+        header
+    }
+
+    /// Rebuilds the records of a batch.
+    fn records(
+        &self,
+        header: &vcf::Header,
+        batch: &RecordBatch,
+    ) -> Result<Vec<vcf::Record>, ArrowError> {
+        let column = |name: &str| batch.column_by_name(name);
+        let required = |name: &str| {
+            column(name).ok_or_else(|| {
+                ArrowError::InvalidArgumentError(format!("Missing column: {}", name))
+            })
+        };
+        let chrom = required("chrom")?;
+        let pos = required("pos")?;
+        let ref_ = required("ref")?;
+        let fixed = [
+            column("id"),
+            column("alt"),
+            column("qual"),
+            column("filter"),
+        ];
+        let info = column("info");
+        let genotypes: Vec<Vec<Option<&ArrayRef>>> = header
+            .sample_names()
+            .iter()
+            .map(|sample| {
+                self.format_keys
+                    .iter()
+                    .map(|key| column(&format!("{}.{}", sample, key)))
+                    .collect()
+            })
+            .collect();
+
+        // Missing values, and empty ones such as `id` without IDs, are written as `.`.
+        let text = |array: Option<&ArrayRef>, i| -> Result<String, ArrowError> {
+            let value = match array {
+                Some(array) => value_text(array, i)?,
+                None => None,
+            };
+            Ok(value
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| ".".into()))
+        };
+        // `id` and `filter` separate their values with `;`, also when split into lists.
+        let separated = |array: Option<&ArrayRef>, i| -> Result<String, ArrowError> {
+            match array {
+                Some(array) if array.data_type().is_nested() && array.is_valid(i) => {
+                    let values = array.as_list::<i32>().value(i);
+                    let values = (0..values.len())
+                        .map(|j| value_text(&values, j).map(Option::unwrap_or_default))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Some(values.join(";"))
+                        .filter(|value| !value.is_empty())
+                        .unwrap_or_else(|| ".".into()))
+                }
+                _ => text(array, i),
+            }
+        };
+        let mut records = Vec::with_capacity(batch.num_rows());
+        for i in 0..batch.num_rows() {
+            let chrom = match chrom.data_type() {
+                DataType::Int32 if !chrom.is_null(i) => {
+                    let index = chrom.as_primitive::<arrow::datatypes::Int32Type>().value(i);
+                    header
+                        .contigs()
+                        .get_index(index as usize)
+                        .map(|(name, _)| name.to_string())
+                        .unwrap_or_else(|| ".".into())
+                }
+                _ => text(Some(chrom), i)?,
+            };
+            let mut line = vec![chrom, text(Some(pos), i)?, separated(fixed[0], i)?];
+            line.push(text(Some(ref_), i)?);
+            line.push(text(fixed[1], i)?);
+            line.push(text(fixed[2], i)?);
+            line.push(separated(fixed[3], i)?);
+            line.push(match &self.info_fields {
+                None => text(info, i)?,
+                Some(info_fields) => {
+                    let mut fields = Vec::new();
+                    for key in info_fields {
+                        let array = required(key)?;
+                        match array.data_type() {
+                            DataType::Boolean => {
+                                if !array.is_null(i) && array.as_boolean().value(i) {
+                                    fields.push(key.clone());
+                                }
+                            }
+                            _ => {
+                                if let Some(value) = value_text(array, i)? {
+                                    fields.push(format!("{}={}", key, value));
+                                }
+                            }
+                        }
+                    }
+                    if fields.is_empty() {
+                        ".".into()
+                    } else {
+                        fields.join(";")
+                    }
+                }
+            });
+            if !self.format_keys.is_empty() {
+                let values = genotypes
+                    .iter()
+                    .map(|sample| sample.iter().map(|array| text(*array, i)).collect())
+                    .collect::<Result<Vec<Vec<_>>, _>>()?;
+                // Keys missing in every sample are dropped, as are trailing missing values.
+                let keys: Vec<usize> = (0..self.format_keys.len())
+                    .filter(|&k| values.iter().any(|sample| sample[k] != "."))
+                    .collect();
+                let keys = if keys.is_empty() { vec![0] } else { keys };
+                line.push(
+                    keys.iter()
+                        .map(|&k| self.format_keys[k].as_str())
+                        .collect::<Vec<_>>()
+                        .join(":"),
+                );
+                for sample in &values {
+                    let mut sample: Vec<&str> = keys.iter().map(|&k| sample[k].as_str()).collect();
+                    while sample.len() > 1 && sample.last() == Some(&".") {
+                        sample.pop();
+                    }
+                    line.push(sample.join(":"));
+                }
+            }
+            let record = vcf::Record::try_from((header, line.join("\t").as_str()))
+                .map_err(|e| ArrowError::ExternalError(e.into()))?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+/// Returns the VCF text of a value, with list elements separated by commas, or `None` if it is
+/// null.
+fn value_text(array: &ArrayRef, i: usize) -> Result<Option<String>, ArrowError> {
+    if array.is_null(i) {
+        return Ok(None);
+    }
+    match array.data_type() {
+        DataType::List(_) => {
+            let values = array.as_list::<i32>().value(i);
+            let values = (0..values.len())
+                .map(|j| Ok(value_text(&values, j)?.unwrap_or_else(|| ".".into())))
+                .collect::<Result<Vec<_>, ArrowError>>()?;
+            Ok(Some(values.join(",")))
+        }
+        _ => {
+            let formatter = ArrayFormatter::try_new(array.as_ref(), &FormatOptions::default())?;
+            Ok(Some(formatter.value(i).to_string()))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    // use super::*;
-    // use arrow::ipc::reader::FileReader;
-    // use arrow::record_batch::RecordBatch;
+    use super::*;
+    use crate::batch_builder::nullable_field;
+    use arrow::array::{Array, BooleanArray, Int32Array, ListArray};
+    use arrow::datatypes::DataType;
+    use arrow::ipc::reader::FileReader;
+    use arrow::record_batch::RecordBatch;
+
+    fn read_sample(region: Option<&str>, options: VcfOptions) -> RecordBatch {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let ipc = reader.records_to_ipc(region).unwrap();
+        let cursor = std::io::Cursor::new(ipc);
+        let mut arrow_reader = FileReader::try_new(cursor, None).unwrap();
+        // make sure we have one batch
+        assert_eq!(arrow_reader.num_batches(), 1);
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_emit_ref_as_index() {
+        let options = VcfOptions {
+            emit_ref_as_index: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read_sample(None, options);
+        let chrom = record_batch
+            .column_by_name("chrom")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(chrom.values(), &[0, 0, 0, 1]);
+
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let reader = VcfReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        assert_eq!(reader.reference_names(), ["sq0", "sq1"]);
+    }
+
+    #[test]
+    fn test_fields() {
+        let options = VcfOptions {
+            info_fields: Some(vec!["DP".into()]),
+            fields: Some(vec!["DP".into(), "chrom".into(), "pos".into()]),
+            ..Default::default()
+        };
+        let record_batch = read_sample(None, options);
+        let schema = record_batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["DP", "chrom", "pos"]);
+        assert_eq!(record_batch.num_rows(), 4);
+
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let options = VcfOptions {
+            fields: Some(vec!["chrom".into(), "DP".into()]),
+            ..Default::default()
+        };
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let error = reader.records_to_ipc(None).unwrap_err();
+        assert!(error.to_string().contains("Invalid field: DP"));
+        assert!(error.to_string().contains("chrom, pos, end, id"));
+    }
+
+    #[test]
+    fn test_threads() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let reader = || VcfReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let batches = |ipc: Vec<u8>| {
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.map(|batch| batch.unwrap()).collect::<Vec<_>>()
+        };
+        let single = batches(reader().records_to_ipc(None).unwrap());
+        let mut multi = reader().with_threads(4);
+        assert_eq!(batches(multi.records_to_ipc(None).unwrap()), single);
+        assert_eq!(multi.count(None).unwrap(), 0);
+        assert_eq!(reader().with_threads(4).count(None).unwrap(), 4);
+
+        let mut multi = reader().with_threads(4).with_limit(1);
+        assert_eq!(
+            batches(multi.records_to_ipc(None).unwrap())[0].num_rows(),
+            1
+        );
+        // A limited scan leaves the reader after the last record returned.
+        assert_eq!(multi.count(None).unwrap(), 3);
+        assert_eq!(multi.count(Some("sq1")).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_scan_with() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let reader = || VcfReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let read = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let ipc = reader()
+            .scan_with(None, |record| record.chromosome().to_string() == "sq0")
+            .unwrap();
+        assert_eq!(read(ipc).num_rows(), 3);
+
+        let ipc = reader()
+            .scan_map(Some("sq0"), |mut record| {
+                *record.quality_score_mut() = None;
+                Some(record)
+            })
+            .unwrap();
+        let record_batch = read(ipc);
+        let qual = record_batch.column_by_name("qual").unwrap();
+        assert_eq!(qual.null_count(), 3);
+    }
+
+    #[test]
+    fn test_read_sample() {
+        let record_batch = read_sample(None, VcfOptions::default());
+        assert_eq!(record_batch.num_rows(), 4);
+        let record_batch = read_sample(Some("sq0:15-40"), VcfOptions::default());
+        assert_eq!(record_batch.num_rows(), 2);
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let reports = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = reports.clone();
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_progress(3, move |records, _, total| {
+                sink.borrow_mut().push((records, total))
+            });
+        assert_eq!(reader.count(None).unwrap(), 4);
+        let size = std::fs::metadata(&dir).unwrap().len();
+        assert_eq!(*reports.borrow(), [(3, Some(size)), (4, Some(size))]);
+
+        // Region queries report their records without the size of the file.
+        reports.borrow_mut().clear();
+        reader.regions_to_ipc(&["sq0", "sq1"]).unwrap();
+        assert_eq!(*reports.borrow(), [(3, None), (4, None)]);
+        reports.borrow_mut().clear();
+        reader
+            .regions_to_ipc_parallel(&["sq0", "sq1"], Some(2))
+            .unwrap();
+        assert_eq!(reports.borrow().last(), Some(&(4, None)));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = cancel.clone();
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_cancel(cancel.clone())
+            .with_progress(2, move |_, _, _| {
+                flag.store(true, std::sync::atomic::Ordering::Relaxed)
+            });
+        let rows = |ipc: Vec<u8>| -> usize {
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.map(|batch| batch.unwrap().num_rows()).sum()
+        };
+        assert_eq!(rows(reader.regions_to_ipc(&["sq0", "sq1"]).unwrap()), 2);
+        // Once cancelled, queries return nothing, on every thread.
+        assert_eq!(rows(reader.records_to_ipc(Some("sq0")).unwrap()), 0);
+        let parallel = reader.regions_to_ipc_parallel(&["sq0", "sq1"], Some(2));
+        assert_eq!(rows(parallel.unwrap()), 0);
+        assert_eq!(reader.count(Some("sq1")).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_new_from_stream() {
+        // A pipe, which can only be read.
+        struct Pipe(std::io::Cursor<Vec<u8>>);
+        impl Read for Pipe {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let compressed = std::fs::read(&dir).unwrap();
+        let mut plain = Vec::new();
+        flate2::read::MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut plain)
+            .unwrap();
+        let expected = read_sample(None, VcfOptions::default());
+        for data in [compressed, plain] {
+            let pipe = BufReader::new(Pipe(std::io::Cursor::new(data)));
+            let mut reader = VcfReader::new_from_stream(pipe).unwrap().with_threads(4);
+            assert_eq!(reader.reference_names(), ["sq0", "sq1"]);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            // Only the provenance metadata differs, without a path.
+            assert_eq!(
+                arrow_reader.next().unwrap().unwrap().columns(),
+                expected.columns()
+            );
+
+            let error = reader.records_to_ipc(Some("sq0")).unwrap_err();
+            assert!(error.to_string().contains("requires seekable input"));
+        }
+    }
+
+    #[test]
+    fn test_count() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        for region in [None, Some("sq0:15-40")] {
+            let expected = read_sample(region, VcfOptions::default()).num_rows();
+            assert_eq!(reader.count(region).unwrap(), expected);
+        }
+        assert_eq!(
+            reader.count_regions(&["sq0:15-40", "sq0:15-40"]).unwrap(),
+            vec![2, 2]
+        );
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let num_rows = |ipc: Vec<u8>| {
+            let arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader
+                .map(|batch| batch.unwrap().num_rows())
+                .sum::<usize>()
+        };
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_limit(4);
+        let regions = ["sq1", "sq0:15-40", "sq0"];
+        assert_eq!(num_rows(reader.regions_to_ipc(&regions).unwrap()), 4);
+        let ipc = reader.regions_to_ipc_parallel(&regions, Some(3)).unwrap();
+        assert_eq!(num_rows(ipc), 4);
+        assert_eq!(num_rows(reader.records_to_ipc(Some("sq0")).unwrap()), 3);
+        let mut reader = reader.with_limit(0);
+        assert_eq!(num_rows(reader.records_to_ipc(None).unwrap()), 0);
+    }
+
+    #[test]
+    fn test_regions_parallel() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let options = VcfOptions {
+            genotype_fields: Some(vec!["GT".into()]),
+            ..Default::default()
+        };
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let read = |ipc: Vec<u8>| {
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let regions = ["sq1", "sq0:15-40", "sq0"];
+        let sequential = read(reader.regions_to_ipc(&regions).unwrap());
+        assert_eq!(sequential.num_rows(), 6);
+        let pos = sequential.column_by_name("pos").unwrap();
+        let pos = pos.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(pos.values(), &[5, 20, 30, 10, 20, 30]);
+        let parallel = read(reader.regions_to_ipc_parallel(&regions, Some(3)).unwrap());
+        assert_eq!(parallel, sequential);
+        assert!(reader.regions_to_ipc(&["sq0:x"]).is_err());
+    }
+
+    #[test]
+    fn test_info_fields() {
+        let options = VcfOptions {
+            info_fields: Some(vec!["DP".into(), "AF".into(), "DB".into(), "ANN".into()]),
+            ..Default::default()
+        };
+        let record_batch = read_sample(None, options);
+        let schema = record_batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "chrom", "pos", "end", "id", "ref", "alt", "qual", "filter", "DP", "AF", "DB",
+                "ANN", "format"
+            ]
+        );
+
+        let dp = record_batch.column_by_name("DP").unwrap();
+        let dp = dp.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(
+            dp.iter().collect::<Vec<_>>(),
+            vec![Some(20), Some(15), Some(5), None]
+        );
+
+        let af = record_batch.column_by_name("AF").unwrap();
+        assert!(matches!(af.data_type(), DataType::List(f) if f.data_type() == &DataType::Float32));
+        let af = af.as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(af.value_length(1), 2);
+        assert!(af.is_null(3));
+
+        let db = record_batch.column_by_name("DB").unwrap();
+        let db = db.as_any().downcast_ref::<BooleanArray>().unwrap();
+        assert_eq!(db.null_count(), 0);
+        assert_eq!(
+            db.iter().collect::<Vec<_>>(),
+            vec![Some(true), Some(false), Some(false), Some(false)]
+        );
+
+        let ann = record_batch.column_by_name("ANN").unwrap();
+        let ann = ann.as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(ann.value_length(0), 2);
+    }
+
+    #[test]
+    fn test_gvcf_blocks() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.g.vcf.gz");
+        let read = |options: VcfOptions| {
+            let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_options(options);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            arrow_reader.next().unwrap().unwrap()
+        };
+        let positions = |record_batch: &RecordBatch| {
+            let column = |name| {
+                let array = record_batch.column_by_name(name).unwrap();
+                let array = array.as_any().downcast_ref::<Int32Array>().unwrap();
+                array.values().to_vec()
+            };
+            column("pos")
+                .into_iter()
+                .zip(column("end"))
+                .collect::<Vec<_>>()
+        };
+
+        let record_batch = read(VcfOptions::default());
+        assert_eq!(
+            positions(&record_batch),
+            vec![(1, 4), (5, 5), (6, 10), (11, 11)]
+        );
+        let alt = record_batch.column_by_name("alt").unwrap();
+        let alt = alt.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(alt.value(0), "<*>");
+        assert_eq!(alt.value(1), "T,<*>");
+
+        let options = VcfOptions {
+            expand_gvcf_blocks: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read(options);
+        assert_eq!(
+            positions(&record_batch),
+            (1..=11).map(|pos| (pos, pos)).collect::<Vec<_>>()
+        );
+        let ref_ = record_batch.column_by_name("ref").unwrap();
+        let ref_ = ref_.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(ref_.value(6), "G");
+    }
+
+    #[test]
+    fn test_structural_variants() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.sv.vcf.gz");
+        let options = VcfOptions {
+            include_sv_fields: Some(true),
+            include_breakend_mates: Some(true),
+            ..Default::default()
+        };
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        let strings = |name| {
+            let array = record_batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<StringArray>().unwrap();
+            array
+                .iter()
+                .map(|v| v.map(String::from))
+                .collect::<Vec<_>>()
+        };
+        let ints = |name| {
+            let array = record_batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<Int32Array>().unwrap();
+            array.iter().collect::<Vec<_>>()
+        };
+
+        let alt = strings("alt");
+        let alt: Vec<_> = alt.iter().map(|v| v.as_deref().unwrap()).collect();
+        assert_eq!(alt, ["<DEL>", "<DUP>", "A[chr2:123[", "T", "]chr1:2000]G"]);
+        assert_eq!(
+            strings("svtype"),
+            [Some("DEL"), Some("DUP"), Some("BND"), None, Some("BND")].map(|v| v.map(String::from))
+        );
+        assert_eq!(ints("svlen"), [Some(-400), Some(250), None, None, None]);
+        assert_eq!(
+            ints("end"),
+            [Some(500), Some(1250), Some(2000), Some(3000), Some(123)]
+        );
+        assert_eq!(
+            strings("mate_chrom"),
+            [None, None, Some("chr2"), None, Some("chr1")].map(|v| v.map(String::from))
+        );
+        assert_eq!(ints("mate_pos"), [None, None, Some(123), None, Some(2000)]);
+        assert_eq!(breakend_mate("[<ctg1>:7[T"), Some(("ctg1", 7)));
+        assert_eq!(breakend_mate("G."), None);
+    }
+
+    #[test]
+    fn test_split_multiallelic() {
+        use std::io::Write;
+
+        let vcf = "##fileformat=VCFv4.3
+##contig=<ID=sq0,length=100>
+##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Total depth\">
+##INFO=<ID=AF,Number=A,Type=Float,Description=\"Allele frequency\">
+##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">
+##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Allelic depths\">
+##FORMAT=<ID=PL,Number=G,Type=Integer,Description=\"Phred-scaled genotype likelihoods\">
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts0\ts1
+sq0\t10\t.\tA\tG\t.\t.\tDP=4;AF=0.5\tGT:AD:PL\t0/1:2,2:10,0,10\t0/0:4,0:0,10,100
+sq0\t20\t.\tC\tT,G\t.\t.\tDP=10;AF=0.1,0.3\tGT:AD:PL\t1/2:2,3,5:90,50,60,40,0,30\t0|2:6,0,4:20,30,40,0,50,60
+";
+        let mut writer = bgzf::Writer::new(Vec::new());
+        writer.write_all(vcf.as_bytes()).unwrap();
+        let data = writer.finish().unwrap();
+        let options = VcfOptions {
+            info_fields: Some(vec!["DP".into(), "AF".into()]),
+            genotype_fields: Some(vec!["GT".into(), "AD".into(), "PL".into()]),
+            split_multiallelic: Some(true),
+            ..Default::default()
+        };
+        let mut reader = VcfReader::new(std::io::Cursor::new(data), csi::Index::default())
+            .unwrap()
+            .with_options(options);
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let record_batch = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(record_batch.num_rows(), 3);
+
+        let strings = |name| {
+            let array = record_batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<StringArray>().unwrap();
+            array
+                .iter()
+                .map(|v| v.unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let lists = |name| {
+            let array = record_batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<ListArray>().unwrap();
+            array
+                .iter()
+                .map(|v| {
+                    let v = v.unwrap();
+                    let v = v.as_any().downcast_ref::<Int32Array>().unwrap();
+                    v.values().to_vec()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(strings("alt"), ["G", "T", "G"]);
+        assert_eq!(strings("s0.GT"), ["0/1", "1/0", "0/1"]);
+        assert_eq!(strings("s1.GT"), ["0/0", "0|0", "0|1"]);
+        assert_eq!(lists("s0.AD"), [[2, 2], [2, 3], [2, 5]]);
+        assert_eq!(lists("s1.AD"), [[4, 0], [6, 0], [6, 4]]);
+        assert_eq!(lists("s0.PL"), [[10, 0, 10], [90, 50, 60], [90, 40, 30]]);
+
+        let af = record_batch.column_by_name("AF").unwrap();
+        let af = af.as_any().downcast_ref::<ListArray>().unwrap();
+        let af: Vec<_> = af
+            .iter()
+            .map(|v| {
+                let v = v.unwrap();
+                v.as_any()
+                    .downcast_ref::<arrow::array::Float32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(af, [vec![0.5], vec![0.1], vec![0.3]]);
+        let dp = record_batch.column_by_name("DP").unwrap();
+        let dp = dp.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(dp.values(), &[4, 10, 10]);
+        assert_eq!(recode_genotype("./12|3", 12), "./1|0");
+    }
+
+    #[test]
+    fn test_qual() {
+        let data = "##fileformat=VCFv4.3\n\
+            ##contig=<ID=sq0,length=100>\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+            sq0\t1\t.\tA\tG\t50\tPASS\t.\n\
+            sq0\t2\t.\tA\tG\t.\tPASS\t.\n\
+            sq0\t3\t.\tA\tG\t0\tPASS\t.\n\
+            sq0\t4\t.\tA\tG\t3.4e38\tPASS\t.\n\
+            sq0\t5\t.\tA\tG\tinf\tPASS\t.\n";
+        let mut reader = VcfReader::new_from_stream(data.as_bytes()).unwrap();
+        let ipc = reader.records_to_ipc(None).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let batch = arrow_reader.next().unwrap().unwrap();
+        let field = batch.schema().field_with_name("qual").unwrap().clone();
+        assert_eq!(field.data_type(), &DataType::Float32);
+        assert!(field.is_nullable());
+        let qual = batch.column_by_name("qual").unwrap();
+        let qual = qual
+            .as_any()
+            .downcast_ref::<arrow::array::Float32Array>()
+            .unwrap();
+        assert_eq!(
+            qual.iter().collect::<Vec<_>>(),
+            [
+                Some(50.0),
+                None,
+                Some(0.0),
+                Some(3.4e38),
+                Some(f32::INFINITY)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ids_and_filters() {
+        let batch = read_sample(None, VcfOptions::default());
+        let column = |batch: &RecordBatch, name| {
+            let array = batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<StringArray>().unwrap();
+            array
+                .iter()
+                .map(|s| s.map(String::from))
+                .collect::<Vec<_>>()
+        };
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(
+            column(&batch, "id"),
+            [some("rs1"), None, some("rs3;rs4"), None]
+        );
+        assert_eq!(
+            column(&batch, "filter"),
+            [some("PASS"), some("q10"), None, some("PASS")]
+        );
+
+        let options = VcfOptions {
+            split_ids: Some(true),
+            split_filters: Some(true),
+            ..Default::default()
+        };
+        let batch = read_sample(None, options);
+        let lists = |name| {
+            let array = batch.column_by_name(name).unwrap();
+            let array = array.as_any().downcast_ref::<ListArray>().unwrap();
+            array
+                .iter()
+                .map(|list| {
+                    let list = list?;
+                    let list = list.as_any().downcast_ref::<StringArray>().unwrap();
+                    Some(list.iter().map(|s| s.unwrap().to_string()).collect())
+                })
+                .collect::<Vec<Option<Vec<String>>>>()
+        };
+        let list = |values: &[&str]| Some(values.iter().map(|s| s.to_string()).collect());
+        assert_eq!(
+            lists("id"),
+            [list(&["rs1"]), None, list(&["rs3", "rs4"]), None]
+        );
+        assert_eq!(
+            lists("filter"),
+            [list(&["PASS"]), list(&["q10"]), None, list(&["PASS"])]
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        use crate::bcf::BcfReader;
+
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let typed = VcfOptions {
+            info_fields: Some(
+                ["DP", "AF", "DB", "ANN", "SVTYPE", "END"]
+                    .map(String::from)
+                    .into(),
+            ),
+            genotype_fields: Some(["GT", "DP", "AD"].map(String::from).into()),
+            split_ids: Some(true),
+            split_filters: Some(true),
+            ..Default::default()
+        };
+        for options in [VcfOptions::default(), typed] {
+            let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+                .unwrap()
+                .with_options(options.clone());
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let expected = FileReader::try_new(std::io::Cursor::new(ipc.clone()), None)
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            // The written FORMAT has every materialized key.
+            let expected = expected
+                .project(
+                    &(0..expected.num_columns())
+                        .filter(|&i| expected.schema().field(i).name() != "format")
+                        .collect::<Vec<_>>(),
+                )
+                .unwrap();
+
+            let header = header_from_str(&header_to_string(reader.header()).unwrap()).unwrap();
+            let batches = FileReader::try_new(std::io::Cursor::new(ipc.clone()), None).unwrap();
+            let mut vcf = Vec::new();
+            write_vcf(&mut vcf, &header, batches).unwrap();
+            let batches = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let mut bcf = Vec::new();
+            write_bcf(&mut bcf, &header, batches).unwrap();
+
+            let ipcs = [
+                VcfReader::new(std::io::Cursor::new(vcf), csi::Index::default())
+                    .unwrap()
+                    .with_options(options.clone())
+                    .records_to_ipc(None)
+                    .unwrap(),
+                BcfReader::new(std::io::Cursor::new(bcf), csi::Index::default())
+                    .unwrap()
+                    .with_options(options)
+                    .records_to_ipc(None)
+                    .unwrap(),
+            ];
+            for ipc in ipcs {
+                let mut arrow_reader =
+                    FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+                let record_batch = arrow_reader.next().unwrap().unwrap();
+                for field in expected.schema().fields() {
+                    assert_eq!(
+                        record_batch.column_by_name(field.name()).unwrap(),
+                        expected.column_by_name(field.name()).unwrap(),
+                        "{}",
+                        field.name()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_genotype_fields() {
+        let options = VcfOptions {
+            genotype_fields: Some(vec!["GT".into(), "DP".into(), "AD".into()]),
+            ..Default::default()
+        };
+        let record_batch = read_sample(None, options);
+        let schema = record_batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(
+            names[9..],
+            ["format", "s0.GT", "s0.DP", "s0.AD", "s1.GT", "s1.DP", "s1.AD"]
+        );
+
+        let gt = record_batch.column_by_name("s0.GT").unwrap();
+        let gt = gt.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            gt.iter().collect::<Vec<_>>(),
+            vec![Some("0/1"), Some("1/2"), Some("./."), Some("0/1")]
+        );
+
+        let dp = record_batch.column_by_name("s1.DP").unwrap();
+        let dp = dp.as_any().downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(
+            dp.iter().collect::<Vec<_>>(),
+            vec![Some(10), Some(7), Some(3), None]
+        );
+
+        // AD has one value per allele, so the multi-allelic site has three.
+        let ad = record_batch.column_by_name("s0.AD").unwrap();
+        assert!(matches!(ad.data_type(), DataType::List(f) if f.data_type() == &DataType::Int32));
+        let ad = ad.as_any().downcast_ref::<ListArray>().unwrap();
+        assert_eq!(ad.value_length(0), 2);
+        assert_eq!(ad.value_length(1), 3);
+        assert!(ad.is_null(2));
+        assert!(ad.is_null(3));
+    }
+
+    #[test]
+    fn test_genotype_fields_samples() {
+        let options = VcfOptions {
+            genotype_fields: Some(vec!["GT".into()]),
+            samples: Some(vec!["s1".into()]),
+            ..Default::default()
+        };
+        let record_batch = read_sample(None, options);
+        assert!(record_batch.column_by_name("s0.GT").is_none());
+        let gt = record_batch.column_by_name("s1.GT").unwrap();
+        let gt = gt.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            gt.iter().collect::<Vec<_>>(),
+            vec![Some("1|1"), Some("0/0"), Some("0|1"), Some("1/1")]
+        );
+    }
+
+    #[test]
+    fn test_unknown_genotype_field_or_sample() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let path = dir.to_str().unwrap();
+        let options = VcfOptions {
+            genotype_fields: Some(vec!["XX".into()]),
+            ..Default::default()
+        };
+        let mut reader = VcfReader::new_from_path(path)
+            .unwrap()
+            .with_options(options);
+        assert!(reader.records_to_ipc(None).is_err());
+
+        let options = VcfOptions {
+            genotype_fields: Some(vec!["GT".into()]),
+            samples: Some(vec!["s2".into()]),
+            ..Default::default()
+        };
+        let mut reader = VcfReader::new_from_path(path)
+            .unwrap()
+            .with_options(options);
+        assert!(reader.records_to_ipc(None).is_err());
+    }
+
+    #[test]
+    fn test_unknown_info_field() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let options = VcfOptions {
+            info_fields: Some(vec!["XX".into()]),
+            ..Default::default()
+        };
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        assert!(reader.records_to_ipc(None).is_err());
+    }
 
     // fn read_record_batch(region: Option<&str>) -> RecordBatch {
     //     let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -287,4 +2832,323 @@ mod tests {
     //     let record_batch = read_record_batch(Some("Y:8028497-17629059"));
     //     assert_eq!(record_batch.num_rows(), 27947);
     // }
+
+    /// Returns the names and types of a list of fields, with nested fields made nullable.
+    fn name_types(fields: &arrow::datatypes::Fields) -> Vec<(String, DataType)> {
+        fields
+            .iter()
+            .map(|field| nullable_field(field))
+            .map(|field| (field.name().clone(), field.data_type().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_fields_match_records() {
+        let options = VcfOptions {
+            info_fields: Some(vec!["DP".into(), "AF".into()]),
+            genotype_fields: Some(vec!["GT".into()]),
+            ..Default::default()
+        };
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options.clone());
+        let fields = reader.fields().unwrap();
+        assert!(fields.iter().all(|field| field.is_nullable()));
+        let record_batch = read_sample(None, options);
+        assert_eq!(
+            name_types(&fields),
+            name_types(record_batch.schema().fields())
+        );
+    }
+
+    #[test]
+    fn test_peek_schema() {
+        let options = VcfOptions {
+            info_fields: Some(vec!["DP".into(), "AF".into(), "ANN".into(), "DB".into()]),
+            genotype_fields: Some(vec!["GT".into(), "AD".into()]),
+            emit_offset: Some(true),
+            ..Default::default()
+        };
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let schema = peek_schema(dir.to_str().unwrap(), &options).unwrap();
+        assert!(schema.metadata().is_empty());
+        let record_batch = read_sample(None, options);
+        assert_eq!(
+            name_types(schema.fields()),
+            name_types(record_batch.schema().fields())
+        );
+    }
+
+    #[test]
+    fn test_reference_lengths() {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let reader = VcfReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        assert_eq!(
+            reader.reference_lengths(),
+            [("sq0".to_string(), 120), ("sq1".to_string(), 90)]
+        );
+    }
+
+    #[test]
+    fn test_header_text() {
+        use std::io::BufRead;
+
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let reader = VcfReader::new_from_path(dir.to_str().unwrap()).unwrap();
+        let file = BufReader::new(File::open(&dir).unwrap());
+        let mut expected = String::new();
+        for line in crate::compression::decompress(file).unwrap().lines() {
+            let line = line.unwrap();
+            if !line.starts_with('#') {
+                break;
+            }
+            expected.push_str(&line);
+            expected.push('\n');
+        }
+        // The lines are the same, but grouped by kind.
+        let text = reader.header_text().unwrap();
+        let mut lines: Vec<_> = text.lines().collect();
+        let mut expected: Vec<_> = expected.lines().collect();
+        assert_eq!(lines.first(), expected.first());
+        assert_eq!(lines.last(), expected.last());
+        lines.sort();
+        expected.sort();
+        assert_eq!(lines, expected);
+        assert_eq!(&header_from_str(&text).unwrap(), reader.header());
+        assert_eq!(reader.header().sample_names().len(), 2);
+    }
+
+    #[test]
+    fn test_require_sorted() {
+        let count = |records: &str, require_sorted| {
+            let mut writer = bgzf::Writer::new(Vec::new());
+            write!(
+                writer,
+                "##fileformat=VCFv4.3\n##contig=<ID=sq0>\n##contig=<ID=sq1>\n\
+                 #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n{records}"
+            )
+            .unwrap();
+            let data = writer.finish().unwrap();
+            let options = VcfOptions {
+                require_sorted: Some(require_sorted),
+                ..Default::default()
+            };
+            VcfReader::new(std::io::Cursor::new(data), csi::Index::default())
+                .unwrap()
+                .with_options(options)
+                .count(None)
+        };
+        let sorted = "sq0\t10\t.\tA\tC\t.\t.\t.\nsq0\t20\t.\tA\tC\t.\t.\t.\n\
+                      sq1\t5\t.\tA\tC\t.\t.\t.\n";
+        assert_eq!(count(sorted, true).unwrap(), 3);
+
+        // Contigs must come in header order.
+        let unsorted = "sq1\t5\t.\tA\tC\t.\t.\t.\nsq0\t10\t.\tA\tC\t.\t.\t.\n";
+        assert_eq!(count(unsorted, false).unwrap(), 2);
+        let error = count(unsorted, true).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("record 2 (sq0:10) comes after sq1:5"));
+        let unsorted = "sq0\t20\t.\tA\tC\t.\t.\t.\nsq0\t10\t.\tA\tC\t.\t.\t.\n";
+        assert!(count(unsorted, true).is_err());
+    }
+
+    #[test]
+    fn test_chrom_alias() {
+        let dir = std::env::temp_dir().join(format!("oxbow-vcf-alias-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let count = |chrom: &str, region: &str, alias: Option<ChromAlias>| {
+            let path = dir.join(format!("{}.vcf.gz", chrom));
+            let mut writer = bgzf::Writer::new(File::create(&path).unwrap());
+            write!(
+                writer,
+                "##fileformat=VCFv4.3\n##contig=<ID={chrom}>\n\
+                 #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\
+                 {chrom}\t10\t.\tA\tC\t.\t.\t.\n{chrom}\t20\t.\tA\tC\t.\t.\t.\n"
+            )
+            .unwrap();
+            writer.finish().unwrap();
+            let path = path.to_str().unwrap();
+            let index = crate::index::build_index(path, None).unwrap();
+            let options = VcfOptions {
+                chrom_alias: alias,
+                ..Default::default()
+            };
+            let mut reader = VcfReader::new_from_path_with_index(path, index)
+                .unwrap()
+                .with_options(options);
+            let batch = reader.regions_to_ipc(&[region]);
+            (reader.count(Some(region)), batch.is_ok())
+        };
+        let ucsc_ensembl = Some(ChromAlias::UcscEnsembl);
+        assert!(count("1", "chr1", None).0.is_err());
+        assert_eq!(count("1", "chr1:1-15", ucsc_ensembl.clone()).0.unwrap(), 1);
+        assert_eq!(count("chr1", "1", ucsc_ensembl.clone()).0.unwrap(), 2);
+        assert!(count("chr1", "1", ucsc_ensembl).1);
+        let map = ChromAlias::Map(HashMap::from([("one".to_string(), "1".to_string())]));
+        assert_eq!(count("1", "one", Some(map)).0.unwrap(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_emit_offset() {
+        let options = VcfOptions {
+            emit_offset: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read_sample(None, options.clone());
+        assert_eq!(
+            record_batch.schema().fields().last().unwrap().name(),
+            "vpos"
+        );
+        let vpos = record_batch
+            .column_by_name("vpos")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::UInt64Array>()
+            .unwrap();
+        let pos = record_batch.column_by_name("pos").unwrap();
+
+        // Each offset reads back the record it was emitted for.
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options.clone());
+        for (i, vpos) in vpos.values().iter().enumerate() {
+            let lo = bgzf::VirtualPosition::from(*vpos).into();
+            let hi = bgzf::VirtualPosition::from(vpos + 1).into();
+            let ipc = reader.records_to_ipc_from_vpos(lo, hi).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let batch = arrow_reader.next().unwrap().unwrap();
+            assert_eq!(batch.num_rows(), 1);
+            assert_eq!(batch.column_by_name("pos").unwrap(), &pos.slice(i, 1));
+        }
+
+        assert!(reader.records_to_ipc(Some("sq0")).is_err());
+        let options = VcfOptions {
+            split_multiallelic: Some(true),
+            ..options
+        };
+        let mut reader = reader.with_options(options);
+        assert!(reader.records_to_ipc(None).is_err());
+    }
+
+    #[test]
+    fn test_scan_from() {
+        let options = VcfOptions {
+            emit_offset: Some(true),
+            ..Default::default()
+        };
+        let record_batch = read_sample(None, options.clone());
+        let vpos = record_batch
+            .column_by_name("vpos")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::UInt64Array>()
+            .unwrap()
+            .value(1);
+
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures/sample.vcf.gz");
+        let mut reader = VcfReader::new_from_path(dir.to_str().unwrap())
+            .unwrap()
+            .with_options(options);
+        let ipc = reader.scan_from(vpos).unwrap();
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        let tail = arrow_reader.next().unwrap().unwrap();
+        assert_eq!(tail, record_batch.slice(1, record_batch.num_rows() - 1));
+
+        assert!(reader.scan_from(0).is_err());
+        assert!(reader.scan_from(vpos + 1).is_err());
+    }
+
+    #[test]
+    fn test_unsigned_positions() {
+        let read = |contig_length: u64, pos: u64, unsigned| {
+            let vcf = format!(
+                "##fileformat=VCFv4.3
+##contig=<ID=sq0,length={}>
+##INFO=<ID=END,Number=1,Type=Integer,Description=\"End position\">
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO
+sq0\t{}\t.\tA\tG\t.\t.\t.
+",
+                contig_length, pos
+            );
+            let mut writer = bgzf::Writer::new(Vec::new());
+            writer.write_all(vcf.as_bytes()).unwrap();
+            let data = writer.finish().unwrap();
+            let options = VcfOptions {
+                unsigned_positions: Some(unsigned),
+                ..Default::default()
+            };
+            let mut reader = VcfReader::new(std::io::Cursor::new(data), csi::Index::default())
+                .unwrap()
+                .with_options(options);
+            let ipc = reader.records_to_ipc(None).unwrap();
+            let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let batch = arrow_reader.next().unwrap().unwrap();
+            let (pos, end) = (
+                batch.column_by_name("pos").unwrap(),
+                batch.column_by_name("end").unwrap(),
+            );
+            assert_eq!(pos.data_type(), end.data_type());
+            let value = |array: &ArrayRef| {
+                let array = arrow::compute::cast(array, &DataType::Int64).unwrap();
+                array.as_primitive::<arrow::datatypes::Int64Type>().value(0)
+            };
+            (pos.data_type().clone(), value(pos), value(end))
+        };
+        assert_eq!(read(100, 10, false), (DataType::Int32, 10, 10));
+        assert_eq!(read(100, 10, true), (DataType::UInt32, 10, 10));
+        // Positions past 2^31 on a 3 Gb contig don't overflow.
+        let pos = 2_500_000_000;
+        assert_eq!(
+            read(3_000_000_000, pos, false),
+            (DataType::Int64, pos as i64, pos as i64)
+        );
+        assert_eq!(
+            read(3_000_000_000, pos, true),
+            (DataType::UInt64, pos as i64, pos as i64)
+        );
+    }
+
+    #[test]
+    fn test_positions_beyond_header() {
+        // Without a contig length, the columns stay 32 bits, so larger positions fail rather than
+        // wrap.
+        let read = |pos: u64, unsigned| {
+            let vcf = format!(
+                "##fileformat=VCFv4.3
+##contig=<ID=sq0>
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO
+sq0\t{}\t.\tA\tG\t.\t.\t.
+",
+                pos
+            );
+            let mut writer = bgzf::Writer::new(Vec::new());
+            writer.write_all(vcf.as_bytes()).unwrap();
+            let data = writer.finish().unwrap();
+            let options = VcfOptions {
+                unsigned_positions: Some(unsigned),
+                ..Default::default()
+            };
+            VcfReader::new(std::io::Cursor::new(data), csi::Index::default())
+                .unwrap()
+                .with_options(options)
+                .records_to_ipc(None)
+        };
+        let error = read(2_500_000_000, false).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid position: 2500000000. Expected a position that fits in the Int32"));
+        assert!(read(2_500_000_000, true).is_ok());
+        let error = read(5_000_000_000, true).unwrap_err();
+        assert!(error.to_string().contains("UInt32"));
+    }
 }
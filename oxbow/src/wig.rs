@@ -0,0 +1,380 @@
+//! Readers for the bedGraph and wiggle coverage track formats.
+//!
+//! Both produce the same columns as the BigWig reader: `chrom`, `start`, `end` and `value`, with
+//! 0-based, half-open coordinates. Track, browser and comment lines are skipped.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float32Builder, StringDictionaryBuilder, UInt32Builder};
+use arrow::{
+    datatypes::{Fields, Int32Type},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+
+use crate::batch_builder::{
+    builder_fields, provenance, write_ipc_err, BatchBuilder, BUFFER_SIZE_BYTES,
+};
+
+/// A coverage interval.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WigRecord {
+    pub chrom: String,
+    pub start: u32,
+    pub end: u32,
+    pub value: f32,
+}
+
+fn invalid_data(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid line: {}", line),
+    )
+}
+
+fn parse<T: std::str::FromStr>(s: &str, line: &str) -> io::Result<T> {
+    s.parse().map_err(|_| invalid_data(line))
+}
+
+/// Returns whether a line carries no data.
+fn is_skipped(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+}
+
+/// A bedGraph reader.
+pub struct BedGraphReader<R> {
+    reader: R,
+    path: Option<PathBuf>,
+}
+
+impl BedGraphReader<BufReader<File>> {
+    /// Creates a bedGraph reader from a given file path.
+    pub fn new_from_path(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut reader = Self::new(BufReader::with_capacity(BUFFER_SIZE_BYTES, file))?;
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> BedGraphReader<R> {
+    /// Creates a bedGraph reader.
+    pub fn new(reader: R) -> io::Result<Self> {
+        Ok(Self { reader, path: None })
+    }
+
+    /// Returns the fields of the batches returned by [`BedGraphReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(WigBatchBuilder::new(0))
+    }
+
+    /// Returns all records as Apache Arrow IPC.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::wig::BedGraphReader;
+    ///
+    /// let mut reader = BedGraphReader::new_from_path("sample.bedGraph").unwrap();
+    /// let ipc = reader.records_to_ipc().unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder = WigBatchBuilder::new(1024).with_metadata(provenance(
+            "bedgraph",
+            self.path.as_deref(),
+            &[],
+        ));
+        let records = (&mut self.reader)
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !is_skipped(line)))
+            .map(|line| line.and_then(|line| parse_bedgraph_line(&line)))
+            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+        write_ipc_err(records, batch_builder)
+    }
+}
+
+fn parse_bedgraph_line(line: &str) -> io::Result<WigRecord> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [chrom, start, end, value] = fields[..] else {
+        return Err(invalid_data(line));
+    };
+    Ok(WigRecord {
+        chrom: chrom.to_string(),
+        start: parse(start, line)?,
+        end: parse(end, line)?,
+        value: parse(value, line)?,
+    })
+}
+
+/// A wiggle reader for `fixedStep` and `variableStep` data.
+pub struct WigReader<R> {
+    reader: R,
+    path: Option<PathBuf>,
+}
+
+impl WigReader<BufReader<File>> {
+    /// Creates a wiggle reader from a given file path.
+    pub fn new_from_path(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut reader = Self::new(BufReader::with_capacity(BUFFER_SIZE_BYTES, file))?;
+        reader.path = Some(PathBuf::from(path));
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> WigReader<R> {
+    /// Creates a wiggle reader.
+    pub fn new(reader: R) -> io::Result<Self> {
+        Ok(Self { reader, path: None })
+    }
+
+    /// Returns the fields of the batches returned by [`WigReader::records_to_ipc`] with the
+    /// current options, without reading any records.
+    pub fn fields(&self) -> Result<Fields, ArrowError> {
+        builder_fields(WigBatchBuilder::new(0))
+    }
+
+    /// Returns all records as Apache Arrow IPC.
+    ///
+    /// The positions implied by each `fixedStep` or `variableStep` declaration are expanded into
+    /// explicit intervals of the declared `span` (1 by default).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use oxbow::wig::WigReader;
+    ///
+    /// let mut reader = WigReader::new_from_path("sample.wig").unwrap();
+    /// let ipc = reader.records_to_ipc().unwrap();
+    /// ```
+    pub fn records_to_ipc(&mut self) -> Result<Vec<u8>, ArrowError> {
+        let batch_builder =
+            WigBatchBuilder::new(1024).with_metadata(provenance("wig", self.path.as_deref(), &[]));
+        let mut step = None;
+        let records = (&mut self.reader)
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !is_skipped(line)))
+            .filter_map(move |line| parse_wig_line(line, &mut step).transpose())
+            .map(|i| i.map_err(|e| ArrowError::ExternalError(e.into())));
+        write_ipc_err(records, batch_builder)
+    }
+}
+
+/// The current `fixedStep` or `variableStep` declaration.
+enum Step {
+    /// `start` is the 1-based position of the next value.
+    Fixed {
+        chrom: String,
+        start: u32,
+        step: u32,
+        span: u32,
+    },
+    Variable {
+        chrom: String,
+        span: u32,
+    },
+}
+
+impl Step {
+    fn parse(line: &str) -> io::Result<Self> {
+        let mut fields = line.split_whitespace();
+        let kind = fields.next().ok_or_else(|| invalid_data(line))?;
+        let (mut chrom, mut start, mut step, mut span) = (None, None, None, 1);
+        for field in fields {
+            let (key, value) = field.split_once('=').ok_or_else(|| invalid_data(line))?;
+            match key {
+                "chrom" => chrom = Some(value.to_string()),
+                "start" => start = Some(parse(value, line)?),
+                "step" => step = Some(parse(value, line)?),
+                "span" => span = parse(value, line)?,
+                _ => return Err(invalid_data(line)),
+            }
+        }
+        let chrom = chrom.ok_or_else(|| invalid_data(line))?;
+        match (kind, start, step) {
+            // Positions are 1-based.
+            ("fixedStep", Some(0), _) => Err(invalid_data(line)),
+            ("fixedStep", Some(start), Some(step)) => Ok(Self::Fixed {
+                chrom,
+                start,
+                step,
+                span,
+            }),
+            ("variableStep", None, None) => Ok(Self::Variable { chrom, span }),
+            _ => Err(invalid_data(line)),
+        }
+    }
+}
+
+/// Parses a wiggle line, updating the current declaration.
+///
+/// Returns `None` for declaration lines.
+fn parse_wig_line(
+    line: io::Result<String>,
+    step: &mut Option<Step>,
+) -> io::Result<Option<WigRecord>> {
+    let line = line?;
+    if line.starts_with("fixedStep") || line.starts_with("variableStep") {
+        *step = Some(Step::parse(&line)?);
+        return Ok(None);
+    }
+    let record = match step.as_mut() {
+        Some(Step::Fixed {
+            chrom,
+            start,
+            step,
+            span,
+        }) => {
+            let value = parse(line.trim(), &line)?;
+            let record = WigRecord {
+                chrom: chrom.clone(),
+                start: *start - 1,
+                end: *start - 1 + *span,
+                value,
+            };
+            *start += *step;
+            record
+        }
+        Some(Step::Variable { chrom, span }) => {
+            let (position, value) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid_data(&line))?;
+            let start = parse::<u32>(position, &line)?
+                .checked_sub(1)
+                .ok_or_else(|| invalid_data(&line))?;
+            WigRecord {
+                chrom: chrom.clone(),
+                start,
+                end: start + *span,
+                value: parse(value.trim(), &line)?,
+            }
+        }
+        // Data must follow a declaration.
+        None => return Err(invalid_data(&line)),
+    };
+    Ok(Some(record))
+}
+
+struct WigBatchBuilder {
+    chrom: StringDictionaryBuilder<Int32Type>,
+    start: UInt32Builder,
+    end: UInt32Builder,
+    value: Float32Builder,
+}
+
+impl WigBatchBuilder {
+    fn new(capacity: usize) -> Self {
+        Self {
+            chrom: StringDictionaryBuilder::<Int32Type>::new(),
+            start: UInt32Builder::with_capacity(capacity),
+            end: UInt32Builder::with_capacity(capacity),
+            value: Float32Builder::with_capacity(capacity),
+        }
+    }
+}
+
+impl BatchBuilder for WigBatchBuilder {
+    type Record<'a> = &'a WigRecord;
+
+    fn push(&mut self, record: Self::Record<'_>) {
+        self.chrom.append_value(&record.chrom);
+        self.start.append_value(record.start);
+        self.end.append_value(record.end);
+        self.value.append_value(record.value);
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, ArrowError> {
+        RecordBatch::try_from_iter(vec![
+            ("chrom", Arc::new(self.chrom.finish()) as ArrayRef),
+            ("start", Arc::new(self.start.finish()) as ArrayRef),
+            ("end", Arc::new(self.end.finish()) as ArrayRef),
+            ("value", Arc::new(self.value.finish()) as ArrayRef),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Float32Array, UInt32Array};
+    use arrow::ipc::reader::FileReader;
+
+    fn read_ipc(ipc: Vec<u8>) -> RecordBatch {
+        let mut arrow_reader = FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+        arrow_reader.next().unwrap().unwrap()
+    }
+
+    fn column<T: 'static + Clone>(record_batch: &RecordBatch, name: &str) -> T {
+        record_batch
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<T>()
+            .unwrap()
+            .clone()
+    }
+
+    fn fixture_path(name: &str) -> String {
+        let mut dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("../fixtures");
+        dir.push(name);
+        dir.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_read_bedgraph() {
+        let mut reader = BedGraphReader::new_from_path(&fixture_path("sample.bedGraph")).unwrap();
+        let record_batch = read_ipc(reader.records_to_ipc().unwrap());
+        assert_eq!(record_batch.num_rows(), 3);
+        let start: UInt32Array = column(&record_batch, "start");
+        let end: UInt32Array = column(&record_batch, "end");
+        let value: Float32Array = column(&record_batch, "value");
+        assert_eq!(start.values(), &[0, 10, 5]);
+        assert_eq!(end.values(), &[10, 25, 8]);
+        assert_eq!(value.values(), &[1.5, 2.0, -0.25]);
+    }
+
+    #[test]
+    fn test_read_wig() {
+        let mut reader = WigReader::new_from_path(&fixture_path("sample.wig")).unwrap();
+        let record_batch = read_ipc(reader.records_to_ipc().unwrap());
+        assert_eq!(record_batch.num_rows(), 6);
+        let start: UInt32Array = column(&record_batch, "start");
+        let end: UInt32Array = column(&record_batch, "end");
+        let value: Float32Array = column(&record_batch, "value");
+        assert_eq!(start.values(), &[0, 10, 20, 4, 19, 100]);
+        assert_eq!(end.values(), &[5, 15, 25, 7, 22, 101]);
+        assert_eq!(value.values(), &[1.0, 2.0, 3.0, 0.5, 1.5, 4.0]);
+        assert_eq!(record_batch.column_by_name("chrom").unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_provenance() {
+        let mut reader = BedGraphReader::new("sq0\t0\t10\t1.0\n".as_bytes()).unwrap();
+        let record_batch = read_ipc(reader.records_to_ipc().unwrap());
+        let schema = record_batch.schema();
+        assert_eq!(schema.metadata()["oxbow.format"], "bedgraph");
+        assert_eq!(
+            schema.metadata()["oxbow.version"],
+            env!("CARGO_PKG_VERSION")
+        );
+        assert!(!schema.metadata().contains_key("oxbow.source_path"));
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        let mut reader = BedGraphReader::new("sq0\t0\t10\n".as_bytes()).unwrap();
+        assert!(reader.records_to_ipc().is_err());
+        let mut reader = WigReader::new("1.0\n".as_bytes()).unwrap();
+        assert!(reader.records_to_ipc().is_err());
+        let mut reader = WigReader::new("fixedStep chrom=sq0 step=1\n1.0\n".as_bytes()).unwrap();
+        assert!(reader.records_to_ipc().is_err());
+    }
+}
@@ -1,24 +1,51 @@
-use std::collections::HashSet;
-use std::io::BufReader;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Seek};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
+use pyo3::exceptions::{PyUserWarning, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyString;
+use pyo3::types::{PyBytes, PyDict, PyString};
 
 use oxbow::bam;
-use oxbow::bam::BamReader;
+use oxbow::bam::{BamMultiReader, BamOptions, BamReader};
 use oxbow::bcf;
+use oxbow::bcf::BcfReader;
+use oxbow::bed::{BedOptions, BedReader};
 use oxbow::bigbed::BigBedReader;
 use oxbow::bigwig::BigWigReader;
-use oxbow::fasta::FastaReader;
-use oxbow::fastq::FastqReader;
+use oxbow::compression::decompress;
+use oxbow::coordinates::CoordinateSystem;
+use oxbow::coverage::WindowAssignment;
+use oxbow::cram;
+use oxbow::cram::CramReader;
+use oxbow::error_policy::{ErrorPolicy, RecordError};
+use oxbow::fasta;
+use oxbow::fasta::{FastaOptions, FastaReader};
+use oxbow::fastq::{FastqOptions, FastqReader};
+use oxbow::flatten::EmptyLists;
+use oxbow::gff::{GffOptions, GffReader};
+use oxbow::gtf::{GtfOptions, GtfReader};
+use oxbow::gxf::{AttributeDef, DuplicateAttributes};
+use oxbow::index::{write_csi, write_tabix, Preset};
+use oxbow::maf::{MafOptions, MafReader};
+use oxbow::naming::{rename_columns as rename_ipc_columns, ColumnNaming};
+use oxbow::paf::{PafOptions, PafReader};
+use oxbow::peak::{PeakFormat, PeakOptions, PeakReader};
+use oxbow::progress::Progress;
+use oxbow::region::{ChromAlias, QueryMode};
+use oxbow::sam::SamReader;
+use oxbow::sequence::SequenceEncoding;
+use oxbow::tsv::{Column, TsvOptions, TsvReader};
+use oxbow::twobit::TwoBitReader;
 use oxbow::vcf;
-// use oxbow::cram::CramReader;
-use oxbow::bcf::BcfReader;
-use oxbow::gff::GffReader;
-use oxbow::gtf::GtfReader;
-use oxbow::vcf::VcfReader;
+use oxbow::vcf::{VcfOptions, VcfReader};
 
 use oxbow::vpos;
+use oxbow::wig::{BedGraphReader, WigReader};
+use oxbow::BatchLimit;
 
 mod file_like;
 
@@ -32,288 +59,2922 @@ fn buffered_file_like(path_or_file_like: PyObject) -> PyResult<BufReader<PyFileL
         .map(|file_like| BufReader::with_capacity(const { 1024 * 1024 }, file_like))
 }
 
+/// Like `buffered_file_like`, for formats read in one pass, which don't need `.seek()`: a pipe
+/// such as `sys.stdin.buffer` can be read.
+fn stream_file_like(path_or_file_like: PyObject) -> PyResult<BufReader<PyFileLikeObject>> {
+    PyFileLikeObject::new(path_or_file_like, true, false, false)
+        .map(|file_like| BufReader::with_capacity(const { 1024 * 1024 }, file_like))
+}
+
+/// The `progress`, `progress_interval` and `cancel` arguments of a scan.
+struct ScanHooks {
+    /// Called with `(records, bytes, total_bytes)`.
+    progress: Option<PyObject>,
+    interval: usize,
+    /// An object with an `is_set` method, e.g. a `threading.Event`.
+    cancel: Option<PyObject>,
+}
+
+impl ScanHooks {
+    fn new(progress: Option<PyObject>, interval: Option<usize>, cancel: Option<PyObject>) -> Self {
+        Self {
+            progress,
+            interval: interval.unwrap_or(PROGRESS_INTERVAL),
+            cancel,
+        }
+    }
+}
+
+/// The number of records between progress reports and checks for cancellation, unless given as
+/// `progress_interval`.
+const PROGRESS_INTERVAL: usize = 10_000;
+
+/// Checks a scan every few records: reports its progress, lets other threads run, and cancels
+/// it once `cancel` is set or a signal such as `KeyboardInterrupt` is raised.
+///
+/// An exception raised by the `progress` callable is reported as unraisable rather than stopping
+/// the scan.
+struct PyScanHook {
+    hooks: ScanHooks,
+    token: Arc<AtomicBool>,
+    /// The error of the signal that cancelled the scan, to raise once it returns.
+    interrupt: Rc<RefCell<Option<PyErr>>>,
+}
+
+impl Progress for PyScanHook {
+    fn report(&mut self, records: u64, bytes: u64, total_bytes: Option<u64>) {
+        Python::with_gil(|py| {
+            if let Some(progress) = &self.hooks.progress {
+                if let Err(e) = progress.call1(py, (records, bytes, total_bytes)) {
+                    e.write_unraisable(py, Some(progress.bind(py)));
+                }
+            }
+            // The scan holds the GIL, so other threads, e.g. one setting `cancel`, only run now.
+            py.allow_threads(|| ());
+            let mut cancelled = self.hooks.cancel.as_ref().is_some_and(|cancel| {
+                cancel
+                    .call_method0(py, "is_set")
+                    .and_then(|set| set.bind(py).is_truthy())
+                    .unwrap_or(false)
+            });
+            if let Err(e) = py.check_signals() {
+                *self.interrupt.borrow_mut() = Some(e);
+                cancelled = true;
+            }
+            if cancelled {
+                self.token.store(true, Ordering::Relaxed);
+            }
+        })
+    }
+}
+
+/// Runs a query with [`run_query!`], checking scans and region queries with a [`PyScanHook`] of
+/// the given [`ScanHooks`]. A cancelled scan returns the records read until then, unless it was
+/// interrupted by a signal, whose error is raised.
+macro_rules! run_scan {
+    ($reader:expr, $query:expr, $hooks:expr) => {{
+        let token = Arc::new(AtomicBool::new(false));
+        let interrupt = Rc::new(RefCell::new(None));
+        let hook = PyScanHook {
+            hooks: $hooks,
+            token: token.clone(),
+            interrupt: interrupt.clone(),
+        };
+        let interval = hook.hooks.interval;
+        let mut reader = $reader.with_cancel(token).with_progress(interval, hook);
+        let result = run_query!(reader, $query);
+        match interrupt.take() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }};
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Reads an index given as a file path, bytes or a file-like object, separately from the data file.
+fn read_index<T>(
+    py: Python,
+    index: PyObject,
+    read: fn(Box<dyn ReadSeek>) -> std::io::Result<T>,
+) -> PyResult<T> {
+    let index: Box<dyn ReadSeek> = if let Ok(path) = index.downcast_bound::<PyString>(py) {
+        Box::new(std::fs::File::open(path.to_string_lossy().as_ref())?)
+    } else if let Ok(bytes) = index.downcast_bound::<PyBytes>(py) {
+        Box::new(std::io::Cursor::new(bytes.as_bytes().to_vec()))
+    } else {
+        Box::new(buffered_file_like(index)?)
+    };
+    Ok(read(index)?)
+}
+
+/// Returns the index given alongside a file-like object, which can't be looked up next to the data
+/// file as it is for a path.
+fn require_index(index: Option<PyObject>) -> PyResult<PyObject> {
+    index.ok_or_else(|| {
+        PyValueError::new_err(
+            "Reading a file-like object requires `index`, as a path, bytes or a file-like object.",
+        )
+    })
+}
+
+/// Writes Arrow IPC file bytes, as returned by the `read_*` functions, to a file-like object in
+/// the Arrow IPC stream format.
+#[pyfunction]
+fn write_ipc_stream(ipc: &[u8], file_like: PyObject) -> PyResult<()> {
+    let file_like = PyFileLikeObject::new(file_like, false, true, false)?;
+    oxbow::io::write_ipc_stream_from_file(ipc, file_like)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(())
+}
+
+/// Renames the columns of Arrow IPC file bytes, as returned by the `read_*` functions, after a
+/// `column_naming` preset (`oxbow_default` or `sam_native`) and a `rename` map of names, which
+/// takes precedence.
+#[pyfunction]
+#[pyo3(signature = (ipc, column_naming=None, rename=None))]
+fn rename_columns(
+    ipc: &[u8],
+    column_naming: Option<&str>,
+    rename: Option<HashMap<String, String>>,
+) -> PyResult<Vec<u8>> {
+    let column_naming = column_naming
+        .map(str::parse::<ColumnNaming>)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?
+        .unwrap_or_default();
+    let mut renames = column_naming.renames();
+    renames.extend(rename.unwrap_or_default());
+    rename_ipc_columns(ipc, &renames).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Flattens a list column of Arrow IPC file bytes, as returned by the `read_*` functions, into
+/// one row per item, e.g. `attributes.Parent` of GFF. Rows with an empty or null list get a null
+/// item, or are dropped if `empty_lists` is `drop`.
+#[pyfunction]
+#[pyo3(signature = (ipc, column, empty_lists=None))]
+fn flatten_list_columns(ipc: &[u8], column: &str, empty_lists: Option<&str>) -> PyResult<Vec<u8>> {
+    let empty_lists = empty_lists
+        .map(str::parse::<EmptyLists>)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?
+        .unwrap_or_default();
+    oxbow::flatten::flatten_list_columns(ipc, column, empty_lists)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// An iterator over the record batches of Arrow IPC file bytes, as returned by the `read_*`
+/// functions, yielding one `pyarrow.RecordBatch` at a time.
+///
+/// Batches are decoded as they are requested. Use a `batch_size` (or `batch_bytes`) when reading
+/// to split the output into several batches.
+#[pyclass]
+struct RecordBatchIterator {
+    batches: oxbow::io::IpcStreamBatches,
+}
+
+#[pymethods]
+impl RecordBatchIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        let Some(stream) = self.batches.next() else {
+            return Ok(None);
+        };
+        let stream = stream.map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let batch = open_ipc_stream(py, &stream)?.call_method0("read_next_batch")?;
+        Ok(Some(batch.unbind()))
+    }
+
+    /// Collects the remaining batches into a `pyarrow.Table`.
+    ///
+    /// The iterator is single-pass: this consumes it, and materializes all remaining batches.
+    #[allow(clippy::wrong_self_convention)]
+    fn to_arrow_table(&mut self, py: Python) -> PyResult<PyObject> {
+        let schema = self.schema(py)?;
+        let mut batches = Vec::new();
+        while let Some(batch) = self.__next__(py)? {
+            batches.push(batch);
+        }
+        let table = py
+            .import("pyarrow")?
+            .getattr("Table")?
+            .call_method1("from_batches", (batches, schema))?;
+        Ok(table.unbind())
+    }
+
+    /// Collects the remaining batches into a `polars.DataFrame`, without copying the Arrow data.
+    ///
+    /// Like [`RecordBatchIterator::to_arrow_table`], this consumes the iterator and fully
+    /// materializes the remaining batches. Requires `polars` in addition to `pyarrow`.
+    #[allow(clippy::wrong_self_convention)]
+    fn to_polars(&mut self, py: Python) -> PyResult<PyObject> {
+        let table = self.to_arrow_table(py)?;
+        let df = py.import("polars")?.call_method1("from_arrow", (table,))?;
+        Ok(df.unbind())
+    }
+
+    /// Exports the remaining batches through the Arrow C stream interface, e.g. to DuckDB or
+    /// Polars.
+    ///
+    /// The stream is backed by a `pyarrow.RecordBatchReader` over this iterator, which the
+    /// returned capsule keeps alive until the consumer releases it. Requires `pyarrow` 14 or later.
+    #[pyo3(signature = (requested_schema=None))]
+    fn __arrow_c_stream__(
+        slf: Bound<'_, Self>,
+        requested_schema: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let py = slf.py();
+        let schema = slf.borrow().schema(py)?;
+        let reader = py
+            .import("pyarrow")?
+            .getattr("RecordBatchReader")?
+            .call_method1("from_batches", (schema, slf))?;
+        let capsule = reader.call_method1("__arrow_c_stream__", (requested_schema,))?;
+        Ok(capsule.unbind())
+    }
+
+    /// The `pyarrow.Schema` of the batches.
+    #[getter]
+    fn schema(&self, py: Python) -> PyResult<PyObject> {
+        let stream = self
+            .batches
+            .schema_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(open_ipc_stream(py, &stream)?.getattr("schema")?.unbind())
+    }
+}
+
+/// Opens an Arrow IPC stream with `pyarrow.ipc.open_stream`.
+fn open_ipc_stream<'py>(py: Python<'py>, stream: &[u8]) -> PyResult<Bound<'py, PyAny>> {
+    py.import("pyarrow.ipc")?
+        .call_method1("open_stream", (PyBytes::new(py, stream),))
+}
+
+/// Iterates over the record batches of Arrow IPC file bytes, as returned by the `read_*`
+/// functions. Requires `pyarrow`.
+///
+/// The bytes are already fully built, so this doesn't stream records from the file.
+#[pyfunction]
+fn iter_batches(ipc: &[u8]) -> PyResult<RecordBatchIterator> {
+    let batches = oxbow::io::IpcStreamBatches::try_new(ipc.to_vec())
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(RecordBatchIterator { batches })
+}
+
+/// A region argument: a region string such as `chr1:1,000-2,000`, or a `(name, start, end)`
+/// tuple of a reference sequence name and 1-based, closed bounds, either of which may be `None`.
+struct RegionArg(String);
+
+impl RegionArg {
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn into_strings(regions: Vec<Self>) -> Vec<String> {
+        regions.into_iter().map(|region| region.0).collect()
+    }
+}
+
+impl<'py> FromPyObject<'py> for RegionArg {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(s) = ob.extract::<String>() {
+            return Ok(Self(s));
+        }
+        let (name, start, end) =
+            ob.extract::<(String, Option<u64>, Option<u64>)>()
+                .map_err(|_| {
+                    PyValueError::new_err(
+                        "A region must be a string or a (name, start, end) tuple.",
+                    )
+                })?;
+        let region = match (start, end) {
+            (None, None) => name,
+            (start, None) => format!("{}:{}", name, start.unwrap_or(1)),
+            (start, Some(end)) => format!("{}:{}-{}", name, start.unwrap_or(1), end),
+        };
+        Ok(Self(region))
+    }
+}
+
+/// A chromosome alias argument: the `"ucsc_ensembl"` preset, or a dict from query names to the
+/// names of the file. See [`ChromAlias`].
+struct ChromAliasArg(ChromAlias);
+
+impl<'py> FromPyObject<'py> for ChromAliasArg {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(s) = ob.extract::<String>() {
+            return s
+                .parse()
+                .map(Self)
+                .map_err(|e: std::io::Error| PyValueError::new_err(e.to_string()));
+        }
+        let map = ob.extract::<HashMap<String, String>>().map_err(|_| {
+            PyValueError::new_err("A chromosome alias must be a preset name or a dict of names.")
+        })?;
+        Ok(Self(ChromAlias::Map(map)))
+    }
+}
+
+/// Parses regions into normalized region strings, from a list of region strings or
+/// `(name, start, end)` tuples, or from the path of a BED file.
+///
+/// Thousands separators are removed, and open-ended regions such as `chr1:500-` are accepted.
+/// BED intervals are converted to 1-based, closed regions.
+#[pyfunction]
+fn parse_regions(py: Python, regions: PyObject) -> PyResult<Vec<String>> {
+    let regions = if let Ok(path) = regions.extract::<String>(py) {
+        oxbow::region::regions_from_bed(path)
+    } else {
+        let regions = RegionArg::into_strings(regions.extract(py)?);
+        oxbow::region::parse_regions(&regions)
+    };
+    regions
+        .map(|regions| regions.iter().map(|region| region.to_string()).collect())
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// A region query: a single region (or none, for all records), or several regions queried either
+/// sequentially or on a number of threads. Scans of all records can also use a number of threads,
+/// to decompress the file, which readers are set up with before running the query.
+enum Query {
+    Region(Option<String>),
+    Regions(Vec<String>, Option<usize>),
+}
+
+impl Query {
+    fn new(
+        region: Option<&str>,
+        regions: Option<Vec<String>>,
+        threads: Option<usize>,
+    ) -> PyResult<Self> {
+        match (region, regions) {
+            (Some(_), Some(_)) => Err(PyValueError::new_err(
+                "Only one of `region` and `regions` may be given.",
+            )),
+            (Some(_), None) if threads.is_some() => Err(PyValueError::new_err(
+                "`threads` requires `regions`, or a scan of all records.",
+            )),
+            (region, None) => Ok(Self::Region(region.map(|r| r.to_string()))),
+            (None, Some(regions)) => Ok(Self::Regions(regions, threads)),
+        }
+    }
+}
+
+/// Runs a [`Query`] against a reader with `records_to_ipc`, `regions_to_ipc` and
+/// `regions_to_ipc_parallel` methods.
+macro_rules! run_query {
+    ($reader:expr, $query:expr) => {
+        match $query {
+            Query::Region(region) => $reader.records_to_ipc(region.as_deref()),
+            Query::Regions(regions, threads) => {
+                let regions: Vec<&str> = regions.iter().map(|r| r.as_str()).collect();
+                match threads {
+                    Some(threads) => $reader.regions_to_ipc_parallel(&regions, Some(threads)),
+                    None => $reader.regions_to_ipc(&regions),
+                }
+            }
+        }
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+    };
+}
+
+/// A record count: the total for a single region (or all records), or one per region.
+#[derive(IntoPyObject)]
+enum Count {
+    Total(usize),
+    Regions(HashMap<String, usize>),
+}
+
+/// Runs a [`Query`] against a reader with `count` and `count_regions` methods.
+macro_rules! run_count {
+    ($reader:expr, $query:expr) => {
+        match $query {
+            Query::Region(region) => $reader.count(region.as_deref()).map(Count::Total),
+            Query::Regions(regions, _) => {
+                let names: Vec<&str> = regions.iter().map(|r| r.as_str()).collect();
+                $reader
+                    .count_regions(&names)
+                    .map(|counts| Count::Regions(regions.into_iter().zip(counts).collect()))
+            }
+        }
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+    };
+}
+
 #[pyfunction]
 fn partition_from_index_file(path: &str, chunksize: u64) -> Vec<(u64, u16)> {
     vpos::partition_from_index_file(path, chunksize)
 }
 
+/// Reads FASTA records from a path or a file-like object.
+///
+/// File-like objects are read sequentially. Querying them by region requires an uncompressed,
+/// seekable file-like object and its FASTA index, given as `index`.
 #[pyfunction]
-#[pyo3(signature = (path, region=None))]
-fn read_fasta(path: &str, region: Option<&str>) -> Vec<u8> {
-    let mut reader = FastaReader::new(path).unwrap();
-    reader.records_to_ipc(region).unwrap()
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, regions=None, reverse_complement=None, batch_size=None, batch_bytes=None, include_length=None, include_gc=None, limit=None, fields=None, index=None, encoding=None, kmer_size=None, kmer_stride=None, allow_ambiguous_kmers=None, hash_kmers=None, min_length=None, max_length=None, max_n_fraction=None, max_lowercase_fraction=None, count_motifs=None))]
+fn read_fasta(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    regions: Option<Vec<RegionArg>>,
+    reverse_complement: Option<Vec<bool>>,
+    batch_size: Option<usize>,
+    batch_bytes: Option<usize>,
+    include_length: Option<bool>,
+    include_gc: Option<bool>,
+    limit: Option<usize>,
+    fields: Option<Vec<String>>,
+    index: Option<PyObject>,
+    encoding: Option<&str>,
+    kmer_size: Option<usize>,
+    kmer_stride: Option<usize>,
+    allow_ambiguous_kmers: Option<bool>,
+    hash_kmers: Option<bool>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    max_n_fraction: Option<f32>,
+    max_lowercase_fraction: Option<f32>,
+    count_motifs: Option<Vec<String>>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let regions = regions.map(RegionArg::into_strings);
+    let options = FastaOptions {
+        include_length,
+        include_gc,
+        encoding: encoding
+            .map(str::parse::<SequenceEncoding>)
+            .transpose()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?,
+        fields,
+        kmer_size,
+        kmer_stride,
+        allow_ambiguous_kmers,
+        hash_kmers,
+        count_motifs,
+        min_length,
+        max_length,
+        max_n_fraction,
+        max_lowercase_fraction,
+    };
+    let mut reader = fasta_reader(py, path_or_file_like, index)?.with_options(options);
+    match (batch_size, batch_bytes) {
+        (Some(_), Some(_)) => {
+            return Err(PyValueError::new_err(
+                "Only one of `batch_size` and `batch_bytes` may be given.",
+            ))
+        }
+        (Some(n), None) => reader = reader.with_batch_limit(BatchLimit::Records(n)),
+        (None, Some(n)) => reader = reader.with_batch_limit(BatchLimit::Bytes(n)),
+        (None, None) => {}
+    }
+    if let Some(limit) = limit {
+        reader = reader.with_limit(limit);
+    }
+    let regions: Vec<String> = match (region, regions) {
+        (Some(region), None) => vec![region.to_string()],
+        (None, Some(regions)) => regions,
+        (None, None) => {
+            if reverse_complement.is_some() {
+                return Err(PyValueError::new_err(
+                    "`reverse_complement` requires `region` or `regions`.",
+                ));
+            }
+            return reader
+                .records_to_ipc(None)
+                .map_err(|e| PyValueError::new_err(e.to_string()));
+        }
+        (Some(_), Some(_)) => {
+            return Err(PyValueError::new_err(
+                "Only one of `region` and `regions` may be given.",
+            ))
+        }
+    };
+    let regions: Vec<&str> = regions.iter().map(|region| region.as_str()).collect();
+    reader
+        .regions_to_ipc(&regions, reverse_complement.as_deref())
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Opens a FASTA reader over a path or a file-like object, with an optional FASTA index.
+///
+/// A file-like object with an index must be uncompressed and seekable. Without one, it is
+/// decompressed if needed and read sequentially.
+fn fasta_reader(
+    py: Python,
+    path_or_file_like: PyObject,
+    index: Option<PyObject>,
+) -> PyResult<FastaReader> {
+    let index = index
+        .map(|index| read_index(py, index, fasta::index_from_reader))
+        .transpose()?;
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let path = string_ref.to_string_lossy();
+        Ok(match index {
+            Some(index) => FastaReader::new_with_index(&path, index)?,
+            None => FastaReader::new(&path)?,
+        })
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        Ok(match index {
+            Some(index) => FastaReader::new_from_reader_with_index(file_like, index),
+            None => FastaReader::new_from_reader(decompress(file_like)?),
+        })
+    }
 }
 
 #[pyfunction]
-fn read_fastq(py: Python, path_or_file_like: PyObject) -> Vec<u8> {
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, decode_quality=None, quality_offset=None, include_length=None, include_gc=None, parse_casava=None, kmer_size=None, kmer_stride=None, allow_ambiguous_kmers=None, hash_kmers=None, min_length=None, max_length=None, count_motifs=None, fields=None))]
+fn read_fastq(
+    py: Python,
+    path_or_file_like: PyObject,
+    decode_quality: Option<bool>,
+    quality_offset: Option<u8>,
+    include_length: Option<bool>,
+    include_gc: Option<bool>,
+    parse_casava: Option<bool>,
+    kmer_size: Option<usize>,
+    kmer_stride: Option<usize>,
+    allow_ambiguous_kmers: Option<bool>,
+    hash_kmers: Option<bool>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    count_motifs: Option<Vec<String>>,
+    fields: Option<Vec<String>>,
+) -> PyResult<Vec<u8>> {
+    let options = FastqOptions {
+        decode_quality,
+        quality_offset,
+        include_length,
+        include_gc,
+        parse_casava,
+        kmer_size,
+        kmer_stride,
+        allow_ambiguous_kmers,
+        hash_kmers,
+        count_motifs,
+        min_length,
+        max_length,
+        fields,
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it like a path
-        let mut reader = FastqReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc().unwrap()
+        let mut reader = FastqReader::new_from_path(string_ref.to_string_lossy().as_ref())?
+            .with_options(options);
+        reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
-        let file_like = buffered_file_like(path_or_file_like)
+        let file_like = stream_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let mut reader = FastqReader::new(file_like).unwrap();
-        reader.records_to_ipc().unwrap()
+        let mut reader = FastqReader::new(decompress(file_like)?)?.with_options(options);
+        reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+/// Opens a FASTQ reader on a file path or a file-like object, decompressing it if needed.
+fn fastq_reader(
+    py: Python,
+    path_or_file_like: PyObject,
+) -> PyResult<FastqReader<Box<dyn BufRead>>> {
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it like a path
+        Ok(FastqReader::new_from_path(
+            string_ref.to_string_lossy().as_ref(),
+        )?)
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = stream_file_like(path_or_file_like)?;
+        Ok(FastqReader::new(decompress(file_like)?)?)
     }
 }
 
 #[pyfunction]
-#[pyo3(signature = (path_or_file_like, region=None, index=None))]
+#[pyo3(signature = (path_or_file_like_1, path_or_file_like_2, decode_quality=None, quality_offset=None))]
+fn read_fastq_paired(
+    py: Python,
+    path_or_file_like_1: PyObject,
+    path_or_file_like_2: PyObject,
+    decode_quality: Option<bool>,
+    quality_offset: Option<u8>,
+) -> PyResult<Vec<u8>> {
+    let options = FastqOptions {
+        decode_quality,
+        quality_offset,
+        ..Default::default()
+    };
+    let mut reader = fastq_reader(py, path_or_file_like_1)?.with_options(options);
+    let mut mates = fastq_reader(py, path_or_file_like_2)?;
+    reader
+        .paired_to_ipc(&mut mates)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, decode_quality=None, quality_offset=None))]
+fn read_fastq_interleaved(
+    py: Python,
+    path_or_file_like: PyObject,
+    decode_quality: Option<bool>,
+    quality_offset: Option<u8>,
+) -> PyResult<Vec<u8>> {
+    let options = FastqOptions {
+        decode_quality,
+        quality_offset,
+        ..Default::default()
+    };
+    let mut reader = fastq_reader(py, path_or_file_like)?.with_options(options);
+    reader
+        .interleaved_to_ipc()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Parses the `coordinate_system` argument, defaulting to the native coordinates of the format.
+fn parse_coordinate_system(coordinate_system: Option<&str>) -> PyResult<Option<CoordinateSystem>> {
+    coordinate_system
+        .map(str::parse::<CoordinateSystem>)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Parses the `query_mode` argument, defaulting to overlap.
+fn parse_query_mode(query_mode: Option<&str>) -> PyResult<QueryMode> {
+    query_mode
+        .map(str::parse::<QueryMode>)
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Parses the `on_error` argument, defaulting to fail.
+fn parse_error_policy(on_error: Option<&str>) -> PyResult<ErrorPolicy> {
+    on_error
+        .map(str::parse::<ErrorPolicy>)
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Issues a `UserWarning` for each record skipped under `on_error="skip_and_collect"`.
+fn warn_skipped(py: Python, errors: &[RecordError]) -> PyResult<()> {
+    let category = py.get_type::<PyUserWarning>();
+    for error in errors {
+        let message = std::ffi::CString::new(format!("Skipped record at {}", error))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        PyErr::warn(py, category.as_any(), &message, 1)?;
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, index=None, tags=None, regions=None, threads=None, min_mapq=None, include_flags=None, exclude_flags=None, limit=None, query_mode=None, fields=None, include_reference_length=None, include_query_length=None, emit_ref_as_index=None, coordinate_system=None, unsigned_positions=None, include_sample=None, include_clip_lengths=None, emit_offset=None, chrom_alias=None, decode_flags=None, require_sorted=None, sample_fraction=None, sample_count=None, sample_seed=None, methylation=None, reference=None, progress=None, progress_interval=None, cancel=None))]
 fn read_bam(
     py: Python,
     path_or_file_like: PyObject,
-    region: Option<&str>,
+    region: Option<RegionArg>,
     index: Option<PyObject>,
-) -> Vec<u8> {
+    tags: Option<Vec<String>>,
+    regions: Option<Vec<RegionArg>>,
+    threads: Option<usize>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    limit: Option<usize>,
+    query_mode: Option<&str>,
+    fields: Option<Vec<String>>,
+    include_reference_length: Option<bool>,
+    include_query_length: Option<bool>,
+    emit_ref_as_index: Option<bool>,
+    coordinate_system: Option<&str>,
+    unsigned_positions: Option<bool>,
+    include_sample: Option<bool>,
+    include_clip_lengths: Option<bool>,
+    emit_offset: Option<bool>,
+    chrom_alias: Option<ChromAliasArg>,
+    decode_flags: Option<bool>,
+    require_sorted: Option<bool>,
+    sample_fraction: Option<f64>,
+    sample_count: Option<usize>,
+    sample_seed: Option<u64>,
+    methylation: Option<bool>,
+    reference: Option<String>,
+    progress: Option<PyObject>,
+    progress_interval: Option<usize>,
+    cancel: Option<PyObject>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let regions = regions.map(RegionArg::into_strings);
+    let query = Query::new(region, regions, threads)?;
+    let query_mode = parse_query_mode(query_mode)?;
+    let hooks = ScanHooks::new(progress, progress_interval, cancel);
+    let methylation_reference = match (methylation.unwrap_or(false), reference) {
+        (true, None) => {
+            return Err(PyValueError::new_err(
+                "`methylation` requires the path of an indexed FASTA `reference`.",
+            ))
+        }
+        (true, reference) => reference,
+        (false, _) => None,
+    };
+    let options = BamOptions {
+        tags,
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        fields,
+        include_reference_length,
+        include_query_length,
+        emit_ref_as_index,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        unsigned_positions,
+        include_sample,
+        include_clip_lengths,
+        emit_offset,
+        chrom_alias: chrom_alias.map(|alias| alias.0),
+        require_sorted,
+        sample_fraction,
+        sample_count,
+        sample_seed,
+        methylation_reference,
+        decode_flags,
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
-        let mut reader = BamReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc(region).unwrap()
+        let path = string_ref.to_string_lossy();
+        let mut reader = match index {
+            Some(index) => {
+                let index = read_index(py, index, bam::index_from_reader)?;
+                BamReader::new_from_path_with_index(&path, index)?
+            }
+            None => BamReader::new_from_path(&path)?,
+        }
+        .with_options(options)
+        .with_query_mode(query_mode);
+        if let Some(limit) = limit {
+            reader = reader.with_limit(limit);
+        }
+        if let (Query::Region(None), Some(threads)) = (&query, threads) {
+            reader = reader.with_threads(threads);
+        }
+        run_scan!(reader, query, hooks)
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let index_file_like = buffered_file_like(index.unwrap())
-            .expect("Unknown argument for `index`. Not a file path string or url, and not a file-like object.");
-        let index = bam::index_from_reader(index_file_like).unwrap();
-        let mut reader = BamReader::new(file_like, index).unwrap();
-        reader.records_to_ipc(region).unwrap()
+        let index = read_index(py, require_index(index)?, bam::index_from_reader)?;
+        let mut reader = BamReader::new(file_like, index)?
+            .with_options(options)
+            .with_query_mode(query_mode);
+        if let Some(limit) = limit {
+            reader = reader.with_limit(limit);
+        }
+        if let (Query::Region(None), Some(threads)) = (&query, threads) {
+            reader = reader.with_threads(threads);
+        }
+        run_scan!(reader, query, hooks)
     }
 }
 
 #[pyfunction]
-#[pyo3(signature = (path_or_file_like, pos_lo, pos_hi, index=None))]
-fn read_bam_vpos(
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (paths, region=None, source=None, tags=None, min_mapq=None, include_flags=None, exclude_flags=None, fields=None, include_reference_length=None, include_query_length=None, emit_ref_as_index=None, coordinate_system=None, unsigned_positions=None, include_sample=None, include_clip_lengths=None, chrom_alias=None, decode_flags=None))]
+fn read_bam_many(
+    paths: Vec<String>,
+    region: Option<RegionArg>,
+    source: Option<bool>,
+    tags: Option<Vec<String>>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    fields: Option<Vec<String>>,
+    include_reference_length: Option<bool>,
+    include_query_length: Option<bool>,
+    emit_ref_as_index: Option<bool>,
+    coordinate_system: Option<&str>,
+    unsigned_positions: Option<bool>,
+    include_sample: Option<bool>,
+    include_clip_lengths: Option<bool>,
+    chrom_alias: Option<ChromAliasArg>,
+    decode_flags: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = BamOptions {
+        tags,
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        fields,
+        include_reference_length,
+        include_query_length,
+        emit_ref_as_index,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        unsigned_positions,
+        include_sample,
+        include_clip_lengths,
+        emit_offset: None,
+        chrom_alias: chrom_alias.map(|alias| alias.0),
+        // The files are not merged in order.
+        require_sorted: None,
+        sample_fraction: None,
+        sample_count: None,
+        sample_seed: None,
+        methylation_reference: None,
+        decode_flags,
+    };
+    let paths = paths.iter().map(String::as_str).collect::<Vec<_>>();
+    let mut reader = BamMultiReader::new_from_paths(&paths)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?
+        .with_options(options)
+        .with_source(source.unwrap_or(false));
+    reader
+        .records_to_ipc(region)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+fn read_groups_bam(path: &str) -> PyResult<Vec<BTreeMap<String, String>>> {
+    Ok(BamReader::new_from_path(path)?.read_groups())
+}
+
+#[pyfunction]
+fn programs_bam(path: &str) -> PyResult<Vec<BTreeMap<String, String>>> {
+    Ok(BamReader::new_from_path(path)?.programs())
+}
+
+#[pyfunction]
+fn read_bam_header(path: &str) -> PyResult<String> {
+    Ok(BamReader::new_from_path(path)?.header_text())
+}
+
+/// Returns a dict from reference sequence names to lengths, keeping their order.
+fn lengths_dict(py: Python, lengths: Vec<(String, u64)>) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    for (name, length) in lengths {
+        dict.set_item(name, length)?;
+    }
+    Ok(dict.into_any().unbind())
+}
+
+#[pyfunction]
+fn reference_lengths_bam(py: Python, path: &str) -> PyResult<PyObject> {
+    lengths_dict(py, BamReader::new_from_path(path)?.reference_lengths())
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, index=None, tags=None, min_mapq=None, include_flags=None, exclude_flags=None, fields=None, include_reference_length=None, include_query_length=None))]
+fn read_bam_unmapped(
     py: Python,
     path_or_file_like: PyObject,
-    pos_lo: (u64, u16),
-    pos_hi: (u64, u16),
     index: Option<PyObject>,
-) -> Vec<u8> {
+    tags: Option<Vec<String>>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    fields: Option<Vec<String>>,
+    include_reference_length: Option<bool>,
+    include_query_length: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let options = BamOptions {
+        tags,
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        fields,
+        include_reference_length,
+        include_query_length,
+        ..Default::default()
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
-        let mut reader = BamReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc_from_vpos(pos_lo, pos_hi).unwrap()
+        let mut reader =
+            BamReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        reader
+            .records_to_ipc_unmapped()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let index_file_like = buffered_file_like(index.unwrap())
-            .expect("Unknown argument for `index`. Not a file path string or url, and not a file-like object.");
-        let index = bam::index_from_reader(index_file_like).unwrap();
-        let mut reader = BamReader::new(file_like, index).unwrap();
-        reader.records_to_ipc_from_vpos(pos_lo, pos_hi).unwrap()
+        let index = read_index(py, require_index(index)?, bam::index_from_reader)?;
+        let mut reader = BamReader::new(file_like, index)?.with_options(options);
+        reader
+            .records_to_ipc_unmapped()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
+/// Returns binned read counts of the given regions, or of all reference sequences, with windows
+/// of `window_size` bases. Reads are counted in the window of their start, or with
+/// `assignment="overlap"` in every window they overlap.
 #[pyfunction]
-#[pyo3(signature = (path_or_file_like, region=None, index=None))]
-fn read_vcf(
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, window_size, regions=None, assignment=None, include_mean_depth=false, index=None, min_mapq=None, include_flags=None, exclude_flags=None, coordinate_system=None))]
+fn read_bam_coverage(
     py: Python,
     path_or_file_like: PyObject,
-    region: Option<&str>,
+    window_size: usize,
+    regions: Option<Vec<RegionArg>>,
+    assignment: Option<&str>,
+    include_mean_depth: bool,
     index: Option<PyObject>,
-) -> Vec<u8> {
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    coordinate_system: Option<&str>,
+) -> PyResult<Vec<u8>> {
+    let regions = regions.map(RegionArg::into_strings);
+    let regions: Option<Vec<&str>> = regions
+        .as_ref()
+        .map(|regions| regions.iter().map(String::as_str).collect());
+    let assignment = assignment
+        .map(str::parse::<WindowAssignment>)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?
+        .unwrap_or_default();
+    let options = BamOptions {
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        ..Default::default()
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
-        let mut reader = VcfReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc(region).unwrap()
+        let path = string_ref.to_string_lossy();
+        let mut reader = match index {
+            Some(index) => {
+                let index = read_index(py, index, bam::index_from_reader)?;
+                BamReader::new_from_path_with_index(&path, index)?
+            }
+            None => BamReader::new_from_path(&path)?,
+        }
+        .with_options(options);
+        reader
+            .coverage_windows(
+                window_size,
+                regions.as_deref(),
+                assignment,
+                include_mean_depth,
+            )
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let index_file_like = buffered_file_like(index.unwrap())
-            .expect("Unknown argument for `index`. Not a file path string or url, and not a file-like object.");
-        let index = vcf::index_from_reader(index_file_like).unwrap();
-        let mut reader = VcfReader::new(file_like, index).unwrap();
-        reader.records_to_ipc(region).unwrap()
+        let index = read_index(py, require_index(index)?, bam::index_from_reader)?;
+        let mut reader = BamReader::new(file_like, index)?.with_options(options);
+        reader
+            .coverage_windows(
+                window_size,
+                regions.as_deref(),
+                assignment,
+                include_mean_depth,
+            )
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
+/// Returns a per-base pileup of the given regions: the number of aligned A, C, G, T and other
+/// bases, and of deletions, at each covered position.
 #[pyfunction]
-#[pyo3(signature = (path_or_file_like, pos_lo, pos_hi, index=None))]
-fn read_vcf_vpos(
+#[pyo3(signature = (path_or_file_like, regions, index=None, min_mapq=None, include_flags=None, exclude_flags=None))]
+fn read_bam_pileup(
     py: Python,
     path_or_file_like: PyObject,
-    pos_lo: (u64, u16),
-    pos_hi: (u64, u16),
+    regions: Vec<RegionArg>,
     index: Option<PyObject>,
-) -> Vec<u8> {
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+) -> PyResult<Vec<u8>> {
+    let regions = RegionArg::into_strings(regions);
+    let regions: Vec<&str> = regions.iter().map(String::as_str).collect();
+    let options = BamOptions {
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        ..Default::default()
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
-        let mut reader = VcfReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc_from_vpos(pos_lo, pos_hi).unwrap()
+        let path = string_ref.to_string_lossy();
+        let mut reader = match index {
+            Some(index) => {
+                let index = read_index(py, index, bam::index_from_reader)?;
+                BamReader::new_from_path_with_index(&path, index)?
+            }
+            None => BamReader::new_from_path(&path)?,
+        }
+        .with_options(options);
+        reader
+            .pileup_to_ipc(&regions)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let index_file_like = buffered_file_like(index.unwrap())
-            .expect("Unknown argument for `index`. Not a file path string or url, and not a file-like object.");
-        let index = vcf::index_from_reader(index_file_like).unwrap();
-        let mut reader = VcfReader::new(file_like, index).unwrap();
-        reader.records_to_ipc_from_vpos(pos_lo, pos_hi).unwrap()
+        let index = read_index(py, require_index(index)?, bam::index_from_reader)?;
+        let mut reader = BamReader::new(file_like, index)?.with_options(options);
+        reader
+            .pileup_to_ipc(&regions)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
+/// Returns one row per read pair in a region, combining the positions, mapping qualities and
+/// flags of both mates. Reads whose mate is not in the region get null mate columns.
+/// `max_buffer` bounds the number of reads held in memory while waiting for their mates.
 #[pyfunction]
-#[pyo3(signature = (path_or_file_like, region=None, index=None))]
-fn read_bcf(
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, index=None, max_buffer=None, min_mapq=None, include_flags=None, exclude_flags=None, emit_ref_as_index=None, coordinate_system=None))]
+fn read_bam_pairs(
     py: Python,
     path_or_file_like: PyObject,
-    region: Option<&str>,
+    region: Option<RegionArg>,
     index: Option<PyObject>,
-) -> Vec<u8> {
+    max_buffer: Option<usize>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    emit_ref_as_index: Option<bool>,
+    coordinate_system: Option<&str>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = BamOptions {
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        emit_ref_as_index,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        ..Default::default()
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
-        let mut reader = BcfReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc(region).unwrap()
+        let path = string_ref.to_string_lossy();
+        let mut reader = match index {
+            Some(index) => {
+                let index = read_index(py, index, bam::index_from_reader)?;
+                BamReader::new_from_path_with_index(&path, index)?
+            }
+            None => BamReader::new_from_path(&path)?,
+        }
+        .with_options(options);
+        reader
+            .pairs_to_ipc(region, max_buffer)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let index_file_like = buffered_file_like(index.unwrap())
-            .expect("Unknown argument for `index`. Not a file path string or url, and not a file-like object.");
-        let index = bcf::index_from_reader(index_file_like).unwrap();
-        let mut reader = BcfReader::new(file_like, index).unwrap();
-        reader.records_to_ipc(region).unwrap()
+        let index = read_index(py, require_index(index)?, bam::index_from_reader)?;
+        let mut reader = BamReader::new(file_like, index)?.with_options(options);
+        reader
+            .pairs_to_ipc(region, max_buffer)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
 #[pyfunction]
-#[pyo3(signature = (path_or_file_like, pos_lo, pos_hi, index=None))]
-fn read_bcf_vpos(
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, pos_lo, pos_hi, index=None, tags=None, min_mapq=None, include_flags=None, exclude_flags=None, fields=None, include_reference_length=None, include_query_length=None, emit_offset=None))]
+fn read_bam_vpos(
     py: Python,
     path_or_file_like: PyObject,
     pos_lo: (u64, u16),
     pos_hi: (u64, u16),
     index: Option<PyObject>,
-) -> Vec<u8> {
+    tags: Option<Vec<String>>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    fields: Option<Vec<String>>,
+    include_reference_length: Option<bool>,
+    include_query_length: Option<bool>,
+    emit_offset: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let options = BamOptions {
+        tags,
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        fields,
+        include_reference_length,
+        include_query_length,
+        emit_offset,
+        ..Default::default()
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
-        let mut reader = VcfReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc_from_vpos(pos_lo, pos_hi).unwrap()
+        let mut reader =
+            BamReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        reader
+            .records_to_ipc_from_vpos(pos_lo, pos_hi)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let index_file_like = buffered_file_like(index.unwrap())
-            .expect("Unknown argument for `index`. Not a file path string or url, and not a file-like object.");
-        let index = bcf::index_from_reader(index_file_like).unwrap();
-        let mut reader = VcfReader::new(file_like, index).unwrap();
-        reader.records_to_ipc_from_vpos(pos_lo, pos_hi).unwrap()
+        let index = read_index(py, require_index(index)?, bam::index_from_reader)?;
+        let mut reader = BamReader::new(file_like, index)?.with_options(options);
+        reader
+            .records_to_ipc_from_vpos(pos_lo, pos_hi)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
 #[pyfunction]
-#[pyo3(signature = (path_or_file_like, region=None, zoom_level=None, zoom_summary_columns=None))]
-fn read_bigwig(
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, vpos, index=None, tags=None, min_mapq=None, include_flags=None, exclude_flags=None, fields=None, include_reference_length=None, include_query_length=None, emit_offset=None))]
+fn read_bam_from(
     py: Python,
     path_or_file_like: PyObject,
-    region: Option<&str>,
-    zoom_level: Option<u32>,
-    zoom_summary_columns: Option<HashSet<String>>,
-) -> Vec<u8> {
-    let zoom_summary_columns_ref = zoom_summary_columns
-        .as_ref()
-        .map(|h| h.iter().map(String::as_str).collect::<HashSet<&str>>());
+    vpos: u64,
+    index: Option<PyObject>,
+    tags: Option<Vec<String>>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    fields: Option<Vec<String>>,
+    include_reference_length: Option<bool>,
+    include_query_length: Option<bool>,
+    emit_offset: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let options = BamOptions {
+        tags,
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        fields,
+        include_reference_length,
+        include_query_length,
+        emit_offset,
+        ..Default::default()
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
         let mut reader =
-            BigWigReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        match zoom_level {
-            Some(zoom_level) => reader
-                .zoom_records_to_ipc(region, zoom_level, zoom_summary_columns_ref)
-                .unwrap(),
-            None => reader.records_to_ipc(region).unwrap(),
+            BamReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        reader
+            .scan_from(vpos)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let index = read_index(py, require_index(index)?, bam::index_from_reader)?;
+        let mut reader = BamReader::new(file_like, index)?.with_options(options);
+        reader
+            .scan_from(vpos)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, reference=None, region=None, index=None, tags=None, min_mapq=None, include_flags=None, exclude_flags=None, query_mode=None, fields=None, include_reference_length=None, include_query_length=None, emit_ref_as_index=None, coordinate_system=None, unsigned_positions=None, include_sample=None, include_clip_lengths=None, chrom_alias=None, decode_flags=None, require_sorted=None, sample_fraction=None, sample_count=None, sample_seed=None))]
+fn read_cram(
+    py: Python,
+    path_or_file_like: PyObject,
+    reference: Option<&str>,
+    region: Option<RegionArg>,
+    index: Option<PyObject>,
+    tags: Option<Vec<String>>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    query_mode: Option<&str>,
+    fields: Option<Vec<String>>,
+    include_reference_length: Option<bool>,
+    include_query_length: Option<bool>,
+    emit_ref_as_index: Option<bool>,
+    coordinate_system: Option<&str>,
+    unsigned_positions: Option<bool>,
+    include_sample: Option<bool>,
+    include_clip_lengths: Option<bool>,
+    chrom_alias: Option<ChromAliasArg>,
+    decode_flags: Option<bool>,
+    require_sorted: Option<bool>,
+    sample_fraction: Option<f64>,
+    sample_count: Option<usize>,
+    sample_seed: Option<u64>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = BamOptions {
+        tags,
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        fields,
+        include_reference_length,
+        include_query_length,
+        emit_ref_as_index,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        unsigned_positions,
+        include_sample,
+        include_clip_lengths,
+        emit_offset: None,
+        chrom_alias: chrom_alias.map(|alias| alias.0),
+        require_sorted,
+        sample_fraction,
+        sample_count,
+        sample_seed,
+        methylation_reference: None,
+        decode_flags,
+    };
+    let query_mode = parse_query_mode(query_mode)?;
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader =
+            CramReader::new_from_path(string_ref.to_string_lossy().as_ref(), reference)?
+                .with_options(options)
+                .with_query_mode(query_mode);
+        reader
+            .records_to_ipc(region)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let index = index
+            .map(|index| read_index(py, index, cram::index_from_reader))
+            .transpose()?;
+        let repository = reference
+            .map(cram::repository_from_path)
+            .transpose()?
+            .unwrap_or_default();
+        let mut reader = CramReader::new(file_like, index, repository)?
+            .with_options(options)
+            .with_query_mode(query_mode);
+        reader
+            .records_to_ipc(region)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, allow_linear_scan=false, tags=None, min_mapq=None, include_flags=None, exclude_flags=None, query_mode=None, fields=None, include_reference_length=None, include_query_length=None, emit_ref_as_index=None, coordinate_system=None, unsigned_positions=None, include_sample=None, include_clip_lengths=None, chrom_alias=None, decode_flags=None, require_sorted=None, sample_fraction=None, sample_count=None, sample_seed=None))]
+fn read_sam(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    allow_linear_scan: bool,
+    tags: Option<Vec<String>>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    query_mode: Option<&str>,
+    fields: Option<Vec<String>>,
+    include_reference_length: Option<bool>,
+    include_query_length: Option<bool>,
+    emit_ref_as_index: Option<bool>,
+    coordinate_system: Option<&str>,
+    unsigned_positions: Option<bool>,
+    include_sample: Option<bool>,
+    include_clip_lengths: Option<bool>,
+    chrom_alias: Option<ChromAliasArg>,
+    decode_flags: Option<bool>,
+    require_sorted: Option<bool>,
+    sample_fraction: Option<f64>,
+    sample_count: Option<usize>,
+    sample_seed: Option<u64>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = BamOptions {
+        tags,
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        fields,
+        include_reference_length,
+        include_query_length,
+        emit_ref_as_index,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        unsigned_positions,
+        include_sample,
+        include_clip_lengths,
+        emit_offset: None,
+        chrom_alias: chrom_alias.map(|alias| alias.0),
+        require_sorted,
+        sample_fraction,
+        sample_count,
+        sample_seed,
+        methylation_reference: None,
+        decode_flags,
+    };
+    let query_mode = parse_query_mode(query_mode)?;
+    let reader = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        SamReader::new_from_path(string_ref.to_string_lossy().as_ref())?
+    } else {
+        // Otherwise, treat it as file-like. Without a path there is no index to query.
+        let file_like = stream_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        SamReader::new(decompress(file_like)?)?
+    };
+    let mut reader = reader
+        .with_options(options)
+        .with_query_mode(query_mode)
+        .with_linear_scan(allow_linear_scan);
+    reader
+        .records_to_ipc(region)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, index=None, info_fields=None, genotype_fields=None, samples=None, regions=None, threads=None, limit=None, emit_ref_as_index=None, coordinate_system=None, unsigned_positions=None, expand_gvcf_blocks=None, include_sv_fields=None, include_breakend_mates=None, split_ids=None, split_filters=None, split_multiallelic=None, emit_offset=None, chrom_alias=None, require_sorted=None, sample_fraction=None, sample_count=None, sample_seed=None, progress=None, progress_interval=None, cancel=None, fields=None))]
+fn read_vcf(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    index: Option<PyObject>,
+    info_fields: Option<Vec<String>>,
+    genotype_fields: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    regions: Option<Vec<RegionArg>>,
+    threads: Option<usize>,
+    limit: Option<usize>,
+    emit_ref_as_index: Option<bool>,
+    coordinate_system: Option<&str>,
+    unsigned_positions: Option<bool>,
+    expand_gvcf_blocks: Option<bool>,
+    include_sv_fields: Option<bool>,
+    include_breakend_mates: Option<bool>,
+    split_ids: Option<bool>,
+    split_filters: Option<bool>,
+    split_multiallelic: Option<bool>,
+    emit_offset: Option<bool>,
+    chrom_alias: Option<ChromAliasArg>,
+    require_sorted: Option<bool>,
+    sample_fraction: Option<f64>,
+    sample_count: Option<usize>,
+    sample_seed: Option<u64>,
+    progress: Option<PyObject>,
+    progress_interval: Option<usize>,
+    cancel: Option<PyObject>,
+    fields: Option<Vec<String>>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let regions = regions.map(RegionArg::into_strings);
+    let query = Query::new(region, regions, threads)?;
+    let hooks = ScanHooks::new(progress, progress_interval, cancel);
+    let options = VcfOptions {
+        info_fields,
+        genotype_fields,
+        samples,
+        emit_ref_as_index,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        unsigned_positions,
+        expand_gvcf_blocks,
+        include_sv_fields,
+        include_breakend_mates,
+        split_ids,
+        split_filters,
+        split_multiallelic,
+        emit_offset,
+        chrom_alias: chrom_alias.map(|alias| alias.0),
+        require_sorted,
+        sample_fraction,
+        sample_count,
+        sample_seed,
+        fields,
+    };
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let path = string_ref.to_string_lossy();
+        let mut reader = match index {
+            Some(index) => {
+                let index = read_index(py, index, vcf::index_from_reader)?;
+                VcfReader::new_from_path_with_index(&path, index)?
+            }
+            None => VcfReader::new_from_path(&path)?,
+        }
+        .with_options(options);
+        if let Some(limit) = limit {
+            reader = reader.with_limit(limit);
         }
+        if let (Query::Region(None), Some(threads)) = (&query, threads) {
+            reader = reader.with_threads(threads);
+        }
+        run_scan!(reader, query, hooks)
     } else {
         // Otherwise, treat it as file-like
+        let Some(index) = index else {
+            // Without an index, read it as a stream, e.g. from a pipe, which can only be scanned
+            // as a whole.
+            let file_like = stream_file_like(path_or_file_like)?;
+            let mut reader = VcfReader::new_from_stream(file_like)?.with_options(options);
+            if let Some(limit) = limit {
+                reader = reader.with_limit(limit);
+            }
+            return run_scan!(reader, query, hooks);
+        };
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let mut reader = BigWigReader::new(file_like).unwrap();
-        match zoom_level {
-            Some(zoom_level) => reader
-                .zoom_records_to_ipc(region, zoom_level, zoom_summary_columns_ref)
-                .unwrap(),
-            None => reader.records_to_ipc(region).unwrap(),
+        let index = read_index(py, index, vcf::index_from_reader)?;
+        let mut reader = VcfReader::new(file_like, index)?.with_options(options);
+        if let Some(limit) = limit {
+            reader = reader.with_limit(limit);
         }
+        if let (Query::Region(None), Some(threads)) = (&query, threads) {
+            reader = reader.with_threads(threads);
+        }
+        run_scan!(reader, query, hooks)
     }
 }
 
 #[pyfunction]
-#[pyo3(signature = (path_or_file_like, region=None, fields=None))]
-fn read_bigbed(
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, pos_lo, pos_hi, index=None, info_fields=None, genotype_fields=None, samples=None, emit_offset=None))]
+fn read_vcf_vpos(
     py: Python,
     path_or_file_like: PyObject,
-    region: Option<&str>,
-    fields: Option<HashSet<String>>,
-) -> Vec<u8> {
-    let fields_ref = fields
-        .as_ref()
-        .map(|h| h.iter().map(String::as_str).collect::<HashSet<&str>>());
+    pos_lo: (u64, u16),
+    pos_hi: (u64, u16),
+    index: Option<PyObject>,
+    info_fields: Option<Vec<String>>,
+    genotype_fields: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    emit_offset: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let options = VcfOptions {
+        info_fields,
+        genotype_fields,
+        samples,
+        emit_offset,
+        ..Default::default()
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
         let mut reader =
-            BigBedReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc(region, fields_ref).unwrap()
+            VcfReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        reader
+            .records_to_ipc_from_vpos(pos_lo, pos_hi)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let mut reader = BigBedReader::new(file_like).unwrap();
-        reader.records_to_ipc(region, fields_ref).unwrap()
+        let index = read_index(py, require_index(index)?, vcf::index_from_reader)?;
+        let mut reader = VcfReader::new(file_like, index)?.with_options(options);
+        reader
+            .records_to_ipc_from_vpos(pos_lo, pos_hi)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
 #[pyfunction]
-fn read_gff(py: Python, path_or_file_like: PyObject) -> Vec<u8> {
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, vpos, index=None, info_fields=None, genotype_fields=None, samples=None, emit_offset=None))]
+fn read_vcf_from(
+    py: Python,
+    path_or_file_like: PyObject,
+    vpos: u64,
+    index: Option<PyObject>,
+    info_fields: Option<Vec<String>>,
+    genotype_fields: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    emit_offset: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let options = VcfOptions {
+        info_fields,
+        genotype_fields,
+        samples,
+        emit_offset,
+        ..Default::default()
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
-        let mut reader = GffReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc().unwrap()
+        let mut reader =
+            VcfReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        reader
+            .scan_from(vpos)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let mut reader = GffReader::new(file_like).unwrap();
-        reader.records_to_ipc().unwrap()
+        let index = read_index(py, require_index(index)?, vcf::index_from_reader)?;
+        let mut reader = VcfReader::new(file_like, index)?.with_options(options);
+        reader
+            .scan_from(vpos)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
 #[pyfunction]
-fn read_gtf(py: Python, path_or_file_like: PyObject) -> Vec<u8> {
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, index=None, info_fields=None, genotype_fields=None, samples=None, emit_ref_as_index=None, coordinate_system=None, unsigned_positions=None, expand_gvcf_blocks=None, include_sv_fields=None, include_breakend_mates=None, split_ids=None, split_filters=None, split_multiallelic=None, emit_offset=None, chrom_alias=None, require_sorted=None, sample_fraction=None, sample_count=None, sample_seed=None, fields=None))]
+fn read_bcf(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    index: Option<PyObject>,
+    info_fields: Option<Vec<String>>,
+    genotype_fields: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    emit_ref_as_index: Option<bool>,
+    coordinate_system: Option<&str>,
+    unsigned_positions: Option<bool>,
+    expand_gvcf_blocks: Option<bool>,
+    include_sv_fields: Option<bool>,
+    include_breakend_mates: Option<bool>,
+    split_ids: Option<bool>,
+    split_filters: Option<bool>,
+    split_multiallelic: Option<bool>,
+    emit_offset: Option<bool>,
+    chrom_alias: Option<ChromAliasArg>,
+    require_sorted: Option<bool>,
+    sample_fraction: Option<f64>,
+    sample_count: Option<usize>,
+    sample_seed: Option<u64>,
+    fields: Option<Vec<String>>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = VcfOptions {
+        info_fields,
+        genotype_fields,
+        samples,
+        emit_ref_as_index,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        unsigned_positions,
+        expand_gvcf_blocks,
+        include_sv_fields,
+        include_breakend_mates,
+        split_ids,
+        split_filters,
+        split_multiallelic,
+        emit_offset,
+        chrom_alias: chrom_alias.map(|alias| alias.0),
+        require_sorted,
+        sample_fraction,
+        sample_count,
+        sample_seed,
+        fields,
+    };
     if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
         // If it's a string, treat it as a path
-        let mut reader = GtfReader::new_from_path(string_ref.to_string_lossy().as_ref()).unwrap();
-        reader.records_to_ipc().unwrap()
+        let path = string_ref.to_string_lossy();
+        let mut reader = match index {
+            Some(index) => {
+                let index = read_index(py, index, bcf::index_from_reader)?;
+                BcfReader::new_from_path_with_index(&path, index)?
+            }
+            None => BcfReader::new_from_path(&path)?,
+        }
+        .with_options(options);
+        reader
+            .records_to_ipc(region)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     } else {
         // Otherwise, treat it as file-like
         let file_like = buffered_file_like(path_or_file_like)
             .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
-        let mut reader = GtfReader::new(file_like).unwrap();
-        reader.records_to_ipc().unwrap()
+        let index = read_index(py, require_index(index)?, bcf::index_from_reader)?;
+        let mut reader = BcfReader::new(file_like, index)?.with_options(options);
+        reader
+            .records_to_ipc(region)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
     }
 }
 
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, pos_lo, pos_hi, index=None, info_fields=None, genotype_fields=None, samples=None, emit_offset=None))]
+fn read_bcf_vpos(
+    py: Python,
+    path_or_file_like: PyObject,
+    pos_lo: (u64, u16),
+    pos_hi: (u64, u16),
+    index: Option<PyObject>,
+    info_fields: Option<Vec<String>>,
+    genotype_fields: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    emit_offset: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let options = VcfOptions {
+        info_fields,
+        genotype_fields,
+        samples,
+        emit_offset,
+        ..Default::default()
+    };
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader =
+            BcfReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        reader
+            .records_to_ipc_from_vpos(pos_lo, pos_hi)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let index = read_index(py, require_index(index)?, bcf::index_from_reader)?;
+        let mut reader = BcfReader::new(file_like, index)?.with_options(options);
+        reader
+            .records_to_ipc_from_vpos(pos_lo, pos_hi)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, vpos, index=None, info_fields=None, genotype_fields=None, samples=None, emit_offset=None))]
+fn read_bcf_from(
+    py: Python,
+    path_or_file_like: PyObject,
+    vpos: u64,
+    index: Option<PyObject>,
+    info_fields: Option<Vec<String>>,
+    genotype_fields: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    emit_offset: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let options = VcfOptions {
+        info_fields,
+        genotype_fields,
+        samples,
+        emit_offset,
+        ..Default::default()
+    };
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader =
+            BcfReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        reader
+            .scan_from(vpos)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let index = read_index(py, require_index(index)?, bcf::index_from_reader)?;
+        let mut reader = BcfReader::new(file_like, index)?.with_options(options);
+        reader
+            .scan_from(vpos)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, region=None, zoom_level=None, zoom_summary_columns=None))]
+fn read_bigwig(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    zoom_level: Option<u32>,
+    zoom_summary_columns: Option<HashSet<String>>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let zoom_summary_columns_ref = zoom_summary_columns
+        .as_ref()
+        .map(|h| h.iter().map(String::as_str).collect::<HashSet<&str>>());
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader = BigWigReader::new_from_path(string_ref.to_string_lossy().as_ref())?;
+        match zoom_level {
+            Some(zoom_level) => {
+                reader.zoom_records_to_ipc(region, zoom_level, zoom_summary_columns_ref)
+            }
+            None => reader.records_to_ipc(region),
+        }
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let mut reader = BigWigReader::new(file_like)?;
+        match zoom_level {
+            Some(zoom_level) => {
+                reader.zoom_records_to_ipc(region, zoom_level, zoom_summary_columns_ref)
+            }
+            None => reader.records_to_ipc(region),
+        }
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, region=None, fields=None))]
+fn read_bigbed(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    fields: Option<HashSet<String>>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let fields_ref = fields
+        .as_ref()
+        .map(|h| h.iter().map(String::as_str).collect::<HashSet<&str>>());
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader = BigBedReader::new_from_path(string_ref.to_string_lossy().as_ref())?;
+        reader
+            .records_to_ipc(region, fields_ref)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let mut reader = BigBedReader::new(file_like)?;
+        reader
+            .records_to_ipc(region, fields_ref)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+/// Resolves the attribute definitions requested from Python.
+///
+/// Explicit `(name, type)` pairs take precedence. Otherwise, if `scan_attributes` is set, the
+/// attributes are discovered by `scan`.
+fn attribute_defs<F>(
+    attributes: Option<Vec<(String, String)>>,
+    scan_attributes: bool,
+    attribute_defs: Option<Vec<(String, String)>>,
+    scan: F,
+) -> PyResult<Option<Vec<AttributeDef>>>
+where
+    F: FnOnce(Vec<AttributeDef>) -> PyResult<Vec<AttributeDef>>,
+{
+    let parse = |pairs: Vec<(String, String)>| {
+        pairs
+            .into_iter()
+            .map(AttributeDef::try_from)
+            .collect::<std::io::Result<Vec<_>>>()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    };
+    if let Some(attributes) = attributes {
+        parse(attributes).map(Some)
+    } else if scan_attributes || attribute_defs.is_some() {
+        // Explicit definitions override the scanned types of their attributes.
+        scan(parse(attribute_defs.unwrap_or_default())?).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, names=None, min_score=None, max_score=None, bed_fields=None, query_mode=None, dictionary_encode=None, coordinate_system=None, missing_as_empty=None, on_error=None, require_sorted=None, sample_fraction=None, sample_count=None, sample_seed=None, fields=None, wide_positions=None))]
+fn read_bed(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    names: Option<Vec<String>>,
+    min_score: Option<i64>,
+    max_score: Option<i64>,
+    bed_fields: Option<usize>,
+    query_mode: Option<&str>,
+    dictionary_encode: Option<Vec<String>>,
+    coordinate_system: Option<&str>,
+    missing_as_empty: Option<bool>,
+    on_error: Option<&str>,
+    require_sorted: Option<bool>,
+    sample_fraction: Option<f64>,
+    sample_count: Option<usize>,
+    sample_seed: Option<u64>,
+    fields: Option<Vec<String>>,
+    wide_positions: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = BedOptions {
+        names,
+        min_score,
+        max_score,
+        bed_fields,
+        dictionary_encode,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        missing_as_empty,
+        on_error: Some(parse_error_policy(on_error)?),
+        require_sorted,
+        sample_fraction,
+        sample_count,
+        sample_seed,
+        fields,
+        wide_positions,
+    };
+    let query_mode = parse_query_mode(query_mode)?;
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader = BedReader::new_from_path(string_ref.to_string_lossy().as_ref())?
+            .with_options(options)
+            .with_query_mode(query_mode);
+        let ipc = reader
+            .records_to_ipc(region)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        warn_skipped(py, reader.errors())?;
+        Ok(ipc)
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = stream_file_like(path_or_file_like)?;
+        let mut reader = BedReader::new(file_like)?
+            .with_options(options)
+            .with_query_mode(query_mode);
+        let ipc = reader
+            .records_to_ipc(region)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        warn_skipped(py, reader.errors())?;
+        Ok(ipc)
+    }
+}
+
+/// Returns the sorted, distinct chromosome names of a BED file.
+#[pyfunction]
+fn chroms_bed(path: &str) -> PyResult<Vec<String>> {
+    Ok(BedReader::new_from_path(path)?.chroms()?)
+}
+
+#[pyfunction]
+fn read_bedgraph(py: Python, path_or_file_like: PyObject) -> PyResult<Vec<u8>> {
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader = BedGraphReader::new_from_path(string_ref.to_string_lossy().as_ref())?;
+        reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        let mut reader = BedGraphReader::new(file_like)?;
+        reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+#[pyfunction]
+fn read_wig(py: Python, path_or_file_like: PyObject) -> PyResult<Vec<u8>> {
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader = WigReader::new_from_path(string_ref.to_string_lossy().as_ref())?;
+        reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        let mut reader = WigReader::new(file_like)?;
+        reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, include_quality=None))]
+fn read_maf(
+    py: Python,
+    path_or_file_like: PyObject,
+    include_quality: Option<bool>,
+) -> PyResult<Vec<u8>> {
+    let options = MafOptions { include_quality };
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader =
+            MafReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        let mut reader = MafReader::new(file_like)?.with_options(options);
+        reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+/// Reads the sequences of a UCSC .2bit file, whole or in the given regions, with their N-blocks
+/// and soft-masking restored.
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, region=None, regions=None))]
+fn read_2bit(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    regions: Option<Vec<RegionArg>>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let regions = regions.map(RegionArg::into_strings);
+    let regions: Option<Vec<String>> = match (region, regions) {
+        (Some(_), Some(_)) => {
+            return Err(PyValueError::new_err(
+                "Only one of `region` and `regions` may be given.",
+            ))
+        }
+        (Some(region), None) => Some(vec![region.to_string()]),
+        (None, regions) => regions,
+    };
+    let regions: Option<Vec<&str>> = regions
+        .as_ref()
+        .map(|regions| regions.iter().map(String::as_str).collect());
+    let ipc = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader = TwoBitReader::new_from_path(string_ref.to_string_lossy().as_ref())?;
+        match regions {
+            Some(regions) => reader.regions_to_ipc(&regions),
+            None => reader.records_to_ipc(None),
+        }
+    } else {
+        // Otherwise, treat it as file-like
+        let mut reader = TwoBitReader::new(buffered_file_like(path_or_file_like)?)?;
+        match regions {
+            Some(regions) => reader.regions_to_ipc(&regions),
+            None => reader.records_to_ipc(None),
+        }
+    };
+    ipc.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, tags=None, dictionary_encode=None, on_error=None))]
+fn read_paf(
+    py: Python,
+    path_or_file_like: PyObject,
+    tags: Option<Vec<String>>,
+    dictionary_encode: Option<Vec<String>>,
+    on_error: Option<&str>,
+) -> PyResult<Vec<u8>> {
+    let options = PafOptions {
+        tags,
+        dictionary_encode,
+        on_error: Some(parse_error_policy(on_error)?),
+    };
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let mut reader =
+            PafReader::new_from_path(string_ref.to_string_lossy().as_ref())?.with_options(options);
+        let ipc = reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        warn_skipped(py, reader.errors())?;
+        Ok(ipc)
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        let mut reader = PafReader::new(decompress(file_like)?)?.with_options(options);
+        let ipc = reader
+            .records_to_ipc()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        warn_skipped(py, reader.errors())?;
+        Ok(ipc)
+    }
+}
+
+/// Reads narrowPeak or broadPeak records from a path or a file-like object.
+fn read_peaks(
+    py: Python,
+    format: PeakFormat,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    query_mode: Option<&str>,
+    coordinate_system: Option<&str>,
+    on_error: Option<&str>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = PeakOptions {
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        on_error: Some(parse_error_policy(on_error)?),
+    };
+    let query_mode = parse_query_mode(query_mode)?;
+    let mut reader = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        PeakReader::new_from_path(string_ref.to_string_lossy().as_ref(), format)?
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        PeakReader::new(decompress(file_like)?, format)?
+    }
+    .with_options(options)
+    .with_query_mode(query_mode);
+    let ipc = reader
+        .records_to_ipc(region)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    warn_skipped(py, reader.errors())?;
+    Ok(ipc)
+}
+
+/// Reads narrowPeak (BED6+4) records, e.g. MACS2 peak calls.
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, region=None, query_mode=None, coordinate_system=None, on_error=None))]
+fn read_narrowpeak(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    query_mode: Option<&str>,
+    coordinate_system: Option<&str>,
+    on_error: Option<&str>,
+) -> PyResult<Vec<u8>> {
+    read_peaks(
+        py,
+        PeakFormat::Narrow,
+        path_or_file_like,
+        region,
+        query_mode,
+        coordinate_system,
+        on_error,
+    )
+}
+
+/// Reads broadPeak (BED6+3) records, e.g. MACS2 broad peak calls.
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, region=None, query_mode=None, coordinate_system=None, on_error=None))]
+fn read_broadpeak(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    query_mode: Option<&str>,
+    coordinate_system: Option<&str>,
+    on_error: Option<&str>,
+) -> PyResult<Vec<u8>> {
+    read_peaks(
+        py,
+        PeakFormat::Broad,
+        path_or_file_like,
+        region,
+        query_mode,
+        coordinate_system,
+        on_error,
+    )
+}
+
+/// Reads a tab-separated table with the given `(name, type)` columns, e.g. `("score",
+/// "Float64")`, from a path or a file-like object.
+///
+/// Regions are queried by the `chrom_column`, `start_column` and `end_column` indices, through a
+/// tabix or CSI index next to a BGZF-compressed path, or else by a linear pass.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, columns, region=None, chrom_column=None, start_column=None, end_column=None, coordinate_system=None, skip_header=None, query_mode=None, batch_size=None, batch_bytes=None, on_error=None))]
+fn read_tsv(
+    py: Python,
+    path_or_file_like: PyObject,
+    columns: Vec<(String, String)>,
+    region: Option<RegionArg>,
+    chrom_column: Option<usize>,
+    start_column: Option<usize>,
+    end_column: Option<usize>,
+    coordinate_system: Option<&str>,
+    skip_header: Option<bool>,
+    query_mode: Option<&str>,
+    batch_size: Option<usize>,
+    batch_bytes: Option<usize>,
+    on_error: Option<&str>,
+) -> PyResult<Vec<u8>> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let columns = columns
+        .into_iter()
+        .map(Column::try_from)
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let options = TsvOptions {
+        chrom_column,
+        start_column,
+        end_column,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        skip_header,
+        on_error: Some(parse_error_policy(on_error)?),
+    };
+    let query_mode = parse_query_mode(query_mode)?;
+    let batch_limit = match (batch_size, batch_bytes) {
+        (Some(_), Some(_)) => {
+            return Err(PyValueError::new_err(
+                "Only one of `batch_size` and `batch_bytes` may be given.",
+            ))
+        }
+        (Some(n), None) => Some(BatchLimit::Records(n)),
+        (None, Some(n)) => Some(BatchLimit::Bytes(n)),
+        (None, None) => None,
+    };
+    let mut reader = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        TsvReader::new_from_path(string_ref.to_string_lossy().as_ref(), columns)?
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        TsvReader::new(decompress(file_like)?, columns)?
+    }
+    .with_options(options)
+    .with_query_mode(query_mode);
+    if let Some(limit) = batch_limit {
+        reader = reader.with_batch_limit(limit);
+    }
+    let ipc = reader
+        .records_to_ipc(region)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    warn_skipped(py, reader.errors())?;
+    Ok(ipc)
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, attributes=None, scan_attributes=false, parent_ids=false, dictionary_encode=None, coordinate_system=None, missing_as_empty=false, infer_attribute_types=false, attribute_defs=None, on_error=None, percent_decode=true, feature_types=None, feature_types_ignore_case=false, fields=None))]
+#[allow(clippy::too_many_arguments)]
+fn read_gff(
+    py: Python,
+    path_or_file_like: PyObject,
+    attributes: Option<Vec<(String, String)>>,
+    scan_attributes: bool,
+    parent_ids: bool,
+    dictionary_encode: Option<Vec<String>>,
+    coordinate_system: Option<&str>,
+    missing_as_empty: bool,
+    infer_attribute_types: bool,
+    attribute_defs: Option<Vec<(String, String)>>,
+    on_error: Option<&str>,
+    percent_decode: bool,
+    feature_types: Option<Vec<String>>,
+    feature_types_ignore_case: bool,
+    fields: Option<Vec<String>>,
+) -> PyResult<Vec<u8>> {
+    let options = GffOptions {
+        parent_ids: Some(parent_ids),
+        dictionary_encode,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        missing_as_empty: Some(missing_as_empty),
+        infer_attribute_types: Some(infer_attribute_types),
+        feature_types,
+        ignore_feature_type_case: Some(feature_types_ignore_case),
+        on_error: Some(parse_error_policy(on_error)?),
+        percent_decode: Some(percent_decode),
+        fields,
+        ..Default::default()
+    };
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let path = string_ref.to_string_lossy();
+        let defs =
+            self::attribute_defs(attributes, scan_attributes, attribute_defs, |overrides| {
+                Ok(GffReader::new_from_path(path.as_ref())?
+                    .with_options(options.clone())
+                    .with_attribute_type_overrides(overrides)
+                    .scan_attribute_defs()?)
+            })?;
+        let mut reader = GffReader::new_from_path(path.as_ref())?.with_options(options);
+        let ipc = reader
+            .records_to_ipc(defs.as_deref())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        warn_skipped(py, reader.errors())?;
+        Ok(ipc)
+    } else {
+        // Otherwise, treat it as file-like
+        let defs =
+            self::attribute_defs(attributes, scan_attributes, attribute_defs, |overrides| {
+                // Scan in a first pass, then rewind for the second.
+                let file_like = buffered_file_like(path_or_file_like.clone_ref(py))?;
+                let defs = GffReader::new(file_like)?
+                    .with_options(options.clone())
+                    .with_attribute_type_overrides(overrides)
+                    .scan_attribute_defs()?;
+                path_or_file_like.call_method1(py, "seek", (0,))?;
+                Ok(defs)
+            })?;
+        let file_like = stream_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let mut reader = GffReader::new(file_like)?.with_options(options);
+        let ipc = reader
+            .records_to_ipc(defs.as_deref())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        warn_skipped(py, reader.errors())?;
+        Ok(ipc)
+    }
+}
+
+/// Returns the sorted, distinct sequence names of a GFF file.
+#[pyfunction]
+fn chroms_gff(path: &str) -> PyResult<Vec<String>> {
+    Ok(GffReader::new_from_path(path)?.chroms()?)
+}
+
+/// Reads the sequences of the `##FASTA` section of a GFF file as FASTA records.
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like))]
+fn read_gff_fasta(py: Python, path_or_file_like: PyObject) -> PyResult<Vec<u8>> {
+    let fasta = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        GffReader::new_from_path(string_ref.to_string_lossy().as_ref())?.embedded_fasta()?
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        GffReader::new(file_like)?.embedded_fasta()?
+    };
+    let mut fasta = fasta.ok_or_else(|| PyValueError::new_err("No ##FASTA section"))?;
+    fasta
+        .records_to_ipc(None)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, attributes=None, scan_attributes=false, parent_ids=false, dictionary_encode=None, coordinate_system=None, missing_as_empty=false, infer_attribute_types=false, attribute_defs=None, on_error=None, feature_types=None, feature_types_ignore_case=false, duplicate_attributes=None, fields=None))]
+#[allow(clippy::too_many_arguments)]
+fn read_gtf(
+    py: Python,
+    path_or_file_like: PyObject,
+    attributes: Option<Vec<(String, String)>>,
+    scan_attributes: bool,
+    parent_ids: bool,
+    dictionary_encode: Option<Vec<String>>,
+    coordinate_system: Option<&str>,
+    missing_as_empty: bool,
+    infer_attribute_types: bool,
+    attribute_defs: Option<Vec<(String, String)>>,
+    on_error: Option<&str>,
+    feature_types: Option<Vec<String>>,
+    feature_types_ignore_case: bool,
+    duplicate_attributes: Option<&str>,
+    fields: Option<Vec<String>>,
+) -> PyResult<Vec<u8>> {
+    let duplicate_attributes = duplicate_attributes
+        .map(str::parse::<DuplicateAttributes>)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?
+        .unwrap_or_default();
+    let options = GtfOptions {
+        parent_ids: Some(parent_ids),
+        dictionary_encode,
+        coordinate_system: parse_coordinate_system(coordinate_system)?,
+        missing_as_empty: Some(missing_as_empty),
+        infer_attribute_types: Some(infer_attribute_types),
+        feature_types,
+        ignore_feature_type_case: Some(feature_types_ignore_case),
+        on_error: Some(parse_error_policy(on_error)?),
+        duplicate_attributes: Some(duplicate_attributes),
+        fields,
+        ..Default::default()
+    };
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let path = string_ref.to_string_lossy();
+        let defs =
+            self::attribute_defs(attributes, scan_attributes, attribute_defs, |overrides| {
+                Ok(GtfReader::new_from_path(path.as_ref())?
+                    .with_options(options.clone())
+                    .with_attribute_type_overrides(overrides)
+                    .scan_attribute_defs()?)
+            })?;
+        let mut reader = GtfReader::new_from_path(path.as_ref())?.with_options(options);
+        let ipc = reader
+            .records_to_ipc(defs.as_deref())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        warn_skipped(py, reader.errors())?;
+        Ok(ipc)
+    } else {
+        // Otherwise, treat it as file-like
+        let defs =
+            self::attribute_defs(attributes, scan_attributes, attribute_defs, |overrides| {
+                // Scan in a first pass, then rewind for the second.
+                let file_like = buffered_file_like(path_or_file_like.clone_ref(py))?;
+                let defs = GtfReader::new(file_like)?
+                    .with_options(options.clone())
+                    .with_attribute_type_overrides(overrides)
+                    .scan_attribute_defs()?;
+                path_or_file_like.call_method1(py, "seek", (0,))?;
+                Ok(defs)
+            })?;
+        let file_like = stream_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let mut reader = GtfReader::new(file_like)?.with_options(options);
+        let ipc = reader
+            .records_to_ipc(defs.as_deref())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        warn_skipped(py, reader.errors())?;
+        Ok(ipc)
+    }
+}
+
+/// Returns the sorted, distinct sequence names of a GTF file.
+#[pyfunction]
+fn chroms_gtf(path: &str) -> PyResult<Vec<String>> {
+    Ok(GtfReader::new_from_path(path)?.chroms()?)
+}
+
+/// Converts fields encoded as an Arrow IPC stream, e.g. by `oxbow::io::fields_to_ipc`, into
+/// `(name, pyarrow type)` pairs. Requires `pyarrow`.
+fn py_fields<E: ToString>(
+    py: Python,
+    stream: Result<Vec<u8>, E>,
+) -> PyResult<Vec<(String, PyObject)>> {
+    let stream = stream.map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let schema = open_ipc_stream(py, &stream)?.getattr("schema")?;
+    schema
+        .try_iter()?
+        .map(|field| {
+            let field = field?;
+            Ok((
+                field.getattr("name")?.extract()?,
+                field.getattr("type")?.unbind(),
+            ))
+        })
+        .collect()
+}
+
+/// Returns the fields of `read_bam` from the header of a BAM file, without its index. Tag columns
+/// are typed from the first `sample_size` records.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path, tags=None, fields=None, include_reference_length=None, include_query_length=None, emit_ref_as_index=None, sample_size=0))]
+fn fields_bam(
+    py: Python,
+    path: &str,
+    tags: Option<Vec<String>>,
+    fields: Option<Vec<String>>,
+    include_reference_length: Option<bool>,
+    include_query_length: Option<bool>,
+    emit_ref_as_index: Option<bool>,
+    sample_size: usize,
+) -> PyResult<Vec<(String, PyObject)>> {
+    let options = BamOptions {
+        tags,
+        fields,
+        include_reference_length,
+        include_query_length,
+        emit_ref_as_index,
+        ..Default::default()
+    };
+    let schema = bam::peek_schema(path, &options, sample_size);
+    py_fields(
+        py,
+        schema.and_then(|schema| oxbow::io::fields_to_ipc(schema.fields().clone())),
+    )
+}
+
+#[pyfunction]
+fn read_vcf_header(path: &str) -> PyResult<String> {
+    Ok(VcfReader::new_from_path(path)?.header_text()?)
+}
+
+#[pyfunction]
+fn read_bcf_header(path: &str) -> PyResult<String> {
+    Ok(BcfReader::new_from_path(path)?.header_text()?)
+}
+
+#[pyfunction]
+fn reference_lengths_vcf(py: Python, path: &str) -> PyResult<PyObject> {
+    lengths_dict(py, VcfReader::new_from_path(path)?.reference_lengths())
+}
+
+#[pyfunction]
+fn reference_lengths_bcf(py: Python, path: &str) -> PyResult<PyObject> {
+    lengths_dict(py, BcfReader::new_from_path(path)?.reference_lengths())
+}
+
+#[pyfunction]
+fn reference_lengths_fasta(py: Python, path: &str) -> PyResult<PyObject> {
+    lengths_dict(py, FastaReader::new(path)?.reference_lengths())
+}
+
+#[pyfunction]
+fn write_vcf(path: &str, ipc: &[u8], header: &str) -> PyResult<()> {
+    let header = vcf::header_from_str(header)?;
+    let file = std::fs::File::create(path)?;
+    oxbow::io::ipc_reader(ipc)
+        .and_then(|batches| vcf::write_vcf(file, &header, batches))
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+fn write_bcf(path: &str, ipc: &[u8], header: &str) -> PyResult<()> {
+    let header = vcf::header_from_str(header)?;
+    let file = std::fs::File::create(path)?;
+    oxbow::io::ipc_reader(ipc)
+        .and_then(|batches| vcf::write_bcf(file, &header, batches))
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Returns the fields of `read_vcf` from the header of a VCF file, without its index.
+#[pyfunction]
+#[pyo3(signature = (path, info_fields=None, genotype_fields=None, samples=None, emit_ref_as_index=None))]
+fn fields_vcf(
+    py: Python,
+    path: &str,
+    info_fields: Option<Vec<String>>,
+    genotype_fields: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    emit_ref_as_index: Option<bool>,
+) -> PyResult<Vec<(String, PyObject)>> {
+    let options = VcfOptions {
+        info_fields,
+        genotype_fields,
+        samples,
+        emit_ref_as_index,
+        ..Default::default()
+    };
+    let schema = vcf::peek_schema(path, &options);
+    py_fields(
+        py,
+        schema.and_then(|schema| oxbow::io::fields_to_ipc(schema.fields().clone())),
+    )
+}
+
+/// Returns the fields of `read_bcf` from the header of a BCF file, without its index.
+#[pyfunction]
+#[pyo3(signature = (path, info_fields=None, genotype_fields=None, samples=None, emit_ref_as_index=None))]
+fn fields_bcf(
+    py: Python,
+    path: &str,
+    info_fields: Option<Vec<String>>,
+    genotype_fields: Option<Vec<String>>,
+    samples: Option<Vec<String>>,
+    emit_ref_as_index: Option<bool>,
+) -> PyResult<Vec<(String, PyObject)>> {
+    let options = VcfOptions {
+        info_fields,
+        genotype_fields,
+        samples,
+        emit_ref_as_index,
+        ..Default::default()
+    };
+    let schema = bcf::peek_schema(path, &options);
+    py_fields(
+        py,
+        schema.and_then(|schema| oxbow::io::fields_to_ipc(schema.fields().clone())),
+    )
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, bed_fields=None, dictionary_encode=None, wide_positions=None))]
+fn fields_bed(
+    py: Python,
+    path: &str,
+    bed_fields: Option<usize>,
+    dictionary_encode: Option<Vec<String>>,
+    wide_positions: Option<bool>,
+) -> PyResult<Vec<(String, PyObject)>> {
+    let options = BedOptions {
+        bed_fields,
+        dictionary_encode,
+        wide_positions,
+        ..Default::default()
+    };
+    let reader = BedReader::new_from_path(path)?.with_options(options);
+    py_fields(py, reader.fields().and_then(oxbow::io::fields_to_ipc))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, attributes=None, scan_attributes=false, parent_ids=false, dictionary_encode=None, infer_attribute_types=false, attribute_defs=None))]
+#[allow(clippy::too_many_arguments)]
+fn fields_gff(
+    py: Python,
+    path: &str,
+    attributes: Option<Vec<(String, String)>>,
+    scan_attributes: bool,
+    parent_ids: bool,
+    dictionary_encode: Option<Vec<String>>,
+    infer_attribute_types: bool,
+    attribute_defs: Option<Vec<(String, String)>>,
+) -> PyResult<Vec<(String, PyObject)>> {
+    let defs = self::attribute_defs(attributes, scan_attributes, attribute_defs, |overrides| {
+        Ok(GffReader::new_from_path(path)?
+            .with_attribute_type_inference(infer_attribute_types)
+            .with_attribute_type_overrides(overrides)
+            .scan_attribute_defs()?)
+    })?;
+    let reader = GffReader::new_from_path(path)?
+        .with_parent_ids(parent_ids)
+        .with_dictionary_encode(dictionary_encode);
+    py_fields(
+        py,
+        reader
+            .fields(defs.as_deref())
+            .and_then(oxbow::io::fields_to_ipc),
+    )
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, attributes=None, scan_attributes=false, parent_ids=false, dictionary_encode=None, infer_attribute_types=false, attribute_defs=None))]
+#[allow(clippy::too_many_arguments)]
+fn fields_gtf(
+    py: Python,
+    path: &str,
+    attributes: Option<Vec<(String, String)>>,
+    scan_attributes: bool,
+    parent_ids: bool,
+    dictionary_encode: Option<Vec<String>>,
+    infer_attribute_types: bool,
+    attribute_defs: Option<Vec<(String, String)>>,
+) -> PyResult<Vec<(String, PyObject)>> {
+    let defs = self::attribute_defs(attributes, scan_attributes, attribute_defs, |overrides| {
+        Ok(GtfReader::new_from_path(path)?
+            .with_attribute_type_inference(infer_attribute_types)
+            .with_attribute_type_overrides(overrides)
+            .scan_attribute_defs()?)
+    })?;
+    let reader = GtfReader::new_from_path(path)?
+        .with_parent_ids(parent_ids)
+        .with_dictionary_encode(dictionary_encode);
+    py_fields(
+        py,
+        reader
+            .fields(defs.as_deref())
+            .and_then(oxbow::io::fields_to_ipc),
+    )
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like))]
+fn count_fasta(py: Python, path_or_file_like: PyObject) -> PyResult<usize> {
+    let mut reader = fasta_reader(py, path_or_file_like, None)?;
+    reader
+        .count()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like))]
+fn count_fastq(py: Python, path_or_file_like: PyObject) -> PyResult<usize> {
+    let mut reader = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it like a path
+        FastqReader::new_from_path(string_ref.to_string_lossy().as_ref())?
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        FastqReader::new(decompress(file_like)?)?
+    };
+    reader
+        .count()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, index=None, regions=None, min_mapq=None, include_flags=None, exclude_flags=None, query_mode=None))]
+fn count_bam(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    index: Option<PyObject>,
+    regions: Option<Vec<RegionArg>>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    query_mode: Option<&str>,
+) -> PyResult<Count> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let regions = regions.map(RegionArg::into_strings);
+    let query = Query::new(region, regions, None)?;
+    let query_mode = parse_query_mode(query_mode)?;
+    let options = BamOptions {
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        ..Default::default()
+    };
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let path = string_ref.to_string_lossy();
+        let mut reader = match index {
+            Some(index) => {
+                let index = read_index(py, index, bam::index_from_reader)?;
+                BamReader::new_from_path_with_index(&path, index)?
+            }
+            None => BamReader::new_from_path(&path)?,
+        }
+        .with_options(options)
+        .with_query_mode(query_mode);
+        run_count!(reader, query)
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let index = read_index(py, require_index(index)?, bam::index_from_reader)?;
+        let mut reader = BamReader::new(file_like, index)?
+            .with_options(options)
+            .with_query_mode(query_mode);
+        run_count!(reader, query)
+    }
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, reference=None, region=None, index=None, min_mapq=None, include_flags=None, exclude_flags=None, query_mode=None))]
+fn count_cram(
+    py: Python,
+    path_or_file_like: PyObject,
+    reference: Option<&str>,
+    region: Option<RegionArg>,
+    index: Option<PyObject>,
+    min_mapq: Option<u8>,
+    include_flags: Option<u16>,
+    exclude_flags: Option<u16>,
+    query_mode: Option<&str>,
+) -> PyResult<usize> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = BamOptions {
+        min_mapq,
+        include_flags,
+        exclude_flags,
+        ..Default::default()
+    };
+    let query_mode = parse_query_mode(query_mode)?;
+    let count = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        CramReader::new_from_path(string_ref.to_string_lossy().as_ref(), reference)?
+            .with_options(options)
+            .with_query_mode(query_mode)
+            .count(region)
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let index = index
+            .map(|index| read_index(py, index, cram::index_from_reader))
+            .transpose()?;
+        let repository = reference
+            .map(cram::repository_from_path)
+            .transpose()?
+            .unwrap_or_default();
+        CramReader::new(file_like, index, repository)?
+            .with_options(options)
+            .with_query_mode(query_mode)
+            .count(region)
+    };
+    count.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, region=None, index=None, regions=None))]
+fn count_vcf(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    index: Option<PyObject>,
+    regions: Option<Vec<RegionArg>>,
+) -> PyResult<Count> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let regions = regions.map(RegionArg::into_strings);
+    let query = Query::new(region, regions, None)?;
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let path = string_ref.to_string_lossy();
+        let mut reader = match index {
+            Some(index) => {
+                let index = read_index(py, index, vcf::index_from_reader)?;
+                VcfReader::new_from_path_with_index(&path, index)?
+            }
+            None => VcfReader::new_from_path(&path)?,
+        };
+        run_count!(reader, query)
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let index = read_index(py, require_index(index)?, vcf::index_from_reader)?;
+        let mut reader = VcfReader::new(file_like, index)?;
+        run_count!(reader, query)
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like, region=None, index=None))]
+fn count_bcf(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    index: Option<PyObject>,
+) -> PyResult<usize> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let count = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        let path = string_ref.to_string_lossy();
+        match index {
+            Some(index) => {
+                let index = read_index(py, index, bcf::index_from_reader)?;
+                BcfReader::new_from_path_with_index(&path, index)?.count(region)
+            }
+            None => BcfReader::new_from_path(&path)?.count(region),
+        }
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        let index = read_index(py, require_index(index)?, bcf::index_from_reader)?;
+        BcfReader::new(file_like, index)?.count(region)
+    };
+    count.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (path_or_file_like, region=None, names=None, min_score=None, max_score=None, bed_fields=None, query_mode=None))]
+fn count_bed(
+    py: Python,
+    path_or_file_like: PyObject,
+    region: Option<RegionArg>,
+    names: Option<Vec<String>>,
+    min_score: Option<i64>,
+    max_score: Option<i64>,
+    bed_fields: Option<usize>,
+    query_mode: Option<&str>,
+) -> PyResult<usize> {
+    let region = region.as_ref().map(RegionArg::as_str);
+    let options = BedOptions {
+        names,
+        min_score,
+        max_score,
+        bed_fields,
+        ..Default::default()
+    };
+    let query_mode = parse_query_mode(query_mode)?;
+    let count = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        BedReader::new_from_path(string_ref.to_string_lossy().as_ref())?
+            .with_options(options)
+            .with_query_mode(query_mode)
+            .count(region)
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        BedReader::new(file_like)?
+            .with_options(options)
+            .with_query_mode(query_mode)
+            .count(region)
+    };
+    count.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like))]
+fn count_gff(py: Python, path_or_file_like: PyObject) -> PyResult<usize> {
+    let count = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        GffReader::new_from_path(string_ref.to_string_lossy().as_ref())?.count()
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        GffReader::new(file_like)?.count()
+    };
+    count.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like))]
+fn count_gtf(py: Python, path_or_file_like: PyObject) -> PyResult<usize> {
+    let count = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        GtfReader::new_from_path(string_ref.to_string_lossy().as_ref())?.count()
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)
+            .expect("Unknown argument for `path_url_or_file_like`. Not a file path string or url, and not a file-like object.");
+        GtfReader::new(file_like)?.count()
+    };
+    count.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like))]
+fn count_maf(py: Python, path_or_file_like: PyObject) -> PyResult<usize> {
+    let count = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        MafReader::new_from_path(string_ref.to_string_lossy().as_ref())?.count()
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        MafReader::new(file_like)?.count()
+    };
+    count.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like))]
+fn count_2bit(py: Python, path_or_file_like: PyObject) -> PyResult<usize> {
+    if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        Ok(TwoBitReader::new_from_path(string_ref.to_string_lossy().as_ref())?.count())
+    } else {
+        // Otherwise, treat it as file-like
+        Ok(TwoBitReader::new(buffered_file_like(path_or_file_like)?)?.count())
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (path_or_file_like))]
+fn count_paf(py: Python, path_or_file_like: PyObject) -> PyResult<usize> {
+    let count = if let Ok(string_ref) = path_or_file_like.downcast_bound::<PyString>(py) {
+        // If it's a string, treat it as a path
+        PafReader::new_from_path(string_ref.to_string_lossy().as_ref())?.count()
+    } else {
+        // Otherwise, treat it as file-like
+        let file_like = buffered_file_like(path_or_file_like)?;
+        PafReader::new(decompress(file_like)?)?.count()
+    };
+    count.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Builds a tabix (or, with `csi=True`, CSI) index of a BGZF-compressed BED, GFF or VCF file.
+///
+/// The index is written next to the file. The preset is inferred from the file name when not
+/// given.
+#[pyfunction]
+#[pyo3(signature = (path, preset=None, csi=false))]
+fn index_file(path: &str, preset: Option<&str>, csi: bool) -> PyResult<()> {
+    let preset = preset
+        .map(str::parse::<Preset>)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let result = if csi {
+        write_csi(path, preset)
+    } else {
+        write_tabix(path, preset)
+    };
+    result.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
 #[pymodule]
 #[pyo3(name = "oxbow")]
 fn py_oxbow(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(read_fasta, m)?)?;
     m.add_function(wrap_pyfunction!(read_fastq, m)?)?;
+    m.add_function(wrap_pyfunction!(read_fastq_paired, m)?)?;
+    m.add_function(wrap_pyfunction!(read_fastq_interleaved, m)?)?;
     m.add_function(wrap_pyfunction!(partition_from_index_file, m)?)?;
+    m.add_function(wrap_pyfunction!(write_ipc_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(rename_columns, m)?)?;
+    m.add_function(wrap_pyfunction!(flatten_list_columns, m)?)?;
+    m.add_function(wrap_pyfunction!(iter_batches, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_regions, m)?)?;
+    m.add_class::<RecordBatchIterator>()?;
     m.add_function(wrap_pyfunction!(read_bam, m)?)?;
     m.add_function(wrap_pyfunction!(read_bam_vpos, m)?)?;
-    // m.add_function(wrap_pyfunction!(read_cram, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bam_from, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bam_unmapped, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bam_many, m)?)?;
+    m.add_function(wrap_pyfunction!(read_groups_bam, m)?)?;
+    m.add_function(wrap_pyfunction!(programs_bam, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bam_header, m)?)?;
+    m.add_function(wrap_pyfunction!(reference_lengths_bam, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bam_pileup, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bam_coverage, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bam_pairs, m)?)?;
+    m.add_function(wrap_pyfunction!(read_cram, m)?)?;
+    m.add_function(wrap_pyfunction!(read_sam, m)?)?;
     // m.add_function(wrap_pyfunction!(read_cram_vpos, m)?)?;
     m.add_function(wrap_pyfunction!(read_vcf, m)?)?;
     m.add_function(wrap_pyfunction!(read_vcf_vpos, m)?)?;
+    m.add_function(wrap_pyfunction!(read_vcf_from, m)?)?;
     m.add_function(wrap_pyfunction!(read_bcf, m)?)?;
     m.add_function(wrap_pyfunction!(read_bcf_vpos, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bcf_from, m)?)?;
     m.add_function(wrap_pyfunction!(read_bigwig, m)?)?;
     m.add_function(wrap_pyfunction!(read_bigbed, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bed, m)?)?;
+    m.add_function(wrap_pyfunction!(chroms_bed, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bedgraph, m)?)?;
+    m.add_function(wrap_pyfunction!(read_wig, m)?)?;
+    m.add_function(wrap_pyfunction!(read_maf, m)?)?;
+    m.add_function(wrap_pyfunction!(read_paf, m)?)?;
+    m.add_function(wrap_pyfunction!(read_narrowpeak, m)?)?;
+    m.add_function(wrap_pyfunction!(read_broadpeak, m)?)?;
+    m.add_function(wrap_pyfunction!(read_tsv, m)?)?;
+    m.add_function(wrap_pyfunction!(read_2bit, m)?)?;
     m.add_function(wrap_pyfunction!(read_gff, m)?)?;
+    m.add_function(wrap_pyfunction!(chroms_gff, m)?)?;
+    m.add_function(wrap_pyfunction!(read_gff_fasta, m)?)?;
     m.add_function(wrap_pyfunction!(read_gtf, m)?)?;
+    m.add_function(wrap_pyfunction!(chroms_gtf, m)?)?;
+    m.add_function(wrap_pyfunction!(index_file, m)?)?;
+    m.add_function(wrap_pyfunction!(fields_bam, m)?)?;
+    m.add_function(wrap_pyfunction!(read_vcf_header, m)?)?;
+    m.add_function(wrap_pyfunction!(read_bcf_header, m)?)?;
+    m.add_function(wrap_pyfunction!(reference_lengths_vcf, m)?)?;
+    m.add_function(wrap_pyfunction!(reference_lengths_bcf, m)?)?;
+    m.add_function(wrap_pyfunction!(reference_lengths_fasta, m)?)?;
+    m.add_function(wrap_pyfunction!(write_vcf, m)?)?;
+    m.add_function(wrap_pyfunction!(write_bcf, m)?)?;
+    m.add_function(wrap_pyfunction!(fields_vcf, m)?)?;
+    m.add_function(wrap_pyfunction!(fields_bcf, m)?)?;
+    m.add_function(wrap_pyfunction!(fields_bed, m)?)?;
+    m.add_function(wrap_pyfunction!(fields_gff, m)?)?;
+    m.add_function(wrap_pyfunction!(fields_gtf, m)?)?;
+    m.add_function(wrap_pyfunction!(count_fasta, m)?)?;
+    m.add_function(wrap_pyfunction!(count_fastq, m)?)?;
+    m.add_function(wrap_pyfunction!(count_bam, m)?)?;
+    m.add_function(wrap_pyfunction!(count_cram, m)?)?;
+    m.add_function(wrap_pyfunction!(count_vcf, m)?)?;
+    m.add_function(wrap_pyfunction!(count_bcf, m)?)?;
+    m.add_function(wrap_pyfunction!(count_bed, m)?)?;
+    m.add_function(wrap_pyfunction!(count_gff, m)?)?;
+    m.add_function(wrap_pyfunction!(count_gtf, m)?)?;
+    m.add_function(wrap_pyfunction!(count_maf, m)?)?;
+    m.add_function(wrap_pyfunction!(count_2bit, m)?)?;
+    m.add_function(wrap_pyfunction!(count_paf, m)?)?;
     Ok(())
 }
@@ -1,7 +1,11 @@
+import io
+import signal
+import threading
 from pathlib import Path
 
 import oxbow as ox  # Remember to build via maturin in the current env
 import polars as pl
+import pytest
 
 # See `../../fixtures/README.md` to download files that aren't checked into the repo
 test_path = Path(__file__).resolve()
@@ -9,9 +13,232 @@ project_root = test_path.parents[2]
 FIXTURES_PATH = Path(project_root / "fixtures")
 
 
+class Pipe:
+    """A file-like object without `seek`, like `sys.stdin.buffer` on a pipe."""
+
+    def __init__(self, data):
+        self._data = io.BytesIO(data)
+
+    def read(self, size=-1):
+        return self._data.read(size)
+
+
+class TestFasta:
+    fasta_path = str(FIXTURES_PATH / "sample.fa")
+
+    def test_filter_masked(self):
+        data = b">a\nACGN\n>b\nACgt\n>c\nAnnn\n"
+        df = pl.read_ipc(ox.read_fasta(io.BytesIO(data), max_n_fraction=0.25))
+        assert df["name"].to_list() == ["a", "b"]
+        df = pl.read_ipc(ox.read_fasta(io.BytesIO(data), max_lowercase_fraction=0.0))
+        assert df["name"].to_list() == ["a"]
+
+    def test_reference_lengths(self):
+        lengths = ox.reference_lengths_fasta(self.fasta_path)
+        assert list(lengths.items()) == [("sq0", 120), ("sq1", 90), ("sq2", 45)]
+
+    def test_read_all(self):
+        ipc = ox.read_fasta(self.fasta_path)
+        df = pl.read_ipc(ipc)
+
+        assert len(df) == 3
+
+    def test_count(self):
+        assert ox.count_fasta(self.fasta_path) == 3
+
+    def test_limit(self):
+        df = pl.read_ipc(ox.read_fasta(self.fasta_path, regions=["sq0", "sq1", "sq2"], limit=2))
+        assert len(df) == 2
+
+    def test_reverse_complement(self):
+        ipc = ox.read_fasta(self.fasta_path, regions=["sq0:1-10", "sq0:1-10"], reverse_complement=[False, True])
+        df = pl.read_ipc(ipc)
+
+        forward, reverse = df["sequence"].to_list()
+        complement = str.maketrans("ACGTacgt", "TGCAtgca")
+        assert reverse == forward.translate(complement)[::-1]
+
+
+    def test_read_batch_bytes(self):
+        ipc = ox.read_fasta(self.fasta_path, batch_bytes=150)
+        df = pl.read_ipc(ipc, rechunk=False)
+
+        assert len(df) == 3
+        assert df.n_chunks() == 2
+
+    def test_read_batch_limit_conflict(self):
+        with pytest.raises(ValueError):
+            ox.read_fasta(self.fasta_path, batch_size=1, batch_bytes=1)
+
+    @pytest.mark.parametrize("name", ["sample.fa.gz", "sample.plain.fa.gz"])
+    def test_read_compressed(self, name):
+        df = pl.read_ipc(ox.read_fasta(str(FIXTURES_PATH / name)))
+
+        assert df.equals(pl.read_ipc(ox.read_fasta(self.fasta_path)))
+
+    def test_query_plain_gzip(self):
+        with pytest.raises(ValueError):
+            ox.read_fasta(str(FIXTURES_PATH / "sample.plain.fa.gz"), region="sq0")
+
+    def test_sequence_stats(self):
+        df = pl.read_ipc(ox.read_fasta(self.fasta_path, include_length=True, include_gc=True))
+
+        assert df.columns[-2:] == ["length", "gc_content"]
+        assert df["length"].to_list() == [120, 90, 45]
+
+    def test_sequence_encoding(self):
+        fasta = b">a\nACGTN\n"
+        df = pl.read_ipc(ox.read_fasta(io.BytesIO(fasta), encoding="TwoBit"))
+        assert df.columns == ["name", "sequence", "sequence_mask"]
+        assert df["sequence"].to_list() == [[0b00011011, 0]]
+        assert df["sequence_mask"].to_list() == [[False, False, False, False, True]]
+
+        df = pl.read_ipc(ox.read_fasta(io.BytesIO(fasta), encoding="OneHot"))
+        assert df["sequence"].to_list() == [
+            [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1], [0, 0, 0, 0]]
+        ]
+        with pytest.raises(ValueError):
+            ox.read_fasta(self.fasta_path, encoding="2bit")
+
+    def test_fields(self):
+        df = pl.read_ipc(
+            ox.read_fasta(self.fasta_path, include_length=True, fields=["length", "name"])
+        )
+        assert df.columns == ["length", "name"]
+
+        with pytest.raises(ValueError, match="Valid fields"):
+            ox.read_fasta(self.fasta_path, fields=["gc_content"])
+
+    def test_read_file_like(self):
+        data = Path(self.fasta_path).read_bytes()
+        index = Path(self.fasta_path + ".fai").read_bytes()
+        expected = pl.read_ipc(ox.read_fasta(self.fasta_path, regions=["sq0:1-10", "sq1"]))
+
+        assert pl.read_ipc(ox.read_fasta(io.BytesIO(data))).equals(pl.read_ipc(ox.read_fasta(self.fasta_path)))
+        assert ox.count_fasta(io.BytesIO(data)) == 3
+        with pytest.raises(ValueError, match="index"):
+            ox.read_fasta(io.BytesIO(data), region="sq0")
+
+        df = pl.read_ipc(ox.read_fasta(io.BytesIO(data), regions=["sq0:1-10", "sq1"], index=index))
+        assert df.equals(expected)
+
+
+class TestFastq:
+    fastq_path = str(FIXTURES_PATH / "sample.fastq")
+
+    def test_read_all(self):
+        df = pl.read_ipc(ox.read_fastq(self.fastq_path))
+
+        assert df.columns == ["name", "description", "sequence", "quality_scores"]
+        assert df["quality_scores"].to_list() == ["IIII#", "!+5?", ""]
+
+    def test_count(self):
+        assert ox.count_fastq(self.fastq_path) == 3
+        with open(FIXTURES_PATH / "sample.fastq.gz", "rb") as f:
+            assert ox.count_fastq(f) == 3
+
+    @pytest.mark.parametrize("name", ["sample.fastq.gz", "sample.fastq.bgz"])
+    def test_read_compressed(self, name):
+        path = FIXTURES_PATH / name
+        expected = pl.read_ipc(ox.read_fastq(self.fastq_path))
+
+        assert pl.read_ipc(ox.read_fastq(str(path))).equals(expected)
+        with open(path, "rb") as f:
+            assert pl.read_ipc(ox.read_fastq(f)).equals(expected)
+
+    def test_paired(self):
+        r1 = b"@p0/1\nACGT\n+\nIIII\n@p1/1\nAC\n+\nII\n"
+        r2 = b"@p0/2\nTTTT\n+\n####\n@p1/2\nGG\n+\nII\n"
+        df = pl.read_ipc(ox.read_fastq_paired(io.BytesIO(r1), io.BytesIO(r2)))
+        assert df.columns == ["name", "seq_1", "qual_1", "seq_2", "qual_2"]
+        assert df["name"].to_list() == ["p0", "p1"]
+        assert df["seq_2"].to_list() == ["TTTT", "GG"]
+
+        df = pl.read_ipc(ox.read_fastq_interleaved(io.BytesIO(r1[:18] + r2[:18])))
+        assert df["name"].to_list() == ["p0"]
+        with pytest.raises(ValueError, match="mismatched names"):
+            ox.read_fastq_paired(io.BytesIO(r1), io.BytesIO(r2.replace(b"p1", b"p2")))
+
+    def test_parse_casava(self):
+        data = b"@M1:7:FC1:1:11:100:200 2:N:0:ACGT\nAC\n+\nII\n@r1 free text\nAC\n+\nII\n"
+        df = pl.read_ipc(ox.read_fastq(io.BytesIO(data), parse_casava=True))
+
+        assert df["description"].to_list() == ["2:N:0:ACGT", "free text"]
+        assert df["lane"].to_list() == [1, None]
+        assert df["read"].to_list() == [2, None]
+        assert df["index"].to_list() == ["ACGT", None]
+
+    def test_filter_length(self):
+        data = b"@r0\nA\n+\nI\n@r1\nACG\n+\nIII\n@r2\nACGTA\n+\nIIIII\n"
+        df = pl.read_ipc(ox.read_fastq(io.BytesIO(data), min_length=3, max_length=5))
+
+        assert df["name"].to_list() == ["r1", "r2"]
+
+    def test_kmers(self):
+        data = b"@r0\nACGTNACGT\n+\nIIIIIIIII\n@r1\nAC\n+\nII\n"
+        df = pl.read_ipc(ox.read_fastq(io.BytesIO(data), kmer_size=3, kmer_stride=2))
+
+        assert df["kmers"].to_list() == [["ACG", "CGT"], []]
+
+        df = pl.read_ipc(ox.read_fastq(io.BytesIO(data), kmer_size=3, hash_kmers=True))
+        assert df.schema["kmers"] == pl.List(pl.UInt32)
+        assert df["kmers"].list.len().to_list() == [4, 0]
+
+    def test_count_motifs(self):
+        data = b"@r0\nCGCGA\n+\nIIIII\n@r1\nCCAGGcctgg\n+\nIIIIIIIIII\n"
+        df = pl.read_ipc(ox.read_fastq(io.BytesIO(data), count_motifs=["CG", "CCWGG"]))
+
+        assert df["CG_count"].to_list() == [2, 0]
+        assert df["CCWGG_count"].to_list() == [0, 2]
+        with pytest.raises(ValueError):
+            ox.read_fastq(io.BytesIO(data), count_motifs=["CX"])
+
+    def test_decode_quality(self):
+        df = pl.read_ipc(ox.read_fastq(self.fastq_path, decode_quality=True))
+
+        assert df.schema["quality_scores"] == pl.List(pl.UInt8)
+        assert df["quality_scores"].to_list() == [[40, 40, 40, 40, 2], [0, 10, 20, 30], []]
+
+    def test_decode_quality_offset_64(self, tmp_path):
+        path = tmp_path / "phred64.fastq"
+        path.write_text("@r0\nACGT\n+\nhh@B\n")
+        df = pl.read_ipc(ox.read_fastq(str(path), decode_quality=True, quality_offset=64))
+
+        assert df["quality_scores"].to_list() == [[40, 40, 0, 2]]
+
+    def test_sequence_stats(self):
+        df = pl.read_ipc(ox.read_fastq(self.fastq_path, include_length=True, include_gc=True))
+
+        assert df["length"].to_list() == [5, 4, 0]
+        assert df["gc_content"].to_list() == [0.5, 1.0, None]
+
+    def test_read_bytes_io(self):
+        expected = pl.read_ipc(ox.read_fastq(self.fastq_path))
+        data = Path(self.fastq_path).read_bytes()
+
+        assert pl.read_ipc(ox.read_fastq(io.BytesIO(data))).equals(expected)
+        assert ox.count_fastq(io.BytesIO(data)) == 3
+
+    @pytest.mark.parametrize("name", ["sample.fastq", "sample.fastq.gz"])
+    def test_read_pipe(self, name):
+        expected = pl.read_ipc(ox.read_fastq(self.fastq_path))
+        data = (FIXTURES_PATH / name).read_bytes()
+
+        assert pl.read_ipc(ox.read_fastq(Pipe(data))).equals(expected)
+
 class TestBam:
     bam_path = str(FIXTURES_PATH / "example.bam")
 
+    def test_cancel(self):
+        cancel = threading.Event()
+        ipc = ox.read_bam(self.bam_path, cancel=cancel, progress=lambda *_: cancel.set(), progress_interval=2)
+
+        assert len(pl.read_ipc(ipc)) == 2
+
+    def test_keyboard_interrupt(self):
+        with pytest.raises(KeyboardInterrupt):
+            ox.read_bam(self.bam_path, progress=lambda *_: signal.raise_signal(signal.SIGINT), progress_interval=1)
+
     def test_read_df(self):
         ipc = ox.read_bam(self.bam_path)
         df = pl.read_ipc(ipc)
@@ -35,6 +262,29 @@ class TestBam:
         # Check number of rows
         assert len(df) == 160_178
 
+    def test_decode_flags(self):
+        df = pl.read_ipc(ox.read_bam(self.bam_path, "chr1:1-100000", decode_flags=True))
+        bits = {
+            "is_paired": 0x1,
+            "is_unmapped": 0x4,
+            "is_reverse": 0x10,
+            "is_read1": 0x40,
+            "is_secondary": 0x100,
+            "is_supplementary": 0x800,
+        }
+        for name, bit in bits.items():
+            assert df.schema[name] == pl.Boolean
+            assert df[name].to_list() == [flag & bit != 0 for flag in df["flag"]]
+
+    def test_chrom_alias(self):
+        expected = pl.read_ipc(ox.read_bam(self.bam_path, "chr1:1-100000"))
+        df = pl.read_ipc(ox.read_bam(self.bam_path, "1:1-100000", chrom_alias="ucsc_ensembl"))
+        assert df.equals(expected)
+        df = pl.read_ipc(ox.read_bam(self.bam_path, "one:1-100000", chrom_alias={"one": "chr1"}))
+        assert df.equals(expected)
+        with pytest.raises(ValueError):
+            ox.read_bam(self.bam_path, "1", chrom_alias="ucsc")
+
     def test_read_region_partial(self):
         ipc = ox.read_bam(self.bam_path, "chr1:1-100000")
         df = pl.read_ipc(ipc)
@@ -42,10 +292,361 @@ class TestBam:
         # Check number of rows
         assert len(df) == 4771
 
+    def test_read_file_like(self):
+        index = Path(self.bam_path + ".bai").read_bytes()
+        with open(self.bam_path, "rb") as f:
+            df = pl.read_ipc(ox.read_bam(f, "chr1:1-100000", index=index))
+        assert len(df) == 4771
+
+        with open(self.bam_path, "rb") as f:
+            with pytest.raises(ValueError, match="index"):
+                ox.read_bam(f)
+
+    def test_count(self):
+        assert ox.count_bam(self.bam_path) == len(pl.read_ipc(ox.read_bam(self.bam_path)))
+        assert ox.count_bam(self.bam_path, "chr1:1-100000") == 4771
+        assert ox.count_bam(self.bam_path, regions=["chr1", "chr1:1-100000"]) == {
+            "chr1": 160_178,
+            "chr1:1-100000": 4771,
+        }
+
+    def test_read_many(self):
+        paths = [self.bam_path, self.bam_path]
+        df = pl.read_ipc(ox.read_bam_many(paths, "chr1:1-100000", source=True))
+        assert len(df) == 2 * 4771
+        assert df["source"].cast(pl.Utf8).unique().to_list() == [self.bam_path]
+
+        with pytest.raises(ValueError, match="Incompatible"):
+            ox.read_bam_many([self.bam_path, str(FIXTURES_PATH / "cigar.bam")])
+
+    def test_read_groups(self):
+        path = str(FIXTURES_PATH / "readgroups.bam")
+        assert ox.read_groups_bam(path) == [
+            {"ID": "rg1", "SM": "alice", "PL": "ILLUMINA", "LB": "lib1"},
+            {"ID": "rg2", "SM": "bob", "PL": "ONT", "LB": "lib2"},
+        ]
+        assert [pg["ID"] for pg in ox.programs_bam(path)] == ["bwa", "samtools"]
+        lengths = ox.reference_lengths_bam(self.bam_path)
+        assert list(lengths)[0] == "chr1"
+        header = ox.read_bam_header(path)
+        assert header.startswith("@HD\t")
+        assert header.count("\n@RG\t") == 2
+
+        df = pl.read_ipc(ox.read_bam(path, include_sample=True))
+        assert df["sample"].cast(pl.Utf8).to_list() == ["alice", "bob", "alice", None, None]
+
+    def test_read_tags(self):
+        ipc = ox.read_bam(str(FIXTURES_PATH / "sample.bam"), tags=["NM", "MD"])
+        df = pl.read_ipc(ipc)
+
+        assert df.columns[-3:] == ["end", "NM", "MD"]
+        assert "tags" not in df.columns
+        assert df.schema["NM"] == pl.Int32
+
+    def test_invalid_tag(self):
+        with pytest.raises(ValueError):
+            ox.read_bam(str(FIXTURES_PATH / "sample.bam"), tags=["NMX"])
+
+    def test_read_regions_parallel(self):
+        path = str(FIXTURES_PATH / "sample.bam")
+        regions = ["chr3", "chr1:1-100000", "chr1"]
+        sequential = pl.read_ipc(ox.read_bam(path, regions=regions, tags=["NM"]))
+        parallel = pl.read_ipc(ox.read_bam(path, regions=regions, tags=["NM"], threads=2))
+
+        assert len(sequential) == 8
+        assert parallel.equals(sequential)
+
+    def test_limit(self):
+        path = str(FIXTURES_PATH / "sample.bam")
+        regions = ["chr1", "chr1:1-100000", "chr3"]
+        assert len(pl.read_ipc(ox.read_bam(path, regions=regions, limit=5))) == 5
+        assert len(pl.read_ipc(ox.read_bam(path, regions=regions, threads=2, limit=5))) == 5
+
+    def test_separate_index(self, tmp_path):
+        path = tmp_path / "sample.bam"
+        path.write_bytes((FIXTURES_PATH / "sample.bam").read_bytes())
+        index = str(FIXTURES_PATH / "sample.bam.bai")
+
+        df = pl.read_ipc(ox.read_bam(str(path), "chr1:1-100000", index=index))
+        assert len(df) == 2
+        assert ox.count_bam(str(path), "chr1:1-100000", index=index) == 2
+
+    def test_filters(self):
+        path = str(FIXTURES_PATH / "flags.bam")
+        df = pl.read_ipc(ox.read_bam(path, exclude_flags=0xD00))
+        assert df["qname"].to_list() == ["p1", "l1", "p1", "m1"]
+
+        df = pl.read_ipc(ox.read_bam(path, min_mapq=30, include_flags=0x1, exclude_flags=0xD00))
+        assert df["qname"].to_list() == ["p1", "p1"]
+
+        assert ox.count_bam(path, min_mapq=30) == 4
+
+    def test_fields(self):
+        path = str(FIXTURES_PATH / "sample.bam")
+        df = pl.read_ipc(ox.read_bam(path, tags=["NM"], fields=["NM", "pos", "qname"]))
+        assert df.columns == ["NM", "pos", "qname"]
+
+        with pytest.raises(ValueError, match="Valid fields"):
+            ox.read_bam(path, fields=["qname", "NM"])
+
+    def test_lengths(self):
+        path = str(FIXTURES_PATH / "cigar.bam")
+        df = pl.read_ipc(
+            ox.read_bam(path, include_reference_length=True, include_query_length=True)
+        )
+        assert df["cigar"].to_list()[:2] == ["5M2I5M", "3S5M3D5M"]
+        assert df["reference_length"].to_list() == [10, 13, 110, 10, None]
+        assert df["query_length"].to_list() == [12, 13, 10, 12, None]
+
+    def test_clip_lengths(self):
+        path = str(FIXTURES_PATH / "cigar.bam")
+        df = pl.read_ipc(ox.read_bam(path, include_clip_lengths=True))
+        assert df["left_soft_clip"].to_list() == [0, 3, 0, 0, None]
+        assert df["right_soft_clip"].to_list() == [0, 0, 0, 2, None]
+        assert df["left_hard_clip"].to_list() == [0, 0, 0, 2, None]
+
+        path = str(FIXTURES_PATH / "pairs.bam")
+        df = pl.read_ipc(ox.read_bam(path, "sq0:500-500", include_clip_lengths=True))
+        assert df["left_hard_clip"].to_list() == [3]
+        assert df["right_hard_clip"].to_list() == [2]
+
+    def test_methylation(self):
+        path = str(FIXTURES_PATH / "bisulfite.bam")
+        reference = str(FIXTURES_PATH / "bisulfite.fa")
+        df = pl.read_ipc(ox.read_bam(path, methylation=True, reference=reference))
+        assert df["meth_cpg"].to_list() == [2, 3, 2, 1, None, None]
+        assert df["unmeth_cpg"].to_list() == [2, 1, 2, 0, None, None]
+
+        with pytest.raises(ValueError, match="reference"):
+            ox.read_bam(path, methylation=True)
+
+    def test_pileup(self):
+        path = str(FIXTURES_PATH / "cigar.bam")
+        df = pl.read_ipc(ox.read_bam_pileup(path, ["sq0:24-31"]))
+        assert df.columns == ["chrom", "pos", "depth", "a", "c", "g", "t", "n", "del"]
+        assert df["pos"].to_list() == list(range(24, 32))
+        assert df["del"].to_list() == [0, 1, 1, 1, 0, 0, 0, 0]
+        assert df["depth"].to_list() == [1, 1, 1, 1, 1, 1, 2, 2]
+
+    def test_coverage(self):
+        path = str(FIXTURES_PATH / "pairs.bam")
+        df = pl.read_ipc(ox.read_bam_coverage(path, 105, ["sq0:1-420"]))
+        assert df.columns == ["chrom", "window_start", "window_end", "read_count"]
+        assert df["window_start"].to_list() == [1, 106, 211, 316]
+        assert df["window_end"].to_list() == [105, 210, 315, 420]
+        assert df["read_count"].to_list() == [1, 1, 1, 1]
+
+        df = pl.read_ipc(
+            ox.read_bam_coverage(
+                path, 105, ["sq0:1-420"], assignment="overlap", include_mean_depth=True
+            )
+        )
+        assert df["read_count"].to_list() == [1, 2, 1, 1]
+        assert "mean_depth" in df.columns
+        with pytest.raises(ValueError):
+            ox.read_bam_coverage(path, 105, assignment="middle")
+
+    def test_pairs(self):
+        path = str(FIXTURES_PATH / "pairs.bam")
+        df = pl.read_ipc(ox.read_bam_pairs(path))
+        assert df["qname"].to_list() == ["p1", "p2", "u1", "o1"]
+        assert df["pos2"].to_list() == [300, 400, None, 100]
+        assert df["fragment_length"].to_list() == [210, 260, None, None]
+
+        df = pl.read_ipc(ox.read_bam_pairs(path, "sq0"))
+        assert df["pos2"].to_list() == [300, 400, None, None]
+        with pytest.raises(ValueError):
+            ox.read_bam_pairs(path, max_buffer=1)
+
+    def test_query_mode(self):
+        path = str(FIXTURES_PATH / "flags.bam")
+        df = pl.read_ipc(ox.read_bam(path, "sq0:15-45"))
+        assert df["qname"].to_list() == ["p1", "s1", "d1", "l1"]
+
+        df = pl.read_ipc(ox.read_bam(path, "sq0:15-45", query_mode="contained"))
+        assert df["qname"].to_list() == ["s1", "d1"]
+
+        assert ox.count_bam(path, "sq0:15-45", query_mode="contained") == 2
+        with pytest.raises(ValueError):
+            ox.read_bam(path, "sq0:15-45", query_mode="inside")
+
+    def test_read_regions_invalid(self):
+        path = str(FIXTURES_PATH / "sample.bam")
+        with pytest.raises(ValueError):
+            ox.read_bam(path, "chr1", regions=["chr3"])
+        with pytest.raises(ValueError):
+            ox.read_bam(path, "chr1", threads=2)
+
+    def test_read_threads(self):
+        path = str(FIXTURES_PATH / "sample.bam")
+        single = pl.read_ipc(ox.read_bam(path, tags=["NM"]))
+        multi = pl.read_ipc(ox.read_bam(path, tags=["NM"], threads=4))
+        assert multi.equals(single)
+        assert len(pl.read_ipc(ox.read_bam(path, threads=4, limit=3))) == 3
+
+        path = str(FIXTURES_PATH / "sample.vcf.gz")
+        assert pl.read_ipc(ox.read_vcf(path, threads=4)).equals(pl.read_ipc(ox.read_vcf(path)))
+
+    def test_read_unmapped(self):
+        path = str(FIXTURES_PATH / "unmapped.bam")
+        mapped = pl.read_ipc(ox.read_bam(path, "sq0"))
+        unmapped = pl.read_ipc(ox.read_bam_unmapped(path))
+
+        assert len(mapped) == 3
+        assert unmapped["qname"].to_list() == ["u1", "u2"]
+        assert unmapped["rname"].null_count() == 2
+
+    def test_emit_ref_as_index(self):
+        path = str(FIXTURES_PATH / "unmapped.bam")
+        df = pl.read_ipc(ox.read_bam(path, emit_ref_as_index=True))
+
+        assert df.schema["rname"] == pl.Int32
+        assert df["rname"].to_list() == [0, 0, 0, 1, None, None]
+
+    def test_emit_offset(self):
+        path = str(FIXTURES_PATH / "sample.bam")
+        df = pl.read_ipc(ox.read_bam(path, emit_offset=True))
+        assert df.columns[-1] == "vpos"
+
+        vpos = df["vpos"][2]
+        lo, hi = (vpos >> 16, vpos & 0xFFFF), ((vpos + 1) >> 16, (vpos + 1) & 0xFFFF)
+        tail = pl.read_ipc(ox.read_bam_vpos(path, lo, hi))
+        assert tail["qname"].to_list() == [df["qname"][2]]
+        with pytest.raises(ValueError):
+            ox.read_bam(path, "sq0", emit_offset=True)
+
+    def test_scan_from(self):
+        path = str(FIXTURES_PATH / "sample.bam")
+        df = pl.read_ipc(ox.read_bam(path, emit_offset=True))
+        tail = pl.read_ipc(ox.read_bam_from(path, df["vpos"][2], emit_offset=True))
+        assert tail["qname"].to_list() == df["qname"].to_list()[2:]
+        with pytest.raises(ValueError):
+            ox.read_bam_from(path, df["vpos"][2] + 1)
+
+
+class TestCram:
+    cram_path = str(FIXTURES_PATH / "sample.cram")
+    fasta_path = str(FIXTURES_PATH / "sample.fa")
+
+    def test_read_df(self):
+        ipc = ox.read_cram(self.cram_path, self.fasta_path)
+        df = pl.read_ipc(ipc)
+
+        assert not df.is_empty()
+
+        # Check number of columns
+        assert len(df.columns) == 13
+
+    def test_read_all(self):
+        ipc = ox.read_cram(self.cram_path, self.fasta_path)
+        df = pl.read_ipc(ipc)
+
+        # Check number of rows
+        assert len(df) == 6
+
+    def test_read_region_partial(self):
+        ipc = ox.read_cram(self.cram_path, self.fasta_path, "sq0:1-40")
+        df = pl.read_ipc(ipc)
+
+        # Check number of rows
+        assert len(df) == 2
+
+    def test_count(self):
+        assert ox.count_cram(self.cram_path, self.fasta_path) == 6
+        assert ox.count_cram(self.cram_path, self.fasta_path, "sq0:1-40") == 2
+
+    def test_missing_files(self):
+        missing = str(FIXTURES_PATH / "missing.cram")
+        with pytest.raises(OSError):
+            ox.read_cram(missing, self.fasta_path)
+        with pytest.raises(OSError):
+            ox.read_cram(self.cram_path, str(FIXTURES_PATH / "missing.fa"))
+        with pytest.raises(OSError):
+            ox.read_vcf(str(FIXTURES_PATH / "missing.vcf.gz"))
+
+
+class TestSam:
+    sam_path = str(FIXTURES_PATH / "sample.sam")
+
+    def test_read_all(self):
+        df = pl.read_ipc(ox.read_sam(self.sam_path))
+        assert len(df) == 5
+
+    def test_linear_scan(self):
+        with pytest.raises(ValueError, match="linear scan"):
+            ox.read_sam(self.sam_path, "sq0:105-155")
+
+        ipc = ox.read_sam(self.sam_path, "sq0:105-155", allow_linear_scan=True)
+        df = pl.read_ipc(ipc)
+        assert df["qname"].to_list() == ["r1", "r2"]
+
+    def test_require_sorted(self):
+        assert len(pl.read_ipc(ox.read_sam(self.sam_path, require_sorted=True))) == 5
+
+        path = str(FIXTURES_PATH / "unsorted.sam")
+        assert len(pl.read_ipc(ox.read_sam(path))) == 3
+        with pytest.raises(ValueError, match="record 3"):
+            ox.read_sam(path, require_sorted=True)
+
 
 class TestVcf:
     vcf_path = str(FIXTURES_PATH / "ALL.chrY.phase3_integrated_v1a.20130502.genotypes.vcf.gz")
 
+    def test_reference_lengths(self):
+        lengths = ox.reference_lengths_vcf(str(FIXTURES_PATH / "sample.vcf.gz"))
+        assert lengths == {"sq0": 120, "sq1": 90}
+
+    @pytest.mark.parametrize("threads", [None, 2])
+    def test_progress(self, threads):
+        path = FIXTURES_PATH / "sample.vcf.gz"
+        reports = []
+        ox.read_vcf(str(path), threads=threads, progress=lambda *args: reports.append(args), progress_interval=3)
+
+        size = path.stat().st_size
+        assert reports == [(3, reports[0][1], size), (4, reports[1][1], size)]
+        assert 0 < reports[0][1] <= reports[1][1] <= size
+
+    @pytest.mark.parametrize("threads", [None, 2])
+    def test_progress_regions(self, threads):
+        path = FIXTURES_PATH / "sample.vcf.gz"
+        reports = []
+        ox.read_vcf(str(path), regions=["sq0", "sq1"], threads=threads, progress=lambda *args: reports.append(args))
+
+        assert reports[-1] == (4, 0, None)
+
+    def test_cancel_regions(self):
+        path = str(FIXTURES_PATH / "sample.vcf.gz")
+        cancel = threading.Event()
+        ipc = ox.read_vcf(path, regions=["sq0", "sq1"], cancel=cancel, progress=lambda *_: cancel.set(), progress_interval=2)
+
+        assert len(pl.read_ipc(ipc)) == 2
+
+    def test_read_pipe(self):
+        path = FIXTURES_PATH / "sample.vcf.gz"
+        expected = pl.read_ipc(ox.read_vcf(str(path)))
+
+        assert pl.read_ipc(ox.read_vcf(Pipe(path.read_bytes()))).equals(expected)
+        with pytest.raises(ValueError, match="requires seekable input"):
+            ox.read_vcf(Pipe(path.read_bytes()), "sq0")
+
+    def test_limit(self):
+        df = pl.read_ipc(ox.read_vcf(self.vcf_path, regions=["Y", "Y:8028497-17629059"], limit=100))
+        assert len(df) == 100
+
+    @pytest.mark.parametrize("csi", [False, True])
+    def test_index_file(self, tmp_path, csi):
+        path = tmp_path / "sample.vcf.gz"
+        path.write_bytes((FIXTURES_PATH / "sample.vcf.gz").read_bytes())
+        ox.index_file(str(path), csi=csi)
+
+        assert (tmp_path / ("sample.vcf.gz.csi" if csi else "sample.vcf.gz.tbi")).exists()
+        df = pl.read_ipc(ox.read_vcf(str(path), "sq0"))
+        assert len(df) == 3
+
+    def test_index_file_invalid_preset(self):
+        with pytest.raises(ValueError):
+            ox.index_file(str(FIXTURES_PATH / "sample.vcf.gz"), "bam")
+
     def test_read_df(self):
         ipc = ox.read_vcf(self.vcf_path)
         df = pl.read_ipc(ipc)
@@ -53,7 +654,7 @@ class TestVcf:
         assert not df.is_empty()
 
         # Check number of columns
-        assert len(df.columns) == 9
+        assert len(df.columns) == 10
 
     def test_read_all(self):
         ipc = ox.read_vcf(self.vcf_path)
@@ -76,6 +677,113 @@ class TestVcf:
         # Check number of rows
         assert len(df) == 27_947
 
+    def test_count(self):
+        assert ox.count_vcf(self.vcf_path) == len(pl.read_ipc(ox.read_vcf(self.vcf_path)))
+        assert ox.count_vcf(self.vcf_path, regions=["Y:8028497-17629059"]) == {
+            "Y:8028497-17629059": 27_947
+        }
+
+    def test_read_info_fields(self):
+        ipc = ox.read_vcf(str(FIXTURES_PATH / "sample.vcf.gz"), info_fields=["DP", "AF", "DB"])
+        df = pl.read_ipc(ipc)
+
+        assert "info" not in df.columns
+        assert df.schema["DP"] == pl.Int32
+        assert df.schema["AF"] == pl.List(pl.Float32)
+        assert df["DB"].to_list() == [True, False, False, False]
+
+    def test_emit_ref_as_index(self):
+        ipc = ox.read_vcf(str(FIXTURES_PATH / "sample.vcf.gz"), emit_ref_as_index=True)
+        df = pl.read_ipc(ipc)
+
+        assert df["chrom"].to_list() == [0, 0, 0, 1]
+
+    def test_read_regions_parallel(self):
+        path = str(FIXTURES_PATH / "sample.vcf.gz")
+        df = pl.read_ipc(ox.read_vcf(path, regions=["sq1", "sq0"], threads=2))
+
+        assert df["pos"].to_list() == [5, 10, 20, 30]
+
+    def test_read_genotype_fields(self):
+        ipc = ox.read_vcf(
+            str(FIXTURES_PATH / "sample.vcf.gz"),
+            genotype_fields=["GT", "AD"],
+            samples=["s1"],
+        )
+        df = pl.read_ipc(ipc)
+
+        assert df.columns[-2:] == ["s1.GT", "s1.AD"]
+        assert df["s1.GT"].to_list() == ["1|1", "0/0", "0|1", "1/1"]
+        assert df["s1.AD"].to_list()[1] == [7, 0, 0]
+
+    def test_read_gvcf(self):
+        path = str(FIXTURES_PATH / "sample.g.vcf.gz")
+        df = pl.read_ipc(ox.read_vcf(path))
+        assert df["end"].to_list() == [4, 5, 10, 11]
+        assert df["alt"].to_list()[0] == "<*>"
+
+        df = pl.read_ipc(ox.read_vcf(path, expand_gvcf_blocks=True))
+        assert df["pos"].to_list() == list(range(1, 12))
+        assert df["end"].to_list() == df["pos"].to_list()
+
+    def test_read_structural_variants(self):
+        path = str(FIXTURES_PATH / "sample.sv.vcf.gz")
+        df = pl.read_ipc(
+            ox.read_vcf(path, include_sv_fields=True, include_breakend_mates=True)
+        )
+        assert df["alt"].to_list() == ["<DEL>", "<DUP>", "A[chr2:123[", "T", "]chr1:2000]G"]
+        assert df["svtype"].to_list() == ["DEL", "DUP", "BND", None, "BND"]
+        assert df["svlen"].to_list() == [-400, 250, None, None, None]
+        assert df["mate_chrom"].to_list() == [None, None, "chr2", None, "chr1"]
+        assert df["mate_pos"].to_list() == [None, None, 123, None, 2000]
+
+    def test_qual(self):
+        df = pl.read_ipc(ox.read_vcf(str(FIXTURES_PATH / "sample.vcf.gz")))
+        assert df.schema["qual"] == pl.Float32
+        assert df["qual"].to_list() == [50.0, None, 12.5, 99.0]
+
+    def test_ids_and_filters(self):
+        path = str(FIXTURES_PATH / "sample.vcf.gz")
+        df = pl.read_ipc(ox.read_vcf(path))
+        assert df["id"].to_list() == ["rs1", None, "rs3;rs4", None]
+        assert df["filter"].to_list() == ["PASS", "q10", None, "PASS"]
+
+        df = pl.read_ipc(ox.read_vcf(path, split_ids=True, split_filters=True))
+        assert df["id"].to_list() == [["rs1"], None, ["rs3", "rs4"], None]
+        assert df["filter"].to_list() == [["PASS"], ["q10"], None, ["PASS"]]
+
+    def test_split_multiallelic(self):
+        ipc = ox.read_vcf(
+            str(FIXTURES_PATH / "sample.vcf.gz"),
+            info_fields=["AF"],
+            genotype_fields=["GT", "AD"],
+            split_multiallelic=True,
+        )
+        df = pl.read_ipc(ipc)
+
+        assert df["pos"].to_list() == [10, 20, 20, 30, 5]
+        assert df["alt"].to_list() == ["G", "T", "G", "A", "<DEL>"]
+        assert df["AF"].to_list()[1:3] == [[0.25], [0.25]]
+        assert df["s0.GT"].to_list()[1:3] == ["1/0", "0/1"]
+        assert df["s1.AD"].to_list()[1:3] == [[7, 0], [7, 0]]
+
+
+    def test_write_round_trip(self, tmp_path):
+        path = str(FIXTURES_PATH / "sample.vcf.gz")
+        kwargs = dict(info_fields=["DP", "AF", "DB"], genotype_fields=["GT", "AD"])
+        ipc = ox.read_vcf(path, **kwargs)
+
+        out = str(tmp_path / "copy.vcf.gz")
+        ox.write_vcf(out, ipc, ox.read_vcf_header(path))
+        ox.index_file(out)
+
+        # The written FORMAT has every materialized key.
+        expected = pl.read_ipc(ipc).drop("format")
+        assert pl.read_ipc(ox.read_vcf(out, **kwargs)).drop("format").equals(expected)
+
+        ox.write_bcf(str(tmp_path / "copy.bcf"), ipc, ox.read_vcf_header(path))
+        assert (tmp_path / "copy.bcf").read_bytes()[:2] == b"\x1f\x8b"
+
 
 class TestBcf:
     bcf_path = str(FIXTURES_PATH / "ALL.chrY.phase3_shapeit2_mvncall_integrated.20130502.genotypes.bcf")
@@ -87,7 +795,7 @@ class TestBcf:
         assert not df.is_empty()
 
         # Check number of columns
-        assert len(df.columns) == 9
+        assert len(df.columns) == 10
 
     def test_read_all(self):
         ipc = ox.read_bcf(self.bcf_path)
@@ -144,6 +852,10 @@ class TestBigWig:
         # Check number of rows
         assert len(df) == 4
 
+    def test_read_unknown_chrom(self):
+        with pytest.raises(ValueError):
+            ox.read_bigwig(self.bigwig_path, "chrZZ:1-100")
+
 
 class TestBigBed:
     bigbed_path = str(FIXTURES_PATH / "small.bigBed")
@@ -164,10 +876,17 @@ class TestBigBed:
         # Count number of rows
         assert len(df) == 27
 
+    def test_read_unknown_chrom(self):
+        with pytest.raises(ValueError):
+            ox.read_bigbed(self.bigbed_path, "chrZZ:1-100")
+
 
 class TestGff:
     gff_path = str(FIXTURES_PATH / "example.gff")
 
+    def test_chroms(self):
+        assert ox.chroms_gff(self.gff_path) == ["ctg123"]
+
     def test_read_df(self):
         ipc = ox.read_gff(self.gff_path)
         df = pl.read_ipc(ipc)
@@ -184,6 +903,195 @@ class TestGff:
         # Check number of rows
         assert len(df) == 6
 
+    def test_count(self):
+        assert ox.count_gff(self.gff_path) == 6
+
+    def test_read_attributes(self):
+        ipc = ox.read_gff(self.gff_path, attributes=[("ID", "String"), ("Parent", "Array")])
+        df = pl.read_ipc(ipc)
+
+        assert df.schema["attributes"] == pl.Struct(
+            {"ID": pl.Utf8, "Parent": pl.List(pl.Utf8)}
+        )
+
+    @pytest.mark.parametrize("empty_lists, parents", [("null", [None, "t1", "t2"]), ("drop", ["t1", "t2"])])
+    def test_flatten_parent(self, tmp_path, empty_lists, parents):
+        path = tmp_path / "parents.gff"
+        path.write_text(
+            "##gff-version 3\n"
+            "chr1\t.\tgene\t100\t900\t.\t+\t.\tID=g1\n"
+            "chr1\t.\texon\t100\t200\t.\t+\t.\tID=e1;Parent=t1,t2\n"
+        )
+        ipc = ox.read_gff(str(path), attributes=[("ID", "String"), ("Parent", "Array")])
+        df = pl.read_ipc(ox.flatten_list_columns(ipc, "attributes.Parent", empty_lists=empty_lists))
+
+        assert df.schema["attributes"] == pl.Struct({"ID": pl.Utf8, "Parent": pl.Utf8})
+        assert df["attributes"].struct.field("Parent").to_list() == parents
+        with pytest.raises(ValueError, match="Expected a list column"):
+            ox.flatten_list_columns(ipc, "attributes.ID")
+
+    def test_numeric_attributes(self, tmp_path):
+        path = tmp_path / "numeric.gff"
+        path.write_text(
+            "##gff-version 3\n"
+            "chr1\t.\tgene\t100\t200\t.\t+\t.\tID=g1;length=101;gc=0.4\n"
+            "chr1\t.\tgene\t300\t400\t.\t+\t.\tID=g2;length=101;gc=1\n"
+        )
+        ipc = ox.read_gff(str(path), scan_attributes=True, infer_attribute_types=True)
+        df = pl.read_ipc(ipc)
+        assert df.schema["attributes"] == pl.Struct(
+            {"ID": pl.Utf8, "length": pl.Int64, "gc": pl.Float64}
+        )
+
+        ipc = ox.read_gff(str(path), attributes=[("length", "Int64"), ("ID", "Int64")])
+        df = pl.read_ipc(ipc)
+        assert df.schema["attributes"] == pl.Struct({"length": pl.Int64, "ID": pl.Utf8})
+
+        ipc = ox.read_gff(
+            str(path), infer_attribute_types=True, attribute_defs=[("length", "String")]
+        )
+        df = pl.read_ipc(ipc)
+        assert df.schema["attributes"] == pl.Struct(
+            {"ID": pl.Utf8, "length": pl.Utf8, "gc": pl.Float64}
+        )
+
+    def test_directives_and_fasta(self):
+        path = str(FIXTURES_PATH / "directives.gff")
+        assert len(pl.read_ipc(ox.read_gff(path))) == 3
+
+        df = pl.read_ipc(ox.read_gff_fasta(path))
+        assert df["name"].to_list() == ["ctg1", "ctg2"]
+
+        with pytest.raises(ValueError):
+            ox.read_gff_fasta(self.gff_path)
+
+    def test_missing_as_empty(self):
+        attributes = [("ID", "String"), ("Parent", "Array")]
+        df = pl.read_ipc(ox.read_gff(self.gff_path, attributes=attributes))
+        assert df["attributes"].struct.field("Parent").null_count() > 0
+
+        df = pl.read_ipc(
+            ox.read_gff(self.gff_path, attributes=attributes, missing_as_empty=True)
+        )
+        assert df["attributes"].struct.field("Parent").null_count() == 0
+        assert df["phase"].null_count() == 0
+
+    def test_on_error(self):
+        path = str(FIXTURES_PATH / "malformed.gff")
+        with pytest.raises(ValueError):
+            ox.read_gff(path)
+
+        df = pl.read_ipc(ox.read_gff(path, scan_attributes=True, on_error="skip"))
+        assert df["attributes"].struct.field("ID").to_list() == ["gene1", "exon1"]
+
+        with pytest.warns(UserWarning, match="line 3"):
+            ox.read_gff(path, on_error="skip_and_collect")
+
+    def test_percent_decode(self):
+        data = (
+            b"##gff-version 3\n"
+            b"chr1\ttest\tgene\t100\t200\t.\t+\t.\tID=g1;Note=a%3Bb%2Cc\n"
+        )
+        attributes = [("Note", "String")]
+        df = pl.read_ipc(ox.read_gff(io.BytesIO(data), attributes=attributes))
+        assert df["attributes"].struct.field("Note").to_list() == ["a;b,c"]
+
+        df = pl.read_ipc(
+            ox.read_gff(io.BytesIO(data), attributes=attributes, percent_decode=False)
+        )
+        assert df["attributes"].struct.field("Note").to_list() == ["a%3Bb%2Cc"]
+
+    def test_scan_attributes(self):
+        ipc = ox.read_gff(self.gff_path, scan_attributes=True)
+        df = pl.read_ipc(ipc)
+
+        assert [f.name for f in df.schema["attributes"].fields] == ["ID", "Name", "Parent"]
+
+    def test_invalid_attribute_type(self):
+        with pytest.raises(ValueError):
+            ox.read_gff(self.gff_path, attributes=[("ID", "Integer")])
+
+    def test_read_parent_ids(self):
+        gff = pl.read_ipc(
+            ox.read_gff(str(FIXTURES_PATH / "transcripts.gff"), parent_ids=True)
+        )
+        gtf = pl.read_ipc(
+            ox.read_gtf(str(FIXTURES_PATH / "transcripts.gtf"), parent_ids=True)
+        )
+
+        assert gff["parent_id"].to_list() == [None, ["g1"], ["t1"], ["t1"], ["t1"]]
+        assert gtf["parent_id"].to_list() == gff["parent_id"].to_list()
+
+    def test_strand_and_phase(self):
+        gff = pl.read_ipc(ox.read_gff(str(FIXTURES_PATH / "transcripts.gff")))
+        gtf = pl.read_ipc(ox.read_gtf(str(FIXTURES_PATH / "transcripts.gtf")))
+
+        assert gff.schema["strand"] == pl.Categorical
+        assert gff.schema["phase"] == pl.Int8
+        assert gff["phase"].to_list() == [None, None, None, 0, None]
+        assert gtf.select("strand", "phase").equals(gff.select("strand", "phase"))
+
+    def test_source_and_score(self):
+        gtf = (
+            b'chr1\thavana\tgene\t100\t900\t.\t+\t.\tgene_id "g1";\n'
+            b'chr1\tensembl\texon\t100\t200\t5.5\t+\t.\tgene_id "g1"; transcript_id "t1";\n'
+        )
+        gff = (
+            b"##gff-version 3\n"
+            b"chr1\thavana\tgene\t100\t900\t.\t+\t.\tID=g1\n"
+            b"chr1\tensembl\texon\t100\t200\t5.5\t+\t.\tID=e1;Parent=t1\n"
+        )
+        gtf = pl.read_ipc(ox.read_gtf(io.BytesIO(gtf)))
+        gff = pl.read_ipc(ox.read_gff(io.BytesIO(gff)))
+
+        assert gtf.schema["source"] == pl.Utf8
+        assert gtf.schema["score"] == pl.Float32
+        assert gtf["score"].to_list() == [None, 5.5]
+        assert gtf.select("source", "score").equals(gff.select("source", "score"))
+
+    def test_feature_types(self):
+        gff = pl.read_ipc(
+            ox.read_gff(str(FIXTURES_PATH / "transcripts.gff"), feature_types=["CDS"])
+        )
+        path = str(FIXTURES_PATH / "transcripts.gtf")
+        gtf = pl.read_ipc(ox.read_gtf(path, feature_types=["CDS"]))
+
+        assert gff["type"].to_list() == ["CDS"]
+        assert gtf["type"].to_list() == ["CDS"]
+        assert len(pl.read_ipc(ox.read_gtf(path, feature_types=["cds"]))) == 0
+        df = pl.read_ipc(
+            ox.read_gtf(path, feature_types=["cds"], feature_types_ignore_case=True)
+        )
+        assert df["start"].to_list() == [150]
+
+        # Skipped features don't contribute attributes.
+        df = pl.read_ipc(ox.read_gtf(path, scan_attributes=True, feature_types=["gene"]))
+        assert [f.name for f in df.schema["attributes"].fields] == ["gene_id"]
+
+    def test_duplicate_attributes(self):
+        gtf = b'chr1\ttest\ttranscript\t100\t200\t.\t+\t.\tgene_id "g1"; tag "basic"; tag "CCDS";\n'
+
+        def read(**kwargs):
+            ipc = ox.read_gtf(io.BytesIO(gtf), scan_attributes=True, **kwargs)
+            return pl.read_ipc(ipc).unnest("attributes")["tag"].to_list()
+
+        assert read() == ["basic"]
+        assert read(duplicate_attributes="last") == ["CCDS"]
+        assert read(duplicate_attributes="collect") == [["basic", "CCDS"]]
+        with pytest.raises(ValueError):
+            read(duplicate_attributes="all")
+
+    def test_dictionary_encode(self):
+        df = pl.read_ipc(ox.read_gff(self.gff_path, dictionary_encode=["seqid", "type"]))
+
+        assert df.schema["seqid"] == pl.Categorical
+        assert df.schema["type"] == pl.Categorical
+        assert df.schema["source"] == pl.Utf8
+        assert df["type"].cast(pl.Utf8).to_list() == ["mRNA"] + ["exon"] * 5
+
+        with pytest.raises(ValueError):
+            ox.read_gff(self.gff_path, dictionary_encode=["attributes"])
+
 
 class TestGtf:
     gtf_path = str(FIXTURES_PATH / "example.gtf")
@@ -203,3 +1111,479 @@ class TestGtf:
 
         # Check number of rows
         assert len(df) == 2
+
+    def test_scan_attributes(self):
+        with open(self.gtf_path, "rb") as f:
+            ipc = ox.read_gtf(f, scan_attributes=True)
+        df = pl.read_ipc(ipc)
+
+        assert len(df) == 2
+        assert len(df.schema["attributes"].fields) == 7
+
+    def test_chroms(self):
+        assert ox.chroms_gtf(self.gtf_path) == ["1"]
+
+
+class TestBed:
+    bed_path = str(FIXTURES_PATH / "sample.bed")
+
+    def test_read_all(self):
+        df = pl.read_ipc(ox.read_bed(self.bed_path))
+
+        assert df.columns == ["chrom", "start", "end", "name", "score", "strand", "rest"]
+        assert len(df) == 5
+
+    def test_filters(self):
+        df = pl.read_ipc(ox.read_bed(self.bed_path, names=["b", "d", "z"]))
+        assert df["name"].to_list() == ["b", "d"]
+
+        df = pl.read_ipc(ox.read_bed(self.bed_path, min_score=250, max_score=750))
+        assert df["name"].to_list() == ["b", "d", "e"]
+
+        assert ox.count_bed(self.bed_path, names=["a", "b"], min_score=100) == 1
+
+    def test_bed_fields(self):
+        df = pl.read_ipc(ox.read_bed(self.bed_path, bed_fields=4))
+        assert df.columns == ["chrom", "start", "end", "name", "rest"]
+        assert df["rest"][0] == "0\t+"
+
+        with pytest.raises(ValueError):
+            ox.read_bed(self.bed_path, bed_fields=13)
+
+    def test_wide_positions(self):
+        data = io.BytesIO(b"chr1\t5000000000\t5000000100\n")
+        with pytest.raises(ValueError, match="wide_positions"):
+            ox.read_bed(data)
+
+        data.seek(0)
+        df = pl.read_ipc(ox.read_bed(data, wide_positions=True))
+        assert df.schema["start"] == pl.UInt64
+        assert df["start"].to_list() == [5_000_000_000]
+
+    def test_missing_as_empty(self):
+        df = pl.read_ipc(ox.read_bed(self.bed_path))
+        assert df["rest"].null_count() == len(df)
+
+        df = pl.read_ipc(ox.read_bed(self.bed_path, missing_as_empty=True))
+        assert df["rest"].null_count() == 0
+        assert df["rest"][0] == ""
+
+    def test_on_error(self):
+        path = str(FIXTURES_PATH / "malformed.bed")
+        with pytest.raises(ValueError):
+            ox.read_bed(path)
+
+        df = pl.read_ipc(ox.read_bed(path, on_error="skip"))
+        assert df["name"].to_list() == ["a", "c"]
+
+        with pytest.warns(UserWarning, match="line 3"):
+            ox.read_bed(path, on_error="skip_and_collect")
+        with pytest.raises(ValueError):
+            ox.read_bed(path, on_error="ignore")
+
+    def test_query(self):
+        path = str(FIXTURES_PATH / "sample.bed.gz")
+        df = pl.read_ipc(ox.read_bed(path, "sq0:15-120"))
+        assert df["name"].to_list() == ["a", "b"]
+
+        df = pl.read_ipc(ox.read_bed(path, ("sq0", 15, 120)))
+        assert df["name"].to_list() == ["a", "b"]
+
+        df = pl.read_ipc(ox.read_bed(path, "sq0:11-149", query_mode="contained"))
+        assert df["name"].to_list() == ["a"]
+
+        assert ox.count_bed(path, "sq0:15-120", query_mode="contained") == 0
+
+        with pytest.raises(ValueError):
+            ox.read_bed(self.bed_path, "sq0")
+
+    def test_require_sorted(self):
+        path = str(FIXTURES_PATH / "unsorted.bed")
+        assert len(pl.read_ipc(ox.read_bed(path))) == 4
+        with pytest.raises(ValueError, match="line 4"):
+            ox.read_bed(path, require_sorted=True)
+
+    def test_subsample(self):
+        path = str(FIXTURES_PATH / "sample.bam")
+        n = len(pl.read_ipc(ox.read_bam(path)))
+        df = pl.read_ipc(ox.read_bam(path, sample_fraction=0.5, sample_seed=1))
+        assert n / 4 < len(df) < n * 3 / 4
+        assert df.equals(pl.read_ipc(ox.read_bam(path, sample_fraction=0.5, sample_seed=1)))
+
+        df = pl.read_ipc(ox.read_bed(self.bed_path, sample_count=2))
+        assert len(df) == 2
+        with pytest.raises(ValueError):
+            ox.read_bed(self.bed_path, sample_fraction=0.5, sample_count=2)
+
+    def test_chroms(self):
+        assert ox.chroms_bed(self.bed_path) == ["sq0", "sq1", "sq2"]
+        assert ox.chroms_bed(str(FIXTURES_PATH / "sample.bed.gz")) == ["sq0", "sq1", "sq2"]
+
+
+class TestBedGraph:
+    def test_read_all(self):
+        df = pl.read_ipc(ox.read_bedgraph(str(FIXTURES_PATH / "sample.bedGraph")))
+
+        assert df.columns == ["chrom", "start", "end", "value"]
+        assert df["start"].to_list() == [0, 10, 5]
+
+    def test_read_file_like(self):
+        with open(FIXTURES_PATH / "sample.bedGraph", "rb") as f:
+            df = pl.read_ipc(ox.read_bedgraph(f))
+
+        assert len(df) == 3
+
+
+class TestWig:
+    def test_read_all(self):
+        df = pl.read_ipc(ox.read_wig(str(FIXTURES_PATH / "sample.wig")))
+
+        assert df.columns == ["chrom", "start", "end", "value"]
+        assert df["start"].to_list() == [0, 10, 20, 4, 19, 100]
+        assert df["end"].to_list() == [5, 15, 25, 7, 22, 101]
+
+
+class TestMaf:
+    maf_path = str(FIXTURES_PATH / "sample.maf")
+
+    def test_read_all(self):
+        df = pl.read_ipc(ox.read_maf(self.maf_path))
+
+        assert df.columns == [
+            "block_id",
+            "score",
+            "src",
+            "start",
+            "size",
+            "strand",
+            "src_size",
+            "text",
+        ]
+        assert df["block_id"].to_list() == [0, 0, 0, 1, 1]
+        assert df["strand"].to_list() == ["+", "+", "-", "+", "-"]
+        assert ox.count_maf(self.maf_path) == 5
+
+    def test_read_quality(self):
+        df = pl.read_ipc(ox.read_maf(self.maf_path, include_quality=True))
+
+        assert df["quality"].to_list() == [
+            None,
+            "99999-9999",
+            None,
+            None,
+            "9999999999",
+        ]
+
+    def test_read_file_like(self):
+        with open(self.maf_path, "rb") as f:
+            df = pl.read_ipc(ox.read_maf(f))
+
+        assert len(df) == 5
+
+
+class TestTwoBit:
+    twobit_path = str(FIXTURES_PATH / "twobit.2bit")
+
+    def test_read_all(self):
+        df = pl.read_ipc(ox.read_2bit(self.twobit_path))
+        fasta = pl.read_ipc(ox.read_fasta(str(FIXTURES_PATH / "twobit.fa")))
+
+        assert df.columns == ["name", "start", "end", "sequence"]
+        assert df["sequence"].to_list() == fasta["sequence"].to_list()
+        assert df["end"].to_list() == [150, 37]
+        assert ox.count_2bit(self.twobit_path) == 2
+
+    def test_read_regions(self):
+        regions = ["chr1:18-45", "chr2:30-33"]
+        df = pl.read_ipc(ox.read_2bit(self.twobit_path, regions=regions))
+        fasta = pl.read_ipc(ox.read_fasta(str(FIXTURES_PATH / "twobit.fa"), regions=regions))
+
+        assert df["sequence"].to_list() == fasta["sequence"].to_list()
+        assert df["start"].to_list() == [18, 30]
+        with pytest.raises(ValueError):
+            ox.read_2bit(self.twobit_path, "chr3")
+
+    def test_read_file_like(self):
+        with open(self.twobit_path, "rb") as f:
+            df = pl.read_ipc(ox.read_2bit(f, "chr1:1-4"))
+
+        assert df["sequence"].to_list() == ["CCCC"]
+
+
+class TestPaf:
+    paf_path = str(FIXTURES_PATH / "sample.paf")
+
+    def test_read_all(self):
+        df = pl.read_ipc(ox.read_paf(self.paf_path))
+
+        assert df.columns == [
+            "query_name",
+            "query_length",
+            "query_start",
+            "query_end",
+            "strand",
+            "target_name",
+            "target_length",
+            "target_start",
+            "target_end",
+            "matches",
+            "alignment_length",
+            "mapq",
+        ]
+        assert df["target_name"].to_list() == ["chr1", "chr2", "chrM"]
+        assert ox.count_paf(self.paf_path) == 3
+
+    def test_read_tags(self):
+        df = pl.read_ipc(ox.read_paf(self.paf_path, tags=["tp", "NM"]))
+
+        assert df["tp"].to_list() == ["P", "S", "P"]
+        assert df["NM"].to_list() == [35, None, 2]
+
+    def test_read_gzip_file_like(self):
+        with open(FIXTURES_PATH / "sample.paf.gz", "rb") as f:
+            df = pl.read_ipc(ox.read_paf(f))
+
+        assert len(df) == 3
+
+
+class TestPeak:
+    def test_read_narrowpeak(self):
+        df = pl.read_ipc(ox.read_narrowpeak(str(FIXTURES_PATH / "sample.narrowPeak")))
+
+        assert df.columns[6:] == ["signal_value", "p_value", "q_value", "peak"]
+        assert df["p_value"].dtype == pl.Float32
+        assert df["peak"].to_list() == [245, 170, 133, 201]
+
+    def test_read_broadpeak(self):
+        df = pl.read_ipc(ox.read_broadpeak(str(FIXTURES_PATH / "sample.broadPeak")))
+
+        assert df.columns[6:] == ["signal_value", "p_value", "q_value"]
+        assert len(df) == 3
+
+    def test_query(self):
+        path = str(FIXTURES_PATH / "sample.narrowPeak.gz")
+        df = pl.read_ipc(ox.read_narrowpeak(path, "chr1:10000-16000"))
+
+        assert df["name"].to_list() == ["sample_peak_1", "sample_peak_2"]
+
+
+class TestTsv:
+    tsv_path = str(FIXTURES_PATH / "peaks.tsv")
+    columns = [
+        ("name", "Utf8"),
+        ("chrom", "Utf8"),
+        ("start", "Int64"),
+        ("end", "Int64"),
+        ("signal", "Float64"),
+    ]
+
+    def read(self, *args, **kwargs):
+        ipc = ox.read_tsv(
+            self.tsv_path,
+            self.columns,
+            *args,
+            chrom_column=1,
+            start_column=2,
+            end_column=3,
+            **kwargs,
+        )
+        return pl.read_ipc(ipc)
+
+    def test_read_all(self):
+        df = self.read()
+
+        assert df.columns == ["name", "chrom", "start", "end", "signal"]
+        assert df["start"].dtype == pl.Int64
+        assert df["signal"].to_list() == [5.5, 2.0, None, 9.25, 1.5]
+
+    def test_query(self):
+        assert self.read("chr1:201-1001")["name"].to_list() == ["peak2", "peak3"]
+        df = self.read("chr1:101-300", query_mode="contained")
+        assert df["name"].to_list() == ["peak1", "peak2"]
+
+    def test_invalid(self):
+        with pytest.raises(ValueError, match="column type"):
+            ox.read_tsv(self.tsv_path, [("name", "Int32")])
+        with pytest.raises(ValueError, match="chrom column"):
+            ox.read_tsv(self.tsv_path, self.columns, "chr1")
+
+
+class TestIpcStream:
+    def test_write_ipc_stream(self):
+        ipc = ox.read_bam(str(FIXTURES_PATH / "sample.bam"))
+        sink = io.BytesIO()
+        ox.write_ipc_stream(ipc, sink)
+        df = pl.read_ipc_stream(sink.getvalue())
+
+        assert df.equals(pl.read_ipc(ipc))
+
+    def test_write_ipc_stream_requires_write(self):
+        ipc = ox.read_bam(str(FIXTURES_PATH / "sample.bam"))
+        with pytest.raises(TypeError):
+            ox.write_ipc_stream(ipc, object())
+
+
+class TestRenameColumns:
+    bed_path = str(FIXTURES_PATH / "sample.bed")
+
+    def test_preset(self):
+        ipc = ox.read_bed(self.bed_path)
+        df = pl.read_ipc(ox.rename_columns(ipc, column_naming="sam_native"))
+
+        assert df.columns[:3] == ["rname", "pos", "end"]
+        assert df.rename({"rname": "chrom", "pos": "start"}).equals(pl.read_ipc(ipc))
+        with pytest.raises(ValueError, match="Invalid column naming"):
+            ox.rename_columns(ipc, column_naming="sam")
+
+    def test_rename(self):
+        ipc = ox.read_bed(self.bed_path)
+        df = pl.read_ipc(ox.rename_columns(ipc, column_naming="sam_native", rename={"chrom": "contig"}))
+
+        assert df.columns[:3] == ["contig", "pos", "end"]
+        with pytest.raises(ValueError, match="two columns would be named end"):
+            ox.rename_columns(ipc, rename={"chrom": "end"})
+
+
+class TestCoordinateSystem:
+    @pytest.mark.parametrize("system, start", [("1-based", 100), ("0-based", 99)])
+    def test_bed_and_gff_agree(self, tmp_path, system, start):
+        bed = tmp_path / "feature.bed"
+        bed.write_text("chr1\t99\t200\n")
+        gff = tmp_path / "feature.gff"
+        gff.write_text("chr1\t.\tgene\t100\t200\t.\t+\t.\tID=g1\n")
+
+        bed_df = pl.read_ipc(ox.read_bed(str(bed), coordinate_system=system))
+        gff_df = pl.read_ipc(ox.read_gff(str(gff), coordinate_system=system))
+        assert bed_df["start"].to_list() == gff_df["start"].to_list() == [start]
+        assert bed_df["end"].to_list() == gff_df["end"].to_list() == [200]
+
+    def test_invalid(self):
+        with pytest.raises(ValueError, match="coordinate system"):
+            ox.read_bed(str(FIXTURES_PATH / "sample.bed"), coordinate_system="2-based")
+
+    def test_unsigned_positions(self):
+        df = pl.read_ipc(ox.read_vcf(str(FIXTURES_PATH / "sample.vcf.gz")))
+        assert df["pos"].dtype == pl.Int32
+        df = pl.read_ipc(
+            ox.read_vcf(str(FIXTURES_PATH / "sample.vcf.gz"), unsigned_positions=True)
+        )
+        assert df["pos"].dtype == df["end"].dtype == pl.UInt32
+
+
+class TestFields:
+    def _scan_fields(self, ipc):
+        pa = pytest.importorskip("pyarrow")
+        schema = pa.ipc.open_file(io.BytesIO(ipc)).schema
+        return [(field.name, field.type) for field in schema]
+
+    def test_fields_vcf(self):
+        path = str(FIXTURES_PATH / "sample.vcf.gz")
+        ipc = ox.read_vcf(path, info_fields=["DP", "AF"])
+
+        assert ox.fields_vcf(path, info_fields=["DP", "AF"]) == self._scan_fields(ipc)
+
+    def test_fields_bam_sample(self):
+        pa = pytest.importorskip("pyarrow")
+        path = str(FIXTURES_PATH / "sample.bam")
+        ipc = ox.read_bam(path, tags=["NM"])
+
+        assert ox.fields_bam(path, tags=["NM"], sample_size=100) == self._scan_fields(ipc)
+        assert dict(ox.fields_bam(path, tags=["NM"]))["NM"] == pa.null()
+
+    def test_fields_gff(self):
+        path = str(FIXTURES_PATH / "example.gff")
+        ipc = ox.read_gff(path, parent_ids=True)
+
+        assert ox.fields_gff(path, parent_ids=True) == self._scan_fields(ipc)
+
+    def test_fields_bed(self):
+        path = str(FIXTURES_PATH / "sample.bed")
+        names = [name for name, _ in ox.fields_bed(path, bed_fields=4)]
+
+        assert names == ["chrom", "start", "end", "name", "rest"]
+
+
+    @pytest.mark.parametrize(
+        "read, name, fields",
+        [
+            (ox.read_vcf, "sample.vcf.gz", ["pos", "chrom"]),
+            (ox.read_bed, "sample.bed", ["end", "chrom"]),
+            (ox.read_fastq, "sample.fastq", ["sequence", "name"]),
+            (ox.read_gff, "example.gff", ["type", "seqid"]),
+            (ox.read_gtf, "example.gtf", ["type", "seqid"]),
+        ],
+    )
+    def test_select_fields(self, read, name, fields):
+        path = str(FIXTURES_PATH / name)
+        df = pl.read_ipc(read(path, fields=fields))
+        assert df.columns == fields
+
+        with pytest.raises(ValueError, match="Invalid field: nope"):
+            read(path, fields=["nope"])
+
+class TestIterBatches:
+    def test_iter_batches(self):
+        pa = pytest.importorskip("pyarrow")
+        ipc = ox.read_fasta(str(FIXTURES_PATH / "sample.fa"), batch_size=2)
+        batches = ox.iter_batches(ipc)
+
+        assert batches.schema.names == ["name", "sequence"]
+        rows = 0
+        for batch in batches:
+            assert isinstance(batch, pa.RecordBatch)
+            rows += batch.num_rows
+            assert rows in (2, 3)
+        assert rows == 3
+
+    def test_to_arrow_table(self):
+        pytest.importorskip("pyarrow")
+        ipc = ox.read_fasta(str(FIXTURES_PATH / "sample.fa"), batch_size=2)
+        table = ox.iter_batches(ipc).to_arrow_table()
+
+        assert table.schema.names == ["name", "sequence"]
+        assert table.num_rows == 3
+
+    def test_to_polars(self):
+        pytest.importorskip("pyarrow")
+        ipc = ox.read_fasta(str(FIXTURES_PATH / "sample.fa"), batch_size=2)
+        batches = ox.iter_batches(ipc)
+        df = batches.to_polars()
+
+        assert df.equals(pl.read_ipc(ipc))
+        # The iterator is single-pass.
+        assert batches.to_polars().is_empty()
+
+    def test_arrow_c_stream(self):
+        pa = pytest.importorskip("pyarrow", minversion="15")
+        ipc = ox.read_fasta(str(FIXTURES_PATH / "sample.fa"), batch_size=2)
+        reader = pa.RecordBatchReader.from_stream(ox.iter_batches(ipc))
+
+        table = reader.read_all()
+        assert table.schema.names == ["name", "sequence"]
+        assert table.num_rows == 3
+
+    def test_iter_batches_invalid(self):
+        with pytest.raises(ValueError):
+            ox.iter_batches(b"not arrow")
+
+
+class TestRegions:
+    def test_parse_regions(self):
+        regions = ["chr1", "chr1:1,000-2,000", "chr1:500-", ("chr2", 10, 20), ("chr2", None, None)]
+        assert ox.parse_regions(regions) == [
+            "chr1",
+            "chr1:1000-2000",
+            "chr1:500",
+            "chr2:10-20",
+            "chr2",
+        ]
+
+    def test_parse_regions_bed(self, tmp_path):
+        path = tmp_path / "regions.bed"
+        path.write_text("chr1\t0\t100\nchr2\t9\t10\tname\n")
+        assert ox.parse_regions(str(path)) == ["chr1:1-100", "chr2:10-10"]
+
+    @pytest.mark.parametrize("region", ["chr1:x-y", "chr1:10-5", ":1-10", ("chr1", "a", 5)])
+    def test_malformed(self, region):
+        with pytest.raises(ValueError):
+            ox.parse_regions([region])
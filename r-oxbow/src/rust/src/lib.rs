@@ -1,9 +1,9 @@
 use extendr_api::prelude::*;
 use oxbow::bam::BamReader;
+use oxbow::bcf::BcfReader;
+use oxbow::cram::CramReader;
 use oxbow::fasta::FastaReader;
 use oxbow::fastq::FastqReader;
-// use oxbow::cram::CramReader;
-use oxbow::bcf::BcfReader;
 use oxbow::vcf::VcfReader;
 use oxbow::vpos;
 
@@ -41,6 +41,14 @@ fn read_bam_vpos(path: &str, cpos_lo: u64, upos_lo: u16, cpos_hi: u64, upos_hi:
         .unwrap()
 }
 
+/// Return Arrow IPC format from a CRAM file.
+/// @export
+#[extendr]
+fn read_cram(path: &str, reference: Option<&str>, region: Option<&str>) -> Vec<u8> {
+    let mut reader = CramReader::new_from_path(path, reference).unwrap();
+    reader.records_to_ipc(region).unwrap()
+}
+
 /// Return Arrow IPC format from a VCF file.
 /// @export
 #[extendr]
@@ -97,6 +105,7 @@ extendr_module! {
     fn read_fastq;
     fn read_bam;
     fn read_bam_vpos;
+    fn read_cram;
     fn read_vcf;
     fn read_vcf_vpos;
     fn read_bcf;